@@ -1,12 +1,23 @@
 /// Build script for eBPF programs
 ///
-/// This script generates kernel type definitions from vmlinux BTF data
+/// This script generates kernel type definitions from vmlinux BTF data and
+/// sets `bpf_target_arch` (as aya-ebpf does) for syscall numbering and
+/// register layout that differ per architecture
 use std::env;
 use std::path::PathBuf;
 
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 
+    println!("cargo:rerun-if-env-changed=CARGO_CFG_BPF_TARGET_ARCH");
+    let arch = env::var("CARGO_CFG_BPF_TARGET_ARCH").unwrap_or_else(|_| {
+        let host = env::var("HOST").unwrap();
+        host.split_once('-')
+            .map_or(host.clone(), |x| x.0.to_string())
+    });
+    println!("cargo:rustc-cfg=bpf_target_arch=\"{}\"", arch);
+    println!("cargo::rustc-check-cfg=cfg(bpf_target_arch, values(\"x86_64\", \"aarch64\"))");
+
     // TODO: Generate vmlinux.rs from BTF
     // This requires reading /sys/kernel/btf/vmlinux and generating Rust bindings
     // For now, we'll use a minimal set of manually defined types
@@ -0,0 +1,388 @@
+#![no_std]
+#![no_main]
+
+//! Block I/O tracer eBPF program
+//!
+//! Times block device requests from `block:block_rq_issue` to
+//! `block:block_rq_complete`, recording the process that submitted them and
+//! how many requests were in flight on the device at issue.
+//!
+//! Requests held by an I/O scheduler or a plug are often dispatched from a
+//! kworker or another task, so the submitting process is taken earlier, at
+//! `block:block_io_start` (Linux 6.5+) or `block:block_rq_insert`.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aya_ebpf::{
+    helpers::{bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns},
+    macros::{map, tracepoint},
+    maps::{Array, HashMap, LruHashMap, PerfEventArray},
+    programs::TracePointContext,
+};
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+
+mod common;
+use common::{MAX_TRACKED_REQUESTS, TASK_COMM_LEN};
+
+#[no_mangle]
+#[link_section = "license"]
+pub static LICENSE: [u8; 4] = *b"GPL\0";
+
+/// Length of the tracepoints' `rwbs` field
+const RWBS_LEN: usize = 8;
+
+#[map]
+static BLOCK_EVENTS: PerfEventArray<BlockIoEventBpf> = PerfEventArray::new(0);
+
+/// Requests issued but not completed yet, by device and sector
+#[map]
+static BLOCK_REQUESTS: HashMap<RequestKey, RequestStart> =
+    HashMap::with_max_entries(MAX_TRACKED_REQUESTS, 0);
+
+/// Submitters of requests not issued yet, by device and sector; requests
+/// merged in front of another change sector and are never looked up
+#[map]
+static BLOCK_SUBMITTERS: LruHashMap<RequestKey, Submitter> =
+    LruHashMap::with_max_entries(MAX_TRACKED_REQUESTS, 0);
+
+/// Requests in flight per device, including those of untraced processes
+#[map]
+static BLOCK_INFLIGHT: HashMap<u32, u64> = HashMap::with_max_entries(1024, 0);
+
+/// PID_FILTER[0] = target_pid (0 = trace all)
+/// PID_FILTER[1] = pidns device number
+/// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// Offsets of the block tracepoint fields, read by the agent from the
+/// tracepoint format (0 = the layout below)
+/// BLOCK_CONFIG[0] = block_rq_issue dev offset
+/// BLOCK_CONFIG[1] = block_rq_issue sector offset
+/// BLOCK_CONFIG[2] = block_rq_issue bytes offset
+/// BLOCK_CONFIG[3] = block_rq_issue rwbs offset
+/// BLOCK_CONFIG[4] = block_rq_complete dev offset
+/// BLOCK_CONFIG[5] = block_rq_complete sector offset
+/// BLOCK_CONFIG[6] = block_rq_complete error offset
+/// BLOCK_CONFIG[7] = submission tracepoint dev offset
+/// BLOCK_CONFIG[8] = submission tracepoint sector offset
+#[map]
+static BLOCK_CONFIG: Array<u64> = Array::with_max_entries(9, 0);
+
+/// Common fields (8), then `dev`, `sector`, `nr_sector`, `bytes` (or
+/// `error`) and `rwbs`, as before `ioprio` was added in Linux 6.9
+const DEFAULT_DEV_OFFSET: usize = 8;
+const DEFAULT_SECTOR_OFFSET: usize = 16;
+const DEFAULT_BYTES_OFFSET: usize = 28;
+const DEFAULT_ERROR_OFFSET: usize = 28;
+const DEFAULT_RWBS_OFFSET: usize = 32;
+
+#[repr(C)]
+pub struct BlockIoEventBpf {
+    /// Issue time
+    pub timestamp: u64,
+    pub latency_ns: u64,
+    pub sector: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub dev: u32,
+    pub bytes: u32,
+    /// Requests in flight on the device at issue, this one included
+    pub queue_depth: u32,
+    pub error: i32,
+    pub rwbs: [u8; RWBS_LEN],
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct RequestKey {
+    pub sector: u64,
+    pub dev: u32,
+    pub _pad: u32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct RequestStart {
+    pub timestamp: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub bytes: u32,
+    pub queue_depth: u32,
+    /// 0 when the issuing process is filtered out
+    pub traced: u32,
+    pub _pad: u32,
+    pub rwbs: [u8; RWBS_LEN],
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+/// Process that submitted a request
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Submitter {
+    pub pid: u32,
+    pub tid: u32,
+    /// 0 when the submitting process is filtered out
+    pub traced: u32,
+    pub _pad: u32,
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+impl Submitter {
+    /// The current task, with the PID filter decision
+    #[inline(always)]
+    fn current() -> Self {
+        let traced = should_trace();
+        let pid_tgid = bpf_get_current_pid_tgid();
+        Submitter {
+            pid: (pid_tgid >> 32) as u32,
+            tid: pid_tgid as u32,
+            traced: traced as u32,
+            _pad: 0,
+            comm: if traced {
+                bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN])
+            } else {
+                [0u8; TASK_COMM_LEN]
+            },
+        }
+    }
+}
+
+#[inline(always)]
+fn arg_offset(index: u32, default: usize) -> usize {
+    match BLOCK_CONFIG.get(index) {
+        Some(&v) if v != 0 => v as usize,
+        _ => default,
+    }
+}
+
+/// Check if the current process matches the PID filter.
+/// Returns true if the event should be processed.
+#[inline(always)]
+fn should_trace() -> bool {
+    let target = match PID_FILTER.get(0) {
+        Some(&v) => v as u32,
+        None => return true, // no filter configured
+    };
+    if target == 0 {
+        return true; // 0 = trace all
+    }
+
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
+    let ns_dev = match PID_FILTER.get(1) {
+        Some(&v) => v,
+        None => return false,
+    };
+    let ns_ino = match PID_FILTER.get(2) {
+        Some(&v) => v,
+        None => return false,
+    };
+
+    let mut nsinfo = aya_ebpf_bindings::bindings::bpf_pidns_info { pid: 0, tgid: 0 };
+    let ret = unsafe {
+        bpf_get_ns_current_pid_tgid(
+            ns_dev,
+            ns_ino,
+            &mut nsinfo as *mut _,
+            core::mem::size_of::<aya_ebpf_bindings::bindings::bpf_pidns_info>() as u32,
+        )
+    };
+    if ret != 0 {
+        return false;
+    }
+
+    nsinfo.tgid == target
+}
+
+/// Add `delta` to the in-flight count of `dev`, returning the new count
+#[inline(always)]
+fn update_inflight(dev: u32, delta: u64) -> u64 {
+    if BLOCK_INFLIGHT.get_ptr_mut(&dev).is_none() {
+        let _ = BLOCK_INFLIGHT.insert(&dev, &0, 0);
+    }
+    let Some(count) = BLOCK_INFLIGHT.get_ptr_mut(&dev) else {
+        return 0;
+    };
+    // Requests on one device are issued and completed on many CPUs
+    let count = unsafe { &*(count as *const AtomicU64) };
+    count
+        .fetch_add(delta, Ordering::Relaxed)
+        .wrapping_add(delta)
+}
+
+/// Attached to `block:block_io_start`, or `block:block_rq_insert` on kernels
+/// without it; both run in the context of the submitting task
+#[tracepoint(name = "block_rq_submit", category = "block")]
+pub fn block_rq_submit(ctx: TracePointContext) -> i64 {
+    try_block_rq_submit(&ctx).unwrap_or_default()
+}
+
+fn try_block_rq_submit(ctx: &TracePointContext) -> Result<i64, i64> {
+    let dev: u32 = unsafe {
+        ctx.read_at(arg_offset(7, DEFAULT_DEV_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let sector: u64 = unsafe {
+        ctx.read_at(arg_offset(8, DEFAULT_SECTOR_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let key = RequestKey {
+        sector,
+        dev,
+        _pad: 0,
+    };
+    let _ = BLOCK_SUBMITTERS.insert(&key, &Submitter::current(), 0);
+    Ok(0)
+}
+
+#[tracepoint(name = "block_rq_issue", category = "block")]
+pub fn block_rq_issue(ctx: TracePointContext) -> i64 {
+    try_block_rq_issue(&ctx).unwrap_or_default()
+}
+
+fn try_block_rq_issue(ctx: &TracePointContext) -> Result<i64, i64> {
+    let dev: u32 = unsafe {
+        ctx.read_at(arg_offset(0, DEFAULT_DEV_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let sector: u64 = unsafe {
+        ctx.read_at(arg_offset(1, DEFAULT_SECTOR_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let key = RequestKey {
+        sector,
+        dev,
+        _pad: 0,
+    };
+
+    // A requeued request is issued again: count it in flight only once and
+    // keep its submitter
+    let previous = unsafe { BLOCK_REQUESTS.get(&key) }.copied();
+    let reissued = previous.is_some();
+    let queue_depth = if reissued {
+        update_inflight(dev, 0)
+    } else {
+        update_inflight(dev, 1)
+    };
+
+    // Dispatched directly without a submission event (no scheduler, no
+    // plug), a request is issued by its submitter
+    let submitter = match previous {
+        Some(previous) => Submitter {
+            pid: previous.pid,
+            tid: previous.tid,
+            traced: previous.traced,
+            _pad: 0,
+            comm: previous.comm,
+        },
+        None => match unsafe { BLOCK_SUBMITTERS.get(&key) } {
+            Some(submitter) => {
+                let submitter = *submitter;
+                let _ = BLOCK_SUBMITTERS.remove(&key);
+                submitter
+            }
+            None => Submitter::current(),
+        },
+    };
+
+    let mut start = RequestStart {
+        timestamp: 0,
+        pid: submitter.pid,
+        tid: submitter.tid,
+        bytes: 0,
+        queue_depth: queue_depth as u32,
+        traced: submitter.traced,
+        _pad: 0,
+        rwbs: [0u8; RWBS_LEN],
+        comm: submitter.comm,
+    };
+    if submitter.traced != 0 {
+        start.bytes = unsafe {
+            ctx.read_at(arg_offset(2, DEFAULT_BYTES_OFFSET))
+                .unwrap_or(0)
+        };
+        start.rwbs = unsafe {
+            ctx.read_at(arg_offset(3, DEFAULT_RWBS_OFFSET))
+                .unwrap_or([0u8; RWBS_LEN])
+        };
+    }
+    start.timestamp = unsafe { bpf_ktime_get_ns() };
+
+    if BLOCK_REQUESTS.insert(&key, &start, 0).is_err() && !reissued {
+        // Untracked: it will never be completed here
+        update_inflight(dev, u64::MAX);
+    }
+
+    Ok(0)
+}
+
+#[tracepoint(name = "block_rq_complete", category = "block")]
+pub fn block_rq_complete(ctx: TracePointContext) -> i64 {
+    try_block_rq_complete(&ctx).unwrap_or_default()
+}
+
+fn try_block_rq_complete(ctx: &TracePointContext) -> Result<i64, i64> {
+    let dev: u32 = unsafe {
+        ctx.read_at(arg_offset(4, DEFAULT_DEV_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let sector: u64 = unsafe {
+        ctx.read_at(arg_offset(5, DEFAULT_SECTOR_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let key = RequestKey {
+        sector,
+        dev,
+        _pad: 0,
+    };
+
+    // Requests issued before tracing started are unknown
+    let start = match unsafe { BLOCK_REQUESTS.get(&key) } {
+        Some(s) => *s,
+        None => return Ok(0),
+    };
+    let _ = BLOCK_REQUESTS.remove(&key);
+    // Adding u64::MAX wraps around to a decrement
+    update_inflight(dev, u64::MAX);
+
+    if start.traced == 0 {
+        return Ok(0);
+    }
+
+    let error: i32 = unsafe {
+        ctx.read_at(arg_offset(6, DEFAULT_ERROR_OFFSET))
+            .unwrap_or(0)
+    };
+    let now = unsafe { bpf_ktime_get_ns() };
+
+    let event = BlockIoEventBpf {
+        timestamp: start.timestamp,
+        latency_ns: now.saturating_sub(start.timestamp),
+        sector,
+        pid: start.pid,
+        tid: start.tid,
+        dev,
+        bytes: start.bytes,
+        queue_depth: start.queue_depth,
+        error,
+        rwbs: start.rwbs,
+        comm: start.comm,
+    };
+    BLOCK_EVENTS.output(ctx, &event, 0);
+
+    Ok(0)
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
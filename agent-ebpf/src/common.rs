@@ -7,6 +7,18 @@
 /// Maximum process name length
 pub const TASK_COMM_LEN: usize = 16;
 
+/// Maximum exec path length captured by the process tracker
+pub const MAX_FILENAME_LEN: usize = 256;
+
+/// Maximum path argument length captured by the syscall tracer
+pub const MAX_SYSCALL_PATH_LEN: usize = 128;
+
+/// Syscall ids are below this (sizes the syscall tracer's per-id maps)
+pub const MAX_SYSCALL_ID: u32 = 512;
+
+/// Log2 latency buckets per syscall (matches SyscallStats::latency_histogram)
+pub const SYSCALL_LATENCY_BUCKETS: usize = 30;
+
 /// BPF helper flags
 pub const BPF_F_USER_STACK: u64 = 1 << 8;
 pub const BPF_F_FAST_STACK_CMP: u64 = 1 << 9;
@@ -19,7 +31,16 @@ pub const BPF_F_REUSE_STACKID: u64 = 1 << 10;
 pub const FUTEX_WAIT: u32 = 0;
 pub const FUTEX_LOCK_PI: u32 = 6;
 pub const FUTEX_WAIT_BITSET: u32 = 9;
+pub const FUTEX_WAKE: u32 = 1;
+pub const FUTEX_UNLOCK_PI: u32 = 7;
+pub const FUTEX_WAKE_BITSET: u32 = 10;
 pub const FUTEX_CMD_MASK: u32 = !128; // ~(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME)
 
 /// Map sizes
 pub const MAX_TRACKED_TIDS: u32 = 16384;
+
+/// Block requests in flight tracked by the block I/O tracer
+pub const MAX_TRACKED_REQUESTS: u32 = 16384;
+
+/// TCP connections followed by the TCP tracer
+pub const MAX_TRACKED_CONNECTIONS: u32 = 65536;
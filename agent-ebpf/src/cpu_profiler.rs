@@ -3,7 +3,8 @@
 
 //! CPU profiler eBPF program
 //!
-//! Captures stack traces and sends sample events to userspace via perf buffer.
+//! Captures stack traces and sends sample events to userspace via perf buffer,
+//! with the labels the sampled thread set through `aperture-labels`.
 
 use aya_ebpf::{
     helpers::{bpf_get_current_comm, bpf_get_smp_processor_id, bpf_ktime_get_ns},
@@ -13,6 +14,10 @@ use aya_ebpf::{
     EbpfContext,
 };
 
+mod common;
+mod labels;
+use labels::{current_labels, LABELS_SIZE};
+
 #[no_mangle]
 #[link_section = "license"]
 pub static LICENSE: [u8; 4] = *b"GPL\0";
@@ -37,6 +42,8 @@ pub struct SampleEvent {
     pub user_stack_id: i32,
     pub kernel_stack_id: i32,
     pub comm: [u8; 16],
+    /// Encoded labels of the sampled thread (zero without labels)
+    pub labels: [u8; LABELS_SIZE],
 }
 
 #[perf_event]
@@ -77,6 +84,7 @@ fn try_cpu_profiler(ctx: &PerfEventContext) -> Result<i64, i64> {
         user_stack_id: user_stack_id as i32,
         kernel_stack_id: kernel_stack_id as i32,
         comm,
+        labels: current_labels(),
     };
 
     EVENTS.output(ctx, &event, 0);
@@ -0,0 +1,74 @@
+//! Profiling labels set by applications through the `aperture-labels`
+//! library, kept per thread for the programs that record events
+//!
+//! The library calls `aperture_labels_set(buf, len)` on every change, with
+//! `buf` its thread's LABELS_SIZE-byte encoded labels; `labels_set` is
+//! attached there and copies them into THREAD_LABELS. Labels set before the
+//! agent attached are written to the map from userspace.
+
+use aya_ebpf::{
+    helpers::{bpf_get_current_pid_tgid, bpf_probe_read_user_buf},
+    macros::{map, uprobe},
+    maps::LruHashMap,
+    programs::ProbeContext,
+};
+
+use crate::common::MAX_TRACKED_TIDS;
+
+/// Encoded labels size (must match aperture_labels::LABELS_SIZE)
+pub const LABELS_SIZE: usize = 128;
+
+/// Labels of a thread, with the process that set them so a reused tid
+/// doesn't inherit them
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ThreadLabels {
+    pub pid: u32,
+    pub _pad: u32,
+    pub labels: [u8; LABELS_SIZE],
+}
+
+/// Current labels by tid
+#[map]
+static THREAD_LABELS: LruHashMap<u32, ThreadLabels> =
+    LruHashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// Entry of `aperture_labels_set(buf, len)`; a length of 0 clears the
+/// thread's labels
+#[uprobe]
+pub fn labels_set(ctx: ProbeContext) -> u32 {
+    let (Some(buf), Some(len)) = (ctx.arg::<u64>(0), ctx.arg::<u64>(1)) else {
+        return 0;
+    };
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let tid = pid_tgid as u32;
+    if len == 0 {
+        let _ = THREAD_LABELS.remove(&tid);
+        return 0;
+    }
+    let entry = ThreadLabels {
+        pid: (pid_tgid >> 32) as u32,
+        _pad: 0,
+        labels: [0; LABELS_SIZE],
+    };
+    if THREAD_LABELS.insert(&tid, &entry, 0).is_err() {
+        return 0;
+    }
+    if let Some(stored) = THREAD_LABELS.get_ptr_mut(&tid) {
+        let stored = unsafe { &mut *stored };
+        if unsafe { bpf_probe_read_user_buf(buf as *const u8, &mut stored.labels) }.is_err() {
+            let _ = THREAD_LABELS.remove(&tid);
+        }
+    }
+    0
+}
+
+/// Labels of the current thread, all zero when it has none
+#[inline(always)]
+pub fn current_labels() -> [u8; LABELS_SIZE] {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    match unsafe { THREAD_LABELS.get(&(pid_tgid as u32)) } {
+        Some(entry) if entry.pid == (pid_tgid >> 32) as u32 => entry.labels,
+        _ => [0; LABELS_SIZE],
+    }
+}
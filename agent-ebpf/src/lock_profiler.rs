@@ -3,29 +3,98 @@
 
 use aya_ebpf::{
     helpers::{bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns},
-    macros::{map, tracepoint},
-    maps::{Array, HashMap, PerfEventArray, StackTrace},
-    programs::TracePointContext,
+    macros::{map, tracepoint, uprobe, uretprobe},
+    maps::{Array, HashMap, LruHashMap, PerfEventArray, StackTrace},
+    programs::{ProbeContext, RetProbeContext, TracePointContext},
+    EbpfContext,
 };
-use aya_ebpf_bindings::helpers::bpf_get_ns_current_pid_tgid;
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
 
 mod common;
-use common::{FUTEX_CMD_MASK, FUTEX_LOCK_PI, FUTEX_WAIT, FUTEX_WAIT_BITSET};
+mod labels;
+use common::{
+    BPF_F_USER_STACK, FUTEX_CMD_MASK, FUTEX_LOCK_PI, FUTEX_UNLOCK_PI, FUTEX_WAIT,
+    FUTEX_WAIT_BITSET, FUTEX_WAKE, FUTEX_WAKE_BITSET, MAX_TRACKED_TIDS,
+};
+use labels::{current_labels, LABELS_SIZE};
 
 #[map]
 static LOCK_EVENTS: PerfEventArray<LockEventBpf> = PerfEventArray::new(0);
 
+/// Kernel lock contention (lock:contention_begin/end)
+#[map]
+static KERNEL_LOCK_EVENTS: PerfEventArray<KernelLockEventBpf> = PerfEventArray::new(0);
+
 #[map]
 static LOCK_STACKS: StackTrace = StackTrace::with_max_entries(1024, 0);
 
 #[map]
 static FUTEX_ENTRIES: HashMap<u32, FutexEntry> = HashMap::with_max_entries(1024, 0);
 
+/// Wakes in progress, by waking tid
+#[map]
+static WAKE_ENTRIES: HashMap<u32, WakeEntry> = HashMap::with_max_entries(1024, 0);
+
+/// Lock a thread took after a contended wait, by tid (its hold is exact)
+#[map]
+static ACQUIRED: LruHashMap<u32, FutexEntry> = LruHashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// Start of the oldest wait on a futex since its last wake
+#[map]
+static WAIT_SINCE: LruHashMap<FutexKey, u64> = LruHashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// Most recent wake of a futex, for attributing waits to their waker
+#[map]
+static LAST_WAKE: LruHashMap<FutexKey, WakeEntry> =
+    LruHashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// User-space lock or unlock call in progress, by tid. Futex operations made
+/// inside one are left to the uprobes so contention isn't counted twice.
+#[map]
+static USER_LOCK_CALLS: HashMap<u32, FutexEntry> = HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// When each thread took each user-space lock, for hold times
+#[map]
+static USER_LOCKS_HELD: LruHashMap<HeldKey, u64> =
+    LruHashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// Kernel lock contention in progress, by tid
+#[map]
+static KERNEL_LOCK_WAITS: HashMap<u32, KernelLockEntry> =
+    HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
 /// PID_FILTER[0] = target_pid (0 = profile all)
 /// PID_FILTER[1] = pidns device number
 /// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// Offsets of the futex and lock tracepoint fields, read by the agent from
+/// the tracepoint format (0 = the usual 64-bit layout below)
+/// LOCK_CONFIG[0] = sys_enter_futex uaddr offset
+/// LOCK_CONFIG[1] = sys_enter_futex op offset
+/// LOCK_CONFIG[2] = sys_exit_futex ret offset
+/// LOCK_CONFIG[3] = shortest user-space acquire or hold reported (ns)
+/// LOCK_CONFIG[4] = contention_begin lock_addr offset
+/// LOCK_CONFIG[5] = contention_begin flags offset
+/// LOCK_CONFIG[6] = contention_end lock_addr offset
 #[map]
-static PID_FILTER: Array<u64> = Array::with_max_entries(3, 0);
+static LOCK_CONFIG: Array<u64> = Array::with_max_entries(7, 0);
+
+/// Common fields (8) and `__syscall_nr` padded to 8, then one 8-byte slot
+/// per argument (or the return value)
+const DEFAULT_UADDR_OFFSET: usize = 16;
+const DEFAULT_OP_OFFSET: usize = 24;
+const DEFAULT_RET_OFFSET: usize = 16;
+
+/// Common fields (8), then `lock_addr` and `flags` (or `ret`)
+const DEFAULT_LOCK_ADDR_OFFSET: usize = 8;
+const DEFAULT_FLAGS_OFFSET: usize = 16;
+
+/// LockEventBpf::kind
+const LOCK_EVENT_WAIT: u32 = 0;
+const LOCK_EVENT_RELEASE: u32 = 1;
 
 #[repr(C)]
 pub struct LockEventBpf {
@@ -37,6 +106,40 @@ pub struct LockEventBpf {
     pub user_stack_id: i64,
     pub kernel_stack_id: i64,
     pub comm: [u8; 16],
+    /// Wait: how long the waker had held the lock. Release: how long the
+    /// releasing thread held it (exact after a contended acquire, else at
+    /// least as long as the oldest waiter waited)
+    pub hold_time_ns: u64,
+    /// Thread whose release ended the wait, 0 if not seen (wait events)
+    pub waker_tid: u32,
+    /// LOCK_EVENT_WAIT or LOCK_EVENT_RELEASE
+    pub kind: u32,
+    /// Encoded labels of the current thread (zero without labels)
+    pub labels: [u8; LABELS_SIZE],
+}
+
+#[repr(C)]
+pub struct KernelLockEventBpf {
+    pub timestamp: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub lock_addr: u64,
+    pub wait_time_ns: u64,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub comm: [u8; 16],
+    /// LCB_F_* flags of every phase of the contention
+    pub flags: u32,
+    pub _pad: u32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct KernelLockEntry {
+    pub timestamp: u64,
+    pub lock_addr: u64,
+    pub flags: u32,
+    pub _pad: u32,
 }
 
 #[derive(Clone, Copy)]
@@ -46,6 +149,54 @@ pub struct FutexEntry {
     pub uaddr: u64,
 }
 
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct HeldKey {
+    pub lock_addr: u64,
+    pub tid: u32,
+    pub _pad: u32,
+}
+
+/// A futex word as seen by one process. The same uaddr in another process
+/// is a different futex; process-shared futexes mapped at different
+/// addresses are not matched up.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct FutexKey {
+    pub uaddr: u64,
+    pub tgid: u32,
+    pub _pad: u32,
+}
+
+impl FutexKey {
+    #[inline(always)]
+    fn new(tgid: u32, uaddr: u64) -> Self {
+        Self {
+            uaddr,
+            tgid,
+            _pad: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct WakeEntry {
+    pub timestamp: u64,
+    pub uaddr: u64,
+    pub hold_time_ns: u64,
+    pub tid: u32,
+    pub _pad: u32,
+}
+
+#[inline(always)]
+fn arg_offset(index: u32, default: usize) -> usize {
+    match LOCK_CONFIG.get(index) {
+        Some(&v) if v != 0 => v as usize,
+        _ => default,
+    }
+}
+
 /// Check if the current process matches the PID filter.
 /// Returns true if the event should be processed.
 #[inline(always)]
@@ -58,6 +209,13 @@ fn should_trace() -> bool {
         return true; // 0 = trace all
     }
 
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
     let ns_dev = match PID_FILTER.get(1) {
         Some(&v) => v,
         None => return false,
@@ -96,28 +254,75 @@ fn try_sys_enter_futex(ctx: &TracePointContext) -> Result<i64, i64> {
 
     let pid_tgid = bpf_get_current_pid_tgid();
     let tid = pid_tgid as u32;
+    let pid = (pid_tgid >> 32) as u32;
+    if unsafe { USER_LOCK_CALLS.get(&tid) }.is_some() {
+        return Ok(0);
+    }
 
     // Read arguments
     // sys_enter_futex(u32 *uaddr, int op, u32 val, struct timespec *utime, u32 *uaddr2, u32 val3)
-    // Offset 16: uaddr, Offset 24: op
-    let uaddr: u64 = unsafe { ctx.read_at(16).map_err(|_| 1i64)? };
-    let op: u32 = unsafe { ctx.read_at(24).map_err(|_| 1i64)? };
+    let uaddr: u64 = unsafe {
+        ctx.read_at(arg_offset(0, DEFAULT_UADDR_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let op: u32 = unsafe {
+        ctx.read_at(arg_offset(1, DEFAULT_OP_OFFSET))
+            .map_err(|_| 1i64)?
+    };
 
-    // Check if it's a wait operation
     let cmd = op & FUTEX_CMD_MASK;
+    let timestamp = unsafe { bpf_ktime_get_ns() };
+    if cmd == FUTEX_WAKE || cmd == FUTEX_UNLOCK_PI || cmd == FUTEX_WAKE_BITSET {
+        return record_wake(pid, tid, uaddr, timestamp);
+    }
+
+    // Otherwise only wait operations
     if cmd != FUTEX_WAIT && cmd != FUTEX_LOCK_PI && cmd != FUTEX_WAIT_BITSET {
         return Ok(0);
     }
 
-    let timestamp = unsafe { bpf_ktime_get_ns() };
-
     let entry = FutexEntry { timestamp, uaddr };
+    // Keeps the oldest start (BPF_NOEXIST)
+    let _ = WAIT_SINCE.insert(&FutexKey::new(pid, uaddr), &timestamp, 1);
 
     FUTEX_ENTRIES.insert(&tid, &entry, 0).map_err(|_| 1i64)?;
 
     Ok(0)
 }
 
+/// A release that may wake waiters: estimate how long `tid` held the lock
+/// and remember the wake for the waiters it ends
+#[inline(always)]
+fn record_wake(pid: u32, tid: u32, uaddr: u64, timestamp: u64) -> Result<i64, i64> {
+    let key = FutexKey::new(pid, uaddr);
+    let mut hold_time_ns = 0;
+    if let Some(acquired) = unsafe { ACQUIRED.get(&tid) } {
+        if acquired.uaddr == uaddr {
+            hold_time_ns = timestamp - acquired.timestamp;
+        }
+    }
+    let _ = ACQUIRED.remove(&tid);
+    if hold_time_ns == 0 {
+        if let Some(&since) = unsafe { WAIT_SINCE.get(&key) } {
+            if since < timestamp {
+                hold_time_ns = timestamp - since;
+            }
+        }
+    }
+    let _ = WAIT_SINCE.remove(&key);
+
+    let wake = WakeEntry {
+        timestamp,
+        uaddr,
+        hold_time_ns,
+        tid,
+        _pad: 0,
+    };
+    let _ = LAST_WAKE.insert(&key, &wake, 0);
+    WAKE_ENTRIES.insert(&tid, &wake, 0).map_err(|_| 1i64)?;
+    Ok(0)
+}
+
 #[tracepoint(name = "sys_exit_futex", category = "syscalls")]
 pub fn sys_exit_futex(ctx: TracePointContext) -> i64 {
     try_sys_exit_futex(&ctx).unwrap_or_default()
@@ -128,6 +333,30 @@ fn try_sys_exit_futex(ctx: &TracePointContext) -> Result<i64, i64> {
     let tid = pid_tgid as u32;
     let pid = (pid_tgid >> 32) as u32;
 
+    if let Some(&wake) = unsafe { WAKE_ENTRIES.get(&tid) } {
+        let _ = WAKE_ENTRIES.remove(&tid);
+        // Number of waiters woken; releases nobody waited on are skipped
+        let ret: i64 = unsafe {
+            ctx.read_at(arg_offset(2, DEFAULT_RET_OFFSET))
+                .map_err(|_| 1i64)?
+        };
+        if ret > 0 {
+            emit(
+                ctx,
+                true,
+                pid,
+                tid,
+                wake.timestamp,
+                wake.uaddr,
+                0,
+                wake.hold_time_ns,
+                0,
+                LOCK_EVENT_RELEASE,
+            );
+        }
+        return Ok(0);
+    }
+
     // Check if we are tracking this thread
     let entry = unsafe {
         match FUTEX_ENTRIES.get(&tid) {
@@ -139,9 +368,203 @@ fn try_sys_exit_futex(ctx: &TracePointContext) -> Result<i64, i64> {
     // Calculate wait time
     let now = unsafe { bpf_ktime_get_ns() };
     let wait_time_ns = now - entry.timestamp;
+    let (timestamp, uaddr) = (entry.timestamp, entry.uaddr);
+
+    // The wake that ended this wait, if it came while we were waiting
+    let (waker_tid, hold_time_ns) = match unsafe { LAST_WAKE.get(&FutexKey::new(pid, uaddr)) } {
+        Some(wake) if wake.timestamp >= timestamp => (wake.tid, wake.hold_time_ns),
+        _ => (0, 0),
+    };
+
+    emit(
+        ctx,
+        true,
+        pid,
+        tid,
+        timestamp,
+        uaddr,
+        wait_time_ns,
+        hold_time_ns,
+        waker_tid,
+        LOCK_EVENT_WAIT,
+    );
+
+    // This thread now holds the lock until its own release
+    let acquired = FutexEntry {
+        timestamp: now,
+        uaddr,
+    };
+    let _ = ACQUIRED.insert(&tid, &acquired, 0);
+
+    // Cleanup
+    FUTEX_ENTRIES.remove(&tid).map_err(|_| 1i64)?;
+
+    Ok(0)
+}
+
+// ---------------------------------------------------------------------------
+// User-space locks (uprobes on pthread and parking_lot lock functions)
+// ---------------------------------------------------------------------------
+
+/// Entry of a lock function; its first argument is the lock
+#[uprobe]
+pub fn user_lock_enter(ctx: ProbeContext) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let lock_addr: u64 = match ctx.arg(0) {
+        Some(addr) => addr,
+        None => return 0,
+    };
+    let entry = FutexEntry {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        uaddr: lock_addr,
+    };
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let _ = USER_LOCK_CALLS.insert(&tid, &entry, 0);
+    0
+}
+
+/// Return of pthread lock functions (0 = acquired)
+#[uretprobe]
+pub fn pthread_lock_return(ctx: RetProbeContext) -> u32 {
+    let ret: i64 = ctx.ret().unwrap_or(-1);
+    user_lock_acquired(&ctx, ret as i32 == 0);
+    0
+}
+
+/// Return of parking_lot slow-path lock functions (true = acquired)
+#[uretprobe]
+pub fn parking_lot_lock_return(ctx: RetProbeContext) -> u32 {
+    let ret: u64 = ctx.ret().unwrap_or(0);
+    user_lock_acquired(&ctx, ret & 0xff != 0);
+    0
+}
+
+/// Report the acquire latency (spinning and blocking alike) and remember
+/// when the lock was taken
+#[inline(always)]
+fn user_lock_acquired<C: EbpfContext>(ctx: &C, acquired: bool) {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let tid = pid_tgid as u32;
+    let entry = match unsafe { USER_LOCK_CALLS.get(&tid) } {
+        Some(&e) => e,
+        None => return,
+    };
+    let _ = USER_LOCK_CALLS.remove(&tid);
+    if !acquired {
+        return;
+    }
+
+    let now = unsafe { bpf_ktime_get_ns() };
+    let wait_time_ns = now - entry.timestamp;
+    if wait_time_ns >= min_user_lock_ns() {
+        emit(
+            ctx,
+            false,
+            (pid_tgid >> 32) as u32,
+            tid,
+            entry.timestamp,
+            entry.uaddr,
+            wait_time_ns,
+            0,
+            0,
+            LOCK_EVENT_WAIT,
+        );
+    }
+
+    let key = HeldKey {
+        lock_addr: entry.uaddr,
+        tid,
+        _pad: 0,
+    };
+    let _ = USER_LOCKS_HELD.insert(&key, &now, 0);
+}
 
+/// Entry of an unlock function: report how long this thread held the lock
+#[uprobe]
+pub fn user_unlock_enter(ctx: ProbeContext) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let lock_addr: u64 = match ctx.arg(0) {
+        Some(addr) => addr,
+        None => return 0,
+    };
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let tid = pid_tgid as u32;
+    let now = unsafe { bpf_ktime_get_ns() };
+
+    let entry = FutexEntry {
+        timestamp: now,
+        uaddr: lock_addr,
+    };
+    let _ = USER_LOCK_CALLS.insert(&tid, &entry, 0);
+
+    let key = HeldKey {
+        lock_addr,
+        tid,
+        _pad: 0,
+    };
+    let acquired = match unsafe { USER_LOCKS_HELD.get(&key) } {
+        Some(&ts) => ts,
+        None => return 0,
+    };
+    let _ = USER_LOCKS_HELD.remove(&key);
+
+    let hold_time_ns = now - acquired;
+    if hold_time_ns >= min_user_lock_ns() {
+        emit(
+            &ctx,
+            false,
+            (pid_tgid >> 32) as u32,
+            tid,
+            now,
+            lock_addr,
+            0,
+            hold_time_ns,
+            0,
+            LOCK_EVENT_RELEASE,
+        );
+    }
+    0
+}
+
+/// Return of an unlock function
+#[uretprobe]
+pub fn user_unlock_return(_ctx: RetProbeContext) -> u32 {
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let _ = USER_LOCK_CALLS.remove(&tid);
+    0
+}
+
+#[inline(always)]
+fn min_user_lock_ns() -> u64 {
+    LOCK_CONFIG.get(3).copied().unwrap_or(0)
+}
+
+/// Send a lock event with the current stacks. The kernel stack is skipped
+/// for uprobes, where it would only show the probe trap.
+#[inline(always)]
+#[allow(clippy::too_many_arguments)]
+fn emit<C: EbpfContext>(
+    ctx: &C,
+    kernel_stack: bool,
+    pid: u32,
+    tid: u32,
+    timestamp: u64,
+    lock_addr: u64,
+    wait_time_ns: u64,
+    hold_time_ns: u64,
+    waker_tid: u32,
+    kind: u32,
+) {
     // Capture stacks - ensure we use the context properly
-    let kernel_stack_id = unsafe { LOCK_STACKS.get_stackid(ctx, 0) }.unwrap_or(-1);
+    let kernel_stack_id = if kernel_stack {
+        unsafe { LOCK_STACKS.get_stackid(ctx, 0) }.unwrap_or(-1)
+    } else {
+        -1
+    };
 
     // BPF_F_USER_STACK = 1 << 8
     let user_stack_id = unsafe { LOCK_STACKS.get_stackid(ctx, 256) }.unwrap_or(-1);
@@ -149,20 +572,228 @@ fn try_sys_exit_futex(ctx: &TracePointContext) -> Result<i64, i64> {
     let comm = bpf_get_current_comm().unwrap_or([0u8; 16]);
 
     let event = LockEventBpf {
-        timestamp: entry.timestamp,
+        timestamp,
         pid,
         tid,
-        lock_addr: entry.uaddr,
+        lock_addr,
         wait_time_ns,
         user_stack_id,
         kernel_stack_id,
         comm,
+        hold_time_ns,
+        waker_tid,
+        kind,
+        labels: current_labels(),
     };
 
     LOCK_EVENTS.output(ctx, &event, 0);
+}
 
-    // Cleanup
-    FUTEX_ENTRIES.remove(&tid).map_err(|_| 1i64)?;
+// ---------------------------------------------------------------------------
+// Heap allocations (uprobes on malloc, calloc, realloc and free), so locks
+// on the heap can be named after where they were allocated
+// ---------------------------------------------------------------------------
+
+/// Allocation in progress, by tid: the requested size
+#[map]
+static ALLOC_CALLS: HashMap<u32, u64> = HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// Live allocations, by process and address; the oldest are dropped first
+/// when full
+#[map]
+static ALLOCS: LruHashMap<AllocKey, AllocEntry> = LruHashMap::with_max_entries(MAX_ALLOCS, 0);
+
+/// User stacks of the allocations in ALLOCS
+#[map]
+static ALLOC_STACKS: StackTrace = StackTrace::with_max_entries(4096, 0);
+
+const MAX_ALLOCS: u32 = 65536;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AllocKey {
+    pub ptr: u64,
+    pub tgid: u32,
+    pub _pad: u32,
+}
+
+impl AllocKey {
+    /// `ptr` in the current process
+    #[inline(always)]
+    fn current(ptr: u64) -> Self {
+        Self {
+            ptr,
+            tgid: (bpf_get_current_pid_tgid() >> 32) as u32,
+            _pad: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AllocEntry {
+    pub size: u64,
+    pub user_stack_id: i64,
+}
+
+#[uprobe]
+pub fn malloc_enter(ctx: ProbeContext) -> u32 {
+    let size: u64 = ctx.arg(0).unwrap_or(0);
+    alloc_enter(size);
+    0
+}
+
+#[uprobe]
+pub fn calloc_enter(ctx: ProbeContext) -> u32 {
+    let count: u64 = ctx.arg(0).unwrap_or(0);
+    let size: u64 = ctx.arg(1).unwrap_or(0);
+    alloc_enter(count.wrapping_mul(size));
+    0
+}
+
+/// realloc frees its first argument and allocates anew
+#[uprobe]
+pub fn realloc_enter(ctx: ProbeContext) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let ptr: u64 = ctx.arg(0).unwrap_or(0);
+    let _ = ALLOCS.remove(&AllocKey::current(ptr));
+    let size: u64 = ctx.arg(1).unwrap_or(0);
+    alloc_enter(size);
+    0
+}
+
+#[inline(always)]
+fn alloc_enter(size: u64) {
+    if size == 0 || !should_trace() {
+        return;
+    }
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let _ = ALLOC_CALLS.insert(&tid, &size, 0);
+}
+
+/// Return of malloc, calloc and realloc: the new allocation and its stack
+#[uretprobe]
+pub fn alloc_return(ctx: RetProbeContext) -> u32 {
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let size = match unsafe { ALLOC_CALLS.get(&tid) } {
+        Some(&size) => size,
+        None => return 0,
+    };
+    let _ = ALLOC_CALLS.remove(&tid);
+
+    let ptr: u64 = ctx.ret().unwrap_or(0);
+    if ptr == 0 {
+        return 0;
+    }
+    let user_stack_id = unsafe { ALLOC_STACKS.get_stackid(&ctx, BPF_F_USER_STACK) }.unwrap_or(-1);
+    let entry = AllocEntry {
+        size,
+        user_stack_id,
+    };
+    let _ = ALLOCS.insert(&AllocKey::current(ptr), &entry, 0);
+    0
+}
+
+#[uprobe]
+pub fn free_enter(ctx: ProbeContext) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let ptr: u64 = ctx.arg(0).unwrap_or(0);
+    let _ = ALLOCS.remove(&AllocKey::current(ptr));
+    0
+}
+
+// ---------------------------------------------------------------------------
+// Kernel locks (lock:contention_begin/end)
+// ---------------------------------------------------------------------------
+
+#[tracepoint(name = "contention_begin", category = "lock")]
+pub fn contention_begin(ctx: TracePointContext) -> i64 {
+    try_contention_begin(&ctx).unwrap_or_default()
+}
+
+fn try_contention_begin(ctx: &TracePointContext) -> Result<i64, i64> {
+    if !should_trace() {
+        return Ok(0);
+    }
+
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let lock_addr: u64 = unsafe {
+        ctx.read_at(arg_offset(4, DEFAULT_LOCK_ADDR_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    let flags: u32 = unsafe {
+        ctx.read_at(arg_offset(5, DEFAULT_FLAGS_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+
+    // A contention can begin several times before it ends (a mutex spins,
+    // then sleeps): keep the first start and collect the flags. Contention
+    // on another lock meanwhile (from an interrupt) is not tracked.
+    if let Some(entry) = KERNEL_LOCK_WAITS.get_ptr_mut(&tid) {
+        let entry = unsafe { &mut *entry };
+        if entry.lock_addr == lock_addr {
+            entry.flags |= flags;
+        }
+        return Ok(0);
+    }
+
+    let entry = KernelLockEntry {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        lock_addr,
+        flags,
+        _pad: 0,
+    };
+    KERNEL_LOCK_WAITS
+        .insert(&tid, &entry, 0)
+        .map_err(|_| 1i64)?;
+
+    Ok(0)
+}
+
+#[tracepoint(name = "contention_end", category = "lock")]
+pub fn contention_end(ctx: TracePointContext) -> i64 {
+    try_contention_end(&ctx).unwrap_or_default()
+}
+
+fn try_contention_end(ctx: &TracePointContext) -> Result<i64, i64> {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let tid = pid_tgid as u32;
+
+    let entry = match unsafe { KERNEL_LOCK_WAITS.get(&tid) } {
+        Some(&e) => e,
+        None => return Ok(0),
+    };
+    let lock_addr: u64 = unsafe {
+        ctx.read_at(arg_offset(6, DEFAULT_LOCK_ADDR_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    if lock_addr != entry.lock_addr {
+        return Ok(0);
+    }
+    let _ = KERNEL_LOCK_WAITS.remove(&tid);
+
+    let now = unsafe { bpf_ktime_get_ns() };
+    let kernel_stack_id = unsafe { LOCK_STACKS.get_stackid(ctx, 0) }.unwrap_or(-1);
+    // BPF_F_USER_STACK = 1 << 8
+    let user_stack_id = unsafe { LOCK_STACKS.get_stackid(ctx, 256) }.unwrap_or(-1);
+
+    let event = KernelLockEventBpf {
+        timestamp: entry.timestamp,
+        pid: (pid_tgid >> 32) as u32,
+        tid,
+        lock_addr,
+        wait_time_ns: now - entry.timestamp,
+        user_stack_id,
+        kernel_stack_id,
+        comm: bpf_get_current_comm().unwrap_or([0u8; 16]),
+        flags: entry.flags,
+        _pad: 0,
+    };
+    KERNEL_LOCK_EVENTS.output(ctx, &event, 0);
 
     Ok(0)
 }
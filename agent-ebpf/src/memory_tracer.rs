@@ -0,0 +1,310 @@
+#![no_std]
+#![no_main]
+
+//! Page fault and reclaim eBPF program
+//!
+//! Samples page faults from `exceptions:page_fault_user`/`page_fault_kernel`
+//! with the faulting task's stacks, and times direct reclaim stalls from
+//! `vmscan:mm_vmscan_direct_reclaim_begin`/`end` (and the memcg variants).
+
+use aya_ebpf::{
+    helpers::{
+        bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_get_prandom_u32, bpf_ktime_get_ns,
+    },
+    macros::{map, tracepoint},
+    maps::{Array, HashMap, PerfEventArray, StackTrace},
+    programs::TracePointContext,
+};
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+
+mod common;
+use common::{BPF_F_USER_STACK, MAX_TRACKED_TIDS, TASK_COMM_LEN};
+
+#[no_mangle]
+#[link_section = "license"]
+pub static LICENSE: [u8; 4] = *b"GPL\0";
+
+#[map]
+static MEMORY_EVENTS: PerfEventArray<MemoryEventBpf> = PerfEventArray::new(0);
+
+#[map]
+static MEMORY_STACKS: StackTrace = StackTrace::with_max_entries(16384, 0);
+
+/// Reclaim in progress, by tid
+#[map]
+static RECLAIM_START: HashMap<u32, ReclaimStart> = HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// PID_FILTER[0] = target_pid (0 = trace all)
+/// PID_FILTER[1] = pidns device number
+/// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// MEMORY_CONFIG[0] = page_fault_user/kernel address offset
+/// MEMORY_CONFIG[1] = page_fault_user/kernel ip offset
+/// MEMORY_CONFIG[2] = page_fault_user/kernel error_code offset
+/// MEMORY_CONFIG[3] = mm_vmscan_direct_reclaim_begin order offset
+/// MEMORY_CONFIG[4] = mm_vmscan_direct_reclaim_end nr_reclaimed offset
+/// MEMORY_CONFIG[5] = mm_vmscan_memcg_reclaim_begin order offset
+/// MEMORY_CONFIG[6] = mm_vmscan_memcg_reclaim_end nr_reclaimed offset
+/// MEMORY_CONFIG[7] = record 1 in N page faults (0/1 = all)
+#[map]
+static MEMORY_CONFIG: Array<u64> = Array::with_max_entries(8, 0);
+
+const CONFIG_FAULT_ADDRESS: u32 = 0;
+const CONFIG_FAULT_IP: u32 = 1;
+const CONFIG_FAULT_ERROR_CODE: u32 = 2;
+const CONFIG_DIRECT_ORDER: u32 = 3;
+const CONFIG_DIRECT_NR_RECLAIMED: u32 = 4;
+const CONFIG_MEMCG_ORDER: u32 = 5;
+const CONFIG_MEMCG_NR_RECLAIMED: u32 = 6;
+const CONFIG_FAULT_SAMPLE_EVERY: u32 = 7;
+
+/// Common fields (8), then `address`, `ip`, `error_code`
+const DEFAULT_FAULT_ADDRESS_OFFSET: usize = 8;
+const DEFAULT_FAULT_IP_OFFSET: usize = 16;
+const DEFAULT_FAULT_ERROR_CODE_OFFSET: usize = 24;
+/// Common fields (8), then `order`, `gfp_flags` for begin and
+/// `nr_reclaimed` for end
+const DEFAULT_ORDER_OFFSET: usize = 8;
+const DEFAULT_NR_RECLAIMED_OFFSET: usize = 8;
+
+/// Event kinds (match the agent's MemoryCollector)
+const KIND_USER_FAULT: u32 = 0;
+const KIND_KERNEL_FAULT: u32 = 1;
+const KIND_DIRECT_RECLAIM: u32 = 2;
+const KIND_MEMCG_RECLAIM: u32 = 3;
+
+#[repr(C)]
+pub struct MemoryEventBpf {
+    pub timestamp: u64,
+    /// Faulting address (faults)
+    pub address: u64,
+    /// Faulting instruction (faults)
+    pub ip: u64,
+    pub error_code: u64,
+    /// Time spent reclaiming (reclaims)
+    pub stall_ns: u64,
+    pub nr_reclaimed: u64,
+    /// Stack ids in MEMORY_STACKS, -1 if none (kernel stacks are only
+    /// taken for faults raised in kernel mode)
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub pid: u32,
+    pub tid: u32,
+    pub kind: u32,
+    /// Allocation order that entered reclaim
+    pub order: u32,
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ReclaimStart {
+    pub timestamp: u64,
+    pub order: u32,
+    pub _pad: u32,
+}
+
+#[inline(always)]
+fn config(index: u32) -> u64 {
+    MEMORY_CONFIG.get(index).copied().unwrap_or(0)
+}
+
+#[inline(always)]
+fn arg_offset(index: u32, default: usize) -> usize {
+    match config(index) {
+        0 => default,
+        v => v as usize,
+    }
+}
+
+/// Check if the current process matches the PID filter.
+/// Returns true if the event should be processed.
+#[inline(always)]
+fn should_trace() -> bool {
+    let target = match PID_FILTER.get(0) {
+        Some(&v) => v as u32,
+        None => return true, // no filter configured
+    };
+    if target == 0 {
+        return true; // 0 = trace all
+    }
+
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
+    let ns_dev = match PID_FILTER.get(1) {
+        Some(&v) => v,
+        None => return false,
+    };
+    let ns_ino = match PID_FILTER.get(2) {
+        Some(&v) => v,
+        None => return false,
+    };
+
+    let mut nsinfo = aya_ebpf_bindings::bindings::bpf_pidns_info { pid: 0, tgid: 0 };
+    let ret = unsafe {
+        bpf_get_ns_current_pid_tgid(
+            ns_dev,
+            ns_ino,
+            &mut nsinfo as *mut _,
+            core::mem::size_of::<aya_ebpf_bindings::bindings::bpf_pidns_info>() as u32,
+        )
+    };
+    if ret != 0 {
+        return false;
+    }
+
+    nsinfo.tgid == target
+}
+
+#[tracepoint(name = "page_fault_user", category = "exceptions")]
+pub fn page_fault_user(ctx: TracePointContext) -> i64 {
+    try_page_fault(&ctx, KIND_USER_FAULT).unwrap_or_default()
+}
+
+#[tracepoint(name = "page_fault_kernel", category = "exceptions")]
+pub fn page_fault_kernel(ctx: TracePointContext) -> i64 {
+    try_page_fault(&ctx, KIND_KERNEL_FAULT).unwrap_or_default()
+}
+
+fn try_page_fault(ctx: &TracePointContext, kind: u32) -> Result<i64, i64> {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    // Kernel threads don't have user stacks to blame
+    if pid_tgid as u32 == 0 || !should_trace() {
+        return Ok(0);
+    }
+    let sample_every = config(CONFIG_FAULT_SAMPLE_EVERY);
+    if sample_every > 1 && (unsafe { bpf_get_prandom_u32() } as u64) % sample_every != 0 {
+        return Ok(0);
+    }
+
+    let user_stack_id = unsafe {
+        MEMORY_STACKS
+            .get_stackid(ctx, BPF_F_USER_STACK)
+            .unwrap_or(-1)
+    };
+    let kernel_stack_id = if kind == KIND_KERNEL_FAULT {
+        unsafe { MEMORY_STACKS.get_stackid(ctx, 0).unwrap_or(-1) }
+    } else {
+        -1
+    };
+
+    let event = MemoryEventBpf {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        address: unsafe {
+            ctx.read_at(arg_offset(
+                CONFIG_FAULT_ADDRESS,
+                DEFAULT_FAULT_ADDRESS_OFFSET,
+            ))
+            .map_err(|_| 1i64)?
+        },
+        ip: unsafe {
+            ctx.read_at(arg_offset(CONFIG_FAULT_IP, DEFAULT_FAULT_IP_OFFSET))
+                .unwrap_or(0)
+        },
+        error_code: unsafe {
+            ctx.read_at(arg_offset(
+                CONFIG_FAULT_ERROR_CODE,
+                DEFAULT_FAULT_ERROR_CODE_OFFSET,
+            ))
+            .unwrap_or(0)
+        },
+        stall_ns: 0,
+        nr_reclaimed: 0,
+        user_stack_id,
+        kernel_stack_id,
+        pid: (pid_tgid >> 32) as u32,
+        tid: pid_tgid as u32,
+        kind,
+        order: 0,
+        comm: bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]),
+    };
+    MEMORY_EVENTS.output(ctx, &event, 0);
+
+    Ok(0)
+}
+
+#[tracepoint(name = "mm_vmscan_direct_reclaim_begin", category = "vmscan")]
+pub fn mm_vmscan_direct_reclaim_begin(ctx: TracePointContext) -> i64 {
+    try_reclaim_begin(&ctx, CONFIG_DIRECT_ORDER).unwrap_or_default()
+}
+
+#[tracepoint(name = "mm_vmscan_memcg_reclaim_begin", category = "vmscan")]
+pub fn mm_vmscan_memcg_reclaim_begin(ctx: TracePointContext) -> i64 {
+    try_reclaim_begin(&ctx, CONFIG_MEMCG_ORDER).unwrap_or_default()
+}
+
+/// Reclaim runs in the task whose allocation needed it
+fn try_reclaim_begin(ctx: &TracePointContext, order_index: u32) -> Result<i64, i64> {
+    if !should_trace() {
+        return Ok(0);
+    }
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let order: i32 = unsafe {
+        ctx.read_at(arg_offset(order_index, DEFAULT_ORDER_OFFSET))
+            .unwrap_or(0)
+    };
+    let start = ReclaimStart {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        order: order as u32,
+        _pad: 0,
+    };
+    let _ = RECLAIM_START.insert(&tid, &start, 0);
+    Ok(0)
+}
+
+#[tracepoint(name = "mm_vmscan_direct_reclaim_end", category = "vmscan")]
+pub fn mm_vmscan_direct_reclaim_end(ctx: TracePointContext) -> i64 {
+    try_reclaim_end(&ctx, CONFIG_DIRECT_NR_RECLAIMED, KIND_DIRECT_RECLAIM).unwrap_or_default()
+}
+
+#[tracepoint(name = "mm_vmscan_memcg_reclaim_end", category = "vmscan")]
+pub fn mm_vmscan_memcg_reclaim_end(ctx: TracePointContext) -> i64 {
+    try_reclaim_end(&ctx, CONFIG_MEMCG_NR_RECLAIMED, KIND_MEMCG_RECLAIM).unwrap_or_default()
+}
+
+fn try_reclaim_end(ctx: &TracePointContext, nr_index: u32, kind: u32) -> Result<i64, i64> {
+    let now = unsafe { bpf_ktime_get_ns() };
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let tid = pid_tgid as u32;
+    let start = match unsafe { RECLAIM_START.get(&tid) } {
+        Some(s) => *s,
+        None => return Ok(0),
+    };
+    let _ = RECLAIM_START.remove(&tid);
+
+    let event = MemoryEventBpf {
+        timestamp: start.timestamp,
+        address: 0,
+        ip: 0,
+        error_code: 0,
+        stall_ns: now.saturating_sub(start.timestamp),
+        nr_reclaimed: unsafe {
+            ctx.read_at(arg_offset(nr_index, DEFAULT_NR_RECLAIMED_OFFSET))
+                .unwrap_or(0)
+        },
+        user_stack_id: -1,
+        kernel_stack_id: -1,
+        pid: (pid_tgid >> 32) as u32,
+        tid,
+        kind,
+        order: start.order,
+        comm: bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]),
+    };
+    MEMORY_EVENTS.output(ctx, &event, 0);
+
+    Ok(0)
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
@@ -0,0 +1,396 @@
+#![no_std]
+#![no_main]
+
+//! User-defined probe eBPF program
+//!
+//! Generic entry/return programs the agent attaches at runtime to the
+//! functions and tracepoints listed on its command line. Each probe gets a
+//! slot: the slot's entry program (`kprobe_N`, `uprobe_N`) records when a
+//! thread entered the function, its return program (`kretprobe_N`,
+//! `uretprobe_N`) reports the call's latency, and tracepoints
+//! (`tracepoint_N`) report each hit. USDT probes (`usdt_N`) are uprobes at
+//! the probe's `nop` that decode the arguments described in USDT_ARGS. The
+//! slot number is the probe id in the events.
+
+use aya_ebpf::{
+    helpers::{
+        bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns, bpf_probe_read_kernel,
+        bpf_probe_read_user,
+    },
+    macros::{kprobe, kretprobe, map, tracepoint, uprobe, uretprobe},
+    maps::{Array, HashMap, PerfEventArray, StackTrace},
+    programs::{ProbeContext, RetProbeContext, TracePointContext},
+    EbpfContext,
+};
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+
+mod common;
+use common::{BPF_F_USER_STACK, MAX_TRACKED_TIDS, TASK_COMM_LEN};
+
+#[no_mangle]
+#[link_section = "license"]
+pub static LICENSE: [u8; 4] = *b"GPL\0";
+
+#[map]
+static PROBE_EVENTS: PerfEventArray<ProbeEventBpf> = PerfEventArray::new(0);
+
+#[map]
+static USDT_EVENTS: PerfEventArray<UsdtEventBpf> = PerfEventArray::new(0);
+
+#[map]
+static PROBE_STACKS: StackTrace = StackTrace::with_max_entries(16384, 0);
+
+/// Calls in progress, by (slot, tid)
+#[map]
+static PROBE_CALLS: HashMap<u64, ProbeCall> = HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// PID_FILTER[0] = target_pid (0 = trace all)
+/// PID_FILTER[1] = pidns device number
+/// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// PROBE_CONFIG[0] = capture stacks (0/1)
+/// PROBE_CONFIG[1] = drop calls shorter than this (ns)
+#[map]
+static PROBE_CONFIG: Array<u64> = Array::with_max_entries(2, 0);
+
+/// USDT_ARGS[slot * MAX_USDT_ARGS + i] = where argument i of the slot's
+/// USDT probe lives
+#[map]
+static USDT_ARGS: Array<UsdtArgBpf> =
+    Array::with_max_entries((MAX_PROBES * MAX_USDT_ARGS) as u32, 0);
+
+/// Program slots (must match `MAX_PROBES` in the agent's config)
+const MAX_PROBES: usize = 16;
+/// Arguments decoded per USDT hit (must match the agent's `MAX_USDT_ARGS`)
+const MAX_USDT_ARGS: usize = 6;
+
+/// USDT argument kinds
+const ARG_CONST: u32 = 1;
+const ARG_REGISTER: u32 = 2;
+const ARG_MEMORY: u32 = 3;
+
+const CONFIG_STACKS: u32 = 0;
+const CONFIG_MIN_LATENCY: u32 = 1;
+
+/// Event kinds (match the agent's ProbeCollector)
+const KIND_CALL: u32 = 0;
+const KIND_HIT: u32 = 1;
+
+#[repr(C)]
+pub struct ProbeEventBpf {
+    /// Function entry, or when the tracepoint fired
+    pub timestamp: u64,
+    /// Entry to return (calls only)
+    pub duration_ns: u64,
+    /// Stack ids in PROBE_STACKS, -1 if none
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub pid: u32,
+    pub tid: u32,
+    /// Slot of the probe
+    pub probe: u32,
+    pub kind: u32,
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+#[repr(C)]
+pub struct UsdtEventBpf {
+    pub timestamp: u64,
+    /// Decoded arguments, the first `nargs` valid
+    pub args: [i64; MAX_USDT_ARGS],
+    pub pid: u32,
+    pub tid: u32,
+    /// Slot of the probe
+    pub probe: u32,
+    pub nargs: u32,
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+/// Where a USDT argument lives
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct UsdtArgBpf {
+    /// 0 = none (end of arguments), or one of the ARG_* kinds
+    pub kind: u32,
+    /// Offset of the register in `struct pt_regs`
+    pub reg_offset: u32,
+    /// Size in bytes
+    pub size: u32,
+    pub signed: u32,
+    /// Constant, or offset from the register for memory arguments
+    pub value: i64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ProbeCall {
+    pub timestamp: u64,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+}
+
+#[inline(always)]
+fn config(index: u32) -> u64 {
+    PROBE_CONFIG.get(index).copied().unwrap_or(0)
+}
+
+/// Check if the current process matches the PID filter.
+/// Returns true if the event should be processed.
+#[inline(always)]
+fn should_trace() -> bool {
+    let target = match PID_FILTER.get(0) {
+        Some(&v) => v as u32,
+        None => return true, // no filter configured
+    };
+    if target == 0 {
+        return true; // 0 = trace all
+    }
+
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
+    let ns_dev = match PID_FILTER.get(1) {
+        Some(&v) => v,
+        None => return false,
+    };
+    let ns_ino = match PID_FILTER.get(2) {
+        Some(&v) => v,
+        None => return false,
+    };
+
+    let mut nsinfo = aya_ebpf_bindings::bindings::bpf_pidns_info { pid: 0, tgid: 0 };
+    let ret = unsafe {
+        bpf_get_ns_current_pid_tgid(
+            ns_dev,
+            ns_ino,
+            &mut nsinfo as *mut _,
+            core::mem::size_of::<aya_ebpf_bindings::bindings::bpf_pidns_info>() as u32,
+        )
+    };
+    if ret != 0 {
+        return false;
+    }
+
+    nsinfo.tgid == target
+}
+
+/// User and kernel stack ids of the current task, -1 when stacks are off
+#[inline(always)]
+fn stack_ids<C: EbpfContext>(ctx: &C) -> (i64, i64) {
+    if config(CONFIG_STACKS) == 0 {
+        return (-1, -1);
+    }
+    unsafe {
+        (
+            PROBE_STACKS
+                .get_stackid(ctx, BPF_F_USER_STACK)
+                .unwrap_or(-1),
+            PROBE_STACKS.get_stackid(ctx, 0).unwrap_or(-1),
+        )
+    }
+}
+
+#[inline(always)]
+fn call_key(slot: u32, tid: u32) -> u64 {
+    ((slot as u64) << 32) | tid as u64
+}
+
+/// Function entry: remember when, and the stacks of the caller.
+/// Recursive calls of the same probe keep the innermost entry.
+#[inline(always)]
+fn probe_entry(ctx: &ProbeContext, slot: u32) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let (user_stack_id, kernel_stack_id) = stack_ids(ctx);
+    let call = ProbeCall {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        user_stack_id,
+        kernel_stack_id,
+    };
+    let _ = PROBE_CALLS.insert(&call_key(slot, tid), &call, 0);
+    0
+}
+
+/// Function return: report the call
+#[inline(always)]
+fn probe_return(ctx: &RetProbeContext, slot: u32) -> u32 {
+    let now = unsafe { bpf_ktime_get_ns() };
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let key = call_key(slot, pid_tgid as u32);
+    let call = match unsafe { PROBE_CALLS.get(&key) } {
+        Some(&c) => c,
+        None => return 0,
+    };
+    let _ = PROBE_CALLS.remove(&key);
+
+    let duration_ns = now.saturating_sub(call.timestamp);
+    if duration_ns < config(CONFIG_MIN_LATENCY) {
+        return 0;
+    }
+    let event = ProbeEventBpf {
+        timestamp: call.timestamp,
+        duration_ns,
+        user_stack_id: call.user_stack_id,
+        kernel_stack_id: call.kernel_stack_id,
+        pid: (pid_tgid >> 32) as u32,
+        tid: pid_tgid as u32,
+        probe: slot,
+        kind: KIND_CALL,
+        comm: bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]),
+    };
+    PROBE_EVENTS.output(ctx, &event, 0);
+    0
+}
+
+/// Tracepoint hit: report it with the stacks that led to it
+#[inline(always)]
+fn probe_hit(ctx: &TracePointContext, slot: u32) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let (user_stack_id, kernel_stack_id) = stack_ids(ctx);
+    let event = ProbeEventBpf {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        duration_ns: 0,
+        user_stack_id,
+        kernel_stack_id,
+        pid: (pid_tgid >> 32) as u32,
+        tid: pid_tgid as u32,
+        probe: slot,
+        kind: KIND_HIT,
+        comm: bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]),
+    };
+    PROBE_EVENTS.output(ctx, &event, 0);
+    0
+}
+
+/// Value of a USDT argument, sign- or zero-extended from its size
+#[inline(always)]
+fn usdt_arg(ctx: &ProbeContext, arg: &UsdtArgBpf) -> Option<i64> {
+    let regs = ctx.regs as *const u8;
+    let raw: u64 = match arg.kind {
+        ARG_CONST => arg.value as u64,
+        ARG_REGISTER => unsafe {
+            bpf_probe_read_kernel(regs.add(arg.reg_offset as usize) as *const u64).ok()?
+        },
+        ARG_MEMORY => unsafe {
+            let base: u64 =
+                bpf_probe_read_kernel(regs.add(arg.reg_offset as usize) as *const u64).ok()?;
+            bpf_probe_read_user((base as i64).wrapping_add(arg.value) as *const u64).ok()?
+        },
+        _ => return None,
+    };
+    if arg.size == 0 || arg.size >= 8 {
+        return Some(raw as i64);
+    }
+    let shift = 64 - arg.size * 8;
+    Some(if arg.signed != 0 {
+        ((raw << shift) as i64) >> shift
+    } else {
+        ((raw << shift) >> shift) as i64
+    })
+}
+
+/// USDT probe hit: report it with its arguments
+#[inline(always)]
+fn usdt_hit(ctx: &ProbeContext, slot: u32) -> u32 {
+    if !should_trace() {
+        return 0;
+    }
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let mut event = UsdtEventBpf {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        args: [0; MAX_USDT_ARGS],
+        pid: (pid_tgid >> 32) as u32,
+        tid: pid_tgid as u32,
+        probe: slot,
+        nargs: 0,
+        comm: bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]),
+    };
+    for i in 0..MAX_USDT_ARGS {
+        let Some(arg) = USDT_ARGS.get(slot * MAX_USDT_ARGS as u32 + i as u32) else {
+            break;
+        };
+        if arg.kind == 0 {
+            break;
+        }
+        // An unreadable argument (paged-out memory) reads as 0
+        event.args[i] = usdt_arg(ctx, arg).unwrap_or(0);
+        event.nargs += 1;
+    }
+    USDT_EVENTS.output(ctx, &event, 0);
+    0
+}
+
+/// The programs of probe slots, one set per slot (must match
+/// `MAX_PROBES` in the agent's config)
+macro_rules! probe_slots {
+    ($($slot:literal => $kprobe:ident, $kretprobe:ident, $uprobe:ident, $uretprobe:ident, $tracepoint:ident, $usdt:ident;)*) => {
+        $(
+            #[kprobe]
+            pub fn $kprobe(ctx: ProbeContext) -> u32 {
+                probe_entry(&ctx, $slot)
+            }
+
+            #[kretprobe]
+            pub fn $kretprobe(ctx: RetProbeContext) -> u32 {
+                probe_return(&ctx, $slot)
+            }
+
+            #[uprobe]
+            pub fn $uprobe(ctx: ProbeContext) -> u32 {
+                probe_entry(&ctx, $slot)
+            }
+
+            #[uretprobe]
+            pub fn $uretprobe(ctx: RetProbeContext) -> u32 {
+                probe_return(&ctx, $slot)
+            }
+
+            #[tracepoint]
+            pub fn $tracepoint(ctx: TracePointContext) -> u32 {
+                probe_hit(&ctx, $slot)
+            }
+
+            #[uprobe]
+            pub fn $usdt(ctx: ProbeContext) -> u32 {
+                usdt_hit(&ctx, $slot)
+            }
+        )*
+    };
+}
+
+probe_slots! {
+    0 => kprobe_0, kretprobe_0, uprobe_0, uretprobe_0, tracepoint_0, usdt_0;
+    1 => kprobe_1, kretprobe_1, uprobe_1, uretprobe_1, tracepoint_1, usdt_1;
+    2 => kprobe_2, kretprobe_2, uprobe_2, uretprobe_2, tracepoint_2, usdt_2;
+    3 => kprobe_3, kretprobe_3, uprobe_3, uretprobe_3, tracepoint_3, usdt_3;
+    4 => kprobe_4, kretprobe_4, uprobe_4, uretprobe_4, tracepoint_4, usdt_4;
+    5 => kprobe_5, kretprobe_5, uprobe_5, uretprobe_5, tracepoint_5, usdt_5;
+    6 => kprobe_6, kretprobe_6, uprobe_6, uretprobe_6, tracepoint_6, usdt_6;
+    7 => kprobe_7, kretprobe_7, uprobe_7, uretprobe_7, tracepoint_7, usdt_7;
+    8 => kprobe_8, kretprobe_8, uprobe_8, uretprobe_8, tracepoint_8, usdt_8;
+    9 => kprobe_9, kretprobe_9, uprobe_9, uretprobe_9, tracepoint_9, usdt_9;
+    10 => kprobe_10, kretprobe_10, uprobe_10, uretprobe_10, tracepoint_10, usdt_10;
+    11 => kprobe_11, kretprobe_11, uprobe_11, uretprobe_11, tracepoint_11, usdt_11;
+    12 => kprobe_12, kretprobe_12, uprobe_12, uretprobe_12, tracepoint_12, usdt_12;
+    13 => kprobe_13, kretprobe_13, uprobe_13, uretprobe_13, tracepoint_13, usdt_13;
+    14 => kprobe_14, kretprobe_14, uprobe_14, uretprobe_14, tracepoint_14, usdt_14;
+    15 => kprobe_15, kretprobe_15, uprobe_15, uretprobe_15, tracepoint_15, usdt_15;
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
@@ -0,0 +1,183 @@
+#![no_std]
+#![no_main]
+
+//! Process lifecycle tracker eBPF program
+//!
+//! Reports exec, exit and fork events so the agent can snapshot memory
+//! mappings while a process is still alive and symbolize its stacks after
+//! it has exited.
+
+use aya_ebpf::{
+    helpers::{
+        bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns,
+        bpf_probe_read_kernel_str_bytes,
+    },
+    macros::{map, tracepoint},
+    maps::{Array, PerfEventArray},
+    programs::TracePointContext,
+    EbpfContext,
+};
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+
+mod common;
+use common::{MAX_FILENAME_LEN, TASK_COMM_LEN};
+
+#[no_mangle]
+#[link_section = "license"]
+pub static LICENSE: [u8; 4] = *b"GPL\0";
+
+/// Event kinds (must match agent/src/collector/process.rs)
+const PROCESS_EXEC: u32 = 0;
+const PROCESS_EXIT: u32 = 1;
+const PROCESS_FORK: u32 = 2;
+
+#[map]
+static PROCESS_EVENTS: PerfEventArray<ProcessEventBpf> = PerfEventArray::new(0);
+
+/// PID_FILTER[0] = target_pid (0 = track all)
+/// PID_FILTER[1] = pidns device number
+/// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+#[repr(C)]
+pub struct ProcessEventBpf {
+    pub timestamp: u64,
+    pub kind: u32,
+    /// Process (tgid) the event is about; the child for fork events
+    pub pid: u32,
+    /// Parent process for fork events, 0 otherwise
+    pub ppid: u32,
+    pub _pad: u32,
+    pub comm: [u8; TASK_COMM_LEN],
+    /// Path passed to execve (exec events only, NUL-terminated)
+    pub filename: [u8; MAX_FILENAME_LEN],
+}
+
+/// Check if the current process matches the PID filter.
+/// Returns true if the event should be processed.
+#[inline(always)]
+fn should_trace() -> bool {
+    let target = match PID_FILTER.get(0) {
+        Some(&v) => v as u32,
+        None => return true, // no filter configured
+    };
+    if target == 0 {
+        return true; // 0 = trace all
+    }
+
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
+    let ns_dev = match PID_FILTER.get(1) {
+        Some(&v) => v,
+        None => return false,
+    };
+    let ns_ino = match PID_FILTER.get(2) {
+        Some(&v) => v,
+        None => return false,
+    };
+
+    let mut nsinfo = aya_ebpf_bindings::bindings::bpf_pidns_info { pid: 0, tgid: 0 };
+    let ret = unsafe {
+        bpf_get_ns_current_pid_tgid(
+            ns_dev,
+            ns_ino,
+            &mut nsinfo as *mut _,
+            core::mem::size_of::<aya_ebpf_bindings::bindings::bpf_pidns_info>() as u32,
+        )
+    };
+    if ret != 0 {
+        return false;
+    }
+
+    nsinfo.tgid == target
+}
+
+#[inline(always)]
+fn new_event(kind: u32, pid: u32) -> ProcessEventBpf {
+    ProcessEventBpf {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        kind,
+        pid,
+        ppid: 0,
+        _pad: 0,
+        comm: bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]),
+        filename: [0u8; MAX_FILENAME_LEN],
+    }
+}
+
+#[tracepoint(name = "sched_process_exec", category = "sched")]
+pub fn sched_process_exec(ctx: TracePointContext) -> i64 {
+    try_sched_process_exec(&ctx).unwrap_or_default()
+}
+
+fn try_sched_process_exec(ctx: &TracePointContext) -> Result<i64, i64> {
+    if !should_trace() {
+        return Ok(0);
+    }
+
+    let pid = (bpf_get_current_pid_tgid() >> 32) as u32;
+    let mut event = new_event(PROCESS_EXEC, pid);
+
+    // Offset 8: __data_loc char[] filename (low 16 bits = offset from ctx)
+    let data_loc: u32 = unsafe { ctx.read_at(8)? };
+    let filename_ptr = unsafe { (ctx.as_ptr() as *const u8).add((data_loc & 0xffff) as usize) };
+    let _ = unsafe { bpf_probe_read_kernel_str_bytes(filename_ptr, &mut event.filename) };
+
+    PROCESS_EVENTS.output(ctx, &event, 0);
+    Ok(0)
+}
+
+#[tracepoint(name = "sched_process_exit", category = "sched")]
+pub fn sched_process_exit(ctx: TracePointContext) -> i64 {
+    try_sched_process_exit(&ctx).unwrap_or_default()
+}
+
+fn try_sched_process_exit(ctx: &TracePointContext) -> Result<i64, i64> {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let tid = pid_tgid as u32;
+    let pid = (pid_tgid >> 32) as u32;
+
+    // Fires for every exiting thread; only the thread-group leader ends the process
+    if tid != pid || !should_trace() {
+        return Ok(0);
+    }
+
+    let event = new_event(PROCESS_EXIT, pid);
+    PROCESS_EVENTS.output(ctx, &event, 0);
+    Ok(0)
+}
+
+#[tracepoint(name = "sched_process_fork", category = "sched")]
+pub fn sched_process_fork(ctx: TracePointContext) -> i64 {
+    try_sched_process_fork(&ctx).unwrap_or_default()
+}
+
+fn try_sched_process_fork(ctx: &TracePointContext) -> Result<i64, i64> {
+    // Runs in the parent's context, so the filter follows the target's children
+    if !should_trace() {
+        return Ok(0);
+    }
+
+    // Offset 24: parent_pid, offset 44: child_pid
+    let parent_pid: u32 = unsafe { ctx.read_at(24)? };
+    let child_pid: u32 = unsafe { ctx.read_at(44)? };
+
+    let mut event = new_event(PROCESS_FORK, child_pid);
+    event.ppid = parent_pid;
+
+    PROCESS_EVENTS.output(ctx, &event, 0);
+    Ok(0)
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
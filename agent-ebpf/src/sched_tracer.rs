@@ -0,0 +1,346 @@
+#![no_std]
+#![no_main]
+
+//! Scheduler run-queue latency eBPF program
+//!
+//! Times how long threads wait for a CPU once runnable: from
+//! `sched:sched_wakeup`/`sched_wakeup_new`, or from being preempted in
+//! `sched:sched_switch`, until `sched_switch` puts them on a CPU.
+
+use aya_ebpf::{
+    helpers::{bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns},
+    macros::{map, tracepoint},
+    maps::{Array, HashMap, LruHashMap, PerfEventArray, StackTrace},
+    programs::TracePointContext,
+    EbpfContext,
+};
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+
+mod common;
+use common::{BPF_F_USER_STACK, MAX_TRACKED_TIDS, TASK_COMM_LEN};
+
+#[no_mangle]
+#[link_section = "license"]
+pub static LICENSE: [u8; 4] = *b"GPL\0";
+
+#[map]
+static SCHED_EVENTS: PerfEventArray<SchedEventBpf> = PerfEventArray::new(0);
+
+#[map]
+static SCHED_STACKS: StackTrace = StackTrace::with_max_entries(4096, 0);
+
+/// Runnable threads waiting for a CPU, by tid
+#[map]
+static RUNQ_START: HashMap<u32, RunqStart> = HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// Process of each thread seen running, by tid (wakeups only know the tid)
+#[map]
+static TASKS: LruHashMap<u32, TaskInfo> = LruHashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// PID_FILTER[0] = target_pid (0 = trace all)
+/// PID_FILTER[1] = pidns device number
+/// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// SCHED_CONFIG[0] = sched_wakeup(_new) pid offset
+/// SCHED_CONFIG[1] = sched_switch prev_state offset
+/// SCHED_CONFIG[2] = sched_switch next_comm offset
+/// SCHED_CONFIG[3] = sched_switch next_pid offset
+/// SCHED_CONFIG[4] = long wait threshold (ns): record the previous task
+/// SCHED_CONFIG[5] = min latency (ns): drop shorter waits
+/// SCHED_CONFIG[6] = capture stacks when threads become runnable (1 = on)
+#[map]
+static SCHED_CONFIG: Array<u64> = Array::with_max_entries(7, 0);
+
+const CONFIG_WAKEUP_PID: u32 = 0;
+const CONFIG_PREV_STATE: u32 = 1;
+const CONFIG_NEXT_COMM: u32 = 2;
+const CONFIG_NEXT_PID: u32 = 3;
+const CONFIG_LONG_WAIT_NS: u32 = 4;
+const CONFIG_MIN_LATENCY_NS: u32 = 5;
+const CONFIG_STACKS: u32 = 6;
+
+/// Common fields (8), then `comm[16]`, `pid`, `prio`, `target_cpu`
+const DEFAULT_WAKEUP_PID_OFFSET: usize = 24;
+/// Common fields (8), then `prev_comm[16]`, `prev_pid`, `prev_prio`,
+/// `prev_state`, `next_comm[16]`, `next_pid`, `next_prio`
+const DEFAULT_PREV_STATE_OFFSET: usize = 32;
+const DEFAULT_NEXT_COMM_OFFSET: usize = 40;
+const DEFAULT_NEXT_PID_OFFSET: usize = 56;
+
+/// `prev_state` of a preempted task (TASK_REPORT_MAX since Linux 4.14);
+/// 0 is a running task that yielded
+const TASK_REPORT_MAX: u64 = 0x100;
+
+#[repr(C)]
+pub struct SchedEventBpf {
+    /// When the thread became runnable
+    pub timestamp: u64,
+    pub delay_ns: u64,
+    /// Process of the thread, 0 if it hasn't been seen running yet
+    pub pid: u32,
+    pub tid: u32,
+    /// Thread that made it runnable (itself when preempted)
+    pub waker_pid: u32,
+    pub waker_tid: u32,
+    /// Task on the CPU before it, for waits over the long wait threshold
+    /// (0 otherwise)
+    pub prev_tid: u32,
+    pub cpu: u32,
+    /// 1 when the wait began with preemption rather than a wakeup
+    pub preempted: u32,
+    pub _pad: u32,
+    /// Stack ids in SCHED_STACKS of the waker, -1 unless stacks are on
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub comm: [u8; TASK_COMM_LEN],
+    pub prev_comm: [u8; TASK_COMM_LEN],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct RunqStart {
+    pub timestamp: u64,
+    pub pid: u32,
+    pub waker_pid: u32,
+    pub waker_tid: u32,
+    pub preempted: u32,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct TaskInfo {
+    pub pid: u32,
+    /// 0 when the process is filtered out
+    pub traced: u32,
+}
+
+#[inline(always)]
+fn config(index: u32) -> u64 {
+    SCHED_CONFIG.get(index).copied().unwrap_or(0)
+}
+
+#[inline(always)]
+fn arg_offset(index: u32, default: usize) -> usize {
+    match config(index) {
+        0 => default,
+        v => v as usize,
+    }
+}
+
+#[inline(always)]
+fn filtering() -> bool {
+    PID_FILTER.get(0).is_some_and(|&v| v != 0)
+}
+
+/// Check if the current process matches the PID filter.
+/// Returns true if the event should be processed.
+#[inline(always)]
+fn should_trace() -> bool {
+    let target = match PID_FILTER.get(0) {
+        Some(&v) => v as u32,
+        None => return true, // no filter configured
+    };
+    if target == 0 {
+        return true; // 0 = trace all
+    }
+
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
+    let ns_dev = match PID_FILTER.get(1) {
+        Some(&v) => v,
+        None => return false,
+    };
+    let ns_ino = match PID_FILTER.get(2) {
+        Some(&v) => v,
+        None => return false,
+    };
+
+    let mut nsinfo = aya_ebpf_bindings::bindings::bpf_pidns_info { pid: 0, tgid: 0 };
+    let ret = unsafe {
+        bpf_get_ns_current_pid_tgid(
+            ns_dev,
+            ns_ino,
+            &mut nsinfo as *mut _,
+            core::mem::size_of::<aya_ebpf_bindings::bindings::bpf_pidns_info>() as u32,
+        )
+    };
+    if ret != 0 {
+        return false;
+    }
+
+    nsinfo.tgid == target
+}
+
+/// Stacks of the current task, when enabled
+#[inline(always)]
+fn current_stacks<C: EbpfContext>(ctx: &C) -> (i64, i64) {
+    if config(CONFIG_STACKS) == 0 {
+        return (-1, -1);
+    }
+    unsafe {
+        (
+            SCHED_STACKS
+                .get_stackid(ctx, BPF_F_USER_STACK)
+                .unwrap_or(-1),
+            SCHED_STACKS.get_stackid(ctx, 0).unwrap_or(-1),
+        )
+    }
+}
+
+/// Start the run-queue wait of thread `tid`, made runnable by the current task
+#[inline(always)]
+fn enqueue(ctx: &TracePointContext, tid: u32, pid: u32, preempted: bool) {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let (user_stack_id, kernel_stack_id) = current_stacks(ctx);
+    let start = RunqStart {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        pid,
+        waker_pid: (pid_tgid >> 32) as u32,
+        waker_tid: pid_tgid as u32,
+        preempted: preempted as u32,
+        user_stack_id,
+        kernel_stack_id,
+    };
+    let _ = RUNQ_START.insert(&tid, &start, 0);
+}
+
+#[tracepoint(name = "sched_wakeup", category = "sched")]
+pub fn sched_wakeup(ctx: TracePointContext) -> i64 {
+    try_sched_wakeup(&ctx, false).unwrap_or_default()
+}
+
+#[tracepoint(name = "sched_wakeup_new", category = "sched")]
+pub fn sched_wakeup_new(ctx: TracePointContext) -> i64 {
+    try_sched_wakeup(&ctx, true).unwrap_or_default()
+}
+
+fn try_sched_wakeup(ctx: &TracePointContext, new_task: bool) -> Result<i64, i64> {
+    let tid: u32 = unsafe {
+        ctx.read_at(arg_offset(CONFIG_WAKEUP_PID, DEFAULT_WAKEUP_PID_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    if tid == 0 {
+        return Ok(0);
+    }
+
+    let pid = match unsafe { TASKS.get(&tid) } {
+        Some(task) => {
+            if task.traced == 0 {
+                return Ok(0);
+            }
+            task.pid
+        }
+        // New tasks are traced if their parent is, so forks of the target
+        // are followed; other threads wait until they've been seen running
+        None if new_task => {
+            if !should_trace() {
+                return Ok(0);
+            }
+            0
+        }
+        None if filtering() => return Ok(0),
+        None => 0,
+    };
+
+    enqueue(ctx, tid, pid, false);
+    Ok(0)
+}
+
+#[tracepoint(name = "sched_switch", category = "sched")]
+pub fn sched_switch(ctx: TracePointContext) -> i64 {
+    try_sched_switch(&ctx).unwrap_or_default()
+}
+
+fn try_sched_switch(ctx: &TracePointContext) -> Result<i64, i64> {
+    let now = unsafe { bpf_ktime_get_ns() };
+
+    // The current task is the one leaving the CPU
+    let pid_tgid = bpf_get_current_pid_tgid();
+    let prev_pid = (pid_tgid >> 32) as u32;
+    let prev_tid = pid_tgid as u32;
+    if prev_tid != 0 {
+        let traced = should_trace();
+        let task = TaskInfo {
+            pid: prev_pid,
+            traced: traced as u32,
+        };
+        let _ = TASKS.insert(&prev_tid, &task, 0);
+
+        // Still runnable: preempted (or yielded), back to waiting for a CPU
+        let prev_state: u64 = unsafe {
+            ctx.read_at(arg_offset(CONFIG_PREV_STATE, DEFAULT_PREV_STATE_OFFSET))
+                .unwrap_or(1)
+        };
+        if traced && (prev_state == 0 || prev_state & TASK_REPORT_MAX != 0) {
+            enqueue(ctx, prev_tid, prev_pid, true);
+        }
+    }
+
+    let next_tid: u32 = unsafe {
+        ctx.read_at(arg_offset(CONFIG_NEXT_PID, DEFAULT_NEXT_PID_OFFSET))
+            .map_err(|_| 1i64)?
+    };
+    if next_tid == 0 {
+        return Ok(0); // idle
+    }
+    let start = match unsafe { RUNQ_START.get(&next_tid) } {
+        Some(s) => *s,
+        None => return Ok(0),
+    };
+    let _ = RUNQ_START.remove(&next_tid);
+
+    let delay_ns = now.saturating_sub(start.timestamp);
+    if delay_ns < config(CONFIG_MIN_LATENCY_NS) {
+        return Ok(0);
+    }
+
+    let long_wait = config(CONFIG_LONG_WAIT_NS);
+    let long = long_wait != 0 && delay_ns >= long_wait;
+    let pid = match start.pid {
+        0 => unsafe { TASKS.get(&next_tid) }.map_or(0, |t| t.pid),
+        pid => pid,
+    };
+
+    let event = SchedEventBpf {
+        timestamp: start.timestamp,
+        delay_ns,
+        pid,
+        tid: next_tid,
+        waker_pid: start.waker_pid,
+        waker_tid: start.waker_tid,
+        prev_tid: if long { prev_tid } else { 0 },
+        cpu: unsafe { aya_ebpf::helpers::bpf_get_smp_processor_id() },
+        preempted: start.preempted,
+        _pad: 0,
+        user_stack_id: start.user_stack_id,
+        kernel_stack_id: start.kernel_stack_id,
+        comm: unsafe {
+            ctx.read_at(arg_offset(CONFIG_NEXT_COMM, DEFAULT_NEXT_COMM_OFFSET))
+                .unwrap_or([0u8; TASK_COMM_LEN])
+        },
+        prev_comm: if long {
+            bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN])
+        } else {
+            [0u8; TASK_COMM_LEN]
+        },
+    };
+    SCHED_EVENTS.output(ctx, &event, 0);
+
+    Ok(0)
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
@@ -2,25 +2,65 @@
 #![no_main]
 
 use aya_ebpf::{
-    helpers::{bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns},
+    bindings::pt_regs,
+    helpers::{
+        bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_get_prandom_u32, bpf_ktime_get_ns,
+        bpf_probe_read_user_str_bytes,
+    },
     macros::{map, raw_tracepoint},
-    maps::{Array, HashMap, PerfEventArray},
+    maps::{Array, HashMap, PerCpuArray, PerfEventArray, StackTrace},
     programs::RawTracePointContext,
-    EbpfContext,
+    EbpfContext, PtRegs,
 };
-use aya_ebpf_bindings::helpers::bpf_get_ns_current_pid_tgid;
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+
+mod common;
+use common::{BPF_F_USER_STACK, MAX_SYSCALL_ID, MAX_SYSCALL_PATH_LEN, SYSCALL_LATENCY_BUCKETS};
 
 #[map]
 static SYSCALL_EVENTS: PerfEventArray<SyscallEventBpf> = PerfEventArray::new(0);
 
+#[map]
+static SYSCALL_STACKS: StackTrace = StackTrace::with_max_entries(4096, 0);
+
 #[map]
 static SYSCALL_ENTRIES: HashMap<u32, SyscallEntry> = HashMap::with_max_entries(1024, 0);
 
 /// PID_FILTER[0] = target_pid (0 = trace all)
 /// PID_FILTER[1] = pidns device number
 /// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// SYSCALL_CONFIG[0] = capture stacks for calls at least this long, in ns
+/// (0 = never)
+/// SYSCALL_CONFIG[1] = drop calls shorter than this, in ns (0 = keep all)
+/// SYSCALL_CONFIG[2] = record 1 in N calls (0 or 1 = all)
+/// SYSCALL_CONFIG[3] = SYSCALL_FILTER use: 0 = none, 1 = allowlist, 2 = denylist
+/// SYSCALL_CONFIG[4] = 1 to update SYSCALL_HIST instead of emitting events
+/// SYSCALL_CONFIG[5] = histogram read interval, advanced by the agent on each
+/// read of SYSCALL_HIST
+#[map]
+static SYSCALL_CONFIG: Array<u64> = Array::with_max_entries(6, 0);
+
+const CONFIG_STACK_THRESHOLD_NS: u32 = 0;
+const CONFIG_MIN_LATENCY_NS: u32 = 1;
+const CONFIG_SAMPLE_EVERY: u32 = 2;
+const CONFIG_FILTER_MODE: u32 = 3;
+const CONFIG_AGGREGATE: u32 = 4;
+const CONFIG_INTERVAL: u32 = 5;
+
+const FILTER_ALLOW: u64 = 1;
+const FILTER_DENY: u64 = 2;
+
+/// SYSCALL_FILTER[id] = 1 if the syscall is on the configured list
 #[map]
-static PID_FILTER: Array<u64> = Array::with_max_entries(3, 0);
+static SYSCALL_FILTER: Array<u32> = Array::with_max_entries(MAX_SYSCALL_ID, 0);
+
+/// Per-syscall latency histograms for aggregation mode, indexed by id
+#[map]
+static SYSCALL_HIST: PerCpuArray<SyscallHistBpf> = PerCpuArray::with_max_entries(MAX_SYSCALL_ID, 0);
 
 #[repr(C)]
 pub struct SyscallEventBpf {
@@ -31,6 +71,27 @@ pub struct SyscallEventBpf {
     pub duration_ns: u64,
     pub return_value: i64,
     pub comm: [u8; 16],
+    /// File descriptor argument, -1 when the syscall takes none
+    pub fd: i64,
+    /// NUL-terminated path argument, empty when the syscall takes none
+    pub path: [u8; MAX_SYSCALL_PATH_LEN],
+    /// Stack ids in SYSCALL_STACKS, -1 unless the call was slow
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+}
+
+/// Running totals for one syscall on one CPU
+#[repr(C)]
+pub struct SyscallHistBpf {
+    pub count: u64,
+    pub total_duration_ns: u64,
+    /// Extremes of this CPU's calls since `interval` began
+    pub max_duration_ns: u64,
+    pub min_duration_ns: u64,
+    pub error_count: u64,
+    /// SYSCALL_CONFIG[5] when the extremes were last reset
+    pub interval: u64,
+    pub buckets: [u64; SYSCALL_LATENCY_BUCKETS],
 }
 
 #[derive(Clone, Copy)]
@@ -38,6 +99,135 @@ pub struct SyscallEventBpf {
 pub struct SyscallEntry {
     pub timestamp: u64,
     pub syscall_id: u32,
+    pub fd: i64,
+    pub path: [u8; MAX_SYSCALL_PATH_LEN],
+}
+
+/// Which argument of a syscall carries what we capture
+enum CapturedArg {
+    None,
+    /// Argument 0 is a file descriptor
+    Fd,
+    /// Argument `n` is a user-space path
+    Path(usize),
+}
+
+#[cfg(not(bpf_target_arch = "aarch64"))]
+#[inline(always)]
+fn captured_arg(syscall_id: u32) -> CapturedArg {
+    match syscall_id {
+        // read, write, close, pread64, pwrite64, readv, writev, connect,
+        // accept, sendto, recvfrom, sendmsg, recvmsg, fsync, fdatasync,
+        // preadv, pwritev
+        0 | 1 | 3 | 17..=20 | 42..=47 | 74 | 75 | 295 | 296 => CapturedArg::Fd,
+        // open, stat, lstat
+        2 | 4 | 6 => CapturedArg::Path(0),
+        // openat, newfstatat, statx, openat2
+        257 | 262 | 332 | 437 => CapturedArg::Path(1),
+        _ => CapturedArg::None,
+    }
+}
+
+/// aarch64 uses the generic syscall table, which has no open/stat/lstat
+#[cfg(bpf_target_arch = "aarch64")]
+#[inline(always)]
+fn captured_arg(syscall_id: u32) -> CapturedArg {
+    match syscall_id {
+        // close, read, write, readv, writev, pread64, pwrite64, preadv,
+        // pwritev, fsync, fdatasync, accept, connect, sendto, recvfrom,
+        // sendmsg, recvmsg
+        57 | 63..=70 | 82 | 83 | 202 | 203 | 206 | 207 | 211 | 212 => CapturedArg::Fd,
+        // openat, newfstatat, statx, openat2
+        56 | 79 | 291 | 437 => CapturedArg::Path(1),
+        _ => CapturedArg::None,
+    }
+}
+
+#[inline(always)]
+fn config(index: u32) -> u64 {
+    SYSCALL_CONFIG.get(index).copied().unwrap_or(0)
+}
+
+/// Check the syscall id against the allow/deny list and take the 1-in-N sample
+#[inline(always)]
+fn should_trace_syscall(syscall_id: u32) -> bool {
+    let listed = || SYSCALL_FILTER.get(syscall_id).copied().unwrap_or(0) != 0;
+    let keep = match config(CONFIG_FILTER_MODE) {
+        FILTER_ALLOW => listed(),
+        FILTER_DENY => !listed(),
+        _ => true,
+    };
+    if !keep {
+        return false;
+    }
+    let sample_every = config(CONFIG_SAMPLE_EVERY);
+    sample_every <= 1 || (unsafe { bpf_get_prandom_u32() } as u64) % sample_every == 0
+}
+
+/// log2(duration_ns), capped to the last bucket (matches `latency_bucket` in
+/// shared/src/types/profile.rs)
+#[inline(always)]
+fn latency_bucket(duration_ns: u64) -> usize {
+    let mut v = duration_ns;
+    let mut log2 = 0usize;
+    if v >= 1 << 32 {
+        v >>= 32;
+        log2 += 32;
+    }
+    if v >= 1 << 16 {
+        v >>= 16;
+        log2 += 16;
+    }
+    if v >= 1 << 8 {
+        v >>= 8;
+        log2 += 8;
+    }
+    if v >= 1 << 4 {
+        v >>= 4;
+        log2 += 4;
+    }
+    if v >= 1 << 2 {
+        v >>= 2;
+        log2 += 2;
+    }
+    if v >= 1 << 1 {
+        log2 += 1;
+    }
+    if log2 >= SYSCALL_LATENCY_BUCKETS {
+        SYSCALL_LATENCY_BUCKETS - 1
+    } else {
+        log2
+    }
+}
+
+/// Add one call to this CPU's histogram of `syscall_id`
+#[inline(always)]
+fn record_histogram(syscall_id: u32, duration_ns: u64, return_value: i64) {
+    let Some(hist) = SYSCALL_HIST.get_ptr_mut(syscall_id) else {
+        return;
+    };
+    // Per-CPU value: nothing else updates it while this program runs
+    let hist = unsafe { &mut *hist };
+    let interval = SYSCALL_CONFIG.get(CONFIG_INTERVAL).copied().unwrap_or(0);
+    if hist.count == 0 || hist.interval != interval {
+        // First call since the agent's last read
+        hist.interval = interval;
+        hist.min_duration_ns = duration_ns;
+        hist.max_duration_ns = duration_ns;
+    } else if duration_ns < hist.min_duration_ns {
+        hist.min_duration_ns = duration_ns;
+    } else if duration_ns > hist.max_duration_ns {
+        hist.max_duration_ns = duration_ns;
+    }
+    hist.count += 1;
+    hist.total_duration_ns += duration_ns;
+    if return_value < 0 {
+        hist.error_count += 1;
+    }
+    let bucket = latency_bucket(duration_ns);
+    if let Some(n) = hist.buckets.get_mut(bucket) {
+        *n += 1;
+    }
 }
 
 /// Check if the current process matches the PID filter.
@@ -52,6 +242,13 @@ fn should_trace() -> bool {
         return true; // 0 = trace all
     }
 
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
     let ns_dev = match PID_FILTER.get(1) {
         Some(&v) => v,
         None => return false,
@@ -95,13 +292,41 @@ fn try_sys_enter(ctx: &RawTracePointContext) -> Result<i32, i64> {
     // args[1] is the syscall ID
     let args = ctx.as_ptr() as *const u64;
     let syscall_id = unsafe { *args.offset(1) } as u32;
+    if !should_trace_syscall(syscall_id) {
+        return Ok(0);
+    }
 
-    let timestamp = unsafe { bpf_ktime_get_ns() };
-
-    let entry = SyscallEntry {
-        timestamp,
+    // args[0] is the task's saved user registers (struct pt_regs *)
+    let regs = PtRegs::new(unsafe { *args } as *mut pt_regs);
+    let mut entry = SyscallEntry {
+        timestamp: 0,
         syscall_id,
+        fd: -1,
+        path: [0u8; MAX_SYSCALL_PATH_LEN],
     };
+    // Histograms have no use for arguments
+    let arg = if config(CONFIG_AGGREGATE) != 0 {
+        CapturedArg::None
+    } else {
+        captured_arg(syscall_id)
+    };
+    match arg {
+        CapturedArg::Fd => {
+            if let Some(fd) = regs.arg::<*const u8>(0) {
+                // fds are C ints; the upper register half is not part of it
+                entry.fd = fd as u64 as i32 as i64;
+            }
+        }
+        CapturedArg::Path(n) => {
+            if let Some(path) = regs.arg::<*const u8>(n) {
+                let _ = unsafe { bpf_probe_read_user_str_bytes(path, &mut entry.path) };
+            }
+        }
+        CapturedArg::None => {}
+    }
+
+    // Taken last so argument capture does not count towards the duration
+    entry.timestamp = unsafe { bpf_ktime_get_ns() };
 
     SYSCALL_ENTRIES.insert(&tid, &entry, 0).map_err(|_| 1i64)?;
 
@@ -131,17 +356,47 @@ fn try_sys_exit(ctx: &RawTracePointContext) -> Result<i32, i64> {
 
     let now = unsafe { bpf_ktime_get_ns() };
     let duration_ns = now - entry.timestamp;
+    let syscall_id = entry.syscall_id;
+
+    if duration_ns < config(CONFIG_MIN_LATENCY_NS) {
+        SYSCALL_ENTRIES.remove(&tid).map_err(|_| 1i64)?;
+        return Ok(0);
+    }
+    if config(CONFIG_AGGREGATE) != 0 {
+        record_histogram(syscall_id, duration_ns, return_value);
+        SYSCALL_ENTRIES.remove(&tid).map_err(|_| 1i64)?;
+        return Ok(0);
+    }
 
     let comm = bpf_get_current_comm().unwrap_or([0u8; 16]);
 
+    let stack_threshold = config(CONFIG_STACK_THRESHOLD_NS);
+    let (user_stack_id, kernel_stack_id) = if stack_threshold != 0 && duration_ns >= stack_threshold
+    {
+        unsafe {
+            (
+                SYSCALL_STACKS
+                    .get_stackid(ctx, BPF_F_USER_STACK)
+                    .unwrap_or(-1),
+                SYSCALL_STACKS.get_stackid(ctx, 0).unwrap_or(-1),
+            )
+        }
+    } else {
+        (-1, -1)
+    };
+
     let event = SyscallEventBpf {
         timestamp: entry.timestamp,
         pid,
         tid,
-        syscall_id: entry.syscall_id,
+        syscall_id,
         duration_ns,
         return_value,
         comm,
+        fd: entry.fd,
+        path: entry.path,
+        user_stack_id,
+        kernel_stack_id,
     };
 
     SYSCALL_EVENTS.output(ctx, &event, 0);
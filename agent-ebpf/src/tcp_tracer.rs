@@ -0,0 +1,638 @@
+#![no_std]
+#![no_main]
+
+//! TCP tracer eBPF program
+//!
+//! Follows TCP connections through `sock:inet_sock_set_state`: connect
+//! latency from SYN_SENT to ESTABLISHED, then bytes sent and received
+//! (`tcp_sendmsg` kretprobe, `tcp_cleanup_rbuf` kprobe), smoothed RTT (`tcp:tcp_probe`)
+//! and retransmits until the socket closes. Retransmits and resets are also
+//! reported as they happen.
+
+use aya_ebpf::{
+    helpers::{
+        bpf_get_current_comm, bpf_get_current_pid_tgid, bpf_ktime_get_ns, bpf_probe_read_kernel,
+    },
+    macros::{kprobe, kretprobe, map, tracepoint},
+    maps::{Array, HashMap, LruHashMap, PerfEventArray},
+    programs::{ProbeContext, RetProbeContext, TracePointContext},
+    EbpfContext,
+};
+use aya_ebpf_bindings::helpers::{bpf_get_current_cgroup_id, bpf_get_ns_current_pid_tgid};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+mod common;
+use common::{MAX_TRACKED_CONNECTIONS, MAX_TRACKED_TIDS, TASK_COMM_LEN};
+
+#[no_mangle]
+#[link_section = "license"]
+pub static LICENSE: [u8; 4] = *b"GPL\0";
+
+#[map]
+static TCP_EVENTS: PerfEventArray<TcpEventBpf> = PerfEventArray::new(0);
+
+/// Open connections, by socket address
+#[map]
+static CONNECTIONS: LruHashMap<u64, ConnInfo> =
+    LruHashMap::with_max_entries(MAX_TRACKED_CONNECTIONS, 0);
+
+/// Socket address of each open connection, by local port and remote
+/// endpoint (`tcp:tcp_probe` has no socket address on older kernels)
+#[map]
+static CONN_TUPLES: LruHashMap<ConnTuple, u64> =
+    LruHashMap::with_max_entries(MAX_TRACKED_CONNECTIONS, 0);
+
+/// Socket of each `tcp_sendmsg` call in progress, by tid
+#[map]
+static SENDMSG_CALLS: HashMap<u32, u64> = HashMap::with_max_entries(MAX_TRACKED_TIDS, 0);
+
+/// PID_FILTER[0] = target_pid (0 = trace all)
+/// PID_FILTER[1] = pidns device number
+/// PID_FILTER[2] = pidns inode number
+/// PID_FILTER[3] = cgroup of a launched command, traced along with the target (0 = none)
+#[map]
+static PID_FILTER: Array<u64> = Array::with_max_entries(4, 0);
+
+/// Offsets of the tracepoint fields, read by the agent from the tracepoint
+/// formats (0 = the layout below)
+/// TCP_CONFIG[0..=8] = inet_sock_set_state skaddr, oldstate, newstate,
+///                     sport, dport, family, protocol, daddr, daddr_v6
+/// TCP_CONFIG[9]     = tcp_retransmit_skb skaddr
+/// TCP_CONFIG[10]    = tcp_send_reset skaddr
+/// TCP_CONFIG[11]    = tcp_receive_reset skaddr
+/// TCP_CONFIG[12..=16] = tcp_probe daddr, sport, dport, family, srtt
+#[map]
+static TCP_CONFIG: Array<u64> = Array::with_max_entries(17, 0);
+
+const CONFIG_STATE_SKADDR: u32 = 0;
+const CONFIG_STATE_OLDSTATE: u32 = 1;
+const CONFIG_STATE_NEWSTATE: u32 = 2;
+const CONFIG_STATE_SPORT: u32 = 3;
+const CONFIG_STATE_DPORT: u32 = 4;
+const CONFIG_STATE_FAMILY: u32 = 5;
+const CONFIG_STATE_PROTOCOL: u32 = 6;
+const CONFIG_STATE_DADDR: u32 = 7;
+const CONFIG_STATE_DADDR_V6: u32 = 8;
+const CONFIG_RETRANSMIT_SKADDR: u32 = 9;
+const CONFIG_SEND_RESET_SKADDR: u32 = 10;
+const CONFIG_RECEIVE_RESET_SKADDR: u32 = 11;
+const CONFIG_PROBE_DADDR: u32 = 12;
+const CONFIG_PROBE_SPORT: u32 = 13;
+const CONFIG_PROBE_DPORT: u32 = 14;
+const CONFIG_PROBE_FAMILY: u32 = 15;
+const CONFIG_PROBE_SRTT: u32 = 16;
+
+/// `inet_sock_set_state`: common fields (8), `skaddr`, `oldstate`,
+/// `newstate`, `sport`, `dport`, `family`, `protocol`, `saddr[4]`,
+/// `daddr[4]`, `saddr_v6[16]`, `daddr_v6[16]`
+const DEFAULT_STATE_OFFSETS: [usize; 9] = [8, 16, 20, 24, 26, 28, 30, 36, 56];
+/// `tcp_retransmit_skb` and `tcp_send_reset` start with `skbaddr`, then
+/// `skaddr`; `tcp_receive_reset` starts with `skaddr`
+const DEFAULT_RETRANSMIT_SKADDR_OFFSET: usize = 16;
+const DEFAULT_SEND_RESET_SKADDR_OFFSET: usize = 16;
+const DEFAULT_RECEIVE_RESET_SKADDR_OFFSET: usize = 8;
+/// `tcp_probe`: `saddr[28]`, `daddr[28]` (sockaddr_in6 sized), `sport`,
+/// `dport`, `family`, `mark`, `data_len`, `snd_nxt`, `snd_una`, `snd_cwnd`,
+/// `ssthresh`, `snd_wnd`, `srtt`
+const DEFAULT_PROBE_OFFSETS: [usize; 5] = [36, 64, 66, 68, 100];
+
+/// `struct sock_common` starts with `skc_daddr`, `skc_rcv_saddr`,
+/// `skc_hash`, `skc_dport`, `skc_num`, `skc_family`; `skc_v6_daddr` follows
+/// the bind node, protocol and netns pointers
+const SKC_DADDR_OFFSET: usize = 0;
+const SKC_DPORT_OFFSET: usize = 12;
+const SKC_NUM_OFFSET: usize = 14;
+const SKC_FAMILY_OFFSET: usize = 16;
+const SKC_V6_DADDR_OFFSET: usize = 56;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+const IPPROTO_TCP: u16 = 6;
+
+const TCP_ESTABLISHED: i32 = 1;
+const TCP_SYN_SENT: i32 = 2;
+const TCP_SYN_RECV: i32 = 3;
+const TCP_CLOSE: i32 = 7;
+
+/// Event kinds (match TcpEventKind in the agent)
+const KIND_CONNECT: u32 = 0;
+const KIND_CONNECT_FAILED: u32 = 1;
+const KIND_RETRANSMIT: u32 = 2;
+const KIND_RESET_SENT: u32 = 3;
+const KIND_RESET_RECEIVED: u32 = 4;
+const KIND_CLOSE: u32 = 5;
+
+/// ConnInfo::traced
+const TRACED_UNKNOWN: u32 = 0;
+const TRACED_YES: u32 = 1;
+const TRACED_NO: u32 = 2;
+
+/// ConnInfo::passive
+const PASSIVE_NO: u16 = 0;
+const PASSIVE_YES: u16 = 1;
+const PASSIVE_UNKNOWN: u16 = 2;
+
+#[repr(C)]
+pub struct TcpEventBpf {
+    pub timestamp: u64,
+    /// Connect latency, or connection lifetime for closes
+    pub duration_ns: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Process owning the connection, 0 if none has used it
+    pub pid: u32,
+    pub tid: u32,
+    pub kind: u32,
+    /// Retransmits over the connection's lifetime (closes)
+    pub retransmits: u32,
+    /// Last smoothed RTT in microseconds, 0 if unknown
+    pub srtt_us: u32,
+    pub _pad: u32,
+    pub family: u16,
+    pub lport: u16,
+    pub rport: u16,
+    /// 1 for accepted connections, 2 if opened before tracing started
+    pub passive: u16,
+    /// IPv4 address in the first 4 bytes, or IPv6 address
+    pub raddr: [u8; 16],
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ConnInfo {
+    /// Connect or accept time, or when first seen
+    pub start_ns: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub traced: u32,
+    pub retransmits: u32,
+    pub srtt_us: u32,
+    /// 1 between SYN_SENT and ESTABLISHED
+    pub connecting: u32,
+    pub family: u16,
+    pub lport: u16,
+    pub rport: u16,
+    pub passive: u16,
+    pub raddr: [u8; 16],
+    pub comm: [u8; TASK_COMM_LEN],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ConnTuple {
+    pub family: u16,
+    pub lport: u16,
+    pub rport: u16,
+    pub _pad: u16,
+    pub raddr: [u8; 16],
+}
+
+#[inline(always)]
+fn arg_offset(index: u32, default: usize) -> usize {
+    match TCP_CONFIG.get(index) {
+        Some(&v) if v != 0 => v as usize,
+        _ => default,
+    }
+}
+
+#[inline(always)]
+fn filtering() -> bool {
+    PID_FILTER.get(0).is_some_and(|&v| v != 0)
+}
+
+/// Check if the current process matches the PID filter.
+/// Returns true if the event should be processed.
+#[inline(always)]
+fn should_trace() -> bool {
+    let target = match PID_FILTER.get(0) {
+        Some(&v) => v as u32,
+        None => return true, // no filter configured
+    };
+    if target == 0 {
+        return true; // 0 = trace all
+    }
+
+    // Children of a launched command share its cgroup
+    if let Some(&cgroup) = PID_FILTER.get(3) {
+        if cgroup != 0 && unsafe { bpf_get_current_cgroup_id() } == cgroup {
+            return true;
+        }
+    }
+
+    let ns_dev = match PID_FILTER.get(1) {
+        Some(&v) => v,
+        None => return false,
+    };
+    let ns_ino = match PID_FILTER.get(2) {
+        Some(&v) => v,
+        None => return false,
+    };
+
+    let mut nsinfo = aya_ebpf_bindings::bindings::bpf_pidns_info { pid: 0, tgid: 0 };
+    let ret = unsafe {
+        bpf_get_ns_current_pid_tgid(
+            ns_dev,
+            ns_ino,
+            &mut nsinfo as *mut _,
+            core::mem::size_of::<aya_ebpf_bindings::bindings::bpf_pidns_info>() as u32,
+        )
+    };
+    if ret != 0 {
+        return false;
+    }
+
+    nsinfo.tgid == target
+}
+
+/// Whether events of `conn` are reported: its process passed the filter,
+/// or no process has used it yet and nothing is filtered
+#[inline(always)]
+fn reported(conn: &ConnInfo) -> bool {
+    match conn.traced {
+        TRACED_YES => true,
+        TRACED_UNKNOWN => !filtering(),
+        _ => false,
+    }
+}
+
+/// Record the current task as the owner of `conn`
+#[inline(always)]
+fn set_owner(conn: &mut ConnInfo) {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    conn.pid = (pid_tgid >> 32) as u32;
+    conn.tid = pid_tgid as u32;
+    conn.comm = bpf_get_current_comm().unwrap_or([0u8; TASK_COMM_LEN]);
+}
+
+#[inline(always)]
+fn tuple_of(conn: &ConnInfo) -> ConnTuple {
+    ConnTuple {
+        family: conn.family,
+        lport: conn.lport,
+        rport: conn.rport,
+        _pad: 0,
+        raddr: conn.raddr,
+    }
+}
+
+/// New connection record for socket `sk`, addressed from `struct sock_common`
+#[inline(always)]
+fn conn_from_sock(sk: u64, now: u64) -> ConnInfo {
+    let read_u16 = |off: usize| unsafe {
+        bpf_probe_read_kernel((sk as usize + off) as *const u16).unwrap_or(0)
+    };
+    let family = read_u16(SKC_FAMILY_OFFSET);
+    let mut raddr = [0u8; 16];
+    if family == AF_INET6 {
+        raddr = unsafe {
+            bpf_probe_read_kernel((sk as usize + SKC_V6_DADDR_OFFSET) as *const [u8; 16])
+                .unwrap_or([0u8; 16])
+        };
+    } else {
+        let v4: [u8; 4] = unsafe {
+            bpf_probe_read_kernel((sk as usize + SKC_DADDR_OFFSET) as *const [u8; 4])
+                .unwrap_or([0u8; 4])
+        };
+        raddr[..4].copy_from_slice(&v4);
+    }
+    ConnInfo {
+        start_ns: now,
+        bytes_sent: 0,
+        bytes_received: 0,
+        pid: 0,
+        tid: 0,
+        traced: TRACED_UNKNOWN,
+        retransmits: 0,
+        srtt_us: 0,
+        connecting: 0,
+        family,
+        lport: read_u16(SKC_NUM_OFFSET),
+        rport: u16::from_be(read_u16(SKC_DPORT_OFFSET)),
+        passive: PASSIVE_UNKNOWN,
+        raddr,
+        comm: [0u8; TASK_COMM_LEN],
+    }
+}
+
+#[inline(always)]
+fn emit<C: EbpfContext>(ctx: &C, conn: &ConnInfo, kind: u32, now: u64) {
+    let duration_ns = match kind {
+        KIND_RETRANSMIT | KIND_RESET_SENT | KIND_RESET_RECEIVED => 0,
+        _ => now.saturating_sub(conn.start_ns),
+    };
+    let event = TcpEventBpf {
+        timestamp: now,
+        duration_ns,
+        bytes_sent: conn.bytes_sent,
+        bytes_received: conn.bytes_received,
+        pid: conn.pid,
+        tid: conn.tid,
+        kind,
+        retransmits: conn.retransmits,
+        srtt_us: conn.srtt_us,
+        _pad: 0,
+        family: conn.family,
+        lport: conn.lport,
+        rport: conn.rport,
+        passive: conn.passive,
+        raddr: conn.raddr,
+        comm: conn.comm,
+    };
+    TCP_EVENTS.output(ctx, &event, 0);
+}
+
+#[tracepoint(name = "inet_sock_set_state", category = "sock")]
+pub fn inet_sock_set_state(ctx: TracePointContext) -> i64 {
+    try_inet_sock_set_state(&ctx).unwrap_or_default()
+}
+
+fn try_inet_sock_set_state(ctx: &TracePointContext) -> Result<i64, i64> {
+    let field = |index: u32| arg_offset(index, DEFAULT_STATE_OFFSETS[index as usize]);
+    let protocol: u16 = unsafe {
+        ctx.read_at(field(CONFIG_STATE_PROTOCOL))
+            .map_err(|_| 1i64)?
+    };
+    if protocol != IPPROTO_TCP {
+        return Ok(0);
+    }
+    let skaddr: u64 = unsafe { ctx.read_at(field(CONFIG_STATE_SKADDR)).map_err(|_| 1i64)? };
+    let oldstate: i32 = unsafe {
+        ctx.read_at(field(CONFIG_STATE_OLDSTATE))
+            .map_err(|_| 1i64)?
+    };
+    let newstate: i32 = unsafe {
+        ctx.read_at(field(CONFIG_STATE_NEWSTATE))
+            .map_err(|_| 1i64)?
+    };
+    let now = unsafe { bpf_ktime_get_ns() };
+
+    match newstate {
+        // connect(): runs in the connecting task
+        TCP_SYN_SENT => {
+            if !should_trace() {
+                return Ok(0);
+            }
+            let mut conn = conn_from_tracepoint(ctx, now)?;
+            conn.traced = TRACED_YES;
+            conn.connecting = 1;
+            conn.passive = PASSIVE_NO;
+            set_owner(&mut conn);
+            let _ = CONNECTIONS.insert(&skaddr, &conn, 0);
+            let _ = CONN_TUPLES.insert(&tuple_of(&conn), &skaddr, 0);
+        }
+        TCP_ESTABLISHED if oldstate == TCP_SYN_SENT => {
+            if let Some(conn) = CONNECTIONS.get_ptr_mut(&skaddr) {
+                let conn = unsafe { &mut *conn };
+                emit(ctx, conn, KIND_CONNECT, now);
+                conn.connecting = 0;
+            }
+        }
+        // Accepted connection, in softirq: the owner is the first task to
+        // send or receive on it
+        TCP_ESTABLISHED if oldstate == TCP_SYN_RECV => {
+            let mut conn = conn_from_tracepoint(ctx, now)?;
+            conn.passive = PASSIVE_YES;
+            let _ = CONNECTIONS.insert(&skaddr, &conn, 0);
+            let _ = CONN_TUPLES.insert(&tuple_of(&conn), &skaddr, 0);
+        }
+        TCP_CLOSE => {
+            let conn = match unsafe { CONNECTIONS.get(&skaddr) } {
+                Some(c) => *c,
+                None => return Ok(0),
+            };
+            let _ = CONNECTIONS.remove(&skaddr);
+            let _ = CONN_TUPLES.remove(&tuple_of(&conn));
+            if reported(&conn) {
+                let kind = if conn.connecting != 0 {
+                    KIND_CONNECT_FAILED
+                } else {
+                    KIND_CLOSE
+                };
+                emit(ctx, &conn, kind, now);
+            }
+        }
+        _ => {}
+    }
+    Ok(0)
+}
+
+/// New connection record addressed from `inet_sock_set_state`
+#[inline(always)]
+fn conn_from_tracepoint(ctx: &TracePointContext, now: u64) -> Result<ConnInfo, i64> {
+    let field = |index: u32| arg_offset(index, DEFAULT_STATE_OFFSETS[index as usize]);
+    let family: u16 = unsafe { ctx.read_at(field(CONFIG_STATE_FAMILY)).map_err(|_| 1i64)? };
+    let mut raddr = [0u8; 16];
+    if family == AF_INET6 {
+        raddr = unsafe {
+            ctx.read_at(field(CONFIG_STATE_DADDR_V6))
+                .map_err(|_| 1i64)?
+        };
+    } else {
+        let v4: [u8; 4] = unsafe { ctx.read_at(field(CONFIG_STATE_DADDR)).map_err(|_| 1i64)? };
+        raddr[..4].copy_from_slice(&v4);
+    }
+    Ok(ConnInfo {
+        start_ns: now,
+        bytes_sent: 0,
+        bytes_received: 0,
+        pid: 0,
+        tid: 0,
+        traced: TRACED_UNKNOWN,
+        retransmits: 0,
+        srtt_us: 0,
+        connecting: 0,
+        family,
+        lport: unsafe { ctx.read_at(field(CONFIG_STATE_SPORT)).map_err(|_| 1i64)? },
+        rport: unsafe { ctx.read_at(field(CONFIG_STATE_DPORT)).map_err(|_| 1i64)? },
+        passive: PASSIVE_NO,
+        raddr,
+        comm: [0u8; TASK_COMM_LEN],
+    })
+}
+
+/// Connection of socket `sk` as used by the current task, recording it
+/// (and its owner) on first use; None if the owner is filtered out
+#[inline(always)]
+fn owned_conn(sk: u64) -> Option<*mut ConnInfo> {
+    if sk == 0 {
+        return None;
+    }
+    if CONNECTIONS.get_ptr_mut(&sk).is_none() {
+        // Opened before tracing started
+        let conn = conn_from_sock(sk, unsafe { bpf_ktime_get_ns() });
+        if conn.family != AF_INET && conn.family != AF_INET6 {
+            return None;
+        }
+        let _ = CONNECTIONS.insert(&sk, &conn, 0);
+        let _ = CONN_TUPLES.insert(&tuple_of(&conn), &sk, 0);
+    }
+    let ptr = CONNECTIONS.get_ptr_mut(&sk)?;
+    let conn = unsafe { &mut *ptr };
+    if conn.traced == TRACED_UNKNOWN {
+        if should_trace() {
+            conn.traced = TRACED_YES;
+            set_owner(conn);
+        } else {
+            conn.traced = TRACED_NO;
+        }
+    }
+    (conn.traced == TRACED_YES).then_some(ptr)
+}
+
+/// Add `n` to a connection's byte counter. A socket can be written and
+/// read on several CPUs at once.
+#[inline(always)]
+fn add_bytes(counter: *mut u64, n: u64) {
+    let counter = unsafe { &*(counter as *const AtomicU64) };
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+#[kprobe]
+pub fn tcp_sendmsg(ctx: ProbeContext) -> u32 {
+    let sk: u64 = ctx.arg(0).unwrap_or(0);
+    if sk != 0 {
+        let tid = bpf_get_current_pid_tgid() as u32;
+        let _ = SENDMSG_CALLS.insert(&tid, &sk, 0);
+    }
+    0
+}
+
+/// Counts what `tcp_sendmsg` queued, which can be less than requested
+#[kretprobe]
+pub fn tcp_sendmsg_return(ctx: RetProbeContext) -> u32 {
+    let tid = bpf_get_current_pid_tgid() as u32;
+    let Some(&sk) = (unsafe { SENDMSG_CALLS.get(&tid) }) else {
+        return 0;
+    };
+    let _ = SENDMSG_CALLS.remove(&tid);
+    let sent: i32 = ctx.ret().unwrap_or(0);
+    if sent <= 0 {
+        return 0;
+    }
+    if let Some(conn) = owned_conn(sk) {
+        add_bytes(
+            unsafe { core::ptr::addr_of_mut!((*conn).bytes_sent) },
+            sent as u64,
+        );
+    }
+    0
+}
+
+#[kprobe]
+pub fn tcp_cleanup_rbuf(ctx: ProbeContext) -> u32 {
+    let sk: u64 = ctx.arg(0).unwrap_or(0);
+    let copied: i32 = ctx.arg(1).unwrap_or(0);
+    if copied <= 0 {
+        return 0;
+    }
+    if let Some(conn) = owned_conn(sk) {
+        add_bytes(
+            unsafe { core::ptr::addr_of_mut!((*conn).bytes_received) },
+            copied as u64,
+        );
+    }
+    0
+}
+
+/// Retransmit or reset on socket `skaddr`, in whatever context the kernel
+/// sends or receives it
+#[inline(always)]
+fn socket_event(ctx: &TracePointContext, skaddr: u64, kind: u32) {
+    if skaddr == 0 {
+        return;
+    }
+    let now = unsafe { bpf_ktime_get_ns() };
+    match CONNECTIONS.get_ptr_mut(&skaddr) {
+        Some(conn) => {
+            let conn = unsafe { &mut *conn };
+            if kind == KIND_RETRANSMIT {
+                conn.retransmits += 1;
+            }
+            if reported(conn) {
+                emit(ctx, conn, kind, now);
+            }
+        }
+        None if !filtering() => {
+            let conn = conn_from_sock(skaddr, now);
+            if conn.family == AF_INET || conn.family == AF_INET6 {
+                emit(ctx, &conn, kind, now);
+            }
+        }
+        None => {}
+    }
+}
+
+#[tracepoint(name = "tcp_retransmit_skb", category = "tcp")]
+pub fn tcp_retransmit_skb(ctx: TracePointContext) -> i64 {
+    let off = arg_offset(CONFIG_RETRANSMIT_SKADDR, DEFAULT_RETRANSMIT_SKADDR_OFFSET);
+    let skaddr: u64 = unsafe { ctx.read_at(off).unwrap_or(0) };
+    socket_event(&ctx, skaddr, KIND_RETRANSMIT);
+    0
+}
+
+#[tracepoint(name = "tcp_send_reset", category = "tcp")]
+pub fn tcp_send_reset(ctx: TracePointContext) -> i64 {
+    let off = arg_offset(CONFIG_SEND_RESET_SKADDR, DEFAULT_SEND_RESET_SKADDR_OFFSET);
+    // No socket for resets answering segments to closed ports
+    let skaddr: u64 = unsafe { ctx.read_at(off).unwrap_or(0) };
+    socket_event(&ctx, skaddr, KIND_RESET_SENT);
+    0
+}
+
+#[tracepoint(name = "tcp_receive_reset", category = "tcp")]
+pub fn tcp_receive_reset(ctx: TracePointContext) -> i64 {
+    let off = arg_offset(
+        CONFIG_RECEIVE_RESET_SKADDR,
+        DEFAULT_RECEIVE_RESET_SKADDR_OFFSET,
+    );
+    let skaddr: u64 = unsafe { ctx.read_at(off).unwrap_or(0) };
+    socket_event(&ctx, skaddr, KIND_RESET_RECEIVED);
+    0
+}
+
+#[tracepoint(name = "tcp_probe", category = "tcp")]
+pub fn tcp_probe(ctx: TracePointContext) -> i64 {
+    try_tcp_probe(&ctx).unwrap_or_default()
+}
+
+fn try_tcp_probe(ctx: &TracePointContext) -> Result<i64, i64> {
+    let field = |index: u32| {
+        let i = (index - CONFIG_PROBE_DADDR) as usize;
+        arg_offset(index, DEFAULT_PROBE_OFFSETS[i])
+    };
+    let family: u16 = unsafe { ctx.read_at(field(CONFIG_PROBE_FAMILY)).map_err(|_| 1i64)? };
+    // `daddr` is a sockaddr_in (address at 4) or sockaddr_in6 (at 8)
+    let daddr = field(CONFIG_PROBE_DADDR);
+    let mut raddr = [0u8; 16];
+    if family == AF_INET6 {
+        raddr = unsafe { ctx.read_at(daddr + 8).map_err(|_| 1i64)? };
+    } else {
+        let v4: [u8; 4] = unsafe { ctx.read_at(daddr + 4).map_err(|_| 1i64)? };
+        raddr[..4].copy_from_slice(&v4);
+    }
+    let tuple = ConnTuple {
+        family,
+        lport: unsafe { ctx.read_at(field(CONFIG_PROBE_SPORT)).map_err(|_| 1i64)? },
+        rport: unsafe { ctx.read_at(field(CONFIG_PROBE_DPORT)).map_err(|_| 1i64)? },
+        _pad: 0,
+        raddr,
+    };
+    let skaddr = match unsafe { CONN_TUPLES.get(&tuple) } {
+        Some(&s) => s,
+        None => return Ok(0),
+    };
+    if let Some(conn) = CONNECTIONS.get_ptr_mut(&skaddr) {
+        let srtt: u32 = unsafe { ctx.read_at(field(CONFIG_PROBE_SRTT)).map_err(|_| 1i64)? };
+        unsafe { (*conn).srtt_us = srtt };
+    }
+    Ok(0)
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
@@ -0,0 +1,658 @@
+//! Record and replay of raw event captures
+//!
+//! A capture holds what the eBPF side produced in a run, before any
+//! symbolization: CPU samples, lock and syscall events with their raw
+//! instruction pointers, plus the executable mappings of every process they
+//! came from. Replaying it rebuilds the profiles offline, with other
+//! symbolization, filters or outputs, and can push the events to an
+//! aggregator later.
+//!
+//! # Format
+//!
+//! The `APCAPTUR` magic and the capture version (little-endian u32), then
+//! records of a one-byte tag, a little-endian u32 length and the payload:
+//!
+//! - info: JSON [`CaptureInfo`], written at start and again when the run
+//!   ends; the last one wins
+//! - events: a wire protocol [`Message`], so events keep the protocol's
+//!   schema versioning and captures of older agents stay readable
+//! - mappings: bincode [`ProcessMappings`] of one process
+//!
+//! A capture cut short (the agent was killed) is read up to its last whole
+//! record. Replay streams it: the info and mappings are read first, skipping
+//! over the events, then the events are read back a record at a time.
+//!
+//! Only CPU samples, lock and syscall events are recorded, which is what
+//! replay rebuilds profiles from.
+
+use anyhow::{Context, Result};
+use aperture_shared::protocol::wire::Message;
+use aperture_shared::types::events::ProfileEvent;
+use aperture_shared::utils::arch::Arch;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::collector::mount_ns::ExecMapping;
+use crate::collector::process::ProcessTable;
+use crate::session::ProfileBundle;
+
+/// Version of the capture container (records and their tags)
+pub const CAPTURE_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"APCAPTUR";
+
+const TAG_INFO: u8 = 1;
+const TAG_EVENTS: u8 = 2;
+const TAG_MAPPINGS: u8 = 3;
+
+/// Events per events record, bounding the memory a record takes to decode
+const EVENTS_PER_RECORD: usize = 16 * 1024;
+
+/// What was recorded, and when
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureInfo {
+    /// Host the capture was recorded on (agent ID of replay pushes)
+    pub hostname: String,
+    /// Profiled process, if one was
+    pub target_pid: Option<i32>,
+    /// CPU sampling period
+    pub sample_period_ns: u64,
+    pub start_time: u64,
+    /// 0 until the run ended
+    pub end_time: u64,
+}
+
+/// Executable mappings of a recorded process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessMappings {
+    pub pid: i32,
+    pub mappings: Vec<ExecMapping>,
+}
+
+/// Writes a capture while profilers run; shared by the profilers of a run
+pub struct CaptureWriter {
+    inner: Mutex<WriterInner>,
+}
+
+struct WriterInner {
+    file: BufWriter<File>,
+    info: CaptureInfo,
+    /// Processes events were recorded from, whose mappings are written at
+    /// the end
+    pids: BTreeSet<i32>,
+    sequence: u64,
+}
+
+impl CaptureWriter {
+    /// Create (or truncate) a capture at `path`
+    pub fn create(path: &Path, info: CaptureInfo) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create capture {}", path.display()))?;
+        let mut inner = WriterInner {
+            file: BufWriter::new(file),
+            info,
+            pids: BTreeSet::new(),
+            sequence: 0,
+        };
+        inner.file.write_all(MAGIC)?;
+        inner.file.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        let info = serde_json::to_vec(&inner.info)?;
+        write_record(&mut inner.file, TAG_INFO, &info)?;
+        info!("Recording raw events to {}", path.display());
+        Ok(Self {
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Append unsymbolized events
+    pub fn write_events(&self, events: Vec<ProfileEvent>) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.pids.extend(events.iter().map(ProfileEvent::pid));
+        let mut events = events.into_iter().peekable();
+        while events.peek().is_some() {
+            let chunk: Vec<ProfileEvent> = events.by_ref().take(EVENTS_PER_RECORD).collect();
+            inner.sequence += 1;
+            let payload = Message::new(inner.sequence, chunk).to_bytes()?;
+            write_record(&mut inner.file, TAG_EVENTS, &payload)?;
+        }
+        Ok(())
+    }
+
+    /// Write the mappings of every recorded process and the end time. Taken
+    /// from `table` when it tracked the process, from `/proc` otherwise.
+    pub fn finish(&self, table: Option<&ProcessTable>) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let live = ProcessTable::new();
+        let pids: Vec<i32> = inner.pids.iter().copied().collect();
+        let mut recorded = 0;
+        for pid in pids {
+            let mappings = table
+                .and_then(|t| t.mappings(pid))
+                .or_else(|| live.snapshot(pid).then(|| live.mappings(pid)).flatten());
+            let Some(mappings) = mappings else {
+                continue;
+            };
+            let payload = bincode::serialize(&ProcessMappings { pid, mappings })?;
+            write_record(&mut inner.file, TAG_MAPPINGS, &payload)?;
+            recorded += 1;
+        }
+        inner.info.end_time = aperture_shared::utils::time::system_time_nanos();
+        let info = serde_json::to_vec(&inner.info)?;
+        write_record(&mut inner.file, TAG_INFO, &info)?;
+        inner.file.flush()?;
+        info!(
+            "Capture: {} event records, mappings of {}/{} processes",
+            inner.sequence,
+            recorded,
+            inner.pids.len()
+        );
+        Ok(())
+    }
+}
+
+fn write_record(file: &mut impl Write, tag: u8, payload: &[u8]) -> Result<()> {
+    let len = u32::try_from(payload.len()).context("Capture record too large")?;
+    file.write_all(&[tag])?;
+    file.write_all(&len.to_le_bytes())?;
+    file.write_all(payload)?;
+    Ok(())
+}
+
+/// Reads the records of a capture one at a time
+struct RecordReader {
+    file: BufReader<File>,
+}
+
+impl RecordReader {
+    /// Check the header of the capture in `file`
+    fn new(file: File) -> Result<Self> {
+        let mut file = BufReader::new(file);
+        let mut header = [0u8; MAGIC.len() + 4];
+        if read_up_to(&mut file, &mut header)? < header.len() || &header[..MAGIC.len()] != MAGIC {
+            anyhow::bail!("Not an aperture capture");
+        }
+        let version = u32::from_le_bytes(header[MAGIC.len()..].try_into().unwrap());
+        if version > CAPTURE_VERSION {
+            anyhow::bail!(
+                "Capture version {} is newer than supported ({})",
+                version,
+                CAPTURE_VERSION
+            );
+        }
+        Ok(Self { file })
+    }
+
+    /// Tag and payload of the next record, the payload only if `wanted` for
+    /// its tag (others are skipped over). None at the end of the capture or
+    /// at a partial last record.
+    fn next(&mut self, wanted: impl Fn(u8) -> bool) -> Result<Option<(u8, Option<Vec<u8>>)>> {
+        let mut head = [0u8; 5];
+        match read_up_to(&mut self.file, &mut head)? {
+            0 => return Ok(None),
+            5 => {}
+            _ => {
+                warn!("Capture ends in a partial record");
+                return Ok(None);
+            }
+        }
+        let tag = head[0];
+        let len = u64::from(u32::from_le_bytes(head[1..].try_into().unwrap()));
+        let mut record = self.file.by_ref().take(len);
+        let (read, payload) = if wanted(tag) {
+            let mut payload = Vec::new();
+            let read = record.read_to_end(&mut payload)? as u64;
+            (read, Some(payload))
+        } else {
+            (std::io::copy(&mut record, &mut std::io::sink())?, None)
+        };
+        if read < len {
+            warn!("Capture ends in a partial record");
+            return Ok(None);
+        }
+        Ok(Some((tag, payload)))
+    }
+}
+
+fn open_capture(path: &Path) -> Result<File> {
+    File::open(path).with_context(|| format!("Failed to read capture {}", path.display()))
+}
+
+/// Fill `buf` as far as `reader` goes, returning the bytes read
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// A capture read back: its info and process mappings. The events stay in
+/// the file and are streamed with [`Capture::event_batches`].
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub info: CaptureInfo,
+    /// Architecture of the recording agent
+    pub arch: Arch,
+    pub processes: Vec<ProcessMappings>,
+    path: PathBuf,
+}
+
+impl Capture {
+    /// Read the info and mappings of the capture at `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = open_capture(path)?;
+        Self::read_records(file, path)
+            .with_context(|| format!("Invalid capture {}", path.display()))
+    }
+
+    fn read_records(file: File, path: &Path) -> Result<Self> {
+        let mut reader = RecordReader::new(file)?;
+        let mut capture = Capture {
+            info: CaptureInfo::default(),
+            arch: Arch::default(),
+            processes: Vec::new(),
+            path: path.to_path_buf(),
+        };
+        // The arch is that of the first events record; the rest are skipped
+        let mut arch = None;
+        while let Some((tag, payload)) = reader.next(|tag| tag != TAG_EVENTS || arch.is_none())? {
+            let Some(payload) = payload else {
+                continue;
+            };
+            match tag {
+                TAG_INFO => capture.info = serde_json::from_slice(&payload)?,
+                TAG_EVENTS => arch = Some(Message::from_bytes(&payload)?.arch),
+                TAG_MAPPINGS => capture.processes.push(bincode::deserialize(&payload)?),
+                // Records of later versions
+                _ => {}
+            }
+        }
+        capture.arch = arch.unwrap_or_default();
+        Ok(capture)
+    }
+
+    /// Recorded events, one events record at a time, only those of `pid` if
+    /// given
+    pub fn event_batches(
+        &self,
+        pid: Option<i32>,
+    ) -> Result<impl Iterator<Item = Result<Vec<ProfileEvent>>>> {
+        let mut reader = RecordReader::new(open_capture(&self.path)?)?;
+        Ok(std::iter::from_fn(move || loop {
+            let payload = match reader.next(|tag| tag == TAG_EVENTS) {
+                Ok(Some((_, Some(payload)))) => payload,
+                Ok(Some((_, None))) => continue,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            return Some(Message::from_bytes(&payload).map(|message| {
+                message
+                    .events
+                    .into_iter()
+                    .filter(|event| pid.is_none() || Some(event.pid()) == pid)
+                    .collect()
+            }));
+        }))
+    }
+
+    /// Process table with the recorded mappings, their files opened at the
+    /// recorded paths on this host
+    pub fn process_table(&self) -> ProcessTable {
+        let table = ProcessTable::new();
+        for process in &self.processes {
+            let restored = table.restore(process.pid, process.mappings.clone());
+            if restored < process.mappings.len() {
+                warn!(
+                    "PID {}: {} of {} mapped files not found on this host",
+                    process.pid,
+                    process.mappings.len() - restored,
+                    process.mappings.len()
+                );
+            }
+        }
+        table
+    }
+
+    /// Rebuild the symbolized profiles of the recorded events
+    pub fn profiles(&self, options: &ReplayOptions) -> Result<ProfileBundle> {
+        use crate::collector::cpu::CpuCollector;
+        use crate::collector::lock::LockCollector;
+        use crate::collector::normalize::FrameNormalizer;
+        use crate::collector::syscall::SyscallCollector;
+
+        let (mut cpu, mut lock, mut syscall) = (None, None, None);
+        // Syscall ids are numbered by the recording host's architecture
+        let syscall_collector = || {
+            let mut collector = SyscallCollector::new();
+            collector.set_arch(self.arch);
+            collector
+        };
+        for batch in self.event_batches(options.pid)? {
+            for event in batch? {
+                match event {
+                    ProfileEvent::CpuSample(sample) => cpu
+                        .get_or_insert_with(|| CpuCollector::new(self.info.sample_period_ns))
+                        .add_sample(sample),
+                    ProfileEvent::Lock(event) => {
+                        lock.get_or_insert_with(LockCollector::new).add_event(event)
+                    }
+                    ProfileEvent::Syscall(event) => syscall
+                        .get_or_insert_with(syscall_collector)
+                        .add_event(event),
+                    ProfileEvent::SyscallSummary(summary) => syscall
+                        .get_or_insert_with(syscall_collector)
+                        .add_summary(summary),
+                    _ => {}
+                }
+            }
+        }
+
+        let symbolizer = crate::ProfileSymbolizer {
+            process_table: Some(Arc::new(self.process_table())),
+            disk_cache: None,
+            normalizer: Arc::new(FrameNormalizer::for_rules_path(
+                options.normalize_rules.as_deref(),
+            )?),
+            target_pid: options.pid.or(self.info.target_pid),
+            offline: true,
+        };
+        let (start_time, end_time) = (self.info.start_time, self.info.end_time);
+        let mut bundle = ProfileBundle::default();
+        if let Some(cpu) = cpu {
+            let mut profile = symbolizer.cpu_profile(&cpu)?;
+            (profile.start_time, profile.end_time) = (start_time, end_time);
+            bundle.cpu = Some(profile);
+        }
+        if let Some(lock) = lock {
+            let mut profile = symbolizer.lock_profile(&lock)?;
+            (profile.start_time, profile.end_time) = (start_time, end_time);
+            bundle.lock = Some(profile);
+        }
+        if let Some(syscall) = syscall {
+            let mut profile = symbolizer.syscall_profile(&syscall)?;
+            (profile.start_time, profile.end_time) = (start_time, end_time);
+            bundle.syscall = Some(profile);
+        }
+        Ok(bundle)
+    }
+}
+
+/// How a capture is replayed
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    /// Only replay the events of this process
+    pub pid: Option<i32>,
+    /// JSON file of frame rewrite/collapse rules applied after symbolization
+    pub normalize_rules: Option<PathBuf>,
+    /// Flamegraph or report path; with several profiles, each gets a
+    /// `.cpu.svg`, `.lock.svg` or `.syscall.txt` suffix as in mode all
+    pub output_path: Option<String>,
+    /// JSON output path, suffixed the same way
+    pub json_output: Option<String>,
+    /// Aggregator the events are pushed to, symbolized from the capture
+    pub aggregator_url: Option<String>,
+}
+
+/// Replay the capture at `path`: rebuild its profiles, write the outputs
+/// and push the events as configured in `options`
+pub async fn replay(path: &Path, options: &ReplayOptions) -> Result<ProfileBundle> {
+    let capture = Capture::read(path)?;
+    info!(
+        "Replaying the capture of {} processes recorded on {}",
+        capture.processes.len(),
+        capture.info.hostname
+    );
+    let bundle = capture.profiles(options)?;
+
+    if let Some(output_path) = &options.output_path {
+        let json_output = options.json_output.as_deref();
+        let several = [
+            bundle.cpu.is_some(),
+            bundle.lock.is_some(),
+            bundle.syscall.is_some(),
+        ]
+        .iter()
+        .filter(|&&some| some)
+        .count()
+            > 1;
+        let suffixed = |path: &str, kind: &str, ext: &str| match several {
+            true => format!("{}.{}.{}", path, kind, ext),
+            false => path.to_string(),
+        };
+        if let Some(profile) = &bundle.cpu {
+            crate::write_cpu_outputs(
+                profile,
+                &suffixed(output_path, "cpu", "svg"),
+                json_output.map(|j| suffixed(j, "cpu", "json")).as_deref(),
+            )?;
+        }
+        if let Some(profile) = &bundle.lock {
+            crate::write_lock_outputs(
+                profile,
+                &suffixed(output_path, "lock", "svg"),
+                json_output.map(|j| suffixed(j, "lock", "json")).as_deref(),
+            )?;
+        }
+        if let Some(profile) = &bundle.syscall {
+            crate::write_syscall_outputs(
+                profile,
+                &suffixed(output_path, "syscall", "txt"),
+                json_output
+                    .map(|j| suffixed(j, "syscall", "json"))
+                    .as_deref(),
+            )?;
+        }
+    }
+
+    if let Some(url) = &options.aggregator_url {
+        push_capture(&capture, options, url).await?;
+    }
+    Ok(bundle)
+}
+
+/// Push the events of a capture to an aggregator under the recording host's
+/// agent ID
+async fn push_capture(capture: &Capture, options: &ReplayOptions, url: &str) -> Result<()> {
+    use crate::collector::normalize::FrameNormalizer;
+    use crate::collector::symbols::SymbolCache;
+
+    let mut sym_cache = SymbolCache::new()
+        .with_process_table(Some(Arc::new(capture.process_table())))
+        .with_normalizer(Arc::new(FrameNormalizer::for_rules_path(
+            options.normalize_rules.as_deref(),
+        )?))
+        .offline();
+    let mut client = None;
+    let mut total = 0;
+    for chunk in capture.event_batches(options.pid)? {
+        let mut chunk = chunk?;
+        if chunk.is_empty() {
+            continue;
+        }
+        total += chunk.len();
+        sym_cache.symbolize_events(&mut chunk, None);
+        crate::push_to_aggregator_as(
+            &mut client,
+            url,
+            &capture.info.hostname,
+            capture.arch,
+            chunk,
+        )
+        .await
+        .context("Failed to push capture")?;
+    }
+    info!("Pushed {} recorded events to {}", total, url);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aperture_shared::types::events::{CpuSample, SyscallSummaryEvent};
+
+    fn sample(pid: i32, user_stack: Vec<u64>) -> ProfileEvent {
+        ProfileEvent::CpuSample(CpuSample {
+            timestamp: 1,
+            pid,
+            tid: pid,
+            cpu_id: 0,
+            user_stack,
+            kernel_stack: vec![],
+            comm: "app".to_string(),
+            user_stack_symbols: vec![],
+            kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
+        })
+    }
+
+    #[test]
+    fn test_capture_roundtrip_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.apcap");
+        let own = std::process::id() as i32;
+        let ip = test_capture_roundtrip_and_replay as *const () as u64;
+
+        let writer = CaptureWriter::create(
+            &path,
+            CaptureInfo {
+                hostname: "host-a".to_string(),
+                target_pid: None,
+                sample_period_ns: 10_000_000,
+                start_time: 100,
+                end_time: 0,
+            },
+        )
+        .unwrap();
+        writer
+            .write_events(vec![sample(own, vec![ip]), sample(own, vec![ip])])
+            .unwrap();
+        writer.write_events(vec![summary(0)]).unwrap();
+        writer.finish(None).unwrap();
+
+        let capture = Capture::read(&path).unwrap();
+        assert_eq!(capture.info.hostname, "host-a");
+        assert!(capture.info.end_time >= capture.info.start_time);
+        assert_eq!(capture.arch, Arch::host());
+        let batches: Vec<_> = capture
+            .event_batches(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        // Our own mappings were read from /proc; PID 7 is not ours to read
+        assert!(capture.processes.iter().any(|p| p.pid == own));
+
+        let bundle = capture.profiles(&ReplayOptions::default()).unwrap();
+        let cpu = bundle.cpu.unwrap();
+        assert_eq!(cpu.total_samples, 2);
+        assert_eq!(cpu.start_time, 100);
+        assert_eq!(bundle.syscall.unwrap().total_events, 3);
+        assert!(bundle.lock.is_none());
+        // Symbolized from the recorded mappings alone
+        let frame = &cpu.samples.keys().next().unwrap().frames[0];
+        assert!(frame
+            .function
+            .as_deref()
+            .is_some_and(|f| f.contains("test_capture_roundtrip_and_replay")));
+
+        let only_syscalls = capture
+            .profiles(&ReplayOptions {
+                pid: Some(7),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(only_syscalls.cpu.is_none());
+    }
+
+    #[test]
+    fn test_truncated_and_foreign_captures() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.apcap");
+        let writer = CaptureWriter::create(&path, CaptureInfo::default()).unwrap();
+        writer.write_events(vec![sample(1, vec![0x1000])]).unwrap();
+        writer.finish(None).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        let read = |bytes: &[u8]| {
+            let path = dir.path().join("other.apcap");
+            std::fs::write(&path, bytes).unwrap();
+            Capture::read(&path).map(|capture| {
+                let batches = capture.event_batches(None).unwrap();
+                batches.map(|batch| batch.unwrap().len()).sum::<usize>()
+            })
+        };
+        assert_eq!(read(&bytes[..bytes.len() - 3]).unwrap(), 1);
+
+        assert!(read(b"not a capture").is_err());
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(CAPTURE_VERSION + 1).to_le_bytes());
+        assert!(read(&newer).is_err());
+    }
+
+    /// Capture of `events` as an agent on `arch` records them
+    fn write_capture(path: &Path, arch: Arch, events: Vec<ProfileEvent>) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(MAGIC).unwrap();
+        file.write_all(&CAPTURE_VERSION.to_le_bytes()).unwrap();
+        let payload = Message::with_arch(1, events, arch).to_bytes().unwrap();
+        write_record(&mut file, TAG_EVENTS, &payload).unwrap();
+    }
+
+    fn summary(syscall_id: u32) -> ProfileEvent {
+        ProfileEvent::SyscallSummary(SyscallSummaryEvent {
+            timestamp: 2,
+            pid: 7,
+            syscall_id,
+            count: 3,
+            total_duration_ns: 300,
+            max_duration_ns: 200,
+            min_duration_ns: 50,
+            error_count: 0,
+            latency_histogram: vec![0; 32],
+            sample_every: 1,
+        })
+    }
+
+    #[test]
+    fn test_capture_keeps_recorded_arch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("arm.apcap");
+        // Recorded on an aarch64 host, replayed on this one
+        write_capture(&path, Arch::Aarch64, vec![summary(56), sample(7, vec![])]);
+
+        let capture = Capture::read(&path).unwrap();
+        assert_eq!(capture.arch, Arch::Aarch64);
+        let events: Vec<ProfileEvent> = capture
+            .event_batches(Some(7))
+            .unwrap()
+            .flat_map(Result::unwrap)
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(capture
+            .event_batches(Some(8))
+            .unwrap()
+            .all(|batch| batch.unwrap().is_empty()));
+    }
+
+    #[test]
+    fn test_replay_names_syscalls_by_recorded_arch() {
+        // 56 is openat on aarch64, clone on x86_64
+        let dir = tempfile::tempdir().unwrap();
+        for (arch, name) in [(Arch::Aarch64, "openat"), (Arch::X86_64, "clone")] {
+            let path = dir.path().join("run.apcap");
+            write_capture(&path, arch, vec![summary(56)]);
+            let capture = Capture::read(&path).unwrap();
+            let bundle = capture.profiles(&ReplayOptions::default()).unwrap();
+            assert_eq!(bundle.syscall.unwrap().syscalls[&56].name, name);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Block I/O event collector
+//!
+//! Collects completed block device requests from the block I/O tracer and
+//! builds per-device, per-operation latency profiles. Device numbers are
+//! resolved to names (`nvme0n1`, `sda`) through sysfs.
+
+use anyhow::Result;
+use aperture_shared::types::events::{BlockIoEvent, BlockIoOp, ProfileEvent};
+use aperture_shared::types::profile::BlockIoProfile;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Length of the tracepoints' `rwbs` field (must match RWBS_LEN)
+pub const RWBS_LEN: usize = 8;
+
+/// Raw block I/O event from eBPF (must match agent-ebpf/src/block_io_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockIoEventBpf {
+    pub timestamp: u64,
+    pub latency_ns: u64,
+    pub sector: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub dev: u32,
+    pub bytes: u32,
+    pub queue_depth: u32,
+    pub error: i32,
+    pub rwbs: [u8; RWBS_LEN],
+    pub comm: [u8; 16],
+}
+
+// Implement traits for reading from perf buffer
+unsafe impl aya::Pod for BlockIoEventBpf {}
+
+/// Block I/O event collector
+#[derive(Debug)]
+pub struct BlockIoCollector {
+    /// Collected events
+    events: Vec<BlockIoEvent>,
+
+    /// Start time
+    start_time: u64,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+
+    /// Device number -> name, None when sysfs doesn't know it
+    devices: HashMap<u32, Option<String>>,
+}
+
+impl Default for BlockIoCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockIoCollector {
+    /// Create a new block I/O collector
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            start_time: aperture_shared::utils::time::system_time_nanos(),
+            push_cursor: 0,
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Add an event to the collector
+    pub fn add_event(&mut self, event: BlockIoEvent) {
+        self.events.push(event);
+    }
+
+    /// Process a raw eBPF event and convert to BlockIoEvent
+    pub fn process_event(&mut self, event: &BlockIoEventBpf) -> Result<()> {
+        let comm = std::str::from_utf8(&event.comm)
+            .unwrap_or("<unknown>")
+            .trim_end_matches('\0')
+            .to_string();
+        let rwbs = std::str::from_utf8(&event.rwbs)
+            .unwrap_or("")
+            .trim_end_matches('\0');
+        let device = self
+            .devices
+            .entry(event.dev)
+            .or_insert_with(|| device_name(event.dev))
+            .clone();
+
+        self.add_event(BlockIoEvent {
+            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+            pid: event.pid as i32,
+            tid: event.tid as i32,
+            comm,
+            dev: event.dev,
+            device,
+            op: BlockIoOp::from_rwbs(rwbs),
+            sector: event.sector,
+            bytes: event.bytes,
+            latency_ns: event.latency_ns,
+            queue_depth: event.queue_depth,
+            error: event.error,
+        });
+        Ok(())
+    }
+
+    /// Build aggregated profile from collected events
+    pub fn build_profile(&self) -> Result<BlockIoProfile> {
+        info!(
+            "Building block I/O profile from {} events",
+            self.events.len()
+        );
+
+        let mut profile = BlockIoProfile::new(self.start_time);
+        profile.end_time = aperture_shared::utils::time::system_time_nanos();
+
+        for event in &self.events {
+            profile.add_event(event);
+        }
+
+        info!(
+            "Block I/O profile built: {} total events, {} devices, {} processes",
+            profile.total_events,
+            profile.devices.len(),
+            profile.processes.len()
+        );
+
+        Ok(profile)
+    }
+
+    /// Return events accumulated since the last call and advance the cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let events: Vec<ProfileEvent> = self.events[self.push_cursor..]
+            .iter()
+            .cloned()
+            .map(ProfileEvent::BlockIo)
+            .collect();
+        self.push_cursor = self.events.len();
+        events
+    }
+}
+
+/// Name of kernel device number `dev` (`major << 20 | minor`), from
+/// `/sys/dev/block/<major>:<minor>/uevent`
+fn device_name(dev: u32) -> Option<String> {
+    let path = format!("/sys/dev/block/{}:{}/uevent", dev >> 20, dev & 0xfffff);
+    parse_devname(&std::fs::read_to_string(path).ok()?)
+}
+
+/// `DEVNAME` of a sysfs `uevent` file
+fn parse_devname(uevent: &str) -> Option<String> {
+    uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("DEVNAME="))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(dev: u32, rwbs: &[u8], bytes: u32, latency_ns: u64) -> BlockIoEventBpf {
+        let mut event = BlockIoEventBpf {
+            timestamp: 0,
+            latency_ns,
+            sector: 2048,
+            pid: 42,
+            tid: 43,
+            dev,
+            bytes,
+            queue_depth: 2,
+            error: 0,
+            rwbs: [0; RWBS_LEN],
+            comm: [0; 16],
+        };
+        event.rwbs[..rwbs.len()].copy_from_slice(rwbs);
+        event.comm[..8].copy_from_slice(b"postgres");
+        event
+    }
+
+    #[test]
+    fn test_parse_devname() {
+        let uevent = "MAJOR=259\nMINOR=0\nDEVNAME=nvme0n1\nDEVTYPE=disk\n";
+        assert_eq!(parse_devname(uevent).as_deref(), Some("nvme0n1"));
+        assert_eq!(parse_devname("MAJOR=8\nMINOR=0\n"), None);
+    }
+
+    #[test]
+    fn test_block_io_collector() {
+        let mut collector = BlockIoCollector::new();
+        // Device numbers with no sysfs entry fall back to major:minor
+        let dev = (4095 << 20) | 7;
+        collector
+            .process_event(&raw(dev, b"WS", 8192, 500_000))
+            .unwrap();
+        collector
+            .process_event(&raw(dev, b"R", 4096, 100_000))
+            .unwrap();
+        collector
+            .process_event(&raw(dev, b"FWS", 4096, 900_000))
+            .unwrap();
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.total_events, 3);
+        let ops = &profile.devices["4095:7"];
+        assert_eq!(ops[&BlockIoOp::Write].count, 2);
+        assert_eq!(ops[&BlockIoOp::Write].bytes, 12288);
+        assert_eq!(ops[&BlockIoOp::Read].max_latency_ns, 100_000);
+        assert_eq!(profile.processes[&42].comm, "postgres");
+
+        assert_eq!(collector.take_pending_events().len(), 3);
+        assert!(collector.take_pending_events().is_empty());
+    }
+}
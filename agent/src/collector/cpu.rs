@@ -6,6 +6,7 @@ use anyhow::Result;
 use aperture_shared::types::events::{CpuSample, ProfileEvent};
 use aperture_shared::types::profile::{Profile, Stack};
 use aya::maps::StackTraceMap;
+use std::collections::{HashMap, HashSet};
 use tracing::{debug, info};
 
 /// Raw sample event from eBPF (must match agent-ebpf/src/cpu_profiler.rs)
@@ -19,6 +20,7 @@ pub struct SampleEvent {
     pub user_stack_id: i32,
     pub kernel_stack_id: i32,
     pub comm: [u8; 16],
+    pub labels: [u8; aperture_labels::LABELS_SIZE],
 }
 
 // Implement traits for reading from perf buffer
@@ -117,6 +119,8 @@ impl CpuCollector {
             comm,
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: aperture_labels::decode(&event.labels).into_iter().collect(),
         };
 
         self.add_sample(sample);
@@ -166,6 +170,19 @@ impl CpuCollector {
         self.samples.len()
     }
 
+    /// User-space IPs grouped by the process they were sampled in, so the
+    /// symbolizer can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut seen: HashMap<i32, HashSet<u64>> = HashMap::new();
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for ev in &self.samples {
+            let seen = seen.entry(ev.pid).or_default();
+            let ips = by_pid.entry(ev.pid).or_default();
+            ips.extend(ev.user_stack.iter().filter(|&&ip| seen.insert(ip)));
+        }
+        by_pid
+    }
+
     /// All events for a final push to the aggregator
     pub fn profile_events(&self) -> Vec<ProfileEvent> {
         self.samples
@@ -216,6 +233,8 @@ mod tests {
             comm: "test".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         }
     }
 
@@ -272,4 +291,15 @@ mod tests {
         assert_eq!(*count, 1);
         assert_eq!(stack.frames.len(), 2);
     }
+
+    #[test]
+    fn test_user_ips_by_pid_dedups_per_process() {
+        let mut collector = CpuCollector::new(10_000_000);
+        collector.add_sample(sample(100, 1, 1, 0, vec![0x2000, 0x1000], vec![]));
+        collector.add_sample(sample(200, 1, 2, 0, vec![0x1000, 0x3000], vec![]));
+        collector.add_sample(sample(300, 2, 3, 0, vec![0x1000], vec![]));
+        let by_pid = collector.user_ips_by_pid();
+        assert_eq!(by_pid[&1], vec![0x2000, 0x1000, 0x3000]);
+        assert_eq!(by_pid[&2], vec![0x1000]);
+    }
 }
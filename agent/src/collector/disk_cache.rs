@@ -0,0 +1,300 @@
+//! Persistent symbol cache
+//!
+//! Resolved user-space frames are stored on disk keyed by `(build_id,
+//! file_offset)`, which identifies code independently of the process and its
+//! ASLR layout. Repeat runs on the same host, and every process mapping the
+//! same library, reuse entries instead of re-parsing DWARF.
+//!
+//! The cache is a single bincode file rewritten atomically on save. When it
+//! grows past its size limit, least recently used entries are evicted.
+
+use anyhow::{Context, Result};
+use aperture_shared::types::events::FrameRef;
+use aperture_shared::types::profile::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// Bumped when the on-disk layout changes; older files are discarded.
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+const CACHE_FILE_NAME: &str = "symbols.bin";
+
+/// Bookkeeping bytes counted per entry on top of its strings
+const ENTRY_OVERHEAD_BYTES: u64 = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedEntry {
+    /// Inline chain, innermost first, ending with the containing function.
+    /// Stored with `ip` zeroed; the caller fills in its own address.
+    frames: Vec<Frame>,
+    /// Unix seconds of the last lookup or insert, for LRU eviction
+    last_used: u64,
+}
+
+impl CachedEntry {
+    fn size_bytes(&self, key: &FrameRef) -> u64 {
+        let strings: usize = self
+            .frames
+            .iter()
+            .map(|f| {
+                f.function.as_ref().map_or(0, String::len)
+                    + f.file.as_ref().map_or(0, String::len)
+                    + f.module.as_ref().map_or(0, String::len)
+            })
+            .sum();
+        (strings + key.build_id.len()) as u64 + ENTRY_OVERHEAD_BYTES
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: Vec<(FrameRef, CachedEntry)>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<FrameRef, CachedEntry>,
+    size_bytes: u64,
+    dirty: bool,
+    hits: u64,
+    misses: u64,
+}
+
+/// On-disk symbol cache shared by all symbolizers of an agent run
+pub struct DiskSymbolCache {
+    path: PathBuf,
+    max_bytes: u64,
+    inner: Mutex<Inner>,
+}
+
+impl DiskSymbolCache {
+    /// Open (or create) the cache in `dir`. An unreadable or outdated cache
+    /// file is discarded rather than failing the run.
+    pub fn open(dir: &Path, max_bytes: u64) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create symbol cache dir {}", dir.display()))?;
+        let path = dir.join(CACHE_FILE_NAME);
+
+        let mut inner = Inner::default();
+        match std::fs::read(&path) {
+            Ok(bytes) => match bincode::deserialize::<CacheFile>(&bytes) {
+                Ok(file) if file.version == CACHE_FORMAT_VERSION => {
+                    for (key, entry) in file.entries {
+                        inner.size_bytes += entry.size_bytes(&key);
+                        inner.entries.insert(key, entry);
+                    }
+                }
+                Ok(file) => info!(
+                    "Discarding symbol cache {} (format v{}, expected v{})",
+                    path.display(),
+                    file.version,
+                    CACHE_FORMAT_VERSION
+                ),
+                Err(e) => warn!(
+                    "Discarding unreadable symbol cache {}: {}",
+                    path.display(),
+                    e
+                ),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Cannot read symbol cache {}: {}", path.display(), e),
+        }
+        info!(
+            "Symbol cache {}: {} entries ({} KiB)",
+            path.display(),
+            inner.entries.len(),
+            inner.size_bytes / 1024
+        );
+
+        Ok(Self {
+            path,
+            max_bytes,
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Look up the inline chain for `key`, with every frame's `ip` set to `ip`.
+    pub fn get(&self, key: &FrameRef, ip: u64) -> Option<Vec<Frame>> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(entry) = inner.entries.get_mut(key) else {
+            inner.misses += 1;
+            return None;
+        };
+        entry.last_used = now_secs();
+        let frames = entry
+            .frames
+            .iter()
+            .map(|f| Frame { ip, ..f.clone() })
+            .collect();
+        inner.hits += 1;
+        Some(frames)
+    }
+
+    /// Store the inline chain (innermost first) resolved for `key`.
+    pub fn insert(&self, key: FrameRef, chain: &[Frame]) {
+        if chain.is_empty() {
+            return;
+        }
+        let entry = CachedEntry {
+            frames: chain.iter().map(|f| Frame { ip: 0, ..f.clone() }).collect(),
+            last_used: now_secs(),
+        };
+        let size = entry.size_bytes(&key);
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(old) = inner.entries.insert(key.clone(), entry) {
+            inner.size_bytes -= old.size_bytes(&key);
+        }
+        inner.size_bytes += size;
+        inner.dirty = true;
+    }
+
+    /// Evict down to the size limit and write the cache if it changed.
+    pub fn save(&self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let evicted = self.evict(&mut inner);
+        if !inner.dirty && evicted == 0 {
+            return Ok(());
+        }
+
+        let file = CacheFile {
+            version: CACHE_FORMAT_VERSION,
+            entries: inner
+                .entries
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        };
+        let bytes = bincode::serialize(&file).context("Failed to encode symbol cache")?;
+        let tmp = self.path.with_extension("bin.tmp");
+        std::fs::write(&tmp, bytes)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        inner.dirty = false;
+
+        info!(
+            "Saved symbol cache: {} entries, {} KiB, {} evicted, hit rate {}/{}",
+            inner.entries.len(),
+            inner.size_bytes / 1024,
+            evicted,
+            inner.hits,
+            inner.hits + inner.misses
+        );
+        Ok(())
+    }
+
+    /// Number of cached entries
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate size of the cached entries in bytes
+    pub fn size_bytes(&self) -> u64 {
+        self.inner.lock().unwrap().size_bytes
+    }
+
+    /// Drop least recently used entries until the cache fits `max_bytes`.
+    fn evict(&self, inner: &mut Inner) -> usize {
+        if inner.size_bytes <= self.max_bytes {
+            return 0;
+        }
+        let mut by_age: Vec<(u64, FrameRef)> = inner
+            .entries
+            .iter()
+            .map(|(k, v)| (v.last_used, k.clone()))
+            .collect();
+        by_age.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut evicted = 0;
+        for (_, key) in by_age {
+            if inner.size_bytes <= self.max_bytes {
+                break;
+            }
+            if let Some(entry) = inner.entries.remove(&key) {
+                inner.size_bytes -= entry.size_bytes(&key);
+                evicted += 1;
+            }
+        }
+        debug!("Evicted {} symbol cache entries", evicted);
+        evicted
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "aperture-disk-cache-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn chain(name: &str) -> Vec<Frame> {
+        vec![Frame {
+            function: Some(name.to_string()),
+            file: Some("src/main.rs".to_string()),
+            line: Some(7),
+            ..Frame::new_unresolved(0x5555_0000_1000)
+        }]
+    }
+
+    #[test]
+    fn test_persists_across_runs() {
+        let dir = temp_dir("persist");
+        let key = FrameRef::new(&[0xab; 20], 0x1234);
+
+        let cache = DiskSymbolCache::open(&dir, 1 << 20).unwrap();
+        assert!(cache.get(&key, 0x1000).is_none());
+        cache.insert(key.clone(), &chain("main"));
+        cache.save().unwrap();
+
+        // Same code at a different (ASLR'd) address in a later run
+        let cache = DiskSymbolCache::open(&dir, 1 << 20).unwrap();
+        let frames = cache.get(&key, 0x7f00_0000_1234).unwrap();
+        assert_eq!(frames[0].ip, 0x7f00_0000_1234);
+        assert_eq!(frames[0].function.as_deref(), Some("main"));
+        assert_eq!(frames[0].line, Some(7));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_evicts_to_size_limit() {
+        let dir = temp_dir("evict");
+        let one = FrameRef::new(&[0x01; 20], 0);
+        let entry_size = CachedEntry {
+            frames: chain("f"),
+            last_used: 0,
+        }
+        .size_bytes(&one);
+
+        let cache = DiskSymbolCache::open(&dir, entry_size * 3).unwrap();
+        for offset in 0..10 {
+            cache.insert(FrameRef::new(&[0x01; 20], offset), &chain("f"));
+        }
+        cache.save().unwrap();
+        assert!(cache.len() <= 3);
+        assert!(cache.size_bytes() <= entry_size * 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
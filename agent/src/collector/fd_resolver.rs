@@ -0,0 +1,177 @@
+//! File descriptor resolution
+//!
+//! Syscall events carry the raw fd argument. It is resolved through
+//! `/proc/PID/fd/N` to a file path, or, for TCP/UDP sockets, to the
+//! connection tuple found by inode in `/proc/PID/net/{tcp,tcp6,udp,udp6}`.
+//! Other fds keep their link name (`pipe:[123]`, unix `socket:[456]`, ...).
+//!
+//! Results are cached per `(pid, fd)`. Successful opens seed the cache with
+//! their path argument so short-lived fds resolve even after they are closed,
+//! and `close` drops the entry so a reused fd number is looked up again.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Entries kept before the cache is reset (bounds memory when tracing
+/// many short-lived processes)
+const MAX_CACHED_FDS: usize = 65536;
+
+const SOCKET_TABLES: [&str; 4] = ["tcp", "tcp6", "udp", "udp6"];
+
+/// Resolves `(pid, fd)` pairs to paths or socket tuples
+#[derive(Debug, Default)]
+pub struct FdResolver {
+    fds: HashMap<(i32, i32), Option<String>>,
+}
+
+impl FdResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What `fd` of `pid` refers to, if it can still be resolved
+    pub fn resolve(&mut self, pid: i32, fd: i32) -> Option<String> {
+        if let Some(target) = self.fds.get(&(pid, fd)) {
+            return target.clone();
+        }
+        let (target, cacheable) = match read_fd_link(pid, fd) {
+            Some(link) => match socket_inode(&link) {
+                Some(inode) => match socket_tuple(pid, inode) {
+                    // An unconnected socket gets its peer later
+                    Some((tuple, connected)) => (Some(tuple), connected),
+                    None => (Some(link), true),
+                },
+                None => (Some(link), true),
+            },
+            // The fd is gone (closed, or the process exited)
+            None => (None, false),
+        };
+        if cacheable {
+            self.insert(pid, fd, target.clone());
+        }
+        target
+    }
+
+    /// Record the path a successful open returned `fd` for
+    pub fn opened(&mut self, pid: i32, fd: i32, path: String) {
+        self.insert(pid, fd, Some(path));
+    }
+
+    /// Forget `fd` after it was closed
+    pub fn closed(&mut self, pid: i32, fd: i32) {
+        self.fds.remove(&(pid, fd));
+    }
+
+    fn insert(&mut self, pid: i32, fd: i32, target: Option<String>) {
+        if self.fds.len() >= MAX_CACHED_FDS {
+            self.fds.clear();
+        }
+        self.fds.insert((pid, fd), target);
+    }
+}
+
+fn read_fd_link(pid: i32, fd: i32) -> Option<String> {
+    std::fs::read_link(format!("/proc/{}/fd/{}", pid, fd))
+        .ok()
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+/// Inode of a `socket:[inode]` fd link
+fn socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+/// `proto local->remote` for the socket with `inode`, and whether it has a peer
+fn socket_tuple(pid: i32, inode: u64) -> Option<(String, bool)> {
+    SOCKET_TABLES.iter().find_map(|proto| {
+        let table = std::fs::read_to_string(format!("/proc/{}/net/{}", pid, proto)).ok()?;
+        let (local, remote) = find_socket(&table, inode)?;
+        let proto = proto.trim_end_matches('6');
+        Some((
+            format!("{} {}->{}", proto, local, remote),
+            remote.port() != 0,
+        ))
+    })
+}
+
+/// Find the local and remote address of `inode` in a `/proc/net/{tcp,udp}[6]` table
+fn find_socket(table: &str, inode: u64) -> Option<(SocketAddr, SocketAddr)> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+        if fields.get(9)?.parse::<u64>().ok()? != inode {
+            return None;
+        }
+        Some((parse_hex_addr(fields[1])?, parse_hex_addr(fields[2])?))
+    })
+}
+
+/// Parse `ADDR:PORT` as printed in `/proc/net/tcp`: the address is the raw
+/// network-order bytes printed as host-order 32-bit words, the port is plain hex.
+fn parse_hex_addr(field: &str) -> Option<SocketAddr> {
+    let (addr, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..addr.len()).step_by(8) {
+        let word = u32::from_str_radix(addr.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn test_find_socket_in_table() {
+        let table = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1538 00000000:0000 0A 00000000:00000000 00:00000000 00000000   999        0 1111 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:1538 0100007F:9C40 01 00000000:00000000 00:00000000 00000000   999        0 2222 1 0000000000000000 20 4 30 10 -1
+";
+        let (local, remote) = find_socket(table, 2222).unwrap();
+        assert_eq!(local.to_string(), "127.0.0.1:5432");
+        assert_eq!(remote.to_string(), "127.0.0.1:40000");
+        assert!(find_socket(table, 3333).is_none());
+
+        let v6 = parse_hex_addr("00000000000000000000000001000000:0050").unwrap();
+        assert_eq!(v6.to_string(), "[::1]:80");
+        assert_eq!(socket_inode("socket:[2222]"), Some(2222));
+        assert_eq!(socket_inode("pipe:[2222]"), None);
+    }
+
+    #[test]
+    fn test_resolve_own_fds() {
+        let pid = std::process::id() as i32;
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let fd = file.as_file().as_raw_fd();
+
+        let mut resolver = FdResolver::new();
+        let expected = file.path().to_string_lossy().into_owned();
+        assert_eq!(resolver.resolve(pid, fd), Some(expected.clone()));
+
+        // Opens seed the cache, closes drop it
+        resolver.opened(pid, 9999, "/tmp/short-lived".to_string());
+        assert_eq!(
+            resolver.resolve(pid, 9999).as_deref(),
+            Some("/tmp/short-lived")
+        );
+        resolver.closed(pid, 9999);
+        assert_eq!(resolver.resolve(pid, 9999), None);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = resolver.resolve(pid, listener.as_raw_fd()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(target, format!("tcp 127.0.0.1:{}->0.0.0.0:0", port));
+    }
+}
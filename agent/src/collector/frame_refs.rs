@@ -0,0 +1,207 @@
+//! Build ID + file offset capture for deferred symbolization
+//!
+//! In deferred mode the agent does not resolve user-space names. Each user
+//! frame is normalized to the ELF file that contains it (identified by its
+//! GNU build ID) and the offset within that file, and the aggregator resolves
+//! names later against debug info uploaded for the build ID. This keeps
+//! DWARF parsing off production hosts and works for stripped binaries.
+
+use aperture_shared::types::events::{FrameRef, ProfileEvent};
+use aperture_shared::utils::arch::is_kernel_ip;
+use blazesym::normalize::Normalizer;
+use blazesym::Pid;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Per-process cache of normalized user-space frames.
+///
+/// Keyed by `(pid, ip)` because the same virtual address maps to different
+/// files in different processes. Holds no blazesym state so it stays `Send`;
+/// a temporary `Normalizer` is created per batch.
+#[derive(Default)]
+pub struct FrameRefCache {
+    cache: HashMap<(i32, u64), Option<FrameRef>>,
+}
+
+impl FrameRefCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fill `user_stack_refs` / `stack_refs` on each event in place.
+    ///
+    /// Frames that cannot be attributed to an ELF file with a build ID (JIT
+    /// code, anonymous mappings, binaries built without `--build-id`) get
+    /// `None` so the caller can fall back to symbolizing them locally.
+    pub fn attach_refs(&mut self, events: &mut [ProfileEvent]) {
+        let mut pending: HashMap<i32, Vec<u64>> = HashMap::new();
+        for event in events.iter() {
+            let (pid, ips) = match event {
+                ProfileEvent::CpuSample(s) => (s.pid, &s.user_stack),
+                ProfileEvent::Lock(ev) => (ev.pid, &ev.stack_trace),
+                ProfileEvent::Syscall(ev) => (ev.pid, &ev.stack_trace),
+                ProfileEvent::KernelLock(ev) => (ev.pid, &ev.stack_trace),
+                // Wakeup stacks are the waker's
+                ProfileEvent::Sched(ev) => (ev.waker_pid, &ev.stack_trace),
+                ProfileEvent::PageFault(ev) => (ev.pid, &ev.stack_trace),
+                ProfileEvent::Probe(ev) => (ev.pid, &ev.stack_trace),
+                _ => continue,
+            };
+            for &ip in ips {
+                if is_kernel_ip(ip) || self.cache.contains_key(&(pid, ip)) {
+                    continue;
+                }
+                let ips = pending.entry(pid).or_default();
+                if !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+        }
+
+        if !pending.is_empty() {
+            let normalizer = Normalizer::builder().enable_build_ids(true).build();
+            for (pid, ips) in pending {
+                self.normalize_pid(&normalizer, pid, &ips);
+            }
+        }
+
+        for event in events.iter_mut() {
+            match event {
+                ProfileEvent::CpuSample(s) => {
+                    s.user_stack_refs = self.refs_for(s.pid, &s.user_stack);
+                }
+                ProfileEvent::Lock(ev) => {
+                    ev.stack_refs = self.refs_for(ev.pid, &ev.stack_trace);
+                }
+                ProfileEvent::Syscall(ev) => {
+                    ev.stack_refs = self.refs_for(ev.pid, &ev.stack_trace);
+                }
+                ProfileEvent::KernelLock(ev) => {
+                    ev.stack_refs = self.refs_for(ev.pid, &ev.stack_trace);
+                }
+                ProfileEvent::Sched(ev) => {
+                    ev.stack_refs = self.refs_for(ev.waker_pid, &ev.stack_trace);
+                }
+                ProfileEvent::PageFault(ev) => {
+                    ev.stack_refs = self.refs_for(ev.pid, &ev.stack_trace);
+                }
+                ProfileEvent::Probe(ev) => {
+                    ev.stack_refs = self.refs_for(ev.pid, &ev.stack_trace);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `(build_id, file_offset)` of each of `pid`'s user `ips`, normalizing
+    /// addresses not seen before. Used to key the persistent symbol cache.
+    pub fn refs(&mut self, pid: i32, ips: &[u64]) -> Vec<Option<FrameRef>> {
+        let missing: Vec<u64> = ips
+            .iter()
+            .copied()
+            .filter(|&ip| !is_kernel_ip(ip) && !self.cache.contains_key(&(pid, ip)))
+            .collect();
+        if !missing.is_empty() {
+            let normalizer = Normalizer::builder().enable_build_ids(true).build();
+            self.normalize_pid(&normalizer, pid, &missing);
+        }
+        self.refs_for(pid, ips)
+    }
+
+    /// Drop cached frames for processes that have exited.
+    pub fn prune_exited(&mut self) {
+        let mut alive: HashMap<i32, bool> = HashMap::new();
+        self.cache.retain(|(pid, _), _| {
+            *alive
+                .entry(*pid)
+                .or_insert_with(|| Path::new(&format!("/proc/{}", pid)).exists())
+        });
+    }
+
+    /// Number of cached `(pid, ip)` entries
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    fn normalize_pid(&mut self, normalizer: &Normalizer, pid: i32, ips: &[u64]) {
+        let output = match normalizer.normalize_user_addrs(Pid::from(pid as u32), ips) {
+            Ok(output) => output,
+            Err(e) => {
+                debug!(pid, "Failed to normalize {} user IPs: {}", ips.len(), e);
+                for &ip in ips {
+                    self.cache.insert((pid, ip), None);
+                }
+                return;
+            }
+        };
+
+        let mut with_ref = 0usize;
+        for (&ip, &(file_offset, meta_idx)) in ips.iter().zip(output.outputs.iter()) {
+            let frame_ref = output
+                .meta
+                .get(meta_idx)
+                .and_then(|meta| meta.as_elf())
+                .and_then(|elf| elf.build_id.as_ref())
+                .map(|build_id| FrameRef::new(build_id, file_offset));
+            with_ref += usize::from(frame_ref.is_some());
+            self.cache.insert((pid, ip), frame_ref);
+        }
+        debug!(
+            pid,
+            "Normalized {}/{} user IPs to build ID + offset",
+            with_ref,
+            ips.len()
+        );
+    }
+
+    fn refs_for(&self, pid: i32, ips: &[u64]) -> Vec<Option<FrameRef>> {
+        ips.iter()
+            .map(|&ip| self.cache.get(&(pid, ip)).cloned().flatten())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aperture_shared::types::events::CpuSample;
+
+    #[test]
+    fn test_refs_for_own_binary() {
+        // Our own text section lives in an ELF file with a build ID
+        let ip = test_refs_for_own_binary as *const () as u64;
+        let pid = std::process::id() as i32;
+        let mut events = vec![ProfileEvent::CpuSample(CpuSample {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            cpu_id: 0,
+            user_stack: vec![ip, 0xffff_ffff_8100_0000],
+            kernel_stack: vec![],
+            comm: "test".to_string(),
+            user_stack_symbols: vec![],
+            kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
+        })];
+
+        let mut cache = FrameRefCache::new();
+        cache.attach_refs(&mut events);
+
+        let ProfileEvent::CpuSample(s) = &events[0] else {
+            panic!("expected CpuSample");
+        };
+        assert_eq!(s.user_stack_refs.len(), 2);
+        assert!(s.user_stack_refs[1].is_none());
+        if let Some(frame_ref) = &s.user_stack_refs[0] {
+            assert!(!frame_ref.build_id.is_empty());
+            assert!(frame_ref.file_offset > 0);
+        }
+        assert_eq!(cache.len(), 1);
+    }
+}
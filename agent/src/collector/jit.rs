@@ -0,0 +1,669 @@
+//! JIT symbol resolution
+//!
+//! Resolves instruction pointers that fall into anonymous executable mappings
+//! (code emitted by Node.js, JVM, .NET and other JIT runtimes) using the
+//! runtime's perf map (`/tmp/perf-<pid>.map`) and jitdump (`jit-<pid>.dump`)
+//! files. Both files are read through `/proc/<pid>/root` so targets in other
+//! mount namespaces resolve, and both are read incrementally so symbols
+//! emitted during a long session are picked up as the files grow.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Module name attached to frames resolved from JIT metadata
+pub const JIT_MODULE: &str = "[jit]";
+
+/// jitdump file magic ("JiTD" in little-endian)
+const JITDUMP_MAGIC: u32 = 0x4A69_5444;
+
+/// Size of the fixed jitdump file header
+const JITDUMP_HEADER_SIZE: usize = 40;
+
+/// Size of the common record prefix (id, total_size, timestamp)
+const JITDUMP_RECORD_PREFIX: usize = 16;
+
+/// jitdump record types we care about
+const JIT_CODE_LOAD: u32 = 0;
+const JIT_CODE_MOVE: u32 = 1;
+
+/// A single JIT-compiled code range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitSymbol {
+    pub start: u64,
+    pub size: u64,
+    pub name: String,
+}
+
+impl JitSymbol {
+    fn contains(&self, ip: u64) -> bool {
+        ip >= self.start && ip < self.start.saturating_add(self.size.max(1))
+    }
+}
+
+/// Sorted set of JIT symbols for one process.
+///
+/// Runtimes may re-emit code at an address previously used by another method,
+/// so a later entry with the same start address replaces the earlier one.
+#[derive(Debug, Default)]
+pub struct JitSymbolTable {
+    symbols: Vec<JitSymbol>,
+    sorted: bool,
+}
+
+impl JitSymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, symbol: JitSymbol) {
+        self.symbols.push(symbol);
+        self.sorted = false;
+    }
+
+    /// Move a code range (jitdump `JIT_CODE_MOVE`)
+    fn relocate(&mut self, old_start: u64, new_start: u64, size: u64) {
+        if let Some(sym) = self.symbols.iter().rev().find(|s| s.start == old_start) {
+            let name = sym.name.clone();
+            self.insert(JitSymbol {
+                start: new_start,
+                size,
+                name,
+            });
+        }
+    }
+
+    fn ensure_sorted(&mut self) {
+        if self.sorted {
+            return;
+        }
+        // Stable sort keeps insertion order for equal starts; keep the newest.
+        self.symbols.sort_by_key(|s| s.start);
+        let mut deduped: Vec<JitSymbol> = Vec::with_capacity(self.symbols.len());
+        for sym in self.symbols.drain(..) {
+            match deduped.last_mut() {
+                Some(last) if last.start == sym.start => *last = sym,
+                _ => deduped.push(sym),
+            }
+        }
+        self.symbols = deduped;
+        self.sorted = true;
+    }
+
+    /// Find the symbol covering `ip`
+    pub fn lookup(&mut self, ip: u64) -> Option<&JitSymbol> {
+        self.ensure_sorted();
+        let idx = self.symbols.partition_point(|s| s.start <= ip);
+        if idx == 0 {
+            return None;
+        }
+        let sym = &self.symbols[idx - 1];
+        sym.contains(ip).then_some(sym)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+/// Parse a perf map line: `START SIZE symbolname` (hex without `0x`).
+pub fn parse_perf_map_line(line: &str) -> Option<JitSymbol> {
+    let line = line.trim_end();
+    let (start, rest) = line.split_once(char::is_whitespace)?;
+    let (size, name) = rest.trim_start().split_once(char::is_whitespace)?;
+    let start = u64::from_str_radix(start.trim_start_matches("0x"), 16).ok()?;
+    let size = u64::from_str_radix(size.trim_start_matches("0x"), 16).ok()?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(JitSymbol {
+        start,
+        size,
+        name: name.to_string(),
+    })
+}
+
+/// Incremental reader over a growing file. Remembers how far it has consumed
+/// so each refresh only reads newly appended bytes.
+#[derive(Debug)]
+struct GrowingFile {
+    path: PathBuf,
+    offset: u64,
+    pending: Vec<u8>,
+}
+
+impl GrowingFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Open the file and get its length, starting over if it was
+    /// truncated/recreated (runtime restarted). Returns whether it started over.
+    fn open(&mut self) -> Result<(File, u64, bool)> {
+        let file = File::open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        let len = file.metadata()?.len();
+        let truncated = len < self.offset;
+        if truncated {
+            self.offset = 0;
+            self.pending.clear();
+        }
+        Ok((file, len, truncated))
+    }
+
+    /// Append any new bytes to `pending`. Returns the number of bytes read.
+    fn read_new(&mut self) -> Result<usize> {
+        let (file, len, _) = self.open()?;
+        self.read_until(file, len)
+    }
+
+    /// Append the bytes of an opened `file` between the offset and `len`
+    fn read_until(&mut self, mut file: File, len: u64) -> Result<usize> {
+        if len == self.offset {
+            return Ok(0);
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut buf = Vec::with_capacity((len - self.offset) as usize);
+        file.take(len - self.offset).read_to_end(&mut buf)?;
+        self.offset += buf.len() as u64;
+        self.pending.extend_from_slice(&buf);
+        Ok(buf.len())
+    }
+}
+
+/// Incremental perf map reader (`/tmp/perf-<pid>.map`)
+#[derive(Debug)]
+pub struct PerfMapReader {
+    file: GrowingFile,
+}
+
+impl PerfMapReader {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            file: GrowingFile::new(path),
+        }
+    }
+
+    /// Read complete lines appended since the last refresh into `table`.
+    /// Returns the number of new symbols.
+    pub fn refresh(&mut self, table: &mut JitSymbolTable) -> Result<usize> {
+        if self.file.read_new()? == 0 {
+            return Ok(0);
+        }
+        // Only consume up to the last newline; a partially written line stays pending
+        let Some(last_nl) = self.file.pending.iter().rposition(|&b| b == b'\n') else {
+            return Ok(0);
+        };
+        let complete: Vec<u8> = self.file.pending.drain(..=last_nl).collect();
+        let mut added = 0;
+        for line in String::from_utf8_lossy(&complete).lines() {
+            if let Some(sym) = parse_perf_map_line(line) {
+                table.insert(sym);
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+}
+
+/// Incremental jitdump reader (`jit-<pid>.dump`)
+///
+/// Format: <https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jitdump-specification.txt>
+#[derive(Debug)]
+pub struct JitDumpReader {
+    file: GrowingFile,
+    header_parsed: bool,
+}
+
+impl JitDumpReader {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            file: GrowingFile::new(path),
+            header_parsed: false,
+        }
+    }
+
+    /// Parse complete records appended since the last refresh into `table`.
+    /// Returns the number of new symbols.
+    pub fn refresh(&mut self, table: &mut JitSymbolTable) -> Result<usize> {
+        // Check for truncation before deciding whether a header comes first
+        let (file, len, truncated) = self.file.open()?;
+        if truncated || self.file.offset == 0 {
+            self.header_parsed = false;
+        }
+        self.file.read_until(file, len)?;
+        let buf = &self.file.pending;
+        let mut pos = 0usize;
+
+        if !self.header_parsed {
+            if buf.len() < JITDUMP_HEADER_SIZE {
+                return Ok(0);
+            }
+            let magic = read_u32(buf, 0);
+            if magic != JITDUMP_MAGIC {
+                anyhow::bail!(
+                    "{}: not a little-endian jitdump file (magic 0x{:08x})",
+                    self.file.path.display(),
+                    magic
+                );
+            }
+            // header.total_size may exceed the fixed part in newer versions
+            let header_size = (read_u32(buf, 8) as usize).max(JITDUMP_HEADER_SIZE);
+            if buf.len() < header_size {
+                return Ok(0);
+            }
+            pos = header_size;
+            self.header_parsed = true;
+        }
+
+        let mut added = 0;
+        while buf.len() - pos >= JITDUMP_RECORD_PREFIX {
+            let id = read_u32(buf, pos);
+            let total = read_u32(buf, pos + 4) as usize;
+            if total < JITDUMP_RECORD_PREFIX {
+                anyhow::bail!(
+                    "{}: corrupt jitdump record at offset {}",
+                    self.file.path.display(),
+                    pos
+                );
+            }
+            if buf.len() - pos < total {
+                break; // record still being written
+            }
+            let body = &buf[pos + JITDUMP_RECORD_PREFIX..pos + total];
+            match id {
+                // pid u32, tid u32, vma u64, code_addr u64, code_size u64, code_index u64, name\0, code
+                JIT_CODE_LOAD if body.len() >= 40 => {
+                    let code_addr = read_u64(body, 16);
+                    let code_size = read_u64(body, 24);
+                    let name_bytes = &body[40..];
+                    let name_len = name_bytes
+                        .iter()
+                        .position(|&b| b == 0)
+                        .unwrap_or(name_bytes.len());
+                    let name = String::from_utf8_lossy(&name_bytes[..name_len]).into_owned();
+                    if !name.is_empty() {
+                        table.insert(JitSymbol {
+                            start: code_addr,
+                            size: code_size,
+                            name,
+                        });
+                        added += 1;
+                    }
+                }
+                // pid u32, tid u32, vma u64, old_code_addr u64, new_code_addr u64, code_size u64, code_index u64
+                JIT_CODE_MOVE if body.len() >= 40 => {
+                    let old_addr = read_u64(body, 16);
+                    let new_addr = read_u64(body, 24);
+                    let size = read_u64(body, 32);
+                    table.relocate(old_addr, new_addr, size);
+                }
+                _ => {}
+            }
+            pos += total;
+        }
+        self.file.pending.drain(..pos);
+        Ok(added)
+    }
+}
+
+fn read_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+/// Executable address range without a backing file (JIT code lives here)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AnonExecRange {
+    start: u64,
+    end: u64,
+}
+
+/// Parse `/proc/PID/maps` content into anonymous executable ranges.
+/// Memfd-backed and `[anon:...]` named regions count as anonymous; `[vdso]`
+/// and friends do not.
+fn parse_anon_exec_ranges(maps: &str) -> Vec<AnonExecRange> {
+    let mut ranges = Vec::new();
+    for line in maps.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(range), Some(perms)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if !perms.contains('x') {
+            continue;
+        }
+        // offset, dev, inode, then optional path
+        let path = fields.nth(3).unwrap_or("");
+        let anonymous = path.is_empty()
+            || path.starts_with("[anon")
+            || path.starts_with("/memfd:")
+            || path.starts_with("//anon");
+        if !anonymous {
+            continue;
+        }
+        if let Some((s, e)) = range.split_once('-') {
+            if let (Ok(start), Ok(end)) = (u64::from_str_radix(s, 16), u64::from_str_radix(e, 16)) {
+                ranges.push(AnonExecRange { start, end });
+            }
+        }
+    }
+    ranges
+}
+
+/// The PID as seen inside the target's own PID namespace (last `NSpid` entry).
+/// Runtimes name their perf map/jitdump files after this PID.
+pub(crate) fn namespace_pid(pid: i32) -> i32 {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find(|l| l.starts_with("NSpid:"))
+                .and_then(|l| l.split_whitespace().last())
+                .and_then(|v| v.parse().ok())
+        })
+        .unwrap_or(pid)
+}
+
+/// Per-process JIT state
+#[derive(Debug)]
+struct ProcessJit {
+    ns_pid: i32,
+    table: JitSymbolTable,
+    perf_map: Option<PerfMapReader>,
+    jitdump: Option<JitDumpReader>,
+    anon_exec: Vec<AnonExecRange>,
+}
+
+impl ProcessJit {
+    fn new(pid: i32) -> Self {
+        Self {
+            ns_pid: namespace_pid(pid),
+            table: JitSymbolTable::new(),
+            perf_map: None,
+            jitdump: None,
+            anon_exec: Vec::new(),
+        }
+    }
+
+    fn in_anon_exec(&self, ip: u64) -> bool {
+        self.anon_exec.iter().any(|r| ip >= r.start && ip < r.end)
+    }
+
+    /// Re-read the target's maps, discover JIT files and pull in new symbols.
+    fn refresh(&mut self, pid: i32) {
+        let root = PathBuf::from(format!("/proc/{}/root", pid));
+        let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap_or_default();
+        if !maps.is_empty() {
+            self.anon_exec = parse_anon_exec_ranges(&maps);
+        }
+
+        if self.perf_map.is_none() {
+            let path = root.join(format!("tmp/perf-{}.map", self.ns_pid));
+            if path.exists() {
+                debug!("Found perf map for PID {}: {}", pid, path.display());
+                self.perf_map = Some(PerfMapReader::new(path));
+            }
+        }
+
+        if self.jitdump.is_none() {
+            // Runtimes mmap the jitdump file so perf can find it; look for it in the maps
+            let suffix = format!("jit-{}.dump", self.ns_pid);
+            let mapped = maps
+                .lines()
+                .filter_map(|l| l.split_whitespace().nth(5))
+                .find(|p| p.ends_with(&suffix))
+                .map(|p| root.join(p.trim_start_matches('/')));
+            let path = mapped.unwrap_or_else(|| root.join("tmp").join(&suffix));
+            if path.exists() {
+                debug!("Found jitdump for PID {}: {}", pid, path.display());
+                self.jitdump = Some(JitDumpReader::new(path));
+            }
+        }
+
+        if let Some(reader) = self.perf_map.as_mut() {
+            match reader.refresh(&mut self.table) {
+                Ok(n) if n > 0 => debug!("PID {}: {} new perf map symbols", pid, n),
+                Ok(_) => {}
+                Err(e) => debug!("PID {}: perf map read failed: {}", pid, e),
+            }
+        }
+        if let Some(reader) = self.jitdump.as_mut() {
+            match reader.refresh(&mut self.table) {
+                Ok(n) if n > 0 => debug!("PID {}: {} new jitdump symbols", pid, n),
+                Ok(_) => {}
+                Err(e) => {
+                    debug!("PID {}: jitdump read failed: {}", pid, e);
+                    self.jitdump = None;
+                }
+            }
+        }
+    }
+}
+
+/// Resolves JIT frames for any number of processes.
+///
+/// Holds only plain data, so it is `Send` and can live inside [`super::symbols::SymbolCache`].
+#[derive(Debug, Default)]
+pub struct JitResolver {
+    processes: HashMap<i32, ProcessJit>,
+}
+
+impl JitResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refresh JIT state for `pid` (maps + newly appended perf map/jitdump entries).
+    /// Call once per symbolization batch before [`Self::lookup`].
+    pub fn refresh(&mut self, pid: i32) {
+        if pid <= 0 || !Path::new(&format!("/proc/{}", pid)).exists() {
+            return;
+        }
+        self.processes
+            .entry(pid)
+            .or_insert_with(|| ProcessJit::new(pid))
+            .refresh(pid);
+    }
+
+    /// Look up a JIT symbol for `ip` in `pid`. Only IPs inside anonymous
+    /// executable mappings are considered, so file-backed code never picks up
+    /// a stale JIT name.
+    pub fn lookup(&mut self, pid: i32, ip: u64) -> Option<String> {
+        let proc_jit = self.processes.get_mut(&pid)?;
+        if !proc_jit.anon_exec.is_empty() && !proc_jit.in_anon_exec(ip) {
+            return None;
+        }
+        proc_jit.table.lookup(ip).map(|s| s.name.clone())
+    }
+
+    /// Whether any JIT symbols are known for `pid`
+    pub fn has_symbols(&self, pid: i32) -> bool {
+        self.processes
+            .get(&pid)
+            .is_some_and(|p| !p.table.is_empty())
+    }
+
+    /// Drop state for processes that no longer exist
+    pub fn prune_exited(&mut self) {
+        self.processes
+            .retain(|pid, _| Path::new(&format!("/proc/{}", pid)).exists());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_perf_map_line() {
+        let sym =
+            parse_perf_map_line("7f3a2c001000 1a0 LazyCompile:*handle /app/server.js:42").unwrap();
+        assert_eq!(sym.start, 0x7f3a2c001000);
+        assert_eq!(sym.size, 0x1a0);
+        assert_eq!(sym.name, "LazyCompile:*handle /app/server.js:42");
+
+        assert!(parse_perf_map_line("garbage").is_none());
+        assert!(parse_perf_map_line("zz 10 foo").is_none());
+    }
+
+    #[test]
+    fn test_table_lookup_and_replacement() {
+        let mut table = JitSymbolTable::new();
+        table.insert(JitSymbol {
+            start: 0x1000,
+            size: 0x100,
+            name: "a".into(),
+        });
+        table.insert(JitSymbol {
+            start: 0x2000,
+            size: 0x10,
+            name: "b".into(),
+        });
+        assert_eq!(table.lookup(0x1080).unwrap().name, "a");
+        assert!(table.lookup(0x1100).is_none());
+        assert!(table.lookup(0x0fff).is_none());
+
+        // Re-JIT at the same address replaces the old name
+        table.insert(JitSymbol {
+            start: 0x1000,
+            size: 0x100,
+            name: "a2".into(),
+        });
+        assert_eq!(table.lookup(0x1000).unwrap().name, "a2");
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_perf_map_incremental_refresh() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut reader = PerfMapReader::new(file.path().to_path_buf());
+        let mut table = JitSymbolTable::new();
+
+        write!(file, "1000 10 first\n2000 1").unwrap();
+        file.flush().unwrap();
+        assert_eq!(reader.refresh(&mut table).unwrap(), 1);
+        assert!(table.lookup(0x2000).is_none());
+
+        // Finish the partial line and add another
+        writeln!(file, "0 second\n3000 10 third").unwrap();
+        file.flush().unwrap();
+        assert_eq!(reader.refresh(&mut table).unwrap(), 2);
+        assert_eq!(table.lookup(0x2008).unwrap().name, "second");
+        assert_eq!(table.lookup(0x3000).unwrap().name, "third");
+    }
+
+    fn jitdump_header() -> Vec<u8> {
+        let mut h = Vec::new();
+        h.extend_from_slice(&JITDUMP_MAGIC.to_le_bytes());
+        h.extend_from_slice(&1u32.to_le_bytes()); // version
+        h.extend_from_slice(&(JITDUMP_HEADER_SIZE as u32).to_le_bytes());
+        h.extend_from_slice(&62u32.to_le_bytes()); // EM_X86_64
+        h.extend_from_slice(&0u32.to_le_bytes()); // pad
+        h.extend_from_slice(&1234u32.to_le_bytes()); // pid
+        h.extend_from_slice(&0u64.to_le_bytes()); // timestamp
+        h.extend_from_slice(&0u64.to_le_bytes()); // flags
+        h
+    }
+
+    fn code_load(addr: u64, size: u64, name: &str) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&1234u32.to_le_bytes());
+        body.extend_from_slice(&1234u32.to_le_bytes());
+        body.extend_from_slice(&addr.to_le_bytes()); // vma
+        body.extend_from_slice(&addr.to_le_bytes()); // code_addr
+        body.extend_from_slice(&size.to_le_bytes());
+        body.extend_from_slice(&0u64.to_le_bytes()); // code_index
+        body.extend_from_slice(name.as_bytes());
+        body.push(0);
+        body.extend(std::iter::repeat(0x90).take(size as usize));
+        let mut rec = Vec::new();
+        rec.extend_from_slice(&JIT_CODE_LOAD.to_le_bytes());
+        rec.extend_from_slice(&((JITDUMP_RECORD_PREFIX + body.len()) as u32).to_le_bytes());
+        rec.extend_from_slice(&0u64.to_le_bytes());
+        rec.extend_from_slice(&body);
+        rec
+    }
+
+    #[test]
+    fn test_jitdump_incremental_refresh() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut reader = JitDumpReader::new(file.path().to_path_buf());
+        let mut table = JitSymbolTable::new();
+
+        let first = code_load(0x7000, 16, "Interpreter::run");
+        let second = code_load(0x8000, 8, "Foo.bar()");
+        file.write_all(&jitdump_header()).unwrap();
+        file.write_all(&first).unwrap();
+        file.write_all(&second[..10]).unwrap();
+        file.flush().unwrap();
+        assert_eq!(reader.refresh(&mut table).unwrap(), 1);
+
+        file.write_all(&second[10..]).unwrap();
+        file.flush().unwrap();
+        assert_eq!(reader.refresh(&mut table).unwrap(), 1);
+        assert_eq!(table.lookup(0x7004).unwrap().name, "Interpreter::run");
+        assert_eq!(table.lookup(0x8000).unwrap().name, "Foo.bar()");
+    }
+
+    #[test]
+    fn test_jitdump_restart_rereads_header() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut reader = JitDumpReader::new(file.path().to_path_buf());
+        let mut table = JitSymbolTable::new();
+
+        let mut first = jitdump_header();
+        first.extend(code_load(0x7000, 64, "Interpreter::run"));
+        file.write_all(&first).unwrap();
+        file.flush().unwrap();
+        assert_eq!(reader.refresh(&mut table).unwrap(), 1);
+
+        // The runtime restarts and writes a shorter dump from scratch
+        file.as_file().set_len(0).unwrap();
+        let mut second = jitdump_header();
+        second.extend(code_load(0x9000, 8, "Foo.bar()"));
+        std::fs::write(file.path(), &second).unwrap();
+        assert_eq!(reader.refresh(&mut table).unwrap(), 1);
+        assert_eq!(table.lookup(0x9000).unwrap().name, "Foo.bar()");
+    }
+
+    #[test]
+    fn test_parse_anon_exec_ranges() {
+        let maps = "\
+55d0c0000000-55d0c0001000 r-xp 00000000 08:01 1234 /usr/bin/node
+7f0000000000-7f0000100000 rwxp 00000000 00:00 0
+7f0000200000-7f0000300000 r-xp 00000000 00:00 0 [anon:v8 code]
+7f0000400000-7f0000500000 rw-p 00000000 00:00 0
+7ffd00000000-7ffd00002000 r-xp 00000000 00:00 0 [vdso]";
+        let ranges = parse_anon_exec_ranges(maps);
+        assert_eq!(
+            ranges,
+            vec![
+                AnonExecRange {
+                    start: 0x7f0000000000,
+                    end: 0x7f0000100000
+                },
+                AnonExecRange {
+                    start: 0x7f0000200000,
+                    end: 0x7f0000300000
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Kernel lock event collector
+//!
+//! Collects kernel lock contention events (mmap_lock, inode locks,
+//! spinlocks, ...) from the `lock:contention_begin`/`contention_end`
+//! tracepoints and builds a profile of them, kept apart from futex and
+//! user-space lock contention.
+
+use anyhow::Result;
+use aperture_shared::types::events::{KernelLockEvent, ProfileEvent};
+use aperture_shared::types::profile::{KernelLockProfile, Stack};
+use aperture_shared::utils::arch::is_kernel_ip;
+use aya::maps::StackTraceMap;
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+/// Raw kernel lock event from eBPF (must match agent-ebpf/src/lock_profiler.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KernelLockEventBpf {
+    pub timestamp: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub lock_addr: u64,
+    pub wait_time_ns: u64,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub comm: [u8; 16],
+    pub flags: u32,
+    pub _pad: u32,
+}
+
+// Implement traits for reading from perf buffer
+unsafe impl aya::Pod for KernelLockEventBpf {}
+
+/// Kernel lock event collector
+#[derive(Debug)]
+pub struct KernelLockCollector {
+    /// Collected events
+    events: Vec<KernelLockEvent>,
+
+    /// Start time
+    start_time: u64,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+}
+
+impl Default for KernelLockCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KernelLockCollector {
+    /// Create a new kernel lock collector
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            start_time: aperture_shared::utils::time::system_time_nanos(),
+            push_cursor: 0,
+        }
+    }
+
+    /// Add an event to the collector
+    pub fn add_event(&mut self, event: KernelLockEvent) {
+        self.events.push(event);
+    }
+
+    /// Process a raw eBPF event and convert to KernelLockEvent
+    pub fn process_event(
+        &mut self,
+        event: &KernelLockEventBpf,
+        stacks: &StackTraceMap<aya::maps::MapData>,
+    ) -> Result<()> {
+        // User stack first, then kernel, as for lock events
+        let mut frames = read_stack(stacks, event.user_stack_id);
+        frames.extend(read_stack(stacks, event.kernel_stack_id));
+
+        let comm = std::str::from_utf8(&event.comm)
+            .unwrap_or("<unknown>")
+            .trim_end_matches('\0')
+            .to_string();
+
+        self.add_event(KernelLockEvent {
+            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+            pid: event.pid as i32,
+            tid: event.tid as i32,
+            lock_addr: event.lock_addr,
+            flags: event.flags,
+            wait_time_ns: event.wait_time_ns,
+            stack_trace: frames,
+            comm,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        });
+        Ok(())
+    }
+
+    /// Build aggregated profile from collected events
+    pub fn build_profile(&self) -> Result<KernelLockProfile> {
+        info!(
+            "Building kernel lock profile from {} events",
+            self.events.len()
+        );
+
+        let mut profile = KernelLockProfile::new(self.start_time);
+        profile.end_time = aperture_shared::utils::time::system_time_nanos();
+
+        for event in &self.events {
+            if event.stack_trace.is_empty() {
+                continue;
+            }
+            let stack = Stack::from_ips(&event.stack_trace);
+            profile.add_contention(event.lock_addr, event.flags, stack, event.wait_time_ns);
+        }
+
+        info!(
+            "Kernel lock profile built: {} total events, {} locks, {} unique contentions",
+            profile.total_events,
+            profile.lock_flags.len(),
+            profile.contentions.len()
+        );
+
+        Ok(profile)
+    }
+
+    /// User-space IPs grouped by the process they were sampled in, so the
+    /// symbolizer can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for ev in &self.events {
+            let ips = by_pid.entry(ev.pid).or_default();
+            for &ip in &ev.stack_trace {
+                if !is_kernel_ip(ip) && !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+        }
+        by_pid
+    }
+
+    /// Return events accumulated since the last call and advance the cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let events: Vec<ProfileEvent> = self.events[self.push_cursor..]
+            .iter()
+            .cloned()
+            .map(ProfileEvent::KernelLock)
+            .collect();
+        self.push_cursor = self.events.len();
+        events
+    }
+}
+
+/// IPs of stack `id` in `stacks`, empty when none was captured
+fn read_stack(stacks: &StackTraceMap<aya::maps::MapData>, id: i64) -> Vec<u64> {
+    if id < 0 {
+        return Vec::new();
+    }
+    match stacks.get(&(id as u32), 0) {
+        Ok(trace) => trace.frames().iter().map(|f| f.ip).collect(),
+        Err(e) => {
+            debug!("Failed to get kernel lock stack {}: {}", id, e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aperture_shared::types::events::{LCB_F_MUTEX, LCB_F_SPIN};
+
+    fn event(lock_addr: u64, flags: u32, wait_time_ns: u64, stack: Vec<u64>) -> KernelLockEvent {
+        KernelLockEvent {
+            timestamp: 1000,
+            pid: 300,
+            tid: 301,
+            lock_addr,
+            flags,
+            wait_time_ns,
+            stack_trace: stack,
+            comm: "test".to_string(),
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_kernel_lock_collector() {
+        let mut collector = KernelLockCollector::new();
+        let stack = vec![0x400000, 0xffffffff81000100];
+        collector.add_event(event(0xffff888000001000, LCB_F_SPIN, 200, stack.clone()));
+        collector.add_event(event(0xffff888000001000, LCB_F_SPIN, 600, stack.clone()));
+        collector.add_event(event(
+            0xffff888000002000,
+            LCB_F_MUTEX | LCB_F_SPIN,
+            5_000,
+            vec![0xffffffff81000200],
+        ));
+        // No stack captured
+        collector.add_event(event(0xffff888000003000, LCB_F_SPIN, 100, vec![]));
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.total_events, 3);
+        assert_eq!(profile.lock_type(0xffff888000001000), "spinlock");
+        assert_eq!(profile.lock_type(0xffff888000002000), "mutex");
+
+        let stats = &profile.contentions[&(0xffff888000001000, Stack::from_ips(&stack))];
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.total_wait_ns, 800);
+
+        assert_eq!(collector.user_ips_by_pid()[&300], vec![0x400000]);
+        assert_eq!(collector.take_pending_events().len(), 4);
+        assert!(collector.take_pending_events().is_empty());
+    }
+}
@@ -1,11 +1,19 @@
 //! Lock event collector
 //!
 //! Collects lock contention events from eBPF and builds profile data
+//!
+//! The eBPF program reports two kinds of events: waits (a thread blocked on
+//! a futex, with the thread that woke it) and releases (a thread woke
+//! waiters, with how long it had held the lock). Waits build the contention
+//! view, releases the holder view.
 
+use super::lock_names::LockNamer;
 use anyhow::Result;
-use aperture_shared::types::events::{LockEvent, ProfileEvent};
+use aperture_shared::types::events::{LockEvent, LockEventKind, ProfileEvent};
 use aperture_shared::types::profile::{LockProfile, Stack};
+use aperture_shared::utils::arch::is_kernel_ip;
 use aya::maps::StackTraceMap;
+use std::collections::HashMap;
 use tracing::{debug, info};
 
 /// Raw lock event from eBPF (must match agent-ebpf/src/lock_profiler.rs)
@@ -20,8 +28,15 @@ pub struct LockEventBpf {
     pub user_stack_id: i64,
     pub kernel_stack_id: i64,
     pub comm: [u8; 16],
+    pub hold_time_ns: u64,
+    pub waker_tid: u32,
+    pub kind: u32,
+    pub labels: [u8; aperture_labels::LABELS_SIZE],
 }
 
+/// LockEventBpf::kind of a release
+const LOCK_EVENT_RELEASE: u32 = 1;
+
 // Implement traits for reading from perf buffer
 unsafe impl aya::Pod for LockEventBpf {}
 
@@ -36,6 +51,9 @@ pub struct LockCollector {
 
     /// Index of first event not yet pushed to aggregator
     push_cursor: usize,
+
+    /// Index of first event whose lock has not been named
+    name_cursor: usize,
 }
 
 impl Default for LockCollector {
@@ -51,6 +69,7 @@ impl LockCollector {
             events: Vec::new(),
             start_time: aperture_shared::utils::time::system_time_nanos(),
             push_cursor: 0,
+            name_cursor: 0,
         }
     }
 
@@ -100,22 +119,16 @@ impl LockCollector {
             }
         }
 
-        let lock_event = LockEvent {
-            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
-            pid: event.pid as i32,
-            tid: event.tid as i32,
-            lock_addr: event.lock_addr,
-            hold_time_ns: 0, // Not captured yet
-            wait_time_ns: event.wait_time_ns,
-            stack_trace: frames,
-            comm,
-            stack_symbols: vec![],
-        };
-
-        self.add_event(lock_event);
+        self.add_event(convert_event(event, frames, comm));
         Ok(())
     }
 
+    /// Name the locks of events collected since the last call
+    pub fn name_locks(&mut self, namer: &mut LockNamer) {
+        namer.name_events(&mut self.events[self.name_cursor..]);
+        self.name_cursor = self.events.len();
+    }
+
     /// Build aggregated profile from collected events
     pub fn build_profile(&self) -> Result<LockProfile> {
         info!("Building lock profile from {} events", self.events.len());
@@ -126,23 +139,49 @@ impl LockCollector {
         profile.end_time = aperture_shared::utils::time::system_time_nanos();
 
         for event in &self.events {
+            if let Some(name) = &event.lock_name {
+                profile.lock_names.insert(event.lock_addr, name.clone());
+            }
             if event.stack_trace.is_empty() {
                 continue;
             }
 
             let stack = Stack::from_ips(&event.stack_trace);
-            profile.add_contention(event.lock_addr, stack, event.wait_time_ns);
+            match event.kind {
+                LockEventKind::Wait => {
+                    profile.add_contention(event.lock_addr, stack, event.wait_time_ns)
+                }
+                LockEventKind::Release => {
+                    profile.add_hold(event.lock_addr, stack, event.hold_time_ns)
+                }
+            }
         }
 
         info!(
-            "Lock profile built: {} total events, {} unique contentions",
+            "Lock profile built: {} total events, {} unique contentions, {} unique holders",
             profile.total_events,
-            profile.contentions.len()
+            profile.contentions.len(),
+            profile.holders.len()
         );
 
         Ok(profile)
     }
 
+    /// User-space IPs grouped by the process they were sampled in, so the
+    /// symbolizer can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for ev in &self.events {
+            let ips = by_pid.entry(ev.pid).or_default();
+            for &ip in &ev.stack_trace {
+                if !is_kernel_ip(ip) && !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+        }
+        by_pid
+    }
+
     /// All events for a final push to the aggregator
     pub fn profile_events(&self) -> Vec<ProfileEvent> {
         self.events
@@ -164,6 +203,31 @@ impl LockCollector {
     }
 }
 
+/// Build a LockEvent from a raw event and its resolved stack
+fn convert_event(event: &LockEventBpf, frames: Vec<u64>, comm: String) -> LockEvent {
+    let kind = if event.kind == LOCK_EVENT_RELEASE {
+        LockEventKind::Release
+    } else {
+        LockEventKind::Wait
+    };
+    LockEvent {
+        timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+        pid: event.pid as i32,
+        tid: event.tid as i32,
+        lock_addr: event.lock_addr,
+        hold_time_ns: event.hold_time_ns,
+        wait_time_ns: event.wait_time_ns,
+        stack_trace: frames,
+        comm,
+        stack_symbols: vec![],
+        stack_refs: vec![],
+        kind,
+        waker_tid: (event.waker_tid != 0).then_some(event.waker_tid as i32),
+        lock_name: None,
+        labels: aperture_labels::decode(&event.labels).into_iter().collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,6 +247,11 @@ mod tests {
             stack_trace: vec![0x400000, 0x400100],
             comm: "test".to_string(),
             stack_symbols: vec![],
+            stack_refs: vec![],
+            kind: LockEventKind::Wait,
+            waker_tid: None,
+            lock_name: None,
+            labels: Default::default(),
         };
 
         let event2 = LockEvent {
@@ -195,6 +264,11 @@ mod tests {
             stack_trace: vec![0x400000, 0x400100],
             comm: "test".to_string(),
             stack_symbols: vec![],
+            stack_refs: vec![],
+            kind: LockEventKind::Wait,
+            waker_tid: None,
+            lock_name: None,
+            labels: Default::default(),
         };
 
         let event3 = LockEvent {
@@ -207,6 +281,11 @@ mod tests {
             stack_trace: vec![0x500000],
             comm: "other".to_string(),
             stack_symbols: vec![],
+            stack_refs: vec![],
+            kind: LockEventKind::Wait,
+            waker_tid: None,
+            lock_name: Some("app::CACHE".to_string()),
+            labels: Default::default(),
         };
 
         collector.add_event(event1);
@@ -231,5 +310,60 @@ mod tests {
         let stats2 = profile.contentions.get(&(0x2000, stack2)).unwrap();
         assert_eq!(stats2.count, 1);
         assert_eq!(stats2.total_wait_ns, 1000);
+        assert_eq!(profile.lock_name(0x2000), "app::CACHE");
+        assert_eq!(profile.lock_name(0x1000), "0x1000");
+    }
+
+    #[test]
+    fn test_releases_build_holder_view() {
+        let raw = |kind, tid, waker_tid, hold_time_ns, wait_time_ns| LockEventBpf {
+            timestamp: 1000,
+            pid: 200,
+            tid,
+            lock_addr: 0x3000,
+            wait_time_ns,
+            user_stack_id: -1,
+            kernel_stack_id: -1,
+            comm: [0; 16],
+            hold_time_ns,
+            waker_tid,
+            kind,
+            labels: [0; aperture_labels::LABELS_SIZE],
+        };
+
+        // Thread 201 released the lock after 4ms, waking thread 202
+        let release = convert_event(
+            &raw(LOCK_EVENT_RELEASE, 201, 0, 4_000_000, 0),
+            vec![0x600000],
+            "holder".to_string(),
+        );
+        let mut waiting = raw(0, 202, 201, 4_000_000, 3_000_000);
+        aperture_labels::encode(
+            &[("endpoint".to_string(), "/api".to_string())],
+            &mut waiting.labels,
+        );
+        let wait = convert_event(&waiting, vec![0x700000], "waiter".to_string());
+        assert_eq!(release.kind, LockEventKind::Release);
+        assert_eq!(release.waker_tid, None);
+        assert_eq!(wait.kind, LockEventKind::Wait);
+        assert_eq!(wait.waker_tid, Some(201));
+        assert_eq!(wait.hold_time_ns, 4_000_000);
+        assert!(release.labels.is_empty());
+        assert_eq!(wait.labels["endpoint"], "/api");
+
+        let mut collector = LockCollector::new();
+        collector.add_event(release);
+        collector.add_event(wait);
+        let profile = collector.build_profile().unwrap();
+
+        assert_eq!(profile.total_events, 2);
+        let held = &profile.holders[&(0x3000, Stack::from_ips(&[0x600000]))];
+        assert_eq!(held.count, 1);
+        assert_eq!(held.total_hold_ns, 4_000_000);
+        let waited = &profile.contentions[&(0x3000, Stack::from_ips(&[0x700000]))];
+        assert_eq!(waited.total_wait_ns, 3_000_000);
+        assert!(!profile
+            .contentions
+            .contains_key(&(0x3000, Stack::from_ips(&[0x600000]))));
     }
 }
@@ -0,0 +1,510 @@
+//! Lock names
+//!
+//! Lock addresses mean nothing outside their process, and change from run
+//! to run. Where possible a lock is named after what it lives in:
+//!
+//! - a global (`.data`/`.bss`): the ELF data symbol containing the address,
+//!   read from the mapped file's symbol tables (`app::CACHE`, `stats_lock+0x8`).
+//!   `.bss` past the end of the file is the anonymous mapping right after
+//!   the file's last mapping, and is looked up in that file.
+//! - a heap object: the allocation site, when the target's malloc and free
+//!   are probed (`--lock-alloc-sites`): the first caller of the allocator
+//!   on the allocation stack (`heap:Pool::new+0x10`)
+//!
+//! Locks on stacks, in anonymous mappings or in allocations made before
+//! profiling started keep their address.
+
+use super::mount_ns::{self, ExecMapping};
+use crate::ebpf::lock_uprobes::strip_legacy_hash;
+use aperture_shared::types::events::LockEvent;
+use aya::maps::{HashMap as BpfHashMap, MapData, StackTraceMap};
+use blazesym::inspect::source::{Elf, Source as InspectSource};
+use blazesym::inspect::Inspector;
+use blazesym::symbolize::source::{Process, Source};
+use blazesym::symbolize::{Input, Symbolized, Symbolizer};
+use blazesym::{Pid, SymType};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Key of a live allocation from eBPF (must match
+/// agent-ebpf/src/lock_profiler.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AllocKeyBpf {
+    pub ptr: u64,
+    pub tgid: u32,
+    pub _pad: u32,
+}
+
+unsafe impl aya::Pod for AllocKeyBpf {}
+
+/// Live allocation from eBPF (must match agent-ebpf/src/lock_profiler.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AllocEntryBpf {
+    pub size: u64,
+    pub user_stack_id: i64,
+}
+
+unsafe impl aya::Pod for AllocEntryBpf {}
+
+/// A line of `/proc/PID/maps`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub start: u64,
+    pub end: u64,
+    pub offset: u64,
+    pub dev: String,
+    pub inode: u64,
+    /// File path, `[heap]`-style name, or empty for anonymous memory
+    pub path: String,
+}
+
+impl Region {
+    fn is_file(&self) -> bool {
+        self.inode != 0 && self.path.starts_with('/')
+    }
+
+    fn same_file(&self, other: &Region) -> bool {
+        self.inode == other.inode && self.dev == other.dev
+    }
+}
+
+/// Parse every mapping of `/proc/PID/maps` content, in address order
+pub fn parse_regions(maps: &str) -> Vec<Region> {
+    let mut regions = Vec::new();
+    for line in maps.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(range), Some(_perms), Some(offset), Some(dev), Some(inode)) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            continue;
+        };
+        let Some((s, e)) = range.split_once('-') else {
+            continue;
+        };
+        let (Ok(start), Ok(end), Ok(offset), Ok(inode)) = (
+            u64::from_str_radix(s, 16),
+            u64::from_str_radix(e, 16),
+            u64::from_str_radix(offset, 16),
+            inode.parse::<u64>(),
+        ) else {
+            continue;
+        };
+        regions.push(Region {
+            start,
+            end,
+            offset,
+            dev: dev.to_string(),
+            inode,
+            path: fields.collect::<Vec<_>>().join(" "),
+        });
+    }
+    regions
+}
+
+/// The mapping of the file `addr` belongs to: the file mapping containing
+/// it, or the last mapping of the file an anonymous `.bss` region follows
+pub fn file_region_for(regions: &[Region], addr: u64) -> Option<&Region> {
+    let idx = regions
+        .iter()
+        .position(|r| r.start <= addr && addr < r.end)?;
+    let region = &regions[idx];
+    if region.is_file() {
+        return Some(region);
+    }
+    let prev = regions.get(idx.checked_sub(1)?)?;
+    (region.path.is_empty() && region.inode == 0 && prev.is_file() && prev.end == region.start)
+        .then_some(prev)
+}
+
+/// A global variable of an ELF file, at its link-time address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataSymbol {
+    pub addr: u64,
+    pub size: u64,
+    pub name: String,
+}
+
+/// Name of the data symbol containing `vaddr`, with the offset into it
+/// when the lock is not its first field. `symbols` is sorted by address.
+pub fn data_symbol_name(symbols: &[DataSymbol], vaddr: u64) -> Option<String> {
+    let idx = symbols
+        .partition_point(|s| s.addr <= vaddr)
+        .checked_sub(1)?;
+    let symbol = &symbols[idx];
+    if vaddr >= symbol.addr + symbol.size.max(1) {
+        return None;
+    }
+    Some(with_offset(&symbol.name, vaddr - symbol.addr))
+}
+
+/// Frames of allocator internals, skipped to find the allocation site
+const ALLOCATOR_FRAMES: &[&str] = &[
+    "malloc",
+    "calloc",
+    "realloc",
+    "__libc_",
+    "__rust_",
+    "__rdl_",
+    "__rg_",
+    "alloc::",
+    "<alloc::",
+    "std::alloc",
+    "<std::alloc",
+    "core::alloc",
+    "operator new",
+];
+
+/// Allocation site of a stack (innermost frame first): its first frame
+/// that isn't allocator code
+pub fn alloc_site_name(frames: &[Option<String>]) -> Option<String> {
+    frames.iter().flatten().find_map(|name| {
+        (!ALLOCATOR_FRAMES.iter().any(|p| name.starts_with(p))).then(|| name.clone())
+    })
+}
+
+fn with_offset(name: &str, offset: u64) -> String {
+    if offset == 0 {
+        name.to_string()
+    } else {
+        format!("{}+0x{:x}", name, offset)
+    }
+}
+
+/// Data symbols of a mapped file and its link-time base
+#[derive(Debug)]
+struct ModuleSymbols {
+    /// Sorted by address
+    symbols: Vec<DataSymbol>,
+    /// Address of the first loadable segment, mapped at the file's start
+    load_address: u64,
+}
+
+/// Resolves lock addresses to names, caching per process and file
+pub struct LockNamer {
+    /// Names resolved so far, None for locks without one
+    names: HashMap<(i32, u64), Option<String>>,
+    /// Data symbols by mapped file (device, inode); None when unreadable
+    modules: HashMap<(String, u64), Option<Arc<ModuleSymbols>>>,
+    /// Mappings of each process, re-read when an address is outside them
+    regions: HashMap<i32, Vec<Region>>,
+    alloc_sites: Option<AllocSites>,
+}
+
+impl Default for LockNamer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockNamer {
+    pub fn new() -> Self {
+        Self {
+            names: HashMap::new(),
+            modules: HashMap::new(),
+            regions: HashMap::new(),
+            alloc_sites: None,
+        }
+    }
+
+    /// Also name heap locks of the target after their allocation site
+    pub fn with_alloc_sites(mut self, alloc_sites: Option<AllocSites>) -> Self {
+        self.alloc_sites = alloc_sites;
+        self
+    }
+
+    /// Fill in the name of each event's lock
+    pub fn name_events(&mut self, events: &mut [LockEvent]) {
+        if let Some(sites) = &mut self.alloc_sites {
+            sites.invalidate();
+        }
+        for event in events {
+            if event.lock_name.is_none() {
+                event.lock_name = self.name(event.pid, event.lock_addr);
+            }
+        }
+    }
+
+    /// Name of the lock at `lock_addr` in process `pid`
+    pub fn name(&mut self, pid: i32, lock_addr: u64) -> Option<String> {
+        if let Some(name) = self.names.get(&(pid, lock_addr)) {
+            return name.clone();
+        }
+        let name = self
+            .global_name(pid, lock_addr)
+            .or_else(|| self.alloc_sites.as_mut()?.name(pid, lock_addr));
+        self.names.insert((pid, lock_addr), name.clone());
+        name
+    }
+
+    fn global_name(&mut self, pid: i32, addr: u64) -> Option<String> {
+        let known = self
+            .regions
+            .get(&pid)
+            .is_some_and(|r| r.iter().any(|r| r.start <= addr && addr < r.end));
+        if !known {
+            let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid)).ok()?;
+            self.regions.insert(pid, parse_regions(&maps));
+        }
+        let regions = &self.regions[&pid];
+
+        let region = file_region_for(regions, addr)?;
+        // The file's first mapping is where its first segment was loaded
+        let base = regions
+            .iter()
+            .find(|r| r.same_file(region) && r.offset == 0)?;
+        let key = (base.dev.clone(), base.inode);
+        let module = match self.modules.get(&key) {
+            Some(module) => module.clone(),
+            None => {
+                let module = load_module(pid, base).map(Arc::new);
+                self.modules.insert(key, module.clone());
+                module
+            }
+        }?;
+
+        let bias = base.start.wrapping_sub(module.load_address & !0xfff);
+        data_symbol_name(&module.symbols, addr.wrapping_sub(bias))
+    }
+}
+
+/// Read the data symbols of the file behind `base`, a mapping of `pid`
+fn load_module(pid: i32, base: &Region) -> Option<ModuleSymbols> {
+    use symbolic::debuginfo::Object;
+
+    let mapping = ExecMapping {
+        start: base.start,
+        end: base.end,
+        offset: base.offset,
+        dev: base.dev.clone(),
+        inode: base.inode,
+        path: base.path.trim_end_matches(" (deleted)").to_string(),
+        deleted: base.path.ends_with(" (deleted)"),
+    };
+    let (file, _) = mount_ns::open_mapping(pid, &mapping)?;
+    let path = format!("/proc/self/fd/{}", file.as_raw_fd());
+
+    let data = std::fs::read(&path).ok()?;
+    let load_address = Object::parse(&data).ok()?.load_address();
+
+    let mut elf = Elf::new(&path);
+    // Variables are only listed from the symbol tables
+    elf.debug_syms = false;
+    let mut symbols = Vec::new();
+    let result = Inspector::new().for_each(&InspectSource::Elf(elf), |sym| {
+        if sym.sym_type == SymType::Variable {
+            let demangled = symbolic_demangle::demangle(&sym.name);
+            symbols.push(DataSymbol {
+                addr: sym.addr,
+                size: sym.size.unwrap_or(0) as u64,
+                name: strip_legacy_hash(&demangled).to_string(),
+            });
+        }
+        ControlFlow::Continue(())
+    });
+    if let Err(e) = result {
+        debug!("Failed to read data symbols of {}: {}", mapping.path, e);
+        return None;
+    }
+    symbols.sort_by_key(|s| s.addr);
+    symbols.dedup_by_key(|s| s.addr);
+    debug!(
+        "{} data symbols in {} for lock names",
+        symbols.len(),
+        mapping.path
+    );
+
+    Some(ModuleSymbols {
+        symbols,
+        load_address,
+    })
+}
+
+/// Allocation sites of the target's live heap allocations, from the
+/// lock profiler's ALLOCS and ALLOC_STACKS maps
+pub struct AllocSites {
+    pid: i32,
+    allocs: BpfHashMap<MapData, AllocKeyBpf, AllocEntryBpf>,
+    stacks: StackTraceMap<MapData>,
+    /// Live allocations sorted by address, read once per naming pass
+    snapshot: Option<Vec<(u64, AllocEntryBpf)>>,
+    /// Site of each allocation stack
+    sites: HashMap<i64, Option<String>>,
+}
+
+impl AllocSites {
+    pub fn new(
+        pid: i32,
+        allocs: BpfHashMap<MapData, AllocKeyBpf, AllocEntryBpf>,
+        stacks: StackTraceMap<MapData>,
+    ) -> Self {
+        Self {
+            pid,
+            allocs,
+            stacks,
+            snapshot: None,
+            sites: HashMap::new(),
+        }
+    }
+
+    /// Re-read the live allocations on the next lookup
+    fn invalidate(&mut self) {
+        self.snapshot = None;
+    }
+
+    fn name(&mut self, pid: i32, addr: u64) -> Option<String> {
+        if pid != self.pid {
+            return None;
+        }
+        let allocs = self.snapshot.get_or_insert_with(|| {
+            let mut allocs: Vec<(u64, AllocEntryBpf)> = self
+                .allocs
+                .iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|(key, _)| key.tgid == pid as u32)
+                .map(|(key, entry)| (key.ptr, entry))
+                .collect();
+            allocs.sort_by_key(|&(ptr, _)| ptr);
+            allocs
+        });
+        let idx = allocs
+            .partition_point(|&(ptr, _)| ptr <= addr)
+            .checked_sub(1)?;
+        let (ptr, entry) = allocs[idx];
+        if addr >= ptr + entry.size || entry.user_stack_id < 0 {
+            return None;
+        }
+
+        let site = match self.sites.get(&entry.user_stack_id) {
+            Some(site) => site.clone(),
+            None => {
+                let site = self.site(entry.user_stack_id);
+                self.sites.insert(entry.user_stack_id, site.clone());
+                site
+            }
+        }?;
+        Some(with_offset(&format!("heap:{}", site), addr - ptr))
+    }
+
+    /// Allocation site of an allocation stack
+    fn site(&self, stack_id: i64) -> Option<String> {
+        let trace = self.stacks.get(&(stack_id as u32), 0).ok()?;
+        let ips: Vec<u64> = trace.frames().iter().map(|f| f.ip).collect();
+        let source = Source::Process(Process::new(Pid::from(self.pid as u32)));
+        // Symbolizers aren't Send, so none is kept across push intervals
+        let names: Vec<Option<String>> =
+            match Symbolizer::new().symbolize(&source, Input::AbsAddr(&ips)) {
+                Ok(results) => results
+                    .iter()
+                    .map(|r| match r {
+                        Symbolized::Sym(sym) => Some(sym.name.to_string()),
+                        Symbolized::Unknown(_) => None,
+                    })
+                    .collect(),
+                Err(e) => {
+                    debug!("Failed to symbolize allocation stack {}: {}", stack_id, e);
+                    return None;
+                }
+            };
+        alloc_site_name(&names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPS: &str = "\
+55d0c0a00000-55d0c0a21000 r--p 00000000 fd:01 1234 /usr/bin/app
+55d0c0a21000-55d0c0b00000 r-xp 00021000 fd:01 1234 /usr/bin/app
+55d0c0b00000-55d0c0b10000 rw-p 00100000 fd:01 1234 /usr/bin/app
+55d0c0b10000-55d0c0b20000 rw-p 00000000 00:00 0
+55d0c1000000-55d0c1100000 rw-p 00000000 00:00 0 [heap]
+7f0000000000-7f0000100000 rw-p 00000000 00:00 0
+";
+
+    #[test]
+    fn test_file_region_for() {
+        let regions = parse_regions(MAPS);
+        assert_eq!(regions.len(), 6);
+        assert_eq!(regions[4].path, "[heap]");
+
+        // .data in the file, .bss in the anonymous mapping right after it
+        let data = file_region_for(&regions, 0x55d0c0b00100).unwrap();
+        assert_eq!(data.offset, 0x100000);
+        let bss = file_region_for(&regions, 0x55d0c0b10100).unwrap();
+        assert_eq!(bss.start, 0x55d0c0b00000);
+
+        assert!(file_region_for(&regions, 0x55d0c1000100).is_none());
+        assert!(file_region_for(&regions, 0x7f0000000100).is_none());
+        assert!(file_region_for(&regions, 0x10).is_none());
+    }
+
+    #[test]
+    fn test_data_symbol_name() {
+        let symbols = vec![
+            DataSymbol {
+                addr: 0x1000,
+                size: 40,
+                name: "app::CACHE".to_string(),
+            },
+            DataSymbol {
+                addr: 0x1100,
+                size: 64,
+                name: "stats".to_string(),
+            },
+        ];
+        assert_eq!(
+            data_symbol_name(&symbols, 0x1000).as_deref(),
+            Some("app::CACHE")
+        );
+        assert_eq!(
+            data_symbol_name(&symbols, 0x1108).as_deref(),
+            Some("stats+0x8")
+        );
+        // Between and before symbols
+        assert_eq!(data_symbol_name(&symbols, 0x1030), None);
+        assert_eq!(data_symbol_name(&symbols, 0x800), None);
+    }
+
+    #[test]
+    fn test_alloc_site_name() {
+        let frames = vec![
+            Some("__rdl_alloc".to_string()),
+            Some("alloc::sync::Arc<T>::new".to_string()),
+            None,
+            Some("app::pool::Pool::new".to_string()),
+            Some("main".to_string()),
+        ];
+        assert_eq!(
+            alloc_site_name(&frames).as_deref(),
+            Some("app::pool::Pool::new")
+        );
+        assert_eq!(alloc_site_name(&[Some("malloc".to_string())]), None);
+    }
+
+    #[test]
+    fn test_name_own_globals() {
+        static NAMED_LOCK: std::sync::Mutex<u64> = std::sync::Mutex::new(0);
+
+        let pid = std::process::id() as i32;
+        let mut namer = LockNamer::new();
+        let addr = &NAMED_LOCK as *const _ as u64;
+        let name = namer.name(pid, addr);
+        // Stripped binaries have no symbol table to name it from
+        if let Some(name) = name {
+            assert!(name.ends_with("NAMED_LOCK"), "{}", name);
+        }
+
+        let on_stack = 0u64;
+        assert_eq!(namer.name(pid, &on_stack as *const _ as u64), None);
+    }
+}
@@ -0,0 +1,250 @@
+//! Page fault and reclaim event collector
+//!
+//! Collects sampled page faults and reclaim stalls from the memory tracer
+//! and builds a fault flamegraph profile plus per-process reclaim stats.
+//! Fault samples are weighted by the sampling ratio so counts estimate
+//! every fault.
+
+use anyhow::Result;
+use aperture_shared::types::events::{PageFaultEvent, ProfileEvent, ReclaimEvent};
+use aperture_shared::types::profile::MemoryProfile;
+use aperture_shared::utils::arch::is_kernel_ip;
+use aya::maps::StackTraceMap;
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+/// Raw memory event from eBPF (must match agent-ebpf/src/memory_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryEventBpf {
+    pub timestamp: u64,
+    pub address: u64,
+    pub ip: u64,
+    pub error_code: u64,
+    pub stall_ns: u64,
+    pub nr_reclaimed: u64,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub pid: u32,
+    pub tid: u32,
+    pub kind: u32,
+    pub order: u32,
+    pub comm: [u8; 16],
+}
+
+// Implement traits for reading from perf buffer
+unsafe impl aya::Pod for MemoryEventBpf {}
+
+/// Event kinds of the BPF program
+const KIND_USER_FAULT: u32 = 0;
+const KIND_KERNEL_FAULT: u32 = 1;
+const KIND_DIRECT_RECLAIM: u32 = 2;
+const KIND_MEMCG_RECLAIM: u32 = 3;
+
+/// Memory event collector
+#[derive(Debug)]
+pub struct MemoryCollector {
+    /// Collected events, page faults and reclaims in arrival order
+    events: Vec<ProfileEvent>,
+
+    /// Start time
+    start_time: u64,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+
+    /// Faults each sample stands for (the tracer records 1 in N)
+    fault_weight: u32,
+}
+
+impl Default for MemoryCollector {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl MemoryCollector {
+    /// Create a new memory collector for faults sampled 1 in `fault_sample_every`
+    pub fn new(fault_sample_every: u32) -> Self {
+        Self {
+            events: Vec::new(),
+            start_time: aperture_shared::utils::time::system_time_nanos(),
+            push_cursor: 0,
+            fault_weight: fault_sample_every.max(1),
+        }
+    }
+
+    /// Process a raw eBPF event and convert to a page fault or reclaim event
+    pub fn process_event(
+        &mut self,
+        event: &MemoryEventBpf,
+        stacks: &StackTraceMap<aya::maps::MapData>,
+    ) -> Result<()> {
+        // User stack first, then kernel, as for CPU samples
+        let mut stack_trace = read_stack(stacks, event.user_stack_id);
+        stack_trace.extend(read_stack(stacks, event.kernel_stack_id));
+        self.convert_event(event, stack_trace);
+        Ok(())
+    }
+
+    fn convert_event(&mut self, event: &MemoryEventBpf, stack_trace: Vec<u64>) {
+        let timestamp = aperture_shared::utils::time::boot_time_to_system_time(event.timestamp);
+        let comm = std::str::from_utf8(&event.comm)
+            .unwrap_or("<unknown>")
+            .trim_end_matches('\0')
+            .to_string();
+
+        let event = match event.kind {
+            KIND_USER_FAULT | KIND_KERNEL_FAULT => ProfileEvent::PageFault(PageFaultEvent {
+                timestamp,
+                pid: event.pid as i32,
+                tid: event.tid as i32,
+                comm,
+                address: event.address,
+                ip: event.ip,
+                error_code: event.error_code,
+                kernel: event.kind == KIND_KERNEL_FAULT,
+                weight: self.fault_weight,
+                stack_trace,
+                stack_symbols: vec![],
+                stack_refs: vec![],
+            }),
+            KIND_DIRECT_RECLAIM | KIND_MEMCG_RECLAIM => ProfileEvent::Reclaim(ReclaimEvent {
+                timestamp,
+                pid: event.pid as i32,
+                tid: event.tid as i32,
+                comm,
+                stall_ns: event.stall_ns,
+                nr_reclaimed: event.nr_reclaimed,
+                order: event.order,
+                memcg: event.kind == KIND_MEMCG_RECLAIM,
+            }),
+            kind => {
+                debug!("Unknown memory event kind {}", kind);
+                return;
+            }
+        };
+        self.events.push(event);
+    }
+
+    /// Build aggregated profile from collected events
+    pub fn build_profile(&self) -> Result<MemoryProfile> {
+        info!("Building memory profile from {} events", self.events.len());
+
+        let mut profile = MemoryProfile::new(self.start_time);
+        profile.end_time = aperture_shared::utils::time::system_time_nanos();
+        profile.faults.end_time = profile.end_time;
+
+        for event in &self.events {
+            match event {
+                ProfileEvent::PageFault(ev) => profile.add_fault(ev),
+                ProfileEvent::Reclaim(ev) => profile.add_reclaim(ev),
+                _ => {}
+            }
+        }
+
+        info!(
+            "Memory profile built: {} total events, ~{} page faults, {} fault stacks, {} reclaim stalls",
+            profile.total_events,
+            profile.total_faults(),
+            profile.faults.samples.len(),
+            profile.total_reclaim().count
+        );
+
+        Ok(profile)
+    }
+
+    /// User-space IPs of fault stacks grouped by process, so the symbolizer
+    /// can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for event in &self.events {
+            let ProfileEvent::PageFault(ev) = event else {
+                continue;
+            };
+            let ips = by_pid.entry(ev.pid).or_default();
+            for &ip in &ev.stack_trace {
+                if !is_kernel_ip(ip) && !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+        }
+        by_pid
+    }
+
+    /// Return events accumulated since the last call and advance the cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let events = self.events[self.push_cursor..].to_vec();
+        self.push_cursor = self.events.len();
+        events
+    }
+}
+
+/// IPs of stack `id` in `stacks`, empty when none was captured
+fn read_stack(stacks: &StackTraceMap<aya::maps::MapData>, id: i64) -> Vec<u64> {
+    if id < 0 {
+        return Vec::new();
+    }
+    match stacks.get(&(id as u32), 0) {
+        Ok(trace) => trace.frames().iter().map(|f| f.ip).collect(),
+        Err(e) => {
+            debug!("Failed to get fault stack {}: {}", id, e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(kind: u32, pid: u32) -> MemoryEventBpf {
+        let mut event = MemoryEventBpf {
+            timestamp: 0,
+            address: 0x7f00_0000_1000,
+            ip: 0x401000,
+            error_code: 6,
+            stall_ns: 2_000_000,
+            nr_reclaimed: 32,
+            user_stack_id: -1,
+            kernel_stack_id: -1,
+            pid,
+            tid: pid,
+            kind,
+            order: 0,
+            comm: [0; 16],
+        };
+        event.comm[..5].copy_from_slice(b"redis");
+        event
+    }
+
+    #[test]
+    fn test_memory_collector() {
+        let mut collector = MemoryCollector::new(100);
+        collector.convert_event(&raw(KIND_USER_FAULT, 7), vec![0x401000, 0x402000]);
+        collector.convert_event(
+            &raw(KIND_KERNEL_FAULT, 7),
+            vec![0x403000, 0xffffffff81000000],
+        );
+        collector.convert_event(&raw(KIND_DIRECT_RECLAIM, 7), vec![]);
+        collector.convert_event(&raw(KIND_MEMCG_RECLAIM, 8), vec![]);
+        collector.convert_event(&raw(99, 7), vec![]);
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.total_events, 4);
+        assert_eq!(profile.faults.total_samples, 200);
+        let process = &profile.processes[&7];
+        assert_eq!(process.comm, "redis");
+        assert_eq!(process.faults, 200);
+        assert_eq!(process.kernel_faults, 100);
+        assert_eq!(process.reclaim.count, 1);
+        assert_eq!(profile.processes[&8].reclaim.memcg_count, 1);
+        assert_eq!(
+            collector.user_ips_by_pid()[&7],
+            vec![0x401000, 0x402000, 0x403000]
+        );
+
+        assert_eq!(collector.take_pending_events().len(), 4);
+        assert!(collector.take_pending_events().is_empty());
+    }
+}
@@ -1,6 +1,20 @@
 //! Event collection and processing
 
+pub mod block_io;
 pub mod cpu;
+pub mod disk_cache;
+pub mod fd_resolver;
+pub mod frame_refs;
+pub mod jit;
+pub mod kernel_lock;
 pub mod lock;
+pub mod lock_names;
+pub mod memory;
+pub mod mount_ns;
+pub mod normalize;
+pub mod probe;
+pub mod process;
+pub mod sched;
 pub mod symbols;
 pub mod syscall;
+pub mod tcp;
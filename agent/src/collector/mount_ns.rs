@@ -0,0 +1,213 @@
+//! Binary lookup across mount namespaces
+//!
+//! A host-side agent (e.g. a Kubernetes DaemonSet) sees container binaries only
+//! through the target's `/proc/PID` entries: paths in `/proc/PID/maps` are
+//! relative to the container's mount namespace. Mapped files are opened, in
+//! order, through:
+//!
+//! 1. `/proc/PID/map_files/<start>-<end>` — the exact mapped file, also after
+//!    it was deleted or replaced (needs `CAP_SYS_ADMIN`)
+//! 2. `/proc/PID/root/<path>` — the path inside the container's root
+//! 3. `<path>` — the path as the agent sees it (host processes)
+//!
+//! Path-based opens are checked against the device and inode of the
+//! `map_files` entry so a binary replaced since the process started is never
+//! used. When that entry cannot be read, the device and inode listed in
+//! `maps` are used instead; overlayfs (the usual container root filesystem)
+//! lists the underlying layer's device there, so only the inode is compared.
+
+use std::fs::File;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+
+/// `f_type` of overlayfs in `statfs(2)`
+const OVERLAYFS_SUPER_MAGIC: i64 = 0x794c_7630;
+
+/// A file-backed executable mapping parsed from `/proc/PID/maps`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExecMapping {
+    pub start: u64,
+    pub end: u64,
+    pub offset: u64,
+    /// `major:minor` device (hex) + inode, used to share handles between processes
+    pub dev: String,
+    pub inode: u64,
+    /// Path inside the process's mount namespace, without the ` (deleted)` marker
+    pub path: String,
+    /// The file was unlinked (or replaced) after being mapped
+    pub deleted: bool,
+}
+
+/// How a mapped file was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MappingAccess {
+    MapFiles,
+    Root,
+    Host,
+}
+
+/// Parse file-backed executable mappings from `/proc/PID/maps` content.
+pub fn parse_exec_mappings(maps: &str) -> Vec<ExecMapping> {
+    let mut mappings = Vec::new();
+    for line in maps.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(range), Some(perms), Some(offset), Some(dev), Some(inode)) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            continue;
+        };
+        if !perms.contains('x') {
+            continue;
+        }
+        let path = fields.collect::<Vec<_>>().join(" ");
+        if !path.starts_with('/') || path.starts_with("/memfd:") {
+            continue;
+        }
+        let Some((s, e)) = range.split_once('-') else {
+            continue;
+        };
+        let (Ok(start), Ok(end), Ok(offset), Ok(inode)) = (
+            u64::from_str_radix(s, 16),
+            u64::from_str_radix(e, 16),
+            u64::from_str_radix(offset, 16),
+            inode.parse::<u64>(),
+        ) else {
+            continue;
+        };
+        if inode == 0 {
+            continue;
+        }
+        let deleted = path.ends_with(" (deleted)");
+        mappings.push(ExecMapping {
+            start,
+            end,
+            offset,
+            dev: dev.to_string(),
+            inode,
+            path: path.trim_end_matches(" (deleted)").to_string(),
+            deleted,
+        });
+    }
+    mappings
+}
+
+/// Open the file behind a mapping of `pid`, trying `map_files`, the process's
+/// root and the agent's own view in that order.
+pub fn open_mapping(pid: i32, mapping: &ExecMapping) -> Option<(File, MappingAccess)> {
+    if let Ok(file) = File::open(map_files_path(pid, mapping)) {
+        return Some((file, MappingAccess::MapFiles));
+    }
+    // A deleted file's path now names nothing, or something else
+    if mapping.deleted {
+        return None;
+    }
+
+    let candidates = [
+        (
+            format!("/proc/{}/root{}", pid, mapping.path),
+            MappingAccess::Root,
+        ),
+        (mapping.path.clone(), MappingAccess::Host),
+    ];
+    candidates.into_iter().find_map(|(path, access)| {
+        let file = File::open(path).ok()?;
+        is_same_file(&file, pid, mapping).then_some((file, access))
+    })
+}
+
+/// True when `pid` lives in a different mount namespace than the agent
+pub fn in_other_mount_ns(pid: i32) -> bool {
+    let ns = |p: &str| std::fs::read_link(format!("/proc/{}/ns/mnt", p)).ok();
+    match (ns(&pid.to_string()), ns("self")) {
+        (Some(target), Some(own)) => target != own,
+        _ => false,
+    }
+}
+
+fn map_files_path(pid: i32, mapping: &ExecMapping) -> String {
+    format!(
+        "/proc/{}/map_files/{:x}-{:x}",
+        pid, mapping.start, mapping.end
+    )
+}
+
+/// Check that a file opened by path is the one the process mapped.
+pub(crate) fn is_same_file(file: &File, pid: i32, mapping: &ExecMapping) -> bool {
+    let Ok(meta) = file.metadata() else {
+        return false;
+    };
+    if let Ok(mapped) = std::fs::metadata(map_files_path(pid, mapping)) {
+        return (mapped.dev(), mapped.ino()) == (meta.dev(), meta.ino());
+    }
+    if is_overlayfs(file) {
+        return meta.ino() == mapping.inode;
+    }
+    meta.ino() == mapping.inode && parse_dev(&mapping.dev).map_or(true, |dev| dev == meta.dev())
+}
+
+fn is_overlayfs(file: &File) -> bool {
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) };
+    ret == 0 && stat.f_type as i64 == OVERLAYFS_SUPER_MAGIC
+}
+
+/// Parse a `maps` device field (`major:minor` in hex) into a `dev_t`
+fn parse_dev(dev: &str) -> Option<u64> {
+    let (major, minor) = dev.split_once(':')?;
+    let major = u32::from_str_radix(major, 16).ok()?;
+    let minor = u32::from_str_radix(minor, 16).ok()?;
+    Some(libc::makedev(major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exec_mappings() {
+        let maps = "\
+55d0c0a00000-55d0c0a21000 r--p 00000000 fd:01 1234 /usr/bin/app
+55d0c0a21000-55d0c0b00000 r-xp 00021000 fd:01 1234 /usr/bin/app
+7f0000000000-7f0000001000 r-xp 00000000 00:00 0
+7f0000100000-7f0000200000 r-xp 00002000 fd:01 99 /opt/my lib.so (deleted)
+7ffd00000000-7ffd00001000 r-xp 00000000 00:00 0 [vdso]
+";
+        let mappings = parse_exec_mappings(maps);
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].start, 0x55d0c0a21000);
+        assert_eq!(mappings[0].offset, 0x21000);
+        assert_eq!(mappings[0].path, "/usr/bin/app");
+        assert!(!mappings[0].deleted);
+        assert_eq!(mappings[1].path, "/opt/my lib.so");
+        assert_eq!(mappings[1].inode, 99);
+        assert!(mappings[1].deleted);
+        assert_eq!(parse_dev("fd:01"), Some(libc::makedev(0xfd, 0x01)));
+    }
+
+    #[test]
+    fn test_open_own_mappings() {
+        let pid = std::process::id() as i32;
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        let mapping = parse_exec_mappings(&maps)
+            .into_iter()
+            .next()
+            .expect("test binary has file-backed text");
+        assert!(open_mapping(pid, &mapping).is_some());
+        assert!(!in_other_mount_ns(pid));
+
+        // A path that names a different file than the mapping is rejected
+        let wrong = ExecMapping {
+            deleted: false,
+            path: "/dev/null".to_string(),
+            inode: mapping.inode.wrapping_add(1),
+            start: 0,
+            end: 0,
+            ..mapping
+        };
+        assert!(open_mapping(pid, &wrong).is_none());
+    }
+}
@@ -0,0 +1,430 @@
+//! Frame normalization
+//!
+//! Applied to symbolized frames so names stay stable across builds:
+//!
+//! 1. `demangle` — mangled C++/Rust/Swift names that reach us undemangled
+//!    (perf maps, stripped symbol tables) are demangled
+//! 2. `strip-hash` — Rust's `::h<16 hex digits>` disambiguator is dropped
+//! 3. rewrite rules — regex replacements on function names
+//! 4. collapse rules — runs of adjacent frames matching a regex are merged
+//!    into one frame (e.g. all `tokio::runtime::*` scheduler frames)
+//!
+//! Rules come from a JSON file:
+//!
+//! ```json
+//! {
+//!   "rewrite": [
+//!     { "name": "closures", "pattern": "\\{\\{closure\\}\\}", "replace": "{closure}" }
+//!   ],
+//!   "collapse": [
+//!     { "name": "tokio-scheduler", "pattern": "^tokio::runtime::", "replace": "tokio::runtime::*" }
+//!   ]
+//! }
+//! ```
+//!
+//! Profiles record how many frames each rule changed in `normalization`.
+
+use anyhow::{Context, Result};
+use aperture_shared::types::profile::{
+    Frame, KernelLockProfile, LockContentionStats, LockHoldStats, LockProfile, ProbeProfile,
+    Profile, SchedProfile, Stack, SyscallProfile,
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Name of the built-in demangling rule
+pub const RULE_DEMANGLE: &str = "demangle";
+
+/// Name of the built-in Rust hash-suffix rule
+pub const RULE_STRIP_HASH: &str = "strip-hash";
+
+/// One user rule as written in the rules file
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleSpec {
+    /// Recorded in the profile when the rule changes a frame
+    pub name: String,
+    /// Regex matched against function names
+    pub pattern: String,
+    /// Replacement (`$1`-style groups allowed) or, for collapse rules, the
+    /// name of the merged frame
+    pub replace: String,
+}
+
+/// Contents of a rules file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RulesFile {
+    #[serde(default)]
+    pub rewrite: Vec<RuleSpec>,
+    #[serde(default)]
+    pub collapse: Vec<RuleSpec>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    regex: Regex,
+    replace: String,
+}
+
+impl Rule {
+    fn compile(spec: &RuleSpec) -> Result<Self> {
+        let regex = Regex::new(&spec.pattern)
+            .with_context(|| format!("Invalid pattern in normalization rule {}", spec.name))?;
+        Ok(Self {
+            name: spec.name.clone(),
+            regex,
+            replace: spec.replace.clone(),
+        })
+    }
+}
+
+/// A normalized name and the rules that produced it
+#[derive(Debug, Clone)]
+struct Normalized {
+    name: String,
+    rules: Vec<String>,
+}
+
+/// Demangles, strips hashes and applies user rules to symbolized frames
+#[derive(Debug)]
+pub struct FrameNormalizer {
+    hash_suffix: Regex,
+    rewrite: Vec<Rule>,
+    collapse: Vec<Rule>,
+    /// Raw name -> normalized name; names repeat across stacks and batches
+    memo: Mutex<HashMap<String, Normalized>>,
+}
+
+impl Default for FrameNormalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameNormalizer {
+    /// Normalizer with only the built-in rules (demangle, strip-hash)
+    pub fn new() -> Self {
+        Self {
+            hash_suffix: Regex::new("::h[0-9a-f]{16}$").expect("valid hash regex"),
+            rewrite: Vec::new(),
+            collapse: Vec::new(),
+            memo: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Built-in rules followed by the rules of `spec`
+    pub fn with_rules(spec: &RulesFile) -> Result<Self> {
+        Ok(Self {
+            rewrite: spec
+                .rewrite
+                .iter()
+                .map(Rule::compile)
+                .collect::<Result<_>>()?,
+            collapse: spec
+                .collapse
+                .iter()
+                .map(Rule::compile)
+                .collect::<Result<_>>()?,
+            ..Self::new()
+        })
+    }
+
+    /// Load user rules from a JSON rules file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read normalization rules {}", path.display()))?;
+        let spec: RulesFile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse normalization rules {}", path.display()))?;
+        Self::with_rules(&spec)
+    }
+
+    /// Normalizer for a run: user rules from `rules_path` if set
+    pub fn for_rules_path(rules_path: Option<&Path>) -> Result<Self> {
+        match rules_path {
+            Some(path) => Self::load(path),
+            None => Ok(Self::new()),
+        }
+    }
+
+    /// Normalize a function name (demangle, strip-hash, rewrite rules).
+    /// Hex placeholders for unresolved addresses are returned as is.
+    pub fn normalize_name(&self, name: &str) -> String {
+        if name.starts_with("0x") {
+            return name.to_string();
+        }
+        self.normalized(name).name
+    }
+
+    fn normalized(&self, name: &str) -> Normalized {
+        if let Some(hit) = self.memo.lock().unwrap().get(name) {
+            return hit.clone();
+        }
+
+        let mut rules = Vec::new();
+        let mut current = name.to_string();
+        let demangled = symbolic_demangle::demangle(&current);
+        if demangled != current {
+            current = demangled.into_owned();
+            rules.push(RULE_DEMANGLE.to_string());
+        }
+        let stripped = self.hash_suffix.replace(&current, "");
+        if stripped != current {
+            current = stripped.into_owned();
+            rules.push(RULE_STRIP_HASH.to_string());
+        }
+        for rule in &self.rewrite {
+            let rewritten = rule.regex.replace_all(&current, rule.replace.as_str());
+            if rewritten != current {
+                current = rewritten.into_owned();
+                rules.push(rule.name.clone());
+            }
+        }
+
+        let normalized = Normalized {
+            name: current,
+            rules,
+        };
+        self.memo
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), normalized.clone());
+        normalized
+    }
+
+    /// Normalize every frame of `stack`, then merge runs matched by collapse
+    /// rules. Rules that changed a frame are counted in `applied`.
+    pub fn normalize_stack(&self, stack: &Stack, applied: &mut BTreeMap<String, u64>) -> Stack {
+        let mut frames: Vec<Frame> = Vec::with_capacity(stack.frames.len());
+        for frame in &stack.frames {
+            let Some(function) = frame.function.as_deref().filter(|f| !f.starts_with("0x")) else {
+                frames.push(frame.clone());
+                continue;
+            };
+            let normalized = self.normalized(function);
+            for rule in normalized.rules {
+                *applied.entry(rule).or_insert(0) += 1;
+            }
+            frames.push(Frame {
+                function: Some(normalized.name),
+                ..frame.clone()
+            });
+        }
+
+        for rule in &self.collapse {
+            frames = collapse_runs(frames, rule, applied);
+        }
+        Stack { frames }
+    }
+
+    /// Normalize all stacks of a CPU profile, merging stacks that become identical
+    pub fn normalize_profile(&self, profile: &mut Profile) {
+        let mut samples = HashMap::with_capacity(profile.samples.len());
+        for (stack, count) in profile.samples.drain() {
+            let stack = self.normalize_stack(&stack, &mut profile.normalization);
+            *samples.entry(stack).or_insert(0) += count;
+        }
+        profile.samples = samples;
+    }
+
+    /// Normalize all stacks of a lock profile, merging stacks that become identical
+    pub fn normalize_lock_profile(&self, profile: &mut LockProfile) {
+        let mut contentions: HashMap<(u64, Stack), LockContentionStats> =
+            HashMap::with_capacity(profile.contentions.len());
+        for ((lock_addr, stack), stats) in profile.contentions.drain() {
+            let stack = self.normalize_stack(&stack, &mut profile.normalization);
+            contentions
+                .entry((lock_addr, stack))
+                .and_modify(|s| s.merge(&stats))
+                .or_insert(stats);
+        }
+        profile.contentions = contentions;
+
+        let mut holders: HashMap<(u64, Stack), LockHoldStats> =
+            HashMap::with_capacity(profile.holders.len());
+        for ((lock_addr, stack), stats) in profile.holders.drain() {
+            let stack = self.normalize_stack(&stack, &mut profile.normalization);
+            holders
+                .entry((lock_addr, stack))
+                .and_modify(|s| s.merge(&stats))
+                .or_insert(stats);
+        }
+        profile.holders = holders;
+    }
+
+    /// Normalize the stacks of a kernel lock profile, merging stacks that
+    /// become identical
+    pub fn normalize_kernel_lock_profile(&self, profile: &mut KernelLockProfile) {
+        let mut contentions: HashMap<(u64, Stack), LockContentionStats> =
+            HashMap::with_capacity(profile.contentions.len());
+        for ((lock_addr, stack), stats) in profile.contentions.drain() {
+            let stack = self.normalize_stack(&stack, &mut profile.normalization);
+            contentions
+                .entry((lock_addr, stack))
+                .and_modify(|s| s.merge(&stats))
+                .or_insert(stats);
+        }
+        profile.contentions = contentions;
+    }
+
+    /// Normalize the slow-call stacks of a syscall profile, merging stacks
+    /// that become identical
+    pub fn normalize_syscall_profile(&self, profile: &mut SyscallProfile) {
+        let applied = &mut profile.normalization;
+        for stats in profile.syscalls.values_mut() {
+            stats.map_slow_stacks(|stack| self.normalize_stack(stack, applied));
+        }
+    }
+
+    /// Normalize the wakeup stacks of a scheduler profile, merging stacks
+    /// that become identical
+    pub fn normalize_sched_profile(&self, profile: &mut SchedProfile) {
+        let applied = &mut profile.normalization;
+        for process in profile.processes.values_mut() {
+            process.map_wakeup_stacks(|stack| self.normalize_stack(stack, applied));
+        }
+    }
+
+    /// Normalize the stacks of every probe
+    pub fn normalize_probe_profile(&self, profile: &mut ProbeProfile) {
+        let applied = &mut profile.normalization;
+        for probe in profile.probes.values_mut() {
+            probe.map_stacks(|stack| self.normalize_stack(stack, applied));
+        }
+    }
+}
+
+/// Replace each run of adjacent frames whose name matches `rule` with one
+/// frame named `rule.replace`, keeping the outermost frame's address.
+fn collapse_runs(
+    frames: Vec<Frame>,
+    rule: &Rule,
+    applied: &mut BTreeMap<String, u64>,
+) -> Vec<Frame> {
+    let matches = |f: &Frame| {
+        f.function
+            .as_deref()
+            .is_some_and(|n| rule.regex.is_match(n))
+    };
+    let mut out: Vec<Frame> = Vec::with_capacity(frames.len());
+    let mut in_run = false;
+    for frame in frames {
+        if !matches(&frame) {
+            in_run = false;
+            out.push(frame);
+            continue;
+        }
+        *applied.entry(rule.name.clone()).or_insert(0) += 1;
+        let merged = Frame {
+            function: Some(rule.replace.clone()),
+            file: None,
+            line: None,
+            ..frame
+        };
+        if in_run {
+            // Frames are innermost first: the later frame is the outer one
+            if let Some(last) = out.last_mut() {
+                *last = merged;
+            }
+        } else {
+            out.push(merged);
+        }
+        in_run = true;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aperture_shared::types::events::{Symbol, SymbolFrame};
+
+    fn stack(names: &[&str]) -> Stack {
+        let symbols: Vec<Option<Symbol>> = names
+            .iter()
+            .map(|n| Some(vec![SymbolFrame::named(*n)]))
+            .collect();
+        let ips: Vec<u64> = (1..=names.len() as u64).map(|i| i * 0x1000).collect();
+        Stack::from_ips_with_symbols(&ips, &symbols)
+    }
+
+    fn names(stack: &Stack) -> Vec<&str> {
+        stack
+            .frames
+            .iter()
+            .map(|f| f.function.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_demangle_and_strip_hash() {
+        let normalizer = FrameNormalizer::new();
+        assert_eq!(
+            normalizer.normalize_name("_ZN3foo3barEv"),
+            "foo::bar()".to_string()
+        );
+        assert_eq!(
+            normalizer.normalize_name("myapp::worker::run::h0123456789abcdef"),
+            "myapp::worker::run"
+        );
+        assert_eq!(normalizer.normalize_name("main"), "main");
+    }
+
+    #[test]
+    fn test_rules_rewrite_collapse_and_record() {
+        let spec: RulesFile = serde_json::from_str(
+            r#"{
+                "rewrite": [{ "name": "closures", "pattern": "\\{\\{closure\\}\\}", "replace": "{closure}" }],
+                "collapse": [{ "name": "tokio", "pattern": "^tokio::runtime::", "replace": "tokio::runtime::*" }]
+            }"#,
+        )
+        .unwrap();
+        let normalizer = FrameNormalizer::with_rules(&spec).unwrap();
+
+        let mut profile = Profile::new(0, 1, 1);
+        profile.add_sample(stack(&[
+            "app::handle::{{closure}}::h0123456789abcdef",
+            "tokio::runtime::task::harness::poll",
+            "tokio::runtime::scheduler::multi_thread::worker::run",
+            "std::thread::main",
+        ]));
+        // Differs only in scheduler internals and build hash
+        profile.add_sample(stack(&[
+            "app::handle::{{closure}}::hfedcba9876543210",
+            "tokio::runtime::task::raw::poll",
+            "std::thread::main",
+        ]));
+        normalizer.normalize_profile(&mut profile);
+
+        for stack in profile.samples.keys() {
+            assert_eq!(
+                names(stack),
+                vec![
+                    "app::handle::{closure}",
+                    "tokio::runtime::*",
+                    "std::thread::main"
+                ]
+            );
+        }
+        assert_eq!(profile.normalization.get(RULE_STRIP_HASH), Some(&2));
+        assert_eq!(profile.normalization.get("closures"), Some(&2));
+        assert_eq!(profile.normalization.get("tokio"), Some(&3));
+        assert!(!profile.normalization.contains_key(RULE_DEMANGLE));
+    }
+
+    #[test]
+    fn test_invalid_rule_is_rejected() {
+        let spec = RulesFile {
+            rewrite: vec![RuleSpec {
+                name: "broken".to_string(),
+                pattern: "(".to_string(),
+                replace: String::new(),
+            }],
+            collapse: Vec::new(),
+        };
+        let err = FrameNormalizer::with_rules(&spec).unwrap_err();
+        assert!(format!("{:#}", err).contains("broken"));
+    }
+}
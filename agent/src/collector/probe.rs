@@ -0,0 +1,362 @@
+//! User-defined probe event collector
+//!
+//! Collects calls, tracepoint hits and USDT hits from the probe tracer and
+//! builds per-probe call counts, latency histograms and stacks. The tracer
+//! reports probes by slot; the collector names them after their spec. Hits
+//! of paired USDT probes are matched per thread into spans.
+
+use anyhow::Result;
+use aperture_shared::types::events::{ProbeEvent, ProfileEvent, UsdtEvent, UsdtSpan};
+use aperture_shared::types::profile::ProbeProfile;
+use aperture_shared::utils::arch::is_kernel_ip;
+use aya::maps::StackTraceMap;
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+use crate::config::UsdtPair;
+use crate::ebpf::usdt::MAX_USDT_ARGS;
+
+/// Raw probe event from eBPF (must match agent-ebpf/src/probe_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeEventBpf {
+    pub timestamp: u64,
+    pub duration_ns: u64,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub pid: u32,
+    pub tid: u32,
+    pub probe: u32,
+    pub kind: u32,
+    pub comm: [u8; 16],
+}
+
+// Implement traits for reading from perf buffer
+unsafe impl aya::Pod for ProbeEventBpf {}
+
+/// Raw USDT hit from eBPF (must match agent-ebpf/src/probe_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UsdtEventBpf {
+    pub timestamp: u64,
+    pub args: [i64; MAX_USDT_ARGS],
+    pub pid: u32,
+    pub tid: u32,
+    pub probe: u32,
+    pub nargs: u32,
+    pub comm: [u8; 16],
+}
+
+unsafe impl aya::Pod for UsdtEventBpf {}
+
+/// Event kinds of the BPF program
+const KIND_CALL: u32 = 0;
+const KIND_HIT: u32 = 1;
+
+/// Probe event collector
+#[derive(Debug)]
+pub struct ProbeCollector {
+    /// Collected events, probe calls and USDT hits in arrival order
+    events: Vec<ProfileEvent>,
+
+    /// Start time
+    start_time: u64,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+
+    /// Probe spec of each slot (`provider:name` for USDT probes)
+    probes: Vec<String>,
+
+    /// USDT probes timed as spans
+    pairs: Vec<UsdtPair>,
+
+    /// Boot-time start hits of spans still open, by (pid, tid, pair);
+    /// nested spans of one pair stack up
+    open_spans: HashMap<(i32, i32, usize), Vec<u64>>,
+}
+
+impl ProbeCollector {
+    /// Create a new probe collector for the probes in slot order
+    pub fn new(probes: Vec<String>, pairs: Vec<UsdtPair>) -> Self {
+        Self {
+            events: Vec::new(),
+            start_time: aperture_shared::utils::time::system_time_nanos(),
+            push_cursor: 0,
+            probes,
+            pairs,
+            open_spans: HashMap::new(),
+        }
+    }
+
+    /// Add an event to the collector
+    pub fn add_event(&mut self, event: ProbeEvent) {
+        self.events.push(ProfileEvent::Probe(event));
+    }
+
+    /// Process a raw eBPF event and convert to ProbeEvent
+    pub fn process_event(
+        &mut self,
+        event: &ProbeEventBpf,
+        stacks: &StackTraceMap<aya::maps::MapData>,
+    ) -> Result<()> {
+        // User stack first, then kernel, as for CPU samples
+        let mut stack_trace = read_stack(stacks, event.user_stack_id);
+        stack_trace.extend(read_stack(stacks, event.kernel_stack_id));
+        self.convert_event(event, stack_trace);
+        Ok(())
+    }
+
+    fn convert_event(&mut self, event: &ProbeEventBpf, stack_trace: Vec<u64>) {
+        let Some(probe) = self.probes.get(event.probe as usize) else {
+            debug!("Event of unknown probe slot {}", event.probe);
+            return;
+        };
+        let duration_ns = match event.kind {
+            KIND_CALL => Some(event.duration_ns),
+            KIND_HIT => None,
+            kind => {
+                debug!("Unknown probe event kind {}", kind);
+                return;
+            }
+        };
+        let comm = std::str::from_utf8(&event.comm)
+            .unwrap_or("<unknown>")
+            .trim_end_matches('\0')
+            .to_string();
+
+        self.add_event(ProbeEvent {
+            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+            pid: event.pid as i32,
+            tid: event.tid as i32,
+            comm,
+            probe: probe.clone(),
+            duration_ns,
+            stack_trace,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        });
+    }
+
+    /// Process a raw USDT hit, closing the span it ends
+    pub fn process_usdt_event(&mut self, event: &UsdtEventBpf) {
+        let Some(probe) = self.probes.get(event.probe as usize).cloned() else {
+            debug!("USDT hit of unknown probe slot {}", event.probe);
+            return;
+        };
+        let (pid, tid) = (event.pid as i32, event.tid as i32);
+        let timestamp = aperture_shared::utils::time::boot_time_to_system_time(event.timestamp);
+        let (provider, name) = probe.split_once(':').unwrap_or((&probe, ""));
+
+        // A probe can end one pair and start another (commit, then begin)
+        let mut span = None;
+        for (i, pair) in self.pairs.iter().enumerate() {
+            if pair.provider != provider {
+                continue;
+            }
+            if span.is_none() && pair.end == name {
+                if let Some(start) = self
+                    .open_spans
+                    .get_mut(&(pid, tid, i))
+                    .and_then(|starts| starts.pop())
+                {
+                    // Durations come from boot times, so clock offset
+                    // drift between conversions doesn't skew them
+                    span = Some(UsdtSpan {
+                        name: pair.name(),
+                        start: timestamp.saturating_sub(event.timestamp.saturating_sub(start)),
+                    });
+                }
+            }
+            if pair.start == name {
+                self.open_spans
+                    .entry((pid, tid, i))
+                    .or_default()
+                    .push(event.timestamp);
+            }
+        }
+
+        let nargs = (event.nargs as usize).min(MAX_USDT_ARGS);
+        self.events.push(ProfileEvent::Usdt(UsdtEvent {
+            timestamp,
+            pid,
+            tid,
+            comm: std::str::from_utf8(&event.comm)
+                .unwrap_or("<unknown>")
+                .trim_end_matches('\0')
+                .to_string(),
+            probe,
+            args: event.args[..nargs].to_vec(),
+            span,
+        }));
+    }
+
+    /// Build aggregated profile from collected events
+    pub fn build_profile(&self) -> Result<ProbeProfile> {
+        info!("Building probe profile from {} events", self.events.len());
+
+        let mut profile = ProbeProfile::new(self.start_time);
+        profile.end_time = aperture_shared::utils::time::system_time_nanos();
+
+        for event in &self.events {
+            match event {
+                ProfileEvent::Probe(ev) => profile.add_event(ev),
+                ProfileEvent::Usdt(ev) => profile.add_usdt(ev),
+                _ => {}
+            }
+        }
+
+        info!(
+            "Probe profile built: {} total events, {} probes hit, {} USDT probes hit, {} spans, {} stacks",
+            profile.total_events,
+            profile.probes.len(),
+            profile.usdt.len(),
+            profile.spans.len(),
+            profile.stack_count()
+        );
+
+        Ok(profile)
+    }
+
+    /// User-space IPs of probe stacks grouped by process, so the symbolizer
+    /// can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for event in &self.events {
+            let ProfileEvent::Probe(ev) = event else {
+                continue;
+            };
+            let ips = by_pid.entry(ev.pid).or_default();
+            for &ip in &ev.stack_trace {
+                if !is_kernel_ip(ip) && !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+        }
+        by_pid
+    }
+
+    /// Return events accumulated since the last call and advance the cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let events = self.events[self.push_cursor..].to_vec();
+        self.push_cursor = self.events.len();
+        events
+    }
+}
+
+/// IPs of stack `id` in `stacks`, empty when none was captured
+fn read_stack(stacks: &StackTraceMap<aya::maps::MapData>, id: i64) -> Vec<u64> {
+    if id < 0 {
+        return Vec::new();
+    }
+    match stacks.get(&(id as u32), 0) {
+        Ok(trace) => trace.frames().iter().map(|f| f.ip).collect(),
+        Err(e) => {
+            debug!("Failed to get probe stack {}: {}", id, e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(probe: u32, kind: u32, duration_ns: u64) -> ProbeEventBpf {
+        let mut event = ProbeEventBpf {
+            timestamp: 0,
+            duration_ns,
+            user_stack_id: -1,
+            kernel_stack_id: -1,
+            pid: 42,
+            tid: 43,
+            probe,
+            kind,
+            comm: [0; 16],
+        };
+        event.comm[..3].copy_from_slice(b"app");
+        event
+    }
+
+    #[test]
+    fn test_probe_collector() {
+        let mut collector = ProbeCollector::new(
+            vec![
+                "kprobe:vfs_read".to_string(),
+                "tracepoint:net:netif_rx".to_string(),
+            ],
+            vec![],
+        );
+        collector.convert_event(
+            &raw(0, KIND_CALL, 2_000),
+            vec![0x401000, 0xffffffff81000000],
+        );
+        collector.convert_event(&raw(0, KIND_CALL, 6_000), vec![]);
+        collector.convert_event(&raw(1, KIND_HIT, 0), vec![]);
+        // Unknown slot and kind are dropped
+        collector.convert_event(&raw(7, KIND_CALL, 1_000), vec![]);
+        collector.convert_event(&raw(0, 9, 1_000), vec![]);
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.total_events, 3);
+        let vfs_read = &profile.probes["kprobe:vfs_read"];
+        assert_eq!(vfs_read.count, 2);
+        assert_eq!(vfs_read.avg_duration_ns(), 4_000);
+        assert_eq!(vfs_read.stacks.len(), 1);
+        assert_eq!(profile.probes["tracepoint:net:netif_rx"].count, 1);
+        assert_eq!(collector.user_ips_by_pid()[&42], vec![0x401000]);
+
+        assert_eq!(collector.take_pending_events().len(), 3);
+        assert!(collector.take_pending_events().is_empty());
+    }
+
+    #[test]
+    fn test_usdt_pairs() {
+        let pair: UsdtPair = "postgresql:query__start:query__done".parse().unwrap();
+        let mut collector = ProbeCollector::new(
+            vec![
+                "postgresql:query__start".to_string(),
+                "postgresql:query__done".to_string(),
+            ],
+            vec![pair],
+        );
+        let hit = |probe: u32, tid: u32, timestamp: u64| {
+            let mut event = UsdtEventBpf {
+                timestamp,
+                args: [0x5000, -1, 0, 0, 0, 0],
+                pid: 42,
+                tid,
+                probe,
+                nargs: 2,
+                comm: [0; 16],
+            };
+            event.comm[..8].copy_from_slice(b"postgres");
+            event
+        };
+        // Nested on thread 43, and one done without a start on thread 44
+        collector.process_usdt_event(&hit(0, 43, 1_000));
+        collector.process_usdt_event(&hit(0, 43, 2_000));
+        collector.process_usdt_event(&hit(1, 43, 3_000));
+        collector.process_usdt_event(&hit(1, 44, 3_500));
+        collector.process_usdt_event(&hit(1, 43, 5_000));
+        collector.process_usdt_event(&hit(9, 43, 6_000));
+
+        let spans: Vec<Option<u64>> = collector
+            .events
+            .iter()
+            .map(|e| match e {
+                ProfileEvent::Usdt(ev) => ev.span.as_ref().map(|s| ev.timestamp - s.start),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spans, vec![None, None, Some(1_000), None, Some(4_000)]);
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.usdt["postgresql:query__done"].count, 3);
+        assert_eq!(profile.usdt["postgresql:query__start"].args[1].min, -1);
+        let span = &profile.spans["postgresql:query__start..query__done"];
+        assert_eq!(span.count, 2);
+        assert_eq!(span.max_duration_ns, 4_000);
+        assert!(collector.user_ips_by_pid().is_empty());
+    }
+}
@@ -0,0 +1,464 @@
+//! Process lifecycle collector
+//!
+//! Turns exec/exit/fork events from the process tracker eBPF program into
+//! `ProcessEvent`s and keeps a snapshot of every tracked process's executable
+//! mappings. Snapshots hold open handles to the mapped files, so stacks from
+//! processes that exited (or whose binary was deleted or replaced) before
+//! symbolization still resolve by file offset.
+
+use super::mount_ns::{open_mapping, parse_exec_mappings, ExecMapping};
+use anyhow::Result;
+use aperture_shared::types::events::{ProcessEvent, ProcessEventKind, ProfileEvent};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Event kinds (must match agent-ebpf/src/process_tracker.rs)
+pub const PROCESS_EXEC: u32 = 0;
+pub const PROCESS_EXIT: u32 = 1;
+pub const PROCESS_FORK: u32 = 2;
+
+/// Snapshots kept at most; exited processes are evicted oldest first.
+const MAX_TRACKED_PROCESSES: usize = 4096;
+
+/// Raw process event from eBPF (must match agent-ebpf/src/process_tracker.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessEventBpf {
+    pub timestamp: u64,
+    pub kind: u32,
+    pub pid: u32,
+    pub ppid: u32,
+    pub _pad: u32,
+    pub comm: [u8; 16],
+    pub filename: [u8; 256],
+}
+
+unsafe impl aya::Pod for ProcessEventBpf {}
+
+#[derive(Debug)]
+struct SnapshotMapping {
+    mapping: ExecMapping,
+    file: Arc<File>,
+}
+
+#[derive(Debug, Default)]
+struct ProcessSnapshot {
+    mappings: Vec<SnapshotMapping>,
+    exited: bool,
+    /// Changes every time the mappings are (re)read
+    generation: u64,
+}
+
+/// Where an address of a snapshotted process lives on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotLocation {
+    /// Path readable by this process that refers to the held file handle
+    pub path: PathBuf,
+    /// Original path of the mapped file in the target process
+    pub module: String,
+    /// Offset of the address within the file
+    pub file_offset: u64,
+}
+
+#[derive(Debug, Default)]
+struct TableInner {
+    processes: HashMap<i32, ProcessSnapshot>,
+    /// Shared handles keyed by (device, inode) so a library mapped by many
+    /// processes costs one descriptor
+    files: HashMap<(String, u64), Arc<File>>,
+    /// Exited PIDs in exit order, for eviction
+    exited: VecDeque<i32>,
+    /// Last snapshot generation handed out
+    generation: u64,
+}
+
+impl TableInner {
+    fn next_generation(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+}
+
+/// Mapping snapshots for tracked processes, shared between the process event
+/// reader and the symbolizers.
+#[derive(Debug, Default)]
+pub struct ProcessTable {
+    inner: Mutex<TableInner>,
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take (or refresh) the mapping snapshot of a live process. Returns false
+    /// if its maps could not be read.
+    pub fn snapshot(&self, pid: i32) -> bool {
+        let Ok(maps) = std::fs::read_to_string(format!("/proc/{}/maps", pid)) else {
+            return false;
+        };
+        let mappings = parse_exec_mappings(&maps);
+        if mappings.is_empty() {
+            return false;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let mut snapshot = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            let key = (mapping.dev.clone(), mapping.inode);
+            let file = match inner.files.get(&key) {
+                Some(file) => file.clone(),
+                None => {
+                    let Some((file, _)) = open_mapping(pid, &mapping) else {
+                        continue;
+                    };
+                    let file = Arc::new(file);
+                    inner.files.insert(key, file.clone());
+                    file
+                }
+            };
+            snapshot.push(SnapshotMapping { mapping, file });
+        }
+        debug!(pid, "Snapshotted {} executable mappings", snapshot.len());
+
+        let generation = inner.next_generation();
+        let entry = inner.processes.entry(pid).or_default();
+        entry.mappings = snapshot;
+        entry.exited = false;
+        entry.generation = generation;
+        inner.exited.retain(|&p| p != pid);
+        Self::evict(&mut inner);
+        true
+    }
+
+    /// Rebuild the snapshot of a recorded process, opening its mapped files
+    /// at their paths on this host. Mappings whose file is missing are
+    /// skipped; returns how many were restored.
+    pub fn restore(&self, pid: i32, mappings: Vec<ExecMapping>) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let mut snapshot = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            let key = (mapping.dev.clone(), mapping.inode);
+            let file = match inner.files.get(&key) {
+                Some(file) => file.clone(),
+                None => match File::open(&mapping.path) {
+                    Ok(file) => {
+                        let file = Arc::new(file);
+                        inner.files.insert(key, file.clone());
+                        file
+                    }
+                    Err(e) => {
+                        debug!(pid, "Cannot open {}: {}", mapping.path, e);
+                        continue;
+                    }
+                },
+            };
+            snapshot.push(SnapshotMapping { mapping, file });
+        }
+        let restored = snapshot.len();
+        let generation = inner.next_generation();
+        inner.processes.insert(
+            pid,
+            ProcessSnapshot {
+                mappings: snapshot,
+                exited: true,
+                generation,
+            },
+        );
+        inner.exited.push_back(pid);
+        Self::evict(&mut inner);
+        restored
+    }
+
+    /// Executable mappings of a snapshotted process
+    pub fn mappings(&self, pid: i32) -> Option<Vec<ExecMapping>> {
+        let inner = self.inner.lock().unwrap();
+        let snapshot = inner.processes.get(&pid)?;
+        Some(
+            snapshot
+                .mappings
+                .iter()
+                .map(|m| m.mapping.clone())
+                .collect(),
+        )
+    }
+
+    /// Copy a parent's snapshot to a forked child that could not be read itself.
+    pub fn inherit(&self, child: i32, parent: i32) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(parent) = inner.processes.get(&parent) else {
+            return;
+        };
+        let mappings = parent
+            .mappings
+            .iter()
+            .map(|m| SnapshotMapping {
+                mapping: m.mapping.clone(),
+                file: m.file.clone(),
+            })
+            .collect();
+        let generation = inner.next_generation();
+        inner.processes.insert(
+            child,
+            ProcessSnapshot {
+                mappings,
+                exited: false,
+                generation,
+            },
+        );
+        Self::evict(&mut inner);
+    }
+
+    /// Mark a process as exited; its snapshot is kept for symbolization.
+    pub fn mark_exited(&self, pid: i32) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(snapshot) = inner.processes.get_mut(&pid) {
+            if !snapshot.exited {
+                snapshot.exited = true;
+                inner.exited.push_back(pid);
+            }
+        }
+    }
+
+    /// Re-read mappings of tracked live processes, picking up libraries loaded
+    /// after exec. Processes whose maps are gone are marked exited.
+    pub fn refresh_alive(&self) {
+        let alive: Vec<i32> = {
+            let inner = self.inner.lock().unwrap();
+            inner
+                .processes
+                .iter()
+                .filter(|(_, s)| !s.exited)
+                .map(|(&pid, _)| pid)
+                .collect()
+        };
+        for pid in alive {
+            if !self.snapshot(pid) {
+                self.mark_exited(pid);
+            }
+        }
+    }
+
+    /// Locate `ip` in the snapshot of `pid`.
+    pub fn locate(&self, pid: i32, ip: u64) -> Option<SnapshotLocation> {
+        let inner = self.inner.lock().unwrap();
+        let snapshot = inner.processes.get(&pid)?;
+        snapshot
+            .mappings
+            .iter()
+            .find(|m| ip >= m.mapping.start && ip < m.mapping.end)
+            .map(|m| SnapshotLocation {
+                path: PathBuf::from(format!("/proc/self/fd/{}", m.file.as_raw_fd())),
+                module: m.mapping.path.clone(),
+                file_offset: ip - m.mapping.start + m.mapping.offset,
+            })
+    }
+
+    /// Generation of the snapshot of `pid`, which changes whenever its
+    /// mappings are read again; 0 when there is no snapshot
+    pub fn generation(&self, pid: i32) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.processes.get(&pid).map_or(0, |s| s.generation)
+    }
+
+    /// True when a snapshot exists for `pid`
+    pub fn contains(&self, pid: i32) -> bool {
+        self.inner.lock().unwrap().processes.contains_key(&pid)
+    }
+
+    /// Number of tracked processes
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().processes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn evict(inner: &mut TableInner) {
+        while inner.processes.len() > MAX_TRACKED_PROCESSES {
+            let Some(pid) = inner.exited.pop_front() else {
+                break;
+            };
+            inner.processes.remove(&pid);
+        }
+        // Close handles no snapshot refers to any more
+        inner.files.retain(|_, file| Arc::strong_count(file) > 1);
+    }
+}
+
+/// Process lifecycle event collector
+#[derive(Debug)]
+pub struct ProcessCollector {
+    /// Collected events
+    events: Vec<ProcessEvent>,
+
+    /// Mapping snapshots, shared with symbolizers
+    table: Arc<ProcessTable>,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+}
+
+impl Default for ProcessCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessCollector {
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            table: Arc::new(ProcessTable::new()),
+            push_cursor: 0,
+        }
+    }
+
+    /// Snapshot table fed by this collector
+    pub fn table(&self) -> Arc<ProcessTable> {
+        self.table.clone()
+    }
+
+    /// Process a raw eBPF event: update snapshots and record a ProcessEvent
+    pub fn process_event(&mut self, event: &ProcessEventBpf) -> Result<()> {
+        let pid = event.pid as i32;
+        let kind = match event.kind {
+            PROCESS_EXEC => {
+                self.table.snapshot(pid);
+                ProcessEventKind::Exec
+            }
+            PROCESS_EXIT => {
+                self.table.mark_exited(pid);
+                ProcessEventKind::Exit
+            }
+            PROCESS_FORK => {
+                // sched_process_fork also fires for new threads
+                if is_thread(pid) {
+                    return Ok(());
+                }
+                if !self.table.snapshot(pid) {
+                    self.table.inherit(pid, event.ppid as i32);
+                }
+                ProcessEventKind::Fork
+            }
+            other => anyhow::bail!("unknown process event kind {}", other),
+        };
+
+        let filename = (kind == ProcessEventKind::Exec)
+            .then(|| c_str(&event.filename))
+            .filter(|f| !f.is_empty());
+
+        self.events.push(ProcessEvent {
+            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+            pid,
+            ppid: event.ppid as i32,
+            kind,
+            comm: c_str(&event.comm),
+            filename,
+        });
+        Ok(())
+    }
+
+    /// All collected events
+    pub fn events(&self) -> &[ProcessEvent] {
+        &self.events
+    }
+
+    /// Take events not yet pushed (for streaming to aggregator). Advances the
+    /// push cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let pending: Vec<ProfileEvent> = self.events[self.push_cursor..]
+            .iter()
+            .cloned()
+            .map(ProfileEvent::Process)
+            .collect();
+        self.push_cursor = self.events.len();
+        pending
+    }
+}
+
+/// True when `tid` is a thread of another process (Tgid differs)
+fn is_thread(tid: i32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/status", tid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find(|l| l.starts_with("Tgid:"))
+                .and_then(|l| l.split_whitespace().nth(1))
+                .and_then(|v| v.parse::<i32>().ok())
+        })
+        .is_some_and(|tgid| tgid != tid)
+}
+
+fn c_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_survives_exit() {
+        let pid = std::process::id() as i32;
+        let ip = test_snapshot_survives_exit as *const () as u64;
+        let table = ProcessTable::new();
+        assert_eq!(table.generation(pid), 0);
+        assert!(table.snapshot(pid));
+        let generation = table.generation(pid);
+        assert!(generation > 0);
+        assert!(table.snapshot(pid));
+        assert!(table.generation(pid) > generation);
+
+        table.mark_exited(pid);
+        let location = table.locate(pid, ip).expect("own text is mapped");
+        assert!(location.path.exists());
+        assert!(location.file_offset > 0);
+        assert!(table.locate(pid, 0x10).is_none());
+    }
+
+    #[test]
+    fn test_restore_recorded_mappings() {
+        let pid = std::process::id() as i32;
+        let ip = test_restore_recorded_mappings as *const () as u64;
+        let table = ProcessTable::new();
+        assert!(table.snapshot(pid));
+        let recorded = table.mappings(pid).unwrap();
+
+        // Replayed under a PID that no live process may own
+        let replay = ProcessTable::new();
+        assert_eq!(replay.restore(-pid, recorded.clone()), recorded.len());
+        assert_eq!(
+            replay.locate(-pid, ip).unwrap().file_offset,
+            table.locate(pid, ip).unwrap().file_offset
+        );
+        assert!(replay.mappings(pid).is_none());
+    }
+
+    #[test]
+    fn test_fork_of_thread_is_ignored() {
+        let mut collector = ProcessCollector::new();
+        let tid = unsafe { libc::gettid() };
+        if tid == std::process::id() as i32 {
+            return; // running on the main thread
+        }
+        let event = ProcessEventBpf {
+            timestamp: 0,
+            kind: PROCESS_FORK,
+            pid: tid as u32,
+            ppid: std::process::id(),
+            _pad: 0,
+            comm: [0; 16],
+            filename: [0; 256],
+        };
+        collector.process_event(&event).unwrap();
+        assert!(collector.events().is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+//! Scheduler run-queue event collector
+//!
+//! Collects run-queue waits from the scheduler tracer and builds per-process
+//! and per-cgroup latency profiles. Wakeups only carry the woken thread's
+//! id, so threads not yet seen running are mapped to their process and
+//! cgroup through `/proc`.
+
+use anyhow::Result;
+use aperture_shared::types::events::{ProfileEvent, SchedEvent};
+use aperture_shared::types::profile::SchedProfile;
+use aperture_shared::utils::arch::is_kernel_ip;
+use aya::maps::StackTraceMap;
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+/// Raw run-queue wait from eBPF (must match agent-ebpf/src/sched_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SchedEventBpf {
+    pub timestamp: u64,
+    pub delay_ns: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub waker_pid: u32,
+    pub waker_tid: u32,
+    pub prev_tid: u32,
+    pub cpu: u32,
+    pub preempted: u32,
+    pub _pad: u32,
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
+    pub comm: [u8; 16],
+    pub prev_comm: [u8; 16],
+}
+
+// Implement traits for reading from perf buffer
+unsafe impl aya::Pod for SchedEventBpf {}
+
+/// Process and cgroup of a thread
+#[derive(Debug, Clone, Default)]
+struct ThreadInfo {
+    pid: Option<i32>,
+    cgroup: Option<String>,
+}
+
+/// Scheduler event collector
+#[derive(Debug)]
+pub struct SchedCollector {
+    /// Collected events
+    events: Vec<SchedEvent>,
+
+    /// Start time
+    start_time: u64,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+
+    /// tid -> process and cgroup, looked up once per thread
+    threads: HashMap<u32, ThreadInfo>,
+}
+
+impl Default for SchedCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedCollector {
+    /// Create a new scheduler collector
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            start_time: aperture_shared::utils::time::system_time_nanos(),
+            push_cursor: 0,
+            threads: HashMap::new(),
+        }
+    }
+
+    /// Add an event to the collector
+    pub fn add_event(&mut self, event: SchedEvent) {
+        self.events.push(event);
+    }
+
+    /// Process a raw eBPF event and convert to SchedEvent
+    pub fn process_event(
+        &mut self,
+        event: &SchedEventBpf,
+        stacks: &StackTraceMap<aya::maps::MapData>,
+    ) -> Result<()> {
+        // User stack first, then kernel, as for lock events
+        let mut stack_trace = read_stack(stacks, event.user_stack_id);
+        stack_trace.extend(read_stack(stacks, event.kernel_stack_id));
+        self.convert_event(event, stack_trace);
+        Ok(())
+    }
+
+    fn convert_event(&mut self, event: &SchedEventBpf, stack_trace: Vec<u64>) {
+        let thread = self
+            .threads
+            .entry(event.tid)
+            .or_insert_with(|| thread_info(event.tid));
+        if event.pid != 0 {
+            thread.pid = Some(event.pid as i32);
+        }
+
+        let preemptor = (event.prev_tid != 0).then(|| {
+            let comm = comm_str(&event.prev_comm);
+            (event.prev_tid as i32, comm)
+        });
+
+        // Threads that exited before being looked up count as their own process
+        let pid = thread.pid.unwrap_or(event.tid as i32);
+        let cgroup = thread.cgroup.clone();
+        self.add_event(SchedEvent {
+            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+            pid,
+            tid: event.tid as i32,
+            comm: comm_str(&event.comm),
+            cpu_id: event.cpu,
+            delay_ns: event.delay_ns,
+            preempted: event.preempted != 0,
+            waker_pid: event.waker_pid as i32,
+            waker_tid: event.waker_tid as i32,
+            cgroup,
+            preemptor_tid: preemptor.as_ref().map(|(tid, _)| *tid),
+            preemptor_comm: preemptor.map(|(_, comm)| comm),
+            stack_trace,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        });
+    }
+
+    /// Build aggregated profile from collected events
+    pub fn build_profile(&self) -> Result<SchedProfile> {
+        info!(
+            "Building scheduler profile from {} events",
+            self.events.len()
+        );
+
+        let mut profile = SchedProfile::new(self.start_time);
+        profile.end_time = aperture_shared::utils::time::system_time_nanos();
+
+        for event in &self.events {
+            profile.add_event(event);
+        }
+
+        info!(
+            "Scheduler profile built: {} total events, {} processes, {} cgroups",
+            profile.total_events,
+            profile.processes.len(),
+            profile.cgroups.len()
+        );
+
+        Ok(profile)
+    }
+
+    /// User-space IPs of wakeup stacks grouped by the waking process, so the
+    /// symbolizer can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for ev in self.events.iter().filter(|ev| !ev.stack_trace.is_empty()) {
+            let ips = by_pid.entry(ev.waker_pid).or_default();
+            for &ip in &ev.stack_trace {
+                if !is_kernel_ip(ip) && !ips.contains(&ip) {
+                    ips.push(ip);
+                }
+            }
+        }
+        by_pid
+    }
+
+    /// Return events accumulated since the last call and advance the cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let events: Vec<ProfileEvent> = self.events[self.push_cursor..]
+            .iter()
+            .cloned()
+            .map(ProfileEvent::Sched)
+            .collect();
+        self.push_cursor = self.events.len();
+        events
+    }
+}
+
+fn comm_str(comm: &[u8; 16]) -> String {
+    std::str::from_utf8(comm)
+        .unwrap_or("<unknown>")
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Process and cgroup of thread `tid` from `/proc/<tid>/status` and
+/// `/proc/<tid>/cgroup` (threads are reachable by tid even though `/proc`
+/// only lists processes)
+fn thread_info(tid: u32) -> ThreadInfo {
+    let read = |file: &str| std::fs::read_to_string(format!("/proc/{}/{}", tid, file)).ok();
+    ThreadInfo {
+        pid: read("status").as_deref().and_then(parse_tgid),
+        cgroup: read("cgroup").as_deref().and_then(parse_cgroup),
+    }
+}
+
+/// `Tgid` of a `/proc/<tid>/status` file
+fn parse_tgid(status: &str) -> Option<i32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Tgid:"))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// cgroup path of a `/proc/<tid>/cgroup` file: the unified (v2) hierarchy's
+/// `0::/path` line, else the v1 hierarchy holding the `cpu` controller
+fn parse_cgroup(cgroups: &str) -> Option<String> {
+    let entries: Vec<(&str, &str)> = cgroups
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let _id = parts.next()?;
+            Some((parts.next()?, parts.next()?))
+        })
+        .collect();
+    entries
+        .iter()
+        .find(|(controllers, _)| controllers.is_empty())
+        .or_else(|| {
+            entries
+                .iter()
+                .find(|(controllers, _)| controllers.split(',').any(|c| c == "cpu"))
+        })
+        .map(|(_, path)| path.to_string())
+}
+
+/// IPs of stack `id` in `stacks`, empty when none was captured
+fn read_stack(stacks: &StackTraceMap<aya::maps::MapData>, id: i64) -> Vec<u64> {
+    if id < 0 {
+        return Vec::new();
+    }
+    match stacks.get(&(id as u32), 0) {
+        Ok(trace) => trace.frames().iter().map(|f| f.ip).collect(),
+        Err(e) => {
+            debug!("Failed to get wakeup stack {}: {}", id, e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(tid: u32, pid: u32, delay_ns: u64, prev: Option<(u32, &[u8])>) -> SchedEventBpf {
+        let mut event = SchedEventBpf {
+            timestamp: 0,
+            delay_ns,
+            pid,
+            tid,
+            waker_pid: 1,
+            waker_tid: 1,
+            prev_tid: 0,
+            cpu: 2,
+            preempted: 0,
+            _pad: 0,
+            user_stack_id: -1,
+            kernel_stack_id: -1,
+            comm: [0; 16],
+            prev_comm: [0; 16],
+        };
+        event.comm[..6].copy_from_slice(b"worker");
+        if let Some((prev_tid, prev_comm)) = prev {
+            event.prev_tid = prev_tid;
+            event.prev_comm[..prev_comm.len()].copy_from_slice(prev_comm);
+        }
+        event
+    }
+
+    #[test]
+    fn test_parse_proc_files() {
+        assert_eq!(
+            parse_tgid("Name:\tworker\nTgid:\t4242\nPid:\t4250\n"),
+            Some(4242)
+        );
+        assert_eq!(parse_tgid("Name:\tworker\n"), None);
+
+        assert_eq!(
+            parse_cgroup("0::/system.slice/nginx.service\n").as_deref(),
+            Some("/system.slice/nginx.service")
+        );
+        let v1 = "12:memory:/docker/abc\n4:cpu,cpuacct:/docker/abc\n1:name=systemd:/init.scope\n";
+        assert_eq!(parse_cgroup(v1).as_deref(), Some("/docker/abc"));
+        assert_eq!(parse_cgroup(""), None);
+    }
+
+    #[test]
+    fn test_sched_collector() {
+        let mut collector = SchedCollector::new();
+        // Thread ids with no /proc entry: the kernel's pid is kept
+        collector.convert_event(&raw(4_000_001, 4_000_000, 50_000, None), vec![]);
+        collector.convert_event(
+            &raw(4_000_001, 0, 30_000_000, Some((77, b"ffmpeg"))),
+            vec![0x401000],
+        );
+        // Never seen running nor in /proc: counted as its own process
+        collector.convert_event(&raw(4_000_002, 0, 1_000, None), vec![]);
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.total_events, 3);
+        let process = &profile.processes[&4_000_000];
+        assert_eq!(process.runq.count, 2);
+        assert_eq!(process.comm, "worker");
+        assert_eq!(process.preemptors["ffmpeg"].count, 1);
+        assert_eq!(process.wakeup_stacks.len(), 1);
+        assert_eq!(profile.processes[&4_000_002].runq.count, 1);
+        assert_eq!(collector.user_ips_by_pid()[&1], vec![0x401000]);
+
+        assert_eq!(collector.take_pending_events().len(), 3);
+        assert!(collector.take_pending_events().is_empty());
+    }
+}
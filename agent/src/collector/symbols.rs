@@ -2,13 +2,49 @@
 //!
 //! Resolves instruction pointers to function names, file names, and line numbers
 
+use super::disk_cache::DiskSymbolCache;
+use super::frame_refs::FrameRefCache;
+use super::jit::{JitResolver, JIT_MODULE};
+use super::mount_ns::in_other_mount_ns;
+use super::normalize::FrameNormalizer;
+use super::process::ProcessTable;
+use crate::config::SymbolizeMode;
 use anyhow::Result;
-use aperture_shared::types::profile::{Frame, LockProfile, Profile, Stack};
-use blazesym::symbolize::source::{Kernel, Process, Source};
-use blazesym::symbolize::{Input, Symbolized, Symbolizer};
+use aperture_shared::types::events::{FrameRef, Symbol};
+use aperture_shared::types::profile::{
+    Frame, KernelLockProfile, LockProfile, ProbeProfile, Profile, SchedProfile, Stack,
+    SyscallProfile,
+};
+use aperture_shared::utils::arch::is_kernel_ip;
+use blazesym::symbolize::source::{Elf, Kernel, Process, Source};
+use blazesym::symbolize::{CodeInfo, Input, Sym, Symbolized, Symbolizer};
 use blazesym::Pid;
-use std::collections::HashMap;
-use tracing::{debug, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// How many of one process's user-space frames resolved to a name
+#[derive(Debug, Clone, PartialEq)]
+pub struct PidSymbolStats {
+    pub pid: i32,
+    /// Unique user IPs that resolved to a function name
+    pub resolved: usize,
+    /// Unique user IPs sampled in the process
+    pub total: usize,
+    /// The process runs in another mount namespace (container)
+    pub container: bool,
+}
+
+impl PidSymbolStats {
+    /// Fraction of frames resolved, 0.0..=1.0
+    pub fn success_rate(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.resolved as f64 / self.total as f64
+    }
+}
 
 /// Symbol resolver using blazesym
 pub struct SymbolResolver {
@@ -17,6 +53,28 @@ pub struct SymbolResolver {
 
     /// Cache of resolved symbols: IP -> Frame
     cache: HashMap<u64, Frame>,
+
+    /// Functions inlined at an IP, innermost first: IP -> inline frames
+    inlined: HashMap<u64, Vec<Frame>>,
+
+    /// Perf map / jitdump symbols for JIT-compiled code
+    jit: JitResolver,
+
+    /// Mapping snapshots: taken at exec time by the process tracker, or on
+    /// demand for targets whose binaries are not visible at their mapped paths
+    processes: Arc<ProcessTable>,
+    snapshot_misses: SnapshotMisses,
+
+    /// Owning PIDs of user IPs, recorded at collection time
+    ip_owners: HashMap<i32, Vec<u64>>,
+
+    /// Persistent cache keyed by build ID + file offset, shared across runs
+    disk: Option<Arc<DiskSymbolCache>>,
+    disk_refs: FrameRefCache,
+
+    /// Resolve user frames from the mapping snapshots only, never from live
+    /// processes (replayed captures, whose PIDs may have been reused)
+    offline: bool,
 }
 
 impl SymbolResolver {
@@ -25,9 +83,39 @@ impl SymbolResolver {
         Self {
             symbolizer: Symbolizer::new(),
             cache: HashMap::new(),
+            inlined: HashMap::new(),
+            jit: JitResolver::new(),
+            processes: Arc::new(ProcessTable::new()),
+            snapshot_misses: SnapshotMisses::default(),
+            ip_owners: HashMap::new(),
+            disk: None,
+            disk_refs: FrameRefCache::new(),
+            offline: false,
         }
     }
 
+    /// Resolve frames of exited processes from the process tracker's snapshots
+    pub fn set_process_table(&mut self, table: Arc<ProcessTable>) {
+        self.processes = table;
+    }
+
+    /// Resolve user frames from the process table only: no live process
+    /// maps, JIT symbols or scan of running processes
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Look up and store user frames in a persistent on-disk cache
+    pub fn set_disk_cache(&mut self, disk: Arc<DiskSymbolCache>) {
+        self.disk = Some(disk);
+    }
+
+    /// Record which process each user IP came from, so system-wide
+    /// resolution can look up the owner directly instead of scanning /proc.
+    pub fn set_user_ip_owners(&mut self, owners: HashMap<i32, Vec<u64>>) {
+        self.ip_owners = owners;
+    }
+
     /// Symbolize a profile by resolving all instruction pointers.
     ///
     /// Splits IPs into kernel (high addresses) vs user (low addresses) and resolves
@@ -43,7 +131,7 @@ impl SymbolResolver {
                 if self.cache.contains_key(&ip) {
                     continue;
                 }
-                if ip >= 0xffff_0000_0000_0000 {
+                if is_kernel_ip(ip) {
                     if !kernel_ips.contains(&ip) {
                         kernel_ips.push(ip);
                     }
@@ -68,12 +156,9 @@ impl SymbolResolver {
 
         // Resolve user IPs
         if !user_ips.is_empty() {
-            if let Some(pid) = pid {
-                if let Err(e) = self.symbolize_ips(&user_ips, Some(pid)) {
-                    warn!("Failed to symbolize user IPs for PID {}: {}", pid, e);
-                }
-            } else {
-                self.resolve_user_ips_systemwide(&user_ips);
+            match pid {
+                Some(pid) => self.resolve_pid_ips(&user_ips, pid),
+                None => self.resolve_user_ips_systemwide(&user_ips),
             }
         }
 
@@ -95,19 +180,69 @@ impl SymbolResolver {
         pid: Option<i32>,
     ) -> Result<()> {
         debug!(
-            "Symbolizing {} unique contention stacks",
+            "Symbolizing {} unique contention and {} holder stacks",
+            profile.contentions.len(),
+            profile.holders.len()
+        );
+
+        let stacks = profile.contentions.keys().chain(profile.holders.keys());
+        self.resolve_stack_frames(stacks.map(|(_, stack)| stack), pid);
+
+        // Replace stacks
+        let mut new_contentions = HashMap::new();
+        for ((lock_addr, stack), stats) in profile.contentions.drain() {
+            let symbolized_stack = self.symbolize_stack(&stack);
+            new_contentions.insert((lock_addr, symbolized_stack), stats);
+        }
+        profile.contentions = new_contentions;
+        let mut new_holders = HashMap::new();
+        for ((lock_addr, stack), stats) in profile.holders.drain() {
+            let symbolized_stack = self.symbolize_stack(&stack);
+            new_holders.insert((lock_addr, symbolized_stack), stats);
+        }
+        profile.holders = new_holders;
+
+        Ok(())
+    }
+
+    /// Symbolize the stacks of a kernel lock profile
+    pub fn symbolize_kernel_lock_profile(
+        &mut self,
+        profile: &mut KernelLockProfile,
+        pid: Option<i32>,
+    ) -> Result<()> {
+        debug!(
+            "Symbolizing {} unique kernel lock contention stacks",
             profile.contentions.len()
         );
+        self.resolve_stack_frames(profile.contentions.keys().map(|(_, stack)| stack), pid);
+
+        let mut new_contentions = HashMap::new();
+        for ((lock_addr, stack), stats) in profile.contentions.drain() {
+            let symbolized_stack = self.symbolize_stack(&stack);
+            new_contentions.insert((lock_addr, symbolized_stack), stats);
+        }
+        profile.contentions = new_contentions;
+
+        Ok(())
+    }
 
+    /// Resolve the uncached frames of combined user+kernel `stacks`, telling
+    /// kernel from user addresses by range
+    fn resolve_stack_frames<'a>(
+        &mut self,
+        stacks: impl Iterator<Item = &'a Stack>,
+        pid: Option<i32>,
+    ) {
         let mut user_ips: Vec<u64> = Vec::new();
         let mut kernel_ips: Vec<u64> = Vec::new();
-        for (_, stack) in profile.contentions.keys() {
+        for stack in stacks {
             for frame in &stack.frames {
                 let ip = frame.ip;
                 if self.cache.contains_key(&ip) {
                     continue;
                 }
-                if ip >= 0xffff_0000_0000_0000 {
+                if is_kernel_ip(ip) {
                     if !kernel_ips.contains(&ip) {
                         kernel_ips.push(ip);
                     }
@@ -123,36 +258,124 @@ impl SymbolResolver {
             }
         }
         if !user_ips.is_empty() {
-            if let Some(pid) = pid {
-                if let Err(e) = self.symbolize_ips(&user_ips, Some(pid)) {
-                    warn!("Failed to symbolize user IPs for PID {}: {}", pid, e);
+            match pid {
+                Some(pid) => self.resolve_pid_ips(&user_ips, pid),
+                None => self.resolve_user_ips_systemwide(&user_ips),
+            }
+        }
+    }
+
+    /// Symbolize the slow-call stacks of a syscall profile (same kernel/user
+    /// split as symbolize_profile)
+    pub fn symbolize_syscall_profile(
+        &mut self,
+        profile: &mut SyscallProfile,
+        pid: Option<i32>,
+    ) -> Result<()> {
+        debug!(
+            "Symbolizing {} unique slow syscall stacks",
+            profile.slow_stack_count()
+        );
+
+        let mut user_ips: Vec<u64> = Vec::new();
+        let mut kernel_ips: Vec<u64> = Vec::new();
+        let stacks = profile
+            .syscalls
+            .values()
+            .flat_map(|stats| stats.slow_stacks.iter());
+        for slow in stacks {
+            for frame in &slow.stack.frames {
+                let ip = frame.ip;
+                if self.cache.contains_key(&ip) {
+                    continue;
+                }
+                if is_kernel_ip(ip) {
+                    if !kernel_ips.contains(&ip) {
+                        kernel_ips.push(ip);
+                    }
+                } else if !user_ips.contains(&ip) {
+                    user_ips.push(ip);
                 }
-            } else {
-                self.resolve_user_ips_systemwide(&user_ips);
             }
         }
 
-        // Replace stacks
-        let mut new_contentions = HashMap::new();
-        for ((lock_addr, stack), stats) in profile.contentions.drain() {
-            let symbolized_stack = self.symbolize_stack(&stack);
-            new_contentions.insert((lock_addr, symbolized_stack), stats);
+        if !kernel_ips.is_empty() {
+            if let Err(e) = self.symbolize_ips(&kernel_ips, None) {
+                warn!("Failed to symbolize kernel IPs: {}", e);
+            }
+        }
+        if !user_ips.is_empty() {
+            match pid {
+                Some(pid) => self.resolve_pid_ips(&user_ips, pid),
+                None => self.resolve_user_ips_systemwide(&user_ips),
+            }
+        }
+
+        for stats in profile.syscalls.values_mut() {
+            stats.map_slow_stacks(|stack| self.symbolize_stack(stack));
+        }
+
+        Ok(())
+    }
+
+    /// Symbolize the wakeup stacks of a scheduler profile (same kernel/user
+    /// split as symbolize_profile)
+    pub fn symbolize_sched_profile(
+        &mut self,
+        profile: &mut SchedProfile,
+        pid: Option<i32>,
+    ) -> Result<()> {
+        debug!(
+            "Symbolizing {} unique wakeup stacks",
+            profile.wakeup_stack_count()
+        );
+        let stacks = profile
+            .processes
+            .values()
+            .flat_map(|p| p.wakeup_stacks.iter().map(|s| &s.stack));
+        self.resolve_stack_frames(stacks, pid);
+
+        for process in profile.processes.values_mut() {
+            process.map_wakeup_stacks(|stack| self.symbolize_stack(stack));
+        }
+
+        Ok(())
+    }
+
+    /// Symbolize the stacks of every probe in place
+    pub fn symbolize_probe_profile(
+        &mut self,
+        profile: &mut ProbeProfile,
+        pid: Option<i32>,
+    ) -> Result<()> {
+        debug!("Symbolizing {} unique probe stacks", profile.stack_count());
+        let stacks = profile
+            .probes
+            .values()
+            .flat_map(|p| p.stacks.iter().map(|s| &s.stack));
+        self.resolve_stack_frames(stacks, pid);
+
+        for probe in profile.probes.values_mut() {
+            probe.map_stacks(|stack| self.symbolize_stack(stack));
         }
-        profile.contentions = new_contentions;
 
         Ok(())
     }
 
-    /// Symbolize a stack by looking up each frame
+    /// Symbolize a stack by looking up each frame. Functions inlined at a
+    /// frame's IP are expanded into their own frames ahead of it.
     fn symbolize_stack(&self, stack: &Stack) -> Stack {
         let symbolized_frames: Vec<Frame> = stack
             .frames
             .iter()
-            .map(|frame| {
-                self.cache
+            .flat_map(|frame| {
+                let inlined = self.inlined.get(&frame.ip).into_iter().flatten().cloned();
+                let frame = self
+                    .cache
                     .get(&frame.ip)
                     .cloned()
-                    .unwrap_or_else(|| frame.clone())
+                    .unwrap_or_else(|| frame.clone());
+                inlined.chain(std::iter::once(frame))
             })
             .collect();
 
@@ -184,31 +407,18 @@ impl SymbolResolver {
 
                     let frame = match result {
                         Symbolized::Sym(sym) => {
-                            // Successfully symbolized
-                            Frame {
-                                ip,
-                                function: Some(sym.name.to_string()),
-                                file: sym
-                                    .module
-                                    .as_ref()
-                                    .and_then(|m| m.to_str())
-                                    .map(String::from),
-                                line: None, // Line info not available in this API version
-                                module: sym
-                                    .module
-                                    .as_ref()
-                                    .and_then(|m| m.to_str())
-                                    .map(String::from),
+                            // Successfully symbolized, with source location and inlined callees
+                            let (frame, inlined) = sym_frames(ip, sym, None);
+                            if !inlined.is_empty() {
+                                self.inlined.insert(ip, inlined);
                             }
+                            frame
                         }
                         Symbolized::Unknown(_) => {
                             // Could not symbolize - use hex address as function name
                             Frame {
-                                ip,
                                 function: Some(format!("0x{:x}", ip)),
-                                file: None,
-                                line: None,
-                                module: None,
+                                ..Frame::new_unresolved(ip)
                             }
                         }
                     };
@@ -221,11 +431,8 @@ impl SymbolResolver {
                 // Add unresolved frames to cache
                 for &ip in ips {
                     self.cache.entry(ip).or_insert_with(|| Frame {
-                        ip,
                         function: Some(format!("0x{:x}", ip)),
-                        file: None,
-                        line: None,
-                        module: None,
+                        ..Frame::new_unresolved(ip)
                     });
                 }
             }
@@ -234,21 +441,111 @@ impl SymbolResolver {
         Ok(())
     }
 
+    /// Resolve user IPs of `pid`: persistent cache first, then the live
+    /// process, its mapping snapshot and JIT symbols. Newly resolved frames
+    /// are added to the persistent cache.
+    fn resolve_pid_ips(&mut self, ips: &[u64], pid: i32) {
+        let pending = load_from_disk(
+            self.disk.as_deref(),
+            &mut self.disk_refs,
+            &mut self.cache,
+            &mut self.inlined,
+            pid,
+            ips,
+        );
+        if pending.is_empty() {
+            return;
+        }
+        if !self.offline && Path::new(&format!("/proc/{}", pid)).exists() {
+            if let Err(e) = self.symbolize_ips(&pending, Some(pid)) {
+                warn!("Failed to symbolize user IPs for PID {}: {}", pid, e);
+            }
+        }
+        self.resolve_snapshot_ips(&pending, pid);
+        if !self.offline {
+            self.resolve_jit_ips(&pending, pid);
+        }
+        store_to_disk(
+            self.disk.as_deref(),
+            &mut self.disk_refs,
+            &self.cache,
+            &self.inlined,
+            pid,
+            &pending,
+        );
+    }
+
+    /// Resolve IPs that blazesym left unresolved against the process's JIT
+    /// symbols (perf map / jitdump). Re-reads newly appended JIT entries first.
+    fn resolve_jit_ips(&mut self, ips: &[u64], pid: i32) {
+        self.jit.refresh(pid);
+        if !self.jit.has_symbols(pid) {
+            return;
+        }
+        for &ip in ips {
+            if self.cache.get(&ip).is_some_and(|f| !is_unresolved(f)) {
+                continue;
+            }
+            if let Some(name) = self.jit.lookup(pid, ip) {
+                self.cache.insert(ip, jit_frame(ip, name));
+            }
+        }
+    }
+
+    /// Resolve IPs that are still unresolved against the mapping snapshot of
+    /// `pid`: the exec-time one for exited processes, or a fresh one that
+    /// reaches container binaries through `/proc/PID/map_files` and `/proc/PID/root`.
+    fn resolve_snapshot_ips(&mut self, ips: &[u64], pid: i32) {
+        let refresh = if self.offline {
+            SnapshotRefresh::Never
+        } else {
+            SnapshotRefresh::Inline
+        };
+        resolve_from_snapshot(
+            &self.symbolizer,
+            &self.processes,
+            &mut self.snapshot_misses,
+            &mut self.cache,
+            &mut self.inlined,
+            pid,
+            ips,
+            refresh,
+        );
+    }
+
     /// Best-effort resolution of userspace IPs when no target PID is specified.
     ///
-    /// Scans /proc for running process PIDs and tries each one until all IPs are
-    /// resolved (or we run out of PIDs to try). Each process may only resolve the
-    /// IPs that belong to its address space.
+    /// IPs whose owning process is known are resolved directly against that
+    /// process (live maps, then its exec-time snapshot). Only the rest fall
+    /// back to scanning /proc and trying each running process until all IPs
+    /// are resolved (or we run out of PIDs to try).
     fn resolve_user_ips_systemwide(&mut self, ips: &[u64]) {
+        use std::collections::HashSet;
         use std::fs;
 
+        let wanted: HashSet<u64> = ips.iter().copied().collect();
+        let mut owned: Vec<(i32, Vec<u64>)> = Vec::new();
+        for (&pid, owner_ips) in &self.ip_owners {
+            let mine: Vec<u64> = owner_ips
+                .iter()
+                .filter(|ip| wanted.contains(ip) && !self.cache.contains_key(ip))
+                .copied()
+                .collect();
+            if !mine.is_empty() {
+                owned.push((pid, mine));
+            }
+        }
+        for (pid, owner_ips) in owned {
+            self.resolve_pid_ips(&owner_ips, pid);
+        }
+
         let unresolved: Vec<u64> = ips
             .iter()
             .filter(|ip| !self.cache.contains_key(ip))
             .copied()
             .collect();
 
-        if unresolved.is_empty() {
+        if unresolved.is_empty() || self.offline {
             return;
         }
 
@@ -323,7 +620,10 @@ impl SymbolResolver {
         events: &mut [aperture_shared::types::events::ProfileEvent],
         pid: Option<i32>,
     ) {
-        use aperture_shared::types::events::ProfileEvent;
+        use aperture_shared::types::events::{
+            KernelLockEvent, LockEvent, PageFaultEvent, ProbeEvent, ProfileEvent, SchedEvent,
+            SyscallEvent,
+        };
 
         // 1. Collect all unique IPs that need resolution, separated by address space
         let mut user_ips: Vec<u64> = Vec::new();
@@ -342,13 +642,18 @@ impl SymbolResolver {
                         }
                     }
                 }
-                ProfileEvent::Lock(ev) => {
-                    for &ip in &ev.stack_trace {
-                        // Lock stacks combine user+kernel; classify by address range
+                ProfileEvent::Lock(LockEvent { stack_trace, .. })
+                | ProfileEvent::Syscall(SyscallEvent { stack_trace, .. })
+                | ProfileEvent::KernelLock(KernelLockEvent { stack_trace, .. })
+                | ProfileEvent::Sched(SchedEvent { stack_trace, .. })
+                | ProfileEvent::PageFault(PageFaultEvent { stack_trace, .. })
+                | ProfileEvent::Probe(ProbeEvent { stack_trace, .. }) => {
+                    for &ip in stack_trace {
+                        // Lock and syscall stacks combine user+kernel; classify by address range
                         if self.cache.contains_key(&ip) {
                             continue;
                         }
-                        let is_kernel = ip >= 0xffff_0000_0000_0000;
+                        let is_kernel = is_kernel_ip(ip);
                         if is_kernel {
                             if !kernel_ips.contains(&ip) {
                                 kernel_ips.push(ip);
@@ -374,34 +679,123 @@ impl SymbolResolver {
                 warn!("Failed to symbolize kernel IPs: {}", e);
             }
         }
+        // JIT fallback for anything still unresolved, using each event's own PID
+        for (ev_pid, ips) in unresolved_user_ips_by_pid(events, &self.cache) {
+            self.resolve_jit_ips(&ips, ev_pid);
+        }
 
         // 4. Populate symbol fields on each event
         for event in events.iter_mut() {
             match event {
                 ProfileEvent::CpuSample(s) => {
-                    s.user_stack_symbols = s
-                        .user_stack
-                        .iter()
-                        .map(|ip| self.cache.get(ip).and_then(|f| f.function.clone()))
-                        .collect();
+                    s.user_stack_symbols =
+                        s.user_stack.iter().map(|&ip| self.symbol_for(ip)).collect();
                     s.kernel_stack_symbols = s
                         .kernel_stack
                         .iter()
-                        .map(|ip| self.cache.get(ip).and_then(|f| f.function.clone()))
+                        .map(|&ip| self.symbol_for(ip))
                         .collect();
                 }
-                ProfileEvent::Lock(ev) => {
-                    ev.stack_symbols = ev
-                        .stack_trace
-                        .iter()
-                        .map(|ip| self.cache.get(ip).and_then(|f| f.function.clone()))
-                        .collect();
+                ProfileEvent::Lock(LockEvent {
+                    stack_trace,
+                    stack_symbols,
+                    ..
+                })
+                | ProfileEvent::Syscall(SyscallEvent {
+                    stack_trace,
+                    stack_symbols,
+                    ..
+                })
+                | ProfileEvent::KernelLock(KernelLockEvent {
+                    stack_trace,
+                    stack_symbols,
+                    ..
+                })
+                | ProfileEvent::Sched(SchedEvent {
+                    stack_trace,
+                    stack_symbols,
+                    ..
+                })
+                | ProfileEvent::PageFault(PageFaultEvent {
+                    stack_trace,
+                    stack_symbols,
+                    ..
+                })
+                | ProfileEvent::Probe(ProbeEvent {
+                    stack_trace,
+                    stack_symbols,
+                    ..
+                }) => {
+                    *stack_symbols = stack_trace.iter().map(|&ip| self.symbol_for(ip)).collect();
                 }
                 _ => {}
             }
         }
     }
 
+    /// Wire symbol for `ip`, including inlined callees and source locations
+    fn symbol_for(&self, ip: u64) -> Option<Symbol> {
+        frame_chain(&self.cache, &self.inlined, ip)
+            .and_then(|chain| Frame::to_symbol(chain, |f| f.function.clone()))
+    }
+
+    /// Per-PID symbolization success for the owners recorded with
+    /// `set_user_ip_owners`, largest processes first.
+    pub fn user_symbol_stats(&self) -> Vec<PidSymbolStats> {
+        let mut stats: Vec<PidSymbolStats> = self
+            .ip_owners
+            .iter()
+            .filter(|(_, ips)| !ips.is_empty())
+            .map(|(&pid, ips)| PidSymbolStats {
+                pid,
+                resolved: ips
+                    .iter()
+                    .filter(|ip| self.cache.get(ip).is_some_and(|f| !is_unresolved(f)))
+                    .count(),
+                total: ips.len(),
+                container: in_other_mount_ns(pid),
+            })
+            .collect();
+        stats.sort_by(|a, b| b.total.cmp(&a.total).then(a.pid.cmp(&b.pid)));
+        stats
+    }
+
+    /// Log per-PID symbolization success rates, flagging poorly resolved processes.
+    pub fn report_user_symbol_stats(&self) {
+        const MAX_REPORTED: usize = 10;
+        const POOR_RATE: f64 = 0.5;
+
+        let stats = self.user_symbol_stats();
+        for s in stats.iter().take(MAX_REPORTED) {
+            let origin = if s.container { " (container)" } else { "" };
+            if s.success_rate() < POOR_RATE {
+                warn!(
+                    "PID {}{}: {}/{} user frames symbolized ({:.0}%)",
+                    s.pid,
+                    origin,
+                    s.resolved,
+                    s.total,
+                    s.success_rate() * 100.0
+                );
+            } else {
+                info!(
+                    "PID {}{}: {}/{} user frames symbolized ({:.0}%)",
+                    s.pid,
+                    origin,
+                    s.resolved,
+                    s.total,
+                    s.success_rate() * 100.0
+                );
+            }
+        }
+        if stats.len() > MAX_REPORTED {
+            debug!(
+                "{} more processes not listed in symbolization report",
+                stats.len() - MAX_REPORTED
+            );
+        }
+    }
+
     /// Get cache size (number of resolved symbols)
     pub fn cache_size(&self) -> usize {
         self.cache.len()
@@ -421,6 +815,22 @@ impl Default for SymbolResolver {
 /// (which IS Send) and creates a temporary `Symbolizer` on each resolution call.
 pub struct SymbolCache {
     cache: HashMap<u64, Frame>,
+    /// Functions inlined at an IP, innermost first
+    inlined: HashMap<u64, Vec<Frame>>,
+    jit: JitResolver,
+    /// Set in deferred mode: user frames with a build ID ship as refs instead of names
+    frame_refs: Option<FrameRefCache>,
+    /// Mapping snapshots: taken at exec time by the process tracker, or on
+    /// demand for targets whose binaries are not visible at their mapped paths
+    processes: Arc<ProcessTable>,
+    snapshot_misses: SnapshotMisses,
+    /// Persistent cache keyed by build ID + file offset, shared across runs
+    disk: Option<Arc<DiskSymbolCache>>,
+    disk_refs: FrameRefCache,
+    /// Applied to names as they are encoded for the wire
+    normalizer: Arc<FrameNormalizer>,
+    /// Resolve user frames from the mapping snapshots only
+    offline: bool,
 }
 
 impl Default for SymbolCache {
@@ -433,9 +843,65 @@ impl SymbolCache {
     pub fn new() -> Self {
         Self {
             cache: HashMap::new(),
+            inlined: HashMap::new(),
+            jit: JitResolver::new(),
+            frame_refs: None,
+            processes: Arc::new(ProcessTable::new()),
+            snapshot_misses: SnapshotMisses::default(),
+            disk: None,
+            disk_refs: FrameRefCache::new(),
+            normalizer: Arc::new(FrameNormalizer::new()),
+            offline: false,
+        }
+    }
+
+    /// Cache for deferred symbolization: user frames that normalize to an ELF
+    /// file with a build ID are sent as `(build_id, file_offset)` refs and left
+    /// for the aggregator to name. Kernel frames and frames without a build ID
+    /// (JIT code, anonymous mappings) are still resolved locally.
+    pub fn deferred() -> Self {
+        Self {
+            frame_refs: Some(FrameRefCache::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Cache matching the configured symbolization mode
+    pub fn for_mode(mode: SymbolizeMode) -> Self {
+        match mode {
+            SymbolizeMode::Agent => Self::new(),
+            SymbolizeMode::Deferred => Self::deferred(),
         }
     }
 
+    /// Resolve frames of exited processes from the process tracker's snapshots
+    pub fn with_process_table(mut self, table: Option<Arc<ProcessTable>>) -> Self {
+        if let Some(table) = table {
+            self.processes = table;
+        }
+        self
+    }
+
+    /// Look up and store user frames in a persistent on-disk cache
+    pub fn with_disk_cache(mut self, disk: Option<Arc<DiskSymbolCache>>) -> Self {
+        self.disk = disk;
+        self
+    }
+
+    /// Normalize pushed names with user rules (demangling and hash stripping
+    /// apply by default)
+    pub fn with_normalizer(mut self, normalizer: Arc<FrameNormalizer>) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// Resolve user frames from the process table only, as for replayed
+    /// captures whose PIDs may now belong to other processes
+    pub fn offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
     /// Resolve symbols for a batch of ProfileEvents in-place.
     ///
     /// Creates a temporary `Symbolizer` for each call (cheap — no persistent state
@@ -445,7 +911,15 @@ impl SymbolCache {
         events: &mut [aperture_shared::types::events::ProfileEvent],
         pid: Option<i32>,
     ) {
-        use aperture_shared::types::events::ProfileEvent;
+        use aperture_shared::types::events::{
+            KernelLockEvent, LockEvent, PageFaultEvent, ProbeEvent, ProfileEvent, SchedEvent,
+            SyscallEvent,
+        };
+
+        if let Some(frame_refs) = self.frame_refs.as_mut() {
+            frame_refs.attach_refs(events);
+            frame_refs.prune_exited();
+        }
 
         // 1. Collect all unique IPs that need resolution, separated by address space
         let mut user_ips: Vec<u64> = Vec::new();
@@ -453,7 +927,10 @@ impl SymbolCache {
         for event in events.iter() {
             match event {
                 ProfileEvent::CpuSample(s) => {
-                    for &ip in &s.user_stack {
+                    for (i, &ip) in s.user_stack.iter().enumerate() {
+                        if has_ref(&s.user_stack_refs, i) {
+                            continue;
+                        }
                         if !self.cache.contains_key(&ip) && !user_ips.contains(&ip) {
                             user_ips.push(ip);
                         }
@@ -464,12 +941,41 @@ impl SymbolCache {
                         }
                     }
                 }
-                ProfileEvent::Lock(ev) => {
-                    for &ip in &ev.stack_trace {
-                        if self.cache.contains_key(&ip) {
+                ProfileEvent::Lock(LockEvent {
+                    stack_trace,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::Syscall(SyscallEvent {
+                    stack_trace,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::KernelLock(KernelLockEvent {
+                    stack_trace,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::Sched(SchedEvent {
+                    stack_trace,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::PageFault(PageFaultEvent {
+                    stack_trace,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::Probe(ProbeEvent {
+                    stack_trace,
+                    stack_refs,
+                    ..
+                }) => {
+                    for (i, &ip) in stack_trace.iter().enumerate() {
+                        if self.cache.contains_key(&ip) || has_ref(stack_refs, i) {
                             continue;
                         }
-                        let is_kernel = ip >= 0xffff_0000_0000_0000;
+                        let is_kernel = is_kernel_ip(ip);
                         if is_kernel {
                             if !kernel_ips.contains(&ip) {
                                 kernel_ips.push(ip);
@@ -483,24 +989,115 @@ impl SymbolCache {
             }
         }
 
-        // 2. Batch-resolve IPs using a temporary Symbolizer
+        // Persistent cache hits skip blazesym; misses are stored once resolved
+        let mut from_disk = 0usize;
+        let mut to_store: Vec<(i32, Vec<u64>)> = Vec::new();
+        if self.disk.is_some() {
+            for (ev_pid, ips) in unresolved_user_ips_by_pid(events, &self.cache) {
+                let pending = load_from_disk(
+                    self.disk.as_deref(),
+                    &mut self.disk_refs,
+                    &mut self.cache,
+                    &mut self.inlined,
+                    ev_pid,
+                    &ips,
+                );
+                from_disk += ips.len() - pending.len();
+                to_store.push((ev_pid, pending));
+            }
+            user_ips.retain(|ip| self.cache.get(ip).map_or(true, is_unresolved));
+        }
+
+        // 2. Batch-resolve IPs using a temporary Symbolizer. Without a target
+        // PID, user IPs are resolved per owning event PID below instead.
         let (mut user_resolved, mut kernel_resolved) = (0u32, 0u32);
-        if !user_ips.is_empty() || !kernel_ips.is_empty() {
-            let symbolizer = Symbolizer::new();
-            if !user_ips.is_empty() {
-                user_resolved = Self::resolve_ips(&symbolizer, &mut self.cache, &user_ips, pid);
+        let symbolizer = Symbolizer::new();
+        if let (Some(pid), false) = (pid, user_ips.is_empty()) {
+            user_resolved = Self::resolve_ips(
+                &symbolizer,
+                &mut self.cache,
+                &mut self.inlined,
+                &user_ips,
+                Some(pid),
+            );
+        }
+        if !kernel_ips.is_empty() {
+            kernel_resolved = Self::resolve_ips(
+                &symbolizer,
+                &mut self.cache,
+                &mut self.inlined,
+                &kernel_ips,
+                None,
+            );
+        }
+        // Re-reading maps must not hold up the push task
+        let refresh = if self.offline {
+            SnapshotRefresh::Never
+        } else {
+            SnapshotRefresh::Background
+        };
+        for (ev_pid, ips) in unresolved_user_ips_by_pid(events, &self.cache) {
+            if pid.is_none() && !self.offline && Path::new(&format!("/proc/{}", ev_pid)).exists() {
+                user_resolved += Self::resolve_ips(
+                    &symbolizer,
+                    &mut self.cache,
+                    &mut self.inlined,
+                    &ips,
+                    Some(ev_pid),
+                );
+            }
+            // Exited processes and container binaries resolve through a mapping snapshot
+            user_resolved += resolve_from_snapshot(
+                &symbolizer,
+                &self.processes,
+                &mut self.snapshot_misses,
+                &mut self.cache,
+                &mut self.inlined,
+                ev_pid,
+                &ips,
+                refresh,
+            );
+        }
+        drop(symbolizer); // Rc freed before any .await
+
+        // JIT fallback: perf map / jitdump lookups for IPs blazesym could not resolve
+        let mut jit_resolved = 0u32;
+        for (ev_pid, ips) in unresolved_user_ips_by_pid(events, &self.cache) {
+            if self.offline {
+                break;
+            }
+            self.jit.refresh(ev_pid);
+            if !self.jit.has_symbols(ev_pid) {
+                continue;
             }
-            if !kernel_ips.is_empty() {
-                kernel_resolved =
-                    Self::resolve_ips(&symbolizer, &mut self.cache, &kernel_ips, None);
+            for ip in ips {
+                if let Some(name) = self.jit.lookup(ev_pid, ip) {
+                    self.cache.insert(ip, jit_frame(ip, name));
+                    jit_resolved += 1;
+                }
             }
-            // symbolizer is dropped here — Rc freed before any .await
         }
-        if !user_ips.is_empty() || !kernel_ips.is_empty() {
+        user_resolved += jit_resolved;
+        self.jit.prune_exited();
+        for (ev_pid, ips) in to_store {
+            store_to_disk(
+                self.disk.as_deref(),
+                &mut self.disk_refs,
+                &self.cache,
+                &self.inlined,
+                ev_pid,
+                &ips,
+            );
+        }
+        if self.disk.is_some() {
+            self.disk_refs.prune_exited();
+        }
+        if !user_ips.is_empty() || !kernel_ips.is_empty() || from_disk > 0 {
             debug!(
-                "Symbol resolution: {}/{} user IPs, {}/{} kernel IPs resolved (cache: {} entries)",
+                "Symbol resolution: {}/{} user IPs ({} from disk cache), {}/{} kernel IPs resolved (cache: {} entries)",
                 user_resolved,
                 user_ips.len(),
+                from_disk,
                 kernel_resolved,
                 kernel_ips.len(),
                 self.cache.len(),
@@ -514,19 +1111,65 @@ impl SymbolCache {
                     s.user_stack_symbols = s
                         .user_stack
                         .iter()
-                        .map(|ip| self.cache.get(ip).and_then(Self::encode_symbol))
+                        .enumerate()
+                        .map(|(i, ip)| {
+                            if has_ref(&s.user_stack_refs, i) {
+                                return None;
+                            }
+                            self.symbol_for(*ip)
+                        })
                         .collect();
                     s.kernel_stack_symbols = s
                         .kernel_stack
                         .iter()
-                        .map(|ip| self.cache.get(ip).and_then(Self::encode_symbol))
+                        .map(|ip| self.symbol_for(*ip))
                         .collect();
                 }
-                ProfileEvent::Lock(ev) => {
-                    ev.stack_symbols = ev
-                        .stack_trace
+                ProfileEvent::Lock(LockEvent {
+                    stack_trace,
+                    stack_symbols,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::Syscall(SyscallEvent {
+                    stack_trace,
+                    stack_symbols,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::KernelLock(KernelLockEvent {
+                    stack_trace,
+                    stack_symbols,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::Sched(SchedEvent {
+                    stack_trace,
+                    stack_symbols,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::PageFault(PageFaultEvent {
+                    stack_trace,
+                    stack_symbols,
+                    stack_refs,
+                    ..
+                })
+                | ProfileEvent::Probe(ProbeEvent {
+                    stack_trace,
+                    stack_symbols,
+                    stack_refs,
+                    ..
+                }) => {
+                    *stack_symbols = stack_trace
                         .iter()
-                        .map(|ip| self.cache.get(ip).and_then(Self::encode_symbol))
+                        .enumerate()
+                        .map(|(i, ip)| {
+                            if has_ref(stack_refs, i) {
+                                return None;
+                            }
+                            self.symbol_for(*ip)
+                        })
                         .collect();
                 }
                 _ => {}
@@ -534,17 +1177,27 @@ impl SymbolCache {
         }
     }
 
-    /// Encode a cached Frame into a symbol string for the wire protocol.
+    /// Wire symbol for `ip`: each frame of its inline chain normalized and
+    /// encoded with `encode_symbol`, plus source locations.
+    fn symbol_for(&self, ip: u64) -> Option<Symbol> {
+        frame_chain(&self.cache, &self.inlined, ip).and_then(|chain| {
+            Frame::to_symbol(chain, |frame| {
+                let func = self.normalizer.normalize_name(frame.function.as_deref()?);
+                Some(Self::encode_symbol(func, frame.module.as_deref()))
+            })
+        })
+    }
+
+    /// Encode a function name into a symbol string for the wire protocol.
     /// Format: "function_name [module_basename]" when module is available.
     /// This allows the UI to parse out the module info.
-    fn encode_symbol(frame: &Frame) -> Option<String> {
-        match (&frame.function, &frame.module) {
-            (Some(func), Some(module)) if !module.is_empty() => {
+    fn encode_symbol(func: String, module: Option<&str>) -> String {
+        match module {
+            Some(module) if !module.is_empty() => {
                 let basename = module.rsplit('/').next().unwrap_or(module);
-                Some(format!("{} [{}]", func, basename))
+                format!("{} [{}]", func, basename)
             }
-            (Some(func), _) => Some(func.clone()),
-            _ => None,
+            _ => func,
         }
     }
 
@@ -552,6 +1205,7 @@ impl SymbolCache {
     fn resolve_ips(
         symbolizer: &Symbolizer,
         cache: &mut HashMap<u64, Frame>,
+        inlined: &mut HashMap<u64, Vec<Frame>>,
         ips: &[u64],
         pid: Option<i32>,
     ) -> u32 {
@@ -571,21 +1225,11 @@ impl SymbolCache {
                     let frame = match result {
                         Symbolized::Sym(sym) => {
                             resolved += 1;
-                            Frame {
-                                ip,
-                                function: Some(sym.name.to_string()),
-                                file: sym
-                                    .module
-                                    .as_ref()
-                                    .and_then(|m| m.to_str())
-                                    .map(String::from),
-                                line: None,
-                                module: sym
-                                    .module
-                                    .as_ref()
-                                    .and_then(|m| m.to_str())
-                                    .map(String::from),
+                            let (frame, chain) = sym_frames(ip, sym, None);
+                            if !chain.is_empty() {
+                                inlined.insert(ip, chain);
                             }
+                            frame
                         }
                         Symbolized::Unknown(reason) => {
                             debug!(
@@ -595,11 +1239,8 @@ impl SymbolCache {
                                 "Unresolved IP"
                             );
                             Frame {
-                                ip,
                                 function: Some(format!("0x{:x}", ip)),
-                                file: None,
-                                line: None,
-                                module: None,
+                                ..Frame::new_unresolved(ip)
                             }
                         }
                     };
@@ -616,11 +1257,8 @@ impl SymbolCache {
                 );
                 for &ip in ips {
                     cache.entry(ip).or_insert_with(|| Frame {
-                        ip,
                         function: Some(format!("0x{:x}", ip)),
-                        file: None,
-                        line: None,
-                        module: None,
+                        ..Frame::new_unresolved(ip)
                     });
                 }
                 0
@@ -629,6 +1267,352 @@ impl SymbolCache {
     }
 }
 
+/// How [`resolve_from_snapshot`] re-reads the mappings of a running process
+/// whose IPs fall outside its snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SnapshotRefresh {
+    /// Never: replayed captures, whose PIDs may have been reused
+    Never,
+    /// Before resolving
+    Inline,
+    /// On a blocking task; the IPs resolve on a later call
+    Background,
+}
+
+/// IPs a process's mapping snapshot could not resolve, kept until the
+/// snapshot is taken again so they are neither retried nor trigger a refresh
+/// on every call
+#[derive(Debug, Default)]
+struct SnapshotMisses(HashMap<i32, (u64, HashSet<u64>)>);
+
+impl SnapshotMisses {
+    fn contains(&self, pid: i32, generation: u64, ip: u64) -> bool {
+        self.0
+            .get(&pid)
+            .is_some_and(|(g, ips)| *g == generation && ips.contains(&ip))
+    }
+
+    fn record(&mut self, pid: i32, generation: u64, ips: impl IntoIterator<Item = u64>) {
+        let entry = self.0.entry(pid).or_default();
+        if entry.0 != generation {
+            *entry = (generation, HashSet::new());
+        }
+        entry.1.extend(ips);
+    }
+}
+
+/// Resolve still-unresolved `ips` of `pid` by file offset through the open
+/// file handles of its mapping snapshot. Works after the process has exited,
+/// for deleted binaries, and for binaries in other mount namespaces. Running
+/// processes are re-snapshotted as `refresh` says when an IP falls outside
+/// the known mappings. Returns the number of IPs resolved.
+#[allow(clippy::too_many_arguments)]
+fn resolve_from_snapshot(
+    symbolizer: &Symbolizer,
+    table: &Arc<ProcessTable>,
+    misses: &mut SnapshotMisses,
+    cache: &mut HashMap<u64, Frame>,
+    inlined: &mut HashMap<u64, Vec<Frame>>,
+    pid: i32,
+    ips: &[u64],
+    refresh: SnapshotRefresh,
+) -> u32 {
+    let generation = table.generation(pid);
+    let pending: Vec<u64> = ips
+        .iter()
+        .copied()
+        .filter(|ip| cache.get(ip).map_or(true, is_unresolved))
+        .filter(|&ip| !misses.contains(pid, generation, ip))
+        .collect();
+    if pending.is_empty() {
+        return 0;
+    }
+    if refresh != SnapshotRefresh::Never
+        && pending.iter().any(|&ip| table.locate(pid, ip).is_none())
+        && Path::new(&format!("/proc/{}", pid)).exists()
+    {
+        match (refresh, tokio::runtime::Handle::try_current()) {
+            (SnapshotRefresh::Background, Ok(runtime)) => {
+                let table = table.clone();
+                runtime.spawn_blocking(move || table.snapshot(pid));
+            }
+            _ => {
+                table.snapshot(pid);
+            }
+        }
+    }
+
+    // Group by backing file so each ELF is opened once
+    let mut by_file: HashMap<std::path::PathBuf, (String, Vec<(u64, u64)>)> = HashMap::new();
+    for &ip in &pending {
+        if let Some(loc) = table.locate(pid, ip) {
+            by_file
+                .entry(loc.path)
+                .or_insert_with(|| (loc.module, Vec::new()))
+                .1
+                .push((ip, loc.file_offset));
+        }
+    }
+
+    let mut resolved = 0u32;
+    for (path, (module, addrs)) in by_file {
+        let offsets: Vec<u64> = addrs.iter().map(|&(_, off)| off).collect();
+        let source = Source::Elf(Elf::new(&path));
+        let results = match symbolizer.symbolize(&source, Input::FileOffset(&offsets)) {
+            Ok(results) => results,
+            Err(e) => {
+                debug!(pid, "Failed to symbolize snapshot of {}: {}", module, e);
+                continue;
+            }
+        };
+        for (&(ip, _), result) in addrs.iter().zip(results.iter()) {
+            if let Symbolized::Sym(sym) = result {
+                let (frame, chain) = sym_frames(ip, sym, Some(&module));
+                if !chain.is_empty() {
+                    inlined.insert(ip, chain);
+                }
+                cache.insert(ip, frame);
+                resolved += 1;
+            }
+        }
+    }
+
+    let unresolved = pending
+        .into_iter()
+        .filter(|ip| cache.get(ip).map_or(true, is_unresolved));
+    misses.record(pid, table.generation(pid), unresolved);
+    resolved
+}
+
+/// Fill `cache` and `inlined` for `pid`'s user `ips` from the persistent
+/// cache, keyed by the build ID and file offset each IP normalizes to.
+/// Returns the IPs that still need resolving.
+fn load_from_disk(
+    disk: Option<&DiskSymbolCache>,
+    refs: &mut FrameRefCache,
+    cache: &mut HashMap<u64, Frame>,
+    inlined: &mut HashMap<u64, Vec<Frame>>,
+    pid: i32,
+    ips: &[u64],
+) -> Vec<u64> {
+    let Some(disk) = disk else {
+        return ips.to_vec();
+    };
+    let mut pending = Vec::new();
+    for (&ip, frame_ref) in ips.iter().zip(refs.refs(pid, ips)) {
+        let mut chain = frame_ref
+            .and_then(|frame_ref| disk.get(&frame_ref, ip))
+            .unwrap_or_default();
+        let Some(frame) = chain.pop() else {
+            pending.push(ip);
+            continue;
+        };
+        if !chain.is_empty() {
+            inlined.insert(ip, chain);
+        }
+        cache.insert(ip, frame);
+    }
+    pending
+}
+
+/// Add the resolved frames of `pid`'s user `ips` to the persistent cache.
+/// Frames without a build ID (JIT code, anonymous mappings) are skipped.
+fn store_to_disk(
+    disk: Option<&DiskSymbolCache>,
+    refs: &mut FrameRefCache,
+    cache: &HashMap<u64, Frame>,
+    inlined: &HashMap<u64, Vec<Frame>>,
+    pid: i32,
+    ips: &[u64],
+) {
+    let Some(disk) = disk else {
+        return;
+    };
+    for (&ip, frame_ref) in ips.iter().zip(refs.refs(pid, ips)) {
+        let (Some(frame_ref), Some(chain)) = (frame_ref, frame_chain(cache, inlined, ip)) else {
+            continue;
+        };
+        if chain.last().map_or(true, |f| is_unresolved(f)) {
+            continue;
+        }
+        let chain: Vec<Frame> = chain.into_iter().cloned().collect();
+        disk.insert(frame_ref, &chain);
+    }
+}
+
+/// Frames for a symbolized address: the function containing it, plus the
+/// functions inlined at it (innermost first, marked inline). Each frame
+/// carries the source location within that function. `module` overrides the
+/// module blazesym reports (snapshot lookups go through `/proc/self/fd`).
+fn sym_frames(ip: u64, sym: &Sym, module: Option<&str>) -> (Frame, Vec<Frame>) {
+    let module = module.map(String::from).or_else(|| {
+        sym.module
+            .as_ref()
+            .and_then(|m| m.to_str())
+            .map(String::from)
+    });
+    let (file, line) = code_location(sym.code_info.as_deref());
+    let frame = Frame {
+        ip,
+        function: Some(sym.name.to_string()),
+        file,
+        line,
+        module: module.clone(),
+        inline: false,
+    };
+    let inlined = sym
+        .inlined
+        .iter()
+        .rev()
+        .map(|inlined_fn| {
+            let (file, line) = code_location(inlined_fn.code_info.as_ref());
+            Frame {
+                ip,
+                function: Some(inlined_fn.name.to_string()),
+                file,
+                line,
+                module: module.clone(),
+                inline: true,
+            }
+        })
+        .collect();
+    (frame, inlined)
+}
+
+fn code_location(info: Option<&CodeInfo>) -> (Option<String>, Option<u32>) {
+    match info {
+        Some(info) => (Some(info.to_path().display().to_string()), info.line),
+        None => (None, None),
+    }
+}
+
+/// Inline chain for `ip`, innermost first, ending with the containing function
+fn frame_chain<'a>(
+    cache: &'a HashMap<u64, Frame>,
+    inlined: &'a HashMap<u64, Vec<Frame>>,
+    ip: u64,
+) -> Option<Vec<&'a Frame>> {
+    let frame = cache.get(&ip)?;
+    Some(
+        inlined
+            .get(&ip)
+            .into_iter()
+            .flatten()
+            .chain(std::iter::once(frame))
+            .collect(),
+    )
+}
+
+/// True when a cached frame only carries the hex placeholder name
+fn is_unresolved(frame: &Frame) -> bool {
+    frame
+        .function
+        .as_ref()
+        .map_or(true, |n| n.starts_with("0x"))
+}
+
+/// True when the frame at `idx` will be symbolized by the aggregator
+fn has_ref(refs: &[Option<FrameRef>], idx: usize) -> bool {
+    refs.get(idx).is_some_and(Option::is_some)
+}
+
+/// Frame for an address resolved from JIT metadata
+fn jit_frame(ip: u64, name: String) -> Frame {
+    Frame {
+        ip,
+        function: Some(name),
+        file: None,
+        line: None,
+        module: Some(JIT_MODULE.to_string()),
+        inline: false,
+    }
+}
+
+/// Group user-space IPs that are still unresolved by the PID of the event
+/// they came from, so JIT lookups run against the right process.
+fn unresolved_user_ips_by_pid(
+    events: &[aperture_shared::types::events::ProfileEvent],
+    cache: &HashMap<u64, Frame>,
+) -> HashMap<i32, Vec<u64>> {
+    use aperture_shared::types::events::{
+        KernelLockEvent, LockEvent, PageFaultEvent, ProbeEvent, ProfileEvent, SchedEvent,
+        SyscallEvent,
+    };
+
+    let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+    let mut push = |pid: i32, ip: u64| {
+        if is_kernel_ip(ip) || cache.get(&ip).is_some_and(|f| !is_unresolved(f)) {
+            return;
+        }
+        let ips = by_pid.entry(pid).or_default();
+        if !ips.contains(&ip) {
+            ips.push(ip);
+        }
+    };
+    for event in events {
+        match event {
+            ProfileEvent::CpuSample(s) => {
+                for (i, &ip) in s.user_stack.iter().enumerate() {
+                    if !has_ref(&s.user_stack_refs, i) {
+                        push(s.pid, ip);
+                    }
+                }
+            }
+            ProfileEvent::Lock(LockEvent {
+                pid,
+                stack_trace,
+                stack_refs,
+                ..
+            })
+            | ProfileEvent::Syscall(SyscallEvent {
+                pid,
+                stack_trace,
+                stack_refs,
+                ..
+            })
+            | ProfileEvent::KernelLock(KernelLockEvent {
+                pid,
+                stack_trace,
+                stack_refs,
+                ..
+            })
+            | ProfileEvent::PageFault(PageFaultEvent {
+                pid,
+                stack_trace,
+                stack_refs,
+                ..
+            })
+            | ProfileEvent::Probe(ProbeEvent {
+                pid,
+                stack_trace,
+                stack_refs,
+                ..
+            }) => {
+                for (i, &ip) in stack_trace.iter().enumerate() {
+                    if !has_ref(stack_refs, i) {
+                        push(*pid, ip);
+                    }
+                }
+            }
+            // Wakeup stacks are the waker's
+            ProfileEvent::Sched(SchedEvent {
+                waker_pid,
+                stack_trace,
+                stack_refs,
+                ..
+            }) => {
+                for (i, &ip) in stack_trace.iter().enumerate() {
+                    if !has_ref(stack_refs, i) {
+                        push(*waker_pid, ip);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    by_pid
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -639,6 +1623,59 @@ mod tests {
         assert_eq!(resolver.cache_size(), 0);
     }
 
+    #[test]
+    fn test_user_symbol_stats() {
+        let pid = std::process::id() as i32;
+        let ip = test_user_symbol_stats as *const () as u64;
+        let mut profile = Profile::new(0, 0, 0);
+        profile.add_sample(Stack::from_ips(&[ip, 0x10]));
+
+        let mut resolver = SymbolResolver::new();
+        resolver.set_user_ip_owners(HashMap::from([(pid, vec![ip, 0x10])]));
+        resolver.symbolize_profile(&mut profile, Some(pid)).unwrap();
+
+        let stats = resolver.user_symbol_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].pid, pid);
+        assert_eq!(stats[0].total, 2);
+        assert_eq!(stats[0].resolved, 1);
+        assert!(!stats[0].container);
+        assert!((stats[0].success_rate() - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_snapshot_misses_wait_for_new_generation() {
+        let pid = std::process::id() as i32;
+        let symbolizer = Symbolizer::new();
+        let table = Arc::new(ProcessTable::new());
+        let mut misses = SnapshotMisses::default();
+        let mut cache = HashMap::new();
+        let mut inlined = HashMap::new();
+
+        // An unmapped IP snapshots the process once, then is not retried
+        let mut generations = Vec::new();
+        for _ in 0..2 {
+            let resolved = resolve_from_snapshot(
+                &symbolizer,
+                &table,
+                &mut misses,
+                &mut cache,
+                &mut inlined,
+                pid,
+                &[0x10],
+                SnapshotRefresh::Inline,
+            );
+            assert_eq!(resolved, 0);
+            generations.push(table.generation(pid));
+        }
+        let generation = generations[0];
+        assert!(generation > 0);
+        assert_eq!(generations[1], generation);
+        assert!(misses.contains(pid, generation, 0x10));
+        assert!(table.snapshot(pid));
+        assert!(!misses.contains(pid, table.generation(pid), 0x10));
+    }
+
     #[test]
     fn test_symbol_cache_is_send() {
         fn assert_send<T: Send>() {}
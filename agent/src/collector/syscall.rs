@@ -1,12 +1,30 @@
 //! Syscall event collector
 //!
-//! Collects syscall events from eBPF and builds profile data
+//! Collects syscall events from eBPF and builds profile data. File
+//! descriptor arguments are resolved to files and sockets here, while the
+//! process still has them open. Calls slower than the stack threshold carry
+//! a stack, which feeds per-syscall latency flamegraphs.
+//!
+//! In aggregation mode no per-call events arrive: the kernel keeps cumulative
+//! per-CPU latency histograms, which are read, summed across CPUs and diffed
+//! against the previous read to produce per-interval summaries. Each read
+//! also starts a new interval, which resets the kernel's per-CPU min and max
+//! on the next call, so summaries carry the extremes of their own calls.
 
+use super::fd_resolver::FdResolver;
 use anyhow::Result;
-use aperture_shared::types::events::{ProfileEvent, SyscallEvent};
+use aperture_shared::types::events::{ProfileEvent, SyscallEvent, SyscallSummaryEvent};
 use aperture_shared::types::profile::SyscallProfile;
-use aperture_shared::utils::syscalls::syscall_name;
-use tracing::info;
+use aperture_shared::utils::arch::{is_kernel_ip, Arch};
+use aperture_shared::utils::syscalls::{
+    opens_path, returns_byte_count, syscall_name_for, MAX_SYSCALL_ID, SYS_CLOSE,
+};
+use aya::maps::{Array, MapData, PerCpuArray, StackTraceMap};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info};
+
+/// Path argument length captured by eBPF (must match MAX_SYSCALL_PATH_LEN)
+pub const MAX_SYSCALL_PATH_LEN: usize = 128;
 
 /// Raw syscall event from eBPF (must match agent-ebpf/src/syscall_tracer.rs)
 #[repr(C)]
@@ -19,11 +37,98 @@ pub struct SyscallEventBpf {
     pub duration_ns: u64,
     pub return_value: i64,
     pub comm: [u8; 16],
+    pub fd: i64,
+    pub path: [u8; MAX_SYSCALL_PATH_LEN],
+    pub user_stack_id: i64,
+    pub kernel_stack_id: i64,
 }
 
 // Implement traits for reading from perf buffer
 unsafe impl aya::Pod for SyscallEventBpf {}
 
+/// Latency buckets kept in-kernel (must match SYSCALL_LATENCY_BUCKETS)
+pub const SYSCALL_LATENCY_BUCKETS: usize = 30;
+
+/// SYSCALL_CONFIG slot of the histogram read interval
+const CONFIG_INTERVAL: u32 = 5;
+
+/// Cumulative per-syscall totals from SYSCALL_HIST (must match
+/// agent-ebpf/src/syscall_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyscallHistBpf {
+    pub count: u64,
+    pub total_duration_ns: u64,
+    /// Extremes of this CPU's calls since `interval` began
+    pub max_duration_ns: u64,
+    pub min_duration_ns: u64,
+    pub error_count: u64,
+    /// Read interval the extremes were last reset in
+    pub interval: u64,
+    pub buckets: [u64; SYSCALL_LATENCY_BUCKETS],
+}
+
+unsafe impl aya::Pod for SyscallHistBpf {}
+
+impl SyscallHistBpf {
+    /// Sum one syscall's per-CPU totals. The extremes are those of the CPUs
+    /// that made calls since `interval` began; a call made while reading
+    /// already starts the next interval on its CPU.
+    fn merge_cpus<'a>(
+        per_cpu: impl IntoIterator<Item = &'a SyscallHistBpf>,
+        interval: u64,
+    ) -> Self {
+        let mut total = SyscallHistBpf {
+            interval,
+            ..Default::default()
+        };
+        let mut extremes: Option<(u64, u64)> = None;
+        for cpu in per_cpu.into_iter().filter(|cpu| cpu.count > 0) {
+            total.count += cpu.count;
+            total.total_duration_ns += cpu.total_duration_ns;
+            total.error_count += cpu.error_count;
+            for (bucket, n) in total.buckets.iter_mut().zip(cpu.buckets) {
+                *bucket += n;
+            }
+            if cpu.interval >= interval {
+                let (min, max) = extremes.unwrap_or((cpu.min_duration_ns, cpu.max_duration_ns));
+                extremes = Some((min.min(cpu.min_duration_ns), max.max(cpu.max_duration_ns)));
+            }
+        }
+        if let Some((min, max)) = extremes {
+            total.min_duration_ns = min;
+            total.max_duration_ns = max;
+        }
+        total
+    }
+}
+
+/// The in-kernel histograms of aggregation mode, and the SYSCALL_CONFIG
+/// interval that resets their extremes
+pub struct SyscallHistograms {
+    hist: PerCpuArray<MapData, SyscallHistBpf>,
+    config: Array<MapData, u64>,
+    interval: u64,
+}
+
+impl SyscallHistograms {
+    pub fn new(hist: PerCpuArray<MapData, SyscallHistBpf>, config: Array<MapData, u64>) -> Self {
+        Self {
+            hist,
+            config,
+            interval: 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for SyscallHistograms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyscallHistograms")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Syscall event collector
 #[derive(Debug)]
 pub struct SyscallCollector {
@@ -35,6 +140,30 @@ pub struct SyscallCollector {
 
     /// Index of first event not yet pushed to aggregator
     push_cursor: usize,
+
+    /// fd -> file/socket lookups
+    fds: FdResolver,
+
+    /// The kernel records 1 in this many calls
+    sample_every: u32,
+
+    /// Traced process, 0 when system-wide
+    target_pid: i32,
+
+    /// Per-interval summaries from the in-kernel histograms
+    summaries: Vec<SyscallSummaryEvent>,
+
+    /// Index of first summary not yet pushed to aggregator
+    summary_cursor: usize,
+
+    /// In-kernel histograms, in aggregation mode
+    histograms: Option<SyscallHistograms>,
+
+    /// Cumulative totals as of the previous histogram read
+    hist_totals: HashMap<u32, SyscallHistBpf>,
+
+    /// Architecture whose numbering the syscall ids use
+    arch: Arch,
 }
 
 impl Default for SyscallCollector {
@@ -50,39 +179,181 @@ impl SyscallCollector {
             events: Vec::new(),
             start_time: aperture_shared::utils::time::system_time_nanos(),
             push_cursor: 0,
+            fds: FdResolver::new(),
+            sample_every: 1,
+            target_pid: 0,
+            summaries: Vec::new(),
+            summary_cursor: 0,
+            histograms: None,
+            hist_totals: HashMap::new(),
+            arch: Arch::host(),
         }
     }
 
+    /// Ratio of the in-kernel 1-in-N sampling, recorded on every event
+    pub fn set_sampling(&mut self, sample_every: u32) {
+        self.sample_every = sample_every.max(1);
+    }
+
+    /// Process the in-kernel histograms belong to
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        self.target_pid = pid.unwrap_or(0);
+    }
+
+    /// Read summaries from the in-kernel histograms
+    pub fn set_histograms(&mut self, histograms: SyscallHistograms) {
+        self.histograms = Some(histograms);
+    }
+
+    /// Name syscalls with the numbering of `arch` (replayed captures)
+    pub fn set_arch(&mut self, arch: Arch) {
+        self.arch = arch;
+    }
+
     /// Add an event to the collector
     pub fn add_event(&mut self, event: SyscallEvent) {
         self.events.push(event);
     }
 
+    /// Add a summary of calls aggregated in the kernel
+    pub fn add_summary(&mut self, summary: SyscallSummaryEvent) {
+        self.summaries.push(summary);
+    }
+
     /// Process a raw eBPF event and convert to SyscallEvent
-    pub fn process_event(&mut self, event: &SyscallEventBpf) -> Result<()> {
+    pub fn process_event(
+        &mut self,
+        event: &SyscallEventBpf,
+        stacks: &StackTraceMap<aya::maps::MapData>,
+    ) -> Result<()> {
+        // User stack first, then kernel, as for lock events
+        let mut stack_trace = read_stack(stacks, event.user_stack_id);
+        stack_trace.extend(read_stack(stacks, event.kernel_stack_id));
+        self.convert_event(event, stack_trace);
+        Ok(())
+    }
+
+    fn convert_event(&mut self, event: &SyscallEventBpf, stack_trace: Vec<u64>) {
         // Convert comm bytes to string
         let comm = std::str::from_utf8(&event.comm)
             .unwrap_or("<unknown>")
             .trim_end_matches('\0')
             .to_string();
 
+        let pid = event.pid as i32;
+        let fd = (event.fd >= 0).then_some(event.fd as i32);
+        let path_len = event
+            .path
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(event.path.len());
+        let path =
+            (path_len > 0).then(|| String::from_utf8_lossy(&event.path[..path_len]).into_owned());
+
+        let target = match fd {
+            Some(fd) => self.fds.resolve(pid, fd),
+            None => path.clone(),
+        };
+        if event.return_value >= 0 && opens_path(event.syscall_id) {
+            if let Some(path) = path {
+                self.fds.opened(pid, event.return_value as i32, path);
+            }
+        }
+        if event.syscall_id == SYS_CLOSE {
+            if let Some(fd) = fd {
+                self.fds.closed(pid, fd);
+            }
+        }
+
         let syscall_event = SyscallEvent {
             timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
-            pid: event.pid as i32,
+            pid,
             tid: event.tid as i32,
             syscall_id: event.syscall_id,
             duration_ns: event.duration_ns,
             return_value: event.return_value,
             comm,
+            fd,
+            target,
+            bytes: (event.return_value >= 0 && returns_byte_count(event.syscall_id))
+                .then_some(event.return_value as u64),
+            stack_trace,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: self.sample_every,
         };
 
         self.add_event(syscall_event);
-        Ok(())
+    }
+
+    /// Read the cumulative per-CPU histograms from SYSCALL_HIST and record
+    /// what changed since the previous read. Does nothing outside
+    /// aggregation mode.
+    pub fn read_histograms(&mut self) {
+        let Some(maps) = &mut self.histograms else {
+            return;
+        };
+        // Calls from here on reset their CPU's extremes
+        let interval = maps.interval;
+        maps.interval += 1;
+        if let Err(e) = maps.config.set(CONFIG_INTERVAL, maps.interval, 0) {
+            debug!("Failed to advance syscall histogram interval: {}", e);
+        }
+        let mut totals = Vec::new();
+        for id in 0..MAX_SYSCALL_ID {
+            match maps.hist.get(&id, 0) {
+                Ok(per_cpu) => {
+                    let total = SyscallHistBpf::merge_cpus(per_cpu.iter(), interval);
+                    if total.count > 0 {
+                        totals.push((id, total));
+                    }
+                }
+                Err(e) => debug!("Failed to read syscall histogram {}: {}", id, e),
+            }
+        }
+        self.record_histograms(totals);
+    }
+
+    /// Turn cumulative per-syscall totals into summaries of the calls made
+    /// since the previous totals
+    fn record_histograms(&mut self, totals: Vec<(u32, SyscallHistBpf)>) {
+        let timestamp = aperture_shared::utils::time::system_time_nanos();
+        for (id, total) in totals {
+            let prev = self.hist_totals.insert(id, total).unwrap_or_default();
+            let count = total.count.saturating_sub(prev.count);
+            if count == 0 {
+                continue;
+            }
+            let latency_histogram: Vec<u64> = total
+                .buckets
+                .iter()
+                .zip(prev.buckets)
+                .map(|(n, p)| n.saturating_sub(p))
+                .collect();
+            self.summaries.push(SyscallSummaryEvent {
+                timestamp,
+                pid: self.target_pid,
+                syscall_id: id,
+                count,
+                total_duration_ns: total
+                    .total_duration_ns
+                    .saturating_sub(prev.total_duration_ns),
+                max_duration_ns: total.max_duration_ns,
+                min_duration_ns: total.min_duration_ns,
+                error_count: total.error_count.saturating_sub(prev.error_count),
+                latency_histogram,
+                sample_every: self.sample_every,
+            });
+        }
     }
 
     /// Build aggregated profile from collected events
     pub fn build_profile(&self) -> Result<SyscallProfile> {
-        info!("Building syscall profile from {} events", self.events.len());
+        info!(
+            "Building syscall profile from {} events and {} summaries",
+            self.events.len(),
+            self.summaries.len()
+        );
 
         let mut profile = SyscallProfile::new(self.start_time);
 
@@ -90,30 +361,51 @@ impl SyscallCollector {
         profile.end_time = aperture_shared::utils::time::system_time_nanos();
 
         for event in &self.events {
-            let name = syscall_name(event.syscall_id);
-            profile.add_syscall(
-                event.syscall_id,
-                name,
-                event.duration_ns,
-                event.return_value,
-            );
+            profile.add_event(syscall_name_for(self.arch, event.syscall_id), event);
+        }
+        for summary in &self.summaries {
+            profile.add_summary(syscall_name_for(self.arch, summary.syscall_id), summary);
         }
 
         info!(
-            "Syscall profile built: {} total events, {} unique syscalls",
+            "Syscall profile built: {} total events, {} unique syscalls, {} files/sockets",
             profile.total_events,
-            profile.syscalls.len()
+            profile.syscalls.len(),
+            profile.io.len()
         );
 
         Ok(profile)
     }
 
+    /// User-space IPs of slow-call stacks grouped by the process they were
+    /// captured in, so the symbolizer can resolve each against its owner.
+    pub fn user_ips_by_pid(&self) -> HashMap<i32, Vec<u64>> {
+        let mut seen: HashMap<i32, HashSet<u64>> = HashMap::new();
+        let mut by_pid: HashMap<i32, Vec<u64>> = HashMap::new();
+        for ev in self.events.iter().filter(|ev| !ev.stack_trace.is_empty()) {
+            let seen = seen.entry(ev.pid).or_default();
+            let ips = by_pid.entry(ev.pid).or_default();
+            ips.extend(
+                ev.stack_trace
+                    .iter()
+                    .filter(|&&ip| !is_kernel_ip(ip) && seen.insert(ip)),
+            );
+        }
+        by_pid
+    }
+
     /// All events for a final push to the aggregator.
     pub fn profile_events(&self) -> Vec<ProfileEvent> {
         self.events
             .iter()
             .cloned()
             .map(ProfileEvent::Syscall)
+            .chain(
+                self.summaries
+                    .iter()
+                    .cloned()
+                    .map(ProfileEvent::SyscallSummary),
+            )
             .collect()
     }
 
@@ -123,12 +415,33 @@ impl SyscallCollector {
             .iter()
             .cloned()
             .map(ProfileEvent::Syscall)
+            .chain(
+                self.summaries[self.summary_cursor..]
+                    .iter()
+                    .cloned()
+                    .map(ProfileEvent::SyscallSummary),
+            )
             .collect();
         self.push_cursor = self.events.len();
+        self.summary_cursor = self.summaries.len();
         events
     }
 }
 
+/// IPs of stack `id` in `stacks`, empty when none was captured
+fn read_stack(stacks: &StackTraceMap<aya::maps::MapData>, id: i64) -> Vec<u64> {
+    if id < 0 {
+        return Vec::new();
+    }
+    match stacks.get(&(id as u32), 0) {
+        Ok(trace) => trace.frames().iter().map(|f| f.ip).collect(),
+        Err(e) => {
+            debug!("Failed to get syscall stack {}: {}", id, e);
+            Vec::new()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,6 +458,13 @@ mod tests {
             duration_ns: 100,
             return_value: 0,
             comm: "test".to_string(),
+            fd: None,
+            target: None,
+            bytes: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: 1,
         };
 
         let event2 = SyscallEvent {
@@ -155,6 +475,13 @@ mod tests {
             duration_ns: 200,
             return_value: 0,
             comm: "test".to_string(),
+            fd: None,
+            target: None,
+            bytes: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: 1,
         };
 
         let event3 = SyscallEvent {
@@ -165,6 +492,13 @@ mod tests {
             duration_ns: 150,
             return_value: -1, // error
             comm: "test".to_string(),
+            fd: None,
+            target: None,
+            bytes: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: 1,
         };
 
         collector.add_event(event1);
@@ -187,5 +521,169 @@ mod tests {
         assert_eq!(write_stats.count, 1);
         assert_eq!(write_stats.total_duration_ns, 150);
         assert_eq!(write_stats.error_count, 1);
+        assert!(profile.io.is_empty());
+    }
+
+    fn raw_event(syscall_id: u32, fd: i64, path: &str, return_value: i64) -> SyscallEventBpf {
+        let mut raw_path = [0u8; MAX_SYSCALL_PATH_LEN];
+        raw_path[..path.len()].copy_from_slice(path.as_bytes());
+        SyscallEventBpf {
+            timestamp: 0,
+            pid: std::process::id(),
+            tid: std::process::id(),
+            syscall_id,
+            duration_ns: 1_000,
+            return_value,
+            comm: [0u8; 16],
+            fd,
+            path: raw_path,
+            user_stack_id: -1,
+            kernel_stack_id: -1,
+        }
+    }
+
+    #[test]
+    fn test_arguments_resolve_to_io_targets() {
+        let mut collector = SyscallCollector::new();
+
+        // openat("/data/wal") = 9000, write(9000) = 512, fsync(9000), close(9000)
+        collector.convert_event(&raw_event(257, -1, "/data/wal", 9000), vec![]);
+        collector.convert_event(&raw_event(1, 9000, "", 512), vec![]);
+        collector.convert_event(&raw_event(74, 9000, "", 0), vec![]);
+        collector.convert_event(&raw_event(3, 9000, "", 0), vec![]);
+        // The fd number is free again and no longer resolves to the file
+        collector.convert_event(&raw_event(0, 9000, "", -9), vec![]);
+
+        let write = &collector.events[1];
+        assert_eq!(write.fd, Some(9000));
+        assert_eq!(write.target.as_deref(), Some("/data/wal"));
+        assert_eq!(write.bytes, Some(512));
+        assert_eq!(collector.events[4].target, None);
+
+        let profile = collector.build_profile().unwrap();
+        let wal = &profile.io["/data/wal"];
+        assert_eq!(wal.count, 4);
+        assert_eq!(wal.bytes, 512);
+        assert_eq!(wal.syscalls["fsync"], 1);
+    }
+
+    #[test]
+    fn test_slow_call_stacks() {
+        let mut collector = SyscallCollector::new();
+        let mut slow = raw_event(74, 3, "", 0);
+        slow.duration_ns = 20_000_000;
+        // fsync called from the same site twice, slowly; a fast call has no stack
+        collector.convert_event(&slow, vec![0x401000, 0x400100, 0xffff_ffff_8100_0000]);
+        collector.convert_event(&slow, vec![0x401000, 0x400100, 0xffff_ffff_8100_0000]);
+        collector.convert_event(&raw_event(74, 3, "", 0), vec![]);
+
+        let profile = collector.build_profile().unwrap();
+        let fsync = &profile.syscalls[&74];
+        assert_eq!(fsync.count, 3);
+        assert_eq!(fsync.slow_stacks.len(), 1);
+        assert_eq!(fsync.slow_stacks[0].count, 2);
+        assert_eq!(fsync.slow_stacks[0].total_duration_ns, 40_000_000);
+
+        let owners = collector.user_ips_by_pid();
+        assert_eq!(
+            owners[&(std::process::id() as i32)],
+            vec![0x401000, 0x400100]
+        );
+    }
+
+    fn hist(count: u64, total: u64, max: u64, min: u64, bucket: usize) -> SyscallHistBpf {
+        let mut h = SyscallHistBpf {
+            count,
+            total_duration_ns: total,
+            max_duration_ns: max,
+            min_duration_ns: min,
+            ..Default::default()
+        };
+        h.buckets[bucket] = count;
+        h
+    }
+
+    #[test]
+    fn test_histogram_totals_become_interval_summaries() {
+        let mut collector = SyscallCollector::new();
+        collector.set_sampling(10);
+        collector.set_target_pid(Some(42));
+
+        // Two CPUs, one of which has not made the call yet
+        let cpu0 = hist(3, 3_000, 1_500, 500, 9);
+        let cpu1 = hist(1, 200, 200, 200, 7);
+        let read = SyscallHistBpf::merge_cpus(&[cpu0, SyscallHistBpf::default(), cpu1], 0);
+        assert_eq!(read.count, 4);
+        assert_eq!(read.min_duration_ns, 200);
+        assert_eq!(read.max_duration_ns, 1_500);
+
+        collector.record_histograms(vec![(0, read)]);
+        let first = collector.take_pending_events();
+        assert_eq!(first.len(), 1);
+
+        // Next read: only fsync is new, read is unchanged
+        collector.record_histograms(vec![(0, read), (74, hist(2, 8_000, 5_000, 3_000, 12))]);
+        let second = collector.take_pending_events();
+        assert_eq!(second.len(), 1);
+        match &second[0] {
+            ProfileEvent::SyscallSummary(s) => {
+                assert_eq!(s.syscall_id, 74);
+                assert_eq!(s.pid, 42);
+                assert_eq!(s.sample_every, 10);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        // Cumulative totals grow; the summary covers only the new calls
+        let cpu2 = hist(2, 600, 300, 300, 8);
+        let grown = SyscallHistBpf::merge_cpus(&[cpu0, cpu1, cpu2], 0);
+        collector.record_histograms(vec![(0, grown)]);
+
+        let profile = collector.build_profile().unwrap();
+        let read_stats = &profile.syscalls[&0];
+        assert_eq!(read_stats.count, 60);
+        assert_eq!(read_stats.total_duration_ns, 38_000);
+        assert_eq!(read_stats.latency_histogram[8], 20);
+        assert_eq!(profile.total_events, 80);
+        assert_eq!(profile.sample_every, 10);
+    }
+
+    #[test]
+    fn test_histogram_summaries_report_interval_extremes() {
+        let mut collector = SyscallCollector::new();
+        let in_interval = |mut h: SyscallHistBpf, interval| {
+            h.interval = interval;
+            h
+        };
+
+        // A 5ms and a 1us call on one CPU, a 700ns call on another
+        let mut cpu0 = hist(2, 5_001_000, 5_000_000, 1_000, 22);
+        let cpu1 = hist(1, 700, 700, 700, 9);
+        let first = SyscallHistBpf::merge_cpus(&[cpu0, cpu1], 0);
+        collector.record_histograms(vec![(0, first)]);
+
+        // Later, only calls of 300-400ns on the first CPU, whose extremes the
+        // kernel reset; the second CPU's are from the previous interval
+        cpu0 = in_interval(hist(5, 5_002_000, 400, 300, 8), 1);
+        let later = SyscallHistBpf::merge_cpus(&[cpu0, cpu1], 1);
+        collector.record_histograms(vec![(0, later)]);
+
+        // Then a 9ms call on the second CPU while the third read is underway
+        let cpu1 = in_interval(hist(2, 9_000_700, 9_000_000, 9_000_000, 23), 3);
+        let slowest = SyscallHistBpf::merge_cpus(&[cpu0, cpu1], 2);
+        collector.record_histograms(vec![(0, slowest)]);
+
+        let extremes: Vec<(u64, u64)> = collector
+            .take_pending_events()
+            .into_iter()
+            .map(|event| match event {
+                ProfileEvent::SyscallSummary(s) => (s.min_duration_ns, s.max_duration_ns),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            extremes,
+            vec![(700, 5_000_000), (300, 400), (9_000_000, 9_000_000)]
+        );
     }
 }
@@ -0,0 +1,286 @@
+//! TCP event collector
+//!
+//! Collects connection events from the TCP tracer and builds per-process,
+//! per-endpoint profiles. Connections opened before tracing started are
+//! classified as accepted when their local port is a listening port.
+
+use anyhow::Result;
+use aperture_shared::types::events::{ProfileEvent, TcpEvent, TcpEventKind};
+use aperture_shared::types::profile::TcpProfile;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use tracing::{debug, info};
+
+/// Raw TCP event from eBPF (must match agent-ebpf/src/tcp_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TcpEventBpf {
+    pub timestamp: u64,
+    pub duration_ns: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub kind: u32,
+    pub retransmits: u32,
+    pub srtt_us: u32,
+    pub _pad: u32,
+    pub family: u16,
+    pub lport: u16,
+    pub rport: u16,
+    pub passive: u16,
+    pub raddr: [u8; 16],
+    pub comm: [u8; 16],
+}
+
+// Implement traits for reading from perf buffer
+unsafe impl aya::Pod for TcpEventBpf {}
+
+const AF_INET6: u16 = 10;
+
+/// `TcpEventBpf::passive` of connections opened before tracing started
+const PASSIVE_UNKNOWN: u16 = 2;
+
+/// TCP event collector
+#[derive(Debug)]
+pub struct TcpCollector {
+    /// Collected events
+    events: Vec<TcpEvent>,
+
+    /// Start time
+    start_time: u64,
+
+    /// Index of first event not yet pushed to aggregator
+    push_cursor: usize,
+
+    /// Local listening ports, read when first needed
+    listen_ports: Option<HashSet<u16>>,
+}
+
+impl Default for TcpCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TcpCollector {
+    /// Create a new TCP collector
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            start_time: aperture_shared::utils::time::system_time_nanos(),
+            push_cursor: 0,
+            listen_ports: None,
+        }
+    }
+
+    /// Add an event to the collector
+    pub fn add_event(&mut self, event: TcpEvent) {
+        self.events.push(event);
+    }
+
+    /// Process a raw eBPF event and convert to TcpEvent
+    pub fn process_event(&mut self, event: &TcpEventBpf) -> Result<()> {
+        let Some(kind) = event_kind(event.kind) else {
+            debug!("Unknown TCP event kind {}", event.kind);
+            return Ok(());
+        };
+        let comm = std::str::from_utf8(&event.comm)
+            .unwrap_or("<unknown>")
+            .trim_end_matches('\0')
+            .to_string();
+        let passive = match event.passive {
+            PASSIVE_UNKNOWN => self
+                .listen_ports
+                .get_or_insert_with(listen_ports)
+                .contains(&event.lport),
+            p => p != 0,
+        };
+
+        self.add_event(TcpEvent {
+            timestamp: aperture_shared::utils::time::boot_time_to_system_time(event.timestamp),
+            pid: event.pid as i32,
+            tid: event.tid as i32,
+            comm,
+            kind,
+            remote_addr: remote_addr(event.family, &event.raddr),
+            remote_port: event.rport,
+            local_port: event.lport,
+            passive,
+            duration_ns: event.duration_ns,
+            bytes_sent: event.bytes_sent,
+            bytes_received: event.bytes_received,
+            retransmits: event.retransmits,
+            srtt_us: event.srtt_us,
+        });
+        Ok(())
+    }
+
+    /// Build aggregated profile from collected events
+    pub fn build_profile(&self) -> Result<TcpProfile> {
+        info!("Building TCP profile from {} events", self.events.len());
+
+        let mut profile = TcpProfile::new(self.start_time);
+        profile.end_time = aperture_shared::utils::time::system_time_nanos();
+
+        for event in &self.events {
+            profile.add_event(event);
+        }
+
+        info!(
+            "TCP profile built: {} total events, {} processes, {} endpoints",
+            profile.total_events,
+            profile.processes.len(),
+            profile.endpoints().count()
+        );
+
+        Ok(profile)
+    }
+
+    /// Return events accumulated since the last call and advance the cursor.
+    pub fn take_pending_events(&mut self) -> Vec<ProfileEvent> {
+        let events: Vec<ProfileEvent> = self.events[self.push_cursor..]
+            .iter()
+            .cloned()
+            .map(ProfileEvent::Tcp)
+            .collect();
+        self.push_cursor = self.events.len();
+        events
+    }
+}
+
+/// Event kind numbering of the BPF program
+fn event_kind(kind: u32) -> Option<TcpEventKind> {
+    Some(match kind {
+        0 => TcpEventKind::Connect,
+        1 => TcpEventKind::ConnectFailed,
+        2 => TcpEventKind::Retransmit,
+        3 => TcpEventKind::ResetSent,
+        4 => TcpEventKind::ResetReceived,
+        5 => TcpEventKind::Close,
+        _ => return None,
+    })
+}
+
+/// Remote address from the BPF program's 16 bytes: IPv4 in the first 4,
+/// IPv4-mapped IPv6 addresses shown as IPv4
+fn remote_addr(family: u16, raddr: &[u8; 16]) -> IpAddr {
+    if family == AF_INET6 {
+        let v6 = Ipv6Addr::from(*raddr);
+        match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(v6),
+        }
+    } else {
+        IpAddr::V4(Ipv4Addr::new(raddr[0], raddr[1], raddr[2], raddr[3]))
+    }
+}
+
+/// Ports in LISTEN state in `/proc/net/tcp` and `/proc/net/tcp6` (the
+/// agent's network namespace)
+fn listen_ports() -> HashSet<u16> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| parse_listen_ports(&table))
+        .collect()
+}
+
+/// Local ports of LISTEN (`0A`) sockets in a `/proc/net/tcp{,6}` table,
+/// whose lines look like `0: 00000000:1F90 00000000:0000 0A ...`
+fn parse_listen_ports(table: &str) -> Vec<u16> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let local = fields.next()?;
+            let state = fields.nth(1)?;
+            if state != "0A" {
+                return None;
+            }
+            u16::from_str_radix(local.rsplit(':').next()?, 16).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(kind: u32, passive: u16, lport: u16) -> TcpEventBpf {
+        let mut event = TcpEventBpf {
+            timestamp: 0,
+            duration_ns: 1_500_000,
+            bytes_sent: 300,
+            bytes_received: 9000,
+            pid: 42,
+            tid: 43,
+            kind,
+            retransmits: 2,
+            srtt_us: 250,
+            _pad: 0,
+            family: 2,
+            lport,
+            rport: 443,
+            passive,
+            raddr: [0; 16],
+            comm: [0; 16],
+        };
+        event.raddr[..4].copy_from_slice(&[192, 168, 1, 20]);
+        event.comm[..4].copy_from_slice(b"curl");
+        event
+    }
+
+    #[test]
+    fn test_remote_addr() {
+        let mut raddr = [0u8; 16];
+        raddr[..4].copy_from_slice(&[10, 0, 0, 1]);
+        assert_eq!(remote_addr(2, &raddr).to_string(), "10.0.0.1");
+
+        let v6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert_eq!(remote_addr(10, &v6.octets()).to_string(), "2001:db8::1");
+        let mapped: Ipv6Addr = "::ffff:10.0.0.1".parse().unwrap();
+        assert_eq!(remote_addr(10, &mapped.octets()).to_string(), "10.0.0.1");
+    }
+
+    #[test]
+    fn test_parse_listen_ports() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1234 1
+   1: 0100007F:9C40 0100007F:1F90 01 00000000:00000000 00:00000000 00000000  1000        0 5678 1
+";
+        assert_eq!(parse_listen_ports(table), vec![8080]);
+
+        let table6 =
+            "  sl  local_address                         remote_address                        st
+   0: 00000000000000000000000000000000:0016 00000000000000000000000000000000:0000 0A
+";
+        assert_eq!(parse_listen_ports(table6), vec![22]);
+    }
+
+    #[test]
+    fn test_tcp_collector() {
+        let mut collector = TcpCollector::new();
+        collector.listen_ports = Some(HashSet::from([8080]));
+        collector.process_event(&raw(0, 0, 50000)).unwrap();
+        collector.process_event(&raw(5, 0, 50000)).unwrap();
+        // Opened before tracing: accepted on a listening port, else outgoing
+        collector.process_event(&raw(5, 2, 8080)).unwrap();
+        collector.process_event(&raw(5, 2, 50001)).unwrap();
+        collector.process_event(&raw(99, 0, 50000)).unwrap();
+
+        let profile = collector.build_profile().unwrap();
+        assert_eq!(profile.total_events, 4);
+        let process = &profile.processes[&42];
+        assert_eq!(process.comm, "curl");
+        let outgoing = &process.endpoints["192.168.1.20:443"];
+        assert_eq!(outgoing.connects, 1);
+        assert_eq!(outgoing.closed, 2);
+        assert_eq!(outgoing.bytes_received, 18000);
+        assert_eq!(process.endpoints["192.168.1.20 -> :8080"].closed, 1);
+
+        assert_eq!(collector.take_pending_events().len(), 4);
+        assert!(collector.take_pending_events().is_empty());
+    }
+}
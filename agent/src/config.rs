@@ -8,7 +8,21 @@ use std::time::Duration;
 pub enum ProfileMode {
     Cpu,
     Lock,
+    /// Kernel lock contention (`lock:contention_begin`/`contention_end`)
+    KernelLock,
     Syscall,
+    /// Block device request latency (`block:block_rq_issue`/`block_rq_complete`)
+    BlockIo,
+    /// Scheduler run-queue latency (`sched:sched_wakeup`/`sched_switch`)
+    Sched,
+    /// TCP connect latency, retransmits, resets, bytes and RTT per endpoint
+    Tcp,
+    /// Page faults (`exceptions:page_fault_*`) and direct reclaim stalls
+    /// (`vmscan:mm_vmscan_direct_reclaim_*`)
+    Memory,
+    /// Call counts and latency of user-listed uprobes, kprobes and
+    /// tracepoints
+    Probe,
     All,
 }
 
@@ -19,13 +33,457 @@ impl std::str::FromStr for ProfileMode {
         match s.to_lowercase().as_str() {
             "cpu" => Ok(ProfileMode::Cpu),
             "lock" => Ok(ProfileMode::Lock),
+            "kernel-lock" => Ok(ProfileMode::KernelLock),
             "syscall" => Ok(ProfileMode::Syscall),
+            "block-io" => Ok(ProfileMode::BlockIo),
+            "sched" => Ok(ProfileMode::Sched),
+            "tcp" => Ok(ProfileMode::Tcp),
+            "memory" => Ok(ProfileMode::Memory),
+            "probe" => Ok(ProfileMode::Probe),
             "all" => Ok(ProfileMode::All),
             _ => anyhow::bail!("Invalid profile mode: {}", s),
         }
     }
 }
 
+/// Where user-space frames pushed to the aggregator get their names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymbolizeMode {
+    /// Resolve names on the agent before pushing
+    #[default]
+    Agent,
+    /// Ship build ID + file offset per frame; the aggregator resolves names
+    /// against uploaded debug info
+    Deferred,
+}
+
+impl std::str::FromStr for SymbolizeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "agent" => Ok(SymbolizeMode::Agent),
+            "deferred" => Ok(SymbolizeMode::Deferred),
+            _ => anyhow::bail!("Invalid symbolize mode: {} (expected agent or deferred)", s),
+        }
+    }
+}
+
+/// Persistent symbol cache location and size limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolCacheConfig {
+    /// Directory holding the cache file
+    pub dir: PathBuf,
+
+    /// Size limit; least recently used entries are evicted beyond it
+    pub max_bytes: u64,
+}
+
+impl SymbolCacheConfig {
+    /// Default size limit in MiB
+    pub const DEFAULT_MAX_MB: u64 = 64;
+
+    /// Cache in `dir`, or in the default directory when `None`: the
+    /// `APERTURE_SYMBOL_CACHE_DIR` environment variable, else
+    /// `$XDG_CACHE_HOME/aperture`, else `~/.cache/aperture`. Returns None if
+    /// no directory can be determined.
+    pub fn new(dir: Option<PathBuf>, max_mb: u64) -> Option<Self> {
+        let env_dir = |var: &str| {
+            std::env::var_os(var)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        let dir = dir
+            .or_else(|| env_dir("APERTURE_SYMBOL_CACHE_DIR"))
+            .or_else(|| env_dir("XDG_CACHE_HOME").map(|d| d.join("aperture")))
+            .or_else(|| env_dir("HOME").map(|d| d.join(".cache").join("aperture")))?;
+        Some(Self {
+            dir,
+            max_bytes: max_mb * 1024 * 1024,
+        })
+    }
+}
+
+/// In-kernel syscall filtering and aggregation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallFilter {
+    /// Trace only these syscall ids (empty = all)
+    pub allow: Vec<u32>,
+
+    /// Never trace these syscall ids
+    pub deny: Vec<u32>,
+
+    /// Drop calls shorter than this
+    pub min_latency: Option<Duration>,
+
+    /// Record 1 in N calls; counts are scaled back up by N
+    pub sample_every: u32,
+
+    /// Keep per-syscall latency histograms in a BPF map instead of emitting
+    /// an event per call (no per-call arguments or stacks)
+    pub aggregate: bool,
+}
+
+impl Default for SyscallFilter {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            min_latency: None,
+            sample_every: 1,
+            aggregate: false,
+        }
+    }
+}
+
+impl SyscallFilter {
+    /// Filter from command-line values: comma-separated syscall names or
+    /// numbers, and a duration string for the latency threshold
+    pub fn from_args(
+        allow: Option<&str>,
+        deny: Option<&str>,
+        min_latency: Option<&str>,
+        sample_every: u32,
+        aggregate: bool,
+    ) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        use aperture_shared::utils::syscalls::parse_syscall_list;
+
+        Ok(Self {
+            allow: allow
+                .map(parse_syscall_list)
+                .transpose()
+                .context("Failed to parse syscall allowlist")?
+                .unwrap_or_default(),
+            deny: deny
+                .map(parse_syscall_list)
+                .transpose()
+                .context("Failed to parse syscall denylist")?
+                .unwrap_or_default(),
+            min_latency: min_latency
+                .map(aperture_shared::utils::parse_duration)
+                .transpose()
+                .context("Failed to parse syscall min latency")?,
+            sample_every,
+            aggregate,
+        })
+    }
+}
+
+/// User-space lock tracing with uprobes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockUprobeConfig {
+    /// Probe pthread mutex/rwlock and parking_lot lock functions
+    pub enabled: bool,
+
+    /// Binaries searched for lock functions besides the target's mappings
+    /// (or libc, without a target), e.g. statically linked programs
+    pub binaries: Vec<PathBuf>,
+
+    /// Drop acquires and holds shorter than this
+    pub min_duration: Duration,
+
+    /// Probe the target's malloc and free so heap locks are named after
+    /// their allocation site
+    pub alloc_sites: bool,
+}
+
+impl Default for LockUprobeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            binaries: Vec::new(),
+            min_duration: Self::DEFAULT_MIN_DURATION,
+            alloc_sites: false,
+        }
+    }
+}
+
+impl LockUprobeConfig {
+    /// Uncontended acquires and short critical sections take well under this
+    pub const DEFAULT_MIN_DURATION: Duration = Duration::from_micros(1);
+
+    /// Config from command-line values, with a duration string for the
+    /// threshold
+    pub fn from_args(
+        enabled: bool,
+        binaries: Vec<PathBuf>,
+        min_duration: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        Ok(Self {
+            enabled,
+            binaries,
+            min_duration: min_duration
+                .map(aperture_shared::utils::parse_duration)
+                .transpose()
+                .context("Failed to parse lock min duration")?
+                .unwrap_or(Self::DEFAULT_MIN_DURATION),
+            alloc_sites: false,
+        })
+    }
+}
+
+/// Run-queue latency tracing in sched mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedConfig {
+    /// Capture the stack that made each thread runnable: the waker's, or
+    /// the thread's own when it was preempted
+    pub stacks: bool,
+
+    /// Record the task that had the CPU before the waiting thread for waits
+    /// at least this long
+    pub long_wait: Duration,
+
+    /// Drop waits shorter than this, in the kernel
+    pub min_latency: Option<Duration>,
+}
+
+impl Default for SchedConfig {
+    fn default() -> Self {
+        Self {
+            stacks: false,
+            long_wait: Self::DEFAULT_LONG_WAIT,
+            min_latency: None,
+        }
+    }
+}
+
+impl SchedConfig {
+    /// Several scheduler ticks at common HZ values
+    pub const DEFAULT_LONG_WAIT: Duration = Duration::from_millis(10);
+
+    /// Config from command-line values, with duration strings for the
+    /// thresholds
+    pub fn from_args(
+        stacks: bool,
+        long_wait: Option<&str>,
+        min_latency: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        Ok(Self {
+            stacks,
+            long_wait: long_wait
+                .map(aperture_shared::utils::parse_duration)
+                .transpose()
+                .context("Failed to parse sched long wait")?
+                .unwrap_or(Self::DEFAULT_LONG_WAIT),
+            min_latency: min_latency
+                .map(aperture_shared::utils::parse_duration)
+                .transpose()
+                .context("Failed to parse sched min latency")?,
+        })
+    }
+}
+
+/// Page fault sampling in memory mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryConfig {
+    /// Record 1 in N page faults; fault counts are scaled back up by N
+    pub fault_sample_every: u32,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            fault_sample_every: 1,
+        }
+    }
+}
+
+/// Most probes one run can attach (the probe tracer's program slots)
+pub const MAX_PROBES: usize = 16;
+
+/// A user-defined probe point
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeSpec {
+    /// `uprobe:<binary>:<symbol>`: entry and return of a user-space
+    /// function; the binary is a path or a library name (`libc`)
+    Uprobe { binary: PathBuf, symbol: String },
+    /// `kprobe:<function>`: entry and return of a kernel function
+    Kprobe { function: String },
+    /// `tracepoint:<category>:<name>`: hits of a kernel tracepoint
+    Tracepoint { category: String, name: String },
+    /// `usdt:<binary>:<provider>:<name>`: hits of a USDT probe in the
+    /// binary's `.note.stapsdt` section, with its arguments
+    Usdt {
+        binary: PathBuf,
+        provider: String,
+        name: String,
+    },
+}
+
+impl ProbeSpec {
+    /// Whether calls are timed from entry to return
+    pub fn has_return(&self) -> bool {
+        !matches!(self, ProbeSpec::Tracepoint { .. } | ProbeSpec::Usdt { .. })
+    }
+}
+
+impl std::str::FromStr for ProbeSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let nonempty = |part: Option<&str>| part.filter(|p| !p.is_empty()).map(str::to_string);
+        let (kind, target) = s.split_once(':').unwrap_or((s, ""));
+        // Symbols may hold `::`, so the binary ends at the first colon
+        let mut parts = target.splitn(2, ':');
+        let spec = match kind {
+            "uprobe" => {
+                nonempty(parts.next())
+                    .zip(nonempty(parts.next()))
+                    .map(|(binary, symbol)| ProbeSpec::Uprobe {
+                        binary: PathBuf::from(binary),
+                        symbol,
+                    })
+            }
+            "kprobe" => nonempty(Some(target)).map(|function| ProbeSpec::Kprobe { function }),
+            "tracepoint" => nonempty(parts.next())
+                .zip(nonempty(parts.next()))
+                .map(|(category, name)| ProbeSpec::Tracepoint { category, name }),
+            "usdt" => {
+                let binary = nonempty(parts.next());
+                let (provider, name) = parts.next().unwrap_or("").split_once(':').unzip();
+                binary.zip(nonempty(provider)).zip(nonempty(name)).map(
+                    |((binary, provider), name)| ProbeSpec::Usdt {
+                        binary: PathBuf::from(binary),
+                        provider,
+                        name,
+                    },
+                )
+            }
+            _ => None,
+        };
+        spec.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid probe: {} (expected uprobe:BINARY:SYMBOL, kprobe:FUNCTION, tracepoint:CATEGORY:NAME or usdt:BINARY:PROVIDER:NAME)",
+                s
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for ProbeSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeSpec::Uprobe { binary, symbol } => {
+                write!(f, "uprobe:{}:{}", binary.display(), symbol)
+            }
+            ProbeSpec::Kprobe { function } => write!(f, "kprobe:{}", function),
+            ProbeSpec::Tracepoint { category, name } => {
+                write!(f, "tracepoint:{}:{}", category, name)
+            }
+            ProbeSpec::Usdt {
+                binary,
+                provider,
+                name,
+            } => write!(f, "usdt:{}:{}:{}", binary.display(), provider, name),
+        }
+    }
+}
+
+/// USDT probes whose hits on one thread open and close a timed span
+/// (`PROVIDER:START:END`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtPair {
+    pub provider: String,
+    pub start: String,
+    pub end: String,
+}
+
+impl UsdtPair {
+    /// Span name in events and reports (`postgresql:query__start..query__done`)
+    pub fn name(&self) -> String {
+        format!("{}:{}..{}", self.provider, self.start, self.end)
+    }
+}
+
+impl std::str::FromStr for UsdtPair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(':').collect::<Vec<_>>()[..] {
+            [provider, start, end]
+                if !provider.is_empty() && !start.is_empty() && !end.is_empty() =>
+            {
+                Ok(Self {
+                    provider: provider.to_string(),
+                    start: start.to_string(),
+                    end: end.to_string(),
+                })
+            }
+            _ => anyhow::bail!("Invalid USDT pair: {} (expected PROVIDER:START:END)", s),
+        }
+    }
+}
+
+/// User-defined probes of probe mode
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeConfig {
+    /// Functions and tracepoints to attach to
+    pub probes: Vec<ProbeSpec>,
+
+    /// Capture the user + kernel stack at each function entry or hit
+    pub stacks: bool,
+
+    /// Drop calls shorter than this, in the kernel
+    pub min_latency: Option<Duration>,
+
+    /// USDT probes timed as start/end pairs
+    pub pairs: Vec<UsdtPair>,
+}
+
+impl ProbeConfig {
+    /// Config from command-line values: probe specs, a duration string
+    /// for the latency threshold and USDT pairs
+    pub fn from_args(
+        probes: &[String],
+        stacks: bool,
+        min_latency: Option<&str>,
+        pairs: &[String],
+    ) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        Ok(Self {
+            probes: probes
+                .iter()
+                .map(|p| p.parse())
+                .collect::<anyhow::Result<_>>()?,
+            stacks,
+            min_latency: min_latency
+                .map(aperture_shared::utils::parse_duration)
+                .transpose()
+                .context("Failed to parse probe min latency")?,
+            pairs: pairs
+                .iter()
+                .map(|p| p.parse())
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+/// Profiling labels set by applications with the `aperture-labels` library
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelConfig {
+    /// Attach thread labels to CPU samples and lock events
+    pub enabled: bool,
+
+    /// Binaries searched for the label function besides the target's
+    /// mappings, e.g. when profiling all processes
+    pub binaries: Vec<PathBuf>,
+}
+
+/// Tracing the target's children along with it, when the agent launched the
+/// target: the CPU profiler's perf events are inherited by new threads and
+/// processes, and the other programs also trace processes in `cgroup`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FollowChildren {
+    /// cgroup holding the target and its children (None = not created)
+    pub cgroup: Option<u64>,
+}
+
 /// Agent configuration
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -56,6 +514,45 @@ pub struct Config {
     /// Push interval in seconds when streaming to aggregator (None = library default, e.g. 5s).
     /// Set via APERTURE_LOW_OVERHEAD=1 for lower CPU/network overhead (e.g. 10s).
     pub push_interval_secs: Option<u64>,
+
+    /// How user-space frames pushed to the aggregator are symbolized
+    pub symbolize: SymbolizeMode,
+
+    /// Persistent symbol cache shared across runs (None = disabled)
+    pub symbol_cache: Option<SymbolCacheConfig>,
+
+    /// JSON file of frame rewrite/collapse rules applied after symbolization
+    pub normalize_rules: Option<PathBuf>,
+
+    /// Capture user + kernel stacks for syscalls taking at least this long
+    /// (None = syscall events carry no stacks)
+    pub syscall_stack_threshold: Option<Duration>,
+
+    /// Which syscalls the tracer records, and whether it aggregates in-kernel
+    pub syscall_filter: SyscallFilter,
+
+    /// Uprobe tracing of user-space locks in lock mode
+    pub lock_uprobes: LockUprobeConfig,
+
+    /// Stacks and thresholds of sched mode
+    pub sched: SchedConfig,
+
+    /// Page fault sampling of memory mode
+    pub memory: MemoryConfig,
+
+    /// Probe points, stacks and threshold of probe mode
+    pub probes: ProbeConfig,
+
+    /// Thread labels on CPU samples and lock events
+    pub labels: LabelConfig,
+
+    /// Capture file the raw events and mapping snapshots are recorded to,
+    /// for `replay` (CPU samples, lock and syscall events only)
+    pub record: Option<PathBuf>,
+
+    /// Children of the target traced along with it (set for launched
+    /// commands)
+    pub follow_children: Option<FollowChildren>,
 }
 
 impl Config {
@@ -74,6 +571,17 @@ impl Config {
             .unwrap_or(Duration::from_secs(5))
     }
 
+    /// True when the run samples stacks that need symbolization
+    pub fn captures_stacks(&self) -> bool {
+        match self.mode {
+            ProfileMode::Syscall => self.syscall_stack_threshold.is_some(),
+            ProfileMode::BlockIo | ProfileMode::Tcp => false,
+            ProfileMode::Sched => self.sched.stacks,
+            ProfileMode::Probe => self.probes.stacks,
+            _ => true,
+        }
+    }
+
     /// Validate configuration
     pub fn validate(&self) -> anyhow::Result<()> {
         // Sample rate only matters for CPU profiling
@@ -91,26 +599,127 @@ impl Config {
             anyhow::bail!("Duration must be greater than 0");
         }
 
+        let filter = &self.syscall_filter;
+        if !filter.allow.is_empty() && !filter.deny.is_empty() {
+            anyhow::bail!("Syscall allowlist and denylist cannot be combined");
+        }
+        if filter.sample_every == 0 {
+            anyhow::bail!("Syscall sampling ratio must be at least 1");
+        }
+        if self.memory.fault_sample_every == 0 {
+            anyhow::bail!("Page fault sampling ratio must be at least 1");
+        }
+        if self.record.is_some()
+            && !matches!(
+                self.mode,
+                ProfileMode::Cpu | ProfileMode::Lock | ProfileMode::Syscall | ProfileMode::All
+            )
+        {
+            anyhow::bail!(
+                "Only CPU samples, lock and syscall events can be recorded; use the cpu, lock, syscall or all mode"
+            );
+        }
+        if filter.aggregate && self.syscall_stack_threshold.is_some() {
+            anyhow::bail!(
+                "Syscall stacks need per-call events; drop the stack threshold or aggregation"
+            );
+        }
+        if !self.lock_uprobes.enabled && !self.lock_uprobes.binaries.is_empty() {
+            anyhow::bail!("Lock binaries are only searched with lock uprobes enabled");
+        }
+        if self.lock_uprobes.alloc_sites && self.target_pid.is_none() {
+            anyhow::bail!("Lock allocation sites need a target PID");
+        }
+        if !self.labels.enabled && !self.labels.binaries.is_empty() {
+            anyhow::bail!("Label binaries are only searched with labels enabled");
+        }
+        if self.labels.enabled {
+            if !matches!(
+                self.mode,
+                ProfileMode::Cpu | ProfileMode::Lock | ProfileMode::All
+            ) {
+                anyhow::bail!("Labels are only recorded in cpu, lock and all modes");
+            }
+            if self.target_pid.is_none() && self.labels.binaries.is_empty() {
+                anyhow::bail!("Labels need a target PID or a label binary");
+            }
+        }
+
+        let probes = &self.probes.probes;
+        if self.mode == ProfileMode::Probe && probes.is_empty() {
+            anyhow::bail!("Probe mode needs at least one probe");
+        }
+        if !matches!(self.mode, ProfileMode::Probe | ProfileMode::All) && !probes.is_empty() {
+            anyhow::bail!("Probes are only attached in probe and all modes");
+        }
+        if probes.len() > MAX_PROBES {
+            anyhow::bail!("Too many probes: {} (max {})", probes.len(), MAX_PROBES);
+        }
+        if let Some((_, probe)) = probes
+            .iter()
+            .enumerate()
+            .find(|(i, p)| probes[..*i].contains(p))
+        {
+            anyhow::bail!("Probe {} is listed twice", probe);
+        }
+        for pair in &self.probes.pairs {
+            for name in [&pair.start, &pair.end] {
+                let listed = probes.iter().any(|p| {
+                    matches!(p, ProbeSpec::Usdt { provider, name: n, .. }
+                        if *provider == pair.provider && n == name)
+                });
+                if !listed {
+                    anyhow::bail!(
+                        "USDT pair {} needs probe {}:{} to be listed",
+                        pair.name(),
+                        pair.provider,
+                        name
+                    );
+                }
+            }
+        }
+
         Ok(())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
-    #[test]
-    fn test_sample_period_calculation() {
-        let config = Config {
+    /// Valid CPU-mode config for tests to override
+    pub(crate) fn base_config() -> Config {
+        Config {
             mode: ProfileMode::Cpu,
             target_pid: None,
-            sample_rate_hz: 100,
-            duration: Duration::from_secs(10),
+            sample_rate_hz: 99,
+            duration: Duration::from_secs(5),
             output_path: "test.svg".to_string(),
             json_output: None,
             filter_path: None,
             aggregator_url: None,
             push_interval_secs: None,
+            symbolize: SymbolizeMode::Agent,
+            symbol_cache: None,
+            normalize_rules: None,
+            syscall_stack_threshold: None,
+            syscall_filter: SyscallFilter::default(),
+            lock_uprobes: LockUprobeConfig::default(),
+            sched: SchedConfig::default(),
+            memory: MemoryConfig::default(),
+            probes: ProbeConfig::default(),
+            labels: LabelConfig::default(),
+            record: None,
+            follow_children: None,
+        }
+    }
+
+    #[test]
+    fn test_sample_period_calculation() {
+        let config = Config {
+            sample_rate_hz: 100,
+            duration: Duration::from_secs(10),
+            ..base_config()
         };
 
         assert_eq!(config.sample_period_ns(), 10_000_000);
@@ -119,29 +728,23 @@ mod tests {
     #[test]
     fn test_config_validation() {
         let valid = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
-            sample_rate_hz: 99,
             duration: Duration::from_secs(30),
-            output_path: "test.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
 
         assert!(valid.validate().is_ok());
 
+        // Recording is limited to the modes replay rebuilds
+        let mut recorded = valid.clone();
+        recorded.record = Some(PathBuf::from("run.apcap"));
+        assert!(recorded.validate().is_ok());
+        recorded.mode = ProfileMode::Tcp;
+        assert!(recorded.validate().is_err());
+
         let invalid = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
             sample_rate_hz: 0,
             duration: Duration::from_secs(30),
-            output_path: "test.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
 
         assert!(invalid.validate().is_err());
@@ -150,15 +753,8 @@ mod tests {
     #[test]
     fn test_validation_rate_too_high() {
         let config = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
             sample_rate_hz: 10001,
-            duration: Duration::from_secs(5),
-            output_path: "test.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
         assert!(config.validate().is_err());
     }
@@ -166,15 +762,8 @@ mod tests {
     #[test]
     fn test_validation_max_rate_ok() {
         let config = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
             sample_rate_hz: 10000,
-            duration: Duration::from_secs(5),
-            output_path: "test.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
         assert!(config.validate().is_ok());
     }
@@ -182,15 +771,8 @@ mod tests {
     #[test]
     fn test_validation_zero_duration() {
         let config = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
-            sample_rate_hz: 99,
             duration: Duration::from_secs(0),
-            output_path: "test.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
         assert!(config.validate().is_err());
     }
@@ -198,15 +780,9 @@ mod tests {
     #[test]
     fn test_sample_period_zero_rate() {
         let config = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
             sample_rate_hz: 0,
             duration: Duration::from_secs(1),
-            output_path: "test.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
         assert_eq!(config.sample_period_ns(), 0);
     }
@@ -214,15 +790,9 @@ mod tests {
     #[test]
     fn test_push_interval_default_and_override() {
         let default_config = Config {
-            mode: ProfileMode::Cpu,
-            target_pid: None,
-            sample_rate_hz: 99,
             duration: Duration::from_secs(10),
             output_path: "out.svg".to_string(),
-            json_output: None,
-            filter_path: None,
-            aggregator_url: None,
-            push_interval_secs: None,
+            ..base_config()
         };
         assert_eq!(default_config.push_interval(), Duration::from_secs(5));
 
@@ -232,4 +802,211 @@ mod tests {
         };
         assert_eq!(low_overhead_config.push_interval(), Duration::from_secs(10));
     }
+
+    #[test]
+    fn test_syscall_filter_validation() {
+        let filter =
+            SyscallFilter::from_args(Some("read,write"), None, Some("1ms"), 10, true).unwrap();
+        assert_eq!(filter.allow, vec![0, 1]);
+        assert_eq!(filter.min_latency, Some(Duration::from_millis(1)));
+
+        let mut config = Config {
+            mode: ProfileMode::Syscall,
+            output_path: "test.txt".to_string(),
+            syscall_filter: filter,
+            ..base_config()
+        };
+        assert!(config.validate().is_ok());
+
+        config.syscall_stack_threshold = Some(Duration::from_millis(10));
+        assert!(config.validate().is_err());
+        config.syscall_stack_threshold = None;
+
+        config.syscall_filter.deny = vec![202];
+        assert!(config.validate().is_err());
+        config.syscall_filter.deny.clear();
+
+        config.syscall_filter.sample_every = 0;
+        assert!(config.validate().is_err());
+        config.syscall_filter.sample_every = 1;
+        assert!(SyscallFilter::from_args(Some("bogus"), None, None, 1, false).is_err());
+
+        config.memory.fault_sample_every = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_lock_uprobe_config() {
+        let uprobes = LockUprobeConfig::from_args(true, vec![], Some("50us")).unwrap();
+        assert_eq!(uprobes.min_duration, Duration::from_micros(50));
+        let default = LockUprobeConfig::from_args(false, vec![], None).unwrap();
+        assert_eq!(default, LockUprobeConfig::default());
+        assert!(LockUprobeConfig::from_args(true, vec![], Some("soon")).is_err());
+
+        let mut config = Config {
+            mode: ProfileMode::Lock,
+            lock_uprobes: default,
+            ..base_config()
+        };
+        config.lock_uprobes.binaries = vec![PathBuf::from("/usr/bin/server")];
+        assert!(config.validate().is_err());
+        config.lock_uprobes.enabled = true;
+        assert!(config.validate().is_ok());
+
+        config.lock_uprobes.alloc_sites = true;
+        assert!(config.validate().is_err());
+        config.target_pid = Some(1234);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_label_config() {
+        let mut config = Config {
+            labels: LabelConfig {
+                enabled: false,
+                binaries: vec![PathBuf::from("/usr/bin/server")],
+            },
+            ..base_config()
+        };
+        assert!(config.validate().is_err());
+        config.labels.enabled = true;
+        assert!(config.validate().is_ok());
+
+        // Without a binary, the target's mappings are searched
+        config.labels.binaries.clear();
+        assert!(config.validate().is_err());
+        config.target_pid = Some(1234);
+        assert!(config.validate().is_ok());
+
+        config.mode = ProfileMode::Syscall;
+        assert!(config.validate().is_err());
+        config.mode = ProfileMode::Lock;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_sched_config() {
+        use std::str::FromStr;
+        assert_eq!(ProfileMode::from_str("sched").unwrap(), ProfileMode::Sched);
+
+        let sched = SchedConfig::from_args(true, Some("5ms"), Some("100us")).unwrap();
+        assert_eq!(sched.long_wait, Duration::from_millis(5));
+        assert_eq!(sched.min_latency, Some(Duration::from_micros(100)));
+        let default = SchedConfig::from_args(false, None, None).unwrap();
+        assert_eq!(default, SchedConfig::default());
+        assert!(SchedConfig::from_args(false, Some("later"), None).is_err());
+
+        let mut config = Config {
+            mode: ProfileMode::Sched,
+            output_path: "runq.txt".to_string(),
+            sched: default,
+            ..base_config()
+        };
+        assert!(!config.captures_stacks());
+        config.sched.stacks = true;
+        assert!(config.captures_stacks());
+    }
+
+    #[test]
+    fn test_probe_config() {
+        use std::str::FromStr;
+        assert_eq!(ProfileMode::from_str("probe").unwrap(), ProfileMode::Probe);
+
+        let specs = [
+            "uprobe:/usr/bin/app:app::handler",
+            "kprobe:vfs_read",
+            "tracepoint:net:netif_rx",
+            "usdt:/usr/bin/postgres:postgresql:query__start",
+            "usdt:/usr/bin/postgres:postgresql:query__done",
+        ]
+        .map(String::from);
+        let pairs = ["postgresql:query__start:query__done".to_string()];
+        let probes = ProbeConfig::from_args(&specs, true, Some("10us"), &pairs).unwrap();
+        assert_eq!(
+            probes.probes,
+            vec![
+                ProbeSpec::Uprobe {
+                    binary: PathBuf::from("/usr/bin/app"),
+                    symbol: "app::handler".to_string(),
+                },
+                ProbeSpec::Kprobe {
+                    function: "vfs_read".to_string(),
+                },
+                ProbeSpec::Tracepoint {
+                    category: "net".to_string(),
+                    name: "netif_rx".to_string(),
+                },
+                ProbeSpec::Usdt {
+                    binary: PathBuf::from("/usr/bin/postgres"),
+                    provider: "postgresql".to_string(),
+                    name: "query__start".to_string(),
+                },
+                ProbeSpec::Usdt {
+                    binary: PathBuf::from("/usr/bin/postgres"),
+                    provider: "postgresql".to_string(),
+                    name: "query__done".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            probes.pairs[0].name(),
+            "postgresql:query__start..query__done"
+        );
+        assert_eq!(probes.min_latency, Some(Duration::from_micros(10)));
+        for (spec, probe) in specs.iter().zip(&probes.probes) {
+            assert_eq!(&probe.to_string(), spec);
+        }
+        assert!(!probes.probes[2].has_return());
+        for bad in [
+            "uprobe:/usr/bin/app",
+            "kprobe:",
+            "tracepoint:net",
+            "fentry:vfs_read",
+            "usdt:/usr/bin/postgres:postgresql",
+        ] {
+            assert!(ProbeSpec::from_str(bad).is_err(), "{}", bad);
+        }
+        assert!(UsdtPair::from_str("postgresql:query__start").is_err());
+
+        let mut config = Config {
+            mode: ProfileMode::Probe,
+            output_path: "probes.txt".to_string(),
+            probes,
+            ..base_config()
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.captures_stacks());
+
+        config.probes.probes.push(config.probes.probes[1].clone());
+        assert!(config.validate().is_err());
+        config.probes.probes.truncate(5);
+
+        config.mode = ProfileMode::Cpu;
+        assert!(config.validate().is_err());
+        config.mode = ProfileMode::All;
+        assert!(config.validate().is_ok());
+        config.mode = ProfileMode::Probe;
+
+        // Both probes of a pair must be attached
+        config.probes.probes.truncate(4);
+        assert!(config.validate().is_err());
+        config.probes.pairs.clear();
+
+        config.probes.probes.clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_symbolize_mode_parse() {
+        use std::str::FromStr;
+        assert_eq!(
+            SymbolizeMode::from_str("agent").unwrap(),
+            SymbolizeMode::Agent
+        );
+        assert_eq!(
+            SymbolizeMode::from_str("Deferred").unwrap(),
+            SymbolizeMode::Deferred
+        );
+        assert!(SymbolizeMode::from_str("remote").is_err());
+    }
 }
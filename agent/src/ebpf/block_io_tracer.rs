@@ -0,0 +1,87 @@
+//! Block I/O tracer eBPF program management
+//!
+//! Handles the lifecycle of the block I/O tracing eBPF program
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, TracepointLinks};
+use crate::config::FollowChildren;
+
+/// Block I/O tracer manager
+pub struct BlockIoTracer {
+    bpf: Ebpf,
+    links: Option<TracepointLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+}
+
+impl BlockIoTracer {
+    /// Create a new block I/O tracer
+    pub fn new() -> Result<Self> {
+        info!("Initializing block I/O tracer");
+
+        let bpf = loader::load_block_io_tracer().context("Failed to load block I/O tracer eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            target_pid: None,
+            follow_children: None,
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Start tracing
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting block I/O tracing");
+
+        if self.links.is_some() {
+            warn!("Block I/O tracer already started");
+            return Ok(());
+        }
+
+        let links =
+            loader::attach_block_io_tracer(&mut self.bpf, self.target_pid, self.follow_children)
+                .context("Failed to attach block I/O tracer")?;
+        self.links = Some(links);
+
+        info!("Block I/O tracing started successfully");
+        Ok(())
+    }
+
+    /// Stop tracing
+    pub fn stop(&mut self) {
+        info!("Stopping block I/O tracing");
+
+        if let Some(_links) = self.links.take() {
+            info!("Block I/O tracing stopped");
+        } else {
+            warn!("Block I/O tracer was not running");
+        }
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for BlockIoTracer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -6,14 +6,18 @@ use anyhow::{Context, Result};
 use aya::Ebpf;
 use tracing::{info, warn};
 
-use super::loader::{self, PerfEventLinks};
+use super::loader::{self, PerfEventLinks, UProbeLinks};
+use crate::config::{FollowChildren, LabelConfig};
 
 /// CPU profiler manager
 pub struct CpuProfiler {
     bpf: Ebpf,
     links: Option<PerfEventLinks>,
+    label_links: Option<UProbeLinks>,
     sample_rate_hz: u64,
     target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    labels: LabelConfig,
 }
 
 impl CpuProfiler {
@@ -27,8 +31,11 @@ impl CpuProfiler {
         Ok(Self {
             bpf,
             links: None,
+            label_links: None,
             sample_rate_hz,
             target_pid: None,
+            follow_children: None,
+            labels: LabelConfig::default(),
         })
     }
 
@@ -42,6 +49,16 @@ impl CpuProfiler {
         self.target_pid = pid;
     }
 
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Set thread label tracking (off by default)
+    pub fn set_labels(&mut self, labels: LabelConfig) {
+        self.labels = labels;
+    }
+
     /// Start profiling
     pub fn start(&mut self) -> Result<()> {
         info!("Starting CPU profiling");
@@ -52,11 +69,24 @@ impl CpuProfiler {
         }
 
         // Attach eBPF program to perf events
-        let links =
-            loader::attach_cpu_profiler(&mut self.bpf, self.sample_rate_hz, self.target_pid)
-                .context("Failed to attach CPU profiler")?;
+        let links = loader::attach_cpu_profiler(
+            &mut self.bpf,
+            self.sample_rate_hz,
+            self.target_pid,
+            self.follow_children,
+        )
+        .context("Failed to attach CPU profiler")?;
 
         self.links = Some(links);
+
+        // Samples are recorded without labels when they can't be read
+        if self.labels.enabled {
+            match loader::attach_label_uprobes(&mut self.bpf, self.target_pid, &self.labels) {
+                Ok(links) if links.is_empty() => warn!("No binaries using profiling labels found"),
+                Ok(links) => self.label_links = Some(links),
+                Err(e) => warn!("Failed to attach label uprobes: {:#}", e),
+            }
+        }
         info!("CPU profiling started successfully");
 
         Ok(())
@@ -66,6 +96,7 @@ impl CpuProfiler {
     pub fn stop(&mut self) -> Result<()> {
         info!("Stopping CPU profiling");
 
+        self.label_links = None;
         if let Some(links) = self.links.take() {
             loader::cleanup(links);
             info!("CPU profiling stopped");
@@ -0,0 +1,92 @@
+//! Kernel lock profiler eBPF program management
+//!
+//! Kernel lock contention is traced by programs of the lock profiler
+//! object, attached to the `lock` tracepoints instead of futex syscalls
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, TracepointLinks};
+use crate::config::FollowChildren;
+
+/// Kernel lock profiler manager
+pub struct KernelLockProfiler {
+    bpf: Ebpf,
+    links: Option<TracepointLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+}
+
+impl KernelLockProfiler {
+    /// Create a new kernel lock profiler
+    pub fn new() -> Result<Self> {
+        info!("Initializing kernel lock profiler");
+
+        let bpf =
+            loader::load_lock_profiler().context("Failed to load kernel lock profiler eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            target_pid: None,
+            follow_children: None,
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Start profiling
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting kernel lock profiling");
+
+        if self.links.is_some() {
+            warn!("Kernel lock profiler already started");
+            return Ok(());
+        }
+
+        let links = loader::attach_kernel_lock_profiler(
+            &mut self.bpf,
+            self.target_pid,
+            self.follow_children,
+        )
+        .context("Failed to attach kernel lock profiler")?;
+        self.links = Some(links);
+
+        info!("Kernel lock profiling started successfully");
+        Ok(())
+    }
+
+    /// Stop profiling
+    pub fn stop(&mut self) {
+        info!("Stopping kernel lock profiling");
+
+        if let Some(_links) = self.links.take() {
+            info!("Kernel lock profiling stopped");
+        } else {
+            warn!("Kernel lock profiler was not running");
+        }
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for KernelLockProfiler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,209 @@
+//! Binaries whose threads set profiling labels
+//!
+//! Applications set labels with the `aperture-labels` library, which calls
+//! its `aperture_labels_set` function on every change. A uprobe there copies
+//! the thread's labels into the THREAD_LABELS map of the CPU and lock
+//! programs, so it has to be attached in each binary linking the library.
+//! Labels the target's threads set before then are read from the library's
+//! threads table when attaching.
+
+use anyhow::{Context, Result};
+use aperture_labels::{ThreadSlot, LABELS_SIZE, MAX_THREADS, THREADS_SYMBOL};
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use crate::collector::{jit, mount_ns};
+
+/// Labels of a thread as THREAD_LABELS stores them (must match
+/// agent-ebpf/src/labels.rs)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ThreadLabelsBpf {
+    pub pid: u32,
+    pub _pad: u32,
+    pub labels: [u8; LABELS_SIZE],
+}
+
+unsafe impl aya::Pod for ThreadLabelsBpf {}
+
+/// Whether the ELF file at `path` defines the label function
+pub fn defines_label_function(path: &Path) -> Result<bool> {
+    use symbolic::debuginfo::Object;
+
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let object =
+        Object::parse(&data).with_context(|| format!("Failed to parse {}", path.display()))?;
+    let defined = object.symbols().any(|symbol| {
+        symbol.address != 0 && symbol.name.as_deref() == Some(aperture_labels::LABELS_SYMBOL)
+    });
+    Ok(defined)
+}
+
+/// Binaries to probe for label changes: those of `binaries` and of the
+/// target's executable mappings that define the label function
+pub fn label_binaries(target_pid: Option<i32>, binaries: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = binaries.to_vec();
+    if let Some(pid) = target_pid {
+        let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap_or_default();
+        let other_ns = mount_ns::in_other_mount_ns(pid);
+        for mapping in mount_ns::parse_exec_mappings(&maps) {
+            if mapping.deleted {
+                continue;
+            }
+            let path = if other_ns {
+                PathBuf::from(format!("/proc/{}/root{}", pid, mapping.path))
+            } else {
+                PathBuf::from(&mapping.path)
+            };
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files
+        .into_iter()
+        .filter(|path| match defines_label_function(path) {
+            Ok(defined) => defined,
+            Err(e) => {
+                tracing::debug!("Skipping {} for labels: {}", path.display(), e);
+                false
+            }
+        })
+        .collect()
+}
+
+/// Current labels of the threads of `pid` that set some through the library
+/// in `binary`, by thread ID as the agent sees it
+pub fn existing_thread_labels(pid: i32, binary: &Path) -> Result<Vec<(u32, [u8; LABELS_SIZE])>> {
+    let table = threads_table_address(pid, binary)?;
+    let mem = File::open(format!("/proc/{}/mem", pid))
+        .with_context(|| format!("Failed to open memory of PID {}", pid))?;
+    let mut slots = vec![0u8; MAX_THREADS * std::mem::size_of::<ThreadSlot>()];
+    mem.read_exact_at(&mut slots, table)
+        .with_context(|| format!("Failed to read the label threads of PID {}", pid))?;
+
+    // The library records thread IDs of the target's PID namespace
+    let host_tids = host_tids(pid);
+    let mut threads = Vec::new();
+    for slot in slots.chunks_exact(std::mem::size_of::<ThreadSlot>()) {
+        let tid = u32::from_ne_bytes(slot[..4].try_into().unwrap());
+        let buf = u64::from_ne_bytes(slot[8..16].try_into().unwrap());
+        // u32::MAX: the slot is being claimed
+        if tid == 0 || tid == u32::MAX || buf == 0 {
+            continue;
+        }
+        let Some(&tid) = host_tids.get(&tid) else {
+            continue;
+        };
+        let mut labels = [0u8; LABELS_SIZE];
+        if mem.read_exact_at(&mut labels, buf).is_ok() && labels[0] != 0 {
+            threads.push((tid, labels));
+        }
+    }
+    Ok(threads)
+}
+
+/// Address of the library's threads table in the memory of `pid`, from its
+/// address in `binary` and where `pid` mapped `binary`
+fn threads_table_address(pid: i32, binary: &Path) -> Result<u64> {
+    use goblin::elf::program_header::{PF_X, PT_LOAD};
+    use goblin::elf::Elf;
+
+    let data =
+        std::fs::read(binary).with_context(|| format!("Failed to read {}", binary.display()))?;
+    let elf = Elf::parse(&data).with_context(|| format!("Failed to parse {}", binary.display()))?;
+    let address = elf
+        .syms
+        .iter()
+        .find(|sym| sym.st_value != 0 && elf.strtab.get_at(sym.st_name) == Some(THREADS_SYMBOL))
+        .map(|sym| sym.st_value)
+        .with_context(|| format!("{} has no {}", binary.display(), THREADS_SYMBOL))?;
+
+    let file = File::open(binary)?;
+    let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid))
+        .with_context(|| format!("Failed to read maps of PID {}", pid))?;
+    let mapping = mount_ns::parse_exec_mappings(&maps)
+        .into_iter()
+        .find(|mapping| mount_ns::is_same_file(&file, pid, mapping))
+        .with_context(|| format!("{} is not mapped by PID {}", binary.display(), pid))?;
+    // The mapping starts at the page of the segment's start, so it may also
+    // cover the end of the previous one; vaddr - offset is what both share
+    let mapped_len = mapping.end - mapping.start;
+    let segment = elf
+        .program_headers
+        .iter()
+        .find(|ph| {
+            ph.p_type == PT_LOAD
+                && ph.p_flags & PF_X != 0
+                && mapping.offset < ph.p_offset + ph.p_filesz
+                && ph.p_offset < mapping.offset + mapped_len
+        })
+        .with_context(|| format!("No code segment of {} is mapped", binary.display()))?;
+    let mapped_vaddr =
+        (segment.p_vaddr.wrapping_sub(segment.p_offset)).wrapping_add(mapping.offset);
+    Ok(address.wrapping_add(mapping.start.wrapping_sub(mapped_vaddr)))
+}
+
+/// Thread IDs of `pid` as seen in its own PID namespace, mapped to the
+/// agent's
+fn host_tids(pid: i32) -> HashMap<u32, u32> {
+    let Ok(tasks) = std::fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return HashMap::new();
+    };
+    tasks
+        .filter_map(|task| task.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .map(|tid| (jit::namespace_pid(tid) as u32, tid as u32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_binaries() {
+        // Calling the library keeps the label function in this binary
+        aperture_labels::clear();
+        let exe = std::env::current_exe().unwrap();
+        assert!(defines_label_function(&exe).unwrap());
+
+        let pid = std::process::id() as i32;
+        let found = label_binaries(Some(pid), &[]);
+        assert!(found
+            .iter()
+            .any(|p| p.canonicalize().ok() == exe.canonicalize().ok()));
+        assert!(label_binaries(None, &[PathBuf::from("/nonexistent")]).is_empty());
+    }
+
+    #[test]
+    fn test_existing_thread_labels() {
+        let (ready, labelled) = std::sync::mpsc::channel();
+        let (done, finish) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            aperture_labels::set(&[("tenant", "acme")]);
+            ready.send(()).unwrap();
+            let _ = finish.recv();
+        });
+        labelled.recv().unwrap();
+
+        let pid = std::process::id() as i32;
+        let exe = std::env::current_exe().unwrap();
+        let threads = existing_thread_labels(pid, &exe).unwrap();
+        let found: Vec<_> = threads
+            .iter()
+            .map(|(_, labels)| aperture_labels::decode(labels))
+            .collect();
+        assert!(found.contains(&vec![("tenant".to_string(), "acme".to_string())]));
+        assert!(threads.iter().all(|(tid, _)| std::path::Path::new(&format!(
+            "/proc/{}/task/{}",
+            pid, tid
+        ))
+        .exists()));
+
+        drop(done);
+        thread.join().unwrap();
+    }
+}
@@ -11,7 +11,19 @@ use aya::{
     util::online_cpus,
     Ebpf,
 };
-use tracing::info;
+use tracing::{debug, info, warn};
+
+use super::usdt::{self, UsdtArg, UsdtArgBpf, UsdtSemaphoreLink, MAX_USDT_ARGS};
+use crate::config::{
+    FollowChildren, LabelConfig, LockUprobeConfig, MemoryConfig, ProbeConfig, ProbeSpec,
+    SchedConfig, SyscallFilter, MAX_PROBES,
+};
+
+/// PID_FILTER[3]: cgroup holding the target and its children when they are
+/// followed (0 = none)
+fn children_cgroup(follow_children: Option<FollowChildren>) -> u64 {
+    follow_children.and_then(|f| f.cgroup).unwrap_or(0)
+}
 
 /// Get the device and inode numbers for the current PID namespace.
 /// These are needed by `bpf_get_ns_current_pid_tgid()` to resolve
@@ -138,6 +150,7 @@ pub fn attach_cpu_profiler(
     bpf: &mut Ebpf,
     sample_rate_hz: u64,
     target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
 ) -> Result<PerfEventLinks> {
     use tracing::debug;
 
@@ -177,7 +190,7 @@ pub fn attach_cpu_profiler(
                     0, // PERF_COUNT_SW_CPU_CLOCK
                     PerfEventScope::OneProcessAnyCpu { pid: pid as u32 },
                     SamplePolicy::Frequency(sample_rate_hz),
-                    false,
+                    follow_children.is_some(),
                 )
                 .context(format!("Failed to attach perf_event for PID {}", pid))?;
 
@@ -217,9 +230,11 @@ pub fn cleanup(links: PerfEventLinks) {
     drop(links);
 }
 
+use aya::programs::kprobe::KProbeLinkId;
 use aya::programs::raw_trace_point::RawTracePointLinkId;
 use aya::programs::trace_point::TracePointLinkId;
-use aya::programs::{RawTracePoint, TracePoint};
+use aya::programs::uprobe::UProbeLinkId;
+use aya::programs::{KProbe, RawTracePoint, TracePoint, UProbe};
 
 /// Storage for tracepoint links
 pub struct TracepointLinks {
@@ -306,7 +321,11 @@ pub fn load_lock_profiler() -> Result<Ebpf> {
 }
 
 /// Attach lock profiler
-pub fn attach_lock_profiler(bpf: &mut Ebpf, target_pid: Option<i32>) -> Result<TracepointLinks> {
+pub fn attach_lock_profiler(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+) -> Result<TracepointLinks> {
     let mut links = TracepointLinks::new();
 
     // Attach sys_enter_futex
@@ -328,6 +347,81 @@ pub fn attach_lock_profiler(bpf: &mut Ebpf, target_pid: Option<i32>) -> Result<T
     links.add(program.attach("syscalls", "sys_exit_futex")?);
 
     // Write PID filter AFTER programs are loaded (so map relocations work)
+    set_lock_pid_filter(bpf, target_pid, follow_children)?;
+
+    // Argument offsets differ between architectures and kernels; the BPF
+    // program falls back to the common 64-bit layout if they can't be read
+    match futex_arg_offsets() {
+        Some((uaddr, op, ret)) => {
+            let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+                bpf.map_mut("LOCK_CONFIG")
+                    .context("Failed to get LOCK_CONFIG map")?,
+            )?;
+            config_map.set(0, uaddr, 0)?;
+            config_map.set(1, op, 0)?;
+            config_map.set(2, ret, 0)?;
+            info!(
+                "Lock profiler futex args: uaddr@{}, op@{}, ret@{}",
+                uaddr, op, ret
+            );
+        }
+        None => info!("Lock profiler futex args: tracepoint format unavailable, using defaults"),
+    }
+
+    Ok(links)
+}
+
+/// Attach the kernel lock contention programs of the lock profiler object
+/// (`lock:contention_begin`/`contention_end`, kernel 5.19+)
+pub fn attach_kernel_lock_profiler(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+) -> Result<TracepointLinks> {
+    let mut links = TracepointLinks::new();
+
+    for name in ["contention_begin", "contention_end"] {
+        let program: &mut TracePoint = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a TracePoint")?;
+        program.load()?;
+        links.add(
+            program
+                .attach("lock", name)
+                .with_context(|| format!("Failed to attach lock:{} (needs kernel 5.19+)", name))?,
+        );
+    }
+
+    set_lock_pid_filter(bpf, target_pid, follow_children)?;
+
+    match contention_arg_offsets() {
+        Some((lock_addr, flags, end_lock_addr)) => {
+            let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+                bpf.map_mut("LOCK_CONFIG")
+                    .context("Failed to get LOCK_CONFIG map")?,
+            )?;
+            config_map.set(4, lock_addr, 0)?;
+            config_map.set(5, flags, 0)?;
+            config_map.set(6, end_lock_addr, 0)?;
+            info!(
+                "Kernel lock profiler args: lock_addr@{}, flags@{}",
+                lock_addr, flags
+            );
+        }
+        None => info!("Kernel lock profiler args: tracepoint format unavailable, using defaults"),
+    }
+
+    Ok(links)
+}
+
+/// Write the lock profiler's PID_FILTER map
+fn set_lock_pid_filter(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+) -> Result<()> {
     let pid_value: u64 = target_pid.unwrap_or(0) as u64;
     let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
         bpf.map_mut("PID_FILTER")
@@ -338,6 +432,7 @@ pub fn attach_lock_profiler(bpf: &mut Ebpf, target_pid: Option<i32>) -> Result<T
         let (dev, ino) = get_pidns_dev_ino()?;
         filter_map.set(1, dev, 0)?;
         filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
         info!(
             "Lock profiler PID filter: pid={}, ns_dev={}, ns_ino={}",
             pid_value, dev, ino
@@ -345,6 +440,359 @@ pub fn attach_lock_profiler(bpf: &mut Ebpf, target_pid: Option<i32>) -> Result<T
     } else {
         info!("Lock profiler PID filter: disabled (tracing all)");
     }
+    Ok(())
+}
+
+/// Storage for uprobe links
+pub struct UProbeLinks {
+    links: Vec<UProbeLinkId>,
+}
+
+impl Default for UProbeLinks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UProbeLinks {
+    pub fn new() -> Self {
+        Self { links: Vec::new() }
+    }
+
+    pub fn add(&mut self, link: UProbeLinkId) {
+        self.links.push(link);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+}
+
+/// Attach the lock profiler's uprobes to the user-space lock functions of
+/// the target (or of libc system-wide). Functions that can't be probed are
+/// skipped; the returned links may be empty.
+pub fn attach_lock_uprobes(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    config: &LockUprobeConfig,
+) -> Result<UProbeLinks> {
+    use super::lock_uprobes::{self, PTHREAD_LIBRARIES};
+
+    const PROGRAMS: [&str; 5] = [
+        "user_lock_enter",
+        "pthread_lock_return",
+        "parking_lot_lock_return",
+        "user_unlock_enter",
+        "user_unlock_return",
+    ];
+    for name in PROGRAMS {
+        let program: &mut UProbe = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a UProbe")?;
+        program.load()?;
+    }
+
+    let mut links = UProbeLinks::new();
+    let mut functions = 0;
+    for function in lock_uprobes::lock_functions(target_pid, &config.binaries) {
+        // A pthread function missing from libc lives in libpthread
+        let fallback = (function.binary == std::path::Path::new(PTHREAD_LIBRARIES[0]))
+            .then_some(PTHREAD_LIBRARIES[1]);
+        let mut attached = false;
+        for binary in
+            std::iter::once(function.binary.as_path()).chain(fallback.map(std::path::Path::new))
+        {
+            let mut ids = Vec::new();
+            for name in function.role.programs() {
+                let program: &mut UProbe = bpf
+                    .program_mut(name)
+                    .with_context(|| format!("{} not found", name))?
+                    .try_into()
+                    .context("Not a UProbe")?;
+                match program.attach(Some(&function.symbol), 0, binary, target_pid) {
+                    Ok(id) => ids.push(id),
+                    Err(e) => {
+                        tracing::debug!(
+                            "Failed to probe {} in {}: {}",
+                            function.symbol,
+                            binary.display(),
+                            e
+                        );
+                        break;
+                    }
+                }
+            }
+            // Entry and return probes only work as a pair
+            if ids.len() == 2 {
+                ids.into_iter().for_each(|id| links.add(id));
+                attached = true;
+                break;
+            }
+        }
+        if attached {
+            functions += 1;
+        }
+    }
+
+    let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("LOCK_CONFIG")
+            .context("Failed to get LOCK_CONFIG map")?,
+    )?;
+    config_map.set(3, config.min_duration.as_nanos() as u64, 0)?;
+
+    info!(
+        "Lock uprobes: {} functions probed, reporting acquires and holds >= {:?}",
+        functions, config.min_duration
+    );
+    Ok(links)
+}
+
+/// Attach the `labels_set` uprobe to the label function of each binary
+/// using the labels library, filling the program's THREAD_LABELS map.
+/// Binaries that can't be probed are skipped; the returned links may be
+/// empty.
+pub fn attach_label_uprobes(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    config: &LabelConfig,
+) -> Result<UProbeLinks> {
+    let program: &mut UProbe = bpf
+        .program_mut("labels_set")
+        .context("labels_set not found")?
+        .try_into()
+        .context("Not a UProbe")?;
+    program.load()?;
+
+    let mut links = UProbeLinks::new();
+    let mut probed = Vec::new();
+    for binary in super::labels::label_binaries(target_pid, &config.binaries) {
+        match program.attach(Some(aperture_labels::LABELS_SYMBOL), 0, &binary, target_pid) {
+            Ok(id) => {
+                info!("Reading profiling labels set in {}", binary.display());
+                links.add(id);
+                probed.push(binary);
+            }
+            Err(e) => warn!("Failed to probe labels in {}: {}", binary.display(), e),
+        }
+    }
+    if let Some(pid) = target_pid {
+        let seeded = seed_thread_labels(bpf, pid, &probed)?;
+        if seeded > 0 {
+            info!("Read the labels of {} threads set before attaching", seeded);
+        }
+    }
+    Ok(links)
+}
+
+/// Fill THREAD_LABELS with the labels the threads of `pid` set before the
+/// uprobes were attached. Changes the uprobes already recorded are newer and
+/// kept.
+fn seed_thread_labels(bpf: &mut Ebpf, pid: i32, binaries: &[std::path::PathBuf]) -> Result<usize> {
+    use super::labels::{existing_thread_labels, ThreadLabelsBpf};
+
+    let mut thread_labels: aya::maps::HashMap<_, u32, ThreadLabelsBpf> =
+        aya::maps::HashMap::try_from(
+            bpf.map_mut("THREAD_LABELS")
+                .context("Failed to get THREAD_LABELS map")?,
+        )?;
+    let mut seeded = 0;
+    for binary in binaries {
+        let threads = match existing_thread_labels(pid, binary) {
+            Ok(threads) => threads,
+            Err(e) => {
+                debug!("No earlier labels read from {}: {:#}", binary.display(), e);
+                continue;
+            }
+        };
+        for (tid, labels) in threads {
+            let entry = ThreadLabelsBpf {
+                pid: pid as u32,
+                _pad: 0,
+                labels,
+            };
+            // BPF_NOEXIST
+            if thread_labels.insert(tid, entry, 1).is_ok() {
+                seeded += 1;
+            }
+        }
+    }
+    Ok(seeded)
+}
+
+/// libc allocation functions and the programs probing their entry and
+/// return, for naming heap locks after their allocation site
+const ALLOC_FUNCTIONS: [(&str, &[&str]); 4] = [
+    ("malloc", &["malloc_enter", "alloc_return"]),
+    ("calloc", &["calloc_enter", "alloc_return"]),
+    ("realloc", &["realloc_enter", "alloc_return"]),
+    ("free", &["free_enter"]),
+];
+
+/// Attach the lock profiler's uprobes to the target's libc allocation
+/// functions, which keep its live heap allocations and their stacks in the
+/// ALLOCS and ALLOC_STACKS maps
+pub fn attach_alloc_uprobes(bpf: &mut Ebpf, target_pid: i32) -> Result<UProbeLinks> {
+    use super::lock_uprobes::PTHREAD_LIBRARIES;
+
+    let mut links = UProbeLinks::new();
+    for (function, programs) in ALLOC_FUNCTIONS {
+        for &name in programs {
+            let program: &mut UProbe = bpf
+                .program_mut(name)
+                .with_context(|| format!("{} not found", name))?
+                .try_into()
+                .context("Not a UProbe")?;
+            if program.fd().is_err() {
+                program.load()?;
+            }
+            let id = program
+                .attach(Some(function), 0, PTHREAD_LIBRARIES[0], Some(target_pid))
+                .with_context(|| format!("Failed to probe {}", function))?;
+            links.add(id);
+        }
+    }
+
+    info!("Allocation uprobes attached to PID {}", target_pid);
+    Ok(links)
+}
+
+/// tracefs mount points, newest first
+const TRACEFS_ROOTS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+/// Offsets of `uaddr` and `op` in the sys_enter_futex tracepoint record and
+/// of `ret` in the sys_exit_futex one
+fn futex_arg_offsets() -> Option<(u64, u64, u64)> {
+    let enter = tracepoint_format("syscalls/sys_enter_futex")?;
+    let exit = tracepoint_format("syscalls/sys_exit_futex")?;
+    Some((
+        tracepoint_field_offset(&enter, "uaddr")?,
+        tracepoint_field_offset(&enter, "op")?,
+        tracepoint_field_offset(&exit, "ret")?,
+    ))
+}
+
+/// Offsets of `lock_addr` and `flags` in `lock:contention_begin`, and of
+/// `lock_addr` in `lock:contention_end`
+fn contention_arg_offsets() -> Option<(u64, u64, u64)> {
+    let begin = tracepoint_format("lock/contention_begin")?;
+    let end = tracepoint_format("lock/contention_end")?;
+    Some((
+        tracepoint_field_offset(&begin, "lock_addr")?,
+        tracepoint_field_offset(&begin, "flags")?,
+        tracepoint_field_offset(&end, "lock_addr")?,
+    ))
+}
+
+/// Format file of tracepoint `event` (`category/name`)
+fn tracepoint_format(event: &str) -> Option<String> {
+    TRACEFS_ROOTS
+        .iter()
+        .find_map(|root| std::fs::read_to_string(format!("{}/events/{}/format", root, event)).ok())
+}
+
+/// Offset of field `name` in a tracepoint `format` file, from lines like
+/// `field:u32 * uaddr;  offset:16;  size:8;  signed:0;` (tab separated) or
+/// `field:char rwbs[8];  offset:32;  ...` for arrays
+fn tracepoint_field_offset(format: &str, name: &str) -> Option<u64> {
+    format.lines().find_map(|line| {
+        let mut parts = line.trim().split(';');
+        let decl = parts.next()?.strip_prefix("field:")?;
+        let field = decl.rsplit([' ', '*']).next()?.split('[').next()?;
+        if field != name {
+            return None;
+        }
+        parts
+            .find_map(|p| p.trim().strip_prefix("offset:"))?
+            .parse()
+            .ok()
+    })
+}
+
+/// Load the process tracker eBPF program
+pub fn load_process_tracker() -> Result<Ebpf> {
+    use aya::EbpfLoader;
+    info!("Loading process tracker eBPF program");
+
+    #[cfg(debug_assertions)]
+    {
+        use std::path::PathBuf;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../target/bpfel-unknown-none/debug/process-tracker");
+        if path.exists() {
+            return EbpfLoader::new()
+                .load_file(&path)
+                .context("Failed to load process tracker");
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        #[cfg(feature = "embed-bpf")]
+        {
+            let bpf_data = aya::include_bytes_aligned!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../target/bpfel-unknown-none/release/process-tracker"
+            ));
+            return EbpfLoader::new()
+                .allow_unsupported_maps()
+                .load(bpf_data)
+                .context("Failed to load process tracker");
+        }
+
+        #[cfg(not(feature = "embed-bpf"))]
+        anyhow::bail!(
+            "Process tracker eBPF program not found; build it or enable `embed-bpf` feature"
+        );
+    }
+
+    // Fallback for debug if file not found
+    #[cfg(debug_assertions)]
+    anyhow::bail!("Process tracker binary not found")
+}
+
+/// Attach process tracker to sched_process_{exec,exit,fork}
+pub fn attach_process_tracker(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+) -> Result<TracepointLinks> {
+    let mut links = TracepointLinks::new();
+
+    for name in [
+        "sched_process_exec",
+        "sched_process_exit",
+        "sched_process_fork",
+    ] {
+        let program: &mut TracePoint = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a TracePoint")?;
+        program.load()?;
+        links.add(program.attach("sched", name)?);
+    }
+
+    // Write PID filter AFTER programs are loaded (so map relocations work)
+    let pid_value: u64 = target_pid.unwrap_or(0) as u64;
+    let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PID_FILTER")
+            .context("Failed to get PID_FILTER map")?,
+    )?;
+    filter_map.set(0, pid_value, 0)?;
+    if pid_value != 0 {
+        let (dev, ino) = get_pidns_dev_ino()?;
+        filter_map.set(1, dev, 0)?;
+        filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
+        info!(
+            "Process tracker PID filter: pid={}, ns_dev={}, ns_ino={}",
+            pid_value, dev, ino
+        );
+    } else {
+        info!("Process tracker PID filter: disabled (tracking all)");
+    }
 
     Ok(links)
 }
@@ -394,6 +842,9 @@ pub fn load_syscall_tracer() -> Result<Ebpf> {
 pub fn attach_syscall_tracer(
     bpf: &mut Ebpf,
     target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    stack_threshold_ns: u64,
+    filter: &SyscallFilter,
 ) -> Result<RawTracepointLinks> {
     let mut links = RawTracepointLinks::new();
 
@@ -426,6 +877,7 @@ pub fn attach_syscall_tracer(
         let (dev, ino) = get_pidns_dev_ino()?;
         filter_map.set(1, dev, 0)?;
         filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
         info!(
             "Syscall tracer PID filter: pid={}, ns_dev={}, ns_ino={}",
             pid_value, dev, ino
@@ -434,18 +886,975 @@ pub fn attach_syscall_tracer(
         info!("Syscall tracer PID filter: disabled (tracing all)");
     }
 
+    let (filter_mode, listed) = if !filter.allow.is_empty() {
+        (1, &filter.allow)
+    } else if !filter.deny.is_empty() {
+        (2, &filter.deny)
+    } else {
+        (0, &filter.allow)
+    };
+    let mut filter_map: aya::maps::Array<_, u32> = aya::maps::Array::try_from(
+        bpf.map_mut("SYSCALL_FILTER")
+            .context("Failed to get SYSCALL_FILTER map")?,
+    )?;
+    for &id in listed {
+        filter_map.set(id, 1, 0)?;
+    }
+
+    let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("SYSCALL_CONFIG")
+            .context("Failed to get SYSCALL_CONFIG map")?,
+    )?;
+    config_map.set(0, stack_threshold_ns, 0)?;
+    if stack_threshold_ns != 0 {
+        info!(
+            "Syscall tracer: capturing stacks for calls >= {} us",
+            stack_threshold_ns / 1000
+        );
+    }
+
+    let min_latency_ns = filter.min_latency.map_or(0, |d| d.as_nanos() as u64);
+    config_map.set(1, min_latency_ns, 0)?;
+    config_map.set(2, filter.sample_every as u64, 0)?;
+    config_map.set(4, filter.aggregate as u64, 0)?;
+    // Mode last, once the list is complete
+    config_map.set(3, filter_mode, 0)?;
+    info!(
+        "Syscall tracer filter: {} listed ({}), min latency {} us, 1 in {}, {}",
+        listed.len(),
+        match filter_mode {
+            1 => "allow",
+            2 => "deny",
+            _ => "none",
+        },
+        min_latency_ns / 1000,
+        filter.sample_every,
+        if filter.aggregate {
+            "in-kernel histograms"
+        } else {
+            "per-call events"
+        }
+    );
+
     Ok(links)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Load the block I/O tracer eBPF program
+pub fn load_block_io_tracer() -> Result<Ebpf> {
+    use aya::EbpfLoader;
+    info!("Loading block I/O tracer eBPF program");
 
-    #[test]
-    #[ignore] // Requires eBPF build artifacts
-    fn test_load_cpu_profiler() {
-        // This test requires the eBPF program to be built
-        let result = load_cpu_profiler();
-        assert!(result.is_err()); // Expected to fail until implemented
+    #[cfg(debug_assertions)]
+    {
+        use std::path::PathBuf;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../target/bpfel-unknown-none/debug/block-io-tracer");
+        if path.exists() {
+            return EbpfLoader::new()
+                .load_file(&path)
+                .context("Failed to load block I/O tracer");
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        #[cfg(feature = "embed-bpf")]
+        {
+            let bpf_data = aya::include_bytes_aligned!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../target/bpfel-unknown-none/release/block-io-tracer"
+            ));
+            return EbpfLoader::new()
+                .allow_unsupported_maps()
+                .load(bpf_data)
+                .context("Failed to load block I/O tracer");
+        }
+
+        #[cfg(not(feature = "embed-bpf"))]
+        anyhow::bail!(
+            "Block I/O tracer eBPF program not found; build it or enable `embed-bpf` feature"
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    anyhow::bail!("Block I/O tracer binary not found")
+}
+
+/// Tracepoints submitting tasks hit, in order of preference:
+/// `block_io_start` (Linux 6.5+) fires for every request, `block_rq_insert`
+/// only for those going through a scheduler or plug
+const BLOCK_SUBMIT_TRACEPOINTS: [&str; 2] = ["block_io_start", "block_rq_insert"];
+
+/// Attach block I/O tracer to `block:block_rq_issue`/`block_rq_complete`,
+/// and to a submission tracepoint naming the submitting process
+pub fn attach_block_io_tracer(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+) -> Result<TracepointLinks> {
+    let mut links = TracepointLinks::new();
+
+    let program: &mut TracePoint = bpf
+        .program_mut("block_rq_submit")
+        .context("block_rq_submit not found")?
+        .try_into()
+        .context("Not a TracePoint")?;
+    program.load()?;
+    let submit =
+        BLOCK_SUBMIT_TRACEPOINTS
+            .into_iter()
+            .find(|name| match program.attach("block", name) {
+                Ok(link) => {
+                    links.add(link);
+                    true
+                }
+                Err(e) => {
+                    tracing::debug!("Block I/O tracer: block:{} unavailable: {}", name, e);
+                    false
+                }
+            });
+    match submit {
+        Some(name) => info!("Block I/O tracer: submitters from block:{}", name),
+        None => warn!(
+            "Block I/O tracer: no submission tracepoint; requests dispatched by \
+             kworkers are attributed to them"
+        ),
+    }
+
+    for name in ["block_rq_issue", "block_rq_complete"] {
+        let program: &mut TracePoint = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a TracePoint")?;
+        program.load()?;
+        links.add(program.attach("block", name)?);
+    }
+
+    // Write PID filter AFTER programs are loaded (so map relocations work)
+    let pid_value: u64 = target_pid.unwrap_or(0) as u64;
+    let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PID_FILTER")
+            .context("Failed to get PID_FILTER map")?,
+    )?;
+    filter_map.set(0, pid_value, 0)?;
+    if pid_value != 0 {
+        let (dev, ino) = get_pidns_dev_ino()?;
+        filter_map.set(1, dev, 0)?;
+        filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
+        info!(
+            "Block I/O tracer PID filter: pid={}, ns_dev={}, ns_ino={}",
+            pid_value, dev, ino
+        );
+    } else {
+        info!("Block I/O tracer PID filter: disabled (tracing all)");
+    }
+
+    // Linux 6.9 added `ioprio` to both tracepoints, moving `rwbs`; the BPF
+    // program falls back to the older layout if the format can't be read
+    match block_rq_arg_offsets(submit.unwrap_or(BLOCK_SUBMIT_TRACEPOINTS[1])) {
+        Some(offsets) => {
+            let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+                bpf.map_mut("BLOCK_CONFIG")
+                    .context("Failed to get BLOCK_CONFIG map")?,
+            )?;
+            for (index, offset) in offsets.iter().enumerate() {
+                config_map.set(index as u32, offset, 0)?;
+            }
+            info!(
+                "Block I/O tracer args: dev@{}, sector@{}, bytes@{}, rwbs@{}, error@{}",
+                offsets[0], offsets[1], offsets[2], offsets[3], offsets[6]
+            );
+        }
+        None => info!("Block I/O tracer args: tracepoint format unavailable, using defaults"),
+    }
+
+    Ok(links)
+}
+
+/// Offsets of `dev`, `sector`, `bytes` and `rwbs` in `block:block_rq_issue`,
+/// of `dev`, `sector` and `error` in `block:block_rq_complete`, then of
+/// `dev` and `sector` in the submission tracepoint `submit`, in BLOCK_CONFIG
+/// order
+fn block_rq_arg_offsets(submit: &str) -> Option<[u64; 9]> {
+    let issue = tracepoint_format("block/block_rq_issue")?;
+    let complete = tracepoint_format("block/block_rq_complete")?;
+    let submit = tracepoint_format(&format!("block/{}", submit)).unwrap_or_else(|| issue.clone());
+    Some([
+        tracepoint_field_offset(&issue, "dev")?,
+        tracepoint_field_offset(&issue, "sector")?,
+        tracepoint_field_offset(&issue, "bytes")?,
+        tracepoint_field_offset(&issue, "rwbs")?,
+        tracepoint_field_offset(&complete, "dev")?,
+        tracepoint_field_offset(&complete, "sector")?,
+        tracepoint_field_offset(&complete, "error")?,
+        tracepoint_field_offset(&submit, "dev")?,
+        tracepoint_field_offset(&submit, "sector")?,
+    ])
+}
+
+/// Load the scheduler tracer eBPF program
+pub fn load_sched_tracer() -> Result<Ebpf> {
+    use aya::EbpfLoader;
+    info!("Loading scheduler tracer eBPF program");
+
+    #[cfg(debug_assertions)]
+    {
+        use std::path::PathBuf;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../target/bpfel-unknown-none/debug/sched-tracer");
+        if path.exists() {
+            return EbpfLoader::new()
+                .load_file(&path)
+                .context("Failed to load scheduler tracer");
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        #[cfg(feature = "embed-bpf")]
+        {
+            let bpf_data = aya::include_bytes_aligned!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../target/bpfel-unknown-none/release/sched-tracer"
+            ));
+            return EbpfLoader::new()
+                .allow_unsupported_maps()
+                .load(bpf_data)
+                .context("Failed to load scheduler tracer");
+        }
+
+        #[cfg(not(feature = "embed-bpf"))]
+        anyhow::bail!(
+            "Scheduler tracer eBPF program not found; build it or enable `embed-bpf` feature"
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    anyhow::bail!("Scheduler tracer binary not found")
+}
+
+/// Attach scheduler tracer to `sched:sched_wakeup`, `sched_wakeup_new` and
+/// `sched_switch`
+pub fn attach_sched_tracer(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    config: &SchedConfig,
+) -> Result<TracepointLinks> {
+    let mut links = TracepointLinks::new();
+
+    for name in ["sched_wakeup", "sched_wakeup_new", "sched_switch"] {
+        let program: &mut TracePoint = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a TracePoint")?;
+        program.load()?;
+        links.add(program.attach("sched", name)?);
+    }
+
+    // Write PID filter AFTER programs are loaded (so map relocations work)
+    let pid_value: u64 = target_pid.unwrap_or(0) as u64;
+    let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PID_FILTER")
+            .context("Failed to get PID_FILTER map")?,
+    )?;
+    filter_map.set(0, pid_value, 0)?;
+    if pid_value != 0 {
+        let (dev, ino) = get_pidns_dev_ino()?;
+        filter_map.set(1, dev, 0)?;
+        filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
+        info!(
+            "Scheduler tracer PID filter: pid={}, ns_dev={}, ns_ino={}",
+            pid_value, dev, ino
+        );
+    } else {
+        info!("Scheduler tracer PID filter: disabled (tracing all)");
+    }
+
+    let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("SCHED_CONFIG")
+            .context("Failed to get SCHED_CONFIG map")?,
+    )?;
+    // The BPF program falls back to the long-standing layout if the format
+    // can't be read
+    match sched_arg_offsets() {
+        Some(offsets) => {
+            for (index, offset) in offsets.iter().enumerate() {
+                config_map.set(index as u32, offset, 0)?;
+            }
+            info!(
+                "Scheduler tracer args: wakeup pid@{}, prev_state@{}, next_comm@{}, next_pid@{}",
+                offsets[0], offsets[1], offsets[2], offsets[3]
+            );
+        }
+        None => info!("Scheduler tracer args: tracepoint format unavailable, using defaults"),
+    }
+
+    let long_wait_ns = config.long_wait.as_nanos() as u64;
+    let min_latency_ns = config.min_latency.map_or(0, |d| d.as_nanos() as u64);
+    config_map.set(4, long_wait_ns, 0)?;
+    config_map.set(5, min_latency_ns, 0)?;
+    config_map.set(6, config.stacks as u64, 0)?;
+    info!(
+        "Scheduler tracer: previous task recorded for waits >= {} us, min latency {} us, wakeup stacks {}",
+        long_wait_ns / 1000,
+        min_latency_ns / 1000,
+        if config.stacks { "on" } else { "off" }
+    );
+
+    Ok(links)
+}
+
+/// Offset of `pid` in `sched:sched_wakeup`, then of `prev_state`,
+/// `next_comm` and `next_pid` in `sched:sched_switch`, in SCHED_CONFIG order
+fn sched_arg_offsets() -> Option<[u64; 4]> {
+    let wakeup = tracepoint_format("sched/sched_wakeup")?;
+    let switch = tracepoint_format("sched/sched_switch")?;
+    Some([
+        tracepoint_field_offset(&wakeup, "pid")?,
+        tracepoint_field_offset(&switch, "prev_state")?,
+        tracepoint_field_offset(&switch, "next_comm")?,
+        tracepoint_field_offset(&switch, "next_pid")?,
+    ])
+}
+
+/// Load the TCP tracer eBPF program
+pub fn load_tcp_tracer() -> Result<Ebpf> {
+    use aya::EbpfLoader;
+    info!("Loading TCP tracer eBPF program");
+
+    #[cfg(debug_assertions)]
+    {
+        use std::path::PathBuf;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../target/bpfel-unknown-none/debug/tcp-tracer");
+        if path.exists() {
+            return EbpfLoader::new()
+                .load_file(&path)
+                .context("Failed to load TCP tracer");
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        #[cfg(feature = "embed-bpf")]
+        {
+            let bpf_data = aya::include_bytes_aligned!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../target/bpfel-unknown-none/release/tcp-tracer"
+            ));
+            return EbpfLoader::new()
+                .allow_unsupported_maps()
+                .load(bpf_data)
+                .context("Failed to load TCP tracer");
+        }
+
+        #[cfg(not(feature = "embed-bpf"))]
+        anyhow::bail!("TCP tracer eBPF program not found; build it or enable `embed-bpf` feature");
+    }
+
+    #[cfg(debug_assertions)]
+    anyhow::bail!("TCP tracer binary not found")
+}
+
+/// Tracepoints of the TCP tracer, as (category, name)
+const TCP_TRACEPOINTS: [(&str, &str); 5] = [
+    ("sock", "inet_sock_set_state"),
+    ("tcp", "tcp_retransmit_skb"),
+    ("tcp", "tcp_send_reset"),
+    ("tcp", "tcp_receive_reset"),
+    ("tcp", "tcp_probe"),
+];
+
+/// Attach TCP tracer to the socket state and TCP tracepoints
+pub fn attach_tcp_tracer(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+) -> Result<TracepointLinks> {
+    let mut links = TracepointLinks::new();
+
+    for (category, name) in TCP_TRACEPOINTS {
+        let program: &mut TracePoint = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a TracePoint")?;
+        program.load()?;
+        links.add(program.attach(category, name)?);
+    }
+
+    // Write PID filter AFTER programs are loaded (so map relocations work)
+    let pid_value: u64 = target_pid.unwrap_or(0) as u64;
+    let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PID_FILTER")
+            .context("Failed to get PID_FILTER map")?,
+    )?;
+    filter_map.set(0, pid_value, 0)?;
+    if pid_value != 0 {
+        let (dev, ino) = get_pidns_dev_ino()?;
+        filter_map.set(1, dev, 0)?;
+        filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
+        info!(
+            "TCP tracer PID filter: pid={}, ns_dev={}, ns_ino={}",
+            pid_value, dev, ino
+        );
+    } else {
+        info!("TCP tracer PID filter: disabled (tracing all)");
+    }
+
+    // `tcp_probe` gained fields over kernel versions; the BPF program falls
+    // back to the 5.x layout if the formats can't be read
+    match tcp_arg_offsets() {
+        Some(offsets) => {
+            let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+                bpf.map_mut("TCP_CONFIG")
+                    .context("Failed to get TCP_CONFIG map")?,
+            )?;
+            for (index, offset) in offsets.iter().enumerate() {
+                config_map.set(index as u32, offset, 0)?;
+            }
+            info!(
+                "TCP tracer args: skaddr@{}, newstate@{}, daddr@{}, tcp_probe srtt@{}",
+                offsets[0], offsets[2], offsets[7], offsets[16]
+            );
+        }
+        None => info!("TCP tracer args: tracepoint format unavailable, using defaults"),
+    }
+
+    Ok(links)
+}
+
+/// Storage for kprobe links
+pub struct KProbeLinks {
+    links: Vec<KProbeLinkId>,
+}
+
+impl Default for KProbeLinks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KProbeLinks {
+    pub fn new() -> Self {
+        Self { links: Vec::new() }
+    }
+
+    pub fn add(&mut self, link: KProbeLinkId) {
+        self.links.push(link);
+    }
+}
+
+/// Attach the TCP tracer's byte counters to `tcp_sendmsg` (entry and
+/// return) and `tcp_cleanup_rbuf`. Kernel functions aren't a stable
+/// interface, so callers treat failure as losing byte counts rather than the
+/// mode.
+pub fn attach_tcp_kprobes(bpf: &mut Ebpf) -> Result<KProbeLinks> {
+    let mut links = KProbeLinks::new();
+
+    for (name, function) in [
+        ("tcp_sendmsg", "tcp_sendmsg"),
+        ("tcp_sendmsg_return", "tcp_sendmsg"),
+        ("tcp_cleanup_rbuf", "tcp_cleanup_rbuf"),
+    ] {
+        let program: &mut KProbe = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a KProbe")?;
+        program.load()?;
+        links.add(
+            program
+                .attach(function, 0)
+                .with_context(|| format!("Failed to attach {} to {}", name, function))?,
+        );
+    }
+
+    Ok(links)
+}
+
+/// Offsets of `skaddr`, `oldstate`, `newstate`, `sport`, `dport`, `family`,
+/// `protocol`, `daddr` and `daddr_v6` in `sock:inet_sock_set_state`, of
+/// `skaddr` in `tcp:tcp_retransmit_skb`, `tcp_send_reset` and
+/// `tcp_receive_reset`, then of `daddr`, `sport`, `dport`, `family` and
+/// `srtt` in `tcp:tcp_probe`, in TCP_CONFIG order
+fn tcp_arg_offsets() -> Option<[u64; 17]> {
+    let state = tracepoint_format("sock/inet_sock_set_state")?;
+    let retransmit = tracepoint_format("tcp/tcp_retransmit_skb")?;
+    let send_reset = tracepoint_format("tcp/tcp_send_reset")?;
+    let receive_reset = tracepoint_format("tcp/tcp_receive_reset")?;
+    let probe = tracepoint_format("tcp/tcp_probe")?;
+    let field = |format: &str, name: &str| tracepoint_field_offset(format, name);
+    Some([
+        field(&state, "skaddr")?,
+        field(&state, "oldstate")?,
+        field(&state, "newstate")?,
+        field(&state, "sport")?,
+        field(&state, "dport")?,
+        field(&state, "family")?,
+        field(&state, "protocol")?,
+        field(&state, "daddr")?,
+        field(&state, "daddr_v6")?,
+        field(&retransmit, "skaddr")?,
+        field(&send_reset, "skaddr")?,
+        field(&receive_reset, "skaddr")?,
+        field(&probe, "daddr")?,
+        field(&probe, "sport")?,
+        field(&probe, "dport")?,
+        field(&probe, "family")?,
+        field(&probe, "srtt")?,
+    ])
+}
+
+/// Load the memory tracer eBPF program
+pub fn load_memory_tracer() -> Result<Ebpf> {
+    use aya::EbpfLoader;
+    info!("Loading memory tracer eBPF program");
+
+    #[cfg(debug_assertions)]
+    {
+        use std::path::PathBuf;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../target/bpfel-unknown-none/debug/memory-tracer");
+        if path.exists() {
+            return EbpfLoader::new()
+                .load_file(&path)
+                .context("Failed to load memory tracer");
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        #[cfg(feature = "embed-bpf")]
+        {
+            let bpf_data = aya::include_bytes_aligned!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../target/bpfel-unknown-none/release/memory-tracer"
+            ));
+            return EbpfLoader::new()
+                .allow_unsupported_maps()
+                .load(bpf_data)
+                .context("Failed to load memory tracer");
+        }
+
+        #[cfg(not(feature = "embed-bpf"))]
+        anyhow::bail!(
+            "Memory tracer eBPF program not found; build it or enable `embed-bpf` feature"
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    anyhow::bail!("Memory tracer binary not found")
+}
+
+/// Tracepoints of the memory tracer, as (category, name, required).
+/// `page_fault_kernel` is missing on some architectures and the memcg
+/// reclaim events without `CONFIG_MEMCG`.
+const MEMORY_TRACEPOINTS: [(&str, &str, bool); 6] = [
+    ("exceptions", "page_fault_user", true),
+    ("exceptions", "page_fault_kernel", false),
+    ("vmscan", "mm_vmscan_direct_reclaim_begin", true),
+    ("vmscan", "mm_vmscan_direct_reclaim_end", true),
+    ("vmscan", "mm_vmscan_memcg_reclaim_begin", false),
+    ("vmscan", "mm_vmscan_memcg_reclaim_end", false),
+];
+
+/// Attach memory tracer to the page fault and reclaim tracepoints
+pub fn attach_memory_tracer(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    config: &MemoryConfig,
+) -> Result<TracepointLinks> {
+    let mut links = TracepointLinks::new();
+
+    for (category, name, required) in MEMORY_TRACEPOINTS {
+        let program: &mut TracePoint = bpf
+            .program_mut(name)
+            .with_context(|| format!("{} not found", name))?
+            .try_into()
+            .context("Not a TracePoint")?;
+        program.load()?;
+        match program.attach(category, name) {
+            Ok(link) => links.add(link),
+            Err(e) if required => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to attach to {}:{} (page fault tracepoints are x86 only)",
+                        category, name
+                    )
+                })
+            }
+            Err(e) => warn!("Memory tracer: {}:{} unavailable: {}", category, name, e),
+        }
+    }
+
+    // Write PID filter AFTER programs are loaded (so map relocations work)
+    let pid_value: u64 = target_pid.unwrap_or(0) as u64;
+    let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PID_FILTER")
+            .context("Failed to get PID_FILTER map")?,
+    )?;
+    filter_map.set(0, pid_value, 0)?;
+    if pid_value != 0 {
+        let (dev, ino) = get_pidns_dev_ino()?;
+        filter_map.set(1, dev, 0)?;
+        filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
+        info!(
+            "Memory tracer PID filter: pid={}, ns_dev={}, ns_ino={}",
+            pid_value, dev, ino
+        );
+    } else {
+        info!("Memory tracer PID filter: disabled (tracing all)");
+    }
+
+    let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("MEMORY_CONFIG")
+            .context("Failed to get MEMORY_CONFIG map")?,
+    )?;
+    // The BPF program falls back to the long-standing layout if the format
+    // can't be read
+    match memory_arg_offsets() {
+        Some(offsets) => {
+            for (index, offset) in offsets.iter().enumerate() {
+                config_map.set(index as u32, offset, 0)?;
+            }
+            info!(
+                "Memory tracer args: fault address@{}, ip@{}, error_code@{}, reclaim order@{}, nr_reclaimed@{}",
+                offsets[0], offsets[1], offsets[2], offsets[3], offsets[4]
+            );
+        }
+        None => info!("Memory tracer args: tracepoint format unavailable, using defaults"),
+    }
+    if let Some((order, nr_reclaimed)) = memcg_reclaim_arg_offsets() {
+        config_map.set(5, order, 0)?;
+        config_map.set(6, nr_reclaimed, 0)?;
+    }
+
+    config_map.set(7, config.fault_sample_every as u64, 0)?;
+    info!(
+        "Memory tracer: recording 1 in {} page faults",
+        config.fault_sample_every
+    );
+
+    Ok(links)
+}
+
+/// Offsets of `address`, `ip` and `error_code` in
+/// `exceptions:page_fault_user`, of `order` in
+/// `vmscan:mm_vmscan_direct_reclaim_begin` and of `nr_reclaimed` in
+/// `mm_vmscan_direct_reclaim_end`, in MEMORY_CONFIG order
+fn memory_arg_offsets() -> Option<[u64; 5]> {
+    let fault = tracepoint_format("exceptions/page_fault_user")?;
+    let begin = tracepoint_format("vmscan/mm_vmscan_direct_reclaim_begin")?;
+    let end = tracepoint_format("vmscan/mm_vmscan_direct_reclaim_end")?;
+    Some([
+        tracepoint_field_offset(&fault, "address")?,
+        tracepoint_field_offset(&fault, "ip")?,
+        tracepoint_field_offset(&fault, "error_code")?,
+        tracepoint_field_offset(&begin, "order")?,
+        tracepoint_field_offset(&end, "nr_reclaimed")?,
+    ])
+}
+
+/// Offsets of `order` and `nr_reclaimed` in the memcg reclaim tracepoints
+fn memcg_reclaim_arg_offsets() -> Option<(u64, u64)> {
+    let begin = tracepoint_format("vmscan/mm_vmscan_memcg_reclaim_begin")?;
+    let end = tracepoint_format("vmscan/mm_vmscan_memcg_reclaim_end")?;
+    Some((
+        tracepoint_field_offset(&begin, "order")?,
+        tracepoint_field_offset(&end, "nr_reclaimed")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore] // Requires eBPF build artifacts
+    fn test_load_cpu_profiler() {
+        // This test requires the eBPF program to be built
+        let result = load_cpu_profiler();
+        assert!(result.is_err()); // Expected to fail until implemented
+    }
+
+    #[test]
+    fn test_tracepoint_field_offset() {
+        let format = "name: sys_enter_futex
+ID: 312
+format:
+\tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;
+\tfield:int __syscall_nr;\toffset:8;\tsize:4;\tsigned:1;
+\tfield:u32 * uaddr;\toffset:16;\tsize:8;\tsigned:0;
+\tfield:int op;\toffset:24;\tsize:8;\tsigned:0;
+\tfield:u32 * uaddr2;\toffset:48;\tsize:8;\tsigned:0;
+";
+        assert_eq!(tracepoint_field_offset(format, "uaddr"), Some(16));
+        assert_eq!(tracepoint_field_offset(format, "op"), Some(24));
+        assert_eq!(tracepoint_field_offset(format, "uaddr2"), Some(48));
+        assert_eq!(tracepoint_field_offset(format, "val"), None);
+
+        let format = "name: contention_begin
+format:
+\tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;
+\tfield:void * lock_addr;\toffset:8;\tsize:8;\tsigned:0;
+\tfield:unsigned int flags;\toffset:16;\tsize:4;\tsigned:0;
+";
+        assert_eq!(tracepoint_field_offset(format, "lock_addr"), Some(8));
+        assert_eq!(tracepoint_field_offset(format, "flags"), Some(16));
+
+        // Array fields carry their length after the name
+        let format = "name: block_rq_issue
+format:
+\tfield:dev_t dev;\toffset:8;\tsize:4;\tsigned:0;
+\tfield:sector_t sector;\toffset:16;\tsize:8;\tsigned:0;
+\tfield:unsigned int bytes;\toffset:28;\tsize:4;\tsigned:0;
+\tfield:unsigned short ioprio;\toffset:32;\tsize:2;\tsigned:0;
+\tfield:char rwbs[8];\toffset:34;\tsize:8;\tsigned:0;
+";
+        assert_eq!(tracepoint_field_offset(format, "bytes"), Some(28));
+        assert_eq!(tracepoint_field_offset(format, "rwbs"), Some(34));
+    }
+}
+
+/// Load the probe tracer eBPF program
+pub fn load_probe_tracer() -> Result<Ebpf> {
+    use aya::EbpfLoader;
+    info!("Loading probe tracer eBPF program");
+
+    #[cfg(debug_assertions)]
+    {
+        use std::path::PathBuf;
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../target/bpfel-unknown-none/debug/probe-tracer");
+        if path.exists() {
+            return EbpfLoader::new()
+                .load_file(&path)
+                .context("Failed to load probe tracer");
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    {
+        #[cfg(feature = "embed-bpf")]
+        {
+            let bpf_data = aya::include_bytes_aligned!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../target/bpfel-unknown-none/release/probe-tracer"
+            ));
+            return EbpfLoader::new()
+                .allow_unsupported_maps()
+                .load(bpf_data)
+                .context("Failed to load probe tracer");
+        }
+
+        #[cfg(not(feature = "embed-bpf"))]
+        anyhow::bail!(
+            "Probe tracer eBPF program not found; build it or enable `embed-bpf` feature"
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    anyhow::bail!("Probe tracer binary not found")
+}
+
+/// Links of user-defined probes, which mix kprobes, uprobes and tracepoints
+#[derive(Default)]
+pub struct DynamicProbeLinks {
+    kprobes: KProbeLinks,
+    uprobes: UProbeLinks,
+    tracepoints: TracepointLinks,
+    /// USDT sites attached with their semaphore, lowered by the kernel when
+    /// the links are dropped
+    semaphores: Vec<UsdtSemaphoreLink>,
+    /// Probe of each program slot: the spec, or `provider:name` for USDT
+    /// probes
+    pub slots: Vec<String>,
+}
+
+/// Attach the probe tracer's slot programs to the user's probe points: each
+/// probe gets the `*_N` programs of the next free slot N, and a USDT probe
+/// one slot per argument layout among its sites. A probe that can't be
+/// attached fails the run, since the user asked for it by name.
+pub fn attach_probe_tracer(
+    bpf: &mut Ebpf,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    config: &ProbeConfig,
+) -> Result<DynamicProbeLinks> {
+    let mut links = DynamicProbeLinks::default();
+    let mut usdt_args: Vec<(usize, Vec<UsdtArg>)> = Vec::new();
+
+    for spec in &config.probes {
+        let slot = links.slots.len();
+        if slot >= MAX_PROBES {
+            anyhow::bail!("Out of probe slots at {} (max {})", spec, MAX_PROBES);
+        }
+        match spec {
+            ProbeSpec::Kprobe { function } => {
+                for kind in ["kprobe", "kretprobe"] {
+                    let name = format!("{}_{}", kind, slot);
+                    let program: &mut KProbe = bpf
+                        .program_mut(&name)
+                        .with_context(|| format!("{} not found", name))?
+                        .try_into()
+                        .context("Not a KProbe")?;
+                    program.load()?;
+                    links.kprobes.add(
+                        program
+                            .attach(function, 0)
+                            .with_context(|| format!("Failed to attach {}", spec))?,
+                    );
+                }
+            }
+            ProbeSpec::Uprobe { binary, symbol } => {
+                let binary = probe_binary_path(binary, target_pid);
+                for kind in ["uprobe", "uretprobe"] {
+                    let name = format!("{}_{}", kind, slot);
+                    let program: &mut UProbe = bpf
+                        .program_mut(&name)
+                        .with_context(|| format!("{} not found", name))?
+                        .try_into()
+                        .context("Not a UProbe")?;
+                    program.load()?;
+                    links.uprobes.add(
+                        program
+                            .attach(Some(symbol), 0, &binary, target_pid)
+                            .with_context(|| format!("Failed to attach {}", spec))?,
+                    );
+                }
+            }
+            ProbeSpec::Tracepoint { category, name } => {
+                let program_name = format!("tracepoint_{}", slot);
+                let program: &mut TracePoint = bpf
+                    .program_mut(&program_name)
+                    .with_context(|| format!("{} not found", program_name))?
+                    .try_into()
+                    .context("Not a TracePoint")?;
+                program.load()?;
+                links.tracepoints.add(
+                    program
+                        .attach(category, name)
+                        .with_context(|| format!("Failed to attach {}", spec))?,
+                );
+            }
+            ProbeSpec::Usdt {
+                binary,
+                provider,
+                name,
+            } => {
+                let opened = probe_binary_path(binary, target_pid);
+                let groups = usdt::probe_sites(&opened, provider, name)
+                    .with_context(|| format!("Failed to attach {}", spec))?;
+                if slot + groups.len() > MAX_PROBES {
+                    anyhow::bail!(
+                        "Out of probe slots at {}: its sites have {} argument layouts",
+                        spec,
+                        groups.len()
+                    );
+                }
+                for (i, sites) in groups.into_iter().enumerate() {
+                    let program_name = format!("usdt_{}", slot + i);
+                    let program: &mut UProbe = bpf
+                        .program_mut(&program_name)
+                        .with_context(|| format!("{} not found", program_name))?
+                        .try_into()
+                        .context("Not a UProbe")?;
+                    program.load()?;
+                    for site in &sites {
+                        let Some(semaphore_offset) = site.semaphore_offset else {
+                            links.uprobes.add(
+                                program
+                                    .attach(None, site.offset, &opened, target_pid)
+                                    .with_context(|| format!("Failed to attach {}", spec))?,
+                            );
+                            continue;
+                        };
+                        links.semaphores.push(
+                            UsdtSemaphoreLink::attach(
+                                program.fd()?,
+                                &opened,
+                                site.offset,
+                                semaphore_offset,
+                                target_pid,
+                            )
+                            .with_context(|| format!("Failed to attach {}", spec))?,
+                        );
+                    }
+                    usdt_args.push((slot + i, sites[0].args.clone()));
+                    links.slots.push(format!("{}:{}", provider, name));
+                }
+            }
+        }
+        if !matches!(spec, ProbeSpec::Usdt { .. }) {
+            links.slots.push(spec.to_string());
+        }
+        info!("Probe {} attached", spec);
+    }
+
+    let mut args_map: aya::maps::Array<_, UsdtArgBpf> = aya::maps::Array::try_from(
+        bpf.map_mut("USDT_ARGS")
+            .context("Failed to get USDT_ARGS map")?,
+    )?;
+    for (slot, args) in usdt_args {
+        for (i, arg) in args.iter().enumerate() {
+            args_map.set((slot * MAX_USDT_ARGS + i) as u32, UsdtArgBpf::from(arg), 0)?;
+        }
+    }
+
+    // Write PID filter AFTER programs are loaded (so map relocations work)
+    let pid_value: u64 = target_pid.unwrap_or(0) as u64;
+    let mut filter_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PID_FILTER")
+            .context("Failed to get PID_FILTER map")?,
+    )?;
+    filter_map.set(0, pid_value, 0)?;
+    if pid_value != 0 {
+        let (dev, ino) = get_pidns_dev_ino()?;
+        filter_map.set(1, dev, 0)?;
+        filter_map.set(2, ino, 0)?;
+        filter_map.set(3, children_cgroup(follow_children), 0)?;
+        info!(
+            "Probe tracer PID filter: pid={}, ns_dev={}, ns_ino={}",
+            pid_value, dev, ino
+        );
+    } else {
+        info!("Probe tracer PID filter: disabled (tracing all)");
+    }
+
+    let mut config_map: aya::maps::Array<_, u64> = aya::maps::Array::try_from(
+        bpf.map_mut("PROBE_CONFIG")
+            .context("Failed to get PROBE_CONFIG map")?,
+    )?;
+    config_map.set(0, config.stacks as u64, 0)?;
+    let min_latency = config.min_latency.unwrap_or_default();
+    config_map.set(1, min_latency.as_nanos() as u64, 0)?;
+    info!(
+        "Probe tracer: {} probes, stacks {}, reporting calls >= {:?}",
+        config.probes.len(),
+        if config.stacks { "on" } else { "off" },
+        min_latency
+    );
+
+    Ok(links)
+}
+
+/// Binary of a uprobe as the agent sees it: paths of a target in another
+/// mount namespace (a container) are reached through `/proc/PID/root`.
+/// Library names are left for aya to resolve.
+fn probe_binary_path(binary: &std::path::Path, target_pid: Option<i32>) -> std::path::PathBuf {
+    match target_pid {
+        Some(pid) if binary.is_absolute() && crate::collector::mount_ns::in_other_mount_ns(pid) => {
+            std::path::PathBuf::from(format!("/proc/{}/root{}", pid, binary.display()))
+        }
+        _ => binary.to_path_buf(),
     }
 }
@@ -6,13 +6,20 @@ use anyhow::{Context, Result};
 use aya::Ebpf;
 use tracing::{info, warn};
 
-use super::loader::{self, TracepointLinks};
+use super::loader::{self, TracepointLinks, UProbeLinks};
+use crate::config::{FollowChildren, LabelConfig, LockUprobeConfig};
 
 /// Lock profiler manager
 pub struct LockProfiler {
     bpf: Ebpf,
     links: Option<TracepointLinks>,
+    uprobe_links: Option<UProbeLinks>,
+    alloc_links: Option<UProbeLinks>,
+    label_links: Option<UProbeLinks>,
     target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    uprobes: LockUprobeConfig,
+    labels: LabelConfig,
 }
 
 impl LockProfiler {
@@ -26,7 +33,13 @@ impl LockProfiler {
         Ok(Self {
             bpf,
             links: None,
+            uprobe_links: None,
+            alloc_links: None,
+            label_links: None,
             target_pid: None,
+            follow_children: None,
+            uprobes: LockUprobeConfig::default(),
+            labels: LabelConfig::default(),
         })
     }
 
@@ -38,6 +51,21 @@ impl LockProfiler {
         self.target_pid = pid;
     }
 
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Set user-space lock tracing (off by default)
+    pub fn set_uprobes(&mut self, uprobes: LockUprobeConfig) {
+        self.uprobes = uprobes;
+    }
+
+    /// Set thread label tracking (off by default)
+    pub fn set_labels(&mut self, labels: LabelConfig) {
+        self.labels = labels;
+    }
+
     /// Start profiling
     pub fn start(&mut self) -> Result<()> {
         info!("Starting lock profiling");
@@ -48,10 +76,38 @@ impl LockProfiler {
         }
 
         // Attach eBPF program to tracepoints
-        let links = loader::attach_lock_profiler(&mut self.bpf, self.target_pid)
-            .context("Failed to attach lock profiler")?;
+        let links =
+            loader::attach_lock_profiler(&mut self.bpf, self.target_pid, self.follow_children)
+                .context("Failed to attach lock profiler")?;
 
         self.links = Some(links);
+
+        // User-space locks are an addition to futex tracing; without them
+        // only kernel-visible contention is reported
+        if self.uprobes.enabled {
+            match loader::attach_lock_uprobes(&mut self.bpf, self.target_pid, &self.uprobes) {
+                Ok(links) if links.is_empty() => {
+                    warn!("No user-space lock functions found to probe")
+                }
+                Ok(links) => self.uprobe_links = Some(links),
+                Err(e) => warn!("Failed to attach lock uprobes: {:#}", e),
+            }
+        }
+        // Heap locks without allocation sites keep their address
+        if let (true, Some(pid)) = (self.uprobes.alloc_sites, self.target_pid) {
+            match loader::attach_alloc_uprobes(&mut self.bpf, pid) {
+                Ok(links) => self.alloc_links = Some(links),
+                Err(e) => warn!("Failed to attach allocation uprobes: {:#}", e),
+            }
+        }
+        // Events are recorded without labels when they can't be read
+        if self.labels.enabled {
+            match loader::attach_label_uprobes(&mut self.bpf, self.target_pid, &self.labels) {
+                Ok(links) if links.is_empty() => warn!("No binaries using profiling labels found"),
+                Ok(links) => self.label_links = Some(links),
+                Err(e) => warn!("Failed to attach label uprobes: {:#}", e),
+            }
+        }
         info!("Lock profiling started successfully");
 
         Ok(())
@@ -61,6 +117,9 @@ impl LockProfiler {
     pub fn stop(&mut self) {
         info!("Stopping lock profiling");
 
+        self.uprobe_links = None;
+        self.alloc_links = None;
+        self.label_links = None;
         if let Some(_links) = self.links.take() {
             // Links are dropped here
             info!("Lock profiling stopped");
@@ -0,0 +1,233 @@
+//! User-space lock functions traced with uprobes
+//!
+//! Futex tracing only sees contention that reaches the kernel. Lock and
+//! unlock functions of pthread (`pthread_mutex_*`, `pthread_rwlock_*`) and
+//! parking_lot (`RawMutex`/`RawRwLock` slow paths) are probed directly, so
+//! acquire latency includes spinning before the futex wait and hold time is
+//! measured from the acquire to the unlock call.
+//!
+//! parking_lot's fast paths are inlined into the caller and cannot be probed:
+//! its acquires are seen only once they take the slow path, and its holds
+//! only when such an acquire is released through the slow path too.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::collector::mount_ns;
+
+/// What a traced lock function does, which picks the probes it gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockFnRole {
+    /// pthread lock call, returns 0 once acquired
+    PthreadLock,
+    /// parking_lot slow-path lock, returns true once acquired
+    ParkingLotLock,
+    /// Releases the lock passed as the first argument
+    Unlock,
+}
+
+impl LockFnRole {
+    /// eBPF programs for the function entry and return
+    pub fn programs(self) -> [&'static str; 2] {
+        match self {
+            LockFnRole::PthreadLock => ["user_lock_enter", "pthread_lock_return"],
+            LockFnRole::ParkingLotLock => ["user_lock_enter", "parking_lot_lock_return"],
+            LockFnRole::Unlock => ["user_unlock_enter", "user_unlock_return"],
+        }
+    }
+}
+
+/// pthread functions, by symbol name
+const PTHREAD_FUNCTIONS: &[(&str, LockFnRole)] = &[
+    ("pthread_mutex_lock", LockFnRole::PthreadLock),
+    ("pthread_mutex_timedlock", LockFnRole::PthreadLock),
+    ("pthread_mutex_unlock", LockFnRole::Unlock),
+    ("pthread_rwlock_rdlock", LockFnRole::PthreadLock),
+    ("pthread_rwlock_wrlock", LockFnRole::PthreadLock),
+    ("pthread_rwlock_timedrdlock", LockFnRole::PthreadLock),
+    ("pthread_rwlock_timedwrlock", LockFnRole::PthreadLock),
+    ("pthread_rwlock_unlock", LockFnRole::Unlock),
+];
+
+/// parking_lot functions, by demangled path without the hash
+const PARKING_LOT_FUNCTIONS: &[(&str, LockFnRole)] = &[
+    (
+        "parking_lot::raw_mutex::RawMutex::lock_slow",
+        LockFnRole::ParkingLotLock,
+    ),
+    (
+        "parking_lot::raw_mutex::RawMutex::unlock_slow",
+        LockFnRole::Unlock,
+    ),
+    (
+        "parking_lot::raw_rwlock::RawRwLock::lock_exclusive_slow",
+        LockFnRole::ParkingLotLock,
+    ),
+    (
+        "parking_lot::raw_rwlock::RawRwLock::lock_shared_slow",
+        LockFnRole::ParkingLotLock,
+    ),
+    (
+        "parking_lot::raw_rwlock::RawRwLock::unlock_exclusive_slow",
+        LockFnRole::Unlock,
+    ),
+    (
+        "parking_lot::raw_rwlock::RawRwLock::unlock_shared_slow",
+        LockFnRole::Unlock,
+    ),
+];
+
+/// Libraries that export the pthread functions, by the names uprobes
+/// resolve through `/proc/PID/maps` or the ld.so cache (glibc before 2.34
+/// keeps them in libpthread)
+pub const PTHREAD_LIBRARIES: [&str; 2] = ["libc", "libpthread"];
+
+/// A lock function found in a binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockFunction {
+    /// Binary to probe, or a library name resolved at attach time
+    pub binary: PathBuf,
+    /// Symbol as it appears in the binary (mangled for Rust)
+    pub symbol: String,
+    pub role: LockFnRole,
+}
+
+/// Role of a symbol, if it is one of the traced lock functions
+pub fn lock_function_role(symbol: &str) -> Option<LockFnRole> {
+    if let Some(&(_, role)) = PTHREAD_FUNCTIONS.iter().find(|(name, _)| *name == symbol) {
+        return Some(role);
+    }
+    if !symbol.contains("parking_lot") {
+        return None;
+    }
+    let demangled = symbolic_demangle::demangle(symbol);
+    let path = strip_legacy_hash(&demangled);
+    PARKING_LOT_FUNCTIONS
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|&(_, role)| role)
+}
+
+/// Drop the `::h<16 hex digits>` suffix of legacy Rust symbol names
+pub(crate) fn strip_legacy_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            path
+        }
+        _ => name,
+    }
+}
+
+/// Lock functions defined in the ELF file at `path`
+pub fn find_lock_functions(path: &Path) -> Result<Vec<LockFunction>> {
+    use symbolic::debuginfo::Object;
+
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let object =
+        Object::parse(&data).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut found: Vec<LockFunction> = Vec::new();
+    for symbol in object.symbols() {
+        // Imports of functions defined elsewhere have no address
+        let Some(name) = symbol.name.as_deref().filter(|_| symbol.address != 0) else {
+            continue;
+        };
+        let Some(role) = lock_function_role(name) else {
+            continue;
+        };
+        if !found.iter().any(|f| f.symbol == name) {
+            found.push(LockFunction {
+                binary: path.to_path_buf(),
+                symbol: name.to_string(),
+                role,
+            });
+        }
+    }
+    Ok(found)
+}
+
+/// Lock functions to probe: everything found in the target's executable
+/// mappings (its binary, libc, Rust libraries) and in `binaries`. Without a
+/// target, the pthread functions are probed in libc system-wide.
+pub fn lock_functions(target_pid: Option<i32>, binaries: &[PathBuf]) -> Vec<LockFunction> {
+    let mut files: Vec<PathBuf> = binaries.to_vec();
+    let mut functions = Vec::new();
+
+    match target_pid {
+        Some(pid) => {
+            let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap_or_default();
+            let other_ns = mount_ns::in_other_mount_ns(pid);
+            for mapping in mount_ns::parse_exec_mappings(&maps) {
+                if mapping.deleted {
+                    continue;
+                }
+                let path = if other_ns {
+                    PathBuf::from(format!("/proc/{}/root{}", pid, mapping.path))
+                } else {
+                    PathBuf::from(&mapping.path)
+                };
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        None => {
+            for &(symbol, role) in PTHREAD_FUNCTIONS {
+                functions.push(LockFunction {
+                    binary: PathBuf::from(PTHREAD_LIBRARIES[0]),
+                    symbol: symbol.to_string(),
+                    role,
+                });
+            }
+        }
+    }
+
+    for path in files {
+        match find_lock_functions(&path) {
+            Ok(found) => functions.extend(found),
+            Err(e) => tracing::debug!("Skipping {} for lock uprobes: {}", path.display(), e),
+        }
+    }
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_function_roles() {
+        assert_eq!(
+            lock_function_role("pthread_mutex_lock"),
+            Some(LockFnRole::PthreadLock)
+        );
+        assert_eq!(
+            lock_function_role("pthread_rwlock_unlock"),
+            Some(LockFnRole::Unlock)
+        );
+        assert_eq!(lock_function_role("pthread_mutex_trylock"), None);
+
+        // Legacy mangling, with the hash suffix
+        assert_eq!(
+            lock_function_role("_ZN11parking_lot9raw_mutex8RawMutex9lock_slow17h0123456789abcdefE"),
+            Some(LockFnRole::ParkingLotLock)
+        );
+        assert_eq!(
+            lock_function_role(
+                "_ZN11parking_lot10raw_rwlock9RawRwLock21unlock_exclusive_slow17hfedcba9876543210E"
+            ),
+            Some(LockFnRole::Unlock)
+        );
+        // Inlined fast paths are not symbols of their own, other slow paths
+        // are not traced
+        assert_eq!(
+            lock_function_role("_ZN11parking_lot9raw_mutex8RawMutex9bump_slow17h0123456789abcdefE"),
+            None
+        );
+
+        assert_eq!(
+            LockFnRole::ParkingLotLock.programs(),
+            ["user_lock_enter", "parking_lot_lock_return"]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Memory tracer eBPF program management
+//!
+//! Handles the lifecycle of the page fault and reclaim eBPF program
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, TracepointLinks};
+use crate::config::{FollowChildren, MemoryConfig};
+
+/// Memory tracer manager
+pub struct MemoryTracer {
+    bpf: Ebpf,
+    links: Option<TracepointLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    config: MemoryConfig,
+}
+
+impl MemoryTracer {
+    /// Create a new memory tracer
+    pub fn new() -> Result<Self> {
+        info!("Initializing memory tracer");
+
+        let bpf = loader::load_memory_tracer().context("Failed to load memory tracer eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            target_pid: None,
+            follow_children: None,
+            config: MemoryConfig::default(),
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Set page fault sampling
+    pub fn set_config(&mut self, config: MemoryConfig) {
+        self.config = config;
+    }
+
+    /// Start tracing
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting memory tracing");
+
+        if self.links.is_some() {
+            warn!("Memory tracer already started");
+            return Ok(());
+        }
+
+        let links = loader::attach_memory_tracer(
+            &mut self.bpf,
+            self.target_pid,
+            self.follow_children,
+            &self.config,
+        )
+        .context("Failed to attach memory tracer")?;
+        self.links = Some(links);
+
+        info!("Memory tracing started successfully");
+        Ok(())
+    }
+
+    /// Stop tracing
+    pub fn stop(&mut self) {
+        info!("Stopping memory tracing");
+
+        if let Some(_links) = self.links.take() {
+            info!("Memory tracing stopped");
+        } else {
+            warn!("Memory tracer was not running");
+        }
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for MemoryTracer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
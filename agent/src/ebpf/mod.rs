@@ -1,6 +1,16 @@
 //! eBPF program management
 
+pub mod block_io_tracer;
 pub mod cpu_profiler;
+pub mod kernel_lock_profiler;
+pub mod labels;
 pub mod loader;
 pub mod lock_profiler;
+pub mod lock_uprobes;
+pub mod memory_tracer;
+pub mod probe_tracer;
+pub mod process_tracker;
+pub mod sched_tracer;
 pub mod syscall_tracer;
+pub mod tcp_tracer;
+pub mod usdt;
@@ -0,0 +1,106 @@
+//! Probe tracer eBPF program management
+//!
+//! Handles the lifecycle of the user-defined probe eBPF program
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, DynamicProbeLinks};
+use crate::config::{FollowChildren, ProbeConfig};
+
+/// Probe tracer manager
+pub struct ProbeTracer {
+    bpf: Ebpf,
+    links: Option<DynamicProbeLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    config: ProbeConfig,
+}
+
+impl ProbeTracer {
+    /// Create a new probe tracer
+    pub fn new() -> Result<Self> {
+        info!("Initializing probe tracer");
+
+        let bpf = loader::load_probe_tracer().context("Failed to load probe tracer eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            target_pid: None,
+            follow_children: None,
+            config: ProbeConfig::default(),
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Set the probe points, stacks and threshold
+    pub fn set_config(&mut self, config: ProbeConfig) {
+        self.config = config;
+    }
+
+    /// Start tracing
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting probe tracing");
+
+        if self.links.is_some() {
+            warn!("Probe tracer already started");
+            return Ok(());
+        }
+
+        let links = loader::attach_probe_tracer(
+            &mut self.bpf,
+            self.target_pid,
+            self.follow_children,
+            &self.config,
+        )
+        .context("Failed to attach probe tracer")?;
+        self.links = Some(links);
+
+        info!("Probe tracing started successfully");
+        Ok(())
+    }
+
+    /// Stop tracing
+    pub fn stop(&mut self) {
+        info!("Stopping probe tracing");
+
+        if let Some(_links) = self.links.take() {
+            info!("Probe tracing stopped");
+        } else {
+            warn!("Probe tracer was not running");
+        }
+    }
+
+    /// Probe of each program slot once started, naming the slots in events
+    pub fn slot_probes(&self) -> Vec<String> {
+        self.links
+            .as_ref()
+            .map(|links| links.slots.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for ProbeTracer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,91 @@
+//! Process tracker eBPF program management
+//!
+//! Handles the lifecycle of the process tracker eBPF program
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, TracepointLinks};
+use crate::config::FollowChildren;
+
+/// Process tracker manager
+pub struct ProcessTracker {
+    bpf: Ebpf,
+    links: Option<TracepointLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+}
+
+impl ProcessTracker {
+    /// Create a new process tracker
+    pub fn new() -> Result<Self> {
+        info!("Initializing process tracker");
+
+        // Load eBPF program
+        let bpf = loader::load_process_tracker().context("Failed to load process tracker eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            target_pid: None,
+            follow_children: None,
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Start tracking
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting process tracking");
+
+        if self.links.is_some() {
+            warn!("Process tracker already started");
+            return Ok(());
+        }
+
+        // Attach eBPF program to tracepoints
+        let links =
+            loader::attach_process_tracker(&mut self.bpf, self.target_pid, self.follow_children)
+                .context("Failed to attach process tracker")?;
+
+        self.links = Some(links);
+        info!("Process tracking started successfully");
+
+        Ok(())
+    }
+
+    /// Stop tracking
+    pub fn stop(&mut self) {
+        info!("Stopping process tracking");
+
+        if let Some(_links) = self.links.take() {
+            // Links are dropped here
+            info!("Process tracking stopped");
+        } else {
+            warn!("Process tracker was not running");
+        }
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for ProcessTracker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
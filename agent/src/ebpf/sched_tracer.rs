@@ -0,0 +1,98 @@
+//! Scheduler tracer eBPF program management
+//!
+//! Handles the lifecycle of the run-queue latency eBPF program
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, TracepointLinks};
+use crate::config::{FollowChildren, SchedConfig};
+
+/// Scheduler tracer manager
+pub struct SchedTracer {
+    bpf: Ebpf,
+    links: Option<TracepointLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    config: SchedConfig,
+}
+
+impl SchedTracer {
+    /// Create a new scheduler tracer
+    pub fn new() -> Result<Self> {
+        info!("Initializing scheduler tracer");
+
+        let bpf = loader::load_sched_tracer().context("Failed to load scheduler tracer eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            target_pid: None,
+            follow_children: None,
+            config: SchedConfig::default(),
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Set stack capture and wait thresholds
+    pub fn set_config(&mut self, config: SchedConfig) {
+        self.config = config;
+    }
+
+    /// Start tracing
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting scheduler tracing");
+
+        if self.links.is_some() {
+            warn!("Scheduler tracer already started");
+            return Ok(());
+        }
+
+        let links = loader::attach_sched_tracer(
+            &mut self.bpf,
+            self.target_pid,
+            self.follow_children,
+            &self.config,
+        )
+        .context("Failed to attach scheduler tracer")?;
+        self.links = Some(links);
+
+        info!("Scheduler tracing started successfully");
+        Ok(())
+    }
+
+    /// Stop tracing
+    pub fn stop(&mut self) {
+        info!("Stopping scheduler tracing");
+
+        if let Some(_links) = self.links.take() {
+            info!("Scheduler tracing stopped");
+        } else {
+            warn!("Scheduler tracer was not running");
+        }
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for SchedTracer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -4,15 +4,20 @@
 
 use anyhow::{Context, Result};
 use aya::Ebpf;
+use std::time::Duration;
 use tracing::{info, warn};
 
 use super::loader::{self, RawTracepointLinks};
+use crate::config::{FollowChildren, SyscallFilter};
 
 /// Syscall tracer manager
 pub struct SyscallTracer {
     bpf: Ebpf,
     links: Option<RawTracepointLinks>,
     target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+    stack_threshold: Option<Duration>,
+    filter: SyscallFilter,
 }
 
 impl SyscallTracer {
@@ -27,6 +32,9 @@ impl SyscallTracer {
             bpf,
             links: None,
             target_pid: None,
+            follow_children: None,
+            stack_threshold: None,
+            filter: SyscallFilter::default(),
         })
     }
 
@@ -38,6 +46,21 @@ impl SyscallTracer {
         self.target_pid = pid;
     }
 
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Capture stacks for calls taking at least `threshold` (None = no stacks)
+    pub fn set_stack_threshold(&mut self, threshold: Option<Duration>) {
+        self.stack_threshold = threshold;
+    }
+
+    /// Syscall allow/deny lists, latency threshold, sampling and aggregation
+    pub fn set_filter(&mut self, filter: SyscallFilter) {
+        self.filter = filter;
+    }
+
     /// Start tracing
     pub fn start(&mut self) -> Result<()> {
         info!("Starting syscall tracing");
@@ -48,8 +71,18 @@ impl SyscallTracer {
         }
 
         // Attach eBPF program to raw tracepoints
-        let links = loader::attach_syscall_tracer(&mut self.bpf, self.target_pid)
-            .context("Failed to attach syscall tracer")?;
+        // At least 1ns: 0 disables stack capture in the eBPF program
+        let stack_threshold_ns = self
+            .stack_threshold
+            .map_or(0, |t| (t.as_nanos() as u64).max(1));
+        let links = loader::attach_syscall_tracer(
+            &mut self.bpf,
+            self.target_pid,
+            self.follow_children,
+            stack_threshold_ns,
+            &self.filter,
+        )
+        .context("Failed to attach syscall tracer")?;
 
         self.links = Some(links);
         info!("Syscall tracing started successfully");
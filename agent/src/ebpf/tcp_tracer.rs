@@ -0,0 +1,95 @@
+//! TCP tracer eBPF program management
+//!
+//! Handles the lifecycle of the TCP tracing eBPF program
+
+use anyhow::{Context, Result};
+use aya::Ebpf;
+use tracing::{info, warn};
+
+use super::loader::{self, KProbeLinks, TracepointLinks};
+use crate::config::FollowChildren;
+
+/// TCP tracer manager
+pub struct TcpTracer {
+    bpf: Ebpf,
+    links: Option<TracepointLinks>,
+    kprobe_links: Option<KProbeLinks>,
+    target_pid: Option<i32>,
+    follow_children: Option<FollowChildren>,
+}
+
+impl TcpTracer {
+    /// Create a new TCP tracer
+    pub fn new() -> Result<Self> {
+        info!("Initializing TCP tracer");
+
+        let bpf = loader::load_tcp_tracer().context("Failed to load TCP tracer eBPF")?;
+
+        Ok(Self {
+            bpf,
+            links: None,
+            kprobe_links: None,
+            target_pid: None,
+            follow_children: None,
+        })
+    }
+
+    /// Set target PID filter
+    pub fn set_target_pid(&mut self, pid: Option<i32>) {
+        if let Some(p) = pid {
+            info!("Will filter for PID {}", p);
+        }
+        self.target_pid = pid;
+    }
+
+    /// Also trace the target's children
+    pub fn set_follow_children(&mut self, follow_children: Option<FollowChildren>) {
+        self.follow_children = follow_children;
+    }
+
+    /// Start tracing
+    pub fn start(&mut self) -> Result<()> {
+        info!("Starting TCP tracing");
+
+        if self.links.is_some() {
+            warn!("TCP tracer already started");
+            return Ok(());
+        }
+
+        let links = loader::attach_tcp_tracer(&mut self.bpf, self.target_pid, self.follow_children)
+            .context("Failed to attach TCP tracer")?;
+        self.links = Some(links);
+
+        // Without the kprobes connections are still traced, with zero bytes
+        match loader::attach_tcp_kprobes(&mut self.bpf) {
+            Ok(links) => self.kprobe_links = Some(links),
+            Err(e) => warn!("Failed to attach TCP byte counters: {:#}", e),
+        }
+
+        info!("TCP tracing started successfully");
+        Ok(())
+    }
+
+    /// Stop tracing
+    pub fn stop(&mut self) {
+        info!("Stopping TCP tracing");
+
+        self.kprobe_links = None;
+        if let Some(_links) = self.links.take() {
+            info!("TCP tracing stopped");
+        } else {
+            warn!("TCP tracer was not running");
+        }
+    }
+
+    /// Get mutable reference to the BPF object for map access
+    pub fn bpf_mut(&mut self) -> &mut Ebpf {
+        &mut self.bpf
+    }
+}
+
+impl Drop for TcpTracer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,588 @@
+//! USDT probes read from `.note.stapsdt`
+//!
+//! Statically defined tracepoints (`DTRACE_PROBE`, `STAP_PROBE`) compile to a
+//! `nop` plus an ELF note recording its address, the probe's provider and
+//! name, an optional semaphore and an assembler-style description of each
+//! argument's location (`-4@%edi 8@-8(%rbp)`). The probe tracer attaches a
+//! uprobe at the `nop` and decodes the arguments from the registers and
+//! memory the note describes.
+//!
+//! Semaphores gate probes whose arguments are expensive to compute: the
+//! application only reaches the `nop` while its semaphore is non-zero. Sites
+//! with a semaphore are attached with the uprobe's reference counter offset,
+//! so the kernel raises the semaphore in every process mapping the binary
+//! while the probe is attached, and lowers it again when it goes away.
+
+use anyhow::{Context, Result};
+use aperture_shared::utils::arch::Arch;
+use std::ffi::CString;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// Arguments decoded per hit (must match agent-ebpf/src/probe_tracer.rs)
+pub const MAX_USDT_ARGS: usize = 6;
+
+/// `n_type` of stapsdt notes
+const NT_STAPSDT: u32 = 3;
+
+/// Where an argument's value lives when the probe fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgLocation {
+    /// Immediate value (`$5`)
+    Const(i64),
+    /// Register, as its offset in `struct pt_regs` (`%rdi`)
+    Register(u32),
+    /// Memory at a register plus an offset (`-8(%rbp)`)
+    Memory { base: u32, offset: i64 },
+}
+
+/// One argument of a USDT probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsdtArg {
+    /// Size in bytes (1, 2, 4 or 8)
+    pub size: u8,
+    pub signed: bool,
+    pub location: ArgLocation,
+}
+
+/// Argument as the probe tracer reads it from USDT_ARGS (must match
+/// agent-ebpf/src/probe_tracer.rs)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsdtArgBpf {
+    /// 0 = none, 1 = constant, 2 = register, 3 = memory at register + value
+    pub kind: u32,
+    pub reg_offset: u32,
+    pub size: u32,
+    pub signed: u32,
+    pub value: i64,
+}
+
+unsafe impl aya::Pod for UsdtArgBpf {}
+
+impl From<&UsdtArg> for UsdtArgBpf {
+    fn from(arg: &UsdtArg) -> Self {
+        let (kind, reg_offset, value) = match arg.location {
+            ArgLocation::Const(v) => (1, 0, v),
+            ArgLocation::Register(reg) => (2, reg, 0),
+            ArgLocation::Memory { base, offset } => (3, base, offset),
+        };
+        Self {
+            kind,
+            reg_offset,
+            size: arg.size as u32,
+            signed: arg.signed as u32,
+            value,
+        }
+    }
+}
+
+/// One site of a USDT probe in a binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtProbe {
+    pub provider: String,
+    pub name: String,
+    /// File offset of the probe's `nop`, where the uprobe goes
+    pub offset: u64,
+    /// File offset of the semaphore, if the probe has one
+    pub semaphore_offset: Option<u64>,
+    pub args: Vec<UsdtArg>,
+}
+
+/// Fields of a stapsdt note, addresses as linked
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StapsdtNote<'a> {
+    pc: u64,
+    base: u64,
+    semaphore: u64,
+    provider: &'a str,
+    name: &'a str,
+    args: &'a str,
+}
+
+/// Parse a 64-bit stapsdt note's descriptor: three addresses, then the
+/// NUL-terminated provider, name and argument strings
+fn parse_stapsdt_note(desc: &[u8]) -> Option<StapsdtNote<'_>> {
+    let address = |i: usize| {
+        desc.get(i * 8..i * 8 + 8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    };
+    let (pc, base, semaphore) = (address(0)?, address(1)?, address(2)?);
+    let mut strings = desc.get(24..)?.split(|&b| b == 0);
+    let mut next = || strings.next().and_then(|s| std::str::from_utf8(s).ok());
+    let (provider, name) = (next()?, next()?);
+    let args = next().unwrap_or("");
+    if provider.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some(StapsdtNote {
+        pc,
+        base,
+        semaphore,
+        provider,
+        name,
+        args,
+    })
+}
+
+/// Every USDT probe site defined in the ELF file at `path`
+pub fn find_usdt_probes(path: &Path) -> Result<Vec<UsdtProbe>> {
+    use goblin::elf::{header, program_header::PT_LOAD, Elf};
+
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let elf = Elf::parse(&data).with_context(|| format!("Failed to parse {}", path.display()))?;
+    if !elf.is_64 || !elf.little_endian {
+        anyhow::bail!(
+            "{}: only 64-bit little-endian ELF files are supported",
+            path.display()
+        );
+    }
+    let arch = match elf.header.e_machine {
+        header::EM_X86_64 => Arch::X86_64,
+        header::EM_AARCH64 => Arch::Aarch64,
+        other => anyhow::bail!("{}: unsupported machine {}", path.display(), other),
+    };
+
+    // Prelinking moves the binary; the note's base records where
+    // `.stapsdt.base` was when the note was written
+    let base_section = elf
+        .section_headers
+        .iter()
+        .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(".stapsdt.base"))
+        .map(|sh| sh.sh_addr);
+    let file_offset = |addr: u64| {
+        elf.program_headers
+            .iter()
+            .find(|ph| ph.p_type == PT_LOAD && ph.p_vaddr <= addr && addr < ph.p_vaddr + ph.p_memsz)
+            .map(|ph| addr - ph.p_vaddr + ph.p_offset)
+    };
+
+    let mut probes = Vec::new();
+    let Some(notes) = elf.iter_note_sections(&data, Some(".note.stapsdt")) else {
+        return Ok(probes);
+    };
+    for note in notes {
+        let note = note.with_context(|| format!("Bad USDT note in {}", path.display()))?;
+        if note.n_type != NT_STAPSDT || note.name != "stapsdt" {
+            continue;
+        }
+        let Some(raw) = parse_stapsdt_note(note.desc) else {
+            debug!("Skipping malformed USDT note in {}", path.display());
+            continue;
+        };
+        let pc = match base_section {
+            Some(actual) if raw.base != 0 => raw.pc.wrapping_add(actual).wrapping_sub(raw.base),
+            _ => raw.pc,
+        };
+        let Some(offset) = file_offset(pc) else {
+            debug!(
+                "USDT probe {}:{} at {:#x} is outside the loaded segments",
+                raw.provider, raw.name, pc
+            );
+            continue;
+        };
+        let args = match parse_args(raw.args, arch) {
+            Ok(args) => args,
+            Err(e) => {
+                warn!("USDT probe {}:{}: {:#}", raw.provider, raw.name, e);
+                continue;
+            }
+        };
+        probes.push(UsdtProbe {
+            provider: raw.provider.to_string(),
+            name: raw.name.to_string(),
+            offset,
+            semaphore_offset: (raw.semaphore != 0)
+                .then(|| file_offset(raw.semaphore))
+                .flatten(),
+            args,
+        });
+    }
+    Ok(probes)
+}
+
+/// Parse a probe's argument descriptions (`-4@%edi 8@-8(%rbp) 4@$5`)
+pub fn parse_args(spec: &str, arch: Arch) -> Result<Vec<UsdtArg>> {
+    let args = split_args(spec)
+        .into_iter()
+        .map(|arg| parse_arg(arg, arch).with_context(|| format!("Unsupported argument {}", arg)))
+        .collect::<Result<Vec<_>>>()?;
+    if args.len() > MAX_USDT_ARGS {
+        anyhow::bail!("{} arguments (max {})", args.len(), MAX_USDT_ARGS);
+    }
+    Ok(args)
+}
+
+/// Split an argument string on whitespace outside brackets, keeping
+/// arm64 operands like `[sp, 16]` whole
+fn split_args(spec: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(s) = start.take() {
+                    args.push(&spec[s..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        args.push(&spec[s..]);
+    }
+    args
+}
+
+/// Parse `SIZE@OPERAND`, a negative size marking a signed value
+fn parse_arg(arg: &str, arch: Arch) -> Result<UsdtArg> {
+    let (size, operand) = arg.split_once('@').context("Missing size")?;
+    let size: i8 = size.parse().context("Bad size")?;
+    if ![1, 2, 4, 8].contains(&size.unsigned_abs()) {
+        anyhow::bail!("Bad size {}", size);
+    }
+    let location = match arch {
+        Arch::X86_64 => parse_x86_operand(operand),
+        Arch::Aarch64 => parse_aarch64_operand(operand),
+    }
+    .context("Bad operand")?;
+    Ok(UsdtArg {
+        size: size.unsigned_abs(),
+        signed: size < 0,
+        location,
+    })
+}
+
+/// AT&T operands: `%reg`, `$imm`, `(%reg)` and `off(%reg)`
+fn parse_x86_operand(operand: &str) -> Option<ArgLocation> {
+    if let Some(imm) = operand.strip_prefix('$') {
+        return parse_int(imm).map(ArgLocation::Const);
+    }
+    if let Some(reg) = operand.strip_prefix('%') {
+        return x86_register(reg).map(ArgLocation::Register);
+    }
+    let (offset, rest) = operand.split_once('(')?;
+    // Index registers (`(%rax,%rbx,8)`) are not supported
+    let reg = rest.strip_suffix(')')?.strip_prefix('%')?;
+    Some(ArgLocation::Memory {
+        base: x86_register(reg)?,
+        offset: if offset.is_empty() {
+            0
+        } else {
+            parse_int(offset)?
+        },
+    })
+}
+
+/// Offset in x86_64 `struct pt_regs` of a register, by any of its names
+fn x86_register(name: &str) -> Option<u32> {
+    const LEGACY: &[(&[&str], u32)] = &[
+        (&["rax", "eax", "ax", "al"], 80),
+        (&["rbx", "ebx", "bx", "bl"], 40),
+        (&["rcx", "ecx", "cx", "cl"], 88),
+        (&["rdx", "edx", "dx", "dl"], 96),
+        (&["rsi", "esi", "si", "sil"], 104),
+        (&["rdi", "edi", "di", "dil"], 112),
+        (&["rbp", "ebp", "bp", "bpl"], 32),
+        (&["rsp", "esp", "sp", "spl"], 152),
+        (&["rip"], 128),
+    ];
+    if let Some(&(_, offset)) = LEGACY.iter().find(|(names, _)| names.contains(&name)) {
+        return Some(offset);
+    }
+    // r8..r15, with a d/w/b suffix for their low bits
+    let n: u32 = name
+        .strip_prefix('r')?
+        .trim_end_matches(['d', 'w', 'b'])
+        .parse()
+        .ok()?;
+    match n {
+        8..=11 => Some(72 - (n - 8) * 8),
+        12..=15 => Some(24 - (n - 12) * 8),
+        _ => None,
+    }
+}
+
+/// arm64 operands: `xN`/`wN`, `sp`, `[reg]`, `[reg, off]` and immediates
+fn parse_aarch64_operand(operand: &str) -> Option<ArgLocation> {
+    if let Some(inner) = operand.strip_prefix('[') {
+        let inner = inner.strip_suffix(']')?;
+        let (reg, offset) = match inner.split_once(',') {
+            Some((reg, offset)) => (reg, parse_int(offset.trim().trim_start_matches('#'))?),
+            None => (inner, 0),
+        };
+        return Some(ArgLocation::Memory {
+            base: aarch64_register(reg.trim())?,
+            offset,
+        });
+    }
+    if let Some(reg) = aarch64_register(operand) {
+        return Some(ArgLocation::Register(reg));
+    }
+    parse_int(operand.trim_start_matches('#')).map(ArgLocation::Const)
+}
+
+/// Offset in arm64 `struct pt_regs` (`regs[31]`, then `sp`)
+fn aarch64_register(name: &str) -> Option<u32> {
+    if name == "sp" {
+        return Some(31 * 8);
+    }
+    let n: u32 = name
+        .strip_prefix('x')
+        .or_else(|| name.strip_prefix('w'))?
+        .parse()
+        .ok()?;
+    (n <= 30).then_some(n * 8)
+}
+
+/// Decimal or `0x` hexadecimal, optionally negative
+fn parse_int(s: &str) -> Option<i64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Shift of the reference counter offset in a uprobe's perf event `config`
+const PERF_UPROBE_REF_CTR_OFFSET_SHIFT: u64 = 32;
+/// `PERF_FLAG_FD_CLOEXEC`, `PERF_EVENT_IOC_SET_BPF` and
+/// `PERF_EVENT_IOC_ENABLE`
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+const PERF_EVENT_IOC_SET_BPF: libc::c_ulong = 0x4004_2408;
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+/// `PERF_ATTR_SIZE_VER1`: the fields up to `config2`
+const PERF_ATTR_SIZE_VER1: u32 = 72;
+
+/// Leading fields of `struct perf_event_attr`, as far as uprobes need
+#[repr(C)]
+#[derive(Debug, Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    /// Path of the probed file
+    config1: u64,
+    /// File offset of the probe
+    config2: u64,
+}
+
+/// Attributes of a uprobe at file offset `offset` of `path` whose kernel
+/// managed reference counter is at file offset `semaphore_offset`
+fn uprobe_attr(pmu: u32, path: &CString, offset: u64, semaphore_offset: u64) -> PerfEventAttr {
+    PerfEventAttr {
+        type_: pmu,
+        size: PERF_ATTR_SIZE_VER1,
+        config: semaphore_offset << PERF_UPROBE_REF_CTR_OFFSET_SHIFT,
+        config1: path.as_ptr() as u64,
+        config2: offset,
+        ..Default::default()
+    }
+}
+
+/// A USDT site attached with its semaphore; the kernel lowers the
+/// semaphore and detaches the program when it is dropped
+#[derive(Debug)]
+pub struct UsdtSemaphoreLink(OwnedFd);
+
+impl UsdtSemaphoreLink {
+    /// Attach the loaded uprobe program `program_fd` at file offset `offset`
+    /// of `binary`, raising the semaphore at file offset `semaphore_offset`
+    /// in `pid`, or in every process mapping `binary` without one
+    pub fn attach(
+        program_fd: &impl AsFd,
+        binary: &Path,
+        offset: u64,
+        semaphore_offset: u64,
+        pid: Option<i32>,
+    ) -> Result<Self> {
+        let pmu: u32 = std::fs::read_to_string("/sys/bus/event_source/devices/uprobe/type")
+            .context("No uprobe PMU to manage USDT semaphores")?
+            .trim()
+            .parse()
+            .context("Bad uprobe PMU type")?;
+        let path = CString::new(binary.as_os_str().as_bytes())?;
+        let attr = uprobe_attr(pmu, &path, offset, semaphore_offset);
+
+        let cpu = if pid.is_some() { -1 } else { 0 };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                pid.unwrap_or(-1),
+                cpu,
+                -1,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error()).with_context(|| {
+                format!(
+                    "Failed to open the uprobe at {:#x} of {}",
+                    offset,
+                    binary.display()
+                )
+            });
+        }
+        let link = Self(unsafe { OwnedFd::from_raw_fd(fd as i32) });
+        for (request, arg) in [
+            (PERF_EVENT_IOC_SET_BPF, program_fd.as_fd().as_raw_fd()),
+            (PERF_EVENT_IOC_ENABLE, 0),
+        ] {
+            if unsafe { libc::ioctl(link.0.as_raw_fd(), request as _, arg) } < 0 {
+                return Err(std::io::Error::last_os_error())
+                    .context("Failed to attach the USDT program");
+            }
+        }
+        Ok(link)
+    }
+}
+
+/// Sites of `provider:name` in `binary`, grouped by argument layout: sites
+/// sharing a layout share one program slot
+pub fn probe_sites(binary: &Path, provider: &str, name: &str) -> Result<Vec<Vec<UsdtProbe>>> {
+    let mut groups: Vec<Vec<UsdtProbe>> = Vec::new();
+    for probe in find_usdt_probes(binary)? {
+        if probe.provider != provider || probe.name != name {
+            continue;
+        }
+        match groups.iter_mut().find(|g| g[0].args == probe.args) {
+            Some(group) => group.push(probe),
+            None => groups.push(vec![probe]),
+        }
+    }
+    if groups.is_empty() {
+        anyhow::bail!(
+            "No USDT probe {}:{} in {}",
+            provider,
+            name,
+            binary.display()
+        );
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_x86_args() {
+        let args = parse_args(
+            "-4@%edi 8@-8(%rbp) 8@%r12 4@$5 2@(%rax) 1@%r9b",
+            Arch::X86_64,
+        )
+        .unwrap();
+        assert_eq!(
+            args.iter().map(|a| a.location).collect::<Vec<_>>(),
+            vec![
+                ArgLocation::Register(112),
+                ArgLocation::Memory {
+                    base: 32,
+                    offset: -8
+                },
+                ArgLocation::Register(24),
+                ArgLocation::Const(5),
+                ArgLocation::Memory {
+                    base: 80,
+                    offset: 0
+                },
+                ArgLocation::Register(64),
+            ]
+        );
+        assert!(args[0].signed);
+        assert_eq!(args[0].size, 4);
+        assert!(!args[1].signed);
+        assert!(parse_args("8@(%rax,%rbx,8)", Arch::X86_64).is_err());
+        assert!(parse_args("3@%rdi", Arch::X86_64).is_err());
+        assert!(parse_args(
+            "8@%rdi 8@%rsi 8@%rdx 8@%rcx 8@%r8 8@%r9 8@%rax",
+            Arch::X86_64
+        )
+        .is_err());
+        assert!(parse_args("", Arch::X86_64).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_aarch64_args() {
+        let args = parse_args("-4@x0 8@[sp, 16] 8@[x29, -8] 4@w1 8@7", Arch::Aarch64).unwrap();
+        assert_eq!(
+            args.iter().map(|a| a.location).collect::<Vec<_>>(),
+            vec![
+                ArgLocation::Register(0),
+                ArgLocation::Memory {
+                    base: 248,
+                    offset: 16
+                },
+                ArgLocation::Memory {
+                    base: 232,
+                    offset: -8
+                },
+                ArgLocation::Register(8),
+                ArgLocation::Const(7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_stapsdt_note() {
+        let mut desc = Vec::new();
+        desc.extend_from_slice(&0x1234u64.to_le_bytes());
+        desc.extend_from_slice(&0x3000u64.to_le_bytes());
+        desc.extend_from_slice(&0u64.to_le_bytes());
+        desc.extend_from_slice(b"postgresql\0query__start\0-8@%rdi\0");
+        let note = parse_stapsdt_note(&desc).unwrap();
+        assert_eq!(note.pc, 0x1234);
+        assert_eq!(note.base, 0x3000);
+        assert_eq!(note.semaphore, 0);
+        assert_eq!(note.provider, "postgresql");
+        assert_eq!(note.name, "query__start");
+        assert_eq!(note.args, "-8@%rdi");
+        assert!(parse_stapsdt_note(&desc[..20]).is_none());
+    }
+
+    #[test]
+    fn test_uprobe_attr() {
+        let path = CString::new("/usr/bin/postgres").unwrap();
+        let attr = uprobe_attr(9, &path, 0x1234, 0x20010);
+        assert_eq!((attr.type_, attr.size), (9, 72));
+        assert_eq!(attr.config, 0x20010 << 32);
+        assert_eq!(attr.config1, path.as_ptr() as u64);
+        assert_eq!(attr.config2, 0x1234);
+        assert_eq!(std::mem::size_of::<PerfEventAttr>(), 72);
+    }
+
+    #[test]
+    fn test_arg_bpf_encoding() {
+        let arg = UsdtArg {
+            size: 4,
+            signed: true,
+            location: ArgLocation::Memory {
+                base: 32,
+                offset: -12,
+            },
+        };
+        let bpf = UsdtArgBpf::from(&arg);
+        assert_eq!(
+            (bpf.kind, bpf.reg_offset, bpf.size, bpf.signed, bpf.value),
+            (3, 32, 4, 1, -12)
+        );
+    }
+}
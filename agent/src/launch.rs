@@ -0,0 +1,360 @@
+//! Profiling a command launched by the agent
+//!
+//! The command is spawned under ptrace and held right after its exec, so the
+//! profilers can attach to it before it runs any of its own code. A tracer
+//! thread then follows it and every process and thread it creates: at each
+//! process exit the mappings are snapshotted while they still exist, so
+//! stacks of short-lived processes can be symbolized afterwards. Profiling
+//! ends when the command itself exits.
+//!
+//! Children are found by the eBPF programs through a cgroup created for the
+//! command (cgroup v2), which they inherit; without one, only the command's
+//! own process is traced by PID-filtered programs.
+
+use anyhow::{Context, Result};
+use std::sync::mpsc;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::collector::process::ProcessTable;
+
+/// Root of the unified (v2) cgroup hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// A launched command, held after its exec until resumed
+pub struct LaunchedCommand {
+    pid: i32,
+    cgroup: Option<CommandCgroup>,
+    /// Lets the tracer resume the command, with the table its processes'
+    /// mappings are snapshotted into; dropping it kills the command
+    resume: Option<mpsc::Sender<Option<Arc<ProcessTable>>>>,
+    exited: watch::Receiver<bool>,
+    tracer: std::thread::JoinHandle<Result<i32>>,
+}
+
+impl LaunchedCommand {
+    /// Spawn `command` (program and arguments), held before its first
+    /// instruction
+    pub fn spawn(command: &[String]) -> Result<Self> {
+        let (program, args) = command.split_first().context("No command to launch")?;
+        let (program, args) = (program.clone(), args.to_vec());
+        let (started_tx, started_rx) = mpsc::channel();
+        let (resume_tx, resume_rx) = mpsc::channel();
+        let (exited_tx, exited_rx) = watch::channel(false);
+
+        // The thread that forks the command is its tracer
+        let tracer = std::thread::Builder::new()
+            .name("aperture-launch".to_string())
+            .spawn(move || {
+                let pid = match spawn_traced(&program, &args) {
+                    Ok(pid) => pid,
+                    Err(e) => {
+                        let _ = started_tx.send(Err(e));
+                        return Ok(-1);
+                    }
+                };
+                let _ = started_tx.send(Ok(pid));
+                let code = match resume_rx.recv() {
+                    Ok(table) => trace(pid, table),
+                    Err(_) => {
+                        unsafe { libc::kill(pid, libc::SIGKILL) };
+                        trace(pid, None)
+                    }
+                };
+                let _ = exited_tx.send(true);
+                code
+            })
+            .context("Failed to start the launch thread")?;
+
+        let pid = started_rx
+            .recv()
+            .context("Launch thread exited")?
+            .with_context(|| format!("Failed to launch {}", command.join(" ")))?;
+        info!("Launched {} as PID {}", command.join(" "), pid);
+
+        let cgroup = match CommandCgroup::create(pid) {
+            Ok(cgroup) => Some(cgroup),
+            Err(e) => {
+                warn!(
+                    "Children of the command are not traced by PID-filtered profilers: {:#}",
+                    e
+                );
+                None
+            }
+        };
+
+        Ok(Self {
+            pid,
+            cgroup,
+            resume: Some(resume_tx),
+            exited: exited_rx,
+            tracer,
+        })
+    }
+
+    /// PID of the command
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// ID of the cgroup holding the command and its children, if one could
+    /// be created
+    pub fn cgroup_id(&self) -> Option<u64> {
+        self.cgroup.as_ref().map(|c| c.id)
+    }
+
+    /// Becomes true once the command has exited
+    pub fn exited(&self) -> watch::Receiver<bool> {
+        self.exited.clone()
+    }
+
+    /// Let the command run, snapshotting the mappings of its processes into
+    /// `table` as they exit
+    pub fn resume(&mut self, table: Option<Arc<ProcessTable>>) {
+        if let Some(resume) = self.resume.take() {
+            let _ = resume.send(table);
+        }
+    }
+
+    /// Kill the command, unless it already exited
+    pub fn kill(&self) {
+        if !*self.exited.borrow() {
+            unsafe { libc::kill(self.pid, libc::SIGKILL) };
+        }
+    }
+
+    /// Kill the command if it was not resumed, and wait for it to exit.
+    /// Returns its exit code, or 128 + the signal that killed it.
+    pub async fn wait(mut self) -> Result<i32> {
+        self.resume.take();
+        let tracer = self.tracer;
+        let code = tokio::task::spawn_blocking(move || tracer.join())
+            .await?
+            .map_err(|_| anyhow::anyhow!("Launch thread panicked"))??;
+        if let Some(cgroup) = self.cgroup.take() {
+            cgroup.remove();
+        }
+        Ok(code)
+    }
+}
+
+/// Spawn `program` with ptrace enabled; returns once it is stopped after
+/// its exec
+fn spawn_traced(program: &str, args: &[String]) -> Result<i32> {
+    use std::os::unix::process::CommandExt;
+
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    // The exec stops the child with SIGTRAP once it is traced
+    unsafe {
+        command.pre_exec(|| {
+            if libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    let pid = child.id() as i32;
+
+    let mut status = 0;
+    if unsafe { libc::waitpid(pid, &mut status, libc::__WALL) } == -1 {
+        return Err(std::io::Error::last_os_error()).context("Failed to wait for the exec");
+    }
+    if !libc::WIFSTOPPED(status) {
+        anyhow::bail!("Command exited before it could be profiled");
+    }
+    let options = libc::PTRACE_O_TRACEFORK
+        | libc::PTRACE_O_TRACEVFORK
+        | libc::PTRACE_O_TRACECLONE
+        | libc::PTRACE_O_TRACEEXEC
+        | libc::PTRACE_O_TRACEEXIT;
+    if unsafe { libc::ptrace(libc::PTRACE_SETOPTIONS, pid, 0, options) } == -1 {
+        return Err(std::io::Error::last_os_error()).context("Failed to set ptrace options");
+    }
+    Ok(pid)
+}
+
+/// Run the command held at `pid` until it exits, following its processes
+/// and threads. Returns its exit code, or 128 + the signal that killed it.
+fn trace(pid: i32, table: Option<Arc<ProcessTable>>) -> Result<i32> {
+    use std::collections::HashSet;
+
+    // Threads and processes seen stopping; the first stop of a new one is
+    // the SIGSTOP it starts with
+    let mut known: HashSet<i32> = HashSet::from([pid]);
+    resume_tracee(pid, 0);
+
+    loop {
+        let mut status = 0;
+        let tid = unsafe { libc::waitpid(-1, &mut status, libc::__WALL | libc::__WNOTHREAD) };
+        if tid == -1 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            return Err(err).context("Failed to wait for the command");
+        }
+
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            known.remove(&tid);
+            if tid == pid {
+                return Ok(if libc::WIFEXITED(status) {
+                    libc::WEXITSTATUS(status)
+                } else {
+                    128 + libc::WTERMSIG(status)
+                });
+            }
+            continue;
+        }
+        if !libc::WIFSTOPPED(status) {
+            continue;
+        }
+
+        let signal = libc::WSTOPSIG(status);
+        let event = (status >> 16) & 0xffff;
+        if event != 0 {
+            // Mappings are still there while the exit is held
+            if event == libc::PTRACE_EVENT_EXIT && is_process(tid) {
+                if let Some(table) = &table {
+                    table.snapshot(tid);
+                }
+            }
+            resume_tracee(tid, 0);
+        } else if known.insert(tid) && signal == libc::SIGSTOP {
+            resume_tracee(tid, 0);
+        } else if is_group_stop(tid) {
+            // Job control stops are not kept while the command is profiled
+            debug!(tid, "Ignoring group stop of launched command");
+            resume_tracee(tid, 0);
+        } else {
+            resume_tracee(tid, signal);
+        }
+    }
+}
+
+fn resume_tracee(tid: i32, signal: i32) {
+    unsafe { libc::ptrace(libc::PTRACE_CONT, tid, 0, signal) };
+}
+
+/// A stop with no signal being delivered, which has no siginfo
+fn is_group_stop(tid: i32) -> bool {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ptrace(libc::PTRACE_GETSIGINFO, tid, 0, &mut info) };
+    ret == -1 && std::io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL)
+}
+
+/// True for the main thread of a process
+fn is_process(tid: i32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/status", tid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|l| l.strip_prefix("Tgid:"))
+                .and_then(|v| v.trim().parse::<i32>().ok())
+        })
+        == Some(tid)
+}
+
+/// cgroup of a launched command, inherited by its children
+struct CommandCgroup {
+    path: std::path::PathBuf,
+    id: u64,
+}
+
+impl CommandCgroup {
+    /// Create a cgroup under the agent's own and move `pid` into it, so the
+    /// command stays under the limits of the agent's unit or container
+    fn create(pid: i32) -> Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = std::path::Path::new(CGROUP_ROOT);
+        if !root.join("cgroup.controllers").exists() {
+            anyhow::bail!("no cgroup v2 hierarchy at {}", CGROUP_ROOT);
+        }
+        let own = std::fs::read_to_string("/proc/self/cgroup")
+            .context("Failed to read /proc/self/cgroup")?;
+        let parent = unified_cgroup_path(&own)
+            .map(|path| root.join(path.trim_start_matches('/')))
+            .context("agent is not in a cgroup v2 hierarchy")?;
+        let path = parent.join(format!("aperture-{}-{}", std::process::id(), pid));
+        std::fs::create_dir(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let cgroup = Self {
+            id: std::fs::metadata(&path)?.ino(),
+            path,
+        };
+        std::fs::write(cgroup.path.join("cgroup.procs"), pid.to_string())
+            .with_context(|| format!("Failed to move PID {} to {}", pid, cgroup.path.display()))?;
+        debug!("Launched command in cgroup {}", cgroup.path.display());
+        Ok(cgroup)
+    }
+
+    /// Remove the cgroup; it stays while children of the command still run
+    fn remove(self) {
+        if let Err(e) = std::fs::remove_dir(&self.path) {
+            debug!("Keeping {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+impl Drop for CommandCgroup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir(&self.path);
+    }
+}
+
+/// Path of the unified (v2) hierarchy in a `/proc/PID/cgroup` file, from its
+/// `0::PATH` line
+fn unified_cgroup_path(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &[&str]) -> i32 {
+        let command: Vec<String> = command.iter().map(|s| s.to_string()).collect();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut launched = LaunchedCommand::spawn(&command).unwrap();
+            let table = Arc::new(ProcessTable::new());
+            let pid = launched.pid();
+            launched.resume(Some(table.clone()));
+            let mut exited = launched.exited();
+            let code = launched.wait().await.unwrap();
+            assert!(*exited.borrow_and_update());
+            // Mappings were taken at the exit
+            assert!(table.contains(pid));
+            code
+        })
+    }
+
+    #[test]
+    fn test_launched_command_exit_codes() {
+        assert_eq!(run(&["sh", "-c", "exit 3"]), 3);
+        // Children are followed and signals are delivered
+        assert_eq!(
+            run(&["sh", "-c", "sh -c 'exit 1'; kill -TERM $$"]),
+            128 + 15
+        );
+        assert!(LaunchedCommand::spawn(&["/nonexistent/command".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_unified_cgroup_path() {
+        let hybrid = "4:memory:/user.slice\n1:name=systemd:/user.slice/session-2.scope\n\
+                      0::/user.slice/session-2.scope\n";
+        assert_eq!(
+            unified_cgroup_path(hybrid),
+            Some("/user.slice/session-2.scope")
+        );
+        assert_eq!(unified_cgroup_path("0::/\n"), Some("/"));
+        assert_eq!(unified_cgroup_path("1:cpu:/\n"), None);
+    }
+}
@@ -3,21 +3,30 @@
 //! This library provides the core functionality for the profiling agent,
 //! including eBPF program loading, event collection, and symbol resolution.
 
+pub mod capture;
 pub mod collector;
 pub mod config;
 pub mod ebpf;
+pub mod launch;
 pub mod output;
 pub mod retry;
+pub mod session;
+mod stream;
 pub mod wasm;
 
 pub use config::Config;
 pub use config::ProfileMode;
+pub use config::SymbolizeMode;
+pub use session::{ProfileBundle, Session, SessionHandle};
 
 use anyhow::{Context, Result};
 use aperture_shared::protocol::wire::Message;
 use aperture_shared::types::events::ProfileEvent;
+use aperture_shared::types::profile::{LockProfile, Profile, SyscallProfile};
+use aperture_shared::utils::arch::Arch;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
+use stream::{stream_perf_events, PushConfig};
 use tracing::{debug, info, warn};
 
 /// Global monotonic sequence counter for aggregator pushes.
@@ -26,6 +35,19 @@ static PUSH_SEQUENCE: AtomicU64 = AtomicU64::new(1);
 /// Max push interval when backing off (cap for streaming to aggregator).
 const PUSH_INTERVAL_MAX: Duration = Duration::from_secs(30);
 
+/// How often live tracked processes are re-snapshotted to pick up dlopen'd libraries.
+const PROCESS_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Process collector shared between the process tracker and the profilers
+type SharedProcessCollector =
+    std::sync::Arc<tokio::sync::Mutex<collector::process::ProcessCollector>>;
+
+/// Persistent symbol cache shared by all symbolizers of a run
+type SharedDiskCache = std::sync::Arc<collector::disk_cache::DiskSymbolCache>;
+
+/// Capture the profilers of a `--record` run append their raw events to
+type SharedRecorder = std::sync::Arc<capture::CaptureWriter>;
+
 /// Generate an agent ID from the hostname (or fallback to PID).
 fn agent_id() -> String {
     hostname::get()
@@ -87,6 +109,7 @@ async fn push_with_client(
         tonic::transport::Channel,
     >,
     agent_id: &str,
+    arch: Arch,
     events: Vec<ProfileEvent>,
 ) -> Result<Option<bool>, anyhow::Error> {
     use aperture_aggregator::server::grpc::proto::PushRequest;
@@ -96,7 +119,7 @@ async fn push_with_client(
     }
     let count = events.len();
     let sequence = PUSH_SEQUENCE.fetch_add(1, Ordering::Relaxed);
-    let message = Message::new(sequence, events);
+    let message = Message::with_arch(sequence, events, arch);
     let payload = message.to_bytes()?;
     let req = PushRequest {
         agent_id: agent_id.to_string(),
@@ -138,6 +161,7 @@ async fn push_to_aggregator(
     >,
     url: &str,
     agent_id: &str,
+    arch: Arch,
     events: Vec<ProfileEvent>,
 ) -> Result<Option<bool>, anyhow::Error> {
     if events.is_empty() {
@@ -153,7 +177,7 @@ async fn push_to_aggregator(
             continue;
         }
         let c = client.as_mut().unwrap();
-        match push_with_client(c, agent_id, chunk.clone()).await {
+        match push_with_client(c, agent_id, arch, chunk.clone()).await {
             Ok(b) => {
                 last_backpressure = b;
             }
@@ -189,10 +213,25 @@ async fn push_to_aggregator_with_retry(
     url: &str,
     agent_id: &str,
     events: Vec<ProfileEvent>,
+) -> Result<Option<bool>, anyhow::Error> {
+    push_to_aggregator_as(client, url, agent_id, Arch::host(), events).await
+}
+
+/// Push with retry as an agent of `arch`, for events recorded on another host
+async fn push_to_aggregator_as(
+    client: &mut Option<
+        aperture_aggregator::server::grpc::proto::aggregator_client::AggregatorClient<
+            tonic::transport::Channel,
+        >,
+    >,
+    url: &str,
+    agent_id: &str,
+    arch: Arch,
+    events: Vec<ProfileEvent>,
 ) -> Result<Option<bool>, anyhow::Error> {
     let mut delay = Duration::from_millis(500);
     for attempt in 1..=3 {
-        match push_to_aggregator(client, url, agent_id, events.clone()).await {
+        match push_to_aggregator(client, url, agent_id, arch, events.clone()).await {
             Ok(r) => return Ok(r),
             Err(e) => {
                 warn!("aggregator push failed (attempt {}/3): {}", attempt, e);
@@ -265,6 +304,7 @@ fn check_symbol_prerequisites(pid: Option<i32>) {
                     "Process {} maps available: {} memory mappings",
                     pid, mappings
                 );
+                check_mapped_binaries(pid, &content);
             }
             Err(e) => warn!(
                 "Cannot read {} — userspace symbols for PID {} unavailable: {}",
@@ -274,17 +314,437 @@ fn check_symbol_prerequisites(pid: Option<i32>) {
     }
 }
 
+/// Check that the target's mapped binaries can be opened, including when the
+/// target runs in a container (another mount namespace).
+fn check_mapped_binaries(pid: i32, maps: &str) {
+    use collector::mount_ns::{
+        in_other_mount_ns, open_mapping, parse_exec_mappings, MappingAccess,
+    };
+
+    let mappings = parse_exec_mappings(maps);
+    if in_other_mount_ns(pid) {
+        info!(
+            "Process {} runs in another mount namespace; binaries are read via /proc/{}/map_files and /proc/{}/root",
+            pid, pid, pid
+        );
+    }
+
+    let mut unreadable = Vec::new();
+    let mut via_map_files = false;
+    for mapping in &mappings {
+        match open_mapping(pid, mapping) {
+            Some((_, access)) => via_map_files |= access == MappingAccess::MapFiles,
+            None if !unreadable.contains(&mapping.path) => unreadable.push(mapping.path.clone()),
+            None => {}
+        }
+    }
+    if !via_map_files && mappings.iter().any(|m| m.deleted) {
+        warn!(
+            "Process {} maps deleted binaries and /proc/{}/map_files is not readable — \
+             their frames will stay unresolved. Fix: run the agent with CAP_SYS_ADMIN",
+            pid, pid
+        );
+    }
+    if !unreadable.is_empty() {
+        warn!(
+            "Cannot open {} mapped binaries of PID {} (e.g. {}) — their frames will show as hex",
+            unreadable.len(),
+            pid,
+            unreadable[0]
+        );
+    }
+}
+
+/// Running process lifecycle tracker and its reader tasks
+struct ProcessTracking {
+    tracker: ebpf::process_tracker::ProcessTracker,
+    collector: SharedProcessCollector,
+    handles: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl ProcessTracking {
+    /// Start tracking exec/exit/fork so stacks from short-lived processes can
+    /// be symbolized after they exit. Tracking is best effort: failures are
+    /// logged and profiling continues without it.
+    fn start(
+        target_pid: Option<i32>,
+        follow_children: Option<config::FollowChildren>,
+    ) -> Option<Self> {
+        match Self::try_start(target_pid, follow_children) {
+            Ok(tracking) => Some(tracking),
+            Err(e) => {
+                warn!(
+                    "Process tracking unavailable ({:#}); stacks of processes that exit \
+                     before symbolization may stay unresolved",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn try_start(
+        target_pid: Option<i32>,
+        follow_children: Option<config::FollowChildren>,
+    ) -> Result<Self> {
+        use collector::process::{ProcessCollector, ProcessEventBpf};
+        use ebpf::process_tracker::ProcessTracker;
+        use std::sync::Arc;
+        use tokio::sync::Mutex;
+
+        let mut tracker = ProcessTracker::new()?;
+        tracker.set_target_pid(target_pid);
+        tracker.set_follow_children(follow_children);
+        tracker.start()?;
+
+        let process_collector = ProcessCollector::new();
+        let table = process_collector.table();
+        // The target is already running; snapshot it now in case it exits mid-profile
+        if let Some(pid) = target_pid {
+            table.snapshot(pid);
+        }
+        let collector = Arc::new(Mutex::new(process_collector));
+
+        let mut handles = stream::spawn_perf_readers::<ProcessEventBpf, _>(
+            tracker.bpf_mut(),
+            "PROCESS_EVENTS",
+            &collector,
+            ProcessCollector::process_event,
+        )?;
+        handles.push(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PROCESS_REFRESH_INTERVAL).await;
+                let table = table.clone();
+                let _ = tokio::task::spawn_blocking(move || table.refresh_alive()).await;
+            }
+        }));
+
+        Ok(Self {
+            tracker,
+            collector,
+            handles,
+        })
+    }
+
+    async fn stop(mut self) {
+        stream::abort_all(self.handles).await;
+        self.tracker.stop();
+        let collector = self.collector.lock().await;
+        info!(
+            "Process tracking: {} events, {} process snapshots",
+            collector.events().len(),
+            collector.table().len()
+        );
+    }
+}
+
 /// Run the profiler with the given configuration.
 pub async fn run_profiler(config: Config) -> Result<()> {
     config.validate().context("Invalid configuration")?;
 
     // Check symbol resolution prerequisites before profiling
     check_symbol_prerequisites(config.target_pid);
+    let recorder = open_recorder(&config)?;
 
+    // Stack-capturing runs need mappings of processes that may exit before symbolization
+    let tracking = if config.captures_stacks() {
+        ProcessTracking::start(config.target_pid, config.follow_children)
+    } else {
+        None
+    };
+    let processes = tracking.as_ref().map(|t| t.collector.clone());
+    let disk_cache = open_symbol_cache(&config);
+
+    let end = ProfileEnd::After(config.duration);
+    let result = run_mode(
+        config,
+        processes.clone(),
+        disk_cache.clone(),
+        end,
+        recorder.clone(),
+    )
+    .await;
+    finish_recording(recorder, processes.as_ref()).await;
+    if let Some(tracking) = tracking {
+        tracking.stop().await;
+    }
+    if let Some(disk_cache) = disk_cache {
+        if let Err(e) = disk_cache.save() {
+            warn!("Failed to save symbol cache: {:#}", e);
+        }
+    }
+    result
+}
+
+/// Profile a command launched by the agent, from its first instruction until
+/// it exits. Its children are profiled along with it; `config.duration` is
+/// ignored. Returns the command's exit code, or 128 + the signal that killed
+/// it.
+pub async fn profile_command(mut config: Config, command: Vec<String>) -> Result<i32> {
+    if config.target_pid.is_some() {
+        anyhow::bail!("A PID and a command to launch are mutually exclusive");
+    }
+    let mut launched = launch::LaunchedCommand::spawn(&command)?;
+    config.target_pid = Some(launched.pid());
+    config.follow_children = Some(config::FollowChildren {
+        cgroup: launched.cgroup_id(),
+    });
+    if let Err(e) = config.validate() {
+        let _ = launched.wait().await;
+        return Err(e.context("Invalid configuration"));
+    }
+    let recorder = match open_recorder(&config) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            let _ = launched.wait().await;
+            return Err(e);
+        }
+    };
+
+    check_symbol_prerequisites(config.target_pid);
+
+    let tracking = if config.captures_stacks() {
+        ProcessTracking::start(config.target_pid, config.follow_children)
+    } else {
+        None
+    };
+    let processes = tracking.as_ref().map(|t| t.collector.clone());
+    let disk_cache = open_symbol_cache(&config);
+
+    // The command runs once every profiler has attached (or failed to)
+    let (ready, mut attached) = tokio::sync::mpsc::channel::<()>(1);
+    let end = ProfileEnd::Until {
+        ready,
+        done: launched.exited(),
+    };
+    let table = match &processes {
+        Some(processes) => Some(processes.lock().await.table()),
+        None => None,
+    };
+    let profiling = run_mode(
+        config,
+        processes.clone(),
+        disk_cache.clone(),
+        end,
+        recorder.clone(),
+    );
+    let resume = async {
+        while attached.recv().await.is_some() {}
+        launched.resume(table);
+    };
+    let (result, ()) = tokio::join!(profiling, resume);
+
+    if result.is_err() {
+        launched.kill();
+    }
+    let code = launched.wait().await;
+    finish_recording(recorder, processes.as_ref()).await;
+    if let Some(tracking) = tracking {
+        tracking.stop().await;
+    }
+    if let Some(disk_cache) = disk_cache {
+        if let Err(e) = disk_cache.save() {
+            warn!("Failed to save symbol cache: {:#}", e);
+        }
+    }
+    result?;
+    code
+}
+
+/// Open the capture of a `--record` run
+fn open_recorder(config: &Config) -> Result<Option<SharedRecorder>> {
+    let Some(path) = &config.record else {
+        return Ok(None);
+    };
+    let info = capture::CaptureInfo {
+        hostname: agent_id(),
+        target_pid: config.target_pid,
+        sample_period_ns: config.sample_period_ns(),
+        start_time: aperture_shared::utils::time::system_time_nanos(),
+        end_time: 0,
+    };
+    let writer = capture::CaptureWriter::create(path, info)?;
+    Ok(Some(std::sync::Arc::new(writer)))
+}
+
+/// Write the mappings of the recorded processes once the profilers stopped,
+/// while the process tracker still holds the snapshots of exited ones
+async fn finish_recording(
+    recorder: Option<SharedRecorder>,
+    processes: Option<&SharedProcessCollector>,
+) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    let table = match processes {
+        Some(processes) => Some(processes.lock().await.table()),
+        None => None,
+    };
+    if let Err(e) = recorder.finish(table.as_deref()) {
+        warn!("Failed to finish capture: {:#}", e);
+    }
+}
+
+/// When a profiling run ends
+#[derive(Clone)]
+enum ProfileEnd {
+    /// After a fixed duration
+    After(Duration),
+    /// When `done` becomes true: a launched command exited or a session was
+    /// stopped. Each profiler holds a `ready` sender, reports on it once
+    /// attached and drops it, so the command or session only starts when
+    /// all have attached or failed.
+    Until {
+        ready: tokio::sync::mpsc::Sender<()>,
+        done: tokio::sync::watch::Receiver<bool>,
+    },
+}
+
+impl ProfileEnd {
+    async fn wait(self) {
+        match self {
+            ProfileEnd::After(duration) => tokio::time::sleep(duration).await,
+            ProfileEnd::Until { ready, mut done } => {
+                let _ = ready.send(()).await;
+                drop(ready);
+                let _ = done.wait_for(|done| *done).await;
+            }
+        }
+    }
+}
+
+/// Symbolizes and normalizes the profiles built from a profiler's collector,
+/// both while it runs (session snapshots) and once it stops
+#[derive(Clone)]
+struct ProfileSymbolizer {
+    process_table: Option<std::sync::Arc<collector::process::ProcessTable>>,
+    disk_cache: Option<SharedDiskCache>,
+    normalizer: std::sync::Arc<collector::normalize::FrameNormalizer>,
+    target_pid: Option<i32>,
+    /// Replaying a capture: the recorded processes are gone
+    offline: bool,
+}
+
+impl ProfileSymbolizer {
+    fn resolver(
+        &self,
+        user_ip_owners: std::collections::HashMap<i32, Vec<u64>>,
+    ) -> collector::symbols::SymbolResolver {
+        let mut resolver = collector::symbols::SymbolResolver::new();
+        if let Some(table) = &self.process_table {
+            resolver.set_process_table(table.clone());
+        }
+        if let Some(disk_cache) = &self.disk_cache {
+            resolver.set_disk_cache(disk_cache.clone());
+        }
+        resolver.set_user_ip_owners(user_ip_owners);
+        resolver.set_offline(self.offline);
+        resolver
+    }
+
+    /// Cache symbolizing the events streamed to the aggregator
+    fn symbol_cache(&self, mode: SymbolizeMode) -> collector::symbols::SymbolCache {
+        collector::symbols::SymbolCache::for_mode(mode)
+            .with_process_table(self.process_table.clone())
+            .with_disk_cache(self.disk_cache.clone())
+            .with_normalizer(self.normalizer.clone())
+    }
+
+    fn cpu_profile(&self, collector: &collector::cpu::CpuCollector) -> Result<Profile> {
+        let mut profile = collector.build_profile()?;
+        if profile.total_samples > 0 {
+            let mut resolver = self.resolver(collector.user_ips_by_pid());
+            resolver.symbolize_profile(&mut profile, self.target_pid)?;
+            resolver.report_user_symbol_stats();
+            self.normalizer.normalize_profile(&mut profile);
+            log_normalization(&profile.normalization);
+        }
+        Ok(profile)
+    }
+
+    fn lock_profile(&self, collector: &collector::lock::LockCollector) -> Result<LockProfile> {
+        let mut profile = collector.build_profile()?;
+        if profile.total_events > 0 {
+            let mut resolver = self.resolver(collector.user_ips_by_pid());
+            resolver.symbolize_lock_profile(&mut profile, self.target_pid)?;
+            resolver.report_user_symbol_stats();
+            self.normalizer.normalize_lock_profile(&mut profile);
+            log_normalization(&profile.normalization);
+        }
+        Ok(profile)
+    }
+
+    fn syscall_profile(
+        &self,
+        collector: &collector::syscall::SyscallCollector,
+    ) -> Result<SyscallProfile> {
+        let mut profile = collector.build_profile()?;
+        if profile.slow_stack_count() > 0 {
+            let mut resolver = self.resolver(collector.user_ips_by_pid());
+            resolver.symbolize_syscall_profile(&mut profile, self.target_pid)?;
+            resolver.report_user_symbol_stats();
+            self.normalizer.normalize_syscall_profile(&mut profile);
+            log_normalization(&profile.normalization);
+        }
+        Ok(profile)
+    }
+}
+
+/// Log which frame normalization rules changed frames, and how many.
+fn log_normalization(applied: &std::collections::BTreeMap<String, u64>) {
+    if applied.is_empty() {
+        return;
+    }
+    let summary: Vec<String> = applied
+        .iter()
+        .map(|(rule, frames)| format!("{} ({} frames)", rule, frames))
+        .collect();
+    info!("Frame normalization applied: {}", summary.join(", "));
+}
+
+/// Open the persistent symbol cache for stack-capturing runs. Failures only
+/// disable the cache.
+fn open_symbol_cache(config: &Config) -> Option<SharedDiskCache> {
+    if !config.captures_stacks() {
+        return None;
+    }
+    let cache_config = config.symbol_cache.as_ref()?;
+    match collector::disk_cache::DiskSymbolCache::open(&cache_config.dir, cache_config.max_bytes) {
+        Ok(cache) => Some(std::sync::Arc::new(cache)),
+        Err(e) => {
+            warn!("Persistent symbol cache disabled: {:#}", e);
+            None
+        }
+    }
+}
+
+async fn run_mode(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    recorder: Option<SharedRecorder>,
+) -> Result<()> {
     match config.mode {
-        config::ProfileMode::Cpu => run_cpu_profiler(config).await,
-        config::ProfileMode::Lock => run_lock_profiler(config).await,
-        config::ProfileMode::Syscall => run_syscall_profiler(config).await,
+        config::ProfileMode::Cpu => {
+            run_cpu_profiler(config, processes, disk_cache, end, recorder).await
+        }
+        config::ProfileMode::Lock => {
+            run_lock_profiler(config, processes, disk_cache, end, recorder).await
+        }
+        config::ProfileMode::KernelLock => {
+            run_kernel_lock_profiler(config, processes, disk_cache, end).await
+        }
+        config::ProfileMode::Syscall => {
+            run_syscall_profiler(config, processes, disk_cache, end, recorder).await
+        }
+        config::ProfileMode::BlockIo => run_block_io_profiler(config, end).await,
+        config::ProfileMode::Sched => run_sched_profiler(config, processes, disk_cache, end).await,
+        config::ProfileMode::Tcp => run_tcp_profiler(config, end).await,
+        config::ProfileMode::Memory => {
+            run_memory_profiler(config, processes, disk_cache, end).await
+        }
+        config::ProfileMode::Probe => run_probe_profiler(config, processes, disk_cache, end).await,
         config::ProfileMode::All => {
             info!("Running all profilers concurrently");
 
@@ -307,31 +767,104 @@ pub async fn run_profiler(config: Config) -> Result<()> {
                 syscall_config.json_output = Some(format!("{}.syscall.json", json));
             }
 
-            let cpu_future = run_cpu_profiler(cpu_config);
-            let lock_future = run_lock_profiler(lock_config);
-            let syscall_future = run_syscall_profiler(syscall_config);
+            // Probes run alongside when listed, so USDT spans can be
+            // correlated with the CPU samples
+            let probe_config = (!config.probes.probes.is_empty()).then(|| {
+                let mut probe_config = config.clone();
+                probe_config.output_path = format!("{}.probe.txt", config.output_path);
+                if let Some(ref json) = config.json_output {
+                    probe_config.json_output = Some(format!("{}.probe.json", json));
+                }
+                probe_config
+            });
+
+            let cpu_future = run_cpu_profiler(
+                cpu_config,
+                processes.clone(),
+                disk_cache.clone(),
+                end.clone(),
+                recorder.clone(),
+            );
+            let lock_future = run_lock_profiler(
+                lock_config,
+                processes.clone(),
+                disk_cache.clone(),
+                end.clone(),
+                recorder.clone(),
+            );
+            let syscall_future = run_syscall_profiler(
+                syscall_config,
+                processes.clone(),
+                disk_cache.clone(),
+                end.clone(),
+                recorder,
+            );
+            let probe_future = async {
+                match probe_config {
+                    Some(probe_config) => {
+                        run_probe_profiler(probe_config, processes, disk_cache, end).await
+                    }
+                    None => Ok(()),
+                }
+            };
 
-            let (cpu_res, lock_res, syscall_res) =
-                tokio::join!(cpu_future, lock_future, syscall_future);
+            let (cpu_res, lock_res, syscall_res, probe_res) =
+                tokio::join!(cpu_future, lock_future, syscall_future, probe_future);
 
             cpu_res.context("CPU profiler failed")?;
             lock_res.context("Lock profiler failed")?;
             syscall_res.context("Syscall profiler failed")?;
+            probe_res.context("Probe profiler failed")?;
 
             Ok(())
         }
     }
 }
 
-async fn run_cpu_profiler(config: Config) -> Result<()> {
-    use aya::maps::{perf::AsyncPerfEventArray, StackTraceMap};
-    use aya::util::online_cpus;
-    use bytes::BytesMut;
+async fn run_cpu_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    recorder: Option<SharedRecorder>,
+) -> Result<()> {
+    let (output_path, json_output) = (config.output_path.clone(), config.json_output.clone());
+    let profile = collect_cpu_profile(config, processes, disk_cache, end, None, recorder).await?;
+    write_cpu_outputs(&profile, &output_path, json_output.as_deref())
+}
+
+/// Write the flamegraph and JSON of a CPU profile that has samples
+fn write_cpu_outputs(
+    profile: &Profile,
+    output_path: &str,
+    json_output: Option<&str>,
+) -> Result<()> {
+    if profile.total_samples > 0 {
+        output::flamegraph::generate_flamegraph(profile, output_path)?;
+
+        if let Some(json_path) = json_output {
+            output::json::generate_json(profile, json_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sample CPU stacks until `end`, returning the symbolized profile
+async fn collect_cpu_profile(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    snapshots: Option<std::sync::Arc<session::Snapshots>>,
+    recorder: Option<SharedRecorder>,
+) -> Result<Profile> {
+    use aya::maps::StackTraceMap;
     use std::sync::Arc;
     use tokio::sync::Mutex;
 
     use collector::cpu::{CpuCollector, SampleEvent};
-    use collector::symbols::{SymbolCache, SymbolResolver};
+    use collector::normalize::FrameNormalizer;
     use ebpf::cpu_profiler::CpuProfiler;
 
     info!(
@@ -339,136 +872,125 @@ async fn run_cpu_profiler(config: Config) -> Result<()> {
         config.duration.as_secs(),
         config.sample_rate_hz
     );
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
 
     // 1. Load and start eBPF program
     let mut profiler =
         CpuProfiler::new(config.sample_rate_hz).context("Failed to create CPU profiler")?;
 
     profiler.set_target_pid(config.target_pid);
+    profiler.set_follow_children(config.follow_children);
+
+    profiler.set_labels(config.labels.clone());
     profiler.start().context("Failed to start profiler")?;
 
     // 2. Set up event collector
     let collector = Arc::new(Mutex::new(CpuCollector::new(config.sample_period_ns())));
 
-    // 3. Get maps for reading events and stacks
+    // 3. Get the map sampled stacks are stored in
     let bpf = profiler.bpf_mut();
-    let events_map = bpf.take_map("EVENTS").context("Failed to get EVENTS map")?;
-    let mut perf_array = AsyncPerfEventArray::try_from(events_map)?;
-
     let stacks_map = bpf.take_map("STACKS").context("Failed to get STACKS map")?;
-    let stack_map = Arc::new(StackTraceMap::try_from(stacks_map)?);
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
 
-    // 4. Spawn per-CPU reader tasks
-    let cpus = online_cpus().map_err(|(msg, e)| anyhow::anyhow!("{}: {}", msg, e))?;
-    let mut handles = Vec::new();
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
+    };
 
-    for cpu_id in cpus {
-        let mut buf = perf_array.open(cpu_id, None)?;
+    // 4. Spawn per-CPU reader tasks, and streaming push if aggregator is configured
+    let push = PushConfig::for_config(&config, CpuCollector::take_pending_events).map(|push| {
+        push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+            .with_processes(processes.clone())
+    });
+    let stream = stream_perf_events(
+        bpf,
+        "EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &SampleEvent| coll.process_event(event, &stack_map),
+    )?;
+
+    if let Some(snapshots) = &snapshots {
         let collector = collector.clone();
-        let stack_map = stack_map.clone();
-
-        handles.push(tokio::spawn(async move {
-            let mut buffers = (0..10)
-                .map(|_| BytesMut::with_capacity(core::mem::size_of::<SampleEvent>() + 64))
-                .collect::<Vec<_>>();
-
-            while let Ok(events) = buf.read_events(&mut buffers).await {
-                for buf_ref in buffers.iter().take(events.read) {
-                    if buf_ref.len() >= core::mem::size_of::<SampleEvent>() {
-                        let event = unsafe { &*(buf_ref.as_ptr() as *const SampleEvent) };
-                        let mut coll = collector.lock().await;
-                        if let Err(e) = coll.process_event(event, &stack_map) {
-                            debug!("Error processing event: {}", e);
-                        }
-                    }
-                }
-            }
-        }));
+        let symbolizer = symbolizer.clone();
+        snapshots.set_cpu(move || symbolizer.cpu_profile(&collector.blocking_lock()));
     }
 
-    // 5. Spawn streaming push task if aggregator is configured
-    let target_pid = config.target_pid;
-    let push_handle = if let Some(ref url) = config.aggregator_url {
-        let url = url.clone();
-        let agent = agent_id();
-        let coll = collector.clone();
-        let initial_interval = config.push_interval();
-        Some(tokio::spawn(async move {
-            let mut client = None;
-            let mut push_interval = initial_interval;
-            let mut sym_cache = SymbolCache::new();
-            loop {
-                tokio::time::sleep(push_interval).await;
-                let mut events = coll.lock().await.take_pending_events();
-                sym_cache.symbolize_events(&mut events, target_pid);
-                let result = push_to_aggregator_with_retry(&mut client, &url, &agent, events).await;
-                match result {
-                    Ok(Some(true)) => {
-                        push_interval = (push_interval + push_interval).min(PUSH_INTERVAL_MAX)
-                    }
-                    Ok(Some(false)) | Ok(None) => push_interval = initial_interval,
-                    Err(e) => warn!("Streaming push failed: {}", e),
-                }
-            }
-        }))
-    } else {
-        None
-    };
-
-    // 6. Wait for profiling duration
-    tokio::time::sleep(config.duration).await;
+    // 5. Wait for profiling duration
+    end.wait().await;
 
-    // 7. Cleanup — abort reader tasks and streaming push, wait for Arc cleanup
-    if let Some(h) = push_handle {
-        h.abort();
-        let _ = h.await;
-    }
-    for handle in &handles {
-        handle.abort();
-    }
-    for handle in handles {
-        let _ = handle.await;
-    }
+    // 6. Cleanup — stop the profiler, then the reader tasks and streaming push
     profiler.stop()?;
+    stream.finish().await;
 
-    // Drop the stack_map Arc so collector is the only one left
-    drop(stack_map);
+    // Session snapshots may still hold the collector
+    let collector = collector.lock().await;
 
-    let mut collector = Arc::try_unwrap(collector)
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap collector Arc"))?
-        .into_inner();
-
-    // Final push of any remaining events (with symbolization)
-    if let Some(ref url) = config.aggregator_url {
-        let mut client = None;
-        let mut events = collector.take_pending_events();
-        let mut sym_cache = SymbolCache::new();
-        sym_cache.symbolize_events(&mut events, config.target_pid);
-        let _ = push_to_aggregator_with_retry(&mut client, url, &agent_id(), events).await;
+    if let Some(recorder) = &recorder {
+        recorder.write_events(collector.profile_events())?;
     }
 
-    let mut profile = collector.build_profile()?;
+    // 7. Symbolize
+    symbolizer.cpu_profile(&collector)
+}
 
-    // 8. Symbolize & Output
-    if profile.total_samples > 0 {
-        let mut resolver = SymbolResolver::new();
-        resolver.symbolize_profile(&mut profile, config.target_pid)?;
-        output::flamegraph::generate_flamegraph(&profile, &config.output_path)?;
+async fn run_lock_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    recorder: Option<SharedRecorder>,
+) -> Result<()> {
+    let (output_path, json_output) = (config.output_path.clone(), config.json_output.clone());
+    let profile = collect_lock_profile(config, processes, disk_cache, end, None, recorder).await?;
+    write_lock_outputs(&profile, &output_path, json_output.as_deref())
+}
 
-        if let Some(json_path) = &config.json_output {
-            output::json::generate_json(&profile, json_path)?;
+/// Write the contention and holder flamegraphs and JSON of a lock profile
+/// that has events
+fn write_lock_outputs(
+    profile: &LockProfile,
+    output_path: &str,
+    json_output: Option<&str>,
+) -> Result<()> {
+    if profile.total_events > 0 {
+        output::flamegraph::generate_lock_flamegraph(profile, output_path)?;
+        if let Some(path) =
+            output::flamegraph::generate_lock_holder_flamegraph(profile, output_path)?
+        {
+            info!("Lock holder flamegraph: {}", path);
+        }
+
+        if let Some(json_path) = json_output {
+            output::json::generate_lock_json(profile, json_path)?;
         }
     }
 
     Ok(())
 }
 
-async fn run_lock_profiler(config: Config) -> Result<()> {
-    use aya::maps::{perf::AsyncPerfEventArray, StackTraceMap};
-    use aya::util::online_cpus;
-    use bytes::BytesMut;
+/// Trace lock contention until `end`, returning the symbolized profile
+async fn collect_lock_profile(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    snapshots: Option<std::sync::Arc<session::Snapshots>>,
+    recorder: Option<SharedRecorder>,
+) -> Result<LockProfile> {
+    use aya::maps::StackTraceMap;
     use collector::lock::{LockCollector, LockEventBpf};
-    use collector::symbols::{SymbolCache, SymbolResolver};
+    use collector::lock_names::{AllocSites, LockNamer};
+    use collector::normalize::FrameNormalizer;
     use ebpf::lock_profiler::LockProfiler;
     use std::sync::Arc;
     use tokio::sync::Mutex;
@@ -477,231 +999,725 @@ async fn run_lock_profiler(config: Config) -> Result<()> {
         "Profiling lock contention for {} seconds",
         config.duration.as_secs()
     );
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
 
     let mut profiler = LockProfiler::new()?;
     profiler.set_target_pid(config.target_pid);
+    profiler.set_follow_children(config.follow_children);
+    profiler.set_uprobes(config.lock_uprobes.clone());
+    profiler.set_labels(config.labels.clone());
     profiler.start()?;
 
     let collector = Arc::new(Mutex::new(LockCollector::new()));
     let bpf = profiler.bpf_mut();
 
-    let events_map = bpf
-        .take_map("LOCK_EVENTS")
-        .context("Failed to get LOCK_EVENTS map")?;
-    let mut perf_array = AsyncPerfEventArray::try_from(events_map)?;
-
     let stacks_map = bpf
         .take_map("LOCK_STACKS")
         .context("Failed to get LOCK_STACKS map")?;
-    let stack_map = Arc::new(StackTraceMap::try_from(stacks_map)?);
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
+
+    // Heap locks are named from the allocations the allocation uprobes track
+    let alloc_sites = match (config.lock_uprobes.alloc_sites, config.target_pid) {
+        (true, Some(pid)) => {
+            let allocs = bpf.take_map("ALLOCS").context("Failed to get ALLOCS map")?;
+            let stacks = bpf
+                .take_map("ALLOC_STACKS")
+                .context("Failed to get ALLOC_STACKS map")?;
+            Some(AllocSites::new(
+                pid,
+                aya::maps::HashMap::try_from(allocs)?,
+                StackTraceMap::try_from(stacks)?,
+            ))
+        }
+        _ => None,
+    };
+    let namer = Arc::new(std::sync::Mutex::new(
+        LockNamer::new().with_alloc_sites(alloc_sites),
+    ));
 
-    let cpus = online_cpus().map_err(|(msg, e)| anyhow::anyhow!("{}: {}", msg, e))?;
-    let mut handles = Vec::new();
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
+    };
 
-    for cpu_id in cpus {
-        let mut buf = perf_array.open(cpu_id, None)?;
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let drain_namer = namer.clone();
+    let push = PushConfig::for_config(&config, move |coll: &mut LockCollector| {
+        coll.name_locks(&mut drain_namer.lock().unwrap());
+        coll.take_pending_events()
+    })
+    .map(|push| {
+        push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+            .with_processes(processes.clone())
+    });
+    let stream = stream_perf_events(
+        bpf,
+        "LOCK_EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &LockEventBpf| coll.process_event(event, &stack_map),
+    )?;
+
+    if let Some(snapshots) = &snapshots {
         let collector = collector.clone();
-        let stack_map = stack_map.clone();
+        let namer = namer.clone();
+        let symbolizer = symbolizer.clone();
+        snapshots.set_lock(move || {
+            let mut collector = collector.blocking_lock();
+            collector.name_locks(&mut namer.lock().unwrap());
+            symbolizer.lock_profile(&collector)
+        });
+    }
 
-        handles.push(tokio::spawn(async move {
-            let mut buffers = (0..10)
-                .map(|_| BytesMut::with_capacity(core::mem::size_of::<LockEventBpf>() + 64))
-                .collect::<Vec<_>>();
-
-            while let Ok(events) = buf.read_events(&mut buffers).await {
-                for buf_ref in buffers.iter().take(events.read) {
-                    if buf_ref.len() >= core::mem::size_of::<LockEventBpf>() {
-                        let event = unsafe { &*(buf_ref.as_ptr() as *const LockEventBpf) };
-                        let mut coll = collector.lock().await;
-                        if let Err(e) = coll.process_event(event, &stack_map) {
-                            debug!("Error processing lock event: {}", e);
-                        }
-                    }
-                }
-            }
-        }));
+    end.wait().await;
+
+    // Cleanup
+    profiler.stop();
+    stream.finish().await;
+
+    // Session snapshots may still hold the collector
+    let mut collector = collector.lock().await;
+    collector.name_locks(&mut namer.lock().unwrap());
+
+    if let Some(recorder) = &recorder {
+        recorder.write_events(collector.profile_events())?;
     }
 
-    // Spawn streaming push task if aggregator is configured
-    let target_pid = config.target_pid;
-    let push_handle = if let Some(ref url) = config.aggregator_url {
-        let url = url.clone();
-        let agent = agent_id();
-        let coll = collector.clone();
-        let initial_interval = config.push_interval();
-        Some(tokio::spawn(async move {
-            let mut client = None;
-            let mut push_interval = initial_interval;
-            let mut sym_cache = SymbolCache::new();
-            loop {
-                tokio::time::sleep(push_interval).await;
-                let mut events = coll.lock().await.take_pending_events();
-                sym_cache.symbolize_events(&mut events, target_pid);
-                let result = push_to_aggregator_with_retry(&mut client, &url, &agent, events).await;
-                match result {
-                    Ok(Some(true)) => {
-                        push_interval = (push_interval + push_interval).min(PUSH_INTERVAL_MAX)
-                    }
-                    Ok(Some(false)) | Ok(None) => push_interval = initial_interval,
-                    Err(e) => warn!("Streaming push failed: {}", e),
-                }
-            }
-        }))
-    } else {
-        None
+    symbolizer.lock_profile(&collector)
+}
+
+async fn run_kernel_lock_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+) -> Result<()> {
+    use aya::maps::StackTraceMap;
+    use collector::kernel_lock::{KernelLockCollector, KernelLockEventBpf};
+    use collector::normalize::FrameNormalizer;
+    use ebpf::kernel_lock_profiler::KernelLockProfiler;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    info!(
+        "Profiling kernel lock contention for {} seconds",
+        config.duration.as_secs()
+    );
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
+
+    let mut profiler = KernelLockProfiler::new()?;
+    profiler.set_target_pid(config.target_pid);
+    profiler.set_follow_children(config.follow_children);
+    profiler.start()?;
+
+    let collector = Arc::new(Mutex::new(KernelLockCollector::new()));
+    let bpf = profiler.bpf_mut();
+
+    let stacks_map = bpf
+        .take_map("LOCK_STACKS")
+        .context("Failed to get LOCK_STACKS map")?;
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
+
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
     };
 
-    tokio::time::sleep(config.duration).await;
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let push =
+        PushConfig::for_config(&config, KernelLockCollector::take_pending_events).map(|push| {
+            push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+                .with_processes(processes.clone())
+        });
+    let stream = stream_perf_events(
+        bpf,
+        "KERNEL_LOCK_EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &KernelLockEventBpf| coll.process_event(event, &stack_map),
+    )?;
+
+    end.wait().await;
 
     // Cleanup
-    if let Some(h) = push_handle {
-        h.abort();
-        let _ = h.await;
-    }
-    for handle in &handles {
-        handle.abort();
-    }
-    for handle in handles {
-        let _ = handle.await;
-    }
     profiler.stop();
+    stream.finish().await;
 
-    let mut collector = Arc::try_unwrap(collector)
+    let collector = Arc::try_unwrap(collector)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner();
 
-    // Final push of remaining events (with symbolization)
-    if let Some(ref url) = config.aggregator_url {
-        let mut client = None;
-        let mut events = collector.take_pending_events();
-        let mut sym_cache = SymbolCache::new();
-        sym_cache.symbolize_events(&mut events, config.target_pid);
-        let _ = push_to_aggregator_with_retry(&mut client, url, &agent_id(), events).await;
-    }
-
     let mut profile = collector.build_profile()?;
 
     if profile.total_events > 0 {
-        let mut resolver = SymbolResolver::new();
-        resolver.symbolize_lock_profile(&mut profile, config.target_pid)?;
-        output::flamegraph::generate_lock_flamegraph(&profile, &config.output_path)?;
+        let mut resolver = symbolizer.resolver(collector.user_ips_by_pid());
+        resolver.symbolize_kernel_lock_profile(&mut profile, config.target_pid)?;
+        resolver.report_user_symbol_stats();
+        symbolizer
+            .normalizer
+            .normalize_kernel_lock_profile(&mut profile);
+        log_normalization(&profile.normalization);
+        output::flamegraph::generate_kernel_lock_flamegraph(&profile, &config.output_path)?;
 
         if let Some(json_path) = &config.json_output {
-            output::json::generate_lock_json(&profile, json_path)?;
+            output::json::generate_kernel_lock_json(&profile, json_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_syscall_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    recorder: Option<SharedRecorder>,
+) -> Result<()> {
+    let output_path = config.output_path.clone();
+    let json_output = config.json_output.clone();
+    let profile =
+        collect_syscall_profile(config, processes, disk_cache, end, None, recorder).await?;
+    write_syscall_outputs(&profile, &output_path, json_output.as_deref())
+}
+
+/// Write the slow-call flamegraphs, histogram report and JSON of a syscall
+/// profile
+fn write_syscall_outputs(
+    profile: &SyscallProfile,
+    output_path: &str,
+    json_output: Option<&str>,
+) -> Result<()> {
+    if profile.slow_stack_count() > 0 {
+        for path in output::flamegraph::generate_syscall_flamegraphs(profile, output_path)? {
+            info!("Slow syscall flamegraph: {}", path);
+        }
+    }
+
+    if profile.total_events > 0 {
+        output::histogram::generate_syscall_histogram(profile, output_path)?;
+
+        if let Some(json_path) = json_output {
+            output::json::generate_syscall_json(profile, json_path)?;
         }
     }
 
     Ok(())
 }
 
-async fn run_syscall_profiler(config: Config) -> Result<()> {
-    use aya::maps::perf::AsyncPerfEventArray;
-    use aya::util::online_cpus;
-    use bytes::BytesMut;
-    use collector::syscall::{SyscallCollector, SyscallEventBpf};
+/// Trace syscalls until `end`, returning the symbolized profile
+async fn collect_syscall_profile(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    snapshots: Option<std::sync::Arc<session::Snapshots>>,
+    recorder: Option<SharedRecorder>,
+) -> Result<SyscallProfile> {
+    use aya::maps::{Array, PerCpuArray, StackTraceMap};
+    use collector::normalize::FrameNormalizer;
+    use collector::syscall::{SyscallCollector, SyscallEventBpf, SyscallHistograms};
     use ebpf::syscall_tracer::SyscallTracer;
     use std::sync::Arc;
     use tokio::sync::Mutex;
 
     info!("Tracing syscalls for {} seconds", config.duration.as_secs());
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
 
     let mut tracer = SyscallTracer::new()?;
     tracer.set_target_pid(config.target_pid);
+    tracer.set_follow_children(config.follow_children);
+    tracer.set_stack_threshold(config.syscall_stack_threshold);
+    tracer.set_filter(config.syscall_filter.clone());
     tracer.start()?;
 
-    let collector = Arc::new(Mutex::new(SyscallCollector::new()));
+    let mut syscall_collector = SyscallCollector::new();
+    syscall_collector.set_sampling(config.syscall_filter.sample_every);
+    syscall_collector.set_target_pid(config.target_pid);
     let bpf = tracer.bpf_mut();
 
-    let events_map = bpf
-        .take_map("SYSCALL_EVENTS")
-        .context("Failed to get SYSCALL_EVENTS map")?;
-    let mut perf_array = AsyncPerfEventArray::try_from(events_map)?;
+    // Aggregation mode: the kernel keeps histograms instead of sending events
+    if config.syscall_filter.aggregate {
+        let hist = bpf
+            .take_map("SYSCALL_HIST")
+            .context("Failed to get SYSCALL_HIST map")?;
+        let syscall_config = bpf
+            .take_map("SYSCALL_CONFIG")
+            .context("Failed to get SYSCALL_CONFIG map")?;
+        syscall_collector.set_histograms(SyscallHistograms::new(
+            PerCpuArray::try_from(hist)?,
+            Array::try_from(syscall_config)?,
+        ));
+    }
+    let collector = Arc::new(Mutex::new(syscall_collector));
 
-    let cpus = online_cpus().map_err(|(msg, e)| anyhow::anyhow!("{}: {}", msg, e))?;
-    let mut handles = Vec::new();
+    let stacks_map = bpf
+        .take_map("SYSCALL_STACKS")
+        .context("Failed to get SYSCALL_STACKS map")?;
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
 
-    for cpu_id in cpus {
-        let mut buf = perf_array.open(cpu_id, None)?;
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
+    };
+
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let push = PushConfig::for_config(&config, |coll: &mut SyscallCollector| {
+        coll.read_histograms();
+        coll.take_pending_events()
+    })
+    .map(|push| {
+        push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+            .with_processes(processes.clone())
+    });
+    let stream = stream_perf_events(
+        bpf,
+        "SYSCALL_EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &SyscallEventBpf| coll.process_event(event, &stack_map),
+    )?;
+
+    if let Some(snapshots) = &snapshots {
         let collector = collector.clone();
+        let symbolizer = symbolizer.clone();
+        snapshots.set_syscall(move || {
+            let mut collector = collector.blocking_lock();
+            collector.read_histograms();
+            symbolizer.syscall_profile(&collector)
+        });
+    }
 
-        handles.push(tokio::spawn(async move {
-            let mut buffers = (0..10)
-                .map(|_| BytesMut::with_capacity(core::mem::size_of::<SyscallEventBpf>() + 64))
-                .collect::<Vec<_>>();
-
-            while let Ok(events) = buf.read_events(&mut buffers).await {
-                for buf_ref in buffers.iter().take(events.read) {
-                    if buf_ref.len() >= core::mem::size_of::<SyscallEventBpf>() {
-                        let event = unsafe { &*(buf_ref.as_ptr() as *const SyscallEventBpf) };
-                        let mut coll = collector.lock().await;
-                        if let Err(e) = coll.process_event(event) {
-                            debug!("Error processing syscall event: {}", e);
-                        }
-                    }
-                }
-            }
-        }));
+    end.wait().await;
+
+    // Cleanup
+    tracer.stop();
+    stream.finish().await;
+
+    // Session snapshots may still hold the collector
+    let mut collector = collector.lock().await;
+    collector.read_histograms();
+
+    if let Some(recorder) = &recorder {
+        recorder.write_events(collector.profile_events())?;
     }
 
-    // Spawn streaming push task if aggregator is configured
-    let push_handle = if let Some(ref url) = config.aggregator_url {
-        let url = url.clone();
-        let agent = agent_id();
-        let coll = collector.clone();
-        let initial_interval = config.push_interval();
-        Some(tokio::spawn(async move {
-            let mut client = None;
-            let mut push_interval = initial_interval;
-            loop {
-                tokio::time::sleep(push_interval).await;
-                let events = coll.lock().await.take_pending_events();
-                let result = push_to_aggregator_with_retry(&mut client, &url, &agent, events).await;
-                match result {
-                    Ok(Some(true)) => {
-                        push_interval = (push_interval + push_interval).min(PUSH_INTERVAL_MAX)
-                    }
-                    Ok(Some(false)) | Ok(None) => push_interval = initial_interval,
-                    Err(e) => warn!("Streaming push failed: {}", e),
-                }
-            }
-        }))
-    } else {
-        None
-    };
+    symbolizer.syscall_profile(&collector)
+}
 
-    tokio::time::sleep(config.duration).await;
+async fn run_block_io_profiler(config: Config, end: ProfileEnd) -> Result<()> {
+    use collector::block_io::{BlockIoCollector, BlockIoEventBpf};
+    use ebpf::block_io_tracer::BlockIoTracer;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    info!(
+        "Tracing block I/O for {} seconds",
+        config.duration.as_secs()
+    );
+
+    let mut tracer = BlockIoTracer::new()?;
+    tracer.set_target_pid(config.target_pid);
+    tracer.set_follow_children(config.follow_children);
+    tracer.start()?;
+
+    let collector = Arc::new(Mutex::new(BlockIoCollector::new()));
+
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let stream = stream_perf_events::<BlockIoEventBpf, _>(
+        tracer.bpf_mut(),
+        "BLOCK_EVENTS",
+        collector.clone(),
+        PushConfig::for_config(&config, BlockIoCollector::take_pending_events),
+        BlockIoCollector::process_event,
+    )?;
+
+    end.wait().await;
 
     // Cleanup
-    if let Some(h) = push_handle {
-        h.abort();
-        let _ = h.await;
+    tracer.stop();
+    stream.finish().await;
+
+    let collector = Arc::try_unwrap(collector)
+        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
+        .into_inner();
+
+    let profile = collector.build_profile()?;
+
+    if profile.total_events > 0 {
+        output::histogram::generate_block_io_histogram(&profile, &config.output_path)?;
+
+        if let Some(json_path) = &config.json_output {
+            output::json::generate_block_io_json(&profile, json_path)?;
+        }
     }
-    for handle in &handles {
-        handle.abort();
+
+    Ok(())
+}
+
+async fn run_tcp_profiler(config: Config, end: ProfileEnd) -> Result<()> {
+    use collector::tcp::{TcpCollector, TcpEventBpf};
+    use ebpf::tcp_tracer::TcpTracer;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    info!(
+        "Tracing TCP connections for {} seconds",
+        config.duration.as_secs()
+    );
+
+    let mut tracer = TcpTracer::new()?;
+    tracer.set_target_pid(config.target_pid);
+    tracer.set_follow_children(config.follow_children);
+    tracer.start()?;
+
+    let collector = Arc::new(Mutex::new(TcpCollector::new()));
+
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let stream = stream_perf_events::<TcpEventBpf, _>(
+        tracer.bpf_mut(),
+        "TCP_EVENTS",
+        collector.clone(),
+        PushConfig::for_config(&config, TcpCollector::take_pending_events),
+        TcpCollector::process_event,
+    )?;
+
+    end.wait().await;
+
+    // Cleanup
+    tracer.stop();
+    stream.finish().await;
+
+    let collector = Arc::try_unwrap(collector)
+        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
+        .into_inner();
+
+    let profile = collector.build_profile()?;
+
+    if profile.total_events > 0 {
+        output::histogram::generate_tcp_histogram(&profile, &config.output_path)?;
+
+        if let Some(json_path) = &config.json_output {
+            output::json::generate_tcp_json(&profile, json_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_sched_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+) -> Result<()> {
+    use aya::maps::StackTraceMap;
+    use collector::normalize::FrameNormalizer;
+    use collector::sched::{SchedCollector, SchedEventBpf};
+    use ebpf::sched_tracer::SchedTracer;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    info!(
+        "Tracing run-queue latency for {} seconds",
+        config.duration.as_secs()
+    );
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
+
+    let mut tracer = SchedTracer::new()?;
+    tracer.set_target_pid(config.target_pid);
+    tracer.set_follow_children(config.follow_children);
+    tracer.set_config(config.sched.clone());
+    tracer.start()?;
+
+    let collector = Arc::new(Mutex::new(SchedCollector::new()));
+    let bpf = tracer.bpf_mut();
+
+    let stacks_map = bpf
+        .take_map("SCHED_STACKS")
+        .context("Failed to get SCHED_STACKS map")?;
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
+
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
+    };
+
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let push = PushConfig::for_config(&config, SchedCollector::take_pending_events).map(|push| {
+        push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+            .with_processes(processes.clone())
+    });
+    let stream = stream_perf_events(
+        bpf,
+        "SCHED_EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &SchedEventBpf| coll.process_event(event, &stack_map),
+    )?;
+
+    end.wait().await;
+
+    // Cleanup
+    tracer.stop();
+    stream.finish().await;
+
+    let collector = Arc::try_unwrap(collector)
+        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
+        .into_inner();
+
+    let mut profile = collector.build_profile()?;
+
+    if profile.wakeup_stack_count() > 0 {
+        let mut resolver = symbolizer.resolver(collector.user_ips_by_pid());
+        resolver.symbolize_sched_profile(&mut profile, config.target_pid)?;
+        resolver.report_user_symbol_stats();
+        symbolizer.normalizer.normalize_sched_profile(&mut profile);
+        log_normalization(&profile.normalization);
+        let wakeup_path = format!("{}.wakeup.svg", config.output_path);
+        output::flamegraph::generate_sched_flamegraph(&profile, &wakeup_path)?;
+        info!("Wakeup flamegraph: {}", wakeup_path);
     }
-    for handle in handles {
-        let _ = handle.await;
+
+    if profile.total_events > 0 {
+        output::histogram::generate_sched_histogram(&profile, &config.output_path)?;
+
+        if let Some(json_path) = &config.json_output {
+            output::json::generate_sched_json(&profile, json_path)?;
+        }
     }
+
+    Ok(())
+}
+
+async fn run_memory_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+) -> Result<()> {
+    use aya::maps::StackTraceMap;
+    use collector::memory::{MemoryCollector, MemoryEventBpf};
+    use collector::normalize::FrameNormalizer;
+    use ebpf::memory_tracer::MemoryTracer;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    info!(
+        "Tracing page faults and reclaim for {} seconds",
+        config.duration.as_secs()
+    );
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
+
+    let mut tracer = MemoryTracer::new()?;
+    tracer.set_target_pid(config.target_pid);
+    tracer.set_follow_children(config.follow_children);
+    tracer.set_config(config.memory.clone());
+    tracer.start()?;
+
+    let collector = Arc::new(Mutex::new(MemoryCollector::new(
+        config.memory.fault_sample_every,
+    )));
+    let bpf = tracer.bpf_mut();
+
+    let stacks_map = bpf
+        .take_map("MEMORY_STACKS")
+        .context("Failed to get MEMORY_STACKS map")?;
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
+
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
+    };
+
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let push = PushConfig::for_config(&config, MemoryCollector::take_pending_events).map(|push| {
+        push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+            .with_processes(processes.clone())
+    });
+    let stream = stream_perf_events(
+        bpf,
+        "MEMORY_EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &MemoryEventBpf| coll.process_event(event, &stack_map),
+    )?;
+
+    end.wait().await;
+
+    // Cleanup
     tracer.stop();
+    stream.finish().await;
 
-    let mut collector = Arc::try_unwrap(collector)
+    let collector = Arc::try_unwrap(collector)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner();
 
-    // Final push of remaining events
-    if let Some(ref url) = config.aggregator_url {
-        let mut client = None;
-        let events = collector.take_pending_events();
-        let _ = push_to_aggregator_with_retry(&mut client, url, &agent_id(), events).await;
+    let mut profile = collector.build_profile()?;
+
+    if profile.faults.total_samples > 0 {
+        let mut resolver = symbolizer.resolver(collector.user_ips_by_pid());
+        resolver.symbolize_profile(&mut profile.faults, config.target_pid)?;
+        resolver.report_user_symbol_stats();
+        symbolizer.normalizer.normalize_profile(&mut profile.faults);
+        log_normalization(&profile.faults.normalization);
+        output::flamegraph::generate_memory_flamegraph(&profile, &config.output_path)?;
     }
 
-    let profile = collector.build_profile()?;
+    if profile.total_events > 0 {
+        let reclaim_path = format!("{}.reclaim.txt", config.output_path);
+        output::histogram::generate_memory_histogram(&profile, &reclaim_path)?;
+        info!("Reclaim stall histograms: {}", reclaim_path);
+
+        if let Some(json_path) = &config.json_output {
+            output::json::generate_memory_json(&profile, json_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_probe_profiler(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+) -> Result<()> {
+    use aya::maps::StackTraceMap;
+    use collector::normalize::FrameNormalizer;
+    use collector::probe::{ProbeCollector, ProbeEventBpf, UsdtEventBpf};
+    use ebpf::probe_tracer::ProbeTracer;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    info!(
+        "Tracing {} probes for {} seconds",
+        config.probes.probes.len(),
+        config.duration.as_secs()
+    );
+    let normalizer = Arc::new(FrameNormalizer::for_rules_path(
+        config.normalize_rules.as_deref(),
+    )?);
+
+    let mut tracer = ProbeTracer::new()?;
+    tracer.set_target_pid(config.target_pid);
+    tracer.set_follow_children(config.follow_children);
+    tracer.set_config(config.probes.clone());
+    tracer.start()?;
+
+    let collector = Arc::new(Mutex::new(ProbeCollector::new(
+        tracer.slot_probes(),
+        config.probes.pairs.clone(),
+    )));
+    let bpf = tracer.bpf_mut();
+
+    let stacks_map = bpf
+        .take_map("PROBE_STACKS")
+        .context("Failed to get PROBE_STACKS map")?;
+    let stack_map = StackTraceMap::try_from(stacks_map)?;
+
+    let process_table = match &processes {
+        Some(p) => Some(p.lock().await.table()),
+        None => None,
+    };
+    let symbolizer = ProfileSymbolizer {
+        process_table,
+        disk_cache,
+        normalizer,
+        target_pid: config.target_pid,
+        offline: false,
+    };
+
+    // Spawn per-CPU readers, and streaming push if aggregator is configured
+    let push = PushConfig::for_config(&config, ProbeCollector::take_pending_events).map(|push| {
+        push.with_symbol_cache(symbolizer.symbol_cache(config.symbolize), config.target_pid)
+            .with_processes(processes.clone())
+    });
+    let mut stream = stream_perf_events(
+        bpf,
+        "PROBE_EVENTS",
+        collector.clone(),
+        push,
+        move |coll, event: &ProbeEventBpf| coll.process_event(event, &stack_map),
+    )?;
+    stream.read(bpf, "USDT_EVENTS", |coll, event: &UsdtEventBpf| {
+        coll.process_usdt_event(event);
+        Ok(())
+    })?;
+
+    end.wait().await;
+
+    // Cleanup
+    tracer.stop();
+    stream.finish().await;
+
+    let collector = Arc::try_unwrap(collector)
+        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
+        .into_inner();
+
+    let mut profile = collector.build_profile()?;
+
+    if profile.stack_count() > 0 {
+        let mut resolver = symbolizer.resolver(collector.user_ips_by_pid());
+        resolver.symbolize_probe_profile(&mut profile, config.target_pid)?;
+        resolver.report_user_symbol_stats();
+        symbolizer.normalizer.normalize_probe_profile(&mut profile);
+        log_normalization(&profile.normalization);
+        for path in output::flamegraph::generate_probe_flamegraphs(&profile, &config.output_path)? {
+            info!("Probe flamegraph: {}", path);
+        }
+    }
 
     if profile.total_events > 0 {
-        output::histogram::generate_syscall_histogram(&profile, &config.output_path)?;
+        output::histogram::generate_probe_histogram(&profile, &config.output_path)?;
 
         if let Some(json_path) = &config.json_output {
-            output::json::generate_syscall_json(&profile, json_path)?;
+            output::json::generate_probe_json(&profile, json_path)?;
         }
     }
 
@@ -14,12 +14,12 @@ use aperture_agent::Config;
 #[command(about = "eBPF-based CPU profiler", long_about = None)]
 #[command(version)]
 struct Args {
-    /// Profiling mode (cpu, lock, syscall, all)
+    /// Profiling mode (cpu, lock, kernel-lock, syscall, block-io, sched, tcp, memory, probe, all)
     #[arg(short, long, default_value = "cpu")]
     mode: String,
 
     /// Process ID to profile (default: profile all processes)
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "command")]
     pid: Option<i32>,
 
     /// Duration to profile (e.g., "30s", "5m", "1h")
@@ -45,6 +45,132 @@ struct Args {
     /// Push collected data to this aggregator gRPC URL (e.g. http://127.0.0.1:50051)
     #[arg(long)]
     aggregator: Option<String>,
+
+    /// Where pushed user-space frames get their names: "agent" resolves them
+    /// locally, "deferred" ships build ID + file offset for the aggregator
+    #[arg(long, default_value = "agent")]
+    symbolize: String,
+
+    /// Directory of the persistent symbol cache (default:
+    /// $APERTURE_SYMBOL_CACHE_DIR, $XDG_CACHE_HOME/aperture or ~/.cache/aperture)
+    #[arg(long)]
+    symbol_cache_dir: Option<std::path::PathBuf>,
+
+    /// Size limit of the persistent symbol cache in MiB
+    #[arg(long, default_value_t = aperture_agent::config::SymbolCacheConfig::DEFAULT_MAX_MB)]
+    symbol_cache_size: u64,
+
+    /// Do not read or write the persistent symbol cache
+    #[arg(long)]
+    no_symbol_cache: bool,
+
+    /// JSON file of frame rewrite/collapse rules applied after symbolization
+    #[arg(long)]
+    normalize_rules: Option<std::path::PathBuf>,
+
+    /// Capture stacks of syscalls taking at least this long (e.g. "10ms",
+    /// "500us"), for per-syscall latency flamegraphs
+    #[arg(long)]
+    syscall_stack_threshold: Option<String>,
+
+    /// Trace only these syscalls (comma-separated names or numbers)
+    #[arg(long, conflicts_with = "exclude_syscalls")]
+    syscalls: Option<String>,
+
+    /// Do not trace these syscalls (comma-separated names or numbers)
+    #[arg(long)]
+    exclude_syscalls: Option<String>,
+
+    /// Drop syscalls shorter than this (e.g. "1ms"), in the kernel
+    #[arg(long)]
+    syscall_min_latency: Option<String>,
+
+    /// Record 1 in N syscalls; counts are scaled back up by N
+    #[arg(long, default_value_t = 1)]
+    syscall_sample: u32,
+
+    /// Keep per-syscall latency histograms in the kernel instead of sending
+    /// an event per call (no file/socket view or stacks)
+    #[arg(long)]
+    syscall_aggregate: bool,
+
+    /// Also trace pthread mutex/rwlock and parking_lot lock functions with
+    /// uprobes, measuring acquire latency (spinning included) and hold time
+    #[arg(long)]
+    lock_uprobes: bool,
+
+    /// Binary to search for lock functions besides the target's mappings
+    /// (repeatable; e.g. a statically linked program)
+    #[arg(long = "lock-binary")]
+    lock_binaries: Vec<std::path::PathBuf>,
+
+    /// Drop user-space lock acquires and holds shorter than this (default 1us)
+    #[arg(long)]
+    lock_min_duration: Option<String>,
+
+    /// Capture the stack that made each thread runnable in sched mode (the
+    /// waker's, or the thread's own when preempted)
+    #[arg(long)]
+    sched_stacks: bool,
+
+    /// Record the task that had the CPU before the waiting thread for
+    /// run-queue waits at least this long (default 10ms)
+    #[arg(long)]
+    sched_long_wait: Option<String>,
+
+    /// Drop run-queue waits shorter than this (e.g. "100us"), in the kernel
+    #[arg(long)]
+    sched_min_latency: Option<String>,
+
+    /// Record 1 in N page faults in memory mode; counts are scaled back up by N
+    #[arg(long, default_value_t = 1)]
+    fault_sample: u32,
+
+    /// Probe to trace in probe mode (repeatable): uprobe:BINARY:SYMBOL,
+    /// kprobe:FUNCTION, tracepoint:CATEGORY:NAME or usdt:BINARY:PROVIDER:NAME
+    #[arg(long = "probe")]
+    probes: Vec<String>,
+
+    /// Capture the stack of each probed call or tracepoint hit
+    #[arg(long)]
+    probe_stacks: bool,
+
+    /// Drop probed calls faster than this (e.g. "1ms"), in the kernel
+    #[arg(long)]
+    probe_min_latency: Option<String>,
+
+    /// Time USDT probes as start/end pairs per thread (repeatable):
+    /// PROVIDER:START:END, with both probes given to --probe
+    #[arg(long = "usdt-pair")]
+    usdt_pairs: Vec<String>,
+
+    /// Attach labels set with the aperture-labels library (endpoint, tenant,
+    /// ...) to CPU samples and lock events
+    #[arg(long)]
+    labels: bool,
+
+    /// Binary to search for the label function besides the target's
+    /// mappings (repeatable; e.g. when profiling all processes)
+    #[arg(long = "labels-binary")]
+    label_binaries: Vec<std::path::PathBuf>,
+
+    /// Record the raw events and process mappings to this capture file, to
+    /// rebuild profiles later with `aperture replay`. Only CPU samples, lock
+    /// and syscall events are recorded (cpu, lock and syscall modes, and
+    /// those profilers of all mode)
+    #[arg(long)]
+    record: Option<std::path::PathBuf>,
+
+    /// Probe the target's malloc and free to name heap locks after their
+    /// allocation site (needs --pid)
+    #[arg(long)]
+    lock_alloc_sites: bool,
+
+    /// Command to launch and profile, with its children, until it exits
+    /// (after `--`; replaces --pid and --duration). Its exit code is
+    /// returned.
+    #[arg(last = true)]
+    command: Vec<String>,
 }
 
 #[tokio::main]
@@ -65,6 +191,49 @@ async fn main() -> Result<()> {
     use aperture_agent::ProfileMode;
     use std::str::FromStr;
     let mode = ProfileMode::from_str(&args.mode)?;
+    let symbolize = aperture_agent::SymbolizeMode::from_str(&args.symbolize)?;
+    let syscall_stack_threshold = args
+        .syscall_stack_threshold
+        .as_deref()
+        .map(aperture_shared::utils::parse_duration)
+        .transpose()
+        .context("Failed to parse syscall stack threshold")?;
+    let syscall_filter = aperture_agent::config::SyscallFilter::from_args(
+        args.syscalls.as_deref(),
+        args.exclude_syscalls.as_deref(),
+        args.syscall_min_latency.as_deref(),
+        args.syscall_sample,
+        args.syscall_aggregate,
+    )?;
+    let mut lock_uprobes = aperture_agent::config::LockUprobeConfig::from_args(
+        args.lock_uprobes,
+        args.lock_binaries.clone(),
+        args.lock_min_duration.as_deref(),
+    )?;
+    lock_uprobes.alloc_sites = args.lock_alloc_sites;
+    let sched = aperture_agent::config::SchedConfig::from_args(
+        args.sched_stacks,
+        args.sched_long_wait.as_deref(),
+        args.sched_min_latency.as_deref(),
+    )?;
+    let probes = aperture_agent::config::ProbeConfig::from_args(
+        &args.probes,
+        args.probe_stacks,
+        args.probe_min_latency.as_deref(),
+        &args.usdt_pairs,
+    )?;
+    let labels = aperture_agent::config::LabelConfig {
+        enabled: args.labels,
+        binaries: args.label_binaries.clone(),
+    };
+    let symbol_cache = if args.no_symbol_cache {
+        None
+    } else {
+        aperture_agent::config::SymbolCacheConfig::new(
+            args.symbol_cache_dir.clone(),
+            args.symbol_cache_size,
+        )
+    };
 
     // Low-overhead preset: reduce CPU and network usage (APERTURE_LOW_OVERHEAD=1)
     let low_overhead = std::env::var("APERTURE_LOW_OVERHEAD").as_deref() == Ok("1");
@@ -86,6 +255,20 @@ async fn main() -> Result<()> {
         filter_path: None,
         aggregator_url: args.aggregator,
         push_interval_secs,
+        symbolize,
+        symbol_cache,
+        normalize_rules: args.normalize_rules,
+        syscall_stack_threshold,
+        syscall_filter,
+        lock_uprobes,
+        sched,
+        memory: aperture_agent::config::MemoryConfig {
+            fault_sample_every: args.fault_sample,
+        },
+        probes,
+        labels,
+        record: args.record,
+        follow_children: None,
     };
 
     // Check if running as root (required for eBPF)
@@ -98,7 +281,11 @@ async fn main() -> Result<()> {
     }
 
     // Run profiler
-    aperture_agent::run_profiler(config).await
+    if args.command.is_empty() {
+        return aperture_agent::run_profiler(config).await;
+    }
+    let code = aperture_agent::profile_command(config, args.command).await?;
+    std::process::exit(code);
 }
 
 /// Initialize tracing/logging
@@ -9,7 +9,10 @@ use std::fs::File;
 use std::io::BufWriter;
 use tracing::info;
 
-use aperture_shared::types::profile::{LockProfile, Stack};
+use aperture_shared::types::profile::{
+    KernelLockProfile, LockProfile, MemoryProfile, ProbeProfile, SchedProfile, Stack,
+    SyscallProfile,
+};
 use std::collections::HashMap;
 
 /// Generate a flamegraph from profile data
@@ -28,6 +31,100 @@ pub fn generate_lock_flamegraph(profile: &LockProfile, output_path: &str) -> Res
     generate_flamegraph_from_stacks(&stacks, output_path, "Lock Contention Flamegraph", "ns")
 }
 
+/// Generate a flamegraph of the code holding contended locks, weighted by
+/// hold time, written to `{output_path}.holders.svg`. Returns the path
+/// written, if any releases were seen.
+pub fn generate_lock_holder_flamegraph(
+    profile: &LockProfile,
+    output_path: &str,
+) -> Result<Option<String>> {
+    if profile.holders.is_empty() {
+        return Ok(None);
+    }
+    let path = format!("{}.holders.svg", output_path);
+    let stacks = profile.holder_weighted_stacks();
+    generate_flamegraph_from_stacks(&stacks, &path, "Lock Holder Flamegraph", "ns")?;
+    Ok(Some(path))
+}
+
+/// Generate a flamegraph from kernel lock profile data, weighted by wait time
+pub fn generate_kernel_lock_flamegraph(
+    profile: &KernelLockProfile,
+    output_path: &str,
+) -> Result<()> {
+    let stacks = profile.as_weighted_stacks();
+    generate_flamegraph_from_stacks(
+        &stacks,
+        output_path,
+        "Kernel Lock Contention Flamegraph",
+        "ns",
+    )
+}
+
+/// Generate one flamegraph per syscall with slow-call stacks, weighted by
+/// latency, written to `{output_path}.slow-{syscall}.svg`. Returns the paths
+/// written.
+pub fn generate_syscall_flamegraphs(
+    profile: &SyscallProfile,
+    output_path: &str,
+) -> Result<Vec<String>> {
+    let mut written = Vec::new();
+    let mut syscalls: Vec<_> = profile
+        .syscalls
+        .values()
+        .filter(|s| !s.slow_stacks.is_empty())
+        .collect();
+    syscalls.sort_by(|a, b| a.name.cmp(&b.name));
+    for stats in syscalls {
+        let path = format!("{}.slow-{}.svg", output_path, stats.name);
+        let title = format!("Slow {} Flamegraph", stats.name);
+        generate_flamegraph_from_stacks(&stats.slow_stacks_by_latency(), &path, &title, "ns")?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Generate a flamegraph of the code that made threads runnable, weighted
+/// by the run-queue waits that followed
+pub fn generate_sched_flamegraph(profile: &SchedProfile, output_path: &str) -> Result<()> {
+    let stacks = profile.wakeup_stacks_by_latency();
+    generate_flamegraph_from_stacks(&stacks, output_path, "Wakeup Flamegraph", "ns")
+}
+
+/// Generate a flamegraph of the code raising page faults, weighted by the
+/// faults each sample stands for
+pub fn generate_memory_flamegraph(profile: &MemoryProfile, output_path: &str) -> Result<()> {
+    generate_flamegraph_from_stacks(
+        &profile.faults.samples,
+        output_path,
+        "Page Fault Flamegraph",
+        "faults",
+    )
+}
+
+/// Generate one flamegraph per probe with stacks, weighted by calls,
+/// written to `{output_path}.{probe}.svg` with the probe spec made
+/// file-name safe (`kprobe_vfs_read`). Returns the paths written.
+pub fn generate_probe_flamegraphs(
+    profile: &ProbeProfile,
+    output_path: &str,
+) -> Result<Vec<String>> {
+    let mut written = Vec::new();
+    for stats in profile.probes.values().filter(|p| !p.stacks.is_empty()) {
+        let name: String = stats
+            .probe
+            .trim_start_matches('/')
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = format!("{}.{}.svg", output_path, name);
+        let title = format!("{} Flamegraph", stats.probe);
+        generate_flamegraph_from_stacks(&stats.stacks_by_count(), &path, &title, "calls")?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
 fn generate_flamegraph_from_stacks(
     stacks: &HashMap<Stack, u64>,
     output_path: &str,
@@ -44,12 +141,7 @@ fn generate_flamegraph_from_stacks(
 
         // Reverse frames (flamegraphs show bottom-up)
         for frame in stack.frames.iter().rev() {
-            let name = if let Some(func) = &frame.function {
-                func.clone()
-            } else {
-                format!("0x{:x}", frame.ip)
-            };
-            frame_names.push(name);
+            frame_names.push(frame.folded_name(false));
         }
 
         if !frame_names.is_empty() {
@@ -111,6 +203,7 @@ mod tests {
                     file: None,
                     line: None,
                     module: None,
+                    inline: false,
                 },
                 Frame {
                     ip: 0x400100,
@@ -118,6 +211,7 @@ mod tests {
                     file: None,
                     line: None,
                     module: None,
+                    inline: false,
                 },
             ],
         };
@@ -152,6 +246,7 @@ mod tests {
                 file: None,
                 line: None,
                 module: None,
+                inline: false,
             }],
         };
         profile.add_sample(stack);
@@ -163,4 +258,27 @@ mod tests {
         let svg = std::fs::read_to_string(&output_path).unwrap();
         assert!(svg.contains("0xdeadbeef"));
     }
+
+    #[test]
+    fn test_syscall_flamegraphs_per_slow_syscall() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("syscalls.txt");
+        let output_path = output_path.to_str().unwrap();
+
+        let mut profile = SyscallProfile::new(0);
+        profile.add_syscall(74, "fsync", 20_000_000, 0);
+        profile.add_slow_stack(
+            74,
+            "fsync",
+            Stack::from_ips(&[0x401000, 0x400100]),
+            20_000_000,
+        );
+        // No stacks captured: no flamegraph
+        profile.add_syscall(0, "read", 500, 64);
+
+        let written = generate_syscall_flamegraphs(&profile, output_path).unwrap();
+        assert_eq!(written, vec![format!("{}.slow-fsync.svg", output_path)]);
+        let svg = std::fs::read_to_string(&written[0]).unwrap();
+        assert!(svg.contains("Slow fsync Flamegraph"));
+    }
 }
@@ -3,7 +3,10 @@
 //! Generates text-based histograms for latency analysis
 
 use anyhow::{Context, Result};
-use aperture_shared::types::profile::SyscallProfile;
+use aperture_shared::types::profile::{
+    BlockIoProfile, MemoryProfile, ProbeProfile, ReclaimStats, RunQueueStats, SchedProfile,
+    SyscallProfile, TcpProfile,
+};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use tracing::info;
@@ -23,6 +26,13 @@ pub fn generate_syscall_histogram(profile: &SyscallProfile, output_path: &str) -
         profile.end_time.saturating_sub(profile.start_time) as f64 / 1_000_000_000.0;
     writeln!(writer, "Total Duration: {:.3} s", duration_secs)?;
     writeln!(writer, "Total Events:   {}", profile.total_events)?;
+    if profile.sample_every > 1 {
+        writeln!(
+            writer,
+            "Sampled 1 in {} calls; counts are estimates",
+            profile.sample_every
+        )?;
+    }
 
     if profile.total_events == 0 {
         writeln!(writer, "\nNo syscall events collected.")?;
@@ -62,6 +72,718 @@ pub fn generate_syscall_histogram(profile: &SyscallProfile, output_path: &str) -
         )?;
     }
 
+    write_io_targets(&mut writer, profile)?;
+    write_slow_stacks(&mut writer, profile)?;
+
+    info!("Histogram generated successfully: {}", output_path);
+    Ok(())
+}
+
+/// Files and sockets to list in the I/O section
+const MAX_IO_TARGETS: usize = 30;
+
+/// I/O view: syscalls with a file/socket argument, grouped by target and
+/// sorted by total time spent
+fn write_io_targets(writer: &mut impl Write, profile: &SyscallProfile) -> Result<()> {
+    if profile.io.is_empty() {
+        return Ok(());
+    }
+    let mut targets: Vec<_> = profile.io.values().collect();
+    targets.sort_by(|a, b| b.total_duration_ns.cmp(&a.total_duration_ns));
+
+    writeln!(writer, "\nI/O by File/Socket")?;
+    writeln!(writer, "==================")?;
+    writeln!(
+        writer,
+        "{:>10} {:>14} {:>12} {:>12} {:>14} {:>10}  Target (syscalls)",
+        "Count", "Total(ns)", "P99(ns)", "Max(ns)", "Bytes", "Errors"
+    )?;
+    writeln!(writer, "{:-<120}", "")?;
+
+    for t in targets.iter().take(MAX_IO_TARGETS) {
+        let p99 = estimate_percentile(&t.latency_histogram, t.count, 0.99);
+        let syscalls = t
+            .syscalls
+            .iter()
+            .map(|(name, count)| format!("{}:{}", name, count))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            writer,
+            "{:>10} {:>14} {:>12} {:>12} {:>14} {:>10}  {} ({})",
+            t.count,
+            t.total_duration_ns,
+            p99,
+            t.max_duration_ns,
+            t.bytes,
+            t.error_count,
+            t.target,
+            syscalls
+        )?;
+    }
+    if targets.len() > MAX_IO_TARGETS {
+        writeln!(
+            writer,
+            "... {} more (see JSON output)",
+            targets.len() - MAX_IO_TARGETS
+        )?;
+    }
+    Ok(())
+}
+
+/// Per syscall: how many calls crossed the stack threshold and how many
+/// distinct stacks they came from
+fn write_slow_stacks(writer: &mut impl Write, profile: &SyscallProfile) -> Result<()> {
+    let mut syscalls: Vec<_> = profile
+        .syscalls
+        .values()
+        .filter(|s| !s.slow_stacks.is_empty())
+        .collect();
+    if syscalls.is_empty() {
+        return Ok(());
+    }
+    syscalls.sort_by_key(|s| {
+        std::cmp::Reverse(
+            s.slow_stacks
+                .iter()
+                .map(|st| st.total_duration_ns)
+                .sum::<u64>(),
+        )
+    });
+
+    writeln!(writer, "\nSlow Calls with Stacks")?;
+    writeln!(writer, "======================")?;
+    writeln!(
+        writer,
+        "{:<20} {:>10} {:>14} {:>8}",
+        "Syscall", "Calls", "Total(ns)", "Stacks"
+    )?;
+    writeln!(writer, "{:-<55}", "")?;
+    for s in syscalls {
+        let calls: u64 = s.slow_stacks.iter().map(|st| st.count).sum();
+        let total: u64 = s.slow_stacks.iter().map(|st| st.total_duration_ns).sum();
+        writeln!(
+            writer,
+            "{:<20} {:>10} {:>14} {:>8}",
+            s.name,
+            calls,
+            total,
+            s.slow_stacks.len()
+        )?;
+    }
+    Ok(())
+}
+
+/// Generate a text report of block I/O latency per device and operation
+pub fn generate_block_io_histogram(profile: &BlockIoProfile, output_path: &str) -> Result<()> {
+    info!("Generating block I/O histogram: {}", output_path);
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create histogram file: {}", output_path))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "Block I/O Latency Profile")?;
+    writeln!(writer, "=========================")?;
+
+    let duration_secs =
+        profile.end_time.saturating_sub(profile.start_time) as f64 / 1_000_000_000.0;
+    writeln!(writer, "Total Duration: {:.3} s", duration_secs)?;
+    writeln!(writer, "Total Requests: {}", profile.total_events)?;
+
+    if profile.total_events == 0 {
+        writeln!(writer, "\nNo block I/O requests collected.")?;
+        return Ok(());
+    }
+
+    writeln!(
+        writer,
+        "\n{:<16} {:<8} {:>10} {:>12} {:>12} {:>12} {:>12} {:>10} {:>7} {:>7} {:>8}",
+        "Device",
+        "Op",
+        "Count",
+        "Avg(us)",
+        "P50(us)",
+        "P99(us)",
+        "Max(us)",
+        "AvgSize",
+        "AvgQD",
+        "MaxQD",
+        "Errors"
+    )?;
+    writeln!(writer, "{:-<125}", "")?;
+
+    for s in profile.stats() {
+        let p50 = estimate_percentile(&s.latency_histogram, s.count, 0.50);
+        let p99 = estimate_percentile(&s.latency_histogram, s.count, 0.99);
+        writeln!(
+            writer,
+            "{:<16} {:<8} {:>10} {:>12} {:>12} {:>12} {:>12} {:>10} {:>7.1} {:>7} {:>8}",
+            s.device,
+            s.op.as_str(),
+            s.count,
+            s.avg_latency_ns() / 1000,
+            p50 / 1000,
+            p99 / 1000,
+            s.max_latency_ns / 1000,
+            s.bytes.checked_div(s.count).unwrap_or(0),
+            s.avg_queue_depth(),
+            s.max_queue_depth,
+            s.error_count
+        )?;
+    }
+
+    for s in profile.stats() {
+        let title = format!("{} {} latency (us)", s.device, s.op.as_str());
+        write_latency_distribution(&mut writer, &title, &s.latency_histogram)?;
+    }
+    write_block_io_processes(&mut writer, profile)?;
+
+    info!("Histogram generated successfully: {}", output_path);
+    Ok(())
+}
+
+/// Width of the longest bar in latency distributions
+const DISTRIBUTION_WIDTH: u64 = 40;
+
+/// Latency distribution in microseconds, one line per non-empty power-of-2
+/// bucket
+fn write_latency_distribution(
+    writer: &mut impl Write,
+    title: &str,
+    histogram: &[u64],
+) -> Result<()> {
+    let Some(first) = histogram.iter().position(|&n| n > 0) else {
+        return Ok(());
+    };
+    let last = histogram.iter().rposition(|&n| n > 0).unwrap_or(first);
+    let max = histogram.iter().copied().max().unwrap_or(1);
+
+    writeln!(writer, "\n{}", title)?;
+    for (i, &count) in histogram[first..=last].iter().enumerate() {
+        let bucket = first + i;
+        // Bucket i covers 2^i to 2^(i+1)-1 ns
+        let low = (1u64 << bucket) / 1000;
+        let high = ((1u64 << (bucket + 1)) - 1) / 1000;
+        let bar = "*".repeat((count * DISTRIBUTION_WIDTH / max) as usize);
+        writeln!(
+            writer,
+            "{:>10} -> {:<10} : {:<10} |{:<40}|",
+            low, high, count, bar
+        )?;
+    }
+    Ok(())
+}
+
+/// Processes to list in the per-process section
+const MAX_BLOCK_IO_PROCESSES: usize = 20;
+
+/// Requests by issuing process, sorted by total latency
+fn write_block_io_processes(writer: &mut impl Write, profile: &BlockIoProfile) -> Result<()> {
+    let mut processes: Vec<_> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.total_latency_ns));
+
+    writeln!(writer, "\nBlock I/O by Process")?;
+    writeln!(writer, "====================")?;
+    writeln!(
+        writer,
+        "{:>8} {:<16} {:>10} {:>14} {:>14}  Ops",
+        "PID", "Comm", "Count", "Bytes", "Total(us)"
+    )?;
+    writeln!(writer, "{:-<90}", "")?;
+    for p in processes.iter().take(MAX_BLOCK_IO_PROCESSES) {
+        let ops = p
+            .ops
+            .iter()
+            .map(|(op, count)| format!("{}:{}", op, count))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            writer,
+            "{:>8} {:<16} {:>10} {:>14} {:>14}  {}",
+            p.pid,
+            p.comm,
+            p.count,
+            p.bytes,
+            p.total_latency_ns / 1000,
+            ops
+        )?;
+    }
+    if processes.len() > MAX_BLOCK_IO_PROCESSES {
+        writeln!(
+            writer,
+            "... {} more (see JSON output)",
+            processes.len() - MAX_BLOCK_IO_PROCESSES
+        )?;
+    }
+    Ok(())
+}
+
+/// Generate a text report of run-queue latency per process and cgroup
+pub fn generate_sched_histogram(profile: &SchedProfile, output_path: &str) -> Result<()> {
+    info!("Generating run-queue histogram: {}", output_path);
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create histogram file: {}", output_path))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "Run-Queue Latency Profile")?;
+    writeln!(writer, "=========================")?;
+
+    let duration_secs =
+        profile.end_time.saturating_sub(profile.start_time) as f64 / 1_000_000_000.0;
+    writeln!(writer, "Total Duration: {:.3} s", duration_secs)?;
+    writeln!(writer, "Total Waits:    {}", profile.total_events)?;
+
+    if profile.total_events == 0 {
+        writeln!(writer, "\nNo run-queue waits collected.")?;
+        return Ok(());
+    }
+
+    let mut processes: Vec<_> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.runq.total_delay_ns));
+
+    writeln!(
+        writer,
+        "\n{:>8} {:<16} {:>10} {:>12} {:>12} {:>12} {:>12} {:>9}",
+        "PID", "Comm", "Waits", "Avg(us)", "P50(us)", "P99(us)", "Max(us)", "Preempted"
+    )?;
+    writeln!(writer, "{:-<100}", "")?;
+    let total = profile.total();
+    write_runq_row(&mut writer, "", "total", &total)?;
+    for p in processes.iter().take(MAX_SCHED_PROCESSES) {
+        write_runq_row(&mut writer, &p.pid.to_string(), &p.comm, &p.runq)?;
+    }
+    if processes.len() > MAX_SCHED_PROCESSES {
+        writeln!(
+            writer,
+            "... {} more (see JSON output)",
+            processes.len() - MAX_SCHED_PROCESSES
+        )?;
+    }
+
+    write_latency_distribution(
+        &mut writer,
+        "All threads run-queue latency (us)",
+        &total.latency_histogram,
+    )?;
+    for p in processes.iter().take(MAX_SCHED_PROCESSES) {
+        let title = format!("{} ({}) run-queue latency (us)", p.comm, p.pid);
+        write_latency_distribution(&mut writer, &title, &p.runq.latency_histogram)?;
+    }
+
+    write_preemptors(&mut writer, profile)?;
+    write_cgroups(&mut writer, profile)?;
+
+    info!("Histogram generated successfully: {}", output_path);
+    Ok(())
+}
+
+/// Processes to list in the run-queue report
+const MAX_SCHED_PROCESSES: usize = 20;
+
+fn write_runq_row(
+    writer: &mut impl Write,
+    pid: &str,
+    comm: &str,
+    stats: &RunQueueStats,
+) -> Result<()> {
+    let p50 = estimate_percentile(&stats.latency_histogram, stats.count, 0.50);
+    let p99 = estimate_percentile(&stats.latency_histogram, stats.count, 0.99);
+    writeln!(
+        writer,
+        "{:>8} {:<16} {:>10} {:>12} {:>12} {:>12} {:>12} {:>9}",
+        pid,
+        comm,
+        stats.count,
+        stats.avg_delay_ns() / 1000,
+        p50 / 1000,
+        p99 / 1000,
+        stats.max_delay_ns / 1000,
+        stats.preempted_count
+    )?;
+    Ok(())
+}
+
+/// Tasks that were on the CPU when long waits ended, per waiting process
+fn write_preemptors(writer: &mut impl Write, profile: &SchedProfile) -> Result<()> {
+    let mut rows: Vec<_> = profile
+        .processes
+        .values()
+        .flat_map(|p| p.preemptors.iter().map(move |(name, s)| (p, name, s)))
+        .collect();
+    if rows.is_empty() {
+        return Ok(());
+    }
+    rows.sort_by_key(|(_, _, s)| std::cmp::Reverse(s.total_delay_ns));
+
+    writeln!(writer, "\nLong Waits by Preempting Task")?;
+    writeln!(writer, "=============================")?;
+    writeln!(
+        writer,
+        "{:>8} {:<16} {:<16} {:>10} {:>14} {:>12}",
+        "PID", "Comm", "Preemptor", "Waits", "Total(us)", "Max(us)"
+    )?;
+    writeln!(writer, "{:-<80}", "")?;
+    for (p, name, s) in rows.iter().take(MAX_SCHED_PROCESSES) {
+        writeln!(
+            writer,
+            "{:>8} {:<16} {:<16} {:>10} {:>14} {:>12}",
+            p.pid,
+            p.comm,
+            name,
+            s.count,
+            s.total_delay_ns / 1000,
+            s.max_delay_ns / 1000
+        )?;
+    }
+    Ok(())
+}
+
+/// Run-queue latency per cgroup, sorted by total wait
+fn write_cgroups(writer: &mut impl Write, profile: &SchedProfile) -> Result<()> {
+    if profile.cgroups.is_empty() {
+        return Ok(());
+    }
+    let mut cgroups: Vec<_> = profile.cgroups.iter().collect();
+    cgroups.sort_by_key(|(_, s)| std::cmp::Reverse(s.total_delay_ns));
+
+    writeln!(writer, "\nRun-Queue Latency by cgroup")?;
+    writeln!(writer, "===========================")?;
+    writeln!(
+        writer,
+        "{:>10} {:>14} {:>12} {:>12} {:>12}  cgroup",
+        "Waits", "Total(us)", "Avg(us)", "P99(us)", "Max(us)"
+    )?;
+    writeln!(writer, "{:-<100}", "")?;
+    for (path, s) in cgroups {
+        let p99 = estimate_percentile(&s.latency_histogram, s.count, 0.99);
+        writeln!(
+            writer,
+            "{:>10} {:>14} {:>12} {:>12} {:>12}  {}",
+            s.count,
+            s.total_delay_ns / 1000,
+            s.avg_delay_ns() / 1000,
+            p99 / 1000,
+            s.max_delay_ns / 1000,
+            path
+        )?;
+    }
+    Ok(())
+}
+
+/// Generate a text table of TCP connections per process and remote endpoint
+pub fn generate_tcp_histogram(profile: &TcpProfile, output_path: &str) -> Result<()> {
+    info!("Generating TCP report: {}", output_path);
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create histogram file: {}", output_path))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "TCP Connection Profile")?;
+    writeln!(writer, "======================")?;
+
+    let duration_secs =
+        profile.end_time.saturating_sub(profile.start_time) as f64 / 1_000_000_000.0;
+    writeln!(writer, "Total Duration: {:.3} s", duration_secs)?;
+    writeln!(writer, "Total Events:   {}", profile.total_events)?;
+
+    if profile.total_events == 0 {
+        writeln!(writer, "\nNo TCP events collected.")?;
+        return Ok(());
+    }
+
+    let mut rows: Vec<_> = profile.endpoints().collect();
+    rows.sort_by_key(|(_, e)| {
+        (
+            std::cmp::Reverse(e.bytes_sent + e.bytes_received),
+            std::cmp::Reverse(e.connects + e.connect_failures),
+        )
+    });
+
+    writeln!(
+        writer,
+        "\n{:>8} {:<16} {:<32} {:>7} {:>6} {:>12} {:>12} {:>8} {:>6} {:>6} {:>12} {:>12} {:>10}",
+        "PID",
+        "Comm",
+        "Endpoint",
+        "Conns",
+        "Fail",
+        "Conn(us)",
+        "ConnP99(us)",
+        "Retrans",
+        "RstTx",
+        "RstRx",
+        "Sent",
+        "Received",
+        "SRTT(us)"
+    )?;
+    writeln!(writer, "{:-<155}", "")?;
+    for (p, e) in rows.iter().take(MAX_TCP_ENDPOINTS) {
+        let attempts = e.connects + e.connect_failures;
+        let p99 = estimate_percentile(&e.connect_histogram, attempts, 0.99);
+        writeln!(
+            writer,
+            "{:>8} {:<16} {:<32} {:>7} {:>6} {:>12} {:>12} {:>8} {:>6} {:>6} {:>12} {:>12} {:>10}",
+            p.pid,
+            p.comm,
+            e.endpoint,
+            e.connects,
+            e.connect_failures,
+            e.avg_connect_ns() / 1000,
+            if attempts > 0 { p99 / 1000 } else { 0 },
+            e.retransmits,
+            e.resets_sent,
+            e.resets_received,
+            e.bytes_sent,
+            e.bytes_received,
+            e.avg_srtt_us()
+        )?;
+    }
+    if rows.len() > MAX_TCP_ENDPOINTS {
+        writeln!(
+            writer,
+            "... {} more (see JSON output)",
+            rows.len() - MAX_TCP_ENDPOINTS
+        )?;
+    }
+
+    let mut connecting: Vec<_> = rows
+        .iter()
+        .filter(|(_, e)| e.connects + e.connect_failures > 0)
+        .collect();
+    connecting.sort_by_key(|(_, e)| std::cmp::Reverse(e.total_connect_ns));
+    for (p, e) in connecting.iter().take(MAX_TCP_ENDPOINTS) {
+        let title = format!(
+            "{} ({}) -> {} connect latency (us)",
+            p.comm, p.pid, e.endpoint
+        );
+        write_latency_distribution(&mut writer, &title, &e.connect_histogram)?;
+    }
+
+    info!("Histogram generated successfully: {}", output_path);
+    Ok(())
+}
+
+/// (Process, endpoint) rows to list in the TCP report
+const MAX_TCP_ENDPOINTS: usize = 30;
+
+/// Generate a text report of page faults and reclaim stalls per process
+pub fn generate_memory_histogram(profile: &MemoryProfile, output_path: &str) -> Result<()> {
+    info!("Generating reclaim stall histogram: {}", output_path);
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create histogram file: {}", output_path))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "Page Fault and Reclaim Profile")?;
+    writeln!(writer, "==============================")?;
+
+    let duration_secs =
+        profile.end_time.saturating_sub(profile.start_time) as f64 / 1_000_000_000.0;
+    let total = profile.total_reclaim();
+    writeln!(writer, "Total Duration:  {:.3} s", duration_secs)?;
+    writeln!(writer, "Page Faults:     ~{}", profile.total_faults())?;
+    writeln!(writer, "Reclaim Stalls:  {}", total.count)?;
+
+    if profile.total_events == 0 {
+        writeln!(writer, "\nNo page faults or reclaim stalls collected.")?;
+        return Ok(());
+    }
+
+    // Stalled processes first, by time lost, then by faults
+    let mut processes: Vec<_> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse((p.reclaim.total_stall_ns, p.faults)));
+
+    writeln!(
+        writer,
+        "\n{:>8} {:<16} {:>12} {:>12} {:>8} {:>12} {:>12} {:>12} {:>10}",
+        "PID", "Comm", "Faults", "KernFaults", "Stalls", "Avg(us)", "P99(us)", "Max(us)", "Pages"
+    )?;
+    writeln!(writer, "{:-<110}", "")?;
+    for p in processes.iter().take(MAX_MEMORY_PROCESSES) {
+        write_reclaim_row(
+            &mut writer,
+            p.pid,
+            &p.comm,
+            p.faults,
+            p.kernel_faults,
+            &p.reclaim,
+        )?;
+    }
+    if processes.len() > MAX_MEMORY_PROCESSES {
+        writeln!(
+            writer,
+            "... {} more (see JSON output)",
+            processes.len() - MAX_MEMORY_PROCESSES
+        )?;
+    }
+
+    write_latency_distribution(
+        &mut writer,
+        "All processes reclaim stall (us)",
+        &total.latency_histogram,
+    )?;
+    for p in processes.iter().take(MAX_MEMORY_PROCESSES) {
+        let title = format!("{} ({}) reclaim stall (us)", p.comm, p.pid);
+        write_latency_distribution(&mut writer, &title, &p.reclaim.latency_histogram)?;
+    }
+
+    info!("Histogram generated successfully: {}", output_path);
+    Ok(())
+}
+
+/// Processes to list in the memory report
+const MAX_MEMORY_PROCESSES: usize = 20;
+
+fn write_reclaim_row(
+    writer: &mut impl Write,
+    pid: i32,
+    comm: &str,
+    faults: u64,
+    kernel_faults: u64,
+    stats: &ReclaimStats,
+) -> Result<()> {
+    let p99 = estimate_percentile(&stats.latency_histogram, stats.count, 0.99);
+    writeln!(
+        writer,
+        "{:>8} {:<16} {:>12} {:>12} {:>8} {:>12} {:>12} {:>12} {:>10}",
+        pid,
+        comm,
+        faults,
+        kernel_faults,
+        stats.count,
+        stats.avg_stall_ns() / 1000,
+        p99 / 1000,
+        stats.max_stall_ns / 1000,
+        stats.nr_reclaimed
+    )?;
+    Ok(())
+}
+
+/// Generate a text report of calls and latency per user-defined probe
+pub fn generate_probe_histogram(profile: &ProbeProfile, output_path: &str) -> Result<()> {
+    info!("Generating probe histogram: {}", output_path);
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create histogram file: {}", output_path))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "Probe Profile")?;
+    writeln!(writer, "=============")?;
+
+    let duration_secs =
+        profile.end_time.saturating_sub(profile.start_time) as f64 / 1_000_000_000.0;
+    writeln!(writer, "Total Duration: {:.3} s", duration_secs)?;
+    writeln!(writer, "Total Events:   {}", profile.total_events)?;
+
+    if profile.total_events == 0 {
+        writeln!(writer, "\nNo probe calls collected.")?;
+        return Ok(());
+    }
+
+    let mut probes: Vec<_> = profile.probes.values().collect();
+    probes.sort_by_key(|p| std::cmp::Reverse((p.total_duration_ns, p.count)));
+
+    writeln!(
+        writer,
+        "\n{:>10} {:>10} {:>12} {:>12} {:>12} {:>12}  Probe",
+        "Count", "Per sec", "Avg(us)", "P50(us)", "P99(us)", "Max(us)"
+    )?;
+    writeln!(writer, "{:-<110}", "")?;
+    for p in &probes {
+        let rate = if duration_secs > 0.0 {
+            p.count as f64 / duration_secs
+        } else {
+            0.0
+        };
+        // Tracepoint hits have no latency
+        if p.total_duration_ns == 0 && p.latency_histogram.iter().all(|&n| n == 0) {
+            writeln!(
+                writer,
+                "{:>10} {:>10.1} {:>12} {:>12} {:>12} {:>12}  {}",
+                p.count, rate, "-", "-", "-", "-", p.probe
+            )?;
+            continue;
+        }
+        let p50 = estimate_percentile(&p.latency_histogram, p.count, 0.50);
+        let p99 = estimate_percentile(&p.latency_histogram, p.count, 0.99);
+        writeln!(
+            writer,
+            "{:>10} {:>10.1} {:>12} {:>12} {:>12} {:>12}  {}",
+            p.count,
+            rate,
+            p.avg_duration_ns() / 1000,
+            p50 / 1000,
+            p99 / 1000,
+            p.max_duration_ns / 1000,
+            p.probe
+        )?;
+    }
+
+    if !profile.usdt.is_empty() {
+        writeln!(writer, "\nUSDT Probes")?;
+        writeln!(writer, "{:-<110}", "")?;
+        writeln!(
+            writer,
+            "{:>10} {:>10}  {:<40} Args (min..max)",
+            "Count", "Per sec", "Probe"
+        )?;
+        for u in profile.usdt.values() {
+            let rate = if duration_secs > 0.0 {
+                u.count as f64 / duration_secs
+            } else {
+                0.0
+            };
+            let args: Vec<String> = u
+                .args
+                .iter()
+                .map(|a| format!("{}..{}", a.min, a.max))
+                .collect();
+            writeln!(
+                writer,
+                "{:>10} {:>10.1}  {:<40} {}",
+                u.count,
+                rate,
+                u.probe,
+                args.join(" ")
+            )?;
+        }
+    }
+
+    if !profile.spans.is_empty() {
+        writeln!(writer, "\nUSDT Spans")?;
+        writeln!(writer, "{:-<110}", "")?;
+        writeln!(
+            writer,
+            "{:>10} {:>12} {:>12} {:>12} {:>12} {:>12}  Span",
+            "Count", "Avg(us)", "P50(us)", "P99(us)", "Max(us)", "CPU samples"
+        )?;
+        for s in profile.spans.values() {
+            let p50 = estimate_percentile(&s.latency_histogram, s.count, 0.50);
+            let p99 = estimate_percentile(&s.latency_histogram, s.count, 0.99);
+            writeln!(
+                writer,
+                "{:>10} {:>12} {:>12} {:>12} {:>12} {:>12}  {}",
+                s.count,
+                s.avg_duration_ns() / 1000,
+                p50 / 1000,
+                p99 / 1000,
+                s.max_duration_ns / 1000,
+                s.cpu_samples,
+                s.span
+            )?;
+        }
+    }
+
+    for p in &probes {
+        let title = format!("{} latency (us)", p.probe);
+        write_latency_distribution(&mut writer, &title, &p.latency_histogram)?;
+    }
+    for s in profile.spans.values() {
+        let title = format!("{} span latency (us)", s.span);
+        write_latency_distribution(&mut writer, &title, &s.latency_histogram)?;
+    }
+
     info!("Histogram generated successfully: {}", output_path);
     Ok(())
 }
@@ -116,4 +838,212 @@ mod tests {
         // P100 should be in bucket 2 (upper bound 8)
         assert_eq!(estimate_percentile(&histogram, 10, 1.0), 8);
     }
+
+    #[test]
+    fn test_block_io_histogram_report() {
+        use aperture_shared::types::events::{BlockIoEvent, BlockIoOp};
+
+        let mut profile = BlockIoProfile::new(0);
+        for latency_ns in [100_000, 120_000, 4_000_000] {
+            profile.add_event(&BlockIoEvent {
+                timestamp: 0,
+                pid: 7,
+                tid: 7,
+                comm: "fio".to_string(),
+                dev: 259 << 20,
+                device: Some("nvme0n1".to_string()),
+                op: BlockIoOp::Read,
+                sector: 0,
+                bytes: 4096,
+                latency_ns,
+                queue_depth: 4,
+                error: 0,
+            });
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("block-io.txt");
+        generate_block_io_histogram(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let report = std::fs::read_to_string(output_path).unwrap();
+        assert!(report.contains("Total Requests: 3"));
+        assert!(report.contains("nvme0n1 read latency (us)"));
+        // 100us and 120us share the 65..131us bucket
+        assert!(report.contains("65 -> 131        : 2 "));
+        assert!(report.contains("fio"));
+    }
+
+    #[test]
+    fn test_sched_histogram_report() {
+        use aperture_shared::types::events::SchedEvent;
+
+        let mut profile = SchedProfile::new(0);
+        for (delay_ns, preemptor) in [(50_000, None), (60_000, None), (30_000_000, Some("ffmpeg"))]
+        {
+            profile.add_event(&SchedEvent {
+                timestamp: 0,
+                pid: 7,
+                tid: 8,
+                comm: "nginx".to_string(),
+                cpu_id: 0,
+                delay_ns,
+                preempted: preemptor.is_some(),
+                waker_pid: 1,
+                waker_tid: 1,
+                cgroup: Some("/system.slice/nginx.service".to_string()),
+                preemptor_tid: preemptor.map(|_| 99),
+                preemptor_comm: preemptor.map(str::to_string),
+                stack_trace: vec![],
+                stack_symbols: vec![],
+                stack_refs: vec![],
+            });
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("runq.txt");
+        generate_sched_histogram(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let report = std::fs::read_to_string(output_path).unwrap();
+        assert!(report.contains("Total Waits:    3"));
+        assert!(report.contains("nginx (7) run-queue latency (us)"));
+        // 50us and 60us share the 32..65us bucket
+        assert!(report.contains("32 -> 65         : 2 "));
+        assert!(report.contains("ffmpeg"));
+        assert!(report.contains("/system.slice/nginx.service"));
+    }
+
+    #[test]
+    fn test_tcp_histogram_report() {
+        use aperture_shared::types::events::{TcpEvent, TcpEventKind};
+
+        let mut profile = TcpProfile::new(0);
+        for (kind, duration_ns) in [
+            (TcpEventKind::Connect, 40_000),
+            (TcpEventKind::Connect, 50_000),
+            (TcpEventKind::Retransmit, 0),
+            (TcpEventKind::Close, 2_000_000_000),
+        ] {
+            profile.add_event(&TcpEvent {
+                timestamp: 0,
+                pid: 11,
+                tid: 11,
+                comm: "api".to_string(),
+                kind,
+                remote_addr: "10.0.0.5".parse().unwrap(),
+                remote_port: 6379,
+                local_port: 41000,
+                passive: false,
+                duration_ns,
+                bytes_sent: 512,
+                bytes_received: 2048,
+                retransmits: 1,
+                srtt_us: 180,
+            });
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("tcp.txt");
+        generate_tcp_histogram(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let report = std::fs::read_to_string(output_path).unwrap();
+        assert!(report.contains("Total Events:   4"));
+        assert!(report.contains("10.0.0.5:6379"));
+        assert!(report.contains("api (11) -> 10.0.0.5:6379 connect latency (us)"));
+        // 40us and 50us share the 32..65us bucket
+        assert!(report.contains("32 -> 65         : 2 "));
+    }
+
+    #[test]
+    fn test_memory_histogram_report() {
+        use aperture_shared::types::events::{PageFaultEvent, ReclaimEvent};
+
+        let mut profile = MemoryProfile::new(0);
+        profile.add_fault(&PageFaultEvent {
+            timestamp: 0,
+            pid: 7,
+            tid: 7,
+            comm: "postgres".to_string(),
+            address: 0x7f00_0000_0000,
+            ip: 0x401000,
+            error_code: 6,
+            kernel: false,
+            weight: 10,
+            stack_trace: vec![0x401000],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        });
+        for stall_ns in [50_000, 60_000, 8_000_000] {
+            profile.add_reclaim(&ReclaimEvent {
+                timestamp: 0,
+                pid: 7,
+                tid: 8,
+                comm: "postgres".to_string(),
+                stall_ns,
+                nr_reclaimed: 32,
+                order: 0,
+                memcg: false,
+            });
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("reclaim.txt");
+        generate_memory_histogram(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let report = std::fs::read_to_string(output_path).unwrap();
+        assert!(report.contains("Page Faults:     ~10"));
+        assert!(report.contains("Reclaim Stalls:  3"));
+        assert!(report.contains("postgres (7) reclaim stall (us)"));
+        // 50us and 60us share the 32..65us bucket
+        assert!(report.contains("32 -> 65         : 2 "));
+    }
+
+    #[test]
+    fn test_probe_histogram_report() {
+        use aperture_shared::types::events::{ProbeEvent, UsdtEvent, UsdtSpan};
+
+        let event = |probe: &str, duration_ns: Option<u64>| ProbeEvent {
+            timestamp: 0,
+            pid: 42,
+            tid: 42,
+            comm: "app".to_string(),
+            probe: probe.to_string(),
+            duration_ns,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut profile = ProbeProfile::new(0);
+        profile.end_time = 2_000_000_000;
+        for duration_ns in [40_000, 50_000, 3_000_000] {
+            profile.add_event(&event("kprobe:vfs_read", Some(duration_ns)));
+        }
+        profile.add_event(&event("tracepoint:net:netif_rx", None));
+        profile.add_usdt(&UsdtEvent {
+            timestamp: 300_000,
+            pid: 42,
+            tid: 42,
+            comm: "app".to_string(),
+            probe: "app:req__done".to_string(),
+            args: vec![-3, 200],
+            span: Some(UsdtSpan {
+                name: "app:req__start..req__done".to_string(),
+                start: 100_000,
+            }),
+        });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("probes.txt");
+        generate_probe_histogram(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let report = std::fs::read_to_string(output_path).unwrap();
+        assert!(report.contains("Total Events:   5"));
+        assert!(report.contains("app:req__done                            -3..-3 200..200"));
+        assert!(report.contains("200            0  app:req__start..req__done"));
+        assert!(report.contains("1.5         1030"));
+        assert!(report.contains("0.5            -"));
+        assert!(report.contains("kprobe:vfs_read latency (us)"));
+        assert!(!report.contains("tracepoint:net:netif_rx latency"));
+        // 40us and 50us share the 32..65us bucket
+        assert!(report.contains("32 -> 65         : 2 "));
+    }
 }
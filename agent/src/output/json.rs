@@ -3,8 +3,12 @@
 //! Exports profile data in JSON format for further analysis
 
 use anyhow::{Context, Result};
-use aperture_shared::types::profile::Profile;
+use aperture_shared::types::profile::{
+    BlockIoProcessStats, BlockIoStats, IoTargetStats, MemoryProcessStats, ProbeStats, Profile,
+    RunQueueStats, SchedProcessStats, SlowStack, TcpProcessStats, UsdtProbeStats, UsdtSpanStats,
+};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufWriter;
 use tracing::info;
@@ -16,6 +20,8 @@ struct JsonProfile<'a> {
     end_time: u64,
     total_samples: u64,
     sample_period_ns: u64,
+    /// Normalization rule -> frames it changed
+    normalization: &'a BTreeMap<String, u64>,
     samples: Vec<JsonSample<'a>>,
 }
 
@@ -44,6 +50,7 @@ pub fn generate_json(profile: &Profile, output_path: &str) -> Result<()> {
         end_time: profile.end_time,
         total_samples: profile.total_samples,
         sample_period_ns: profile.sample_period_ns,
+        normalization: &profile.normalization,
         samples,
     };
 
@@ -66,12 +73,48 @@ struct JsonLockProfile<'a> {
     start_time: u64,
     end_time: u64,
     total_events: u64,
+    normalization: &'a BTreeMap<String, u64>,
     contentions: Vec<JsonLockContention<'a>>,
+    holders: Vec<JsonLockHolder<'a>>,
+    /// Contention and holds per lock identity
+    locks: Vec<aperture_shared::types::profile::LockGroup>,
 }
 
 #[derive(Serialize)]
 struct JsonLockContention<'a> {
     lock_addr: String,
+    lock_name: Option<&'a String>,
+    stack: Vec<&'a aperture_shared::types::profile::Frame>,
+    count: u64,
+    total_wait_ns: u64,
+    max_wait_ns: u64,
+    min_wait_ns: u64,
+}
+
+#[derive(Serialize)]
+struct JsonLockHolder<'a> {
+    lock_addr: String,
+    lock_name: Option<&'a String>,
+    stack: Vec<&'a aperture_shared::types::profile::Frame>,
+    count: u64,
+    total_hold_ns: u64,
+    max_hold_ns: u64,
+    min_hold_ns: u64,
+}
+
+#[derive(Serialize)]
+struct JsonKernelLockProfile<'a> {
+    start_time: u64,
+    end_time: u64,
+    total_events: u64,
+    normalization: &'a BTreeMap<String, u64>,
+    contentions: Vec<JsonKernelLockContention<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonKernelLockContention<'a> {
+    lock_addr: String,
+    lock_type: String,
     stack: Vec<&'a aperture_shared::types::profile::Frame>,
     count: u64,
     total_wait_ns: u64,
@@ -91,6 +134,7 @@ pub fn generate_lock_json(
         .iter()
         .map(|((addr, stack), stats)| JsonLockContention {
             lock_addr: format!("0x{:x}", addr),
+            lock_name: profile.lock_names.get(addr),
             stack: stack.frames.iter().collect(),
             count: stats.count,
             total_wait_ns: stats.total_wait_ns,
@@ -99,11 +143,28 @@ pub fn generate_lock_json(
         })
         .collect();
 
+    let holders: Vec<JsonLockHolder> = profile
+        .holders
+        .iter()
+        .map(|((addr, stack), stats)| JsonLockHolder {
+            lock_addr: format!("0x{:x}", addr),
+            lock_name: profile.lock_names.get(addr),
+            stack: stack.frames.iter().collect(),
+            count: stats.count,
+            total_hold_ns: stats.total_hold_ns,
+            max_hold_ns: stats.max_hold_ns,
+            min_hold_ns: stats.min_hold_ns,
+        })
+        .collect();
+
     let json_profile = JsonLockProfile {
         start_time: profile.start_time,
         end_time: profile.end_time,
         total_events: profile.total_events,
+        normalization: &profile.normalization,
         contentions,
+        holders,
+        locks: profile.group_by_lock(),
     };
 
     let file = File::create(output_path)
@@ -117,17 +178,62 @@ pub fn generate_lock_json(
     Ok(())
 }
 
+/// Generate JSON output from kernel lock profile data
+pub fn generate_kernel_lock_json(
+    profile: &aperture_shared::types::profile::KernelLockProfile,
+    output_path: &str,
+) -> Result<()> {
+    info!("Generating kernel lock profile JSON: {}", output_path);
+
+    let contentions: Vec<JsonKernelLockContention> = profile
+        .contentions
+        .iter()
+        .map(|((addr, stack), stats)| JsonKernelLockContention {
+            lock_addr: format!("0x{:x}", addr),
+            lock_type: profile.lock_type(*addr),
+            stack: stack.frames.iter().collect(),
+            count: stats.count,
+            total_wait_ns: stats.total_wait_ns,
+            max_wait_ns: stats.max_wait_ns,
+            min_wait_ns: stats.min_wait_ns,
+        })
+        .collect();
+
+    let json_profile = JsonKernelLockProfile {
+        start_time: profile.start_time,
+        end_time: profile.end_time,
+        total_events: profile.total_events,
+        normalization: &profile.normalization,
+        contentions,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, &json_profile)
+        .context("Failed to serialize kernel lock profile to JSON")?;
+
+    info!("JSON output written to {}", output_path);
+    Ok(())
+}
+
 /// JSON-serializable syscall profile
 #[derive(Serialize)]
-struct JsonSyscallProfile {
+struct JsonSyscallProfile<'a> {
     start_time: u64,
     end_time: u64,
     total_events: u64,
-    syscalls: Vec<JsonSyscallStats>,
+    /// 1 in N calls were recorded; counts are scaled up by N
+    sample_every: u32,
+    syscalls: Vec<JsonSyscallStats<'a>>,
+    normalization: &'a BTreeMap<String, u64>,
+    /// Per file/socket, sorted by total time
+    io: Vec<&'a IoTargetStats>,
 }
 
 #[derive(Serialize)]
-struct JsonSyscallStats {
+struct JsonSyscallStats<'a> {
     id: u32,
     name: String,
     count: u64,
@@ -136,6 +242,8 @@ struct JsonSyscallStats {
     min_duration_ns: u64,
     error_count: u64,
     latency_histogram: Vec<u64>,
+    /// Stacks of calls over the stack threshold
+    slow_stacks: &'a [SlowStack],
 }
 
 /// Generate JSON output from syscall profile data
@@ -157,14 +265,21 @@ pub fn generate_syscall_json(
             min_duration_ns: stats.min_duration_ns,
             error_count: stats.error_count,
             latency_histogram: stats.latency_histogram.clone(),
+            slow_stacks: &stats.slow_stacks,
         })
         .collect();
 
+    let mut io: Vec<&IoTargetStats> = profile.io.values().collect();
+    io.sort_by(|a, b| b.total_duration_ns.cmp(&a.total_duration_ns));
+
     let json_profile = JsonSyscallProfile {
         start_time: profile.start_time,
         end_time: profile.end_time,
         total_events: profile.total_events,
+        sample_every: profile.sample_every,
         syscalls,
+        normalization: &profile.normalization,
+        io,
     };
 
     let file = File::create(output_path)
@@ -178,6 +293,236 @@ pub fn generate_syscall_json(
     Ok(())
 }
 
+/// JSON-serializable block I/O profile
+#[derive(Serialize)]
+struct JsonBlockIoProfile<'a> {
+    start_time: u64,
+    end_time: u64,
+    total_events: u64,
+    /// Per device and operation, with latency and size histograms
+    devices: Vec<&'a BlockIoStats>,
+    /// Per issuing process, sorted by total latency
+    processes: Vec<&'a BlockIoProcessStats>,
+}
+
+/// Generate JSON output from block I/O profile data
+pub fn generate_block_io_json(
+    profile: &aperture_shared::types::profile::BlockIoProfile,
+    output_path: &str,
+) -> Result<()> {
+    info!("Generating block I/O profile JSON: {}", output_path);
+
+    let mut processes: Vec<&BlockIoProcessStats> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.total_latency_ns));
+
+    let json_profile = JsonBlockIoProfile {
+        start_time: profile.start_time,
+        end_time: profile.end_time,
+        total_events: profile.total_events,
+        devices: profile.stats().collect(),
+        processes,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, &json_profile)
+        .context("Failed to serialize block I/O profile to JSON")?;
+
+    info!("JSON output written to {}", output_path);
+    Ok(())
+}
+
+/// JSON-serializable scheduler profile
+#[derive(Serialize)]
+struct JsonSchedProfile<'a> {
+    start_time: u64,
+    end_time: u64,
+    total_events: u64,
+    /// All processes combined
+    total: RunQueueStats,
+    /// Per process, sorted by total wait, with preemptors and wakeup stacks
+    processes: Vec<&'a SchedProcessStats>,
+    /// cgroup path -> waits of its threads
+    cgroups: &'a BTreeMap<String, RunQueueStats>,
+    /// Normalization rule -> frames it changed
+    normalization: &'a BTreeMap<String, u64>,
+}
+
+/// Generate JSON output from scheduler profile data
+pub fn generate_sched_json(
+    profile: &aperture_shared::types::profile::SchedProfile,
+    output_path: &str,
+) -> Result<()> {
+    info!("Generating scheduler profile JSON: {}", output_path);
+
+    let mut processes: Vec<&SchedProcessStats> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.runq.total_delay_ns));
+
+    let json_profile = JsonSchedProfile {
+        start_time: profile.start_time,
+        end_time: profile.end_time,
+        total_events: profile.total_events,
+        total: profile.total(),
+        processes,
+        cgroups: &profile.cgroups,
+        normalization: &profile.normalization,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, &json_profile)
+        .context("Failed to serialize scheduler profile to JSON")?;
+
+    info!("JSON output written to {}", output_path);
+    Ok(())
+}
+
+/// JSON-serializable TCP profile
+#[derive(Serialize)]
+struct JsonTcpProfile<'a> {
+    start_time: u64,
+    end_time: u64,
+    total_events: u64,
+    /// Per process, sorted by bytes transferred, with per-endpoint stats
+    processes: Vec<&'a TcpProcessStats>,
+}
+
+/// Generate JSON output from TCP profile data
+pub fn generate_tcp_json(
+    profile: &aperture_shared::types::profile::TcpProfile,
+    output_path: &str,
+) -> Result<()> {
+    info!("Generating TCP profile JSON: {}", output_path);
+
+    let mut processes: Vec<&TcpProcessStats> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.total_bytes()));
+
+    let json_profile = JsonTcpProfile {
+        start_time: profile.start_time,
+        end_time: profile.end_time,
+        total_events: profile.total_events,
+        processes,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, &json_profile)
+        .context("Failed to serialize TCP profile to JSON")?;
+
+    info!("JSON output written to {}", output_path);
+    Ok(())
+}
+
+/// JSON-serializable memory profile
+#[derive(Serialize)]
+struct JsonMemoryProfile<'a> {
+    start_time: u64,
+    end_time: u64,
+    total_events: u64,
+    /// Estimated faults (samples scaled by the sampling ratio)
+    total_faults: u64,
+    normalization: &'a BTreeMap<String, u64>,
+    /// Faulting stacks, heaviest first
+    fault_stacks: Vec<JsonSample<'a>>,
+    /// Per process, sorted by reclaim stall time then faults
+    processes: Vec<&'a MemoryProcessStats>,
+}
+
+/// Generate JSON output from memory profile data
+pub fn generate_memory_json(
+    profile: &aperture_shared::types::profile::MemoryProfile,
+    output_path: &str,
+) -> Result<()> {
+    info!("Generating memory profile JSON: {}", output_path);
+
+    let mut fault_stacks: Vec<JsonSample> = profile
+        .faults
+        .samples
+        .iter()
+        .map(|(stack, count)| JsonSample {
+            count: *count,
+            frames: stack.frames.iter().collect(),
+        })
+        .collect();
+    fault_stacks.sort_by_key(|s| std::cmp::Reverse(s.count));
+
+    let mut processes: Vec<&MemoryProcessStats> = profile.processes.values().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse((p.reclaim.total_stall_ns, p.faults)));
+
+    let json_profile = JsonMemoryProfile {
+        start_time: profile.start_time,
+        end_time: profile.end_time,
+        total_events: profile.total_events,
+        total_faults: profile.total_faults(),
+        normalization: &profile.faults.normalization,
+        fault_stacks,
+        processes,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, &json_profile)
+        .context("Failed to serialize memory profile to JSON")?;
+
+    info!("JSON output written to {}", output_path);
+    Ok(())
+}
+
+/// JSON-serializable probe profile
+#[derive(Serialize)]
+struct JsonProbeProfile<'a> {
+    start_time: u64,
+    end_time: u64,
+    total_events: u64,
+    /// Per probe, sorted by total latency then calls, with stacks
+    probes: Vec<&'a ProbeStats>,
+    /// USDT probe `provider:name` -> its hits
+    usdt: &'a BTreeMap<String, UsdtProbeStats>,
+    /// Span name -> spans between its paired USDT probes
+    spans: &'a BTreeMap<String, UsdtSpanStats>,
+    /// Normalization rule -> frames it changed
+    normalization: &'a BTreeMap<String, u64>,
+}
+
+/// Generate JSON output from probe profile data
+pub fn generate_probe_json(
+    profile: &aperture_shared::types::profile::ProbeProfile,
+    output_path: &str,
+) -> Result<()> {
+    info!("Generating probe profile JSON: {}", output_path);
+
+    let mut probes: Vec<&ProbeStats> = profile.probes.values().collect();
+    probes.sort_by_key(|p| std::cmp::Reverse((p.total_duration_ns, p.count)));
+
+    let json_profile = JsonProbeProfile {
+        start_time: profile.start_time,
+        end_time: profile.end_time,
+        total_events: profile.total_events,
+        probes,
+        usdt: &profile.usdt,
+        spans: &profile.spans,
+        normalization: &profile.normalization,
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path))?;
+    let writer = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(writer, &json_profile)
+        .context("Failed to serialize probe profile to JSON")?;
+
+    info!("JSON output written to {}", output_path);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,6 +557,7 @@ mod tests {
                 file: None,
                 line: None,
                 module: None,
+                inline: false,
             }],
         };
         profile.add_sample(stack);
@@ -230,4 +576,231 @@ mod tests {
         assert_eq!(parsed["samples"].as_array().unwrap().len(), 1);
         assert_eq!(parsed["samples"][0]["count"], 1);
     }
+
+    #[test]
+    fn test_syscall_json_lists_io_targets() {
+        use aperture_shared::types::profile::SyscallProfile;
+
+        let mut profile = SyscallProfile::new(0);
+        profile.add_syscall(0, "read", 300, 128);
+        profile.add_io(
+            "tcp 10.0.0.1:5432->10.0.0.2:40112",
+            "read",
+            300,
+            128,
+            Some(128),
+        );
+        profile.add_syscall(74, "fsync", 9_000, 0);
+        profile.add_io("/data/wal", "fsync", 9_000, 0, None);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("syscalls.json");
+        generate_syscall_json(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(output_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed["io"][0]["target"], "/data/wal");
+        assert_eq!(parsed["io"][1]["bytes"], 128);
+    }
+
+    #[test]
+    fn test_block_io_json_lists_devices_and_processes() {
+        use aperture_shared::types::events::{BlockIoEvent, BlockIoOp};
+        use aperture_shared::types::profile::BlockIoProfile;
+
+        let request = |pid, op, latency_ns| BlockIoEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            dev: 8 << 20,
+            device: Some("sda".to_string()),
+            op,
+            sector: 0,
+            bytes: 4096,
+            latency_ns,
+            queue_depth: 1,
+            error: 0,
+        };
+        let mut profile = BlockIoProfile::new(0);
+        profile.add_event(&request(1, BlockIoOp::Read, 100_000));
+        profile.add_event(&request(2, BlockIoOp::Write, 900_000));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("block-io.json");
+        generate_block_io_json(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(output_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed["total_events"], 2);
+        assert_eq!(parsed["devices"][0]["op"], "read");
+        assert_eq!(parsed["devices"][1]["device"], "sda");
+        assert_eq!(parsed["processes"][0]["pid"], 2);
+    }
+
+    #[test]
+    fn test_sched_json_lists_processes_and_cgroups() {
+        use aperture_shared::types::events::SchedEvent;
+        use aperture_shared::types::profile::SchedProfile;
+
+        let wait = |pid, delay_ns| SchedEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            cpu_id: 0,
+            delay_ns,
+            preempted: false,
+            waker_pid: 1,
+            waker_tid: 1,
+            cgroup: Some("/batch.slice".to_string()),
+            preemptor_tid: None,
+            preemptor_comm: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut profile = SchedProfile::new(0);
+        profile.add_event(&wait(1, 10_000));
+        profile.add_event(&wait(2, 900_000));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("sched.json");
+        generate_sched_json(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(output_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed["total_events"], 2);
+        assert_eq!(parsed["total"]["count"], 2);
+        assert_eq!(parsed["processes"][0]["pid"], 2);
+        assert_eq!(parsed["cgroups"]["/batch.slice"]["count"], 2);
+    }
+
+    #[test]
+    fn test_tcp_json_lists_processes_and_endpoints() {
+        use aperture_shared::types::events::{TcpEvent, TcpEventKind};
+        use aperture_shared::types::profile::TcpProfile;
+
+        let close = |pid, bytes_received| TcpEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            kind: TcpEventKind::Close,
+            remote_addr: "2001:db8::7".parse().unwrap(),
+            remote_port: 443,
+            local_port: 50000,
+            passive: false,
+            duration_ns: 1_000_000,
+            bytes_sent: 100,
+            bytes_received,
+            retransmits: 0,
+            srtt_us: 90,
+        };
+        let mut profile = TcpProfile::new(0);
+        profile.add_event(&close(1, 1_000));
+        profile.add_event(&close(2, 50_000));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("tcp.json");
+        generate_tcp_json(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(output_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed["total_events"], 2);
+        assert_eq!(parsed["processes"][0]["pid"], 2);
+        let endpoint = &parsed["processes"][0]["endpoints"]["[2001:db8::7]:443"];
+        assert_eq!(endpoint["bytes_received"], 50_000);
+        assert_eq!(endpoint["closed"], 1);
+    }
+
+    #[test]
+    fn test_memory_json_lists_fault_stacks_and_processes() {
+        use aperture_shared::types::events::{PageFaultEvent, ReclaimEvent};
+        use aperture_shared::types::profile::MemoryProfile;
+
+        let fault = |pid, stack_trace| PageFaultEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            address: 0x7f00_0000_0000,
+            ip: 0x401000,
+            error_code: 6,
+            kernel: false,
+            weight: 5,
+            stack_trace,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut profile = MemoryProfile::new(0);
+        profile.add_fault(&fault(1, vec![0x401000]));
+        profile.add_fault(&fault(2, vec![0x402000]));
+        profile.add_fault(&fault(2, vec![0x402000]));
+        profile.add_reclaim(&ReclaimEvent {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            comm: "proc1".to_string(),
+            stall_ns: 1_000_000,
+            nr_reclaimed: 32,
+            order: 0,
+            memcg: true,
+        });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("memory.json");
+        generate_memory_json(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(output_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed["total_events"], 4);
+        assert_eq!(parsed["total_faults"], 15);
+        assert_eq!(parsed["fault_stacks"][0]["count"], 10);
+        // The stalled process sorts first
+        assert_eq!(parsed["processes"][0]["pid"], 1);
+        assert_eq!(parsed["processes"][0]["reclaim"]["memcg_count"], 1);
+    }
+
+    #[test]
+    fn test_probe_json_lists_probes() {
+        use aperture_shared::types::events::{ProbeEvent, UsdtEvent};
+        use aperture_shared::types::profile::ProbeProfile;
+
+        let call = |probe: &str, duration_ns| ProbeEvent {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            comm: "app".to_string(),
+            probe: probe.to_string(),
+            duration_ns,
+            stack_trace: vec![0x401000],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut profile = ProbeProfile::new(0);
+        profile.add_event(&call("tracepoint:net:netif_rx", None));
+        profile.add_event(&call("kprobe:vfs_read", Some(5_000)));
+        profile.add_usdt(&UsdtEvent {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            comm: "app".to_string(),
+            probe: "app:req__start".to_string(),
+            args: vec![9],
+            span: None,
+        });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("probes.json");
+        generate_probe_json(&profile, output_path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(output_path).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(parsed["total_events"], 3);
+        assert_eq!(parsed["probes"][0]["probe"], "kprobe:vfs_read");
+        assert_eq!(parsed["usdt"]["app:req__start"]["args"][0]["max"], 9);
+        assert_eq!(parsed["probes"][0]["stacks"][0]["count"], 1);
+        assert_eq!(parsed["probes"][1]["probe"], "tracepoint:net:netif_rx");
+    }
 }
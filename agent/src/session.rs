@@ -0,0 +1,365 @@
+//! In-process profiling sessions
+//!
+//! For benchmark and test harnesses that bracket a code region with a
+//! profile: a [`Session`] runs the CPU, lock and syscall profilers of a
+//! [`Config`] until it is stopped, and hands back the symbolized profiles in
+//! memory instead of writing them out.
+//!
+//! ```no_run
+//! # async fn example(config: aperture_agent::Config) -> anyhow::Result<()> {
+//! let session = aperture_agent::Session::start(config).await?;
+//! // ... code under test ...
+//! let bundle = session.stop().await?;
+//! assert!(bundle.cpu.is_some_and(|profile| profile.total_samples > 0));
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context, Result};
+use aperture_shared::types::profile::{LockProfile, Profile, SyscallProfile};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::config::{Config, ProfileMode};
+use crate::{ProcessTracking, ProfileEnd, SharedDiskCache, SharedProcessCollector, SharedRecorder};
+
+/// Profiles collected by a session
+#[derive(Debug, Clone, Default)]
+pub struct ProfileBundle {
+    /// CPU profile (modes cpu and all)
+    pub cpu: Option<Profile>,
+    /// Lock contention profile (modes lock and all)
+    pub lock: Option<LockProfile>,
+    /// Syscall profile (modes syscall and all)
+    pub syscall: Option<SyscallProfile>,
+    /// Profilers of mode all that failed, while the others' profiles are
+    /// still returned
+    pub errors: Vec<String>,
+}
+
+/// Entry point of in-process profiling sessions
+pub struct Session;
+
+impl Session {
+    /// Attach the profilers of `config` and return once they collect. Modes
+    /// cpu, lock, syscall and all are supported; `config.duration` and the
+    /// output paths are not used, while an aggregator still gets the events
+    /// streamed and `config.record` a capture.
+    pub async fn start(config: Config) -> Result<SessionHandle> {
+        config.validate().context("Invalid configuration")?;
+        if !matches!(
+            config.mode,
+            ProfileMode::Cpu | ProfileMode::Lock | ProfileMode::Syscall | ProfileMode::All
+        ) {
+            anyhow::bail!(
+                "Sessions support the cpu, lock, syscall and all modes, not {:?}",
+                config.mode
+            );
+        }
+
+        crate::check_symbol_prerequisites(config.target_pid);
+        let recorder = crate::open_recorder(&config)?;
+
+        let tracking = if config.captures_stacks() {
+            ProcessTracking::start(config.target_pid, config.follow_children)
+        } else {
+            None
+        };
+        let disk_cache = crate::open_symbol_cache(&config);
+
+        let (stop, done) = watch::channel(false);
+        let (ready, mut attached) = tokio::sync::mpsc::channel::<()>(1);
+        let end = ProfileEnd::Until { ready, done };
+        let snapshots = Arc::new(Snapshots::default());
+
+        let task = tokio::spawn(run(
+            config,
+            tracking,
+            disk_cache,
+            end,
+            snapshots.clone(),
+            recorder,
+        ));
+        let mut running = 0;
+        while attached.recv().await.is_some() {
+            running += 1;
+        }
+        if running == 0 {
+            task.await??;
+            anyhow::bail!("No profiler of the session could attach");
+        }
+
+        Ok(SessionHandle {
+            stop,
+            snapshots,
+            task,
+        })
+    }
+}
+
+/// A running session; dropping it stops the profilers and discards their
+/// profiles
+pub struct SessionHandle {
+    stop: watch::Sender<bool>,
+    snapshots: Arc<Snapshots>,
+    task: tokio::task::JoinHandle<Result<ProfileBundle>>,
+}
+
+impl SessionHandle {
+    /// Profiles of what was collected so far, while the session goes on.
+    /// Histograms aggregated in the kernel (`--syscall-aggregate`) are read
+    /// as of now.
+    pub async fn snapshot(&self) -> Result<ProfileBundle> {
+        let snapshots = self.snapshots.clone();
+        tokio::task::spawn_blocking(move || snapshots.take()).await?
+    }
+
+    /// Stop the profilers and return the final profiles
+    pub async fn stop(self) -> Result<ProfileBundle> {
+        let _ = self.stop.send(true);
+        self.task.await?
+    }
+}
+
+/// Builds the profile of a running profiler from its collector
+type Snapshot<T> = Arc<dyn Fn() -> Result<T> + Send + Sync>;
+
+/// Snapshot builders registered by the profilers of a session once they run
+#[derive(Default)]
+pub(crate) struct Snapshots {
+    cpu: Mutex<Option<Snapshot<Profile>>>,
+    lock: Mutex<Option<Snapshot<LockProfile>>>,
+    syscall: Mutex<Option<Snapshot<SyscallProfile>>>,
+}
+
+impl Snapshots {
+    pub(crate) fn set_cpu(&self, snapshot: impl Fn() -> Result<Profile> + Send + Sync + 'static) {
+        *self.cpu.lock().unwrap() = Some(Arc::new(snapshot));
+    }
+
+    pub(crate) fn set_lock(
+        &self,
+        snapshot: impl Fn() -> Result<LockProfile> + Send + Sync + 'static,
+    ) {
+        *self.lock.lock().unwrap() = Some(Arc::new(snapshot));
+    }
+
+    pub(crate) fn set_syscall(
+        &self,
+        snapshot: impl Fn() -> Result<SyscallProfile> + Send + Sync + 'static,
+    ) {
+        *self.syscall.lock().unwrap() = Some(Arc::new(snapshot));
+    }
+
+    /// Build a profile from each registered profiler; blocks while
+    /// symbolizing
+    fn take(&self) -> Result<ProfileBundle> {
+        fn build<T>(snapshot: &Mutex<Option<Snapshot<T>>>) -> Result<Option<T>> {
+            let snapshot = snapshot.lock().unwrap().clone();
+            snapshot.map(|snapshot| snapshot()).transpose()
+        }
+        Ok(ProfileBundle {
+            cpu: build(&self.cpu)?,
+            lock: build(&self.lock)?,
+            syscall: build(&self.syscall)?,
+            errors: Vec::new(),
+        })
+    }
+}
+
+/// Run the profilers of a session until it is stopped
+async fn run(
+    config: Config,
+    tracking: Option<ProcessTracking>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    snapshots: Arc<Snapshots>,
+    recorder: Option<SharedRecorder>,
+) -> Result<ProfileBundle> {
+    let processes = tracking.as_ref().map(|t| t.collector.clone());
+    let result = collect(
+        config,
+        processes.clone(),
+        disk_cache.clone(),
+        end,
+        snapshots,
+        recorder.clone(),
+    )
+    .await;
+    crate::finish_recording(recorder, processes.as_ref()).await;
+    if let Some(tracking) = tracking {
+        tracking.stop().await;
+    }
+    if let Some(disk_cache) = disk_cache {
+        if let Err(e) = disk_cache.save() {
+            warn!("Failed to save symbol cache: {:#}", e);
+        }
+    }
+    result
+}
+
+async fn collect(
+    config: Config,
+    processes: Option<SharedProcessCollector>,
+    disk_cache: Option<SharedDiskCache>,
+    end: ProfileEnd,
+    snapshots: Arc<Snapshots>,
+    recorder: Option<SharedRecorder>,
+) -> Result<ProfileBundle> {
+    let snapshots = Some(snapshots);
+    let mut bundle = ProfileBundle::default();
+    match config.mode {
+        ProfileMode::Cpu => {
+            bundle.cpu = Some(
+                crate::collect_cpu_profile(config, processes, disk_cache, end, snapshots, recorder)
+                    .await?,
+            );
+        }
+        ProfileMode::Lock => {
+            bundle.lock = Some(
+                crate::collect_lock_profile(
+                    config, processes, disk_cache, end, snapshots, recorder,
+                )
+                .await?,
+            );
+        }
+        ProfileMode::Syscall => {
+            bundle.syscall = Some(
+                crate::collect_syscall_profile(
+                    config, processes, disk_cache, end, snapshots, recorder,
+                )
+                .await?,
+            );
+        }
+        _ => {
+            let (cpu, lock, syscall) = tokio::join!(
+                crate::collect_cpu_profile(
+                    config.clone(),
+                    processes.clone(),
+                    disk_cache.clone(),
+                    end.clone(),
+                    snapshots.clone(),
+                    recorder.clone(),
+                ),
+                crate::collect_lock_profile(
+                    config.clone(),
+                    processes.clone(),
+                    disk_cache.clone(),
+                    end.clone(),
+                    snapshots.clone(),
+                    recorder.clone(),
+                ),
+                crate::collect_syscall_profile(
+                    config, processes, disk_cache, end, snapshots, recorder,
+                ),
+            );
+            bundle = bundle_all(cpu, lock, syscall)?;
+        }
+    }
+    Ok(bundle)
+}
+
+/// Bundle the profiles of mode all, keeping those of the profilers that
+/// succeeded; fails only when none did
+fn bundle_all(
+    cpu: Result<Profile>,
+    lock: Result<LockProfile>,
+    syscall: Result<SyscallProfile>,
+) -> Result<ProfileBundle> {
+    fn keep<T>(result: Result<T>, profiler: &str, errors: &mut Vec<String>) -> Option<T> {
+        result
+            .map_err(|e| {
+                warn!("{} profiler failed: {:#}", profiler, e);
+                errors.push(format!("{} profiler failed: {:#}", profiler, e));
+            })
+            .ok()
+    }
+    let mut errors = Vec::new();
+    let cpu = keep(cpu, "CPU", &mut errors);
+    let lock = keep(lock, "Lock", &mut errors);
+    let syscall = keep(syscall, "Syscall", &mut errors);
+    if cpu.is_none() && lock.is_none() && syscall.is_none() {
+        anyhow::bail!(
+            "Every profiler of the session failed: {}",
+            errors.join("; ")
+        );
+    }
+    Ok(ProfileBundle {
+        cpu,
+        lock,
+        syscall,
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_snapshot_and_stop() {
+        let snapshots = Arc::new(Snapshots::default());
+        let (stop, mut done) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            let _ = done.wait_for(|done| *done).await;
+            Ok(ProfileBundle {
+                cpu: Some(Profile::new(0, 1, 10)),
+                ..Default::default()
+            })
+        });
+        let session = SessionHandle {
+            stop,
+            snapshots: snapshots.clone(),
+            task,
+        };
+
+        // Nothing registered yet
+        let bundle = session.snapshot().await.unwrap();
+        assert!(bundle.cpu.is_none() && bundle.lock.is_none() && bundle.syscall.is_none());
+
+        snapshots.set_cpu(|| Ok(Profile::new(0, 1, 10)));
+        let bundle = session.snapshot().await.unwrap();
+        assert_eq!(bundle.cpu.unwrap().total_samples, 0);
+        assert!(bundle.lock.is_none());
+
+        snapshots.set_lock(|| anyhow::bail!("collector gone"));
+        assert!(session.snapshot().await.is_err());
+
+        let bundle = session.stop().await.unwrap();
+        assert!(bundle.cpu.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_session_rejects_other_modes() {
+        for mode in [ProfileMode::Tcp, ProfileMode::BlockIo] {
+            let config = Config {
+                mode,
+                ..crate::config::tests::base_config()
+            };
+            let err = Session::start(config).await.err().unwrap();
+            assert!(err.to_string().starts_with("Sessions support"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_all_mode_keeps_profiles_of_working_profilers() {
+        let bundle = bundle_all(
+            Ok(Profile::new(0, 1, 10)),
+            Err(anyhow::anyhow!("no lock symbols")),
+            Ok(SyscallProfile::new(0)),
+        )
+        .unwrap();
+        assert!(bundle.cpu.is_some() && bundle.syscall.is_some());
+        assert!(bundle.lock.is_none());
+        assert_eq!(bundle.errors, ["Lock profiler failed: no lock symbols"]);
+
+        let err = bundle_all(
+            Err(anyhow::anyhow!("a")),
+            Err(anyhow::anyhow!("b")),
+            Err(anyhow::anyhow!("c")),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Syscall profiler failed: c"));
+    }
+}
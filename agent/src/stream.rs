@@ -0,0 +1,239 @@
+//! Streaming of perf buffer events into collectors and on to the aggregator
+//!
+//! Every tracer hands its events to userspace through a per-CPU perf event
+//! array. [`stream_perf_events`] spawns a reader per online CPU that feeds
+//! those events to a shared collector and, when the run has an aggregator,
+//! a task that periodically pushes what the collector has pending.
+//! [`EventStream::finish`] stops both and pushes whatever is left.
+
+use crate::collector::symbols::SymbolCache;
+use crate::PUSH_INTERVAL_MAX;
+use crate::{agent_id, push_to_aggregator_with_retry, Config, SharedProcessCollector};
+use anyhow::{Context, Result};
+use aperture_shared::types::events::ProfileEvent;
+use aya::maps::perf::AsyncPerfEventArray;
+use aya::util::online_cpus;
+use aya::Ebpf;
+use bytes::BytesMut;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Takes the events a collector has not pushed yet
+type Drain<C> = Box<dyn Fn(&mut C) -> Vec<ProfileEvent> + Send + Sync>;
+
+/// Where and how a profiler's pending events are pushed while it runs
+pub(crate) struct PushConfig<C> {
+    url: String,
+    interval: Duration,
+    drain: Drain<C>,
+    /// Shared by the periodic pushes and the final one, so it stays warm
+    symbols: Option<(std::sync::Mutex<SymbolCache>, Option<i32>)>,
+    processes: Option<SharedProcessCollector>,
+}
+
+impl<C> PushConfig<C> {
+    /// Push to the run's aggregator, if it has one, taking the pending
+    /// events of the collector with `drain`
+    pub(crate) fn for_config(
+        config: &Config,
+        drain: impl Fn(&mut C) -> Vec<ProfileEvent> + Send + Sync + 'static,
+    ) -> Option<Self> {
+        let url = config.aggregator_url.clone()?;
+        Some(Self {
+            url,
+            interval: config.push_interval(),
+            drain: Box::new(drain),
+            symbols: None,
+            processes: None,
+        })
+    }
+
+    /// Symbolize the events of `target_pid` with `cache` before pushing them
+    pub(crate) fn with_symbol_cache(mut self, cache: SymbolCache, target_pid: Option<i32>) -> Self {
+        self.symbols = Some((std::sync::Mutex::new(cache), target_pid));
+        self
+    }
+
+    /// Send the tracked process events along with each push
+    pub(crate) fn with_processes(mut self, processes: Option<SharedProcessCollector>) -> Self {
+        self.processes = processes;
+        self
+    }
+
+    /// Take the collector's pending events, symbolized and with the process
+    /// events collected since the last push
+    async fn take_events(&self, collector: &Mutex<C>) -> Vec<ProfileEvent> {
+        let mut events = (self.drain)(&mut *collector.lock().await);
+        if let Some((cache, target_pid)) = &self.symbols {
+            cache
+                .lock()
+                .unwrap()
+                .symbolize_events(&mut events, *target_pid);
+        }
+        if let Some(p) = &self.processes {
+            events.extend(p.lock().await.take_pending_events());
+        }
+        events
+    }
+}
+
+/// Reader and push tasks of a running profiler
+pub(crate) struct EventStream<C> {
+    collector: Arc<Mutex<C>>,
+    readers: Vec<JoinHandle<()>>,
+    push: Option<(Arc<PushConfig<C>>, JoinHandle<()>)>,
+}
+
+/// Feed the events of the `map_name` perf array to `collector` through
+/// `handle`, pushing the collector's pending events as configured by `push`
+pub(crate) fn stream_perf_events<E, C>(
+    bpf: &mut Ebpf,
+    map_name: &str,
+    collector: Arc<Mutex<C>>,
+    push: Option<PushConfig<C>>,
+    handle: impl Fn(&mut C, &E) -> Result<()> + Send + Sync + 'static,
+) -> Result<EventStream<C>>
+where
+    E: Copy + Send + 'static,
+    C: Send + 'static,
+{
+    let readers = spawn_perf_readers(bpf, map_name, &collector, handle)?;
+    let push = push.map(|push| {
+        let push = Arc::new(push);
+        let task = tokio::spawn(push_loop(push.clone(), collector.clone()));
+        (push, task)
+    });
+    Ok(EventStream {
+        collector,
+        readers,
+        push,
+    })
+}
+
+impl<C: Send + 'static> EventStream<C> {
+    /// Also feed the events of a second perf array to the collector
+    pub(crate) fn read<E>(
+        &mut self,
+        bpf: &mut Ebpf,
+        map_name: &str,
+        handle: impl Fn(&mut C, &E) -> Result<()> + Send + Sync + 'static,
+    ) -> Result<()>
+    where
+        E: Copy + Send + 'static,
+    {
+        let readers = spawn_perf_readers(bpf, map_name, &self.collector, handle)?;
+        self.readers.extend(readers);
+        Ok(())
+    }
+
+    /// Stop reading and pushing, then push the events still pending
+    pub(crate) async fn finish(mut self) {
+        let (push, mut tasks) = match self.push.take() {
+            Some((push, task)) => (Some(push), vec![task]),
+            None => (None, Vec::new()),
+        };
+        tasks.append(&mut self.readers);
+        abort_all(tasks).await;
+
+        if let Some(push) = push {
+            let events = push.take_events(&self.collector).await;
+            let mut client = None;
+            if let Err(e) =
+                push_to_aggregator_with_retry(&mut client, &push.url, &agent_id(), events).await
+            {
+                warn!("Final push failed: {}", e);
+            }
+        }
+    }
+}
+
+impl<C> Drop for EventStream<C> {
+    fn drop(&mut self) {
+        for reader in &self.readers {
+            reader.abort();
+        }
+        if let Some((_, task)) = &self.push {
+            task.abort();
+        }
+    }
+}
+
+/// Abort tasks and wait for them to release what they hold
+pub(crate) async fn abort_all(handles: Vec<JoinHandle<()>>) {
+    for handle in &handles {
+        handle.abort();
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+}
+
+/// Spawn a task per online CPU feeding the events of the `map_name` perf
+/// array to `collector` through `handle`
+pub(crate) fn spawn_perf_readers<E, C>(
+    bpf: &mut Ebpf,
+    map_name: &str,
+    collector: &Arc<Mutex<C>>,
+    handle: impl Fn(&mut C, &E) -> Result<()> + Send + Sync + 'static,
+) -> Result<Vec<JoinHandle<()>>>
+where
+    E: Copy + Send + 'static,
+    C: Send + 'static,
+{
+    let events_map = bpf
+        .take_map(map_name)
+        .with_context(|| format!("Failed to get {} map", map_name))?;
+    let mut perf_array = AsyncPerfEventArray::try_from(events_map)?;
+
+    let handle = Arc::new(handle);
+    let cpus = online_cpus().map_err(|(msg, e)| anyhow::anyhow!("{}: {}", msg, e))?;
+    let mut handles = Vec::new();
+    for cpu_id in cpus {
+        let mut buf = perf_array.open(cpu_id, None)?;
+        let collector = collector.clone();
+        let handle = handle.clone();
+        let map_name = map_name.to_string();
+
+        handles.push(tokio::spawn(async move {
+            let mut buffers = (0..10)
+                .map(|_| BytesMut::with_capacity(core::mem::size_of::<E>() + 64))
+                .collect::<Vec<_>>();
+
+            while let Ok(events) = buf.read_events(&mut buffers).await {
+                for buf_ref in buffers.iter().take(events.read) {
+                    if buf_ref.len() >= core::mem::size_of::<E>() {
+                        let event =
+                            unsafe { std::ptr::read_unaligned(buf_ref.as_ptr() as *const E) };
+                        let mut coll = collector.lock().await;
+                        if let Err(e) = handle(&mut coll, &event) {
+                            debug!("Error processing {} event: {}", map_name, e);
+                        }
+                    }
+                }
+            }
+        }));
+    }
+    Ok(handles)
+}
+
+/// Push the collector's pending events every push interval, backing off
+/// while the aggregator reports backpressure
+async fn push_loop<C>(push: Arc<PushConfig<C>>, collector: Arc<Mutex<C>>) {
+    let agent = agent_id();
+    let mut client = None;
+    let mut push_interval = push.interval;
+    loop {
+        tokio::time::sleep(push_interval).await;
+        let events = push.take_events(&collector).await;
+        match push_to_aggregator_with_retry(&mut client, &push.url, &agent, events).await {
+            Ok(Some(true)) => {
+                push_interval = (push_interval + push_interval).min(PUSH_INTERVAL_MAX)
+            }
+            Ok(Some(false)) | Ok(None) => push_interval = push.interval,
+            Err(e) => warn!("Streaming push failed: {}", e),
+        }
+    }
+}
@@ -1,8 +1,11 @@
 use anyhow::Result;
+use aperture_agent::collector::kernel_lock::KernelLockCollector;
 use aperture_agent::collector::lock::LockCollector;
 use aperture_agent::collector::syscall::SyscallCollector;
 use aperture_agent::output::{flamegraph, histogram, json};
-use aperture_shared::types::events::{LockEvent, SyscallEvent};
+use aperture_shared::types::events::{
+    KernelLockEvent, LockEvent, LockEventKind, SyscallEvent, LCB_F_WRITE,
+};
 use tempfile::NamedTempFile;
 
 #[test]
@@ -18,17 +21,36 @@ fn test_lock_pipeline() -> Result<()> {
         stack_trace: vec![0x400000],
         comm: "test".to_string(),
         stack_symbols: vec![],
+        stack_refs: vec![],
+        kind: LockEventKind::Wait,
+        waker_tid: Some(2),
+        lock_name: None,
+        labels: Default::default(),
+    };
+    let release = LockEvent {
+        tid: 2,
+        hold_time_ns: 700,
+        wait_time_ns: 0,
+        stack_trace: vec![0x410000],
+        kind: LockEventKind::Release,
+        waker_tid: None,
+        ..event.clone()
     };
     collector.add_event(event);
+    collector.add_event(release);
 
     let profile = collector.build_profile()?;
-    assert_eq!(profile.total_events, 1);
+    assert_eq!(profile.total_events, 2);
+    assert_eq!(profile.holders.len(), 1);
 
     let temp_file = NamedTempFile::new()?;
     let path = temp_file.path().to_str().unwrap();
 
     // Test output generation
     flamegraph::generate_lock_flamegraph(&profile, path)?;
+    let holders = flamegraph::generate_lock_holder_flamegraph(&profile, path)?;
+    assert_eq!(holders, Some(format!("{}.holders.svg", path)));
+    let _ = std::fs::remove_file(format!("{}.holders.svg", path));
 
     let json_path = format!("{}.json", path);
     json::generate_lock_json(&profile, &json_path)?;
@@ -36,6 +58,39 @@ fn test_lock_pipeline() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_kernel_lock_pipeline() -> Result<()> {
+    let mut collector = KernelLockCollector::new();
+    collector.add_event(KernelLockEvent {
+        timestamp: 1000,
+        pid: 1,
+        tid: 1,
+        lock_addr: 0xffff888000004000,
+        flags: LCB_F_WRITE,
+        wait_time_ns: 2_000,
+        stack_trace: vec![0x400000, 0xffffffff81000000],
+        comm: "test".to_string(),
+        stack_symbols: vec![],
+        stack_refs: vec![],
+    });
+
+    let profile = collector.build_profile()?;
+    assert_eq!(profile.total_events, 1);
+    assert_eq!(profile.lock_type(0xffff888000004000), "rwsem:W");
+
+    let temp_file = NamedTempFile::new()?;
+    let path = temp_file.path().to_str().unwrap();
+    flamegraph::generate_kernel_lock_flamegraph(&profile, path)?;
+
+    let json_path = format!("{}.json", path);
+    json::generate_kernel_lock_json(&profile, &json_path)?;
+    let json = std::fs::read_to_string(&json_path)?;
+    assert!(json.contains("\"lock_type\": \"rwsem:W\""));
+    let _ = std::fs::remove_file(&json_path);
+
+    Ok(())
+}
+
 #[test]
 fn test_syscall_pipeline() -> Result<()> {
     let mut collector = SyscallCollector::new();
@@ -47,6 +102,13 @@ fn test_syscall_pipeline() -> Result<()> {
         duration_ns: 100,
         return_value: 0,
         comm: "test".to_string(),
+        fd: None,
+        target: None,
+        bytes: None,
+        stack_trace: vec![],
+        stack_symbols: vec![],
+        stack_refs: vec![],
+        sample_every: 1,
     };
     collector.add_event(event);
 
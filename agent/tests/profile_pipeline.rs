@@ -27,6 +27,8 @@ fn test_full_pipeline_collect_build_output() {
             comm: "myapp".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         });
     }
     for i in 0..20 {
@@ -40,6 +42,8 @@ fn test_full_pipeline_collect_build_output() {
             comm: "myapp".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         });
     }
 
@@ -4,11 +4,17 @@
 
 use anyhow::Result;
 use aperture_shared::protocol::wire::Message;
-use aperture_shared::types::events::ProfileEvent;
-use aperture_shared::types::profile::{LockProfile, Profile, Stack, SyscallProfile};
-use aperture_shared::utils::syscalls::syscall_name;
+use aperture_shared::types::events::{Labels, LockEventKind, ProfileEvent, Symbol};
+use aperture_shared::types::profile::{
+    BlockIoProfile, KernelLockProfile, LockGroup, LockProfile, MemoryProcessStats, MemoryProfile,
+    ProbeProfile, Profile, SchedProfile, Stack, SyscallProfile, TcpProfile,
+};
+use aperture_shared::utils::syscalls::{canonical_syscall_id, syscall_name_for};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::symbols::{self, SymbolStore};
 
 /// Result of aggregating multiple batches of profile events.
 ///
@@ -17,10 +23,81 @@ use serde::{Deserialize, Serialize};
 pub struct AggregateResult {
     pub cpu: Option<Profile>,
     pub lock: Option<LockProfile>,
+    pub kernel_lock: Option<KernelLockProfile>,
     pub syscall: Option<SyscallProfile>,
+    pub block_io: Option<BlockIoProfile>,
+    pub sched: Option<SchedProfile>,
+    pub tcp: Option<TcpProfile>,
+    pub memory: Option<MemoryProfile>,
+    pub probe: Option<ProbeProfile>,
+    /// CPU samples and lock waits per value of the grouped label
+    pub labels: Option<LabelBreakdown>,
     pub total_events: u64,
 }
 
+/// Filter and grouping by the profiling labels of CPU samples and lock
+/// events
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelQuery {
+    /// Keep only events carrying all of these labels; other event types,
+    /// which have no labels, are dropped too
+    pub filter: Labels,
+    /// Break CPU samples and lock waits down by the value of this label
+    pub group_by: Option<String>,
+}
+
+impl LabelQuery {
+    /// Parse `key=value` filters
+    pub fn from_args(filter: &[String], group_by: Option<String>) -> Result<Self> {
+        let filter = filter
+            .iter()
+            .map(|f| match f.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+                _ => anyhow::bail!("Invalid label filter: {} (expected KEY=VALUE)", f),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { filter, group_by })
+    }
+
+    /// Whether events labelled `labels` are kept
+    fn matches(&self, labels: &Labels) -> bool {
+        self.filter
+            .iter()
+            .all(|(key, value)| labels.get(key) == Some(value))
+    }
+}
+
+/// CPU samples and lock waits per value of one label
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelBreakdown {
+    pub key: String,
+    /// By label value; events without the label are under `None`
+    pub groups: BTreeMap<Option<String>, LabelGroupStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelGroupStats {
+    pub cpu_samples: u64,
+    pub lock_waits: u64,
+    pub lock_wait_ns: u64,
+}
+
+/// One label value of a breakdown, for JSON output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelGroupJson {
+    /// None for events without the label
+    pub value: Option<String>,
+    #[serde(flatten)]
+    pub stats: LabelGroupStats,
+}
+
+/// JSON-safe representation of a label breakdown, busiest values first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelBreakdownJson {
+    pub key: String,
+    pub groups: Vec<LabelGroupJson>,
+}
+
 /// JSON-safe representation of an aggregated CPU profile.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuProfileJson {
@@ -44,11 +121,53 @@ pub struct LockProfileJson {
     pub end_time: u64,
     pub total_events: u64,
     pub contentions: Vec<LockContentionJson>,
+    /// Stacks that held contended locks, by total hold time
+    #[serde(default)]
+    pub holders: Vec<LockHoldJson>,
+    /// Contention and holds per lock identity, most waited-on first
+    #[serde(default)]
+    pub locks: Vec<LockGroup>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockContentionJson {
     pub lock_addr: u64,
+    /// Variable or allocation site of the lock, when the agent named it
+    #[serde(default)]
+    pub lock_name: Option<String>,
+    pub stack: Stack,
+    pub count: u64,
+    pub total_wait_ns: u64,
+    pub max_wait_ns: u64,
+    pub min_wait_ns: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockHoldJson {
+    pub lock_addr: u64,
+    #[serde(default)]
+    pub lock_name: Option<String>,
+    pub stack: Stack,
+    pub count: u64,
+    pub total_hold_ns: u64,
+    pub max_hold_ns: u64,
+    pub min_hold_ns: u64,
+}
+
+/// JSON-safe representation of kernel lock contention profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelLockProfileJson {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub total_events: u64,
+    pub contentions: Vec<KernelLockContentionJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelLockContentionJson {
+    pub lock_addr: u64,
+    /// `spinlock`, `rwsem:W`, `mutex`, ...
+    pub lock_type: String,
     pub stack: Stack,
     pub count: u64,
     pub total_wait_ns: u64,
@@ -56,12 +175,40 @@ pub struct LockContentionJson {
     pub min_wait_ns: u64,
 }
 
+/// JSON-safe representation of page fault and reclaim profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryProfileJson {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub total_events: u64,
+    /// Estimated faults, the sum of `fault_stacks` counts before truncation
+    pub total_faults: u64,
+    /// Faulting stacks weighted by the faults each sample stands for
+    pub fault_stacks: Vec<StackCountJson>,
+    /// Per process, sorted by reclaim stall time then faults
+    pub processes: Vec<MemoryProcessStats>,
+}
+
 /// JSON-safe aggregate result.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AggregateResultJson {
     pub cpu: Option<CpuProfileJson>,
     pub lock: Option<LockProfileJson>,
+    #[serde(default)]
+    pub kernel_lock: Option<KernelLockProfileJson>,
     pub syscall: Option<SyscallProfile>,
+    #[serde(default)]
+    pub block_io: Option<BlockIoProfile>,
+    #[serde(default)]
+    pub sched: Option<SchedProfile>,
+    #[serde(default)]
+    pub tcp: Option<TcpProfile>,
+    #[serde(default)]
+    pub memory: Option<MemoryProfileJson>,
+    #[serde(default)]
+    pub probe: Option<ProbeProfile>,
+    #[serde(default)]
+    pub labels: Option<LabelBreakdownJson>,
     pub total_events: u64,
 }
 
@@ -100,6 +247,7 @@ impl AggregateResult {
                 .iter()
                 .map(|((addr, stack), stats)| LockContentionJson {
                     lock_addr: *addr,
+                    lock_name: p.lock_names.get(addr).cloned(),
                     stack: stack.clone(),
                     count: stats.count,
                     total_wait_ns: stats.total_wait_ns,
@@ -109,18 +257,112 @@ impl AggregateResult {
                 .collect();
             contentions.sort_by(|a, b| b.total_wait_ns.cmp(&a.total_wait_ns));
             contentions.truncate(MAX_JSON_STACKS);
+            let mut holders: Vec<LockHoldJson> = p
+                .holders
+                .iter()
+                .map(|((addr, stack), stats)| LockHoldJson {
+                    lock_addr: *addr,
+                    lock_name: p.lock_names.get(addr).cloned(),
+                    stack: stack.clone(),
+                    count: stats.count,
+                    total_hold_ns: stats.total_hold_ns,
+                    max_hold_ns: stats.max_hold_ns,
+                    min_hold_ns: stats.min_hold_ns,
+                })
+                .collect();
+            holders.sort_by(|a, b| b.total_hold_ns.cmp(&a.total_hold_ns));
+            holders.truncate(MAX_JSON_STACKS);
             LockProfileJson {
                 start_time: p.start_time,
                 end_time: p.end_time,
                 total_events: p.total_events,
                 contentions,
+                holders,
+                locks: p.group_by_lock(),
+            }
+        });
+
+        let kernel_lock = self.kernel_lock.as_ref().map(|p| {
+            let mut contentions: Vec<KernelLockContentionJson> = p
+                .contentions
+                .iter()
+                .map(|((addr, stack), stats)| KernelLockContentionJson {
+                    lock_addr: *addr,
+                    lock_type: p.lock_type(*addr),
+                    stack: stack.clone(),
+                    count: stats.count,
+                    total_wait_ns: stats.total_wait_ns,
+                    max_wait_ns: stats.max_wait_ns,
+                    min_wait_ns: stats.min_wait_ns,
+                })
+                .collect();
+            contentions.sort_by(|a, b| b.total_wait_ns.cmp(&a.total_wait_ns));
+            contentions.truncate(MAX_JSON_STACKS);
+            KernelLockProfileJson {
+                start_time: p.start_time,
+                end_time: p.end_time,
+                total_events: p.total_events,
+                contentions,
+            }
+        });
+
+        let memory = self.memory.as_ref().map(|p| {
+            let mut fault_stacks: Vec<StackCountJson> = p
+                .faults
+                .samples
+                .iter()
+                .map(|(stack, &count)| StackCountJson {
+                    stack: stack.clone(),
+                    count,
+                })
+                .collect();
+            fault_stacks.sort_by_key(|s| std::cmp::Reverse(s.count));
+            fault_stacks.truncate(MAX_JSON_STACKS);
+            let mut processes: Vec<MemoryProcessStats> = p.processes.values().cloned().collect();
+            processes.sort_by_key(|p| std::cmp::Reverse((p.reclaim.total_stall_ns, p.faults)));
+            MemoryProfileJson {
+                start_time: p.start_time,
+                end_time: p.end_time,
+                total_events: p.total_events,
+                total_faults: p.total_faults(),
+                fault_stacks,
+                processes,
+            }
+        });
+
+        let labels = self.labels.as_ref().map(|b| {
+            let mut groups: Vec<LabelGroupJson> = b
+                .groups
+                .iter()
+                .map(|(value, stats)| LabelGroupJson {
+                    value: value.clone(),
+                    stats: stats.clone(),
+                })
+                .collect();
+            groups.sort_by_key(|g| {
+                std::cmp::Reverse((
+                    g.stats.cpu_samples,
+                    g.stats.lock_wait_ns,
+                    g.stats.lock_waits,
+                ))
+            });
+            LabelBreakdownJson {
+                key: b.key.clone(),
+                groups,
             }
         });
 
         AggregateResultJson {
             cpu,
             lock,
+            kernel_lock,
             syscall: self.syscall.clone(),
+            block_io: self.block_io.clone(),
+            sched: self.sched.clone(),
+            tcp: self.tcp.clone(),
+            memory,
+            probe: self.probe.clone(),
+            labels,
             total_events: self.total_events,
         }
     }
@@ -137,10 +379,48 @@ pub struct AggregateBatchesResult {
 ///
 /// Each payload is a base64-encoded bincode `Message` containing `Vec<ProfileEvent>`.
 /// Events are routed to the appropriate profile builder based on their variant.
+/// Frames shipped as build ID + offset are named from the installed symbol store.
 pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult> {
+    aggregate_batches_with_symbols(payloads, symbols::global())
+}
+
+/// Like [`aggregate_batches`], resolving deferred frames against `symbols`.
+pub fn aggregate_batches_with_symbols(
+    payloads: &[String],
+    symbols: Option<&SymbolStore>,
+) -> Result<AggregateBatchesResult> {
+    aggregate_labeled(payloads, symbols, &LabelQuery::default())
+}
+
+/// Like [`aggregate_batches`], keeping and grouping events by their labels
+/// as `labels` asks.
+pub fn aggregate_batches_with_labels(
+    payloads: &[String],
+    labels: &LabelQuery,
+) -> Result<AggregateBatchesResult> {
+    aggregate_labeled(payloads, symbols::global(), labels)
+}
+
+fn aggregate_labeled(
+    payloads: &[String],
+    symbols: Option<&SymbolStore>,
+    query: &LabelQuery,
+) -> Result<AggregateBatchesResult> {
     let mut cpu: Option<Profile> = None;
     let mut lock: Option<LockProfile> = None;
+    let mut kernel_lock: Option<KernelLockProfile> = None;
     let mut syscall: Option<SyscallProfile> = None;
+    let mut block_io: Option<BlockIoProfile> = None;
+    let mut sched: Option<SchedProfile> = None;
+    let mut tcp: Option<TcpProfile> = None;
+    let mut memory: Option<MemoryProfile> = None;
+    let mut probe: Option<ProbeProfile> = None;
+    // CPU samples by thread, credited to the USDT spans they fall in
+    let mut thread_samples: Vec<(i32, i32, u64, Stack)> = Vec::new();
+    let mut label_groups = query.group_by.as_ref().map(|key| LabelBreakdown {
+        key: key.clone(),
+        groups: BTreeMap::new(),
+    });
     let mut total_events: u64 = 0;
     let mut skipped_batches: u32 = 0;
 
@@ -153,7 +433,7 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
                 continue;
             }
         };
-        let msg = match Message::from_bytes(&bytes) {
+        let mut msg = match Message::from_bytes(&bytes) {
             Ok(m) => m,
             Err(e) => {
                 tracing::warn!(
@@ -165,7 +445,36 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
             }
         };
 
+        if let Some(store) = symbols {
+            store.symbolize_events(&mut msg.events);
+        }
+
+        // Syscall ids are numbered per architecture of the pushing agent;
+        // merged profiles use the x86_64 numbering
+        let arch = msg.arch;
         for event in msg.events {
+            let labels = match &event {
+                ProfileEvent::CpuSample(sample) => Some(&sample.labels),
+                ProfileEvent::Lock(ev) => Some(&ev.labels),
+                _ => None,
+            };
+            if !query.filter.is_empty() && !labels.is_some_and(|l| query.matches(l)) {
+                continue;
+            }
+            if let (Some(breakdown), Some(labels)) = (label_groups.as_mut(), labels) {
+                let group = breakdown
+                    .groups
+                    .entry(labels.get(&breakdown.key).cloned())
+                    .or_default();
+                match &event {
+                    ProfileEvent::CpuSample(_) => group.cpu_samples += 1,
+                    ProfileEvent::Lock(ev) if ev.kind == LockEventKind::Wait => {
+                        group.lock_waits += 1;
+                        group.lock_wait_ns += ev.wait_time_ns;
+                    }
+                    _ => {}
+                }
+            }
             total_events += 1;
             match event {
                 ProfileEvent::CpuSample(sample) => {
@@ -179,7 +488,7 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
                     }
                     // Combine user + kernel stacks with pre-resolved symbols
                     let mut ips = Vec::new();
-                    let mut symbols: Vec<Option<String>> = Vec::new();
+                    let mut symbols: Vec<Option<Symbol>> = Vec::new();
                     ips.extend_from_slice(&sample.user_stack);
                     symbols.extend_from_slice(&sample.user_stack_symbols);
                     while symbols.len() < ips.len() {
@@ -197,6 +506,12 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
                         } else {
                             Stack::from_ips(&ips)
                         };
+                        thread_samples.push((
+                            sample.pid,
+                            sample.tid,
+                            sample.timestamp,
+                            stack.clone(),
+                        ));
                         profile.add_sample(stack);
                     }
                 }
@@ -208,6 +523,35 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
                     if ev.timestamp > profile.end_time {
                         profile.end_time = ev.timestamp;
                     }
+                    if let Some(name) = &ev.lock_name {
+                        profile.lock_names.insert(ev.lock_addr, name.clone());
+                    }
+                    if !ev.stack_trace.is_empty() {
+                        let has_symbols = ev.stack_symbols.iter().any(|s| s.is_some());
+                        let stack = if has_symbols {
+                            Stack::from_ips_with_symbols(&ev.stack_trace, &ev.stack_symbols)
+                        } else {
+                            Stack::from_ips(&ev.stack_trace)
+                        };
+                        match ev.kind {
+                            LockEventKind::Wait => {
+                                profile.add_contention(ev.lock_addr, stack, ev.wait_time_ns)
+                            }
+                            LockEventKind::Release => {
+                                profile.add_hold(ev.lock_addr, stack, ev.hold_time_ns)
+                            }
+                        }
+                    }
+                }
+                ProfileEvent::KernelLock(ev) => {
+                    let profile =
+                        kernel_lock.get_or_insert_with(|| KernelLockProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
                     if !ev.stack_trace.is_empty() {
                         let has_symbols = ev.stack_symbols.iter().any(|s| s.is_some());
                         let stack = if has_symbols {
@@ -215,10 +559,10 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
                         } else {
                             Stack::from_ips(&ev.stack_trace)
                         };
-                        profile.add_contention(ev.lock_addr, stack, ev.wait_time_ns);
+                        profile.add_contention(ev.lock_addr, ev.flags, stack, ev.wait_time_ns);
                     }
                 }
-                ProfileEvent::Syscall(ev) => {
+                ProfileEvent::Syscall(mut ev) => {
                     let profile = syscall.get_or_insert_with(|| SyscallProfile::new(ev.timestamp));
                     if ev.timestamp < profile.start_time {
                         profile.start_time = ev.timestamp;
@@ -226,21 +570,123 @@ pub fn aggregate_batches(payloads: &[String]) -> Result<AggregateBatchesResult>
                     if ev.timestamp > profile.end_time {
                         profile.end_time = ev.timestamp;
                     }
-                    let name = syscall_name(ev.syscall_id);
-                    profile.add_syscall(ev.syscall_id, name, ev.duration_ns, ev.return_value);
+                    let name = syscall_name_for(arch, ev.syscall_id);
+                    ev.syscall_id = canonical_syscall_id(arch, ev.syscall_id);
+                    profile.add_event(name, &ev);
+                }
+                ProfileEvent::SyscallSummary(mut summary) => {
+                    let profile =
+                        syscall.get_or_insert_with(|| SyscallProfile::new(summary.timestamp));
+                    if summary.timestamp < profile.start_time {
+                        profile.start_time = summary.timestamp;
+                    }
+                    if summary.timestamp > profile.end_time {
+                        profile.end_time = summary.timestamp;
+                    }
+                    let name = syscall_name_for(arch, summary.syscall_id);
+                    summary.syscall_id = canonical_syscall_id(arch, summary.syscall_id);
+                    profile.add_summary(name, &summary);
+                }
+                ProfileEvent::BlockIo(ev) => {
+                    let profile = block_io.get_or_insert_with(|| BlockIoProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_event(&ev);
+                }
+                ProfileEvent::Sched(ev) => {
+                    let profile = sched.get_or_insert_with(|| SchedProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_event(&ev);
+                }
+                ProfileEvent::Tcp(ev) => {
+                    let profile = tcp.get_or_insert_with(|| TcpProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_event(&ev);
+                }
+                ProfileEvent::PageFault(ev) => {
+                    let profile = memory.get_or_insert_with(|| MemoryProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_fault(&ev);
+                }
+                ProfileEvent::Reclaim(ev) => {
+                    let profile = memory.get_or_insert_with(|| MemoryProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_reclaim(&ev);
+                }
+                ProfileEvent::Probe(ev) => {
+                    let profile = probe.get_or_insert_with(|| ProbeProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_event(&ev);
+                }
+                ProfileEvent::Usdt(ev) => {
+                    let profile = probe.get_or_insert_with(|| ProbeProfile::new(ev.timestamp));
+                    if ev.timestamp < profile.start_time {
+                        profile.start_time = ev.timestamp;
+                    }
+                    if ev.timestamp > profile.end_time {
+                        profile.end_time = ev.timestamp;
+                    }
+                    profile.add_usdt(&ev);
                 }
                 ProfileEvent::GpuKernel(_) => {
                     // GPU profiling not yet supported in aggregation
                 }
+                ProfileEvent::Process(_) => {
+                    // Lifecycle events are kept in storage; they carry no profile data
+                }
             }
         }
     }
 
+    if let Some(profile) = probe.as_mut() {
+        profile.attribute_cpu_samples(
+            thread_samples
+                .iter()
+                .map(|(pid, tid, ts, stack)| (*pid, *tid, *ts, stack)),
+        );
+    }
+
     Ok(AggregateBatchesResult {
         result: AggregateResult {
             cpu,
             lock,
+            kernel_lock,
             syscall,
+            block_io,
+            sched,
+            tcp,
+            memory,
+            probe,
+            labels: label_groups,
             total_events,
         },
         skipped_batches,
@@ -252,15 +698,83 @@ pub fn filter_by_type(result: &mut AggregateResult, event_type: &str) {
     match event_type {
         "cpu" => {
             result.lock = None;
+            result.kernel_lock = None;
             result.syscall = None;
+            result.block_io = None;
+            result.sched = None;
+            result.tcp = None;
+            result.memory = None;
+            result.probe = None;
         }
+        // User and kernel lock contention are shown side by side
         "lock" => {
             result.cpu = None;
             result.syscall = None;
+            result.block_io = None;
+            result.sched = None;
+            result.tcp = None;
+            result.memory = None;
+            result.probe = None;
         }
         "syscall" => {
             result.cpu = None;
             result.lock = None;
+            result.kernel_lock = None;
+            result.block_io = None;
+            result.sched = None;
+            result.tcp = None;
+            result.memory = None;
+            result.probe = None;
+        }
+        "block-io" => {
+            result.cpu = None;
+            result.lock = None;
+            result.kernel_lock = None;
+            result.syscall = None;
+            result.sched = None;
+            result.tcp = None;
+            result.memory = None;
+            result.probe = None;
+        }
+        "sched" => {
+            result.cpu = None;
+            result.lock = None;
+            result.kernel_lock = None;
+            result.syscall = None;
+            result.block_io = None;
+            result.tcp = None;
+            result.memory = None;
+            result.probe = None;
+        }
+        "tcp" => {
+            result.cpu = None;
+            result.lock = None;
+            result.kernel_lock = None;
+            result.syscall = None;
+            result.block_io = None;
+            result.sched = None;
+            result.memory = None;
+            result.probe = None;
+        }
+        "memory" => {
+            result.cpu = None;
+            result.lock = None;
+            result.kernel_lock = None;
+            result.syscall = None;
+            result.block_io = None;
+            result.sched = None;
+            result.tcp = None;
+            result.probe = None;
+        }
+        "probe" => {
+            result.cpu = None;
+            result.lock = None;
+            result.kernel_lock = None;
+            result.syscall = None;
+            result.block_io = None;
+            result.sched = None;
+            result.tcp = None;
+            result.memory = None;
         }
         _ => {} // "" or "all" — keep everything
     }
@@ -269,10 +783,21 @@ pub fn filter_by_type(result: &mut AggregateResult, event_type: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aperture_shared::types::events::{CpuSample, LockEvent, SyscallEvent};
+    use aperture_shared::types::events::{
+        BlockIoEvent, BlockIoOp, CpuSample, KernelLockEvent, LockEvent, PageFaultEvent, ProbeEvent,
+        ReclaimEvent, SchedEvent, SymbolFrame, SyscallEvent, SyscallSummaryEvent, TcpEvent,
+        TcpEventKind, UsdtEvent, UsdtSpan, LCB_F_SPIN,
+    };
+    use aperture_shared::utils::arch::Arch;
+    use aperture_shared::utils::syscalls::AARCH64_ONLY_SYSCALL_BASE;
 
     fn make_payload(events: Vec<ProfileEvent>) -> String {
-        let msg = Message::new(1, events);
+        make_arch_payload(Arch::X86_64, events)
+    }
+
+    fn make_arch_payload(arch: Arch, events: Vec<ProfileEvent>) -> String {
+        let mut msg = Message::new(1, events);
+        msg.arch = arch;
         let bytes = msg.to_bytes().unwrap();
         BASE64.encode(bytes)
     }
@@ -288,6 +813,8 @@ mod tests {
             comm: "test".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         })
     }
 
@@ -302,6 +829,11 @@ mod tests {
             stack_trace: stack,
             comm: "test".to_string(),
             stack_symbols: vec![],
+            stack_refs: vec![],
+            kind: LockEventKind::Wait,
+            waker_tid: None,
+            lock_name: None,
+            labels: Default::default(),
         })
     }
 
@@ -335,6 +867,13 @@ mod tests {
                 duration_ns: 100,
                 return_value: 0,
                 comm: "test".to_string(),
+                fd: None,
+                target: None,
+                bytes: None,
+                stack_trace: vec![],
+                stack_symbols: vec![],
+                stack_refs: vec![],
+                sample_every: 1,
             }),
             lock_ev(3000, 0x1000, 500, vec![0x4000]),
         ]);
@@ -380,6 +919,13 @@ mod tests {
                 duration_ns: 100,
                 return_value: 0,
                 comm: "test".to_string(),
+                fd: None,
+                target: None,
+                bytes: None,
+                stack_trace: vec![],
+                stack_symbols: vec![],
+                stack_refs: vec![],
+                sample_every: 1,
             }),
         ]);
         let mut out = aggregate_batches(&[payload]).unwrap();
@@ -398,8 +944,13 @@ mod tests {
             user_stack: vec![0x1000, 0x2000],
             kernel_stack: vec![],
             comm: "test".to_string(),
-            user_stack_symbols: vec![Some("main".to_string()), Some("compute".to_string())],
+            user_stack_symbols: vec![
+                Some(vec![SymbolFrame::named("main")]),
+                Some(vec![SymbolFrame::named("compute")]),
+            ],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         })]);
         let out = aggregate_batches(&[payload]).unwrap();
         let cpu = out.result.cpu.unwrap();
@@ -407,4 +958,527 @@ mod tests {
         assert_eq!(stack.frames[0].function.as_deref(), Some("main"));
         assert_eq!(stack.frames[1].function.as_deref(), Some("compute"));
     }
+
+    #[test]
+    fn test_aggregate_slow_syscall_stacks() {
+        let fsync = |ts, duration_ns, stack_trace: Vec<u64>| {
+            ProfileEvent::Syscall(SyscallEvent {
+                timestamp: ts,
+                pid: 1,
+                tid: 1,
+                syscall_id: 74,
+                duration_ns,
+                return_value: 0,
+                comm: "test".to_string(),
+                fd: Some(3),
+                target: None,
+                bytes: None,
+                stack_symbols: stack_trace
+                    .iter()
+                    .map(|_| Some(vec![SymbolFrame::named("wal_sync")]))
+                    .collect(),
+                stack_trace,
+                stack_refs: vec![],
+                sample_every: 1,
+            })
+        };
+        let payload = make_payload(vec![
+            fsync(1000, 30_000_000, vec![0x1000]),
+            fsync(2000, 10_000_000, vec![0x1000]),
+            fsync(3000, 200, vec![]),
+        ]);
+        let out = aggregate_batches(&[payload]).unwrap();
+        let syscall = out.result.syscall.unwrap();
+        let stats = &syscall.syscalls[&74];
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.slow_stacks.len(), 1);
+        let slow = &stats.slow_stacks[0];
+        assert_eq!(slow.count, 2);
+        assert_eq!(slow.total_duration_ns, 40_000_000);
+        assert_eq!(slow.stack.frames[0].function.as_deref(), Some("wal_sync"));
+    }
+
+    #[test]
+    fn test_aggregate_syscall_summaries() {
+        let summary = |ts, count, sample_every| {
+            let mut latency_histogram = vec![0; 30];
+            latency_histogram[10] = count;
+            ProfileEvent::SyscallSummary(SyscallSummaryEvent {
+                timestamp: ts,
+                pid: 0,
+                syscall_id: 1,
+                count,
+                total_duration_ns: count * 1_500,
+                max_duration_ns: 2_000,
+                min_duration_ns: 1_024,
+                error_count: 0,
+                latency_histogram,
+                sample_every,
+            })
+        };
+        // Two agents: one recording every call, one 1 in 100
+        let payload = make_payload(vec![summary(1000, 5, 1), summary(2000, 3, 100)]);
+        let out = aggregate_batches(&[payload]).unwrap();
+        let syscall = out.result.syscall.unwrap();
+        let write = &syscall.syscalls[&1];
+        assert_eq!(write.name, "write");
+        assert_eq!(write.count, 305);
+        assert_eq!(write.latency_histogram[10], 305);
+        assert_eq!(syscall.total_events, 305);
+        assert_eq!(syscall.sample_every, 100);
+        assert_eq!(syscall.end_time, 2000);
+    }
+
+    #[test]
+    fn test_aggregate_names_syscalls_by_agent_arch() {
+        let summary = |syscall_id| {
+            ProfileEvent::SyscallSummary(SyscallSummaryEvent {
+                timestamp: 1000,
+                pid: 0,
+                syscall_id,
+                count: 2,
+                total_duration_ns: 2_000,
+                max_duration_ns: 1_500,
+                min_duration_ns: 500,
+                error_count: 0,
+                latency_histogram: vec![0; 30],
+                sample_every: 1,
+            })
+        };
+        // fsync is 74 on x86_64 and 82 on aarch64, where 74 is signalfd4
+        // 250 is keyctl on x86_64 and unassigned on aarch64
+        let x86 = make_arch_payload(Arch::X86_64, vec![summary(74), summary(250)]);
+        let arm = make_arch_payload(Arch::Aarch64, vec![summary(82), summary(74), summary(250)]);
+        let out = aggregate_batches(&[x86, arm]).unwrap();
+        let syscall = out.result.syscall.unwrap();
+        let fsync = &syscall.syscalls[&74];
+        assert_eq!(fsync.name, "fsync");
+        assert_eq!(fsync.count, 4);
+        assert_eq!(syscall.syscalls[&289].name, "signalfd4");
+        assert!(!syscall.syscalls.contains_key(&82));
+        assert_eq!(syscall.syscalls[&250].name, "keyctl");
+        assert_eq!(syscall.syscalls[&250].count, 2);
+        assert_eq!(
+            syscall.syscalls[&(AARCH64_ONLY_SYSCALL_BASE + 250)].name,
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn test_aggregate_lock_releases_as_holders() {
+        let ProfileEvent::Lock(wait) = lock_ev(1000, 0x1000, 300, vec![0x4000]) else {
+            unreachable!()
+        };
+        let release = LockEvent {
+            tid: 2,
+            hold_time_ns: 900,
+            wait_time_ns: 0,
+            stack_trace: vec![0x5000],
+            kind: LockEventKind::Release,
+            ..wait.clone()
+        };
+        let payload = make_payload(vec![
+            ProfileEvent::Lock(LockEvent {
+                waker_tid: Some(2),
+                ..wait
+            }),
+            ProfileEvent::Lock(release),
+        ]);
+        let out = aggregate_batches(&[payload]).unwrap();
+        let json = out.result.to_json().lock.unwrap();
+        assert_eq!(json.holders.len(), 1);
+        assert_eq!(json.holders[0].max_hold_ns, 900);
+
+        let lock = out.result.lock.unwrap();
+        assert_eq!(lock.total_events, 2);
+        assert_eq!(lock.contentions.len(), 1);
+        let held = &lock.holders[&(0x1000, Stack::from_ips(&[0x5000]))];
+        assert_eq!(held.total_hold_ns, 900);
+    }
+
+    #[test]
+    fn test_aggregate_groups_locks_by_name() {
+        let named = |ts, addr, wait_ns, name: &str| {
+            let ProfileEvent::Lock(ev) = lock_ev(ts, addr, wait_ns, vec![0x4000]) else {
+                unreachable!()
+            };
+            ProfileEvent::Lock(LockEvent {
+                lock_name: Some(name.to_string()),
+                ..ev
+            })
+        };
+        // One global, at a different address in each process
+        let payload = make_payload(vec![
+            named(1000, 0x1000, 300, "app::CACHE"),
+            named(2000, 0x9000, 700, "app::CACHE"),
+            lock_ev(3000, 0x2000, 100, vec![0x4000]),
+        ]);
+        let out = aggregate_batches(&[payload]).unwrap();
+        let json = out.result.to_json().lock.unwrap();
+        assert_eq!(json.locks.len(), 2);
+        assert_eq!(json.locks[0].name, "app::CACHE");
+        assert_eq!(json.locks[0].lock_addrs, vec![0x1000, 0x9000]);
+        assert_eq!(json.locks[0].contention.total_wait_ns, 1000);
+        assert_eq!(json.locks[1].name, "0x2000");
+        assert_eq!(json.contentions[0].lock_name.as_deref(), Some("app::CACHE"));
+    }
+
+    #[test]
+    fn test_aggregate_kernel_locks_beside_user_locks() {
+        let kernel_ev = |ts, wait_time_ns| {
+            ProfileEvent::KernelLock(KernelLockEvent {
+                timestamp: ts,
+                pid: 1,
+                tid: 1,
+                lock_addr: 0xffff888000003000,
+                flags: LCB_F_SPIN,
+                wait_time_ns,
+                stack_trace: vec![0x4000, 0xffffffff81000000],
+                comm: "test".to_string(),
+                stack_symbols: vec![],
+                stack_refs: vec![],
+            })
+        };
+        let payload = make_payload(vec![
+            lock_ev(1000, 0x1000, 300, vec![0x4000]),
+            kernel_ev(2000, 40),
+            kernel_ev(3000, 60),
+        ]);
+        let mut out = aggregate_batches(&[payload]).unwrap();
+        filter_by_type(&mut out.result, "lock");
+        assert!(out.result.lock.is_some());
+
+        let json = out.result.to_json().kernel_lock.unwrap();
+        assert_eq!(json.total_events, 2);
+        assert_eq!(json.start_time, 2000);
+        assert_eq!(json.contentions.len(), 1);
+        assert_eq!(json.contentions[0].lock_type, "spinlock");
+        assert_eq!(json.contentions[0].total_wait_ns, 100);
+
+        filter_by_type(&mut out.result, "syscall");
+        assert!(out.result.kernel_lock.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_block_io_by_device() {
+        let request = |ts, device: &str, op, latency_ns| {
+            ProfileEvent::BlockIo(BlockIoEvent {
+                timestamp: ts,
+                pid: 1,
+                tid: 1,
+                comm: "test".to_string(),
+                dev: 8 << 20,
+                device: Some(device.to_string()),
+                op,
+                sector: 0,
+                bytes: 4096,
+                latency_ns,
+                queue_depth: 1,
+                error: 0,
+            })
+        };
+        // Two agents writing to devices of the same name
+        let p1 = make_payload(vec![
+            request(1000, "sda", BlockIoOp::Write, 200_000),
+            request(2000, "sda", BlockIoOp::Read, 50_000),
+        ]);
+        let p2 = make_payload(vec![request(3000, "sda", BlockIoOp::Write, 600_000)]);
+        let mut out =
+            aggregate_batches(&[p1, p2, make_payload(vec![cpu(4000, vec![0x1000], vec![])])])
+                .unwrap();
+        filter_by_type(&mut out.result, "block-io");
+        assert!(out.result.cpu.is_none());
+
+        let block_io = out.result.to_json().block_io.unwrap();
+        assert_eq!(block_io.total_events, 3);
+        assert_eq!(block_io.end_time, 3000);
+        let write = &block_io.devices["sda"][&BlockIoOp::Write];
+        assert_eq!(write.count, 2);
+        assert_eq!(write.avg_latency_ns(), 400_000);
+        assert_eq!(block_io.processes[&1].count, 3);
+    }
+
+    #[test]
+    fn test_aggregate_sched_by_process() {
+        let wait = |ts, pid, delay_ns| {
+            ProfileEvent::Sched(SchedEvent {
+                timestamp: ts,
+                pid,
+                tid: pid,
+                comm: format!("proc{}", pid),
+                cpu_id: 0,
+                delay_ns,
+                preempted: false,
+                waker_pid: 1,
+                waker_tid: 1,
+                cgroup: Some("/app.slice".to_string()),
+                preemptor_tid: None,
+                preemptor_comm: None,
+                stack_trace: vec![],
+                stack_symbols: vec![],
+                stack_refs: vec![],
+            })
+        };
+        let p1 = make_payload(vec![wait(1000, 10, 2_000), wait(2000, 10, 4_000)]);
+        let p2 = make_payload(vec![wait(3000, 11, 9_000)]);
+        let mut out = aggregate_batches(&[p1, p2]).unwrap();
+        filter_by_type(&mut out.result, "sched");
+
+        let sched = out.result.to_json().sched.unwrap();
+        assert_eq!(sched.total_events, 3);
+        assert_eq!(sched.start_time, 1000);
+        assert_eq!(sched.end_time, 3000);
+        assert_eq!(sched.processes[&10].runq.avg_delay_ns(), 3_000);
+        assert_eq!(sched.cgroups["/app.slice"].count, 3);
+
+        filter_by_type(&mut out.result, "cpu");
+        assert!(out.result.sched.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_tcp_by_endpoint() {
+        let event = |ts, kind, bytes_received| {
+            ProfileEvent::Tcp(TcpEvent {
+                timestamp: ts,
+                pid: 5,
+                tid: 5,
+                comm: "api".to_string(),
+                kind,
+                remote_addr: "10.1.2.3".parse().unwrap(),
+                remote_port: 5432,
+                local_port: 40000,
+                passive: false,
+                duration_ns: 2_000_000,
+                bytes_sent: 10,
+                bytes_received,
+                retransmits: 0,
+                srtt_us: 500,
+            })
+        };
+        let p1 = make_payload(vec![
+            event(1000, TcpEventKind::Connect, 0),
+            event(2000, TcpEventKind::Close, 700),
+        ]);
+        let p2 = make_payload(vec![event(3000, TcpEventKind::Close, 300)]);
+        let mut out = aggregate_batches(&[p1, p2]).unwrap();
+        filter_by_type(&mut out.result, "tcp");
+
+        let tcp = out.result.to_json().tcp.unwrap();
+        assert_eq!(tcp.total_events, 3);
+        assert_eq!(tcp.end_time, 3000);
+        let endpoint = &tcp.processes[&5].endpoints["10.1.2.3:5432"];
+        assert_eq!(endpoint.connects, 1);
+        assert_eq!(endpoint.closed, 2);
+        assert_eq!(endpoint.bytes_received, 1000);
+
+        filter_by_type(&mut out.result, "sched");
+        assert!(out.result.tcp.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_memory_faults_and_reclaim() {
+        let fault = |ts, symbol: &str| {
+            ProfileEvent::PageFault(PageFaultEvent {
+                timestamp: ts,
+                pid: 9,
+                tid: 9,
+                comm: "java".to_string(),
+                address: 0x7f00_0000_0000,
+                ip: 0x401000,
+                error_code: 6,
+                kernel: false,
+                weight: 4,
+                stack_trace: vec![0x401000],
+                stack_symbols: vec![Some(vec![SymbolFrame::named(symbol)])],
+                stack_refs: vec![],
+            })
+        };
+        let reclaim = ProfileEvent::Reclaim(ReclaimEvent {
+            timestamp: 500,
+            pid: 9,
+            tid: 10,
+            comm: "java".to_string(),
+            stall_ns: 4_000_000,
+            nr_reclaimed: 64,
+            order: 2,
+            memcg: false,
+        });
+        let p1 = make_payload(vec![fault(1000, "memset"), reclaim]);
+        let p2 = make_payload(vec![fault(3000, "memset")]);
+        let mut out = aggregate_batches(&[p1, p2]).unwrap();
+        filter_by_type(&mut out.result, "memory");
+
+        let memory = out.result.to_json().memory.unwrap();
+        assert_eq!(memory.total_events, 3);
+        assert_eq!(memory.start_time, 500);
+        assert_eq!(memory.end_time, 3000);
+        assert_eq!(memory.total_faults, 8);
+        assert_eq!(memory.fault_stacks.len(), 1);
+        let top = &memory.fault_stacks[0];
+        assert_eq!(top.stack.frames[0].function.as_deref(), Some("memset"));
+        assert_eq!(top.count, 8);
+        assert_eq!(memory.processes[0].reclaim.max_stall_ns, 4_000_000);
+
+        filter_by_type(&mut out.result, "cpu");
+        assert!(out.result.memory.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_probe_calls() {
+        let call = |ts, probe: &str, duration_ns: Option<u64>| {
+            ProfileEvent::Probe(ProbeEvent {
+                timestamp: ts,
+                pid: 5,
+                tid: 5,
+                comm: "app".to_string(),
+                probe: probe.to_string(),
+                duration_ns,
+                stack_trace: vec![0x401000],
+                stack_symbols: vec![Some(vec![SymbolFrame::named("handle_request")])],
+                stack_refs: vec![],
+            })
+        };
+        let p1 = make_payload(vec![
+            call(1000, "kprobe:vfs_read", Some(2_000)),
+            call(1500, "tracepoint:net:netif_rx", None),
+        ]);
+        let p2 = make_payload(vec![call(2000, "kprobe:vfs_read", Some(6_000))]);
+        let mut out = aggregate_batches(&[p1, p2]).unwrap();
+        filter_by_type(&mut out.result, "probe");
+
+        let probe = out.result.to_json().probe.unwrap();
+        assert_eq!(probe.total_events, 3);
+        assert_eq!(probe.start_time, 1000);
+        assert_eq!(probe.end_time, 2000);
+        let vfs_read = &probe.probes["kprobe:vfs_read"];
+        assert_eq!(vfs_read.count, 2);
+        assert_eq!(vfs_read.max_duration_ns, 6_000);
+        assert_eq!(vfs_read.stacks[0].count, 2);
+        assert_eq!(probe.probes["tracepoint:net:netif_rx"].count, 1);
+
+        filter_by_type(&mut out.result, "memory");
+        assert!(out.result.probe.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_usdt_spans_with_cpu() {
+        let hit = |ts, name: &str, span_start: Option<u64>| {
+            ProfileEvent::Usdt(UsdtEvent {
+                timestamp: ts,
+                pid: 1,
+                tid: 1,
+                comm: "test".to_string(),
+                probe: format!("app:{}", name),
+                args: vec![7],
+                span: span_start.map(|start| UsdtSpan {
+                    name: "app:req__start..req__done".to_string(),
+                    start,
+                }),
+            })
+        };
+        let p1 = make_payload(vec![
+            hit(1000, "req__start", None),
+            cpu(3000, vec![0x401000], vec![]),
+        ]);
+        let p2 = make_payload(vec![
+            hit(5000, "req__done", Some(1000)),
+            cpu(6000, vec![0x402000], vec![]),
+        ]);
+        let mut out = aggregate_batches(&[p1, p2]).unwrap();
+        filter_by_type(&mut out.result, "probe");
+
+        let probe = out.result.to_json().probe.unwrap();
+        assert_eq!(probe.total_events, 2);
+        assert_eq!(probe.usdt["app:req__start"].count, 1);
+        assert_eq!(probe.usdt["app:req__done"].args[0].max, 7);
+        let span = &probe.spans["app:req__start..req__done"];
+        assert_eq!(span.count, 1);
+        assert_eq!(span.max_duration_ns, 4000);
+        assert_eq!(span.cpu_samples, 1);
+        assert_eq!(span.cpu_stacks[0].stack.frames[0].ip, 0x401000);
+        assert!(out.result.cpu.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_by_labels() {
+        fn labelled(mut event: ProfileEvent, pairs: &[(&str, &str)]) -> ProfileEvent {
+            let labels = pairs
+                .iter()
+                .map(|&(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            match &mut event {
+                ProfileEvent::CpuSample(s) => s.labels = labels,
+                ProfileEvent::Lock(ev) => ev.labels = labels,
+                _ => unreachable!(),
+            }
+            event
+        }
+        let payload = make_payload(vec![
+            labelled(
+                cpu(1, vec![0x1], vec![]),
+                &[("endpoint", "/a"), ("tenant", "x")],
+            ),
+            labelled(
+                cpu(2, vec![0x2], vec![]),
+                &[("endpoint", "/a"), ("tenant", "y")],
+            ),
+            labelled(
+                cpu(3, vec![0x3], vec![]),
+                &[("endpoint", "/b"), ("tenant", "x")],
+            ),
+            cpu(4, vec![0x4], vec![]),
+            labelled(lock_ev(5, 0x10, 700, vec![0x5]), &[("endpoint", "/b")]),
+            ProfileEvent::Syscall(SyscallEvent {
+                timestamp: 6,
+                pid: 1,
+                tid: 1,
+                syscall_id: 0,
+                duration_ns: 100,
+                return_value: 0,
+                comm: "test".to_string(),
+                fd: None,
+                target: None,
+                bytes: None,
+                stack_trace: vec![],
+                stack_symbols: vec![],
+                stack_refs: vec![],
+                sample_every: 1,
+            }),
+        ]);
+
+        let query = LabelQuery::from_args(&[], Some("endpoint".to_string())).unwrap();
+        let result = aggregate_batches_with_labels(std::slice::from_ref(&payload), &query)
+            .unwrap()
+            .result;
+        assert_eq!(result.total_events, 6);
+        let json = result.to_json().labels.unwrap();
+        assert_eq!(json.key, "endpoint");
+        let groups: Vec<_> = json
+            .groups
+            .iter()
+            .map(|g| {
+                (
+                    g.value.as_deref(),
+                    g.stats.cpu_samples,
+                    g.stats.lock_wait_ns,
+                )
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![(Some("/a"), 2, 0), (Some("/b"), 1, 700), (None, 1, 0)]
+        );
+
+        // Only CPU and lock events carrying every filtered label are kept
+        let query = LabelQuery::from_args(&["tenant=x".to_string()], None).unwrap();
+        let result = aggregate_batches_with_labels(&[payload], &query)
+            .unwrap()
+            .result;
+        assert_eq!(result.total_events, 2);
+        assert_eq!(result.cpu.unwrap().total_samples, 2);
+        assert!(result.lock.is_none() && result.syscall.is_none());
+        assert!(result.labels.is_none());
+
+        assert!(LabelQuery::from_args(&["tenant".to_string()], None).is_err());
+    }
 }
@@ -26,6 +26,17 @@ pub fn grpc_auth_failure(reason: &str) {
     );
 }
 
+/// Log an admin HTTP request refused for lack of a valid Bearer token.
+pub fn http_auth_failure(path: &str, reason: &str) {
+    warn!(
+        target: AUDIT_TARGET,
+        event = "http_auth_failure",
+        result = "denied",
+        path = %path,
+        reason = %reason,
+    );
+}
+
 /// Log admin HTTP request (sensitive endpoints: metrics, readiness).
 pub fn admin_http_request(path: &str, status: u16) {
     info!(
@@ -21,6 +21,10 @@ pub struct AggregatorConfig {
 
     /// Optional bearer token for gRPC authentication
     pub auth_token: Option<String>,
+
+    /// Directory of uploaded debug files for deferred symbolization
+    /// (None = symbol store disabled)
+    pub symbol_dir: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +53,7 @@ impl Default for AggregatorConfig {
                 .unwrap_or(10_000),
             max_message_size: max_message_mb * 1024 * 1024,
             auth_token: std::env::var("APERTURE_AUTH_TOKEN").ok(),
+            symbol_dir: std::env::var("APERTURE_SYMBOL_DIR").ok(),
         }
     }
 }
@@ -70,10 +70,14 @@ pub async fn export_json(
 /// - speedscope (`speedscope collapsed.txt`)
 /// - Grafana Pyroscope ingestion
 /// - pprof conversion tools
+///
+/// Inlined functions appear as their own frames with the `_[i]` suffix. With
+/// `with_lines`, each frame is annotated with its `(file:line)` when known.
 pub async fn export_collapsed(
     buffer: &InMemoryBuffer,
     store: Option<&Arc<dyn BatchStore>>,
     limit: u32,
+    with_lines: bool,
 ) -> Response<Body> {
     let payloads = fetch_payloads(buffer, store, limit).await;
 
@@ -108,11 +112,7 @@ pub async fn export_collapsed(
             .frames
             .iter()
             .rev()
-            .map(|f| {
-                f.function
-                    .clone()
-                    .unwrap_or_else(|| format!("0x{:x}", f.ip))
-            })
+            .map(|f| f.folded_name(with_lines))
             .collect();
         if !frames.is_empty() {
             collapsed.push_str(&frames.join(";"));
@@ -9,6 +9,7 @@ use aperture_aggregator::{
     config::{AggregatorConfig, StorageConfig},
     server::grpc,
     storage::BatchStore,
+    symbols::{self, SymbolStore},
 };
 use std::sync::Arc;
 use tokio::signal;
@@ -59,6 +60,12 @@ async fn main() -> Result<()> {
     let config = load_config();
     info!("Starting Aperture aggregator on {}", config.listen_addr);
 
+    if let Some(ref dir) = config.symbol_dir {
+        let store = SymbolStore::open(dir).context("Failed to open symbol store")?;
+        info!("Symbol store enabled: {}", dir);
+        symbols::install(Arc::new(store));
+    }
+
     let buffer = Arc::new(InMemoryBuffer::new(config.max_buffer_batches));
     #[allow(unused_mut)]
     let mut service = grpc::AggregatorService::new(buffer.clone());
@@ -96,10 +103,15 @@ async fn main() -> Result<()> {
         .context("Invalid admin listen address")?;
 
     let store_for_admin = store_handle.clone();
+    let auth_token = config.auth_token.clone();
     let admin_handle = tokio::spawn(async move {
-        if let Err(e) =
-            aperture_aggregator::server::http::serve_admin(admin_addr, buffer, store_for_admin)
-                .await
+        if let Err(e) = aperture_aggregator::server::http::serve_admin(
+            admin_addr,
+            buffer,
+            store_for_admin,
+            auth_token,
+        )
+        .await
         {
             tracing::error!("Admin HTTP server error: {}", e);
         }
@@ -1,5 +1,5 @@
 //! REST API for the web UI.
-//! Serves /api/aggregate, /api/diff, /api/batches with JSON and CORS.
+//! Serves /api/aggregate, /api/diff, /api/batches, /api/symbols with JSON and CORS.
 
 use crate::aggregate;
 use crate::alerts::{AlertMetric, AlertStore, MetricSnapshot, Operator, Severity};
@@ -7,7 +7,10 @@ use crate::buffer::InMemoryBuffer;
 use crate::storage::BatchStore;
 use crate::MAX_AGGREGATE_BATCH_LIMIT;
 use aperture_shared::types::diff;
-use aperture_shared::types::profile::{LockProfile, Profile, SyscallProfile};
+use aperture_shared::types::profile::{
+    BlockIoProfile, LockProfile, Profile, SchedProfile, SyscallProfile,
+};
+use hyper::body::HttpBody;
 use hyper::{body::to_bytes, Body, Request, Response, StatusCode};
 use std::sync::Arc;
 use std::time::Duration;
@@ -26,7 +29,10 @@ fn cors_preflight() -> Response<Body> {
         .status(StatusCode::NO_CONTENT)
         .header("Access-Control-Allow-Origin", "*")
         .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
-        .header("Access-Control-Allow-Headers", "Content-Type")
+        .header(
+            "Access-Control-Allow-Headers",
+            "Content-Type, Authorization",
+        )
         .header("Access-Control-Max-Age", "86400")
         .body(Body::empty())
         .expect("response build")
@@ -38,6 +44,44 @@ fn add_cors_headers(mut res: Response<Body>) -> Response<Body> {
     res
 }
 
+/// Largest debug file accepted by `POST /api/symbols`
+const MAX_SYMBOL_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
+
+/// Stream an upload body into `file`, stopping once it exceeds
+/// `MAX_SYMBOL_UPLOAD_BYTES`
+async fn receive_upload(
+    body: &mut Body,
+    file: &mut tokio::fs::File,
+) -> Result<(), (String, StatusCode)> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut len = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| (e.to_string(), StatusCode::BAD_REQUEST))?;
+        len += chunk.len();
+        if len > MAX_SYMBOL_UPLOAD_BYTES {
+            return Err((
+                format!("debug file exceeds {} bytes", MAX_SYMBOL_UPLOAD_BYTES),
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ));
+        }
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| (e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| (e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn symbols_not_configured() -> Response<Body> {
+    let body = serde_json::json!({
+        "error": "symbol store not configured (set APERTURE_SYMBOL_DIR)"
+    })
+    .to_string();
+    add_cors_headers(json_response(&body, StatusCode::SERVICE_UNAVAILABLE))
+}
+
 #[derive(serde::Deserialize)]
 struct AggregateRequest {
     agent_id: Option<String>,
@@ -45,6 +89,11 @@ struct AggregateRequest {
     time_end_ns: Option<i64>,
     limit: Option<u32>,
     event_type: Option<String>,
+    /// Keep only CPU samples and lock events with all of these labels
+    #[serde(default)]
+    labels: aperture_shared::types::events::Labels,
+    /// Break CPU samples and lock waits down by this label
+    group_by_label: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -64,6 +113,7 @@ pub async fn handle_api(
     buffer: &InMemoryBuffer,
     store: Option<Arc<dyn BatchStore>>,
     alert_store: &AlertStore,
+    auth_token: Option<&str>,
 ) -> Result<Response<Body>, hyper::Error> {
     if req.method() == hyper::Method::OPTIONS {
         return Ok(cors_preflight());
@@ -201,7 +251,11 @@ pub async fn handle_api(
                 .unwrap_or_default()
         };
 
-        let out = match aggregate::aggregate_batches(&payloads) {
+        let labels = aggregate::LabelQuery {
+            filter: api_req.labels,
+            group_by: api_req.group_by_label.filter(|k| !k.is_empty()),
+        };
+        let out = match aggregate::aggregate_batches_with_labels(&payloads, &labels) {
             Ok(o) => o,
             Err(e) => {
                 let body = serde_json::json!({ "error": e.to_string() }).to_string();
@@ -339,8 +393,22 @@ pub async fn handle_api(
                 let d = diff::diff_syscall(&b, &c);
                 serde_json::to_string(&d).unwrap()
             }
+            "block-io" => {
+                let b = baseline.block_io.unwrap_or_else(|| BlockIoProfile::new(0));
+                let c = comparison
+                    .block_io
+                    .unwrap_or_else(|| BlockIoProfile::new(0));
+                let d = diff::diff_block_io(&b, &c);
+                serde_json::to_string(&d).unwrap()
+            }
+            "sched" => {
+                let b = baseline.sched.unwrap_or_else(|| SchedProfile::new(0));
+                let c = comparison.sched.unwrap_or_else(|| SchedProfile::new(0));
+                let d = diff::diff_sched(&b, &c);
+                serde_json::to_string(&d).unwrap()
+            }
             _ => {
-                let body = serde_json::json!({ "result_json": "", "error": format!("event_type must be cpu, lock, or syscall, got {}", event_type) }).to_string();
+                let body = serde_json::json!({ "result_json": "", "error": format!("event_type must be cpu, lock, syscall, block-io, or sched, got {}", event_type) }).to_string();
                 let res = add_cors_headers(json_response(&body, StatusCode::BAD_REQUEST));
                 return Ok(res);
             }
@@ -468,6 +536,72 @@ pub async fn handle_api(
         return Ok(add_cors_headers(json_response(&body, StatusCode::OK)));
     }
 
+    // ── Symbol store endpoints ────────────────────────────────────────────
+
+    // GET /api/symbols — list build IDs with an uploaded debug file
+    if path == "/api/symbols" && method == hyper::Method::GET {
+        let Some(symbols) = crate::symbols::global() else {
+            return Ok(symbols_not_configured());
+        };
+        let (body, status) = match symbols.build_ids() {
+            Ok(ids) => (
+                serde_json::json!({ "build_ids": ids, "error": "" }),
+                StatusCode::OK,
+            ),
+            Err(e) => (
+                serde_json::json!({ "build_ids": [], "error": e.to_string() }),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        };
+        return Ok(add_cors_headers(json_response(&body.to_string(), status)));
+    }
+
+    // POST /api/symbols[?build_id=<hex>] — upload a debug file (raw ELF body)
+    if path == "/api/symbols" && method == hyper::Method::POST {
+        if let Err(reason) =
+            crate::server::auth::check_http_bearer(req.headers(), &path, auth_token)
+        {
+            let body = serde_json::json!({ "build_id": "", "error": reason }).to_string();
+            return Ok(add_cors_headers(json_response(
+                &body,
+                StatusCode::UNAUTHORIZED,
+            )));
+        }
+        let Some(symbols) = crate::symbols::global() else {
+            return Ok(symbols_not_configured());
+        };
+        let mut expected = None::<String>;
+        if let Some(q) = req.uri().query() {
+            for part in q.split('&') {
+                if let Some(("build_id", v)) = part.split_once('=') {
+                    expected = Some(v.to_string());
+                }
+            }
+        }
+        let mut body = req.into_body();
+        let upload = symbols.upload_path();
+        let received = match tokio::fs::File::create(&upload).await {
+            Ok(mut file) => receive_upload(&mut body, &mut file).await,
+            Err(e) => Err((e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)),
+        };
+        if let Err((error, status)) = received {
+            let _ = tokio::fs::remove_file(&upload).await;
+            let body = serde_json::json!({ "build_id": "", "error": error }).to_string();
+            return Ok(add_cors_headers(json_response(&body, status)));
+        }
+        let (body, status) = match symbols.insert_file(&upload, expected.as_deref()) {
+            Ok(build_id) => (
+                serde_json::json!({ "build_id": build_id, "error": "" }),
+                StatusCode::CREATED,
+            ),
+            Err(e) => (
+                serde_json::json!({ "build_id": "", "error": format!("{:#}", e) }),
+                StatusCode::BAD_REQUEST,
+            ),
+        };
+        return Ok(add_cors_headers(json_response(&body.to_string(), status)));
+    }
+
     // ── Export endpoints ──────────────────────────────────────────────────
 
     // GET /api/export/json — download aggregated profile as JSON
@@ -497,18 +631,23 @@ pub async fn handle_api(
     // GET /api/export/collapsed — download collapsed-stack format
     if path == "/api/export/collapsed" && method == hyper::Method::GET {
         let mut limit = crate::MAX_AGGREGATE_BATCH_LIMIT;
+        let mut with_lines = false;
         if let Some(q) = req.uri().query() {
             for part in q.split('&') {
                 if let Some((k, v)) = part.split_once('=') {
-                    if k == "limit" {
-                        if let Ok(n) = v.parse::<u32>() {
-                            limit = n.min(crate::MAX_AGGREGATE_BATCH_LIMIT);
+                    match k {
+                        "limit" => {
+                            if let Ok(n) = v.parse::<u32>() {
+                                limit = n.min(crate::MAX_AGGREGATE_BATCH_LIMIT);
+                            }
                         }
+                        "lines" => with_lines = v == "1" || v == "true",
+                        _ => {}
                     }
                 }
             }
         }
-        let res = crate::export::export_collapsed(buffer, store.as_ref(), limit).await;
+        let res = crate::export::export_collapsed(buffer, store.as_ref(), limit, with_lines).await;
         return Ok(res);
     }
 
@@ -3,6 +3,34 @@
 use crate::audit;
 use tonic::{Request, Status};
 
+/// Check the `authorization` header of an admin HTTP request against the
+/// token gRPC clients must present.
+///
+/// Passes when `expected_token` is `None`; otherwise returns why the request
+/// is refused, after logging it for `path`.
+pub fn check_http_bearer(
+    headers: &hyper::HeaderMap,
+    path: &str,
+    expected_token: Option<&str>,
+) -> Result<(), &'static str> {
+    let Some(expected) = expected_token else {
+        return Ok(());
+    };
+    let reason = match headers.get(hyper::header::AUTHORIZATION) {
+        None => "Missing authorization header",
+        Some(val) => match val.to_str() {
+            Err(_) => "Invalid authorization header encoding",
+            Ok(val) => match val.strip_prefix("Bearer ") {
+                None => "Missing Bearer prefix",
+                Some(token) if token == expected => return Ok(()),
+                Some(_) => "Invalid token",
+            },
+        },
+    };
+    audit::http_auth_failure(path, reason);
+    Err(reason)
+}
+
 /// Create a tonic interceptor that validates bearer tokens.
 ///
 /// If `expected_token` is `None`, authentication is disabled and all requests pass.
@@ -46,6 +74,23 @@ pub fn make_auth_interceptor(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_http_bearer() {
+        let mut headers = hyper::HeaderMap::new();
+        assert!(check_http_bearer(&headers, "/api/symbols", None).is_ok());
+        assert_eq!(
+            check_http_bearer(&headers, "/api/symbols", Some("secret123")),
+            Err("Missing authorization header")
+        );
+        headers.insert("authorization", "Bearer wrong".parse().unwrap());
+        assert_eq!(
+            check_http_bearer(&headers, "/api/symbols", Some("secret123")),
+            Err("Invalid token")
+        );
+        headers.insert("authorization", "Bearer secret123".parse().unwrap());
+        assert!(check_http_bearer(&headers, "/api/symbols", Some("secret123")).is_ok());
+    }
+
     #[test]
     fn test_auth_disabled() {
         let interceptor = make_auth_interceptor(None);
@@ -264,7 +264,11 @@ impl Aggregator for AggregatorService {
             }
         };
 
-        let out = match crate::aggregate::aggregate_batches(&payloads) {
+        let labels = crate::aggregate::LabelQuery {
+            filter: req.labels.into_iter().collect(),
+            group_by: req.group_by_label.filter(|k| !k.is_empty()),
+        };
+        let out = match crate::aggregate::aggregate_batches_with_labels(&payloads, &labels) {
             Ok(o) => o,
             Err(e) => {
                 return Ok(Response::new(AggregateResponse {
@@ -349,7 +353,9 @@ impl Aggregator for AggregatorService {
         let comparison = comparison_out.result;
 
         use aperture_shared::types::diff;
-        use aperture_shared::types::profile::{LockProfile, Profile, SyscallProfile};
+        use aperture_shared::types::profile::{
+            BlockIoProfile, LockProfile, Profile, SchedProfile, SyscallProfile,
+        };
 
         let json = match req.event_type.as_str() {
             "cpu" => {
@@ -370,13 +376,27 @@ impl Aggregator for AggregatorService {
                 let d = diff::diff_syscall(&b, &c);
                 serde_json::to_string(&d)
             }
+            "block-io" => {
+                let b = baseline.block_io.unwrap_or_else(|| BlockIoProfile::new(0));
+                let c = comparison
+                    .block_io
+                    .unwrap_or_else(|| BlockIoProfile::new(0));
+                let d = diff::diff_block_io(&b, &c);
+                serde_json::to_string(&d)
+            }
+            "sched" => {
+                let b = baseline.sched.unwrap_or_else(|| SchedProfile::new(0));
+                let c = comparison.sched.unwrap_or_else(|| SchedProfile::new(0));
+                let d = diff::diff_sched(&b, &c);
+                serde_json::to_string(&d)
+            }
             other => {
                 return Ok(Response::new(DiffResponse {
                     result_json: String::new(),
                     error: format!(
-                        "event_type must be 'cpu', 'lock', or 'syscall', got '{}'",
-                        other
-                    ),
+                    "event_type must be 'cpu', 'lock', 'syscall', 'block-io', or 'sched', got '{}'",
+                    other
+                ),
                 }))
             }
         }
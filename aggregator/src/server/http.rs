@@ -12,24 +12,31 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 /// Start the admin HTTP server: /healthz, /readyz, /metrics, and /api/*.
+///
+/// Uploads to the symbol store need `auth_token`, as gRPC pushes do.
 pub async fn serve_admin(
     addr: SocketAddr,
     buffer: Arc<InMemoryBuffer>,
     store: Option<Arc<dyn BatchStore>>,
+    auth_token: Option<String>,
 ) -> Result<(), hyper::Error> {
     let alert_store = Arc::new(AlertStore::new());
+    let auth_token: Option<Arc<str>> = auth_token.map(Into::into);
     let make_svc = make_service_fn(move |_| {
         let buffer = buffer.clone();
         let store = store.clone();
         let alert_store = alert_store.clone();
+        let auth_token = auth_token.clone();
         async move {
             Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
                 let buffer = buffer.clone();
                 let store = store.clone();
                 let alert_store = alert_store.clone();
+                let auth_token = auth_token.clone();
                 async move {
                     if req.uri().path().starts_with("/api") {
-                        api::handle_api(req, &buffer, store, &alert_store).await
+                        api::handle_api(req, &buffer, store, &alert_store, auth_token.as_deref())
+                            .await
                     } else {
                         handle(req, &buffer)
                     }
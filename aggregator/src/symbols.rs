@@ -0,0 +1,397 @@
+//! Symbol store for deferred symbolization
+//!
+//! Agents started with `--symbolize deferred` ship user frames as
+//! `(build_id, file_offset)` pairs instead of names. Debug files (or unstripped
+//! binaries) are uploaded once per build ID via `POST /api/symbols` and kept on
+//! disk in the same layout as `/usr/lib/debug/.build-id`:
+//!
+//! ```text
+//! <APERTURE_SYMBOL_DIR>/ab/cdef0123....debug
+//! ```
+//!
+//! Frames are resolved when batches are aggregated; results are cached per
+//! build ID so repeated queries do not re-parse DWARF.
+
+use anyhow::{Context, Result};
+use aperture_shared::types::events::{FrameRef, ProfileEvent, Symbol};
+use aperture_shared::types::profile::Frame;
+use blazesym::symbolize::source::{Elf, Source};
+use blazesym::symbolize::{CodeInfo, Input, Sym, Symbolized, Symbolizer};
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Process-wide store, installed at startup when `APERTURE_SYMBOL_DIR` is set
+static GLOBAL_STORE: OnceCell<Arc<SymbolStore>> = OnceCell::new();
+
+/// Install the process-wide symbol store used by aggregation and the admin API.
+pub fn install(store: Arc<SymbolStore>) {
+    if GLOBAL_STORE.set(store).is_err() {
+        tracing::warn!("Symbol store already installed; ignoring");
+    }
+}
+
+/// The process-wide symbol store, if one was installed.
+pub fn global() -> Option<&'static SymbolStore> {
+    GLOBAL_STORE.get().map(|s| s.as_ref())
+}
+
+/// Debug files indexed by build ID, with a per-build-ID cache of resolved symbols.
+pub struct SymbolStore {
+    dir: PathBuf,
+    /// build ID -> (file offset -> symbol)
+    cache: Mutex<HashMap<String, HashMap<u64, Option<Symbol>>>>,
+}
+
+impl SymbolStore {
+    /// Open (and create if needed) a symbol directory.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("create symbol dir {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Path a debug file for `build_id` is stored at. None if the build ID is
+    /// not a plausible lowercase hex string (also rules out path traversal).
+    fn path_for(&self, build_id: &str) -> Option<PathBuf> {
+        if !is_valid_build_id(build_id) {
+            return None;
+        }
+        let (prefix, rest) = build_id.split_at(2);
+        Some(self.dir.join(prefix).join(format!("{}.debug", rest)))
+    }
+
+    /// True when a debug file has been uploaded for `build_id`.
+    pub fn contains(&self, build_id: &str) -> bool {
+        self.path_for(build_id).is_some_and(|p| p.is_file())
+    }
+
+    /// Build IDs with an uploaded debug file, sorted.
+    pub fn build_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for prefix in std::fs::read_dir(&self.dir)? {
+            let prefix = prefix?;
+            if !prefix.file_type()?.is_dir() {
+                continue;
+            }
+            let prefix_name = prefix.file_name().to_string_lossy().into_owned();
+            for entry in std::fs::read_dir(prefix.path())? {
+                let name = entry?.file_name().to_string_lossy().into_owned();
+                if let Some(rest) = name.strip_suffix(".debug") {
+                    let id = format!("{}{}", prefix_name, rest);
+                    if is_valid_build_id(&id) {
+                        ids.push(id);
+                    }
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Store an uploaded ELF file under its build ID and return that ID.
+    ///
+    /// The build ID is read from the file's `.note.gnu.build-id`; if the
+    /// uploader named one (`expected`), it must match. Replacing an existing
+    /// file drops the cached names for that build ID.
+    pub fn insert(&self, bytes: &[u8], expected: Option<&str>) -> Result<String> {
+        let tmp = self.upload_path();
+        std::fs::write(&tmp, bytes).context("write uploaded debug file")?;
+        self.insert_file(&tmp, expected)
+    }
+
+    /// Fresh path within the store to receive an upload at, so that
+    /// [`SymbolStore::insert_file`] can move it into place
+    pub fn upload_path(&self) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        self.dir.join(format!(".upload-{}.tmp", nanos))
+    }
+
+    /// Store the debug file received at `tmp` (see [`SymbolStore::insert`]);
+    /// `tmp` is moved into place, or removed if the file is refused
+    pub fn insert_file(&self, tmp: &Path, expected: Option<&str>) -> Result<String> {
+        let result = self.insert_from(tmp, expected);
+        if result.is_err() {
+            let _ = std::fs::remove_file(tmp);
+        }
+        result
+    }
+
+    fn insert_from(&self, tmp: &Path, expected: Option<&str>) -> Result<String> {
+        let raw = blazesym::helper::read_elf_build_id(tmp)
+            .context("uploaded file is not a readable ELF file")?
+            .context("uploaded ELF file has no build ID note")?;
+        let build_id = FrameRef::build_id_hex(&raw);
+        if let Some(expected) = expected {
+            if !expected.eq_ignore_ascii_case(&build_id) {
+                anyhow::bail!(
+                    "build ID mismatch: expected {}, file has {}",
+                    expected,
+                    build_id
+                );
+            }
+        }
+
+        let path = self
+            .path_for(&build_id)
+            .context("build ID too short to index")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(tmp, &path).context("move debug file into place")?;
+        self.cache.lock().unwrap().remove(&build_id);
+        tracing::info!(build_id = %build_id, "Stored debug file");
+        Ok(build_id)
+    }
+
+    /// Resolve file offsets within the ELF file identified by `build_id`.
+    ///
+    /// Returns one entry per offset; `None` when no debug file was uploaded
+    /// for the build ID or the offset does not fall within a known symbol.
+    pub fn resolve(&self, build_id: &str, offsets: &[u64]) -> Vec<Option<Symbol>> {
+        let Some(path) = self.path_for(build_id).filter(|p| p.is_file()) else {
+            return vec![None; offsets.len()];
+        };
+
+        let missing: Vec<u64> = {
+            let cache = self.cache.lock().unwrap();
+            let known = cache.get(build_id);
+            let mut missing: Vec<u64> = offsets
+                .iter()
+                .copied()
+                .filter(|off| known.map_or(true, |k| !k.contains_key(off)))
+                .collect();
+            missing.sort_unstable();
+            missing.dedup();
+            missing
+        };
+
+        if !missing.is_empty() {
+            let resolved = symbolize_file_offsets(&path, &missing);
+            let mut cache = self.cache.lock().unwrap();
+            let entry = cache.entry(build_id.to_string()).or_default();
+            for (off, symbol) in missing.into_iter().zip(resolved) {
+                entry.insert(off, symbol);
+            }
+        }
+
+        let cache = self.cache.lock().unwrap();
+        let known = cache.get(build_id);
+        offsets
+            .iter()
+            .map(|off| known.and_then(|k| k.get(off).cloned().flatten()))
+            .collect()
+    }
+
+    /// Fill in missing user-space symbol names on events that carry frame refs.
+    ///
+    /// Names the agent already resolved are kept; only frames with a ref and
+    /// no name are looked up.
+    pub fn symbolize_events(&self, events: &mut [ProfileEvent]) {
+        // Group offsets by build ID so each debug file is opened once per batch
+        let mut wanted: HashMap<String, Vec<u64>> = HashMap::new();
+        for event in events.iter() {
+            let (refs, symbols) = match event {
+                ProfileEvent::CpuSample(s) => (&s.user_stack_refs, &s.user_stack_symbols),
+                ProfileEvent::Lock(ev) => (&ev.stack_refs, &ev.stack_symbols),
+                ProfileEvent::Syscall(ev) => (&ev.stack_refs, &ev.stack_symbols),
+                ProfileEvent::KernelLock(ev) => (&ev.stack_refs, &ev.stack_symbols),
+                ProfileEvent::Sched(ev) => (&ev.stack_refs, &ev.stack_symbols),
+                ProfileEvent::PageFault(ev) => (&ev.stack_refs, &ev.stack_symbols),
+                ProfileEvent::Probe(ev) => (&ev.stack_refs, &ev.stack_symbols),
+                _ => continue,
+            };
+            for (i, frame_ref) in refs.iter().enumerate() {
+                if let Some(r) = frame_ref {
+                    if symbols.get(i).map_or(true, Option::is_none) {
+                        wanted
+                            .entry(r.build_id.clone())
+                            .or_default()
+                            .push(r.file_offset);
+                    }
+                }
+            }
+        }
+        if wanted.is_empty() {
+            return;
+        }
+
+        let mut names: HashMap<(String, u64), Symbol> = HashMap::new();
+        for (build_id, offsets) in wanted {
+            let resolved = self.resolve(&build_id, &offsets);
+            for (off, symbol) in offsets.into_iter().zip(resolved) {
+                if let Some(symbol) = symbol {
+                    names.insert((build_id.clone(), off), symbol);
+                }
+            }
+        }
+
+        for event in events.iter_mut() {
+            let (refs, symbols) = match event {
+                ProfileEvent::CpuSample(s) => (&s.user_stack_refs, &mut s.user_stack_symbols),
+                ProfileEvent::Lock(ev) => (&ev.stack_refs, &mut ev.stack_symbols),
+                ProfileEvent::Syscall(ev) => (&ev.stack_refs, &mut ev.stack_symbols),
+                ProfileEvent::KernelLock(ev) => (&ev.stack_refs, &mut ev.stack_symbols),
+                ProfileEvent::Sched(ev) => (&ev.stack_refs, &mut ev.stack_symbols),
+                ProfileEvent::PageFault(ev) => (&ev.stack_refs, &mut ev.stack_symbols),
+                ProfileEvent::Probe(ev) => (&ev.stack_refs, &mut ev.stack_symbols),
+                _ => continue,
+            };
+            if symbols.len() < refs.len() {
+                symbols.resize(refs.len(), None);
+            }
+            for (i, frame_ref) in refs.iter().enumerate() {
+                let Some(r) = frame_ref else { continue };
+                if symbols[i].is_none() {
+                    symbols[i] = names.get(&(r.build_id.clone(), r.file_offset)).cloned();
+                }
+            }
+        }
+    }
+}
+
+/// GNU build IDs are 20 bytes (SHA-1) in practice; accept anything from
+/// 4 bytes up to 64 as long as it is lowercase hex.
+fn is_valid_build_id(build_id: &str) -> bool {
+    (8..=128).contains(&build_id.len())
+        && build_id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn symbolize_file_offsets(path: &Path, offsets: &[u64]) -> Vec<Option<Symbol>> {
+    let symbolizer = Symbolizer::new();
+    let source = Source::Elf(Elf::new(path));
+    match symbolizer.symbolize(&source, Input::FileOffset(offsets)) {
+        Ok(results) => results
+            .into_iter()
+            .map(|r| match r {
+                Symbolized::Sym(sym) => sym_symbol(&sym),
+                Symbolized::Unknown(_) => None,
+            })
+            .collect(),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), "Symbolizing uploaded debug file failed: {}", e);
+            vec![None; offsets.len()]
+        }
+    }
+}
+
+/// Wire symbol for a resolved address: inlined callees (innermost first)
+/// followed by the containing function, each with its source location.
+fn sym_symbol(sym: &Sym) -> Option<Symbol> {
+    let frame = |name: &str, info: Option<&CodeInfo>, inline: bool| Frame {
+        ip: 0,
+        function: Some(name.to_string()),
+        file: info.map(|i| i.to_path().display().to_string()),
+        line: info.and_then(|i| i.line),
+        module: None,
+        inline,
+    };
+    let chain: Vec<Frame> = sym
+        .inlined
+        .iter()
+        .rev()
+        .map(|f| frame(&f.name, f.code_info.as_ref(), true))
+        .chain(std::iter::once(frame(
+            &sym.name,
+            sym.code_info.as_deref(),
+            false,
+        )))
+        .collect();
+    Frame::to_symbol(&chain, |f| f.function.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aperture_shared::types::events::CpuSample;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("aperture-symbols-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_build_id_validation() {
+        assert!(is_valid_build_id("0123456789abcdef"));
+        assert!(!is_valid_build_id("0123"));
+        assert!(!is_valid_build_id("../../etc/passwd"));
+        assert!(!is_valid_build_id("0123456789ABCDEF"));
+    }
+
+    #[test]
+    fn test_insert_rejects_non_elf() {
+        let dir = temp_dir("non-elf");
+        let store = SymbolStore::open(&dir).unwrap();
+        assert!(store.insert(b"not an elf file", None).is_err());
+        assert!(store.build_ids().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_upload_and_resolve_own_binary() {
+        let exe = std::env::current_exe().unwrap();
+        let Ok(Some(raw)) = blazesym::helper::read_elf_build_id(&exe) else {
+            return; // test binary linked without a build ID
+        };
+        let dir = temp_dir("own-binary");
+        let store = SymbolStore::open(&dir).unwrap();
+
+        let bytes = std::fs::read(&exe).unwrap();
+        let build_id = store.insert(&bytes, None).unwrap();
+        assert_eq!(build_id, FrameRef::build_id_hex(&raw));
+        assert!(store.contains(&build_id));
+        assert_eq!(store.build_ids().unwrap(), vec![build_id.clone()]);
+        assert!(store.insert(&bytes, Some("deadbeefdeadbeef")).is_err());
+
+        // A frame in this very test function resolves back to its name
+        let ip = test_upload_and_resolve_own_binary as *const () as u64;
+        let normalizer = blazesym::normalize::Normalizer::new();
+        let output = normalizer
+            .normalize_user_addrs(blazesym::Pid::Slf, &[ip])
+            .unwrap();
+        let (file_offset, _) = output.outputs[0];
+        let symbols = store.resolve(&build_id, &[file_offset]);
+        assert!(symbols[0]
+            .as_ref()
+            .and_then(|s| s.last())
+            .is_some_and(|f| f.function.contains("test_upload_and_resolve_own_binary")));
+
+        // Unknown build IDs leave frames unnamed
+        let mut events = vec![ProfileEvent::CpuSample(CpuSample {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            cpu_id: 0,
+            user_stack: vec![0x1000],
+            kernel_stack: vec![],
+            comm: "deferred".to_string(),
+            user_stack_symbols: vec![],
+            kernel_stack_symbols: vec![],
+            user_stack_refs: vec![Some(FrameRef {
+                build_id: "00112233445566778899".to_string(),
+                file_offset: 0x1000,
+            })],
+            labels: Default::default(),
+        })];
+        store.symbolize_events(&mut events);
+        let ProfileEvent::CpuSample(s) = &events[0] else {
+            panic!("expected CpuSample");
+        };
+        assert_eq!(s.user_stack_symbols, vec![None]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
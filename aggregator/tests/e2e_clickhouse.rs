@@ -36,6 +36,8 @@ async fn e2e_push_query_and_storage() {
             comm: "e2e-test".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         })],
     );
     let payload = message.to_bytes().expect("serialize message");
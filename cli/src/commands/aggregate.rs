@@ -28,9 +28,23 @@ pub struct AggregateArgs {
     #[arg(short, long, default_value = "1000")]
     pub limit: u32,
 
-    /// Event type: cpu, lock, syscall, or all
+    /// Event type: cpu, lock, syscall, block-io, sched, tcp, memory, probe, or all
     #[arg(short = 't', long, default_value = "")]
     pub event_type: String,
+
+    /// List lock contention per lock (variable or allocation site) instead
+    /// of per address and stack
+    #[arg(long)]
+    pub group_by_lock: bool,
+
+    /// Keep only CPU samples and lock events with this profiling label
+    /// (repeatable): KEY=VALUE
+    #[arg(long = "label")]
+    pub labels: Vec<String>,
+
+    /// Break CPU samples and lock waits down by this profiling label
+    #[arg(long)]
+    pub group_by_label: Option<String>,
 }
 
 pub async fn run(args: AggregateArgs) -> Result<()> {
@@ -38,12 +52,18 @@ pub async fn run(args: AggregateArgs) -> Result<()> {
         .await
         .context("Failed to connect to aggregator")?;
 
+    let labels = aperture_aggregator::aggregate::LabelQuery::from_args(
+        &args.labels,
+        args.group_by_label.clone(),
+    )?;
     let request = AggregateRequest {
         agent_id: args.agent_id.clone(),
         time_start_ns: args.start,
         time_end_ns: args.end,
         limit: args.limit,
         event_type: args.event_type.clone(),
+        labels: labels.filter.into_iter().collect(),
+        group_by_label: labels.group_by,
     };
 
     let response = client
@@ -76,12 +96,7 @@ pub async fn run(args: AggregateArgs) -> Result<()> {
                 .stack
                 .frames
                 .iter()
-                .map(|f| {
-                    f.function
-                        .as_deref()
-                        .unwrap_or(&format!("0x{:x}", f.ip))
-                        .to_string()
-                })
+                .map(|f| f.folded_name(false))
                 .collect::<Vec<_>>()
                 .join(";");
             println!("  [{:>5}] {}", sc.count, label);
@@ -91,11 +106,54 @@ pub async fn run(args: AggregateArgs) -> Result<()> {
     if let Some(lock) = &result.lock {
         println!("\n=== Lock Contention ===");
         println!("  Total events: {}", lock.total_events);
-        println!("  Unique contentions: {}", lock.contentions.len());
-        for c in lock.contentions.iter().take(10) {
+        if args.group_by_lock {
+            println!("  Locks: {}", lock.locks.len());
+            for g in lock.locks.iter().take(20) {
+                println!(
+                    "  lock={} addrs={} waits={} total_wait={:.2}ms max_wait={:.2}ms total_hold={:.2}ms",
+                    g.name,
+                    g.lock_addrs.len(),
+                    g.contention.count,
+                    g.contention.total_wait_ns as f64 / 1_000_000.0,
+                    g.contention.max_wait_ns as f64 / 1_000_000.0,
+                    g.hold.total_hold_ns as f64 / 1_000_000.0
+                );
+            }
+        } else {
+            println!("  Unique contentions: {}", lock.contentions.len());
+            for c in lock.contentions.iter().take(10) {
+                println!(
+                    "  lock={} count={} total_wait={:.2}ms max_wait={:.2}ms",
+                    lock_label(c.lock_addr, c.lock_name.as_deref()),
+                    c.count,
+                    c.total_wait_ns as f64 / 1_000_000.0,
+                    c.max_wait_ns as f64 / 1_000_000.0
+                );
+            }
+            if !lock.holders.is_empty() {
+                println!("  Holders:");
+                for h in lock.holders.iter().take(10) {
+                    println!(
+                        "  lock={} releases={} total_hold={:.2}ms max_hold={:.2}ms",
+                        lock_label(h.lock_addr, h.lock_name.as_deref()),
+                        h.count,
+                        h.total_hold_ns as f64 / 1_000_000.0,
+                        h.max_hold_ns as f64 / 1_000_000.0
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(kernel_lock) = &result.kernel_lock {
+        println!("\n=== Kernel Lock Contention ===");
+        println!("  Total events: {}", kernel_lock.total_events);
+        println!("  Unique contentions: {}", kernel_lock.contentions.len());
+        for c in kernel_lock.contentions.iter().take(10) {
             println!(
-                "  lock=0x{:x} count={} total_wait={:.2}ms max_wait={:.2}ms",
+                "  lock=0x{:x} type={} count={} total_wait={:.2}ms max_wait={:.2}ms",
                 c.lock_addr,
+                c.lock_type,
                 c.count,
                 c.total_wait_ns as f64 / 1_000_000.0,
                 c.max_wait_ns as f64 / 1_000_000.0
@@ -124,7 +182,234 @@ pub async fn run(args: AggregateArgs) -> Result<()> {
                 stats.name, stats.count, avg_us, max_us, stats.error_count
             );
         }
+
+        if !syscall.io.is_empty() {
+            println!("\n=== I/O by File/Socket ===");
+            let mut targets: Vec<_> = syscall.io.values().collect();
+            targets.sort_by(|a, b| b.total_duration_ns.cmp(&a.total_duration_ns));
+            println!(
+                "  {:>8} {:>12} {:>12} {:>12} {:>8}  TARGET",
+                "COUNT", "TOTAL (ms)", "MAX (us)", "BYTES", "ERRORS"
+            );
+            for stats in targets.iter().take(20) {
+                println!(
+                    "  {:>8} {:>12.2} {:>12.1} {:>12} {:>8}  {}",
+                    stats.count,
+                    stats.total_duration_ns as f64 / 1_000_000.0,
+                    stats.max_duration_ns as f64 / 1000.0,
+                    stats.bytes,
+                    stats.error_count,
+                    stats.target
+                );
+            }
+        }
+    }
+
+    if let Some(block_io) = &result.block_io {
+        println!("\n=== Block I/O ===");
+        println!("  Total requests: {}", block_io.total_events);
+        println!(
+            "  {:>16} {:>8} {:>8} {:>12} {:>12} {:>12} {:>7} {:>8}",
+            "DEVICE", "OP", "COUNT", "AVG (us)", "MAX (us)", "BYTES", "AVG QD", "ERRORS"
+        );
+        for stats in block_io.stats() {
+            println!(
+                "  {:>16} {:>8} {:>8} {:>12.1} {:>12.1} {:>12} {:>7.1} {:>8}",
+                stats.device,
+                stats.op.as_str(),
+                stats.count,
+                stats.avg_latency_ns() as f64 / 1000.0,
+                stats.max_latency_ns as f64 / 1000.0,
+                stats.bytes,
+                stats.avg_queue_depth(),
+                stats.error_count
+            );
+        }
+    }
+
+    if let Some(sched) = &result.sched {
+        println!("\n=== Run-Queue Latency ===");
+        println!("  Total waits: {}", sched.total_events);
+        println!(
+            "  {:>8} {:>16} {:>8} {:>12} {:>12} {:>10}  TOP PREEMPTOR",
+            "PID", "COMM", "WAITS", "AVG (us)", "MAX (us)", "PREEMPTED"
+        );
+        let mut processes: Vec<_> = sched.processes.values().collect();
+        processes.sort_by_key(|p| std::cmp::Reverse(p.runq.total_delay_ns));
+        for p in processes.iter().take(20) {
+            let preemptor = p
+                .preemptors
+                .iter()
+                .max_by_key(|(_, s)| s.total_delay_ns)
+                .map_or("-", |(name, _)| name.as_str());
+            println!(
+                "  {:>8} {:>16} {:>8} {:>12.1} {:>12.1} {:>10}  {}",
+                p.pid,
+                p.comm,
+                p.runq.count,
+                p.runq.avg_delay_ns() as f64 / 1000.0,
+                p.runq.max_delay_ns as f64 / 1000.0,
+                p.runq.preempted_count,
+                preemptor
+            );
+        }
+        for (cgroup, stats) in &sched.cgroups {
+            println!(
+                "  cgroup {}: {} waits, avg {:.1} us, max {:.1} us",
+                cgroup,
+                stats.count,
+                stats.avg_delay_ns() as f64 / 1000.0,
+                stats.max_delay_ns as f64 / 1000.0
+            );
+        }
+    }
+
+    if let Some(tcp) = &result.tcp {
+        println!("\n=== TCP ===");
+        println!("  Total events: {}", tcp.total_events);
+        println!(
+            "  {:>8} {:>16} {:>32} {:>7} {:>6} {:>12} {:>8} {:>7} {:>12} {:>12} {:>10}",
+            "PID",
+            "COMM",
+            "ENDPOINT",
+            "CONNS",
+            "FAIL",
+            "CONN (us)",
+            "RETRANS",
+            "RESETS",
+            "SENT",
+            "RECEIVED",
+            "SRTT (us)"
+        );
+        let mut endpoints: Vec<_> = tcp.endpoints().collect();
+        endpoints.sort_by_key(|(_, e)| std::cmp::Reverse(e.bytes_sent + e.bytes_received));
+        for (p, e) in endpoints.iter().take(20) {
+            println!(
+                "  {:>8} {:>16} {:>32} {:>7} {:>6} {:>12.1} {:>8} {:>7} {:>12} {:>12} {:>10}",
+                p.pid,
+                p.comm,
+                e.endpoint,
+                e.connects,
+                e.connect_failures,
+                e.avg_connect_ns() as f64 / 1000.0,
+                e.retransmits,
+                e.resets_sent + e.resets_received,
+                e.bytes_sent,
+                e.bytes_received,
+                e.avg_srtt_us()
+            );
+        }
+    }
+
+    if let Some(memory) = &result.memory {
+        println!("\n=== Memory ===");
+        println!("  Total events: {}", memory.total_events);
+        println!("  Page faults (estimated): {}", memory.total_faults);
+        println!(
+            "  {:>8} {:>16} {:>12} {:>12} {:>8} {:>12} {:>12} {:>10}",
+            "PID", "COMM", "FAULTS", "KERN FAULTS", "STALLS", "AVG (us)", "MAX (us)", "PAGES"
+        );
+        for p in memory.processes.iter().take(20) {
+            println!(
+                "  {:>8} {:>16} {:>12} {:>12} {:>8} {:>12.1} {:>12.1} {:>10}",
+                p.pid,
+                p.comm,
+                p.faults,
+                p.kernel_faults,
+                p.reclaim.count,
+                p.reclaim.avg_stall_ns() as f64 / 1000.0,
+                p.reclaim.max_stall_ns as f64 / 1000.0,
+                p.reclaim.nr_reclaimed
+            );
+        }
+        println!("  Unique fault stacks: {}", memory.fault_stacks.len());
+        for sc in memory.fault_stacks.iter().take(10) {
+            let label = sc
+                .stack
+                .frames
+                .iter()
+                .map(|f| f.folded_name(false))
+                .collect::<Vec<_>>()
+                .join(";");
+            println!("  [{:>5}] {}", sc.count, label);
+        }
+    }
+
+    if let Some(probe) = &result.probe {
+        println!("\n=== Probes ===");
+        println!("  Total events: {}", probe.total_events);
+        println!(
+            "  {:>10} {:>12} {:>12} {:>8}  PROBE",
+            "COUNT", "AVG (us)", "MAX (us)", "STACKS"
+        );
+        for p in probe.probes.values() {
+            println!(
+                "  {:>10} {:>12.1} {:>12.1} {:>8}  {}",
+                p.count,
+                p.avg_duration_ns() as f64 / 1000.0,
+                p.max_duration_ns as f64 / 1000.0,
+                p.stacks.len(),
+                p.probe
+            );
+        }
+        for u in probe.usdt.values() {
+            let args: Vec<String> = u
+                .args
+                .iter()
+                .map(|a| format!("{}..{}", a.min, a.max))
+                .collect();
+            println!(
+                "  {:>10} {:>12} {:>12} {:>8}  {} [{}]",
+                u.count,
+                "-",
+                "-",
+                "-",
+                u.probe,
+                args.join(" ")
+            );
+        }
+        if !probe.spans.is_empty() {
+            println!(
+                "  {:>10} {:>12} {:>12} {:>8}  SPAN",
+                "COUNT", "AVG (us)", "MAX (us)", "CPU"
+            );
+            for s in probe.spans.values() {
+                println!(
+                    "  {:>10} {:>12.1} {:>12.1} {:>8}  {}",
+                    s.count,
+                    s.avg_duration_ns() as f64 / 1000.0,
+                    s.max_duration_ns as f64 / 1000.0,
+                    s.cpu_samples,
+                    s.span
+                );
+            }
+        }
+    }
+
+    if let Some(labels) = &result.labels {
+        println!("\n=== By Label: {} ===", labels.key);
+        println!(
+            "  {:>10} {:>10} {:>14}  VALUE",
+            "SAMPLES", "LOCK WAITS", "LOCK WAIT (ms)"
+        );
+        for g in &labels.groups {
+            println!(
+                "  {:>10} {:>10} {:>14.2}  {}",
+                g.stats.cpu_samples,
+                g.stats.lock_waits,
+                g.stats.lock_wait_ns as f64 / 1_000_000.0,
+                g.value.as_deref().unwrap_or("(none)")
+            );
+        }
     }
 
     Ok(())
 }
+
+/// A lock's name with its address, or the address alone
+fn lock_label(lock_addr: u64, lock_name: Option<&str>) -> String {
+    match lock_name {
+        Some(name) => format!("{} (0x{:x})", name, lock_addr),
+        None => format!("0x{:x}", lock_addr),
+    }
+}
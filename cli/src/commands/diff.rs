@@ -12,7 +12,7 @@ pub struct DiffArgs {
     #[arg(short, long, default_value = "http://127.0.0.1:50051")]
     pub endpoint: String,
 
-    /// Event type to diff: cpu, lock, or syscall
+    /// Event type to diff: cpu, lock, syscall, block-io, or sched
     #[arg(short = 't', long)]
     pub event_type: String,
 
@@ -43,6 +43,11 @@ pub struct DiffArgs {
     /// Max batches per window
     #[arg(short, long, default_value = "1000")]
     pub limit: u32,
+
+    /// Compare lock contention per lock (variable or allocation site)
+    /// instead of per address and stack
+    #[arg(long)]
+    pub group_by_lock: bool,
 }
 
 pub async fn run(args: DiffArgs) -> Result<()> {
@@ -78,8 +83,10 @@ pub async fn run(args: DiffArgs) -> Result<()> {
 
     match args.event_type.as_str() {
         "cpu" => print_cpu_diff(&res.result_json)?,
-        "lock" => print_lock_diff(&res.result_json)?,
+        "lock" => print_lock_diff(&res.result_json, args.group_by_lock)?,
         "syscall" => print_syscall_diff(&res.result_json)?,
+        "block-io" => print_block_io_diff(&res.result_json)?,
+        "sched" => print_sched_diff(&res.result_json)?,
         other => anyhow::bail!("Unknown event type: {}", other),
     }
 
@@ -105,12 +112,7 @@ fn print_cpu_diff(json: &str) -> Result<()> {
             .stack
             .frames
             .iter()
-            .map(|f| {
-                f.function
-                    .as_deref()
-                    .unwrap_or(&format!("0x{:x}", f.ip))
-                    .to_string()
-            })
+            .map(|f| f.folded_name(false))
             .collect::<Vec<_>>()
             .join(";");
         let sign = if s.delta >= 0 { "+" } else { "" };
@@ -154,7 +156,89 @@ fn print_syscall_diff(json: &str) -> Result<()> {
     Ok(())
 }
 
-fn print_lock_diff(json: &str) -> Result<()> {
+fn print_block_io_diff(json: &str) -> Result<()> {
+    let diff: aperture_shared::types::diff::BlockIoDiff =
+        serde_json::from_str(json).context("parse BlockIoDiff")?;
+
+    println!("=== Block I/O Diff ===");
+    println!(
+        "  Baseline: {} requests | Comparison: {} requests",
+        diff.baseline_total, diff.comparison_total
+    );
+    println!(
+        "\n  {:>16} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10} {:>10} {:>6} {:>6}",
+        "DEVICE",
+        "OP",
+        "B.COUNT",
+        "C.COUNT",
+        "DELTA",
+        "B.AVG(us)",
+        "C.AVG(us)",
+        "D.AVG(us)",
+        "B.QD",
+        "C.QD"
+    );
+
+    for r in diff.requests.iter().take(20) {
+        println!(
+            "  {:>16} {:>8} {:>8} {:>8} {:>+8} {:>10.1} {:>10.1} {:>+10.1} {:>6.1} {:>6.1}",
+            r.device,
+            r.op.as_str(),
+            r.baseline_count,
+            r.comparison_count,
+            r.delta_count,
+            r.baseline_avg_ns / 1000.0,
+            r.comparison_avg_ns / 1000.0,
+            r.delta_avg_ns / 1000.0,
+            r.baseline_avg_queue_depth,
+            r.comparison_avg_queue_depth
+        );
+    }
+
+    Ok(())
+}
+
+fn print_sched_diff(json: &str) -> Result<()> {
+    let diff: aperture_shared::types::diff::SchedDiff =
+        serde_json::from_str(json).context("parse SchedDiff")?;
+
+    println!("=== Run-Queue Latency Diff ===");
+    println!(
+        "  Baseline: {} waits | Comparison: {} waits",
+        diff.baseline_total, diff.comparison_total
+    );
+    println!(
+        "\n  {:>16} {:>8} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "COMM",
+        "B.COUNT",
+        "C.COUNT",
+        "DELTA",
+        "B.AVG(us)",
+        "C.AVG(us)",
+        "D.AVG(us)",
+        "B.MAX(us)",
+        "C.MAX(us)"
+    );
+
+    for p in diff.processes.iter().take(20) {
+        println!(
+            "  {:>16} {:>8} {:>8} {:>+8} {:>10.1} {:>10.1} {:>+10.1} {:>10.1} {:>10.1}",
+            p.comm,
+            p.baseline_count,
+            p.comparison_count,
+            p.delta_count,
+            p.baseline_avg_ns / 1000.0,
+            p.comparison_avg_ns / 1000.0,
+            p.delta_avg_ns / 1000.0,
+            p.baseline_max_ns as f64 / 1000.0,
+            p.comparison_max_ns as f64 / 1000.0
+        );
+    }
+
+    Ok(())
+}
+
+fn print_lock_diff(json: &str, group_by_lock: bool) -> Result<()> {
     let diff: aperture_shared::types::diff::LockDiff =
         serde_json::from_str(json).context("parse LockDiff")?;
 
@@ -163,18 +247,38 @@ fn print_lock_diff(json: &str) -> Result<()> {
         "  Baseline: {} events | Comparison: {} events",
         diff.baseline_total, diff.comparison_total
     );
+
+    if group_by_lock {
+        println!(
+            "\n  {:>8} {:>8} {:>12} {:>12}  LOCK",
+            "B.COUNT", "C.COUNT", "DELTA_WAIT", "DELTA_HOLD"
+        );
+        for l in diff.locks.iter().take(20) {
+            println!(
+                "  {:>8} {:>8} {:>+10.2}ms {:>+10.2}ms  {}",
+                l.baseline_count,
+                l.comparison_count,
+                l.delta_wait_ns as f64 / 1_000_000.0,
+                l.delta_hold_ns as f64 / 1_000_000.0,
+                l.name
+            );
+        }
+        return Ok(());
+    }
+
     println!(
-        "\n  {:>18} {:>8} {:>8} {:>12}",
+        "\n  {:>18} {:>8} {:>8} {:>12}  LOCK",
         "LOCK_ADDR", "B.COUNT", "C.COUNT", "DELTA_WAIT"
     );
 
     for c in diff.contentions.iter().take(20) {
         println!(
-            "  0x{:016x} {:>8} {:>8} {:>+10.2}ms",
+            "  0x{:016x} {:>8} {:>8} {:>+10.2}ms  {}",
             c.lock_addr,
             c.baseline_count,
             c.comparison_count,
-            c.delta_wait_ns as f64 / 1_000_000.0
+            c.delta_wait_ns as f64 / 1_000_000.0,
+            c.lock_name.as_deref().unwrap_or("")
         );
     }
 
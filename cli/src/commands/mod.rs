@@ -4,3 +4,4 @@ pub mod aggregate;
 pub mod diff;
 pub mod profile;
 pub mod query;
+pub mod replay;
@@ -0,0 +1,62 @@
+//! Replay command implementation
+
+use anyhow::Result;
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Capture file written by `profile --record`
+    pub capture: std::path::PathBuf,
+
+    /// Output file for the flamegraph (SVG) or syscall report; with several
+    /// profiles in the capture, each gets a .cpu.svg, .lock.svg or
+    /// .syscall.txt suffix
+    #[arg(short, long, default_value = "flamegraph.svg")]
+    pub output: String,
+
+    /// Also output raw data in JSON format
+    #[arg(long)]
+    pub json: Option<String>,
+
+    /// Only replay the events of this process
+    #[arg(short, long)]
+    pub pid: Option<i32>,
+
+    /// JSON file of frame rewrite/collapse rules applied after symbolization
+    #[arg(long)]
+    pub normalize_rules: Option<std::path::PathBuf>,
+
+    /// Push the recorded events to this aggregator gRPC URL, under the
+    /// recording host's agent ID
+    #[arg(long)]
+    pub aggregator: Option<String>,
+
+    /// Verbose logging
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+pub async fn run(args: ReplayArgs) -> Result<()> {
+    let options = aperture_agent::capture::ReplayOptions {
+        pid: args.pid,
+        normalize_rules: args.normalize_rules,
+        output_path: Some(args.output),
+        json_output: args.json,
+        aggregator_url: args.aggregator,
+    };
+    let bundle = aperture_agent::capture::replay(&args.capture, &options).await?;
+
+    if let Some(profile) = &bundle.cpu {
+        println!("CPU: {} samples", profile.total_samples);
+    }
+    if let Some(profile) = &bundle.lock {
+        println!("Lock: {} events", profile.total_events);
+    }
+    if let Some(profile) = &bundle.syscall {
+        println!("Syscall: {} events", profile.total_events);
+    }
+    if bundle.cpu.is_none() && bundle.lock.is_none() && bundle.syscall.is_none() {
+        println!("No events in the capture.");
+    }
+    Ok(())
+}
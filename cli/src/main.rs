@@ -3,6 +3,7 @@
 //! This is a higher-level CLI that supports multiple commands:
 //! - profile: Run profiling (wraps agent)
 //! - query: Query aggregated data
+//! - replay: Rebuild profiles from a recorded capture
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -24,7 +25,7 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Run profiling on a process or system
-    Profile(commands::profile::ProfileArgs),
+    Profile(Box<commands::profile::ProfileArgs>),
 
     /// Query aggregated profiling data from the aggregator
     Query(commands::query::QueryArgs),
@@ -34,6 +35,9 @@ enum Commands {
 
     /// Compare two time windows (differential profiling)
     Diff(commands::diff::DiffArgs),
+
+    /// Rebuild profiles from a recorded capture, offline
+    Replay(commands::replay::ReplayArgs),
 }
 
 #[tokio::main]
@@ -43,11 +47,15 @@ async fn main() -> Result<()> {
     match cli.command {
         Commands::Profile(args) => {
             init_tracing(args.verbose);
-            commands::profile::run(args).await
+            commands::profile::run(*args).await
         }
         Commands::Query(args) => commands::query::run(args).await,
         Commands::Aggregate(args) => commands::aggregate::run(args).await,
         Commands::Diff(args) => commands::diff::run(args).await,
+        Commands::Replay(args) => {
+            init_tracing(args.verbose);
+            commands::replay::run(args).await
+        }
     }
 }
 
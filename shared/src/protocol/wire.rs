@@ -6,20 +6,30 @@
 //! # Schema evolution
 //!
 //! Bincode is positional (not self-describing), so adding fields to event structs
-//! breaks decoding of old payloads. We handle this via `LegacyMessage` types that
-//! mirror the original (pre-symbol) struct shapes. When `from_bytes` fails with the
-//! current schema it tries `LegacyMessage`, then converts to the current types with
-//! the new fields defaulted.
+//! breaks decoding of old payloads. Each field addition bumps `PROTOCOL_VERSION`
+//! and keeps the previous struct shapes around as private types:
+//!
+//! - `V1Message`: version 1 with pre-resolved symbol names
+//! - `LegacyMessage`: version 1 before symbol fields were added
+//!
+//! When `from_bytes` fails with the current schema it walks back through the
+//! older shapes, then converts to the current types with the new fields defaulted.
 
 use crate::types::events::{
-    CpuId, CpuSample, GpuKernelEvent, LockEvent, Pid, ProfileEvent, StackTrace, SyscallEvent, Tid,
-    Timestamp,
+    CpuId, CpuSample, GpuKernelEvent, Labels, LockEvent, LockEventKind, Pid, ProfileEvent,
+    StackTrace, Symbol, SymbolFrame, SyscallEvent, Tid, Timestamp,
 };
+use crate::utils::arch::Arch;
 use anyhow::Result;
 use bincode::Options;
 
 /// Protocol version
-pub const PROTOCOL_VERSION: u32 = 1;
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Version of payloads sent before frame refs, source locations and the
+/// agent's architecture were added (the v1 schema and the pre-symbol legacy
+/// schema both carry this)
+const V1_PROTOCOL_VERSION: u32 = 1;
 
 /// Single bincode config for wire format: fixint encoding so vec lengths and enum tags
 /// have a fixed size and cannot be misinterpreted across builds or bincode versions.
@@ -29,6 +39,50 @@ fn wire_bincode() -> impl bincode::config::Options {
         .allow_trailing_bytes()
 }
 
+/// Symbols of a v1 payload, which are plain function names
+fn v1_symbols(symbols: Vec<Option<String>>) -> Vec<Option<Symbol>> {
+    symbols
+        .into_iter()
+        .map(|s| s.map(|name| vec![SymbolFrame::named(name)]))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Syscall event shared by the v1 schemas (no argument fields)
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct LegacySyscallEvent {
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub syscall_id: u32,
+    pub duration_ns: u64,
+    pub return_value: i64,
+    pub comm: String,
+}
+
+impl LegacySyscallEvent {
+    fn into_current(self) -> SyscallEvent {
+        SyscallEvent {
+            timestamp: self.timestamp,
+            pid: self.pid,
+            tid: self.tid,
+            syscall_id: self.syscall_id,
+            duration_ns: self.duration_ns,
+            return_value: self.return_value,
+            comm: self.comm,
+            fd: None,
+            target: None,
+            bytes: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: 1,
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Legacy types (v1 schema before symbol fields were added)
 // ---------------------------------------------------------------------------
@@ -60,7 +114,7 @@ struct LegacyLockEvent {
 enum LegacyProfileEvent {
     CpuSample(LegacyCpuSample),
     Lock(LegacyLockEvent),
-    Syscall(SyscallEvent),
+    Syscall(LegacySyscallEvent),
     GpuKernel(GpuKernelEvent),
 }
 
@@ -77,6 +131,8 @@ impl LegacyProfileEvent {
                 comm: s.comm,
                 user_stack_symbols: vec![],
                 kernel_stack_symbols: vec![],
+                user_stack_refs: vec![],
+                labels: Labels::new(),
             }),
             LegacyProfileEvent::Lock(e) => ProfileEvent::Lock(LockEvent {
                 timestamp: e.timestamp,
@@ -88,8 +144,13 @@ impl LegacyProfileEvent {
                 stack_trace: e.stack_trace,
                 comm: e.comm,
                 stack_symbols: vec![],
+                stack_refs: vec![],
+                kind: LockEventKind::Wait,
+                waker_tid: None,
+                lock_name: None,
+                labels: Labels::new(),
             }),
-            LegacyProfileEvent::Syscall(e) => ProfileEvent::Syscall(e),
+            LegacyProfileEvent::Syscall(e) => ProfileEvent::Syscall(e.into_current()),
             LegacyProfileEvent::GpuKernel(e) => ProfileEvent::GpuKernel(e),
         }
     }
@@ -108,10 +169,123 @@ impl LegacyMessage {
             version: self.version,
             sequence: self.sequence,
             events: self.events.into_iter().map(|e| e.into_current()).collect(),
+            arch: Arch::X86_64,
         }
     }
 }
 
+// ---------------------------------------------------------------------------
+// V1 types (symbol names, before frame refs were added)
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct V1CpuSample {
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub cpu_id: CpuId,
+    pub user_stack: StackTrace,
+    pub kernel_stack: StackTrace,
+    pub comm: String,
+    pub user_stack_symbols: Vec<Option<String>>,
+    pub kernel_stack_symbols: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct V1LockEvent {
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub lock_addr: u64,
+    pub hold_time_ns: u64,
+    pub wait_time_ns: u64,
+    pub stack_trace: StackTrace,
+    pub comm: String,
+    pub stack_symbols: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+enum V1ProfileEvent {
+    CpuSample(V1CpuSample),
+    Lock(V1LockEvent),
+    Syscall(LegacySyscallEvent),
+    GpuKernel(GpuKernelEvent),
+}
+
+impl V1ProfileEvent {
+    fn into_current(self) -> ProfileEvent {
+        match self {
+            V1ProfileEvent::CpuSample(s) => ProfileEvent::CpuSample(CpuSample {
+                timestamp: s.timestamp,
+                pid: s.pid,
+                tid: s.tid,
+                cpu_id: s.cpu_id,
+                user_stack: s.user_stack,
+                kernel_stack: s.kernel_stack,
+                comm: s.comm,
+                user_stack_symbols: v1_symbols(s.user_stack_symbols),
+                kernel_stack_symbols: v1_symbols(s.kernel_stack_symbols),
+                user_stack_refs: vec![],
+                labels: Labels::new(),
+            }),
+            V1ProfileEvent::Lock(e) => ProfileEvent::Lock(LockEvent {
+                timestamp: e.timestamp,
+                pid: e.pid,
+                tid: e.tid,
+                lock_addr: e.lock_addr,
+                hold_time_ns: e.hold_time_ns,
+                wait_time_ns: e.wait_time_ns,
+                stack_trace: e.stack_trace,
+                comm: e.comm,
+                stack_symbols: v1_symbols(e.stack_symbols),
+                stack_refs: vec![],
+                kind: LockEventKind::Wait,
+                waker_tid: None,
+                lock_name: None,
+                labels: Labels::new(),
+            }),
+            V1ProfileEvent::Syscall(e) => ProfileEvent::Syscall(e.into_current()),
+            V1ProfileEvent::GpuKernel(e) => ProfileEvent::GpuKernel(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct V1Message {
+    pub version: u32,
+    pub sequence: u64,
+    pub events: Vec<V1ProfileEvent>,
+}
+
+impl V1Message {
+    fn into_current(self) -> Message {
+        Message {
+            version: self.version,
+            sequence: self.sequence,
+            events: self.events.into_iter().map(|e| e.into_current()).collect(),
+            arch: Arch::X86_64,
+        }
+    }
+}
+
+/// Decode `bytes` as `M` with the wire config, then the legacy varint config,
+/// accepting only a message that carries the expected version.
+fn decode_versioned<M: serde::de::DeserializeOwned>(
+    bytes: &[u8],
+    version: u32,
+    version_of: fn(&M) -> u32,
+) -> Option<M> {
+    wire_bincode()
+        .deserialize::<M>(bytes)
+        .ok()
+        .filter(|m| version_of(m) == version)
+        .or_else(|| {
+            bincode::deserialize::<M>(bytes)
+                .ok()
+                .filter(|m| version_of(m) == version)
+        })
+}
+
 // ---------------------------------------------------------------------------
 // Current message type
 // ---------------------------------------------------------------------------
@@ -122,15 +296,24 @@ pub struct Message {
     pub version: u32,
     pub sequence: u64,
     pub events: Vec<ProfileEvent>,
+    /// Architecture of the agent that recorded the events (syscall ids and
+    /// addresses are interpreted by it)
+    pub arch: Arch,
 }
 
 impl Message {
-    /// Create a new message
+    /// Create a new message from the host architecture
     pub fn new(sequence: u64, events: Vec<ProfileEvent>) -> Self {
+        Self::with_arch(sequence, events, Arch::host())
+    }
+
+    /// Create a new message of events recorded on `arch` (replayed captures)
+    pub fn with_arch(sequence: u64, events: Vec<ProfileEvent>, arch: Arch) -> Self {
         Self {
             version: PROTOCOL_VERSION,
             sequence,
             events,
+            arch,
         }
     }
 
@@ -141,35 +324,24 @@ impl Message {
 
     /// Deserialize message from bytes (bincode), validating the protocol version.
     ///
-    /// Attempts decoding in order:
-    /// 1. Current schema, fixint encoding
-    /// 2. Current schema, legacy varint encoding
-    /// 3. Legacy schema (no symbol fields), fixint encoding
-    /// 4. Legacy schema, legacy varint encoding
+    /// Attempts decoding in order, each with fixint then legacy varint encoding:
+    /// 1. Current schema
+    /// 2. V1 schema (symbol names)
+    /// 3. Legacy schema (no symbol fields)
+    ///
+    /// Version 1 messages come from x86_64 agents.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        // 1. Current schema, fixint
-        if let Ok(msg) = wire_bincode().deserialize::<Self>(bytes) {
-            if msg.version == PROTOCOL_VERSION {
-                return Ok(msg);
-            }
+        if let Some(msg) = decode_versioned::<Self>(bytes, PROTOCOL_VERSION, |m| m.version) {
+            return Ok(msg);
         }
-        // 2. Current schema, legacy varint
-        if let Ok(msg) = bincode::deserialize::<Self>(bytes) {
-            if msg.version == PROTOCOL_VERSION {
-                return Ok(msg);
-            }
-        }
-        // 3. Legacy schema (pre-symbol fields), fixint
-        if let Ok(msg) = wire_bincode().deserialize::<LegacyMessage>(bytes) {
-            if msg.version == PROTOCOL_VERSION {
-                return Ok(msg.into_current());
-            }
+        if let Some(msg) = decode_versioned::<V1Message>(bytes, V1_PROTOCOL_VERSION, |m| m.version)
+        {
+            return Ok(msg.into_current());
         }
-        // 4. Legacy schema, legacy varint
-        if let Ok(msg) = bincode::deserialize::<LegacyMessage>(bytes) {
-            if msg.version == PROTOCOL_VERSION {
-                return Ok(msg.into_current());
-            }
+        if let Some(msg) =
+            decode_versioned::<LegacyMessage>(bytes, V1_PROTOCOL_VERSION, |m| m.version)
+        {
+            return Ok(msg.into_current());
         }
         anyhow::bail!("failed to decode message: neither current nor legacy schema succeeded")
     }
@@ -178,6 +350,10 @@ impl Message {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::events::{
+        BlockIoEvent, BlockIoOp, FrameRef, KernelLockEvent, PageFaultEvent, ProbeEvent,
+        ReclaimEvent, SchedEvent, SyscallSummaryEvent, TcpEvent, TcpEventKind, UsdtEvent, UsdtSpan,
+    };
 
     #[test]
     fn test_roundtrip_fixint() {
@@ -211,7 +387,7 @@ mod tests {
     fn test_legacy_schema_decode() {
         // Serialize with legacy structs via fixint
         let legacy_msg = LegacyMessage {
-            version: PROTOCOL_VERSION,
+            version: V1_PROTOCOL_VERSION,
             sequence: 99,
             events: vec![
                 LegacyProfileEvent::CpuSample(LegacyCpuSample {
@@ -262,6 +438,15 @@ mod tests {
     /// Verify new-format roundtrip still works with symbol fields populated.
     #[test]
     fn test_new_schema_with_symbols() {
+        let symbol = vec![
+            SymbolFrame {
+                function: "parse".to_string(),
+                file: Some("src/parser.rs".to_string()),
+                line: Some(88),
+                inline: true,
+            },
+            SymbolFrame::named("main"),
+        ];
         let msg = Message::new(
             50,
             vec![ProfileEvent::CpuSample(CpuSample {
@@ -272,8 +457,10 @@ mod tests {
                 user_stack: vec![0x100],
                 kernel_stack: vec![],
                 comm: "sym".to_string(),
-                user_stack_symbols: vec![Some("main".to_string())],
+                user_stack_symbols: vec![Some(symbol.clone())],
                 kernel_stack_symbols: vec![],
+                user_stack_refs: vec![],
+                labels: Default::default(),
             })],
         );
         let bytes = msg.to_bytes().unwrap();
@@ -281,9 +468,431 @@ mod tests {
         assert_eq!(decoded.events.len(), 1);
         match &decoded.events[0] {
             ProfileEvent::CpuSample(s) => {
-                assert_eq!(s.user_stack_symbols, vec![Some("main".to_string())]);
+                assert_eq!(s.user_stack_symbols, vec![Some(symbol)]);
+            }
+            _ => panic!("expected CpuSample"),
+        }
+    }
+
+    /// A v1 agent (symbols, no frame refs) must still decode after the bump.
+    #[test]
+    fn test_v1_schema_decode() {
+        let v1_msg = V1Message {
+            version: V1_PROTOCOL_VERSION,
+            sequence: 5,
+            events: vec![V1ProfileEvent::CpuSample(V1CpuSample {
+                timestamp: 1,
+                pid: 2,
+                tid: 2,
+                cpu_id: 0,
+                user_stack: vec![0x100, 0x200],
+                kernel_stack: vec![],
+                comm: "v1-agent".to_string(),
+                user_stack_symbols: vec![Some("main".to_string()), None],
+                kernel_stack_symbols: vec![],
+            })],
+        };
+        let bytes = wire_bincode().serialize(&v1_msg).unwrap();
+        let decoded = Message::from_bytes(&bytes).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::CpuSample(s) => {
+                assert_eq!(
+                    s.user_stack_symbols,
+                    vec![Some(vec![SymbolFrame::named("main")]), None]
+                );
+                assert!(s.user_stack_refs.is_empty());
+            }
+            _ => panic!("expected CpuSample"),
+        }
+    }
+
+    #[test]
+    fn test_syscall_args_roundtrip() {
+        let msg = Message::new(
+            9,
+            vec![ProfileEvent::Syscall(SyscallEvent {
+                timestamp: 1,
+                pid: 4,
+                tid: 4,
+                syscall_id: 0,
+                duration_ns: 900,
+                return_value: 64,
+                comm: "reader".to_string(),
+                fd: Some(7),
+                target: Some("/var/lib/db/data.log".to_string()),
+                bytes: Some(64),
+                stack_trace: vec![0x100, 0xffff_ffff_8100_0000],
+                stack_symbols: vec![Some(vec![SymbolFrame::named("main")]), None],
+                stack_refs: vec![],
+                sample_every: 1,
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::Syscall(e) => {
+                assert_eq!(e.fd, Some(7));
+                assert_eq!(e.target.as_deref(), Some("/var/lib/db/data.log"));
+                assert_eq!(e.bytes, Some(64));
+                assert_eq!(e.stack_trace, vec![0x100, 0xffff_ffff_8100_0000]);
+                assert_eq!(
+                    e.stack_symbols,
+                    vec![Some(vec![SymbolFrame::named("main")]), None]
+                );
             }
+            _ => panic!("expected Syscall"),
+        }
+    }
+
+    #[test]
+    fn test_labels_roundtrip() {
+        let labels: Labels = [("endpoint", "/api/users"), ("tenant", "acme")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let msg = Message::new(
+            32,
+            vec![ProfileEvent::CpuSample(CpuSample {
+                timestamp: 1,
+                pid: 2,
+                tid: 3,
+                cpu_id: 0,
+                user_stack: vec![],
+                kernel_stack: vec![],
+                comm: "app".to_string(),
+                user_stack_symbols: vec![],
+                kernel_stack_symbols: vec![],
+                user_stack_refs: vec![],
+                labels: labels.clone(),
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.version, PROTOCOL_VERSION);
+        match &decoded.events[0] {
+            ProfileEvent::CpuSample(s) => assert_eq!(s.labels, labels),
             _ => panic!("expected CpuSample"),
         }
     }
+
+    #[test]
+    fn test_usdt_roundtrip() {
+        let msg = Message::new(
+            30,
+            vec![ProfileEvent::Usdt(UsdtEvent {
+                timestamp: 18,
+                pid: 12,
+                tid: 13,
+                comm: "postgres".to_string(),
+                probe: "postgresql:query__done".to_string(),
+                args: vec![0x5500_0000_1000, -1],
+                span: Some(UsdtSpan {
+                    name: "postgresql:query__start..query__done".to_string(),
+                    start: 10,
+                }),
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::Usdt(e) => {
+                assert_eq!(e.probe, "postgresql:query__done");
+                assert_eq!(e.args, vec![0x5500_0000_1000, -1]);
+                assert_eq!(e.span.as_ref().unwrap().start, 10);
+            }
+            _ => panic!("expected Usdt"),
+        }
+    }
+
+    #[test]
+    fn test_probe_roundtrip() {
+        let msg = Message::new(
+            28,
+            vec![
+                ProfileEvent::Probe(ProbeEvent {
+                    timestamp: 16,
+                    pid: 10,
+                    tid: 11,
+                    comm: "app".to_string(),
+                    probe: "uprobe:/usr/bin/app:handle_request".to_string(),
+                    duration_ns: Some(250_000),
+                    stack_trace: vec![0x401000],
+                    stack_symbols: vec![Some(vec![SymbolFrame::named("handle_request")])],
+                    stack_refs: vec![],
+                }),
+                ProfileEvent::Probe(ProbeEvent {
+                    timestamp: 17,
+                    pid: 0,
+                    tid: 0,
+                    comm: "swapper/0".to_string(),
+                    probe: "tracepoint:net:netif_rx".to_string(),
+                    duration_ns: None,
+                    stack_trace: vec![],
+                    stack_symbols: vec![],
+                    stack_refs: vec![],
+                }),
+            ],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::Probe(e) => {
+                assert_eq!(e.probe, "uprobe:/usr/bin/app:handle_request");
+                assert_eq!(e.duration_ns, Some(250_000));
+                assert_eq!(
+                    e.stack_symbols[0],
+                    Some(vec![SymbolFrame::named("handle_request")])
+                );
+            }
+            _ => panic!("expected Probe"),
+        }
+        match &decoded.events[1] {
+            ProfileEvent::Probe(e) => assert_eq!(e.duration_ns, None),
+            _ => panic!("expected Probe"),
+        }
+    }
+
+    #[test]
+    fn test_memory_roundtrip() {
+        let msg = Message::new(
+            26,
+            vec![
+                ProfileEvent::PageFault(PageFaultEvent {
+                    timestamp: 14,
+                    pid: 10,
+                    tid: 11,
+                    comm: "redis".to_string(),
+                    address: 0x7f00_0000_1000,
+                    ip: 0x401000,
+                    error_code: 6,
+                    kernel: false,
+                    weight: 10,
+                    stack_trace: vec![0x401000, 0x402000],
+                    stack_symbols: vec![Some(vec![SymbolFrame::named("zmalloc")]), None],
+                    stack_refs: vec![],
+                }),
+                ProfileEvent::Reclaim(ReclaimEvent {
+                    timestamp: 15,
+                    pid: 10,
+                    tid: 12,
+                    comm: "redis".to_string(),
+                    stall_ns: 3_000_000,
+                    nr_reclaimed: 32,
+                    order: 0,
+                    memcg: true,
+                }),
+            ],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::PageFault(e) => {
+                assert_eq!(e.address, 0x7f00_0000_1000);
+                assert_eq!(e.weight, 10);
+                assert_eq!(
+                    e.stack_symbols[0],
+                    Some(vec![SymbolFrame::named("zmalloc")])
+                );
+            }
+            _ => panic!("expected PageFault"),
+        }
+        match &decoded.events[1] {
+            ProfileEvent::Reclaim(e) => {
+                assert_eq!(e.stall_ns, 3_000_000);
+                assert!(e.memcg);
+            }
+            _ => panic!("expected Reclaim"),
+        }
+    }
+
+    #[test]
+    fn test_tcp_roundtrip() {
+        let msg = Message::new(
+            24,
+            vec![ProfileEvent::Tcp(TcpEvent {
+                timestamp: 13,
+                pid: 10,
+                tid: 11,
+                comm: "nginx".to_string(),
+                kind: TcpEventKind::Close,
+                remote_addr: "2001:db8::1".parse().unwrap(),
+                remote_port: 443,
+                local_port: 40000,
+                passive: false,
+                duration_ns: 5_000_000_000,
+                bytes_sent: 1024,
+                bytes_received: 65536,
+                retransmits: 2,
+                srtt_us: 850,
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::Tcp(e) => {
+                assert_eq!(e.kind, TcpEventKind::Close);
+                assert_eq!(e.endpoint(), "[2001:db8::1]:443");
+                assert_eq!(e.bytes_received, 65536);
+                assert_eq!(e.srtt_us, 850);
+            }
+            _ => panic!("expected Tcp"),
+        }
+    }
+
+    #[test]
+    fn test_sched_roundtrip() {
+        let msg = Message::new(
+            22,
+            vec![ProfileEvent::Sched(SchedEvent {
+                timestamp: 12,
+                pid: 8,
+                tid: 9,
+                comm: "worker".to_string(),
+                cpu_id: 3,
+                delay_ns: 25_000_000,
+                preempted: true,
+                waker_pid: 8,
+                waker_tid: 9,
+                cgroup: Some("/system.slice/app.service".to_string()),
+                preemptor_tid: Some(77),
+                preemptor_comm: Some("ffmpeg".to_string()),
+                stack_trace: vec![0x401000],
+                stack_symbols: vec![Some(vec![SymbolFrame::named("main")])],
+                stack_refs: vec![],
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::Sched(e) => {
+                assert!(e.preempted);
+                assert_eq!(e.delay_ns, 25_000_000);
+                assert_eq!(e.preemptor_comm.as_deref(), Some("ffmpeg"));
+                assert_eq!(e.cgroup.as_deref(), Some("/system.slice/app.service"));
+            }
+            _ => panic!("expected Sched"),
+        }
+    }
+
+    #[test]
+    fn test_block_io_roundtrip() {
+        let msg = Message::new(
+            20,
+            vec![ProfileEvent::BlockIo(BlockIoEvent {
+                timestamp: 11,
+                pid: 6,
+                tid: 7,
+                comm: "postgres".to_string(),
+                dev: 259 << 20,
+                device: Some("nvme0n1".to_string()),
+                op: BlockIoOp::Write,
+                sector: 2048,
+                bytes: 8192,
+                latency_ns: 120_000,
+                queue_depth: 3,
+                error: 0,
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::BlockIo(e) => {
+                assert_eq!(e.op, BlockIoOp::Write);
+                assert_eq!(e.device_name(), "nvme0n1");
+                assert_eq!(e.queue_depth, 3);
+            }
+            _ => panic!("expected BlockIo"),
+        }
+    }
+
+    #[test]
+    fn test_kernel_lock_roundtrip() {
+        let msg = Message::new(
+            17,
+            vec![ProfileEvent::KernelLock(KernelLockEvent {
+                timestamp: 9,
+                pid: 4,
+                tid: 5,
+                lock_addr: 0xffff888000002000,
+                flags: 0x4,
+                wait_time_ns: 70,
+                stack_trace: vec![0xffffffff81000000],
+                comm: "kworker".to_string(),
+                stack_symbols: vec![],
+                stack_refs: vec![],
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        match &decoded.events[0] {
+            ProfileEvent::KernelLock(e) => {
+                assert_eq!(e.lock_addr, 0xffff888000002000);
+                assert_eq!(e.lock_type(), "rwsem:W");
+            }
+            _ => panic!("expected KernelLock"),
+        }
+    }
+
+    #[test]
+    fn test_arch_roundtrip() {
+        let mut msg = Message::new(14, vec![]);
+        assert_eq!(msg.arch, Arch::host());
+        msg.arch = Arch::Aarch64;
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.version, PROTOCOL_VERSION);
+        assert_eq!(decoded.arch, Arch::Aarch64);
+    }
+
+    #[test]
+    fn test_syscall_summary_roundtrip() {
+        let msg = Message::new(
+            12,
+            vec![ProfileEvent::SyscallSummary(SyscallSummaryEvent {
+                timestamp: 5,
+                pid: 0,
+                syscall_id: 0,
+                count: 40,
+                total_duration_ns: 4_000,
+                max_duration_ns: 300,
+                min_duration_ns: 50,
+                error_count: 1,
+                latency_histogram: vec![0, 0, 0, 0, 0, 20, 20],
+                sample_every: 10,
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.version, PROTOCOL_VERSION);
+        match &decoded.events[0] {
+            ProfileEvent::SyscallSummary(s) => {
+                assert_eq!(s.count, 40);
+                assert_eq!(s.sample_every, 10);
+                assert_eq!(s.latency_histogram.len(), 7);
+            }
+            _ => panic!("expected SyscallSummary"),
+        }
+    }
+
+    #[test]
+    fn test_frame_refs_roundtrip() {
+        let frame_ref = FrameRef {
+            build_id: "deadbeef".to_string(),
+            file_offset: 0x1234,
+        };
+        let msg = Message::new(
+            6,
+            vec![ProfileEvent::Lock(LockEvent {
+                timestamp: 1,
+                pid: 3,
+                tid: 3,
+                lock_addr: 0x10,
+                hold_time_ns: 0,
+                wait_time_ns: 50,
+                stack_trace: vec![0x100, 0x200],
+                comm: "deferred".to_string(),
+                stack_symbols: vec![],
+                stack_refs: vec![Some(frame_ref.clone()), None],
+                kind: LockEventKind::Wait,
+                waker_tid: None,
+                lock_name: None,
+                labels: Default::default(),
+            })],
+        );
+        let decoded = Message::from_bytes(&msg.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.version, PROTOCOL_VERSION);
+        match &decoded.events[0] {
+            ProfileEvent::Lock(e) => assert_eq!(e.stack_refs, vec![Some(frame_ref), None]),
+            _ => panic!("expected Lock"),
+        }
+    }
 }
@@ -3,9 +3,12 @@
 //! Compares two profiles (baseline vs comparison) and computes per-entry deltas.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use super::profile::{LockProfile, Profile, Stack, SyscallProfile};
+use super::events::BlockIoOp;
+use super::profile::{
+    BlockIoProfile, LockProfile, Profile, RunQueueStats, SchedProfile, Stack, SyscallProfile,
+};
 
 // ── CPU diff ────────────────────────────────────────────────────────────────
 
@@ -30,18 +33,22 @@ pub struct StackDiff {
 }
 
 /// Compare two CPU profiles stack-by-stack.
+///
+/// Stacks are matched by frame names ([`Stack::name_key`]), not addresses, so
+/// profiles from different builds or ASLR layouts line up.
 pub fn diff_cpu(baseline: &Profile, comparison: &Profile) -> CpuDiff {
-    let all_stacks: HashSet<&Stack> = baseline
-        .samples
-        .keys()
-        .chain(comparison.samples.keys())
-        .collect();
+    // name key -> (representative stack, baseline count, comparison count)
+    let mut by_name: HashMap<Vec<String>, (&Stack, u64, u64)> = HashMap::new();
+    for (stack, &count) in &baseline.samples {
+        by_name.entry(stack.name_key()).or_insert((stack, 0, 0)).1 += count;
+    }
+    for (stack, &count) in &comparison.samples {
+        by_name.entry(stack.name_key()).or_insert((stack, 0, 0)).2 += count;
+    }
 
-    let mut stacks: Vec<StackDiff> = all_stacks
-        .into_iter()
-        .map(|stack| {
-            let b = baseline.samples.get(stack).copied().unwrap_or(0);
-            let c = comparison.samples.get(stack).copied().unwrap_or(0);
+    let mut stacks: Vec<StackDiff> = by_name
+        .into_values()
+        .map(|(stack, b, c)| {
             let delta = c as i64 - b as i64;
             let delta_pct = if b > 0 {
                 delta as f64 / b as f64 * 100.0
@@ -151,6 +158,161 @@ pub fn diff_syscall(baseline: &SyscallProfile, comparison: &SyscallProfile) -> S
     }
 }
 
+// ── Block I/O diff ──────────────────────────────────────────────────────────
+
+/// Diff of two block I/O profiles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIoDiff {
+    pub baseline_total: u64,
+    pub comparison_total: u64,
+    /// Per device and operation, sorted by |change in total latency| descending.
+    pub requests: Vec<BlockIoStatsDiff>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIoStatsDiff {
+    pub device: String,
+    pub op: BlockIoOp,
+    pub baseline_count: u64,
+    pub comparison_count: u64,
+    pub delta_count: i64,
+    pub baseline_avg_ns: f64,
+    pub comparison_avg_ns: f64,
+    pub delta_avg_ns: f64,
+    pub baseline_bytes: u64,
+    pub comparison_bytes: u64,
+    pub baseline_avg_queue_depth: f64,
+    pub comparison_avg_queue_depth: f64,
+}
+
+/// Compare two block I/O profiles per device and operation.
+pub fn diff_block_io(baseline: &BlockIoProfile, comparison: &BlockIoProfile) -> BlockIoDiff {
+    let all_keys: HashSet<(&str, BlockIoOp)> = baseline
+        .stats()
+        .chain(comparison.stats())
+        .map(|s| (s.device.as_str(), s.op))
+        .collect();
+
+    let mut requests: Vec<(BlockIoStatsDiff, i128)> = all_keys
+        .into_iter()
+        .map(|(device, op)| {
+            let b = baseline.devices.get(device).and_then(|ops| ops.get(&op));
+            let c = comparison.devices.get(device).and_then(|ops| ops.get(&op));
+
+            let b_count = b.map_or(0, |s| s.count);
+            let c_count = c.map_or(0, |s| s.count);
+            let b_avg = b.map_or(0.0, |s| s.avg_latency_ns() as f64);
+            let c_avg = c.map_or(0.0, |s| s.avg_latency_ns() as f64);
+            let b_total = b.map_or(0, |s| s.total_latency_ns);
+            let c_total = c.map_or(0, |s| s.total_latency_ns);
+
+            let diff = BlockIoStatsDiff {
+                device: device.to_string(),
+                op,
+                baseline_count: b_count,
+                comparison_count: c_count,
+                delta_count: c_count as i64 - b_count as i64,
+                baseline_avg_ns: b_avg,
+                comparison_avg_ns: c_avg,
+                delta_avg_ns: c_avg - b_avg,
+                baseline_bytes: b.map_or(0, |s| s.bytes),
+                comparison_bytes: c.map_or(0, |s| s.bytes),
+                baseline_avg_queue_depth: b.map_or(0.0, |s| s.avg_queue_depth()),
+                comparison_avg_queue_depth: c.map_or(0.0, |s| s.avg_queue_depth()),
+            };
+            (diff, c_total as i128 - b_total as i128)
+        })
+        .collect();
+
+    requests.sort_by_key(|r| std::cmp::Reverse(r.1.unsigned_abs()));
+
+    BlockIoDiff {
+        baseline_total: baseline.total_events,
+        comparison_total: comparison.total_events,
+        requests: requests.into_iter().map(|(d, _)| d).collect(),
+    }
+}
+
+// ── Scheduler diff ──────────────────────────────────────────────────────────
+
+/// Diff of two run-queue latency profiles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedDiff {
+    pub baseline_total: u64,
+    pub comparison_total: u64,
+    /// Per process name (PIDs change between runs), sorted by |change in
+    /// total wait| descending.
+    pub processes: Vec<SchedProcessDiff>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedProcessDiff {
+    pub comm: String,
+    pub baseline_count: u64,
+    pub comparison_count: u64,
+    pub delta_count: i64,
+    pub baseline_avg_ns: f64,
+    pub comparison_avg_ns: f64,
+    pub delta_avg_ns: f64,
+    pub baseline_max_ns: u64,
+    pub comparison_max_ns: u64,
+}
+
+/// Run-queue waits of `profile` per process name
+fn runq_by_comm(profile: &SchedProfile) -> HashMap<&str, RunQueueStats> {
+    let mut by_comm: HashMap<&str, RunQueueStats> = HashMap::new();
+    for p in profile.processes.values() {
+        let stats = by_comm.entry(p.comm.as_str()).or_default();
+        stats.count += p.runq.count;
+        stats.total_delay_ns += p.runq.total_delay_ns;
+        stats.max_delay_ns = stats.max_delay_ns.max(p.runq.max_delay_ns);
+    }
+    by_comm
+}
+
+/// Compare two run-queue latency profiles per process name.
+pub fn diff_sched(baseline: &SchedProfile, comparison: &SchedProfile) -> SchedDiff {
+    let b_by_comm = runq_by_comm(baseline);
+    let c_by_comm = runq_by_comm(comparison);
+    let all_comms: HashSet<&str> = b_by_comm.keys().chain(c_by_comm.keys()).copied().collect();
+
+    let mut processes: Vec<(SchedProcessDiff, i128)> = all_comms
+        .into_iter()
+        .map(|comm| {
+            let b = b_by_comm.get(comm);
+            let c = c_by_comm.get(comm);
+
+            let b_count = b.map_or(0, |s| s.count);
+            let c_count = c.map_or(0, |s| s.count);
+            let b_avg = b.map_or(0.0, |s| s.avg_delay_ns() as f64);
+            let c_avg = c.map_or(0.0, |s| s.avg_delay_ns() as f64);
+            let b_total = b.map_or(0, |s| s.total_delay_ns);
+            let c_total = c.map_or(0, |s| s.total_delay_ns);
+
+            let diff = SchedProcessDiff {
+                comm: comm.to_string(),
+                baseline_count: b_count,
+                comparison_count: c_count,
+                delta_count: c_count as i64 - b_count as i64,
+                baseline_avg_ns: b_avg,
+                comparison_avg_ns: c_avg,
+                delta_avg_ns: c_avg - b_avg,
+                baseline_max_ns: b.map_or(0, |s| s.max_delay_ns),
+                comparison_max_ns: c.map_or(0, |s| s.max_delay_ns),
+            };
+            (diff, c_total as i128 - b_total as i128)
+        })
+        .collect();
+
+    processes.sort_by_key(|p| std::cmp::Reverse(p.1.unsigned_abs()));
+
+    SchedDiff {
+        baseline_total: baseline.total_events,
+        comparison_total: comparison.total_events,
+        processes: processes.into_iter().map(|(d, _)| d).collect(),
+    }
+}
+
 // ── Lock diff ───────────────────────────────────────────────────────────────
 
 /// Diff of two lock contention profiles.
@@ -159,18 +321,35 @@ pub struct LockDiff {
     pub baseline_total: u64,
     pub comparison_total: u64,
     pub contentions: Vec<LockContentionDiff>,
+    /// Per lock identity: the same global or allocation site matches across
+    /// runs even when its address moved
+    #[serde(default)]
+    pub locks: Vec<LockGroupDiff>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockContentionDiff {
     pub lock_addr: u64,
+    #[serde(default)]
+    pub lock_name: Option<String>,
     pub stack: Stack,
     pub baseline_count: u64,
     pub comparison_count: u64,
     pub delta_wait_ns: i64,
 }
 
-/// Compare two lock contention profiles per (lock_addr, stack).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockGroupDiff {
+    /// Lock name, or its address in hex when it has none
+    pub name: String,
+    pub baseline_count: u64,
+    pub comparison_count: u64,
+    pub delta_wait_ns: i64,
+    pub delta_hold_ns: i64,
+}
+
+/// Compare two lock contention profiles per (lock_addr, stack) and per
+/// lock identity.
 pub fn diff_lock(baseline: &LockProfile, comparison: &LockProfile) -> LockDiff {
     let all_keys: HashSet<&(u64, Stack)> = baseline
         .contentions
@@ -187,6 +366,11 @@ pub fn diff_lock(baseline: &LockProfile, comparison: &LockProfile) -> LockDiff {
             let c_wait = c.map_or(0, |s| s.total_wait_ns);
             LockContentionDiff {
                 lock_addr: key.0,
+                lock_name: comparison
+                    .lock_names
+                    .get(&key.0)
+                    .or_else(|| baseline.lock_names.get(&key.0))
+                    .cloned(),
                 stack: key.1.clone(),
                 baseline_count: b.map_or(0, |s| s.count),
                 comparison_count: c.map_or(0, |s| s.count),
@@ -201,16 +385,47 @@ pub fn diff_lock(baseline: &LockProfile, comparison: &LockProfile) -> LockDiff {
             .cmp(&a.delta_wait_ns.unsigned_abs())
     });
 
+    let mut locks: HashMap<String, LockGroupDiff> = HashMap::new();
+    for (group, sign) in baseline
+        .group_by_lock()
+        .into_iter()
+        .map(|g| (g, -1))
+        .chain(comparison.group_by_lock().into_iter().map(|g| (g, 1)))
+    {
+        let diff = locks
+            .entry(group.name.clone())
+            .or_insert_with(|| LockGroupDiff {
+                name: group.name,
+                ..Default::default()
+            });
+        if sign < 0 {
+            diff.baseline_count += group.contention.count;
+        } else {
+            diff.comparison_count += group.contention.count;
+        }
+        diff.delta_wait_ns += sign * group.contention.total_wait_ns as i64;
+        diff.delta_hold_ns += sign * group.hold.total_hold_ns as i64;
+    }
+    let mut locks: Vec<LockGroupDiff> = locks.into_values().collect();
+    locks.sort_by(|a, b| {
+        b.delta_wait_ns
+            .unsigned_abs()
+            .cmp(&a.delta_wait_ns.unsigned_abs())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
     LockDiff {
         baseline_total: baseline.total_events,
         comparison_total: comparison.total_events,
         contentions,
+        locks,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::events::SymbolFrame;
 
     #[test]
     fn test_diff_cpu_basic() {
@@ -270,6 +485,28 @@ mod tests {
         assert_eq!(diff.stacks[0].delta_pct, 0.0);
     }
 
+    #[test]
+    fn test_diff_cpu_matches_stacks_by_name() {
+        let named = |ips: &[u64]| {
+            let symbols = vec![
+                Some(vec![SymbolFrame::named("work")]),
+                Some(vec![SymbolFrame::named("main")]),
+            ];
+            Stack::from_ips_with_symbols(ips, &symbols)
+        };
+        let mut baseline = Profile::new(0, 1000, 10_000_000);
+        let mut comparison = Profile::new(1000, 2000, 10_000_000);
+        // Same code, loaded at different addresses in the two runs
+        baseline.add_sample(named(&[0x5555_0000_1000, 0x5555_0000_2000]));
+        comparison.add_sample(named(&[0x7f00_0000_1000, 0x7f00_0000_2000]));
+        comparison.add_sample(named(&[0x7f00_0000_1000, 0x7f00_0000_2000]));
+
+        let diff = diff_cpu(&baseline, &comparison);
+        assert_eq!(diff.stacks.len(), 1);
+        assert_eq!(diff.stacks[0].baseline_count, 1);
+        assert_eq!(diff.stacks[0].comparison_count, 2);
+    }
+
     #[test]
     fn test_diff_syscall_basic() {
         let mut baseline = SyscallProfile::new(0);
@@ -296,6 +533,90 @@ mod tests {
         assert!((s.delta_avg_ns - 100.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_diff_block_io_by_device_and_op() {
+        use crate::types::events::BlockIoEvent;
+
+        let request = |device: &str, op, latency_ns| BlockIoEvent {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            comm: "db".to_string(),
+            dev: 0,
+            device: Some(device.to_string()),
+            op,
+            sector: 0,
+            bytes: 4096,
+            latency_ns,
+            queue_depth: 1,
+            error: 0,
+        };
+        let mut baseline = BlockIoProfile::new(0);
+        let mut comparison = BlockIoProfile::new(1000);
+        for _ in 0..10 {
+            baseline.add_event(&request("nvme0n1", BlockIoOp::Read, 100_000));
+            baseline.add_event(&request("nvme0n1", BlockIoOp::Write, 50_000));
+            comparison.add_event(&request("nvme0n1", BlockIoOp::Read, 110_000));
+        }
+        // Writes moved to a slower device
+        for _ in 0..10 {
+            comparison.add_event(&request("sda", BlockIoOp::Write, 900_000));
+        }
+
+        let diff = diff_block_io(&baseline, &comparison);
+        assert_eq!(diff.baseline_total, 20);
+        assert_eq!(diff.requests.len(), 3);
+        let first = &diff.requests[0];
+        assert_eq!((first.device.as_str(), first.op), ("sda", BlockIoOp::Write));
+        assert_eq!(first.baseline_count, 0);
+        assert!((first.comparison_avg_ns - 900_000.0).abs() < 0.01);
+        let read = &diff.requests[2];
+        assert_eq!(read.op, BlockIoOp::Read);
+        assert!((read.delta_avg_ns - 10_000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_diff_sched_by_process_name() {
+        use crate::types::events::SchedEvent;
+
+        let wait = |pid, comm: &str, delay_ns| SchedEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: comm.to_string(),
+            cpu_id: 0,
+            delay_ns,
+            preempted: false,
+            waker_pid: 0,
+            waker_tid: 0,
+            cgroup: None,
+            preemptor_tid: None,
+            preemptor_comm: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut baseline = SchedProfile::new(0);
+        let mut comparison = SchedProfile::new(1000);
+        for _ in 0..10 {
+            baseline.add_event(&wait(100, "api", 10_000));
+            baseline.add_event(&wait(200, "batch", 5_000));
+            // Restarted under a new PID, waiting longer
+            comparison.add_event(&wait(300, "api", 2_000_000));
+            comparison.add_event(&wait(200, "batch", 5_000));
+        }
+
+        let diff = diff_sched(&baseline, &comparison);
+        assert_eq!(diff.baseline_total, 20);
+        assert_eq!(diff.processes.len(), 2);
+        let api = &diff.processes[0];
+        assert_eq!(api.comm, "api");
+        assert_eq!(api.delta_count, 0);
+        assert!((api.delta_avg_ns - 1_990_000.0).abs() < 0.01);
+        assert_eq!(api.comparison_max_ns, 2_000_000);
+        assert_eq!(diff.processes[1].delta_avg_ns, 0.0);
+    }
+
     #[test]
     fn test_diff_lock_basic() {
         let mut baseline = LockProfile::new(0);
@@ -316,5 +637,37 @@ mod tests {
         assert_eq!(c.comparison_count, 1);
         // baseline total_wait = 800, comparison = 1000
         assert_eq!(c.delta_wait_ns, 200);
+        assert_eq!(c.lock_name, None);
+        assert_eq!(diff.locks[0].name, "0x1000");
+    }
+
+    #[test]
+    fn test_diff_lock_by_identity() {
+        let mut baseline = LockProfile::new(0);
+        let mut comparison = LockProfile::new(1000);
+        let stack = Stack::from_ips(&[0x400000]);
+
+        // The same global at a different address in each run
+        baseline.lock_names.insert(0x1000, "app::CACHE".to_string());
+        baseline.add_contention(0x1000, stack.clone(), 500);
+        comparison
+            .lock_names
+            .insert(0x7000, "app::CACHE".to_string());
+        comparison.add_contention(0x7000, stack.clone(), 2000);
+        comparison.add_contention(0x7000, stack.clone(), 1000);
+
+        let diff = diff_lock(&baseline, &comparison);
+        assert_eq!(diff.contentions.len(), 2);
+        assert!(diff
+            .contentions
+            .iter()
+            .all(|c| c.lock_name.as_deref() == Some("app::CACHE")));
+
+        assert_eq!(diff.locks.len(), 1);
+        let cache = &diff.locks[0];
+        assert_eq!(cache.name, "app::CACHE");
+        assert_eq!(cache.baseline_count, 1);
+        assert_eq!(cache.comparison_count, 2);
+        assert_eq!(cache.delta_wait_ns, 2500);
     }
 }
@@ -4,6 +4,7 @@
 //! processed by the agent.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Timestamp in nanoseconds since boot
 pub type Timestamp = u64;
@@ -20,6 +21,68 @@ pub type CpuId = u32;
 /// Stack trace represented as an array of instruction pointers
 pub type StackTrace = Vec<u64>;
 
+/// Profiling labels the application set on a thread (`endpoint`, `tenant`)
+/// with the `aperture-labels` library
+pub type Labels = BTreeMap<String, String>;
+
+/// Location of a user-space frame inside the ELF file that contains it.
+///
+/// Lets an agent ship stacks without resolving names locally: the aggregator
+/// symbolizes the offset against debug info uploaded for the same build ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FrameRef {
+    /// GNU build ID of the ELF file, lowercase hex
+    pub build_id: String,
+
+    /// File offset of the instruction within that ELF file
+    pub file_offset: u64,
+}
+
+impl FrameRef {
+    pub fn new(build_id: &[u8], file_offset: u64) -> Self {
+        Self {
+            build_id: Self::build_id_hex(build_id),
+            file_offset,
+        }
+    }
+
+    /// Lowercase hex form of a raw build ID, as used in `.build-id` paths
+    pub fn build_id_hex(build_id: &[u8]) -> String {
+        build_id.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// One function a stack address resolved to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SymbolFrame {
+    pub function: String,
+
+    /// Source file of the address, when debug info has line tables
+    pub file: Option<String>,
+
+    /// Source line of the address, when debug info has line tables
+    pub line: Option<u32>,
+
+    /// Inlined into the next frame of the symbol
+    pub inline: bool,
+}
+
+impl SymbolFrame {
+    /// A frame known only by name (symbol tables, JIT maps, kallsyms)
+    pub fn named(function: impl Into<String>) -> Self {
+        Self {
+            function: function.into(),
+            file: None,
+            line: None,
+            inline: false,
+        }
+    }
+}
+
+/// What a stack address resolved to: the functions inlined at it, innermost
+/// first, then the function containing it
+pub type Symbol = Vec<SymbolFrame>;
+
 /// CPU profiling sample event
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuSample {
@@ -44,13 +107,32 @@ pub struct CpuSample {
     /// Process name (comm)
     pub comm: String,
 
-    /// Pre-resolved symbol names for user_stack IPs (parallel array, same length)
+    /// Pre-resolved symbols for user_stack IPs (parallel array, same length)
+    #[serde(default)]
+    pub user_stack_symbols: Vec<Option<Symbol>>,
+
+    /// Pre-resolved symbols for kernel_stack IPs (parallel array, same length)
+    #[serde(default)]
+    pub kernel_stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for user_stack IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
     #[serde(default)]
-    pub user_stack_symbols: Vec<Option<String>>,
+    pub user_stack_refs: Vec<Option<FrameRef>>,
 
-    /// Pre-resolved symbol names for kernel_stack IPs (parallel array, same length)
+    /// Labels of the sampled thread when the sample was taken
     #[serde(default)]
-    pub kernel_stack_symbols: Vec<Option<String>>,
+    pub labels: Labels,
+}
+
+/// What a lock event measures
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockEventKind {
+    /// A thread waited for the lock; `stack_trace` is the waiter
+    #[default]
+    Wait,
+    /// A thread released the lock to waiters; `stack_trace` is the holder
+    Release,
 }
 
 /// Lock contention event
@@ -60,14 +142,111 @@ pub struct LockEvent {
     pub pid: Pid,
     pub tid: Tid,
     pub lock_addr: u64,
+    /// How long the lock was held before the release that ended the wait
+    /// (waits) or before this release (releases), 0 if unknown
     pub hold_time_ns: u64,
+    /// Time spent blocked (waits only)
+    pub wait_time_ns: u64,
+    pub stack_trace: StackTrace,
+    pub comm: String,
+
+    /// Pre-resolved symbols for stack_trace IPs (parallel array, same length)
+    #[serde(default)]
+    pub stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for stack_trace IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
+    #[serde(default)]
+    pub stack_refs: Vec<Option<FrameRef>>,
+
+    #[serde(default)]
+    pub kind: LockEventKind,
+
+    /// Thread whose release ended the wait, if it was seen
+    #[serde(default)]
+    pub waker_tid: Option<Tid>,
+
+    /// Variable or allocation site the lock lives in (`CACHE`,
+    /// `heap:Pool::new+0x10`), when the agent could resolve it
+    #[serde(default)]
+    pub lock_name: Option<String>,
+
+    /// Labels of the waiting (or releasing) thread at the time
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+// `lock:contention_begin` flags (`LCB_F_*` in include/trace/events/lock.h)
+pub const LCB_F_SPIN: u32 = 1 << 0;
+pub const LCB_F_READ: u32 = 1 << 1;
+pub const LCB_F_WRITE: u32 = 1 << 2;
+pub const LCB_F_RT: u32 = 1 << 3;
+pub const LCB_F_PERCPU: u32 = 1 << 4;
+pub const LCB_F_MUTEX: u32 = 1 << 5;
+
+/// Kernel lock contention (mmap_lock, inode locks, spinlocks, ...), from
+/// the `lock:contention_begin` and `lock:contention_end` tracepoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelLockEvent {
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    /// Kernel address of the lock
+    pub lock_addr: u64,
+    /// `LCB_F_*` flags of the contention, see [`kernel_lock_type`]
+    pub flags: u32,
     pub wait_time_ns: u64,
+    /// User stack followed by the kernel stack, as for [`LockEvent`]
     pub stack_trace: StackTrace,
     pub comm: String,
 
-    /// Pre-resolved symbol names for stack_trace IPs (parallel array, same length)
+    /// Pre-resolved symbols for stack_trace IPs (parallel array, same length)
     #[serde(default)]
-    pub stack_symbols: Vec<Option<String>>,
+    pub stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for stack_trace IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
+    #[serde(default)]
+    pub stack_refs: Vec<Option<FrameRef>>,
+}
+
+impl KernelLockEvent {
+    pub fn lock_type(&self) -> String {
+        kernel_lock_type(self.flags)
+    }
+}
+
+/// Lock type named after `LCB_F_*` flags, the way `perf lock contention`
+/// does: `spinlock`, `rwlock:R`, `rwsem:W`, `mutex`, `rt-mutex`,
+/// `pcpu-sem:R`, ... Readers and writers of one lock seen together drop the
+/// `:R`/`:W` suffix.
+pub fn kernel_lock_type(flags: u32) -> String {
+    let rw = flags & (LCB_F_READ | LCB_F_WRITE);
+    let base = if flags & LCB_F_MUTEX != 0 {
+        // Optimistic spinning on a mutex also sets LCB_F_SPIN
+        return "mutex".to_string();
+    } else if flags & LCB_F_PERCPU != 0 {
+        "pcpu-sem"
+    } else if flags & LCB_F_RT != 0 {
+        if rw == 0 {
+            return "rt-mutex".to_string();
+        }
+        "rt-rwlock"
+    } else if flags & LCB_F_SPIN != 0 {
+        if rw == 0 {
+            return "spinlock".to_string();
+        }
+        "rwlock"
+    } else if rw != 0 {
+        "rwsem"
+    } else {
+        return "unknown".to_string();
+    };
+    match rw {
+        LCB_F_READ => format!("{}:R", base),
+        LCB_F_WRITE => format!("{}:W", base),
+        _ => base.to_string(),
+    }
 }
 
 /// Syscall event
@@ -80,6 +259,179 @@ pub struct SyscallEvent {
     pub duration_ns: u64,
     pub return_value: i64,
     pub comm: String,
+
+    /// File descriptor argument (read/write/fsync/socket syscalls)
+    #[serde(default)]
+    pub fd: Option<i32>,
+
+    /// File or socket the syscall touched: the path argument of
+    /// open/stat-like syscalls, or what `fd` resolved to (a path, or a
+    /// socket tuple like `tcp 10.0.0.1:5432->10.0.0.2:40112`)
+    #[serde(default)]
+    pub target: Option<String>,
+
+    /// Bytes transferred, for successful read/write/send/recv syscalls
+    #[serde(default)]
+    pub bytes: Option<u64>,
+
+    /// User + kernel stack, captured only for calls slower than the agent's
+    /// stack threshold
+    #[serde(default)]
+    pub stack_trace: StackTrace,
+
+    /// Pre-resolved symbols for stack_trace IPs (parallel array, same length)
+    #[serde(default)]
+    pub stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for stack_trace IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
+    #[serde(default)]
+    pub stack_refs: Vec<Option<FrameRef>>,
+
+    /// Calls this event stands for: N when the agent recorded 1 in N calls
+    #[serde(default = "exact_sampling")]
+    pub sample_every: u32,
+}
+
+/// Per-syscall latency summary kept in-kernel by the agent's aggregation
+/// mode, covering the calls since the previous summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyscallSummaryEvent {
+    /// End of the interval covered
+    pub timestamp: Timestamp,
+    /// Target process, 0 when tracing system-wide
+    pub pid: Pid,
+    pub syscall_id: u32,
+    pub count: u64,
+    pub total_duration_ns: u64,
+    /// Longest call in the interval
+    pub max_duration_ns: u64,
+    /// Shortest call in the interval
+    pub min_duration_ns: u64,
+    pub error_count: u64,
+    /// Same log2 buckets as `SyscallStats::latency_histogram`
+    pub latency_histogram: Vec<u64>,
+    /// Counts are of 1 in N calls; multiply by this to estimate all calls
+    pub sample_every: u32,
+}
+
+fn exact_sampling() -> u32 {
+    1
+}
+
+/// Block device operation of a request, from the kernel's `rwbs` string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockIoOp {
+    Read,
+    Write,
+    Discard,
+    Flush,
+    Other,
+}
+
+impl BlockIoOp {
+    /// Operation of an `rwbs` string like `WS`, `R`, `FWS` or `DS`. A leading
+    /// `F` before another operation letter is a preflush, not the operation.
+    pub fn from_rwbs(rwbs: &str) -> Self {
+        let bytes = rwbs.as_bytes();
+        let op = match bytes {
+            [b'F', next, ..] if b"RWDFN".contains(next) => *next,
+            [first, ..] => *first,
+            [] => 0,
+        };
+        match op {
+            b'R' => BlockIoOp::Read,
+            b'W' => BlockIoOp::Write,
+            b'D' => BlockIoOp::Discard,
+            b'F' => BlockIoOp::Flush,
+            _ => BlockIoOp::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockIoOp::Read => "read",
+            BlockIoOp::Write => "write",
+            BlockIoOp::Discard => "discard",
+            BlockIoOp::Flush => "flush",
+            BlockIoOp::Other => "other",
+        }
+    }
+}
+
+/// Block I/O request, from issue to the device until its completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIoEvent {
+    /// When the request was issued to the device
+    pub timestamp: Timestamp,
+    /// Process running when the request was issued: the submitter for
+    /// direct and synchronous I/O, often a flusher thread for writeback
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    /// Kernel device number (`major << 20 | minor`)
+    pub dev: u32,
+    /// Device name like `nvme0n1`, when the agent could resolve it
+    pub device: Option<String>,
+    pub op: BlockIoOp,
+    pub sector: u64,
+    pub bytes: u32,
+    /// Issue to completion
+    pub latency_ns: u64,
+    /// Requests in flight on the device when this one was issued, itself included
+    pub queue_depth: u32,
+    /// Negative errno from completion, 0 on success
+    pub error: i32,
+}
+
+impl BlockIoEvent {
+    /// Device name, or `major:minor` when unresolved
+    pub fn device_name(&self) -> String {
+        self.device
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", self.dev >> 20, self.dev & 0xfffff))
+    }
+}
+
+/// Time a thread spent runnable, waiting for a CPU
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedEvent {
+    /// When the thread became runnable
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    /// CPU the thread got
+    pub cpu_id: CpuId,
+    /// Runnable until switched in
+    pub delay_ns: u64,
+    /// The wait began with the thread being preempted, not woken up
+    pub preempted: bool,
+    /// Thread that woke it up (itself when preempted)
+    pub waker_pid: Pid,
+    pub waker_tid: Tid,
+    /// cgroup v2 path of the thread (`/system.slice/nginx.service`), when
+    /// the agent could resolve it
+    pub cgroup: Option<String>,
+    /// Task that had the CPU just before the thread got it, for waits
+    /// longer than the agent's long-wait threshold
+    pub preemptor_tid: Option<Tid>,
+    pub preemptor_comm: Option<String>,
+
+    /// User + kernel stack of the waker when the thread was made runnable,
+    /// captured only when the agent has wakeup stacks enabled
+    #[serde(default)]
+    pub stack_trace: StackTrace,
+
+    /// Pre-resolved symbols for stack_trace IPs (parallel array, same length)
+    #[serde(default)]
+    pub stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for stack_trace IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
+    #[serde(default)]
+    pub stack_refs: Vec<Option<FrameRef>>,
 }
 
 /// GPU kernel execution event
@@ -93,6 +445,205 @@ pub struct GpuKernelEvent {
     pub block_size: (u32, u32, u32),
 }
 
+/// Kind of process lifecycle transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProcessEventKind {
+    Exec,
+    Exit,
+    Fork,
+}
+
+/// Process lifecycle event (exec/exit/fork)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessEvent {
+    pub timestamp: Timestamp,
+    /// Process the event is about (the child for fork events)
+    pub pid: Pid,
+    /// Parent process for fork events, 0 otherwise
+    pub ppid: Pid,
+    pub kind: ProcessEventKind,
+    pub comm: String,
+    /// Path passed to execve (exec events only)
+    pub filename: Option<String>,
+}
+
+/// What happened on a TCP connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcpEventKind {
+    /// Handshake completed
+    Connect,
+    /// Connection closed before the handshake completed (refused, timed out)
+    ConnectFailed,
+    Retransmit,
+    ResetSent,
+    ResetReceived,
+    /// Connection closed; carries its totals
+    Close,
+}
+
+impl TcpEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TcpEventKind::Connect => "connect",
+            TcpEventKind::ConnectFailed => "connect_failed",
+            TcpEventKind::Retransmit => "retransmit",
+            TcpEventKind::ResetSent => "reset_sent",
+            TcpEventKind::ResetReceived => "reset_received",
+            TcpEventKind::Close => "close",
+        }
+    }
+}
+
+/// TCP connection event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcpEvent {
+    pub timestamp: Timestamp,
+    /// Process that connected, or first used an accepted connection; 0 if
+    /// none has
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    pub kind: TcpEventKind,
+    pub remote_addr: std::net::IpAddr,
+    pub remote_port: u16,
+    pub local_port: u16,
+    /// Accepted rather than connected
+    pub passive: bool,
+    /// Handshake time for Connect and ConnectFailed, connection lifetime
+    /// for Close, 0 otherwise
+    pub duration_ns: u64,
+    /// Bytes over the connection's lifetime (so far, for Close the total)
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Retransmits over the connection's lifetime
+    pub retransmits: u32,
+    /// Last smoothed round-trip time in microseconds, 0 if unknown
+    pub srtt_us: u32,
+}
+
+impl TcpEvent {
+    /// Remote endpoint the event is grouped under: `addr:port` for
+    /// outgoing connections, `addr -> :port` (the local listening port) for
+    /// accepted ones, whose remote ports are ephemeral
+    pub fn endpoint(&self) -> String {
+        let addr = match self.remote_addr {
+            std::net::IpAddr::V4(a) => a.to_string(),
+            std::net::IpAddr::V6(a) => format!("[{}]", a),
+        };
+        if self.passive {
+            format!("{} -> :{}", addr, self.local_port)
+        } else {
+            format!("{}:{}", addr, self.remote_port)
+        }
+    }
+}
+
+/// Sampled page fault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageFaultEvent {
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    /// Faulting address
+    pub address: u64,
+    /// Faulting instruction
+    pub ip: u64,
+    /// Architecture fault error code (x86: bit 0 protection, 1 write,
+    /// 2 user, 4 instruction fetch)
+    pub error_code: u64,
+    /// Raised in kernel mode (e.g. by `copy_from_user`) rather than by a
+    /// user instruction
+    pub kernel: bool,
+    /// Faults this sample stands for (the agent records 1 in N)
+    pub weight: u32,
+
+    /// User stack, then the kernel stack for kernel-mode faults
+    #[serde(default)]
+    pub stack_trace: StackTrace,
+
+    /// Pre-resolved symbols for stack_trace IPs (parallel array, same length)
+    #[serde(default)]
+    pub stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for stack_trace IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
+    #[serde(default)]
+    pub stack_refs: Vec<Option<FrameRef>>,
+}
+
+/// Time a task stalled in memory reclaim on its own allocation path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReclaimEvent {
+    /// When reclaim began
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    pub stall_ns: u64,
+    /// Pages freed
+    pub nr_reclaimed: u64,
+    /// Order of the allocation that entered reclaim
+    pub order: u32,
+    /// Reclaim was for the task's memory cgroup limit rather than the
+    /// system running low
+    pub memcg: bool,
+}
+
+/// Call of a user-defined probe's function, or hit of its tracepoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeEvent {
+    /// Function entry, or when the tracepoint fired
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    /// Probe spec as given to the agent (`kprobe:vfs_read`)
+    pub probe: String,
+    /// Entry to return; None for tracepoint hits
+    pub duration_ns: Option<u64>,
+
+    /// User + kernel stack at function entry or at the tracepoint, captured
+    /// only when the agent has probe stacks enabled
+    #[serde(default)]
+    pub stack_trace: StackTrace,
+
+    /// Pre-resolved symbols for stack_trace IPs (parallel array, same length)
+    #[serde(default)]
+    pub stack_symbols: Vec<Option<Symbol>>,
+
+    /// Build ID + file offset for stack_trace IPs, for deferred symbolization
+    /// (parallel array, empty when the agent symbolized locally)
+    #[serde(default)]
+    pub stack_refs: Vec<Option<FrameRef>>,
+}
+
+/// Hit of a USDT probe, with its arguments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsdtEvent {
+    pub timestamp: Timestamp,
+    pub pid: Pid,
+    pub tid: Tid,
+    pub comm: String,
+    /// Probe as `provider:name` (`postgresql:query__start`)
+    pub probe: String,
+    /// Argument values, sign- or zero-extended from their declared size;
+    /// pointers are user-space addresses
+    pub args: Vec<i64>,
+    /// Span this hit ends, when its probe closes a configured pair
+    pub span: Option<UsdtSpan>,
+}
+
+/// Time between the hits of a paired start and end probe on one thread
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsdtSpan {
+    /// Pair name (`postgresql:query__start..query__done`)
+    pub name: String,
+    /// Timestamp of the start probe's hit
+    pub start: Timestamp,
+}
+
 /// Unified profiling event type
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProfileEvent {
@@ -100,6 +651,16 @@ pub enum ProfileEvent {
     Lock(LockEvent),
     Syscall(SyscallEvent),
     GpuKernel(GpuKernelEvent),
+    Process(ProcessEvent),
+    SyscallSummary(SyscallSummaryEvent),
+    KernelLock(KernelLockEvent),
+    BlockIo(BlockIoEvent),
+    Sched(SchedEvent),
+    Tcp(TcpEvent),
+    PageFault(PageFaultEvent),
+    Reclaim(ReclaimEvent),
+    Probe(ProbeEvent),
+    Usdt(UsdtEvent),
 }
 
 impl ProfileEvent {
@@ -110,6 +671,16 @@ impl ProfileEvent {
             ProfileEvent::Lock(e) => e.timestamp,
             ProfileEvent::Syscall(e) => e.timestamp,
             ProfileEvent::GpuKernel(e) => e.timestamp,
+            ProfileEvent::Process(e) => e.timestamp,
+            ProfileEvent::SyscallSummary(e) => e.timestamp,
+            ProfileEvent::KernelLock(e) => e.timestamp,
+            ProfileEvent::BlockIo(e) => e.timestamp,
+            ProfileEvent::Sched(e) => e.timestamp,
+            ProfileEvent::Tcp(e) => e.timestamp,
+            ProfileEvent::PageFault(e) => e.timestamp,
+            ProfileEvent::Reclaim(e) => e.timestamp,
+            ProfileEvent::Probe(e) => e.timestamp,
+            ProfileEvent::Usdt(e) => e.timestamp,
         }
     }
 
@@ -120,6 +691,16 @@ impl ProfileEvent {
             ProfileEvent::Lock(e) => e.pid,
             ProfileEvent::Syscall(e) => e.pid,
             ProfileEvent::GpuKernel(e) => e.pid,
+            ProfileEvent::Process(e) => e.pid,
+            ProfileEvent::SyscallSummary(e) => e.pid,
+            ProfileEvent::KernelLock(e) => e.pid,
+            ProfileEvent::BlockIo(e) => e.pid,
+            ProfileEvent::Sched(e) => e.pid,
+            ProfileEvent::Tcp(e) => e.pid,
+            ProfileEvent::PageFault(e) => e.pid,
+            ProfileEvent::Reclaim(e) => e.pid,
+            ProfileEvent::Probe(e) => e.pid,
+            ProfileEvent::Usdt(e) => e.pid,
         }
     }
 }
@@ -140,6 +721,8 @@ mod tests {
             comm: "test".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         };
 
         let json = serde_json::to_string(&sample).unwrap();
@@ -149,6 +732,13 @@ mod tests {
         assert_eq!(sample.timestamp, deserialized.timestamp);
     }
 
+    #[test]
+    fn test_frame_ref_hex_build_id() {
+        let frame_ref = FrameRef::new(&[0xde, 0xad, 0x01], 0x40);
+        assert_eq!(frame_ref.build_id, "dead01");
+        assert_eq!(frame_ref.file_offset, 0x40);
+    }
+
     #[test]
     fn test_profile_event_bincode_serialization() {
         use bincode::Options;
@@ -164,6 +754,13 @@ mod tests {
             duration_ns: 1000,
             return_value: 0,
             comm: "test".to_string(),
+            fd: None,
+            target: None,
+            bytes: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: 1,
         });
 
         let bytes = config.serialize(&event).unwrap();
@@ -186,4 +783,62 @@ mod tests {
             _ => panic!("Wrong variant"),
         }
     }
+
+    #[test]
+    fn test_kernel_lock_types() {
+        assert_eq!(kernel_lock_type(LCB_F_SPIN), "spinlock");
+        assert_eq!(kernel_lock_type(LCB_F_SPIN | LCB_F_READ), "rwlock:R");
+        assert_eq!(kernel_lock_type(LCB_F_WRITE), "rwsem:W");
+        assert_eq!(kernel_lock_type(LCB_F_READ | LCB_F_WRITE), "rwsem");
+        assert_eq!(kernel_lock_type(LCB_F_MUTEX | LCB_F_SPIN), "mutex");
+        assert_eq!(kernel_lock_type(LCB_F_RT), "rt-mutex");
+        assert_eq!(kernel_lock_type(LCB_F_PERCPU | LCB_F_READ), "pcpu-sem:R");
+        assert_eq!(kernel_lock_type(0), "unknown");
+    }
+
+    #[test]
+    fn test_block_io_op_from_rwbs() {
+        assert_eq!(BlockIoOp::from_rwbs("R"), BlockIoOp::Read);
+        assert_eq!(BlockIoOp::from_rwbs("RA"), BlockIoOp::Read);
+        assert_eq!(BlockIoOp::from_rwbs("WS"), BlockIoOp::Write);
+        // Preflush + write, and a FUA write
+        assert_eq!(BlockIoOp::from_rwbs("FWS"), BlockIoOp::Write);
+        assert_eq!(BlockIoOp::from_rwbs("WFS"), BlockIoOp::Write);
+        assert_eq!(BlockIoOp::from_rwbs("FF"), BlockIoOp::Flush);
+        assert_eq!(BlockIoOp::from_rwbs("F"), BlockIoOp::Flush);
+        assert_eq!(BlockIoOp::from_rwbs("DE"), BlockIoOp::Discard);
+        assert_eq!(BlockIoOp::from_rwbs("N"), BlockIoOp::Other);
+        assert_eq!(BlockIoOp::from_rwbs(""), BlockIoOp::Other);
+    }
+
+    #[test]
+    fn test_tcp_endpoint() {
+        let mut event = TcpEvent {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            comm: "curl".to_string(),
+            kind: TcpEventKind::Connect,
+            remote_addr: "10.0.0.7".parse().unwrap(),
+            remote_port: 443,
+            local_port: 51234,
+            passive: false,
+            duration_ns: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            retransmits: 0,
+            srtt_us: 0,
+        };
+        assert_eq!(event.endpoint(), "10.0.0.7:443");
+        event.remote_addr = "fd00::7".parse().unwrap();
+        assert_eq!(event.endpoint(), "[fd00::7]:443");
+        // Accepted: grouped by client address and local port
+        event.passive = true;
+        event.local_port = 8080;
+        assert_eq!(event.endpoint(), "[fd00::7] -> :8080");
+        assert_eq!(
+            serde_json::to_string(&TcpEventKind::ResetReceived).unwrap(),
+            "\"reset_received\""
+        );
+    }
 }
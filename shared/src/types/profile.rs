@@ -3,8 +3,13 @@
 //! These types represent aggregated profiling data, suitable for storage
 //! and visualization.
 
+use crate::types::events::{
+    kernel_lock_type, BlockIoEvent, BlockIoOp, PageFaultEvent, Pid, ProbeEvent, ReclaimEvent,
+    SchedEvent, Symbol, SymbolFrame, SyscallEvent, SyscallSummaryEvent, TcpEvent, TcpEventKind,
+    Tid, Timestamp, UsdtEvent,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// A single frame in a stack trace
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -23,6 +28,11 @@ pub struct Frame {
 
     /// Module/library name
     pub module: Option<String>,
+
+    /// True for a function inlined into the next (outer) frame; inline frames
+    /// share the instruction pointer of the frame they were inlined into
+    #[serde(default)]
+    pub inline: bool,
 }
 
 impl Frame {
@@ -34,6 +44,7 @@ impl Frame {
             file: None,
             line: None,
             module: None,
+            inline: false,
         }
     }
 
@@ -41,6 +52,69 @@ impl Frame {
     pub fn is_symbolized(&self) -> bool {
         self.function.is_some()
     }
+
+    /// `file:line` when both are known
+    pub fn location(&self) -> Option<String> {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+            _ => None,
+        }
+    }
+
+    /// Name for folded/collapsed stack output. Inline frames get the `_[i]`
+    /// suffix flamegraph tools color as inlined; `with_location` appends the
+    /// source location so each line gets its own box.
+    pub fn folded_name(&self, with_location: bool) -> String {
+        let mut name = self
+            .function
+            .clone()
+            .unwrap_or_else(|| format!("0x{:x}", self.ip));
+        if with_location {
+            if let Some(location) = self.location() {
+                name = format!("{} ({})", name, location);
+            }
+        }
+        if self.inline {
+            name.push_str("_[i]");
+        }
+        name
+    }
+
+    /// Expand the symbol an agent resolved `ip` to into frames, innermost
+    /// first
+    pub fn from_symbol(ip: u64, symbol: &[SymbolFrame]) -> Vec<Frame> {
+        symbol
+            .iter()
+            .map(|frame| Frame {
+                ip,
+                function: Some(frame.function.clone()),
+                file: frame.file.clone(),
+                line: frame.line,
+                module: None,
+                inline: frame.inline,
+            })
+            .collect()
+    }
+
+    /// Symbol for an inline chain (innermost first), naming each frame with
+    /// `name`. Inverse of [`Frame::from_symbol`].
+    pub fn to_symbol<'a>(
+        chain: impl IntoIterator<Item = &'a Frame>,
+        name: impl Fn(&Frame) -> Option<String>,
+    ) -> Option<Symbol> {
+        let symbol = chain
+            .into_iter()
+            .map(|frame| {
+                Some(SymbolFrame {
+                    function: name(frame)?,
+                    file: frame.file.clone(),
+                    line: frame.line,
+                    inline: frame.inline,
+                })
+            })
+            .collect::<Option<Symbol>>()?;
+        (!symbol.is_empty()).then_some(symbol)
+    }
 }
 
 /// A complete stack trace with symbol information
@@ -51,6 +125,12 @@ pub struct Stack {
 }
 
 impl Stack {
+    /// Frame names, innermost first: identifies the stack independently of
+    /// addresses, so stacks from different builds or processes compare equal.
+    pub fn name_key(&self) -> Vec<String> {
+        self.frames.iter().map(|f| f.folded_name(false)).collect()
+    }
+
     /// Create a new stack from instruction pointers
     pub fn from_ips(ips: &[u64]) -> Self {
         Self {
@@ -58,22 +138,17 @@ impl Stack {
         }
     }
 
-    /// Create a stack from IPs with optional pre-resolved symbol names.
-    /// `symbols` is a parallel array to `ips`; entries with `Some(name)` populate the frame's function field.
-    pub fn from_ips_with_symbols(ips: &[u64], symbols: &[Option<String>]) -> Self {
+    /// Create a stack from IPs with optional pre-resolved symbols.
+    /// `symbols` is a parallel array to `ips`; entries with `Some(symbol)` populate the
+    /// frame's function, source location and inlined callers (see [`Frame::from_symbol`]).
+    pub fn from_ips_with_symbols(ips: &[u64], symbols: &[Option<Symbol>]) -> Self {
         Self {
             frames: ips
                 .iter()
                 .enumerate()
-                .map(|(i, &ip)| {
-                    let function = symbols.get(i).and_then(|s| s.clone());
-                    Frame {
-                        ip,
-                        function,
-                        file: None,
-                        line: None,
-                        module: None,
-                    }
+                .flat_map(|(i, &ip)| match symbols.get(i).and_then(|s| s.as_deref()) {
+                    Some(symbol) => Frame::from_symbol(ip, symbol),
+                    None => vec![Frame::new_unresolved(ip)],
                 })
                 .collect(),
         }
@@ -97,6 +172,11 @@ pub struct Profile {
 
     /// Sampling period in nanoseconds
     pub sample_period_ns: u64,
+
+    /// Frame normalization rules that changed frames, with the number of
+    /// (unique-stack) frames each one changed
+    #[serde(default)]
+    pub normalization: BTreeMap<String, u64>,
 }
 
 impl Profile {
@@ -108,6 +188,7 @@ impl Profile {
             samples: HashMap::new(),
             total_samples: 0,
             sample_period_ns,
+            normalization: BTreeMap::new(),
         }
     }
 
@@ -117,6 +198,12 @@ impl Profile {
         self.total_samples += 1;
     }
 
+    /// Add a sample standing for `weight` events (sampled 1 in `weight`)
+    pub fn add_weighted_sample(&mut self, stack: Stack, weight: u64) {
+        *self.samples.entry(stack).or_insert(0) += weight;
+        self.total_samples += weight;
+    }
+
     /// Get the duration of the profile in nanoseconds
     pub fn duration_ns(&self) -> u64 {
         self.end_time.saturating_sub(self.start_time)
@@ -141,6 +228,16 @@ pub struct LockContentionStats {
     pub min_wait_ns: u64,
 }
 
+impl LockContentionStats {
+    /// Fold another set of stats for the same key into this one
+    pub fn merge(&mut self, other: &LockContentionStats) {
+        self.count += other.count;
+        self.total_wait_ns += other.total_wait_ns;
+        self.max_wait_ns = self.max_wait_ns.max(other.max_wait_ns);
+        self.min_wait_ns = self.min_wait_ns.min(other.min_wait_ns);
+    }
+}
+
 impl Default for LockContentionStats {
     fn default() -> Self {
         Self {
@@ -152,6 +249,36 @@ impl Default for LockContentionStats {
     }
 }
 
+/// Statistics for how long a lock was held before being released to waiters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockHoldStats {
+    pub count: u64,
+    pub total_hold_ns: u64,
+    pub max_hold_ns: u64,
+    pub min_hold_ns: u64,
+}
+
+impl LockHoldStats {
+    /// Fold another set of stats for the same key into this one
+    pub fn merge(&mut self, other: &LockHoldStats) {
+        self.count += other.count;
+        self.total_hold_ns += other.total_hold_ns;
+        self.max_hold_ns = self.max_hold_ns.max(other.max_hold_ns);
+        self.min_hold_ns = self.min_hold_ns.min(other.min_hold_ns);
+    }
+}
+
+impl Default for LockHoldStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total_hold_ns: 0,
+            max_hold_ns: 0,
+            min_hold_ns: u64::MAX,
+        }
+    }
+}
+
 /// Profile of lock contention events
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockProfile {
@@ -159,7 +286,16 @@ pub struct LockProfile {
     pub end_time: u64,
     // (lock_addr, stack) -> stats
     pub contentions: HashMap<(u64, Stack), LockContentionStats>,
+    /// Contended releases: (lock_addr, holder stack) -> how long it was held
+    #[serde(default)]
+    pub holders: HashMap<(u64, Stack), LockHoldStats>,
     pub total_events: u64,
+    /// Frame normalization rules that changed frames (see [`Profile::normalization`])
+    #[serde(default)]
+    pub normalization: BTreeMap<String, u64>,
+    /// Variable or allocation site of each lock the agent could name
+    #[serde(default)]
+    pub lock_names: HashMap<u64, String>,
 }
 
 impl LockProfile {
@@ -168,7 +304,10 @@ impl LockProfile {
             start_time,
             end_time: 0,
             contentions: HashMap::new(),
+            holders: HashMap::new(),
             total_events: 0,
+            normalization: BTreeMap::new(),
+            lock_names: HashMap::new(),
         }
     }
 
@@ -182,6 +321,143 @@ impl LockProfile {
         self.total_events += 1;
     }
 
+    /// Record a release that woke waiters, from the releasing thread's stack
+    pub fn add_hold(&mut self, lock_addr: u64, stack: Stack, hold_ns: u64) {
+        let stats = self.holders.entry((lock_addr, stack)).or_default();
+
+        stats.count += 1;
+        stats.total_hold_ns += hold_ns;
+        stats.max_hold_ns = stats.max_hold_ns.max(hold_ns);
+        stats.min_hold_ns = stats.min_hold_ns.min(hold_ns);
+        self.total_events += 1;
+    }
+
+    pub fn as_weighted_stacks(&self) -> HashMap<Stack, u64> {
+        let mut stacks = HashMap::new();
+        for ((_, stack), stats) in &self.contentions {
+            *stacks.entry(stack.clone()).or_insert(0) += stats.total_wait_ns;
+        }
+        stacks
+    }
+
+    /// Holder stacks weighted by the time they held contended locks
+    pub fn holder_weighted_stacks(&self) -> HashMap<Stack, u64> {
+        let mut stacks = HashMap::new();
+        for ((_, stack), stats) in &self.holders {
+            *stacks.entry(stack.clone()).or_insert(0) += stats.total_hold_ns;
+        }
+        stacks
+    }
+
+    /// Name of the lock at `lock_addr`, or its address in hex
+    pub fn lock_name(&self, lock_addr: u64) -> String {
+        match self.lock_names.get(&lock_addr) {
+            Some(name) => name.clone(),
+            None => format!("0x{:x}", lock_addr),
+        }
+    }
+
+    /// Contention and holds merged per lock identity: every address with
+    /// the same name (a global in several processes, the objects of one
+    /// allocation site) is one lock. Most waited-on first.
+    pub fn group_by_lock(&self) -> Vec<LockGroup> {
+        let mut groups: HashMap<String, LockGroup> = HashMap::new();
+        for ((lock_addr, _), stats) in &self.contentions {
+            self.lock_group(&mut groups, *lock_addr)
+                .contention
+                .merge(stats);
+        }
+        for ((lock_addr, _), stats) in &self.holders {
+            self.lock_group(&mut groups, *lock_addr).hold.merge(stats);
+        }
+
+        let mut groups: Vec<LockGroup> = groups.into_values().collect();
+        for group in &mut groups {
+            group.lock_addrs.sort_unstable();
+        }
+        groups.sort_by(|a, b| {
+            b.contention
+                .total_wait_ns
+                .cmp(&a.contention.total_wait_ns)
+                .then_with(|| b.hold.total_hold_ns.cmp(&a.hold.total_hold_ns))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        groups
+    }
+
+    fn lock_group<'a>(
+        &self,
+        groups: &'a mut HashMap<String, LockGroup>,
+        lock_addr: u64,
+    ) -> &'a mut LockGroup {
+        let name = self.lock_name(lock_addr);
+        let group = groups.entry(name.clone()).or_insert_with(|| LockGroup {
+            name,
+            lock_addrs: Vec::new(),
+            contention: LockContentionStats::default(),
+            hold: LockHoldStats::default(),
+        });
+        if !group.lock_addrs.contains(&lock_addr) {
+            group.lock_addrs.push(lock_addr);
+        }
+        group
+    }
+}
+
+/// Lock contention and holds of one lock identity (see [`LockProfile::group_by_lock`])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockGroup {
+    /// Lock name, or its address in hex when it has none
+    pub name: String,
+    /// Addresses merged into this lock
+    pub lock_addrs: Vec<u64>,
+    pub contention: LockContentionStats,
+    pub hold: LockHoldStats,
+}
+
+/// Profile of kernel lock contention, aggregated like [`LockProfile`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelLockProfile {
+    pub start_time: u64,
+    pub end_time: u64,
+    // (lock_addr, stack) -> stats
+    pub contentions: HashMap<(u64, Stack), LockContentionStats>,
+    /// `LCB_F_*` flags seen for each lock, which give its type
+    pub lock_flags: HashMap<u64, u32>,
+    pub total_events: u64,
+    /// Frame normalization rules that changed frames (see [`Profile::normalization`])
+    #[serde(default)]
+    pub normalization: BTreeMap<String, u64>,
+}
+
+impl KernelLockProfile {
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: 0,
+            contentions: HashMap::new(),
+            lock_flags: HashMap::new(),
+            total_events: 0,
+            normalization: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_contention(&mut self, lock_addr: u64, flags: u32, stack: Stack, wait_ns: u64) {
+        *self.lock_flags.entry(lock_addr).or_default() |= flags;
+        let stats = self.contentions.entry((lock_addr, stack)).or_default();
+
+        stats.count += 1;
+        stats.total_wait_ns += wait_ns;
+        stats.max_wait_ns = stats.max_wait_ns.max(wait_ns);
+        stats.min_wait_ns = stats.min_wait_ns.min(wait_ns);
+        self.total_events += 1;
+    }
+
+    /// Type of the lock at `lock_addr` (`spinlock`, `rwsem:W`, ...)
+    pub fn lock_type(&self, lock_addr: u64) -> String {
+        kernel_lock_type(self.lock_flags.get(&lock_addr).copied().unwrap_or(0))
+    }
+
     pub fn as_weighted_stacks(&self) -> HashMap<Stack, u64> {
         let mut stacks = HashMap::new();
         for ((_, stack), stats) in &self.contentions {
@@ -203,6 +479,20 @@ pub struct SyscallStats {
     pub error_count: u64,
     // Power-of-2 buckets from 1ns to ~1s (30 buckets)
     pub latency_histogram: Vec<u64>,
+    /// Stacks of calls slower than the agent's stack threshold
+    #[serde(default)]
+    pub slow_stacks: Vec<SlowStack>,
+    /// Position of each stack in `slow_stacks`, rebuilt when stale
+    #[serde(skip)]
+    slow_index: HashMap<Stack, usize>,
+}
+
+/// Stack of slow calls to one syscall
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowStack {
+    pub stack: Stack,
+    pub count: u64,
+    pub total_duration_ns: u64,
 }
 
 impl SyscallStats {
@@ -216,6 +506,128 @@ impl SyscallStats {
             min_duration_ns: u64::MAX,
             error_count: 0,
             latency_histogram: vec![0; 30],
+            slow_stacks: Vec::new(),
+            slow_index: HashMap::new(),
+        }
+    }
+
+    /// Record a slow call's stack
+    pub fn add_slow_stack(&mut self, stack: Stack, duration_ns: u64) {
+        self.add_slow_calls(stack, duration_ns, 1);
+    }
+
+    /// Record `calls` slow calls of `duration_ns` each from `stack`
+    fn add_slow_calls(&mut self, stack: Stack, duration_ns: u64, calls: u64) {
+        add_slow_stack(
+            &mut self.slow_stacks,
+            &mut self.slow_index,
+            stack,
+            duration_ns,
+            calls,
+        );
+    }
+
+    /// Rewrite every slow stack (symbolization, normalization), merging
+    /// stacks that become identical
+    pub fn map_slow_stacks(&mut self, f: impl FnMut(&Stack) -> Stack) {
+        map_slow_stacks(&mut self.slow_stacks, &mut self.slow_index, f);
+    }
+
+    /// Slow stacks weighted by their total latency, for flamegraphs
+    pub fn slow_stacks_by_latency(&self) -> HashMap<Stack, u64> {
+        slow_stacks_by_latency(&self.slow_stacks)
+    }
+}
+
+/// Add `calls` occurrences of `duration_ns` each from `stack` to `stacks`,
+/// finding it through `index` (rebuilt when stale)
+fn add_slow_stack(
+    stacks: &mut Vec<SlowStack>,
+    index: &mut HashMap<Stack, usize>,
+    stack: Stack,
+    duration_ns: u64,
+    calls: u64,
+) {
+    if index.len() != stacks.len() {
+        *index = stacks
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.stack.clone(), i))
+            .collect();
+    }
+    let slow = match index.get(&stack) {
+        Some(&i) => &mut stacks[i],
+        None => {
+            index.insert(stack.clone(), stacks.len());
+            stacks.push(SlowStack {
+                stack,
+                count: 0,
+                total_duration_ns: 0,
+            });
+            stacks.last_mut().unwrap()
+        }
+    };
+    slow.count += calls;
+    slow.total_duration_ns += duration_ns * calls;
+}
+
+/// Rewrite every stack of `stacks`, merging stacks that become identical
+fn map_slow_stacks(
+    stacks: &mut Vec<SlowStack>,
+    index: &mut HashMap<Stack, usize>,
+    mut f: impl FnMut(&Stack) -> Stack,
+) {
+    let old = std::mem::take(stacks);
+    index.clear();
+    for slow in old {
+        let stack = f(&slow.stack);
+        match index.get(&stack) {
+            Some(&i) => {
+                stacks[i].count += slow.count;
+                stacks[i].total_duration_ns += slow.total_duration_ns;
+            }
+            None => {
+                index.insert(stack.clone(), stacks.len());
+                stacks.push(SlowStack { stack, ..slow });
+            }
+        }
+    }
+}
+
+fn slow_stacks_by_latency(stacks: &[SlowStack]) -> HashMap<Stack, u64> {
+    stacks
+        .iter()
+        .map(|s| (s.stack.clone(), s.total_duration_ns))
+        .collect()
+}
+
+/// I/O statistics for one file or socket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IoTargetStats {
+    /// Path or socket tuple
+    pub target: String,
+    pub count: u64,
+    pub total_duration_ns: u64,
+    pub max_duration_ns: u64,
+    pub error_count: u64,
+    /// Bytes read or written
+    pub bytes: u64,
+    /// Syscall name -> calls on this target
+    pub syscalls: BTreeMap<String, u64>,
+    pub latency_histogram: Vec<u64>,
+}
+
+impl IoTargetStats {
+    pub fn new(target: String) -> Self {
+        Self {
+            target,
+            count: 0,
+            total_duration_ns: 0,
+            max_duration_ns: 0,
+            error_count: 0,
+            bytes: 0,
+            syscalls: BTreeMap::new(),
+            latency_histogram: vec![0; 30],
         }
     }
 }
@@ -227,6 +639,20 @@ pub struct SyscallProfile {
     pub end_time: u64,
     pub syscalls: HashMap<u32, SyscallStats>,
     pub total_events: u64,
+    /// Latency and bytes per file/socket, for syscalls with a known target
+    #[serde(default)]
+    pub io: HashMap<String, IoTargetStats>,
+    /// Normalization rule -> frames it changed (slow-call stacks)
+    #[serde(default)]
+    pub normalization: BTreeMap<String, u64>,
+    /// Largest sampling ratio merged in: counts and durations are estimates
+    /// scaled up from 1 in N recorded calls (1 = every call was recorded)
+    #[serde(default = "exact_sampling")]
+    pub sample_every: u32,
+}
+
+fn exact_sampling() -> u32 {
+    1
 }
 
 impl SyscallProfile {
@@ -236,104 +662,1219 @@ impl SyscallProfile {
             end_time: 0,
             syscalls: HashMap::new(),
             total_events: 0,
+            io: HashMap::new(),
+            normalization: BTreeMap::new(),
+            sample_every: 1,
         }
     }
 
-    pub fn add_syscall(&mut self, id: u32, name: &str, duration_ns: u64, return_value: i64) {
-        let stats = self
-            .syscalls
+    /// Account one traced call: its syscall stats, its file/socket and its
+    /// stack, each scaled by the event's sampling ratio
+    pub fn add_event(&mut self, name: &str, ev: &SyscallEvent) {
+        let calls = self.note_sampling(ev.sample_every);
+        self.record_syscall(ev.syscall_id, name, ev.duration_ns, ev.return_value, calls);
+        if let Some(target) = &ev.target {
+            self.record_io(
+                target,
+                name,
+                ev.duration_ns,
+                ev.return_value,
+                ev.bytes,
+                calls,
+            );
+        }
+        if !ev.stack_trace.is_empty() {
+            let has_symbols = ev.stack_symbols.iter().any(|s| s.is_some());
+            let stack = if has_symbols {
+                Stack::from_ips_with_symbols(&ev.stack_trace, &ev.stack_symbols)
+            } else {
+                Stack::from_ips(&ev.stack_trace)
+            };
+            self.stats_mut(ev.syscall_id, name)
+                .add_slow_calls(stack, ev.duration_ns, calls);
+        }
+    }
+
+    /// Merge an in-kernel histogram summary, scaled by its sampling ratio
+    pub fn add_summary(&mut self, name: &str, summary: &SyscallSummaryEvent) {
+        let calls = self.note_sampling(summary.sample_every);
+        let stats = self.stats_mut(summary.syscall_id, name);
+        stats.count += summary.count * calls;
+        stats.total_duration_ns += summary.total_duration_ns * calls;
+        stats.error_count += summary.error_count * calls;
+        if summary.count > 0 {
+            stats.max_duration_ns = stats.max_duration_ns.max(summary.max_duration_ns);
+            stats.min_duration_ns = stats.min_duration_ns.min(summary.min_duration_ns);
+        }
+        for (bucket, &n) in stats
+            .latency_histogram
+            .iter_mut()
+            .zip(&summary.latency_histogram)
+        {
+            *bucket += n * calls;
+        }
+        self.total_events += summary.count * calls;
+    }
+
+    /// Record a sampling ratio, returning the calls one recorded call stands for
+    fn note_sampling(&mut self, sample_every: u32) -> u64 {
+        let sample_every = sample_every.max(1);
+        self.sample_every = self.sample_every.max(sample_every);
+        sample_every as u64
+    }
+
+    fn stats_mut(&mut self, id: u32, name: &str) -> &mut SyscallStats {
+        self.syscalls
             .entry(id)
-            .or_insert_with(|| SyscallStats::new(id, name.to_string()));
+            .or_insert_with(|| SyscallStats::new(id, name.to_string()))
+    }
 
-        stats.count += 1;
-        stats.total_duration_ns += duration_ns;
+    /// Record the stack of a slow call. Called in addition to `add_syscall`.
+    pub fn add_slow_stack(&mut self, id: u32, name: &str, stack: Stack, duration_ns: u64) {
+        self.stats_mut(id, name).add_slow_stack(stack, duration_ns);
+    }
+
+    /// Number of distinct slow-call stacks across all syscalls
+    pub fn slow_stack_count(&self) -> usize {
+        self.syscalls.values().map(|s| s.slow_stacks.len()).sum()
+    }
+
+    pub fn add_syscall(&mut self, id: u32, name: &str, duration_ns: u64, return_value: i64) {
+        self.record_syscall(id, name, duration_ns, return_value, 1);
+    }
+
+    fn record_syscall(
+        &mut self,
+        id: u32,
+        name: &str,
+        duration_ns: u64,
+        return_value: i64,
+        calls: u64,
+    ) {
+        let stats = self.stats_mut(id, name);
+
+        stats.count += calls;
+        stats.total_duration_ns += duration_ns * calls;
         stats.max_duration_ns = stats.max_duration_ns.max(duration_ns);
         stats.min_duration_ns = stats.min_duration_ns.min(duration_ns);
 
         if return_value < 0 {
-            stats.error_count += 1;
+            stats.error_count += calls;
         }
 
-        // Calculate bucket: log2(duration_ns)
-        // 0..1ns -> 0
-        // 2..3ns -> 1
-        // ...
-        let bucket = if duration_ns == 0 {
-            0
-        } else {
-            (63 - duration_ns.leading_zeros()).min(29) as usize
-        };
-        stats.latency_histogram[bucket] += 1;
+        stats.latency_histogram[latency_bucket(duration_ns)] += calls;
 
-        self.total_events += 1;
+        self.total_events += calls;
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_profile_add_sample() {
-        let mut profile = Profile::new(0, 1000, 10_000_000);
+    /// Account a syscall against the file or socket it touched. Called in
+    /// addition to `add_syscall`.
+    pub fn add_io(
+        &mut self,
+        target: &str,
+        name: &str,
+        duration_ns: u64,
+        return_value: i64,
+        bytes: Option<u64>,
+    ) {
+        self.record_io(target, name, duration_ns, return_value, bytes, 1);
+    }
 
-        let stack = Stack::from_ips(&[0x400000, 0x400100]);
-        profile.add_sample(stack.clone());
-        profile.add_sample(stack.clone());
+    fn record_io(
+        &mut self,
+        target: &str,
+        name: &str,
+        duration_ns: u64,
+        return_value: i64,
+        bytes: Option<u64>,
+        calls: u64,
+    ) {
+        let stats = self
+            .io
+            .entry(target.to_string())
+            .or_insert_with(|| IoTargetStats::new(target.to_string()));
 
-        assert_eq!(profile.total_samples, 2);
-        assert_eq!(*profile.samples.get(&stack).unwrap(), 2);
+        stats.count += calls;
+        stats.total_duration_ns += duration_ns * calls;
+        stats.max_duration_ns = stats.max_duration_ns.max(duration_ns);
+        if return_value < 0 {
+            stats.error_count += calls;
+        }
+        stats.bytes += bytes.unwrap_or(0) * calls;
+        *stats.syscalls.entry(name.to_string()).or_insert(0) += calls;
+        stats.latency_histogram[latency_bucket(duration_ns)] += calls;
     }
+}
 
-    #[test]
-    fn test_sampling_rate_calculation() {
-        let profile = Profile::new(0, 1000, 10_000_000); // 10ms period
-        assert!((profile.sampling_rate_hz() - 100.0).abs() < 0.01);
-    }
+/// Block I/O statistics for one operation on one device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIoStats {
+    pub device: String,
+    pub op: BlockIoOp,
+    pub count: u64,
+    pub total_latency_ns: u64,
+    pub max_latency_ns: u64,
+    pub min_latency_ns: u64,
+    pub error_count: u64,
+    pub bytes: u64,
+    /// Same power-of-2 buckets as `SyscallStats::latency_histogram`
+    pub latency_histogram: Vec<u64>,
+    /// Power-of-2 request sizes: bucket i holds 2^i..2^(i+1)-1 bytes
+    pub size_histogram: Vec<u64>,
+    /// Sum of the queue depths requests saw at issue, for the average
+    pub total_queue_depth: u64,
+    pub max_queue_depth: u32,
+}
 
-    #[test]
-    fn test_sampling_rate_zero_period() {
-        let profile = Profile::new(0, 1000, 0);
-        assert_eq!(profile.sampling_rate_hz(), 0.0);
+impl BlockIoStats {
+    pub fn new(device: String, op: BlockIoOp) -> Self {
+        Self {
+            device,
+            op,
+            count: 0,
+            total_latency_ns: 0,
+            max_latency_ns: 0,
+            min_latency_ns: u64::MAX,
+            error_count: 0,
+            bytes: 0,
+            latency_histogram: vec![0; 30],
+            size_histogram: vec![0; 30],
+            total_queue_depth: 0,
+            max_queue_depth: 0,
+        }
     }
 
-    #[test]
-    fn test_duration_ns() {
-        let profile = Profile::new(1000, 5000, 0);
-        assert_eq!(profile.duration_ns(), 4000);
+    /// Average latency, 0 without requests
+    pub fn avg_latency_ns(&self) -> u64 {
+        self.total_latency_ns.checked_div(self.count).unwrap_or(0)
     }
 
-    #[test]
-    fn test_frame_new_unresolved() {
-        let frame = Frame::new_unresolved(0xdeadbeef);
-        assert_eq!(frame.ip, 0xdeadbeef);
-        assert!(!frame.is_symbolized());
+    /// Average queue depth at issue, 0 without requests
+    pub fn avg_queue_depth(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_queue_depth as f64 / self.count as f64
+        }
     }
+}
 
-    #[test]
-    fn test_frame_is_symbolized() {
-        let mut frame = Frame::new_unresolved(0x1000);
-        assert!(!frame.is_symbolized());
-        frame.function = Some("main".to_string());
-        assert!(frame.is_symbolized());
-    }
+/// Block I/O issued by one process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIoProcessStats {
+    pub pid: i32,
+    pub comm: String,
+    pub count: u64,
+    pub bytes: u64,
+    pub total_latency_ns: u64,
+    /// Operation name -> requests
+    pub ops: BTreeMap<String, u64>,
+}
 
-    #[test]
-    fn test_stack_from_ips() {
-        let stack = Stack::from_ips(&[0x1000, 0x2000, 0x3000]);
-        assert_eq!(stack.frames.len(), 3);
-        assert_eq!(stack.frames[0].ip, 0x1000);
-        assert_eq!(stack.frames[2].ip, 0x3000);
-        assert!(stack.frames.iter().all(|f| !f.is_symbolized()));
+/// Profile of block device requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockIoProfile {
+    pub start_time: u64,
+    pub end_time: u64,
+    /// Device name -> operation -> stats
+    pub devices: BTreeMap<String, BTreeMap<BlockIoOp, BlockIoStats>>,
+    /// Requests per issuing process
+    pub processes: HashMap<i32, BlockIoProcessStats>,
+    pub total_events: u64,
+}
+
+impl BlockIoProfile {
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: 0,
+            devices: BTreeMap::new(),
+            processes: HashMap::new(),
+            total_events: 0,
+        }
     }
 
-    #[test]
-    fn test_profile_multiple_unique_stacks() {
-        let mut profile = Profile::new(0, 1000, 10_000_000);
+    /// Account one completed request against its device, operation and process
+    pub fn add_event(&mut self, ev: &BlockIoEvent) {
+        let device = ev.device_name();
+        let stats = self
+            .devices
+            .entry(device.clone())
+            .or_default()
+            .entry(ev.op)
+            .or_insert_with(|| BlockIoStats::new(device, ev.op));
 
-        profile.add_sample(Stack::from_ips(&[0x1000]));
-        profile.add_sample(Stack::from_ips(&[0x2000]));
-        profile.add_sample(Stack::from_ips(&[0x1000])); // duplicate
+        stats.count += 1;
+        stats.total_latency_ns += ev.latency_ns;
+        stats.max_latency_ns = stats.max_latency_ns.max(ev.latency_ns);
+        stats.min_latency_ns = stats.min_latency_ns.min(ev.latency_ns);
+        if ev.error != 0 {
+            stats.error_count += 1;
+        }
+        stats.bytes += ev.bytes as u64;
+        stats.latency_histogram[latency_bucket(ev.latency_ns)] += 1;
+        stats.size_histogram[latency_bucket(ev.bytes as u64)] += 1;
+        stats.total_queue_depth += ev.queue_depth as u64;
+        stats.max_queue_depth = stats.max_queue_depth.max(ev.queue_depth);
+
+        let process = self
+            .processes
+            .entry(ev.pid)
+            .or_insert_with(|| BlockIoProcessStats {
+                pid: ev.pid,
+                comm: ev.comm.clone(),
+                count: 0,
+                bytes: 0,
+                total_latency_ns: 0,
+                ops: BTreeMap::new(),
+            });
+        process.count += 1;
+        process.bytes += ev.bytes as u64;
+        process.total_latency_ns += ev.latency_ns;
+        *process.ops.entry(ev.op.as_str().to_string()).or_insert(0) += 1;
+
+        self.total_events += 1;
+    }
+
+    /// Stats of every device and operation, by device then operation
+    pub fn stats(&self) -> impl Iterator<Item = &BlockIoStats> {
+        self.devices.values().flat_map(|ops| ops.values())
+    }
+}
+
+/// Run-queue latency of a process or cgroup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunQueueStats {
+    pub count: u64,
+    pub total_delay_ns: u64,
+    pub max_delay_ns: u64,
+    pub min_delay_ns: u64,
+    /// Waits that began with preemption rather than a wakeup
+    pub preempted_count: u64,
+    /// Same power-of-2 buckets as `SyscallStats::latency_histogram`
+    pub latency_histogram: Vec<u64>,
+}
+
+impl Default for RunQueueStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total_delay_ns: 0,
+            max_delay_ns: 0,
+            min_delay_ns: u64::MAX,
+            preempted_count: 0,
+            latency_histogram: vec![0; 30],
+        }
+    }
+}
+
+impl RunQueueStats {
+    fn add_wait(&mut self, delay_ns: u64, preempted: bool) {
+        self.count += 1;
+        self.total_delay_ns += delay_ns;
+        self.max_delay_ns = self.max_delay_ns.max(delay_ns);
+        self.min_delay_ns = self.min_delay_ns.min(delay_ns);
+        if preempted {
+            self.preempted_count += 1;
+        }
+        self.latency_histogram[latency_bucket(delay_ns)] += 1;
+    }
+
+    /// Average wait, 0 without waits
+    pub fn avg_delay_ns(&self) -> u64 {
+        self.total_delay_ns.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+/// Long waits that ended with a given task giving up the CPU
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreemptorStats {
+    pub count: u64,
+    pub total_delay_ns: u64,
+    pub max_delay_ns: u64,
+}
+
+/// Run-queue latency of one process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedProcessStats {
+    pub pid: i32,
+    pub comm: String,
+    #[serde(default)]
+    pub cgroup: Option<String>,
+    pub runq: RunQueueStats,
+    /// Task name -> long waits it was on the CPU for just before
+    pub preemptors: BTreeMap<String, PreemptorStats>,
+    /// Stacks that made the process's threads runnable, weighted by wait
+    #[serde(default)]
+    pub wakeup_stacks: Vec<SlowStack>,
+    /// Position of each stack in `wakeup_stacks`, rebuilt when stale
+    #[serde(skip)]
+    stack_index: HashMap<Stack, usize>,
+}
+
+impl SchedProcessStats {
+    pub fn new(pid: i32, comm: String) -> Self {
+        Self {
+            pid,
+            comm,
+            cgroup: None,
+            runq: RunQueueStats::default(),
+            preemptors: BTreeMap::new(),
+            wakeup_stacks: Vec::new(),
+            stack_index: HashMap::new(),
+        }
+    }
+
+    /// Rewrite every wakeup stack (symbolization, normalization), merging
+    /// stacks that become identical
+    pub fn map_wakeup_stacks(&mut self, f: impl FnMut(&Stack) -> Stack) {
+        map_slow_stacks(&mut self.wakeup_stacks, &mut self.stack_index, f);
+    }
+
+    /// Wakeup stacks weighted by the waits they started, for flamegraphs
+    pub fn wakeup_stacks_by_latency(&self) -> HashMap<Stack, u64> {
+        slow_stacks_by_latency(&self.wakeup_stacks)
+    }
+}
+
+/// Profile of scheduler run-queue latency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedProfile {
+    pub start_time: u64,
+    pub end_time: u64,
+    /// Waits per process
+    pub processes: HashMap<i32, SchedProcessStats>,
+    /// cgroup path -> waits of its threads
+    pub cgroups: BTreeMap<String, RunQueueStats>,
+    pub total_events: u64,
+    /// Normalization rule -> frames it changed (wakeup stacks)
+    #[serde(default)]
+    pub normalization: BTreeMap<String, u64>,
+}
+
+impl SchedProfile {
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: 0,
+            processes: HashMap::new(),
+            cgroups: BTreeMap::new(),
+            total_events: 0,
+            normalization: BTreeMap::new(),
+        }
+    }
+
+    /// Account one run-queue wait against its process and cgroup
+    pub fn add_event(&mut self, ev: &SchedEvent) {
+        let process = self
+            .processes
+            .entry(ev.pid)
+            .or_insert_with(|| SchedProcessStats::new(ev.pid, ev.comm.clone()));
+        process.runq.add_wait(ev.delay_ns, ev.preempted);
+        if process.cgroup.is_none() {
+            process.cgroup = ev.cgroup.clone();
+        }
+        if let Some(preemptor) = &ev.preemptor_comm {
+            let stats = process.preemptors.entry(preemptor.clone()).or_default();
+            stats.count += 1;
+            stats.total_delay_ns += ev.delay_ns;
+            stats.max_delay_ns = stats.max_delay_ns.max(ev.delay_ns);
+        }
+        if !ev.stack_trace.is_empty() {
+            let has_symbols = ev.stack_symbols.iter().any(|s| s.is_some());
+            let stack = if has_symbols {
+                Stack::from_ips_with_symbols(&ev.stack_trace, &ev.stack_symbols)
+            } else {
+                Stack::from_ips(&ev.stack_trace)
+            };
+            add_slow_stack(
+                &mut process.wakeup_stacks,
+                &mut process.stack_index,
+                stack,
+                ev.delay_ns,
+                1,
+            );
+        }
+
+        if let Some(cgroup) = &ev.cgroup {
+            self.cgroups
+                .entry(cgroup.clone())
+                .or_default()
+                .add_wait(ev.delay_ns, ev.preempted);
+        }
+
+        self.total_events += 1;
+    }
+
+    /// Number of distinct wakeup stacks across all processes
+    pub fn wakeup_stack_count(&self) -> usize {
+        self.processes.values().map(|p| p.wakeup_stacks.len()).sum()
+    }
+
+    /// Wakeup stacks of all processes weighted by the waits they started,
+    /// for flamegraphs
+    pub fn wakeup_stacks_by_latency(&self) -> HashMap<Stack, u64> {
+        let mut stacks: HashMap<Stack, u64> = HashMap::new();
+        for p in self.processes.values() {
+            for (stack, ns) in p.wakeup_stacks_by_latency() {
+                *stacks.entry(stack).or_default() += ns;
+            }
+        }
+        stacks
+    }
+
+    /// Waits of all processes combined
+    pub fn total(&self) -> RunQueueStats {
+        let mut total = RunQueueStats::default();
+        for p in self.processes.values() {
+            total.count += p.runq.count;
+            total.total_delay_ns += p.runq.total_delay_ns;
+            total.max_delay_ns = total.max_delay_ns.max(p.runq.max_delay_ns);
+            total.min_delay_ns = total.min_delay_ns.min(p.runq.min_delay_ns);
+            total.preempted_count += p.runq.preempted_count;
+            for (bucket, n) in total
+                .latency_histogram
+                .iter_mut()
+                .zip(&p.runq.latency_histogram)
+            {
+                *bucket += n;
+            }
+        }
+        total
+    }
+}
+
+/// TCP traffic between one process and one remote endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcpEndpointStats {
+    /// `addr:port`, or `addr -> :port` for accepted connections
+    pub endpoint: String,
+    pub connects: u64,
+    pub connect_failures: u64,
+    pub total_connect_ns: u64,
+    pub max_connect_ns: u64,
+    /// Same power-of-2 buckets as `SyscallStats::latency_histogram`
+    pub connect_histogram: Vec<u64>,
+    pub retransmits: u64,
+    pub resets_sent: u64,
+    pub resets_received: u64,
+    /// Connections closed; bytes and RTT come from these
+    pub closed: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Sum of the last smoothed RTT of closed connections that had one
+    pub total_srtt_us: u64,
+    pub srtt_samples: u64,
+    pub max_srtt_us: u32,
+}
+
+impl TcpEndpointStats {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            connects: 0,
+            connect_failures: 0,
+            total_connect_ns: 0,
+            max_connect_ns: 0,
+            connect_histogram: vec![0; 30],
+            retransmits: 0,
+            resets_sent: 0,
+            resets_received: 0,
+            closed: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            total_srtt_us: 0,
+            srtt_samples: 0,
+            max_srtt_us: 0,
+        }
+    }
+
+    fn add_event(&mut self, ev: &TcpEvent) {
+        match ev.kind {
+            TcpEventKind::Connect | TcpEventKind::ConnectFailed => {
+                if ev.kind == TcpEventKind::Connect {
+                    self.connects += 1;
+                } else {
+                    self.connect_failures += 1;
+                }
+                self.total_connect_ns += ev.duration_ns;
+                self.max_connect_ns = self.max_connect_ns.max(ev.duration_ns);
+                self.connect_histogram[latency_bucket(ev.duration_ns)] += 1;
+            }
+            TcpEventKind::Retransmit => self.retransmits += 1,
+            TcpEventKind::ResetSent => self.resets_sent += 1,
+            TcpEventKind::ResetReceived => self.resets_received += 1,
+            TcpEventKind::Close => {
+                self.closed += 1;
+                self.bytes_sent += ev.bytes_sent;
+                self.bytes_received += ev.bytes_received;
+                if ev.srtt_us > 0 {
+                    self.total_srtt_us += ev.srtt_us as u64;
+                    self.srtt_samples += 1;
+                    self.max_srtt_us = self.max_srtt_us.max(ev.srtt_us);
+                }
+            }
+        }
+    }
+
+    /// Average handshake time of connection attempts, 0 without any
+    pub fn avg_connect_ns(&self) -> u64 {
+        self.total_connect_ns
+            .checked_div(self.connects + self.connect_failures)
+            .unwrap_or(0)
+    }
+
+    /// Average smoothed RTT of closed connections, 0 if none had one
+    pub fn avg_srtt_us(&self) -> u64 {
+        self.total_srtt_us
+            .checked_div(self.srtt_samples)
+            .unwrap_or(0)
+    }
+}
+
+/// TCP traffic of one process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcpProcessStats {
+    pub pid: i32,
+    pub comm: String,
+    /// Endpoint -> stats
+    pub endpoints: BTreeMap<String, TcpEndpointStats>,
+}
+
+impl TcpProcessStats {
+    /// Bytes sent and received over closed connections, all endpoints
+    pub fn total_bytes(&self) -> u64 {
+        self.endpoints
+            .values()
+            .map(|e| e.bytes_sent + e.bytes_received)
+            .sum()
+    }
+}
+
+/// Profile of TCP connections per process and remote endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcpProfile {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub processes: HashMap<i32, TcpProcessStats>,
+    pub total_events: u64,
+}
+
+impl TcpProfile {
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: 0,
+            processes: HashMap::new(),
+            total_events: 0,
+        }
+    }
+
+    /// Account one event against its process and endpoint
+    pub fn add_event(&mut self, ev: &TcpEvent) {
+        let process = self
+            .processes
+            .entry(ev.pid)
+            .or_insert_with(|| TcpProcessStats {
+                pid: ev.pid,
+                comm: ev.comm.clone(),
+                endpoints: BTreeMap::new(),
+            });
+        let endpoint = ev.endpoint();
+        process
+            .endpoints
+            .entry(endpoint.clone())
+            .or_insert_with(|| TcpEndpointStats::new(endpoint))
+            .add_event(ev);
+        self.total_events += 1;
+    }
+
+    /// Every (process, endpoint) pair
+    pub fn endpoints(&self) -> impl Iterator<Item = (&TcpProcessStats, &TcpEndpointStats)> {
+        self.processes
+            .values()
+            .flat_map(|p| p.endpoints.values().map(move |e| (p, e)))
+    }
+}
+
+/// Memory reclaim stalls of a process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReclaimStats {
+    pub count: u64,
+    pub total_stall_ns: u64,
+    pub max_stall_ns: u64,
+    pub min_stall_ns: u64,
+    /// Stalls reclaiming for a memory cgroup limit
+    pub memcg_count: u64,
+    /// Pages freed
+    pub nr_reclaimed: u64,
+    /// Same power-of-2 buckets as `SyscallStats::latency_histogram`
+    pub latency_histogram: Vec<u64>,
+}
+
+impl Default for ReclaimStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total_stall_ns: 0,
+            max_stall_ns: 0,
+            min_stall_ns: u64::MAX,
+            memcg_count: 0,
+            nr_reclaimed: 0,
+            latency_histogram: vec![0; 30],
+        }
+    }
+}
+
+impl ReclaimStats {
+    fn add_stall(&mut self, ev: &ReclaimEvent) {
+        self.count += 1;
+        self.total_stall_ns += ev.stall_ns;
+        self.max_stall_ns = self.max_stall_ns.max(ev.stall_ns);
+        self.min_stall_ns = self.min_stall_ns.min(ev.stall_ns);
+        if ev.memcg {
+            self.memcg_count += 1;
+        }
+        self.nr_reclaimed += ev.nr_reclaimed;
+        self.latency_histogram[latency_bucket(ev.stall_ns)] += 1;
+    }
+
+    /// Average stall, 0 without stalls
+    pub fn avg_stall_ns(&self) -> u64 {
+        self.total_stall_ns.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+/// Page faults and reclaim stalls of one process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryProcessStats {
+    pub pid: i32,
+    pub comm: String,
+    /// Estimated page faults (samples scaled by their sampling ratio)
+    pub faults: u64,
+    /// Of which raised in kernel mode
+    pub kernel_faults: u64,
+    pub reclaim: ReclaimStats,
+}
+
+/// Profile of page faults and memory reclaim stalls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryProfile {
+    pub start_time: u64,
+    pub end_time: u64,
+    /// Faulting stacks weighted by the faults each sample stands for
+    pub faults: Profile,
+    pub processes: HashMap<i32, MemoryProcessStats>,
+    pub total_events: u64,
+}
+
+impl MemoryProfile {
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: 0,
+            faults: Profile::new(start_time, 0, 0),
+            processes: HashMap::new(),
+            total_events: 0,
+        }
+    }
+
+    fn process(&mut self, pid: i32, comm: &str) -> &mut MemoryProcessStats {
+        self.processes
+            .entry(pid)
+            .or_insert_with(|| MemoryProcessStats {
+                pid,
+                comm: comm.to_string(),
+                faults: 0,
+                kernel_faults: 0,
+                reclaim: ReclaimStats::default(),
+            })
+    }
+
+    /// Account one sampled page fault against its stack and process
+    pub fn add_fault(&mut self, ev: &PageFaultEvent) {
+        let weight = ev.weight.max(1) as u64;
+        if !ev.stack_trace.is_empty() {
+            let has_symbols = ev.stack_symbols.iter().any(|s| s.is_some());
+            let stack = if has_symbols {
+                Stack::from_ips_with_symbols(&ev.stack_trace, &ev.stack_symbols)
+            } else {
+                Stack::from_ips(&ev.stack_trace)
+            };
+            self.faults.add_weighted_sample(stack, weight);
+        }
+
+        let process = self.process(ev.pid, &ev.comm);
+        process.faults += weight;
+        if ev.kernel {
+            process.kernel_faults += weight;
+        }
+        self.total_events += 1;
+    }
+
+    /// Account one reclaim stall against its process
+    pub fn add_reclaim(&mut self, ev: &ReclaimEvent) {
+        self.process(ev.pid, &ev.comm).reclaim.add_stall(ev);
+        self.total_events += 1;
+    }
+
+    /// Estimated page faults of all processes
+    pub fn total_faults(&self) -> u64 {
+        self.processes.values().map(|p| p.faults).sum()
+    }
+
+    /// Reclaim stalls of all processes combined
+    pub fn total_reclaim(&self) -> ReclaimStats {
+        let mut total = ReclaimStats::default();
+        for p in self.processes.values() {
+            let r = &p.reclaim;
+            total.count += r.count;
+            total.total_stall_ns += r.total_stall_ns;
+            total.max_stall_ns = total.max_stall_ns.max(r.max_stall_ns);
+            total.min_stall_ns = total.min_stall_ns.min(r.min_stall_ns);
+            total.memcg_count += r.memcg_count;
+            total.nr_reclaimed += r.nr_reclaimed;
+            for (bucket, n) in total.latency_histogram.iter_mut().zip(&r.latency_histogram) {
+                *bucket += n;
+            }
+        }
+        total
+    }
+}
+
+/// Calls or hits of one user-defined probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeStats {
+    /// Probe spec (`uprobe:/usr/bin/app:handle_request`)
+    pub probe: String,
+    /// Calls that returned, or tracepoint hits
+    pub count: u64,
+    /// Latency of calls; zero for tracepoints, which have no return
+    pub total_duration_ns: u64,
+    pub max_duration_ns: u64,
+    pub min_duration_ns: u64,
+    /// Same power-of-2 buckets as `SyscallStats::latency_histogram`
+    pub latency_histogram: Vec<u64>,
+    /// Stacks at function entry or at the tracepoint, with their calls
+    #[serde(default)]
+    pub stacks: Vec<SlowStack>,
+    /// Position of each stack in `stacks`, rebuilt when stale
+    #[serde(skip)]
+    stack_index: HashMap<Stack, usize>,
+}
+
+impl ProbeStats {
+    pub fn new(probe: String) -> Self {
+        Self {
+            probe,
+            count: 0,
+            total_duration_ns: 0,
+            max_duration_ns: 0,
+            min_duration_ns: u64::MAX,
+            latency_histogram: vec![0; 30],
+            stacks: Vec::new(),
+            stack_index: HashMap::new(),
+        }
+    }
+
+    /// Average call latency, 0 without timed calls
+    pub fn avg_duration_ns(&self) -> u64 {
+        self.total_duration_ns.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// Rewrite every stack (symbolization, normalization), merging stacks
+    /// that become identical
+    pub fn map_stacks(&mut self, f: impl FnMut(&Stack) -> Stack) {
+        map_slow_stacks(&mut self.stacks, &mut self.stack_index, f);
+    }
+
+    /// Stacks weighted by the calls or hits they made, for flamegraphs
+    pub fn stacks_by_count(&self) -> HashMap<Stack, u64> {
+        self.stacks
+            .iter()
+            .map(|s| (s.stack.clone(), s.count))
+            .collect()
+    }
+}
+
+/// Range of values one USDT probe argument took
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct UsdtArgStats {
+    pub min: i64,
+    pub max: i64,
+}
+
+/// Hits of one USDT probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsdtProbeStats {
+    /// `provider:name`
+    pub probe: String,
+    pub count: u64,
+    /// Per argument, in declaration order
+    pub args: Vec<UsdtArgStats>,
+}
+
+/// CPU samples of one stack taken inside a span
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpanStack {
+    pub stack: Stack,
+    pub samples: u64,
+}
+
+/// Spans between a pair of USDT probes, and the CPU they used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsdtSpanStats {
+    /// Pair name (`postgresql:query__start..query__done`)
+    pub span: String,
+    pub count: u64,
+    pub total_duration_ns: u64,
+    pub max_duration_ns: u64,
+    pub min_duration_ns: u64,
+    /// Same power-of-2 buckets as `SyscallStats::latency_histogram`
+    pub latency_histogram: Vec<u64>,
+    /// CPU samples taken on a span's thread while it was open
+    pub cpu_samples: u64,
+    #[serde(default)]
+    pub cpu_stacks: Vec<SpanStack>,
+    /// Position of each stack in `cpu_stacks`
+    #[serde(skip)]
+    cpu_index: HashMap<Stack, usize>,
+}
+
+impl UsdtSpanStats {
+    pub fn new(span: String) -> Self {
+        Self {
+            span,
+            count: 0,
+            total_duration_ns: 0,
+            max_duration_ns: 0,
+            min_duration_ns: u64::MAX,
+            latency_histogram: vec![0; 30],
+            cpu_samples: 0,
+            cpu_stacks: Vec::new(),
+            cpu_index: HashMap::new(),
+        }
+    }
+
+    pub fn avg_duration_ns(&self) -> u64 {
+        self.total_duration_ns.checked_div(self.count).unwrap_or(0)
+    }
+
+    fn add_cpu_sample(&mut self, stack: &Stack) {
+        self.cpu_samples += 1;
+        match self.cpu_index.get(stack) {
+            Some(&i) => self.cpu_stacks[i].samples += 1,
+            None => {
+                self.cpu_index.insert(stack.clone(), self.cpu_stacks.len());
+                self.cpu_stacks.push(SpanStack {
+                    stack: stack.clone(),
+                    samples: 1,
+                });
+            }
+        }
+    }
+
+    /// CPU stacks weighted by their samples, for flamegraphs
+    pub fn cpu_stacks_by_samples(&self) -> HashMap<Stack, u64> {
+        self.cpu_stacks
+            .iter()
+            .map(|s| (s.stack.clone(), s.samples))
+            .collect()
+    }
+}
+
+/// A closed span on one thread, kept to match CPU samples against
+#[derive(Debug, Clone)]
+struct SpanInterval {
+    start: Timestamp,
+    end: Timestamp,
+    span: String,
+}
+
+/// Profile of user-defined probes: call counts, latency and stacks per probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeProfile {
+    pub start_time: u64,
+    pub end_time: u64,
+    /// Probe spec -> its calls
+    pub probes: BTreeMap<String, ProbeStats>,
+    /// USDT probe `provider:name` -> its hits
+    #[serde(default)]
+    pub usdt: BTreeMap<String, UsdtProbeStats>,
+    /// Span name -> the spans between its paired USDT probes
+    #[serde(default)]
+    pub spans: BTreeMap<String, UsdtSpanStats>,
+    pub total_events: u64,
+    /// Normalization rule -> frames it changed (probe stacks)
+    #[serde(default)]
+    pub normalization: BTreeMap<String, u64>,
+    /// Closed spans per thread, for `attribute_cpu_samples`
+    #[serde(skip)]
+    span_intervals: HashMap<(Pid, Tid), Vec<SpanInterval>>,
+}
+
+impl ProbeProfile {
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: 0,
+            probes: BTreeMap::new(),
+            usdt: BTreeMap::new(),
+            spans: BTreeMap::new(),
+            total_events: 0,
+            normalization: BTreeMap::new(),
+            span_intervals: HashMap::new(),
+        }
+    }
+
+    /// Account one call or hit against its probe
+    pub fn add_event(&mut self, ev: &ProbeEvent) {
+        let stats = self
+            .probes
+            .entry(ev.probe.clone())
+            .or_insert_with(|| ProbeStats::new(ev.probe.clone()));
+        stats.count += 1;
+        let duration_ns = ev.duration_ns.unwrap_or(0);
+        if let Some(d) = ev.duration_ns {
+            stats.total_duration_ns += d;
+            stats.max_duration_ns = stats.max_duration_ns.max(d);
+            stats.min_duration_ns = stats.min_duration_ns.min(d);
+            stats.latency_histogram[latency_bucket(d)] += 1;
+        }
+        if !ev.stack_trace.is_empty() {
+            let has_symbols = ev.stack_symbols.iter().any(|s| s.is_some());
+            let stack = if has_symbols {
+                Stack::from_ips_with_symbols(&ev.stack_trace, &ev.stack_symbols)
+            } else {
+                Stack::from_ips(&ev.stack_trace)
+            };
+            add_slow_stack(
+                &mut stats.stacks,
+                &mut stats.stack_index,
+                stack,
+                duration_ns,
+                1,
+            );
+        }
+        self.total_events += 1;
+    }
+
+    /// Account one USDT hit against its probe, and the span it ends
+    pub fn add_usdt(&mut self, ev: &UsdtEvent) {
+        let stats = self
+            .usdt
+            .entry(ev.probe.clone())
+            .or_insert_with(|| UsdtProbeStats {
+                probe: ev.probe.clone(),
+                count: 0,
+                args: Vec::new(),
+            });
+        stats.count += 1;
+        for (i, &value) in ev.args.iter().enumerate() {
+            match stats.args.get_mut(i) {
+                Some(arg) => {
+                    arg.min = arg.min.min(value);
+                    arg.max = arg.max.max(value);
+                }
+                None => stats.args.push(UsdtArgStats {
+                    min: value,
+                    max: value,
+                }),
+            }
+        }
+
+        if let Some(span) = &ev.span {
+            let d = ev.timestamp.saturating_sub(span.start);
+            let stats = self
+                .spans
+                .entry(span.name.clone())
+                .or_insert_with(|| UsdtSpanStats::new(span.name.clone()));
+            stats.count += 1;
+            stats.total_duration_ns += d;
+            stats.max_duration_ns = stats.max_duration_ns.max(d);
+            stats.min_duration_ns = stats.min_duration_ns.min(d);
+            stats.latency_histogram[latency_bucket(d)] += 1;
+            self.span_intervals
+                .entry((ev.pid, ev.tid))
+                .or_default()
+                .push(SpanInterval {
+                    start: span.start,
+                    end: ev.timestamp,
+                    span: span.name.clone(),
+                });
+        }
+        self.total_events += 1;
+    }
+
+    /// Credit CPU samples (pid, tid, timestamp, stack) to the spans that
+    /// were open on their thread when they were taken. A sample inside
+    /// nested spans counts once for each distinct span name. Call once all
+    /// span ends have been added.
+    pub fn attribute_cpu_samples<'a>(
+        &mut self,
+        samples: impl IntoIterator<Item = (Pid, Tid, Timestamp, &'a Stack)>,
+    ) {
+        if self.span_intervals.is_empty() {
+            return;
+        }
+        // By start, with the longest span per thread bounding how far back
+        // an open span can have started
+        let mut longest: HashMap<(Pid, Tid), u64> = HashMap::new();
+        for (&key, intervals) in self.span_intervals.iter_mut() {
+            intervals.sort_by_key(|i| i.start);
+            let max = intervals.iter().map(|i| i.end - i.start).max();
+            longest.insert(key, max.unwrap_or(0));
+        }
+
+        for (pid, tid, timestamp, stack) in samples {
+            let Some(intervals) = self.span_intervals.get(&(pid, tid)) else {
+                continue;
+            };
+            let earliest = timestamp.saturating_sub(longest[&(pid, tid)]);
+            let started = intervals.partition_point(|i| i.start <= timestamp);
+            let mut credited: Vec<&str> = Vec::new();
+            for interval in intervals[..started].iter().rev() {
+                if interval.start < earliest {
+                    break;
+                }
+                if timestamp <= interval.end && !credited.contains(&interval.span.as_str()) {
+                    credited.push(&interval.span);
+                }
+            }
+            for span in credited {
+                if let Some(stats) = self.spans.get_mut(span) {
+                    stats.add_cpu_sample(stack);
+                }
+            }
+        }
+    }
+
+    /// Number of distinct stacks across all probes
+    pub fn stack_count(&self) -> usize {
+        self.probes.values().map(|p| p.stacks.len()).sum()
+    }
+}
+
+/// Power-of-2 latency bucket: log2(duration_ns)
+/// 0..1ns -> 0
+/// 2..3ns -> 1
+/// ...
+fn latency_bucket(duration_ns: u64) -> usize {
+    if duration_ns == 0 {
+        0
+    } else {
+        (63 - duration_ns.leading_zeros()).min(29) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_add_sample() {
+        let mut profile = Profile::new(0, 1000, 10_000_000);
+
+        let stack = Stack::from_ips(&[0x400000, 0x400100]);
+        profile.add_sample(stack.clone());
+        profile.add_sample(stack.clone());
+
+        assert_eq!(profile.total_samples, 2);
+        assert_eq!(*profile.samples.get(&stack).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_sampling_rate_calculation() {
+        let profile = Profile::new(0, 1000, 10_000_000); // 10ms period
+        assert!((profile.sampling_rate_hz() - 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_sampling_rate_zero_period() {
+        let profile = Profile::new(0, 1000, 0);
+        assert_eq!(profile.sampling_rate_hz(), 0.0);
+    }
+
+    #[test]
+    fn test_duration_ns() {
+        let profile = Profile::new(1000, 5000, 0);
+        assert_eq!(profile.duration_ns(), 4000);
+    }
+
+    #[test]
+    fn test_frame_new_unresolved() {
+        let frame = Frame::new_unresolved(0xdeadbeef);
+        assert_eq!(frame.ip, 0xdeadbeef);
+        assert!(!frame.is_symbolized());
+    }
+
+    #[test]
+    fn test_frame_is_symbolized() {
+        let mut frame = Frame::new_unresolved(0x1000);
+        assert!(!frame.is_symbolized());
+        frame.function = Some("main".to_string());
+        assert!(frame.is_symbolized());
+    }
+
+    #[test]
+    fn test_symbol_inline_chain_roundtrip() {
+        let chain = [
+            Frame {
+                ip: 0x1000,
+                function: Some("inner".to_string()),
+                file: Some("src/lib.rs".to_string()),
+                line: Some(12),
+                module: None,
+                inline: true,
+            },
+            Frame {
+                ip: 0x1000,
+                function: Some("outer".to_string()),
+                file: Some("src/main.rs".to_string()),
+                line: Some(40),
+                module: None,
+                inline: false,
+            },
+        ];
+        let symbol = Frame::to_symbol(&chain, |f| f.function.clone()).unwrap();
+        assert_eq!(Frame::from_symbol(0x1000, &symbol), chain);
+
+        let stack = Stack::from_ips_with_symbols(
+            &[0x1000, 0x2000, 0x3000],
+            &[
+                Some(symbol),
+                Some(vec![SymbolFrame::named("main [app]")]),
+                None,
+            ],
+        );
+        let names: Vec<String> = stack.frames.iter().map(|f| f.folded_name(true)).collect();
+        assert_eq!(
+            names,
+            [
+                "inner (src/lib.rs:12)_[i]",
+                "outer (src/main.rs:40)",
+                "main [app]",
+                "0x3000"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stack_from_ips() {
+        let stack = Stack::from_ips(&[0x1000, 0x2000, 0x3000]);
+        assert_eq!(stack.frames.len(), 3);
+        assert_eq!(stack.frames[0].ip, 0x1000);
+        assert_eq!(stack.frames[2].ip, 0x3000);
+        assert!(stack.frames.iter().all(|f| !f.is_symbolized()));
+    }
+
+    #[test]
+    fn test_profile_multiple_unique_stacks() {
+        let mut profile = Profile::new(0, 1000, 10_000_000);
+
+        profile.add_sample(Stack::from_ips(&[0x1000]));
+        profile.add_sample(Stack::from_ips(&[0x2000]));
+        profile.add_sample(Stack::from_ips(&[0x1000])); // duplicate
 
         assert_eq!(profile.total_samples, 3);
         assert_eq!(profile.samples.len(), 2);
@@ -346,4 +1887,464 @@ mod tests {
             1
         );
     }
+
+    #[test]
+    fn test_lock_profile_group_by_lock() {
+        let mut profile = LockProfile::new(0);
+        let a = Stack::from_ips(&[0x400000]);
+        let b = Stack::from_ips(&[0x400100]);
+        // Two objects of one allocation site, and an unnamed lock
+        profile
+            .lock_names
+            .insert(0x1000, "heap:Pool::new".to_string());
+        profile
+            .lock_names
+            .insert(0x2000, "heap:Pool::new".to_string());
+        profile.add_contention(0x1000, a.clone(), 100);
+        profile.add_contention(0x2000, b.clone(), 300);
+        profile.add_hold(0x2000, a.clone(), 40);
+        profile.add_contention(0x3000, a, 50);
+
+        assert_eq!(profile.lock_name(0x1000), "heap:Pool::new");
+        assert_eq!(profile.lock_name(0x3000), "0x3000");
+
+        let groups = profile.group_by_lock();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "heap:Pool::new");
+        assert_eq!(groups[0].lock_addrs, vec![0x1000, 0x2000]);
+        assert_eq!(groups[0].contention.count, 2);
+        assert_eq!(groups[0].contention.total_wait_ns, 400);
+        assert_eq!(groups[0].contention.max_wait_ns, 300);
+        assert_eq!(groups[0].hold.total_hold_ns, 40);
+        assert_eq!(groups[1].name, "0x3000");
+        assert_eq!(groups[1].hold.count, 0);
+    }
+
+    #[test]
+    fn test_kernel_lock_profile_types() {
+        use crate::types::events::{LCB_F_READ, LCB_F_WRITE};
+
+        let mut profile = KernelLockProfile::new(0);
+        let stack = Stack::from_ips(&[0xffffffff81000000]);
+        profile.add_contention(0xffff888000001000, LCB_F_READ, stack.clone(), 100);
+        profile.add_contention(0xffff888000001000, LCB_F_READ, stack.clone(), 300);
+        assert_eq!(profile.lock_type(0xffff888000001000), "rwsem:R");
+
+        profile.add_contention(0xffff888000001000, LCB_F_WRITE, stack.clone(), 50);
+        assert_eq!(profile.lock_type(0xffff888000001000), "rwsem");
+        assert_eq!(profile.total_events, 3);
+        let stats = &profile.contentions[&(0xffff888000001000, stack)];
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min_wait_ns, 50);
+        assert_eq!(stats.max_wait_ns, 300);
+    }
+
+    #[test]
+    fn test_syscall_profile_io_targets() {
+        let mut profile = SyscallProfile::new(0);
+        profile.add_io("/data/wal", "write", 1_000, 4096, Some(4096));
+        profile.add_io("/data/wal", "fsync", 50_000, 0, None);
+        profile.add_io("/data/wal", "write", 2_000, -5, None);
+
+        let wal = &profile.io["/data/wal"];
+        assert_eq!(wal.count, 3);
+        assert_eq!(wal.bytes, 4096);
+        assert_eq!(wal.max_duration_ns, 50_000);
+        assert_eq!(wal.error_count, 1);
+        assert_eq!(wal.syscalls["write"], 2);
+        assert_eq!(wal.latency_histogram.iter().sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn test_slow_stacks_merge_after_rewrite() {
+        let mut profile = SyscallProfile::new(0);
+        let a = Stack::from_ips(&[0x1000, 0x2000]);
+        let b = Stack::from_ips(&[0x1100, 0x2000]);
+        profile.add_syscall(74, "fsync", 5_000, 0);
+        profile.add_slow_stack(74, "fsync", a.clone(), 5_000);
+        profile.add_slow_stack(74, "fsync", a.clone(), 7_000);
+        profile.add_slow_stack(74, "fsync", b, 1_000);
+
+        let fsync = profile.syscalls.get_mut(&74).unwrap();
+        assert_eq!(fsync.slow_stacks.len(), 2);
+        assert_eq!(fsync.slow_stacks_by_latency()[&a], 12_000);
+
+        // Both leaf addresses fall in the same function once symbolized
+        fsync.map_slow_stacks(|stack| Stack {
+            frames: stack
+                .frames
+                .iter()
+                .map(|f| Frame {
+                    ip: f.ip & !0xfff,
+                    ..f.clone()
+                })
+                .collect(),
+        });
+        assert_eq!(fsync.slow_stacks.len(), 1);
+        assert_eq!(fsync.slow_stacks[0].count, 3);
+        assert_eq!(fsync.slow_stacks[0].total_duration_ns, 13_000);
+
+        // The index survives a JSON round trip
+        let mut decoded: SyscallStats =
+            serde_json::from_str(&serde_json::to_string(fsync).unwrap()).unwrap();
+        decoded.add_slow_stack(fsync.slow_stacks[0].stack.clone(), 1);
+        assert_eq!(decoded.slow_stacks.len(), 1);
+        assert_eq!(decoded.slow_stacks[0].count, 4);
+    }
+    #[test]
+    fn test_sampled_events_and_summaries_are_scaled() {
+        let mut profile = SyscallProfile::new(0);
+        let read = SyscallEvent {
+            timestamp: 1,
+            pid: 1,
+            tid: 1,
+            syscall_id: 0,
+            duration_ns: 1_000,
+            return_value: 64,
+            comm: "test".to_string(),
+            fd: Some(3),
+            target: Some("/data/log".to_string()),
+            bytes: Some(64),
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+            sample_every: 10,
+        };
+        profile.add_event("read", &read);
+        assert_eq!(profile.sample_every, 10);
+        assert_eq!(profile.total_events, 10);
+        assert_eq!(profile.syscalls[&0].total_duration_ns, 10_000);
+        assert_eq!(profile.syscalls[&0].latency_histogram[9], 10);
+        assert_eq!(profile.io["/data/log"].bytes, 640);
+
+        let mut latency_histogram = vec![0; 30];
+        latency_histogram[9] = 2;
+        latency_histogram[12] = 1;
+        profile.add_summary(
+            "read",
+            &SyscallSummaryEvent {
+                timestamp: 2,
+                pid: 0,
+                syscall_id: 0,
+                count: 3,
+                total_duration_ns: 6_000,
+                max_duration_ns: 4_000,
+                min_duration_ns: 900,
+                error_count: 1,
+                latency_histogram,
+                sample_every: 10,
+            },
+        );
+        let read = &profile.syscalls[&0];
+        assert_eq!(read.count, 40);
+        assert_eq!(read.error_count, 10);
+        assert_eq!(read.max_duration_ns, 4_000);
+        assert_eq!(read.min_duration_ns, 900);
+        assert_eq!(read.latency_histogram[9], 30);
+        assert_eq!(read.latency_histogram[12], 10);
+        assert_eq!(profile.total_events, 40);
+    }
+
+    #[test]
+    fn test_block_io_profile_by_device_and_op() {
+        let request = |pid, device: &str, op, bytes, latency_ns, queue_depth| BlockIoEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            dev: 259 << 20,
+            device: Some(device.to_string()),
+            op,
+            sector: 0,
+            bytes,
+            latency_ns,
+            queue_depth,
+            error: 0,
+        };
+        let mut profile = BlockIoProfile::new(0);
+        profile.add_event(&request(10, "nvme0n1", BlockIoOp::Read, 4096, 100_000, 1));
+        profile.add_event(&request(10, "nvme0n1", BlockIoOp::Read, 4096, 300_000, 3));
+        profile.add_event(&request(
+            11,
+            "nvme0n1",
+            BlockIoOp::Write,
+            65536,
+            2_000_000,
+            2,
+        ));
+        let mut flush = request(11, "sda", BlockIoOp::Flush, 0, 5_000_000, 1);
+        flush.device = None;
+        flush.error = -5;
+        profile.add_event(&flush);
+
+        let read = &profile.devices["nvme0n1"][&BlockIoOp::Read];
+        assert_eq!(read.count, 2);
+        assert_eq!(read.avg_latency_ns(), 200_000);
+        assert_eq!(read.size_histogram[12], 2);
+        assert_eq!(read.max_queue_depth, 3);
+        assert_eq!(read.avg_queue_depth(), 2.0);
+        assert_eq!(profile.devices["259:0"][&BlockIoOp::Flush].error_count, 1);
+        assert_eq!(profile.stats().count(), 3);
+        assert_eq!(profile.processes[&11].ops["write"], 1);
+        assert_eq!(profile.processes[&11].bytes, 65536);
+        assert_eq!(profile.total_events, 4);
+
+        // Operations are JSON object keys
+        let json = serde_json::to_string(&profile).unwrap();
+        assert!(json.contains("\"write\":{"));
+        let decoded: BlockIoProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.devices["nvme0n1"][&BlockIoOp::Write].bytes, 65536);
+    }
+
+    #[test]
+    fn test_sched_profile_by_process_and_cgroup() {
+        let wait = |pid, delay_ns, preempted| SchedEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            cpu_id: 0,
+            delay_ns,
+            preempted,
+            waker_pid: 1,
+            waker_tid: 1,
+            cgroup: Some("/app.slice".to_string()),
+            preemptor_tid: None,
+            preemptor_comm: None,
+            stack_trace: vec![],
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut profile = SchedProfile::new(0);
+        profile.add_event(&wait(10, 1_000, false));
+        profile.add_event(&wait(10, 3_000, true));
+        let mut long = wait(11, 20_000_000, true);
+        long.preemptor_tid = Some(99);
+        long.preemptor_comm = Some("ffmpeg".to_string());
+        long.stack_trace = vec![0x401000, 0x402000];
+        profile.add_event(&long.clone());
+        long.cgroup = None;
+        profile.add_event(&long);
+
+        let p10 = &profile.processes[&10].runq;
+        assert_eq!(p10.count, 2);
+        assert_eq!(p10.avg_delay_ns(), 2_000);
+        assert_eq!(p10.preempted_count, 1);
+        assert_eq!(p10.latency_histogram[9], 1);
+        assert_eq!(p10.latency_histogram[11], 1);
+
+        let p11 = &profile.processes[&11];
+        assert_eq!(p11.preemptors["ffmpeg"].count, 2);
+        assert_eq!(p11.wakeup_stacks.len(), 1);
+        assert_eq!(p11.wakeup_stacks[0].total_duration_ns, 40_000_000);
+        assert_eq!(profile.wakeup_stack_count(), 1);
+
+        assert_eq!(profile.cgroups["/app.slice"].count, 3);
+        assert_eq!(profile.total().count, 4);
+        assert_eq!(profile.total().max_delay_ns, 20_000_000);
+        assert_eq!(profile.total_events, 4);
+    }
+
+    #[test]
+    fn test_tcp_profile_by_process_and_endpoint() {
+        let event = |pid, kind, remote_port| TcpEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            kind,
+            remote_addr: "10.0.0.2".parse().unwrap(),
+            remote_port,
+            local_port: 50000,
+            passive: false,
+            duration_ns: 1_000_000,
+            bytes_sent: 100,
+            bytes_received: 4000,
+            retransmits: 1,
+            srtt_us: 300,
+        };
+        let mut profile = TcpProfile::new(0);
+        profile.add_event(&event(1, TcpEventKind::Connect, 5432));
+        profile.add_event(&event(1, TcpEventKind::Retransmit, 5432));
+        profile.add_event(&event(1, TcpEventKind::Close, 5432));
+        let mut failed = event(1, TcpEventKind::ConnectFailed, 6379);
+        failed.duration_ns = 3_000_000;
+        profile.add_event(&failed);
+        profile.add_event(&event(2, TcpEventKind::ResetReceived, 5432));
+
+        let p1 = &profile.processes[&1];
+        let pg = &p1.endpoints["10.0.0.2:5432"];
+        assert_eq!(pg.connects, 1);
+        assert_eq!(pg.retransmits, 1);
+        assert_eq!(pg.closed, 1);
+        assert_eq!(pg.bytes_received, 4000);
+        assert_eq!(pg.avg_srtt_us(), 300);
+        assert_eq!(pg.connect_histogram[19], 1);
+        assert_eq!(p1.endpoints["10.0.0.2:6379"].connect_failures, 1);
+        assert_eq!(p1.endpoints["10.0.0.2:6379"].avg_connect_ns(), 3_000_000);
+        assert_eq!(p1.total_bytes(), 4100);
+        assert_eq!(
+            profile.processes[&2].endpoints["10.0.0.2:5432"].resets_received,
+            1
+        );
+        assert_eq!(profile.endpoints().count(), 3);
+        assert_eq!(profile.total_events, 5);
+    }
+
+    #[test]
+    fn test_memory_profile_faults_and_reclaim() {
+        let fault = |pid, kernel, stack_trace| PageFaultEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            address: 0x7f00_0000_0000,
+            ip: 0x401000,
+            error_code: 6,
+            kernel,
+            weight: 10,
+            stack_trace,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let reclaim = |pid, stall_ns, memcg| ReclaimEvent {
+            timestamp: 0,
+            pid,
+            tid: pid,
+            comm: format!("proc{}", pid),
+            stall_ns,
+            nr_reclaimed: 32,
+            order: 0,
+            memcg,
+        };
+        let mut profile = MemoryProfile::new(0);
+        profile.add_fault(&fault(1, false, vec![0x401000, 0x402000]));
+        profile.add_fault(&fault(1, false, vec![0x401000, 0x402000]));
+        profile.add_fault(&fault(1, true, vec![0x401000, 0xffffffff81000000]));
+        // Counted for the process even without a stack
+        profile.add_fault(&fault(2, false, vec![]));
+        profile.add_reclaim(&reclaim(1, 1_000, false));
+        profile.add_reclaim(&reclaim(1, 3_000_000, true));
+
+        assert_eq!(profile.faults.samples.len(), 2);
+        assert_eq!(profile.faults.total_samples, 30);
+        assert_eq!(
+            profile.faults.samples[&Stack::from_ips(&[0x401000, 0x402000])],
+            20
+        );
+        let p1 = &profile.processes[&1];
+        assert_eq!(p1.faults, 30);
+        assert_eq!(p1.kernel_faults, 10);
+        assert_eq!(p1.reclaim.count, 2);
+        assert_eq!(p1.reclaim.memcg_count, 1);
+        assert_eq!(p1.reclaim.nr_reclaimed, 64);
+        assert_eq!(p1.reclaim.latency_histogram[9], 1);
+        assert_eq!(p1.reclaim.latency_histogram[21], 1);
+        assert_eq!(profile.processes[&2].faults, 10);
+        assert_eq!(profile.total_faults(), 40);
+        assert_eq!(profile.total_reclaim().max_stall_ns, 3_000_000);
+        assert_eq!(profile.total_events, 6);
+    }
+    #[test]
+    fn test_probe_profile_calls_and_hits() {
+        let call = |duration_ns: Option<u64>, stack: Vec<u64>| ProbeEvent {
+            timestamp: 0,
+            pid: 1,
+            tid: 1,
+            comm: "app".to_string(),
+            probe: "uprobe:/usr/bin/app:handle_request".to_string(),
+            duration_ns,
+            stack_trace: stack,
+            stack_symbols: vec![],
+            stack_refs: vec![],
+        };
+        let mut profile = ProbeProfile::new(0);
+        profile.add_event(&call(Some(1_000), vec![0x401000]));
+        profile.add_event(&call(Some(3_000), vec![0x401000]));
+        profile.add_event(&call(Some(500_000), vec![0x402000]));
+        profile.add_event(&ProbeEvent {
+            probe: "tracepoint:net:netif_rx".to_string(),
+            ..call(None, vec![])
+        });
+
+        let handler = &profile.probes["uprobe:/usr/bin/app:handle_request"];
+        assert_eq!(handler.count, 3);
+        assert_eq!(handler.avg_duration_ns(), 168_000);
+        assert_eq!(handler.min_duration_ns, 1_000);
+        assert_eq!(handler.max_duration_ns, 500_000);
+        assert_eq!(handler.latency_histogram[9], 1);
+        assert_eq!(handler.latency_histogram[11], 1);
+        assert_eq!(handler.latency_histogram[18], 1);
+        assert_eq!(handler.stacks_by_count()[&Stack::from_ips(&[0x401000])], 2);
+
+        let netif_rx = &profile.probes["tracepoint:net:netif_rx"];
+        assert_eq!(netif_rx.count, 1);
+        assert_eq!(netif_rx.avg_duration_ns(), 0);
+        assert_eq!(profile.stack_count(), 2);
+        assert_eq!(profile.total_events, 4);
+    }
+
+    #[test]
+    fn test_probe_profile_usdt_spans_and_cpu() {
+        let hit =
+            |timestamp, tid, probe: &str, args: Vec<i64>, span_start: Option<u64>| UsdtEvent {
+                timestamp,
+                pid: 1,
+                tid,
+                comm: "postgres".to_string(),
+                probe: probe.to_string(),
+                args,
+                span: span_start.map(|start| crate::types::events::UsdtSpan {
+                    name: "postgresql:query__start..query__done".to_string(),
+                    start,
+                }),
+            };
+        let mut profile = ProbeProfile::new(0);
+        profile.add_usdt(&hit(
+            1_000,
+            1,
+            "postgresql:query__start",
+            vec![0x5000],
+            None,
+        ));
+        profile.add_usdt(&hit(
+            5_000,
+            1,
+            "postgresql:query__done",
+            vec![-1],
+            Some(1_000),
+        ));
+        profile.add_usdt(&hit(
+            6_000,
+            2,
+            "postgresql:query__done",
+            vec![7],
+            Some(2_000),
+        ));
+
+        let done = &profile.usdt["postgresql:query__done"];
+        assert_eq!(done.count, 2);
+        assert_eq!((done.args[0].min, done.args[0].max), (-1, 7));
+        let span = &profile.spans["postgresql:query__start..query__done"];
+        assert_eq!(span.count, 2);
+        assert_eq!(span.avg_duration_ns(), 4_000);
+
+        let parse = Stack::from_ips(&[0x401000]);
+        let idle = Stack::from_ips(&[0x402000]);
+        profile.attribute_cpu_samples([
+            (1, 1, 3_000, &parse),
+            (1, 1, 4_000, &parse),
+            // After the span, on another thread, in another process
+            (1, 1, 5_500, &idle),
+            (1, 3, 3_000, &idle),
+            (2, 2, 3_000, &idle),
+            (1, 2, 2_000, &idle),
+        ]);
+        let span = &profile.spans["postgresql:query__start..query__done"];
+        assert_eq!(span.cpu_samples, 3);
+        let stacks = span.cpu_stacks_by_samples();
+        assert_eq!(stacks[&parse], 2);
+        assert_eq!(stacks[&idle], 1);
+        assert_eq!(profile.total_events, 3);
+    }
 }
@@ -0,0 +1,81 @@
+//! CPU architecture rules
+//!
+//! Syscall numbers and the split between user and kernel addresses differ
+//! per architecture. Agents record the architecture they run on in every
+//! pushed batch so the aggregator can interpret ids from mixed fleets.
+
+use serde::{Deserialize, Serialize};
+
+/// Architectures the agent runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arch {
+    /// Also assumed for batches from agents that predate the field
+    #[default]
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    /// Architecture this binary was built for (other targets use the x86_64
+    /// rules)
+    pub const fn host() -> Self {
+        if cfg!(target_arch = "aarch64") {
+            Arch::Aarch64
+        } else {
+            Arch::X86_64
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+        }
+    }
+
+    /// Lowest kernel address. User space ends well below it on every
+    /// supported configuration: x86_64 with 4- or 5-level paging (kernel
+    /// from 0xffff_8000_0000_0000 or 0xff00_0000_0000_0000), aarch64 with
+    /// 48- or 52-bit virtual addresses (kernel from 0xffff_0000_0000_0000
+    /// or 0xfff0_0000_0000_0000).
+    pub const fn kernel_ip_start(self) -> u64 {
+        match self {
+            Arch::X86_64 => 0xff00_0000_0000_0000,
+            Arch::Aarch64 => 0xfff0_0000_0000_0000,
+        }
+    }
+
+    pub const fn is_kernel_ip(self, ip: u64) -> bool {
+        ip >= self.kernel_ip_start()
+    }
+}
+
+impl std::fmt::Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// True if `ip` is a kernel address on the host architecture
+pub fn is_kernel_ip(ip: u64) -> bool {
+    Arch::host().is_kernel_ip(ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_ip_split() {
+        for arch in [Arch::X86_64, Arch::Aarch64] {
+            assert!(arch.is_kernel_ip(0xffff_ffff_8100_0000), "{}", arch);
+            assert!(!arch.is_kernel_ip(0x0000_7fff_f7a0_0000), "{}", arch);
+        }
+        // 5-level paging kernel, 52-bit VA kernel
+        assert!(Arch::X86_64.is_kernel_ip(0xff11_0000_0000_0000));
+        assert!(Arch::Aarch64.is_kernel_ip(0xfff1_0000_0000_0000));
+        assert!(!Arch::Aarch64.is_kernel_ip(0x000f_ffff_ffff_f000));
+        assert_eq!(Arch::default(), Arch::X86_64);
+    }
+}
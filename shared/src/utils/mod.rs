@@ -1,5 +1,6 @@
 //! Utility functions and helpers
 
+pub mod arch;
 pub mod syscalls;
 pub mod time;
 
@@ -10,11 +11,17 @@ pub fn bytes_to_hex(bytes: &[u8]) -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-/// Parse a duration string (e.g., "30s", "5m", "1h")
+/// Parse a duration string (e.g., "500us", "10ms", "30s", "5m", "1h")
 pub fn parse_duration(s: &str) -> Result<std::time::Duration> {
     let s = s.trim();
 
-    if let Some(num_str) = s.strip_suffix('s') {
+    if let Some(num_str) = s.strip_suffix("us") {
+        let micros: u64 = num_str.parse()?;
+        Ok(std::time::Duration::from_micros(micros))
+    } else if let Some(num_str) = s.strip_suffix("ms") {
+        let millis: u64 = num_str.parse()?;
+        Ok(std::time::Duration::from_millis(millis))
+    } else if let Some(num_str) = s.strip_suffix('s') {
         let secs: u64 = num_str.parse()?;
         Ok(std::time::Duration::from_secs(secs))
     } else if let Some(num_str) = s.strip_suffix('m') {
@@ -40,6 +47,8 @@ mod tests {
         assert_eq!(parse_duration("5m").unwrap().as_secs(), 300);
         assert_eq!(parse_duration("1h").unwrap().as_secs(), 3600);
         assert_eq!(parse_duration("60").unwrap().as_secs(), 60);
+        assert_eq!(parse_duration("10ms").unwrap().as_millis(), 10);
+        assert_eq!(parse_duration("500us").unwrap().as_micros(), 500);
     }
 
     #[test]
@@ -29,6 +29,8 @@ export interface CpuProfileJson {
 
 export interface LockContentionJson {
   lock_addr: number;
+  /** Variable or allocation site of the lock, when the agent named it */
+  lock_name?: string | null;
   stack: Stack;
   count: number;
   total_wait_ns: number;
@@ -36,11 +38,60 @@ export interface LockContentionJson {
   min_wait_ns: number;
 }
 
+export interface LockHoldJson {
+  lock_addr: number;
+  lock_name?: string | null;
+  stack: Stack;
+  count: number;
+  total_hold_ns: number;
+  max_hold_ns: number;
+  min_hold_ns: number;
+}
+
 export interface LockProfileJson {
   start_time: number;
   end_time: number;
   total_events: number;
   contentions: LockContentionJson[];
+  holders?: LockHoldJson[];
+  /** Contention and holds per lock identity, most waited-on first */
+  locks?: LockGroupJson[];
+}
+
+export interface LockGroupJson {
+  /** Lock name, or its address in hex when it has none */
+  name: string;
+  lock_addrs: number[];
+  contention: {
+    count: number;
+    total_wait_ns: number;
+    max_wait_ns: number;
+    min_wait_ns: number;
+  };
+  hold: {
+    count: number;
+    total_hold_ns: number;
+    max_hold_ns: number;
+    min_hold_ns: number;
+  };
+}
+
+export interface KernelLockContentionJson {
+  lock_addr: number;
+  /** spinlock, rwsem:W, mutex, ... */
+  lock_type: string;
+  stack: Stack;
+  count: number;
+  total_wait_ns: number;
+  max_wait_ns: number;
+  min_wait_ns: number;
+}
+
+export interface KernelLockProfileJson {
+  start_time: number;
+  end_time: number;
+  total_events: number;
+  contentions: KernelLockContentionJson[];
 }
 
 export interface SyscallStats {
@@ -52,6 +103,14 @@ export interface SyscallStats {
   min_duration_ns: number;
   error_count: number;
   latency_histogram: number[];
+  /** Stacks of calls slower than the agent's stack threshold */
+  slow_stacks?: SlowStack[];
+}
+
+export interface SlowStack {
+  stack: Stack;
+  count: number;
+  total_duration_ns: number;
 }
 
 export interface SyscallProfileJson {
@@ -59,12 +118,217 @@ export interface SyscallProfileJson {
   end_time: number;
   syscalls: Record<string, SyscallStats>;
   total_events: number;
+  /** 1 in N calls were recorded in-kernel; counts are scaled up by N */
+  sample_every?: number;
+}
+
+export interface BlockIoStats {
+  device: string;
+  /** read, write, discard, flush or other */
+  op: string;
+  count: number;
+  total_latency_ns: number;
+  max_latency_ns: number;
+  min_latency_ns: number;
+  error_count: number;
+  bytes: number;
+  latency_histogram: number[];
+  /** Power-of-2 request sizes in bytes */
+  size_histogram: number[];
+  total_queue_depth: number;
+  max_queue_depth: number;
+}
+
+export interface BlockIoProcessStats {
+  pid: number;
+  comm: string;
+  count: number;
+  bytes: number;
+  total_latency_ns: number;
+  ops: Record<string, number>;
+}
+
+export interface BlockIoProfileJson {
+  start_time: number;
+  end_time: number;
+  /** Device name -> operation -> stats */
+  devices: Record<string, Record<string, BlockIoStats>>;
+  processes: Record<string, BlockIoProcessStats>;
+  total_events: number;
+}
+
+export interface RunQueueStats {
+  count: number;
+  total_delay_ns: number;
+  max_delay_ns: number;
+  min_delay_ns: number;
+  /** Waits that began with preemption rather than a wakeup */
+  preempted_count: number;
+  latency_histogram: number[];
+}
+
+export interface PreemptorStats {
+  count: number;
+  total_delay_ns: number;
+  max_delay_ns: number;
+}
+
+export interface SchedProcessStats {
+  pid: number;
+  comm: string;
+  cgroup?: string | null;
+  runq: RunQueueStats;
+  /** Task name -> long waits it was on the CPU for just before */
+  preemptors: Record<string, PreemptorStats>;
+  /** Stacks that made the process's threads runnable */
+  wakeup_stacks?: SlowStack[];
+}
+
+export interface SchedProfileJson {
+  start_time: number;
+  end_time: number;
+  processes: Record<string, SchedProcessStats>;
+  /** cgroup path -> waits of its threads */
+  cgroups: Record<string, RunQueueStats>;
+  total_events: number;
+}
+
+export interface TcpEndpointStats {
+  /** `addr:port`, or `addr -> :port` for accepted connections */
+  endpoint: string;
+  connects: number;
+  connect_failures: number;
+  total_connect_ns: number;
+  max_connect_ns: number;
+  connect_histogram: number[];
+  retransmits: number;
+  resets_sent: number;
+  resets_received: number;
+  closed: number;
+  bytes_sent: number;
+  bytes_received: number;
+  total_srtt_us: number;
+  srtt_samples: number;
+  max_srtt_us: number;
+}
+
+export interface TcpProcessStats {
+  pid: number;
+  comm: string;
+  /** Endpoint -> stats */
+  endpoints: Record<string, TcpEndpointStats>;
+}
+
+export interface TcpProfileJson {
+  start_time: number;
+  end_time: number;
+  processes: Record<string, TcpProcessStats>;
+  total_events: number;
+}
+
+export interface ReclaimStats {
+  count: number;
+  total_stall_ns: number;
+  max_stall_ns: number;
+  min_stall_ns: number;
+  /** Stalls reclaiming for a memory cgroup limit */
+  memcg_count: number;
+  /** Pages freed */
+  nr_reclaimed: number;
+  latency_histogram: number[];
+}
+
+export interface MemoryProcessStats {
+  pid: number;
+  comm: string;
+  /** Estimated page faults (samples scaled by their sampling ratio) */
+  faults: number;
+  kernel_faults: number;
+  reclaim: ReclaimStats;
+}
+
+export interface MemoryProfileJson {
+  start_time: number;
+  end_time: number;
+  total_events: number;
+  total_faults: number;
+  /** Faulting stacks weighted by the faults each sample stands for */
+  fault_stacks: StackCount[];
+  /** Sorted by reclaim stall time, then faults */
+  processes: MemoryProcessStats[];
+}
+
+export interface ProbeStats {
+  /** Probe spec, e.g. `kprobe:vfs_read` */
+  probe: string;
+  /** Calls that returned, or tracepoint hits */
+  count: number;
+  /** Zero for tracepoints */
+  total_duration_ns: number;
+  max_duration_ns: number;
+  min_duration_ns: number;
+  latency_histogram: number[];
+  stacks?: SlowStack[];
+}
+
+export interface UsdtProbeStats {
+  /** `provider:name` */
+  probe: string;
+  count: number;
+  /** Range of each argument, in declaration order */
+  args: { min: number; max: number }[];
+}
+
+export interface UsdtSpanStats {
+  /** Pair name, e.g. `postgresql:query__start..query__done` */
+  span: string;
+  count: number;
+  total_duration_ns: number;
+  max_duration_ns: number;
+  min_duration_ns: number;
+  latency_histogram: number[];
+  /** CPU samples taken on a span's thread while it was open */
+  cpu_samples: number;
+  cpu_stacks?: { stack: Stack; samples: number }[];
+}
+
+export interface ProbeProfileJson {
+  start_time: number;
+  end_time: number;
+  /** Keyed by probe spec */
+  probes: Record<string, ProbeStats>;
+  /** Keyed by `provider:name` */
+  usdt?: Record<string, UsdtProbeStats>;
+  /** Keyed by span name */
+  spans?: Record<string, UsdtSpanStats>;
+  total_events: number;
+}
+
+export interface LabelGroupJson {
+  /** Label value; null for events without the label. */
+  value: string | null;
+  cpu_samples: number;
+  lock_waits: number;
+  lock_wait_ns: number;
+}
+
+/** CPU samples and lock waits per value of one profiling label, busiest first. */
+export interface LabelBreakdownJson {
+  key: string;
+  groups: LabelGroupJson[];
 }
 
 export interface AggregateResultJson {
   cpu?: CpuProfileJson;
   lock?: LockProfileJson;
+  kernel_lock?: KernelLockProfileJson;
   syscall?: SyscallProfileJson;
+  block_io?: BlockIoProfileJson;
+  sched?: SchedProfileJson;
+  tcp?: TcpProfileJson;
+  memory?: MemoryProfileJson;
+  probe?: ProbeProfileJson;
+  labels?: LabelBreakdownJson;
   total_events: number;
   /** Batches skipped due to invalid/corrupt payload (bincode decode errors). */
   skipped_batches?: number;
@@ -79,7 +79,7 @@ function computeInsights(data: AggregateResultJson): Insight[] {
     const worst = sorted[0];
     if (worst) {
       const topFrame = worst.stack.frames[0];
-      const lockName = topFrame?.function ?? `0x${worst.lock_addr.toString(16)}`;
+      const lockName = worst.lock_name ?? topFrame?.function ?? `0x${worst.lock_addr.toString(16)}`;
       insights.push({
         severity: worst.total_wait_ns > 1e9 ? "critical" : worst.total_wait_ns > 1e6 ? "warn" : "info",
         category: "lock",
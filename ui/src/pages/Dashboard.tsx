@@ -58,6 +58,7 @@ function HealthBadge({ health }: { health: HealthInfo | null }) {
 export default function Dashboard() {
   const dashboard = useDashboard();
   const [eventType, setEventType] = useState<"cpu" | "lock" | "syscall" | "">("");
+  const [groupLocks, setGroupLocks] = useState(false);
   const { start, end } = dashboard?.timeRange ?? { start: 0, end: 0 };
 
   const healthQuery = useHealthQuery();
@@ -96,6 +97,7 @@ export default function Dashboard() {
 
   const cpu = aggregate?.cpu;
   const lock = aggregate?.lock;
+  const kernelLock = aggregate?.kernel_lock;
   const syscall = aggregate?.syscall;
   const totalSamples = cpu?.total_samples ?? 0;
   const stacksCount = cpu?.stacks?.length ?? 0;
@@ -287,24 +289,116 @@ export default function Dashboard() {
         {/* Lock contention table when lock mode is selected */}
         {lock && lock.contentions.length > 0 && (eventType === "lock" || eventType === "") && (
           <div className="rounded-md border border-border bg-card p-4">
-            <h2 className="text-sm font-medium text-foreground mb-3">Lock Contention</h2>
+            <div className="flex items-center justify-between mb-3">
+              <h2 className="text-sm font-medium text-foreground">Lock Contention</h2>
+              <button
+                onClick={() => setGroupLocks(!groupLocks)}
+                className={`px-2.5 py-1 rounded text-xs font-medium transition-colors ${
+                  groupLocks
+                    ? "bg-primary text-primary-foreground"
+                    : "bg-muted text-muted-foreground hover:text-foreground"
+                }`}
+              >
+                Group by lock
+              </button>
+            </div>
+            <div className="rounded-md border border-border overflow-hidden">
+              <table className="w-full text-xs">
+                <thead>
+                  <tr className="border-b border-border bg-muted/30">
+                    <th className="text-left px-3 py-2 font-medium text-muted-foreground">Lock</th>
+                    <th className="text-right px-3 py-2 font-medium text-muted-foreground">Count</th>
+                    <th className="text-right px-3 py-2 font-medium text-muted-foreground">Total wait</th>
+                    <th className="text-right px-3 py-2 font-medium text-muted-foreground">Max wait</th>
+                  </tr>
+                </thead>
+                <tbody>
+                  {groupLocks && (lock.locks ?? []).slice(0, 20).map((g, i) => {
+                    const addrs = g.lock_addrs.map((a) => `0x${a.toString(16)}`).join(", ");
+                    return (
+                      <tr key={i} className="border-b border-border/50 hover:bg-muted/20">
+                        <td className="px-3 py-2 font-mono text-foreground truncate max-w-xs" title={addrs}>{g.name}</td>
+                        <td className="text-right px-3 py-2 font-mono">{g.contention.count.toLocaleString()}</td>
+                        <td className="text-right px-3 py-2 font-mono">{formatNs(g.contention.total_wait_ns)}</td>
+                        <td className="text-right px-3 py-2 font-mono">{formatNs(g.contention.max_wait_ns)}</td>
+                      </tr>
+                    );
+                  })}
+                  {!groupLocks && lock.contentions.slice(0, 20).map((c, i) => {
+                    const topFrame = c.stack.frames[0];
+                    const site = topFrame?.function ?? `0x${c.lock_addr.toString(16)}`;
+                    const label = c.lock_name ? `${c.lock_name} · ${site}` : site;
+                    return (
+                      <tr key={i} className="border-b border-border/50 hover:bg-muted/20">
+                        <td className="px-3 py-2 font-mono text-foreground truncate max-w-xs" title={label}>{label}</td>
+                        <td className="text-right px-3 py-2 font-mono">{c.count.toLocaleString()}</td>
+                        <td className="text-right px-3 py-2 font-mono">{formatNs(c.total_wait_ns)}</td>
+                        <td className="text-right px-3 py-2 font-mono">{formatNs(c.max_wait_ns)}</td>
+                      </tr>
+                    );
+                  })}
+                </tbody>
+              </table>
+            </div>
+          </div>
+        )}
+
+        {/* Code holding contended locks, from releases that woke waiters */}
+        {lock && (lock.holders?.length ?? 0) > 0 && (eventType === "lock" || eventType === "") && (
+          <div className="rounded-md border border-border bg-card p-4">
+            <h2 className="text-sm font-medium text-foreground mb-3">Lock Holders</h2>
+            <div className="rounded-md border border-border overflow-hidden">
+              <table className="w-full text-xs">
+                <thead>
+                  <tr className="border-b border-border bg-muted/30">
+                    <th className="text-left px-3 py-2 font-medium text-muted-foreground">Holder</th>
+                    <th className="text-right px-3 py-2 font-medium text-muted-foreground">Releases</th>
+                    <th className="text-right px-3 py-2 font-medium text-muted-foreground">Total hold</th>
+                    <th className="text-right px-3 py-2 font-medium text-muted-foreground">Max hold</th>
+                  </tr>
+                </thead>
+                <tbody>
+                  {(lock.holders ?? []).slice(0, 20).map((h, i) => {
+                    const topFrame = h.stack.frames[0];
+                    const label = topFrame?.function ?? h.lock_name ?? `0x${h.lock_addr.toString(16)}`;
+                    return (
+                      <tr key={i} className="border-b border-border/50 hover:bg-muted/20">
+                        <td className="px-3 py-2 font-mono text-foreground truncate max-w-xs" title={label}>{label}</td>
+                        <td className="text-right px-3 py-2 font-mono">{h.count.toLocaleString()}</td>
+                        <td className="text-right px-3 py-2 font-mono">{formatNs(h.total_hold_ns)}</td>
+                        <td className="text-right px-3 py-2 font-mono">{formatNs(h.max_hold_ns)}</td>
+                      </tr>
+                    );
+                  })}
+                </tbody>
+              </table>
+            </div>
+          </div>
+        )}
+
+        {/* Kernel lock contention, next to the user-space lock tables */}
+        {kernelLock && kernelLock.contentions.length > 0 && (eventType === "lock" || eventType === "") && (
+          <div className="rounded-md border border-border bg-card p-4">
+            <h2 className="text-sm font-medium text-foreground mb-3">Kernel Lock Contention</h2>
             <div className="rounded-md border border-border overflow-hidden">
               <table className="w-full text-xs">
                 <thead>
                   <tr className="border-b border-border bg-muted/30">
                     <th className="text-left px-3 py-2 font-medium text-muted-foreground">Lock</th>
+                    <th className="text-left px-3 py-2 font-medium text-muted-foreground">Type</th>
                     <th className="text-right px-3 py-2 font-medium text-muted-foreground">Count</th>
                     <th className="text-right px-3 py-2 font-medium text-muted-foreground">Total wait</th>
                     <th className="text-right px-3 py-2 font-medium text-muted-foreground">Max wait</th>
                   </tr>
                 </thead>
                 <tbody>
-                  {lock.contentions.slice(0, 20).map((c, i) => {
+                  {kernelLock.contentions.slice(0, 20).map((c, i) => {
                     const topFrame = c.stack.frames[0];
                     const label = topFrame?.function ?? `0x${c.lock_addr.toString(16)}`;
                     return (
                       <tr key={i} className="border-b border-border/50 hover:bg-muted/20">
                         <td className="px-3 py-2 font-mono text-foreground truncate max-w-xs" title={label}>{label}</td>
+                        <td className="px-3 py-2 font-mono text-muted-foreground">{c.lock_type}</td>
                         <td className="text-right px-3 py-2 font-mono">{c.count.toLocaleString()}</td>
                         <td className="text-right px-3 py-2 font-mono">{formatNs(c.total_wait_ns)}</td>
                         <td className="text-right px-3 py-2 font-mono">{formatNs(c.max_wait_ns)}</td>
@@ -1,11 +1,12 @@
 import { useState, useMemo } from "react";
 import { AppLayout } from "@/components/layout/AppLayout";
 import { LatencyHistogram } from "@/components/profiler/LatencyHistogram";
+import { FlamegraphViewer } from "@/components/profiler/FlamegraphViewer";
 import { useDashboard } from "@/contexts/DashboardContext";
 import { useAggregateQuery } from "@/api/queries";
 import { formatNs } from "@/lib/format";
 import { cn } from "@/lib/utils";
-import type { SyscallStats } from "@/api/types";
+import type { StackCount, SyscallStats } from "@/api/types";
 
 type SortKey = "count" | "avg" | "max" | "min" | "errors" | "total";
 
@@ -88,7 +89,17 @@ export default function SyscallsPage() {
     ? rows.find((r) => r.name === selectedSyscall)
     : null;
 
+  // Slow-call stacks weighted by latency, so wide frames are where time went
+  const slowStacks = useMemo((): StackCount[] => {
+    return (selectedRow?.slow_stacks ?? []).map((s) => ({
+      stack: s.stack,
+      count: s.total_duration_ns,
+    }));
+  }, [selectedRow]);
+  const slowTotal = slowStacks.reduce((s, st) => s + st.count, 0);
+
   const totalEvents = syscall?.total_events ?? 0;
+  const sampleEvery = syscall?.sample_every ?? 1;
   const uniqueSyscalls = rows.length;
   const totalDuration = rows.reduce((s, r) => s + r.total_duration_ns, 0);
   const totalErrors = rows.reduce((s, r) => s + r.error_count, 0);
@@ -105,6 +116,12 @@ export default function SyscallsPage() {
           <p className="text-xs text-destructive">{aggregateQuery.error.message}</p>
         )}
 
+        {sampleEvery > 1 && (
+          <p className="text-xs text-muted-foreground">
+            Sampled 1 in {sampleEvery.toLocaleString()} calls; counts are estimates.
+          </p>
+        )}
+
         {/* Summary stats */}
         <div className="grid grid-cols-4 gap-3">
           <div className="rounded-md border border-border bg-card p-3">
@@ -158,6 +175,17 @@ export default function SyscallsPage() {
               <span>Max: <span className="font-mono text-foreground">{formatNs(selectedRow.max_duration_ns)}</span></span>
             </div>
             <LatencyHistogram histogram={selectedRow.latency_histogram} height={140} />
+            {slowStacks.length > 0 && (
+              <div className="mt-4">
+                <h3 className="text-xs font-medium text-foreground mb-2">
+                  Slow call stacks{" "}
+                  <span className="text-muted-foreground">
+                    (weighted by latency, {formatNs(slowTotal)})
+                  </span>
+                </h3>
+                <FlamegraphViewer stacks={slowStacks} totalSamples={slowTotal} height={300} />
+              </div>
+            )}
           </div>
         )}
 
@@ -300,7 +328,7 @@ export default function SyscallsPage() {
         )}
 
         <p className="text-[11px] text-muted-foreground">
-          Click a row to view its latency histogram. Syscall events are captured via raw tracepoints and do not include stack traces.
+          Click a row to view its latency histogram. Stacks are captured only for calls slower than the agent's --syscall-stack-threshold and shown as a latency-weighted flamegraph.
         </p>
       </div>
     </AppLayout>
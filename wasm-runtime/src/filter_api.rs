@@ -10,7 +10,9 @@ use aperture_shared::types::events::ProfileEvent;
 #[repr(C)]
 #[derive(Debug, Clone, Default)]
 pub struct EventContext {
-    /// 0 = CpuSample, 1 = Lock, 2 = Syscall, 3 = GpuKernel
+    /// 0 = CpuSample, 1 = Lock, 2 = Syscall, 3 = GpuKernel, 4 = Process,
+    /// 5 = SyscallSummary, 6 = KernelLock, 7 = BlockIo, 8 = Sched, 9 = Tcp,
+    /// 10 = PageFault, 11 = Reclaim, 12 = Probe, 13 = Usdt
     pub event_type: u32,
     /// Process ID
     pub pid: i32,
@@ -24,15 +26,19 @@ pub struct EventContext {
     pub user_stack_depth: u32,
     /// Kernel stack depth (CpuSample only)
     pub kernel_stack_depth: u32,
-    /// Lock address (Lock only)
+    /// Lock address (Lock and KernelLock only)
     pub lock_addr: u64,
-    /// Wait time in nanoseconds (Lock only)
+    /// Wait time in nanoseconds (Lock and KernelLock only)
     pub wait_time_ns: u64,
-    /// Syscall ID (Syscall only)
+    /// Syscall ID (Syscall and SyscallSummary only)
     pub syscall_id: u32,
-    /// Syscall duration in nanoseconds (Syscall only)
+    /// Syscall duration in nanoseconds (Syscall only; total of the
+    /// summarized calls for SyscallSummary, request latency for BlockIo,
+    /// run-queue delay for Sched, handshake or connection lifetime for Tcp,
+    /// stall for Reclaim, call latency for Probe, span length for the Usdt
+    /// hit that ends a span)
     pub duration_ns: u64,
-    /// Syscall return value (Syscall only)
+    /// Syscall return value (Syscall only; completion error for BlockIo)
     pub return_value: i64,
     /// Length of comm string (stored after this struct in memory)
     pub comm_len: u32,
@@ -98,6 +104,129 @@ impl EventContext {
                 },
                 e.kernel_name.clone(),
             ),
+            ProfileEvent::Process(e) => (
+                Self {
+                    event_type: 4,
+                    pid: e.pid,
+                    tid: e.pid,
+                    timestamp: e.timestamp,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::SyscallSummary(e) => (
+                Self {
+                    event_type: 5,
+                    pid: e.pid,
+                    timestamp: e.timestamp,
+                    syscall_id: e.syscall_id,
+                    duration_ns: e.total_duration_ns,
+                    ..Default::default()
+                },
+                String::new(),
+            ),
+            ProfileEvent::KernelLock(e) => (
+                Self {
+                    event_type: 6,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    lock_addr: e.lock_addr,
+                    wait_time_ns: e.wait_time_ns,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::BlockIo(e) => (
+                Self {
+                    event_type: 7,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    duration_ns: e.latency_ns,
+                    return_value: e.error as i64,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::Sched(e) => (
+                Self {
+                    event_type: 8,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    cpu_id: e.cpu_id,
+                    duration_ns: e.delay_ns,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::Tcp(e) => (
+                Self {
+                    event_type: 9,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    duration_ns: e.duration_ns,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::PageFault(e) => (
+                Self {
+                    event_type: 10,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::Reclaim(e) => (
+                Self {
+                    event_type: 11,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    duration_ns: e.stall_ns,
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::Probe(e) => (
+                Self {
+                    event_type: 12,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    duration_ns: e.duration_ns.unwrap_or(0),
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
+            ProfileEvent::Usdt(e) => (
+                Self {
+                    event_type: 13,
+                    pid: e.pid,
+                    tid: e.tid,
+                    timestamp: e.timestamp,
+                    duration_ns: e
+                        .span
+                        .as_ref()
+                        .map_or(0, |s| e.timestamp.saturating_sub(s.start)),
+                    comm_len: e.comm.len() as u32,
+                    ..Default::default()
+                },
+                e.comm.clone(),
+            ),
         }
     }
 
@@ -125,6 +254,8 @@ mod tests {
             comm: "myapp".to_string(),
             user_stack_symbols: vec![],
             kernel_stack_symbols: vec![],
+            user_stack_refs: vec![],
+            labels: Default::default(),
         };
         let event = ProfileEvent::CpuSample(sample);
         let (ctx, comm) = EventContext::from_event(&event);
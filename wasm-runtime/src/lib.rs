@@ -20,7 +20,10 @@
 //! ```rust,ignore
 //! #[repr(C)]
 //! struct EventContext {
-//!     event_type: u32,  // 0=CPU, 1=Lock, 2=Syscall, 3=GPU
+//!     event_type: u32,  // 0=CPU, 1=Lock, 2=Syscall, 3=GPU, 4=Process, 5=SyscallSummary,
+//!                       // 6=KernelLock, 7=BlockIo, 8=Sched, 9=Tcp,
+//!                       // 10=PageFault, 11=Reclaim, 12=Probe,
+//!                       // 13=Usdt
 //!     pid: i32,
 //!     tid: i32,
 //!     // ... (see filter_api::EventContext for full layout)